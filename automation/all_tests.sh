@@ -33,6 +33,7 @@ fi
 
 ./automation/all_rust_tests.sh
 
+cargo run -p sync-test -- --mock-server
 cargo run -p sync-test
 
 ./gradlew test
@@ -12,15 +12,19 @@ data class Device(
     val pushEndpointExpired: Boolean,
     val isCurrentDevice: Boolean,
     val lastAccessTime: Long?,
-    val capabilities: List<Capability>
+    val capabilities: List<Capability>,
+    // The names of all the commands the device supports, including custom commands.
+    val commands: List<String> = listOf()
 ) {
     enum class Capability {
-        SEND_TAB;
+        SEND_TAB,
+        CLOSE_TABS;
 
         companion object {
             internal fun fromMessage(msg: MsgTypes.Device.Capability): Capability {
                 return when (msg) {
                     MsgTypes.Device.Capability.SEND_TAB -> SEND_TAB
+                    MsgTypes.Device.Capability.CLOSE_TABS -> CLOSE_TABS
                 }.exhaustive
             }
         }
@@ -86,7 +90,8 @@ data class Device(
                     pushEndpointExpired = msg.pushEndpointExpired,
                     isCurrentDevice = msg.isCurrentDevice,
                     lastAccessTime = if (msg.hasLastAccessTime()) msg.lastAccessTime else null,
-                    capabilities = msg.capabilitiesList.map { Capability.fromMessage(it) }
+                    capabilities = msg.capabilitiesList.map { Capability.fromMessage(it) },
+                    commands = msg.commandsList
             )
         }
         internal fun fromCollectionMessage(msg: MsgTypes.Devices): Array<Device> {
@@ -97,11 +102,12 @@ data class Device(
     }
 }
 
-fun Set<Device.Capability>.toCollectionMessage(): MsgTypes.Capabilities {
-    val builder = MsgTypes.Capabilities.newBuilder()
+fun Set<Device.Capability>.toCollectionMessage(customCommands: Set<String> = setOf()): MsgTypes.Capabilities {
+    val builder = MsgTypes.Capabilities.newBuilder().addAllCommands(customCommands)
     this.forEach {
         when (it) {
             Device.Capability.SEND_TAB -> builder.addCapability(MsgTypes.Device.Capability.SEND_TAB)
+            Device.Capability.CLOSE_TABS -> builder.addCapability(MsgTypes.Device.Capability.CLOSE_TABS)
         }.exhaustive
     }
     return builder.build()
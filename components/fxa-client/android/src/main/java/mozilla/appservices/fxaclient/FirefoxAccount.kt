@@ -543,9 +543,16 @@ class FirefoxAccount(handle: FxaHandle, persistCallback: PersistCallback?) : Aut
      * This method should be called once per "device lifetime".
      *
      * This performs network requests, and should not be used on the main thread.
+     *
+     * @param customCommands The names of custom commands to register, see [sendDeviceCommand]
      */
-    fun initializeDevice(name: String, deviceType: Device.Type, supportedCapabilities: Set<Device.Capability>) {
-        val (nioBuf, len) = supportedCapabilities.toCollectionMessage().toNioDirectBuffer()
+    fun initializeDevice(
+        name: String,
+        deviceType: Device.Type,
+        supportedCapabilities: Set<Device.Capability>,
+        customCommands: Set<String> = setOf()
+    ) {
+        val (nioBuf, len) = supportedCapabilities.toCollectionMessage(customCommands).toNioDirectBuffer()
         rustCall { e ->
             val ptr = Native.getDirectBufferPointer(nioBuf)
             LibFxAFFI.INSTANCE.fxa_initialize_device(this.handle.get(), name, deviceType.toNumber(), ptr, len, e)
@@ -562,9 +569,11 @@ class FirefoxAccount(handle: FxaHandle, persistCallback: PersistCallback?) : Aut
      * This method should be called at least every time the sync keys change (because Send Tab relies on them).
      *
      * This performs network requests, and should not be used on the main thread.
+     *
+     * @param customCommands The names of custom commands to register, see [sendDeviceCommand]
      */
-    fun ensureCapabilities(supportedCapabilities: Set<Device.Capability>) {
-        val (nioBuf, len) = supportedCapabilities.toCollectionMessage().toNioDirectBuffer()
+    fun ensureCapabilities(supportedCapabilities: Set<Device.Capability>, customCommands: Set<String> = setOf()) {
+        val (nioBuf, len) = supportedCapabilities.toCollectionMessage(customCommands).toNioDirectBuffer()
         rustCall { e ->
             val ptr = Native.getDirectBufferPointer(nioBuf)
             LibFxAFFI.INSTANCE.fxa_ensure_capabilities(this.handle.get(), ptr, len, e)
@@ -572,6 +581,25 @@ class FirefoxAccount(handle: FxaHandle, persistCallback: PersistCallback?) : Aut
         this.tryPersistState()
     }
 
+    /**
+     * Invoke a custom device command on another device.
+     *
+     * Custom commands are registered by passing their names in the `customCommands` of
+     * [initializeDevice] or [ensureCapabilities]. Their payloads are JSON strings, and
+     * other devices invoking them are received as [IncomingDeviceCommand.Custom].
+     *
+     * This performs network requests, and should not be used on the main thread.
+     *
+     * @param commandName The command name
+     * @param targetDeviceId The target Device ID
+     * @param payload The JSON payload
+     */
+    fun sendDeviceCommand(commandName: String, targetDeviceId: String, payload: String) {
+        rustCall { e ->
+            LibFxAFFI.INSTANCE.fxa_send_device_command(this.handle.get(), commandName, targetDeviceId, payload, e)
+        }
+    }
+
     /**
      * Send a single tab to another device identified by its device ID.
      *
@@ -587,6 +615,79 @@ class FirefoxAccount(handle: FxaHandle, persistCallback: PersistCallback?) : Aut
         }
     }
 
+    /**
+     * Send several tabs to another device identified by its device ID, packing as many
+     * tabs as possible into each command.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     *
+     * @param targetDeviceId The target Device ID
+     * @param tabs The tabs to send, each with its history entries. The current entry is the last one.
+     * @return [SendTabsResult] listing the tabs that were delivered, and the ones that failed
+     */
+    fun sendTabs(targetDeviceId: String, tabs: List<Array<TabHistoryEntry>>): SendTabsResult {
+        val builder = MsgTypes.TabsToSend.newBuilder()
+        tabs.forEach { entries ->
+            val tab = MsgTypes.TabsToSend.Tab.newBuilder()
+            entries.forEach {
+                tab.addEntries(
+                    MsgTypes.IncomingDeviceCommand.SendTabData.TabHistoryEntry.newBuilder()
+                        .setTitle(it.title)
+                        .setUrl(it.url)
+                )
+            }
+            builder.addTabs(tab)
+        }
+        val (nioBuf, len) = builder.build().toNioDirectBuffer()
+        val resultBuffer = rustCall { e ->
+            val ptr = Native.getDirectBufferPointer(nioBuf)
+            LibFxAFFI.INSTANCE.fxa_send_tabs(this.handle.get(), targetDeviceId, ptr, len, e)
+        }
+        try {
+            val msg = MsgTypes.SendTabsResult.parseFrom(resultBuffer.asCodedInputStream()!!)
+            return SendTabsResult.fromMessage(msg)
+        } finally {
+            LibFxAFFI.INSTANCE.fxa_bytebuffer_free(resultBuffer)
+        }
+    }
+
+    /**
+     * Ask another device identified by its device ID to close the tabs with the given urls.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     *
+     * @param targetDeviceId The target Device ID
+     * @param urls The urls of the tabs to close
+     */
+    fun closeTabs(targetDeviceId: String, urls: List<String>) {
+        val (nioBuf, len) = MsgTypes.CloseTabsUrls.newBuilder().addAllUrls(urls).build().toNioDirectBuffer()
+        rustCall { e ->
+            val ptr = Native.getDirectBufferPointer(nioBuf)
+            LibFxAFFI.INSTANCE.fxa_close_tabs(this.handle.get(), targetDeviceId, ptr, len, e)
+        }
+    }
+
+    /**
+     * Tell the device that asked us to close tabs which of its urls we closed.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     *
+     * @param targetDeviceId The Device ID of the device that sent [IncomingDeviceCommand.CloseTabsRequested]
+     * @param closed The urls of the tabs we closed
+     * @param notFound The urls we didn't have open
+     */
+    fun replyToCloseTabs(targetDeviceId: String, closed: List<String>, notFound: List<String>) {
+        val (nioBuf, len) = MsgTypes.CloseTabsReply.newBuilder()
+            .addAllClosed(closed)
+            .addAllNotFound(notFound)
+            .build()
+            .toNioDirectBuffer()
+        rustCall { e ->
+            val ptr = Native.getDirectBufferPointer(nioBuf)
+            LibFxAFFI.INSTANCE.fxa_reply_to_close_tabs(this.handle.get(), targetDeviceId, ptr, len, e)
+        }
+    }
+
     @Synchronized
     override fun close() {
         val handle = this.handle.getAndSet(0)
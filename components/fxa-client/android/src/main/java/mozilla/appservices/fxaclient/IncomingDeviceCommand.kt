@@ -12,6 +12,16 @@ data class TabHistoryEntry(
 sealed class IncomingDeviceCommand {
     // A tab with all its history entries (back button).
     class TabReceived(val from: Device?, val entries: Array<TabHistoryEntry>) : IncomingDeviceCommand()
+    // Another device asked us to close the tabs with these urls.
+    class CloseTabsRequested(val from: Device?, val urls: Array<String>) : IncomingDeviceCommand()
+    // A device we asked to close tabs told us which of them it closed.
+    class TabsClosed(
+        val from: Device?,
+        val closed: Array<String>,
+        val notFound: Array<String>
+    ) : IncomingDeviceCommand()
+    // A custom command, with its JSON payload.
+    class Custom(val from: Device?, val command: String, val payload: String) : IncomingDeviceCommand()
 
     companion object {
         internal fun fromMessage(msg: MsgTypes.IncomingDeviceCommand): IncomingDeviceCommand {
@@ -25,6 +35,29 @@ sealed class IncomingDeviceCommand {
                         }.toTypedArray()
                     )
                 }
+                MsgTypes.IncomingDeviceCommand.IncomingDeviceCommandType.CLOSE_TABS_REQUESTED -> {
+                    val data = msg.closeTabsRequestedData
+                    CloseTabsRequested(
+                        from = if (data.hasFrom()) Device.fromMessage(data.from) else null,
+                        urls = data.urlsList.toTypedArray()
+                    )
+                }
+                MsgTypes.IncomingDeviceCommand.IncomingDeviceCommandType.TABS_CLOSED -> {
+                    val data = msg.tabsClosedData
+                    TabsClosed(
+                        from = if (data.hasFrom()) Device.fromMessage(data.from) else null,
+                        closed = data.closedList.toTypedArray(),
+                        notFound = data.notFoundList.toTypedArray()
+                    )
+                }
+                MsgTypes.IncomingDeviceCommand.IncomingDeviceCommandType.CUSTOM -> {
+                    val data = msg.customData
+                    Custom(
+                        from = if (data.hasFrom()) Device.fromMessage(data.from) else null,
+                        command = data.command,
+                        payload = data.payload
+                    )
+                }
                 null -> throw NullPointerException("IncomingDeviceCommand type cannot be null.")
             }.exhaustive
         }
@@ -0,0 +1,23 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.fxaclient
+
+/**
+ * The outcome of [FirefoxAccount.sendTabs]. Tabs are identified by their
+ * index in the list passed to `sendTabs`.
+ */
+data class SendTabsResult(
+    val delivered: List<Int>,
+    val failed: List<Int>
+) {
+    companion object {
+        internal fun fromMessage(msg: MsgTypes.SendTabsResult): SendTabsResult {
+            return SendTabsResult(
+                delivered = msg.deliveredList,
+                failed = msg.failedList
+            )
+        }
+    }
+}
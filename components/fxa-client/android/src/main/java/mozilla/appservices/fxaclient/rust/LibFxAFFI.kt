@@ -87,6 +87,38 @@ internal interface LibFxAFFI : Library {
     )
     fun fxa_send_tab(fxa: FxaHandle, targetDeviceId: String, title: String, url: String, e: RustError.ByReference)
 
+    fun fxa_send_tabs(
+        fxa: FxaHandle,
+        targetDeviceId: String,
+        tabs_data: Pointer,
+        tabs_len: Int,
+        e: RustError.ByReference
+    ): RustBuffer.ByValue
+
+    fun fxa_close_tabs(
+        fxa: FxaHandle,
+        targetDeviceId: String,
+        urls_data: Pointer,
+        urls_len: Int,
+        e: RustError.ByReference
+    )
+
+    fun fxa_send_device_command(
+        fxa: FxaHandle,
+        commandName: String,
+        targetDeviceId: String,
+        payload: String,
+        e: RustError.ByReference
+    )
+
+    fun fxa_reply_to_close_tabs(
+        fxa: FxaHandle,
+        targetDeviceId: String,
+        reply_data: Pointer,
+        reply_len: Int,
+        e: RustError.ByReference
+    )
+
     fun fxa_migrate_from_session_token(
         fxa: FxaHandle,
         sessionToken: String,
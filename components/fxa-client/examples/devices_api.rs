@@ -107,6 +107,28 @@ fn main() -> Result<()> {
                             };
                             webbrowser::open(&tab.url).unwrap();
                         }
+                        IncomingDeviceCommand::CloseTabsRequested { sender, payload } => {
+                            match sender {
+                                Some(ref d) => println!(
+                                    "{} asked us to close: {}",
+                                    d.display_name,
+                                    payload.urls.join(", ")
+                                ),
+                                None => println!("Asked to close: {}", payload.urls.join(", ")),
+                            };
+                        }
+                        IncomingDeviceCommand::TabsClosed { payload, .. } => {
+                            println!(
+                                "Closed: {}; not found: {}",
+                                payload.closed.join(", "),
+                                payload.not_found.join(", ")
+                            );
+                        }
+                        IncomingDeviceCommand::Custom {
+                            command, payload, ..
+                        } => {
+                            println!("Received {}: {}", command, payload);
+                        }
                     }
                 }
                 thread::sleep(time::Duration::from_secs(1));
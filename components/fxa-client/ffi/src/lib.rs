@@ -12,11 +12,12 @@ use ffi_support::{
     ConcurrentHandleMap, ExternError, FfiStr,
 };
 use fxa_client::{
+    commands::custom::CustomCommand,
     device::{Capability as DeviceCapability, PushSubscription},
     migrator::MigrationState,
     msg_types, FirefoxAccount,
 };
-use std::os::raw::c_char;
+use std::{os::raw::c_char, sync::Arc};
 use url::Url;
 
 lazy_static::lazy_static! {
@@ -526,6 +527,16 @@ pub extern "C" fn fxa_handle_push_message(
     })
 }
 
+/// Registers a `CustomCommand` for each custom command in `capabilities`, so
+/// that FFI consumers can add commands without writing any Rust.
+fn register_custom_commands(fxa: &mut FirefoxAccount, capabilities: &[DeviceCapability]) {
+    for capability in capabilities {
+        if let DeviceCapability::Command(name) = capability {
+            fxa.register_device_command(Arc::new(CustomCommand::new(name)));
+        }
+    }
+}
+
 /// Initalizes our own device, most of the time this will be called right after
 /// logging-in for the first time.
 ///
@@ -548,6 +559,7 @@ pub unsafe extern "C" fn fxa_initialize_device(
         // This should not fail as device_type i32 representation is derived from our .proto schema.
         let device_type =
             msg_types::device::Type::from_i32(device_type).expect("Unknown device type code");
+        register_custom_commands(fxa, &capabilities);
         fxa.initialize_device(name.as_str(), device_type.into(), &capabilities)
     })
 }
@@ -568,10 +580,32 @@ pub unsafe extern "C" fn fxa_ensure_capabilities(
     ACCOUNTS.call_with_result_mut(error, handle, |fxa| {
         let capabilities =
             DeviceCapability::from_protobuf_array_ptr(capabilities_data, capabilities_len);
+        register_custom_commands(fxa, &capabilities);
         fxa.ensure_capabilities(&capabilities)
     })
 }
 
+/// Invoke a custom device command on another device identified by its
+/// Device ID, with a JSON payload. The command must have been registered with
+/// `fxa_initialize_device` or `fxa_ensure_capabilities`.
+#[no_mangle]
+pub extern "C" fn fxa_send_device_command(
+    handle: u64,
+    command_name: FfiStr<'_>,
+    target_device_id: FfiStr<'_>,
+    payload: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("fxa_send_device_command");
+    let command_name = command_name.as_str();
+    let target = target_device_id.as_str();
+    let payload = payload.as_str();
+    ACCOUNTS.call_with_result_mut(error, handle, |fxa| -> fxa_client::Result<()> {
+        let payload = serde_json::from_str(payload)?;
+        fxa.send_device_command(command_name, target, &payload)
+    })
+}
+
 /// Send a tab to another device identified by its Device ID.
 #[no_mangle]
 pub extern "C" fn fxa_send_tab(
@@ -588,6 +622,73 @@ pub extern "C" fn fxa_send_tab(
     ACCOUNTS.call_with_result_mut(error, handle, |fxa| fxa.send_tab(target, title, url))
 }
 
+/// Send several tabs to another device identified by its Device ID.
+///
+/// # Safety
+/// This function is unsafe because it will dereference `tabs_data` and
+/// read `tabs_len` bytes from it.
+///
+/// A destructor [fxa_bytebuffer_free] is provided for releasing the memory for the
+/// returned pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn fxa_send_tabs(
+    handle: u64,
+    target_device_id: FfiStr<'_>,
+    tabs_data: *const u8,
+    tabs_len: i32,
+    error: &mut ExternError,
+) -> ByteBuffer {
+    log::debug!("fxa_send_tabs");
+    let target = target_device_id.as_str();
+    ACCOUNTS.call_with_result_mut(error, handle, |fxa| {
+        let tabs = msg_types::TabsToSend::from_protobuf_ptr(tabs_data, tabs_len);
+        fxa.send_tabs(target, tabs)
+    })
+}
+
+/// Ask another device identified by its Device ID to close some of its tabs.
+///
+/// # Safety
+/// This function is unsafe because it will dereference `urls_data` and
+/// read `urls_len` bytes from it.
+#[no_mangle]
+pub unsafe extern "C" fn fxa_close_tabs(
+    handle: u64,
+    target_device_id: FfiStr<'_>,
+    urls_data: *const u8,
+    urls_len: i32,
+    error: &mut ExternError,
+) {
+    log::debug!("fxa_close_tabs");
+    let target = target_device_id.as_str();
+    ACCOUNTS.call_with_result_mut(error, handle, |fxa| {
+        let urls = msg_types::CloseTabsUrls::from_protobuf_ptr(urls_data, urls_len);
+        fxa.close_tabs(target, urls)
+    })
+}
+
+/// Tell the device that asked us to close tabs which of its URLs we closed.
+///
+/// # Safety
+/// This function is unsafe because it will dereference `reply_data` and
+/// read `reply_len` bytes from it.
+#[no_mangle]
+pub unsafe extern "C" fn fxa_reply_to_close_tabs(
+    handle: u64,
+    target_device_id: FfiStr<'_>,
+    reply_data: *const u8,
+    reply_len: i32,
+    error: &mut ExternError,
+) {
+    log::debug!("fxa_reply_to_close_tabs");
+    let target = target_device_id.as_str();
+    ACCOUNTS.call_with_result_mut(error, handle, |fxa| {
+        let (closed, not_found) =
+            msg_types::CloseTabsReply::from_protobuf_ptr(reply_data, reply_len);
+        fxa.reply_to_close_tabs(target, closed, not_found)
+    })
+}
+
 define_handle_map_deleter!(ACCOUNTS, fxa_free);
 define_string_destructor!(fxa_str_free);
 define_bytebuffer_destructor!(fxa_bytebuffer_free);
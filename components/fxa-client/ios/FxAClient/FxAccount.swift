@@ -98,6 +98,30 @@ class FxAccount: RustFxAccount {
         }
     }
 
+    override func sendTabs(targetId: String, tabs: [[TabData]]) throws -> SendTabsResult {
+        return try notifyAuthErrors {
+            try super.sendTabs(targetId: targetId, tabs: tabs)
+        }
+    }
+
+    override func closeTabs(targetId: String, urls: [String]) throws {
+        return try notifyAuthErrors {
+            try super.closeTabs(targetId: targetId, urls: urls)
+        }
+    }
+
+    override func sendDeviceCommand(command: String, targetId: String, payload: String) throws {
+        return try notifyAuthErrors {
+            try super.sendDeviceCommand(command: command, targetId: targetId, payload: payload)
+        }
+    }
+
+    override func replyToCloseTabs(targetId: String, closed: [String], notFound: [String]) throws {
+        return try notifyAuthErrors {
+            try super.replyToCloseTabs(targetId: targetId, closed: closed, notFound: notFound)
+        }
+    }
+
     override func initializeDevice(
         name: String,
         deviceType: DeviceType,
@@ -11,6 +11,8 @@ public struct Device {
     public let isCurrentDevice: Bool
     public let lastAccessTime: UInt64?
     public let capabilities: [DeviceCapability]
+    /// The names of all the commands the device supports, including custom commands.
+    public let commands: [String]
     public let subscriptionExpired: Bool
     public let subscription: DevicePushSubscription?
 
@@ -25,6 +27,7 @@ public struct Device {
         isCurrentDevice = msg.isCurrentDevice
         lastAccessTime = msg.hasLastAccessTime ? msg.lastAccessTime : nil
         capabilities = msg.capabilities.map { DeviceCapability.fromMsg(msg: $0) }
+        commands = msg.commands
         subscriptionExpired = msg.pushEndpointExpired
         subscription = msg.hasPushSubscription ?
             DevicePushSubscription(msg: msg.pushSubscription) :
@@ -65,16 +68,22 @@ public enum DeviceType {
 
 public enum DeviceCapability {
     case sendTab
+    case closeTabs
+    /// A custom command, designated by its name, whose payloads are JSON strings.
+    case custom(String)
 
     internal static func fromMsg(msg: MsgTypes_Device.Capability) -> DeviceCapability {
         switch msg {
         case .sendTab: return .sendTab
+        case .closeTabs: return .closeTabs
         }
     }
 
-    internal func toMsg() -> MsgTypes_Device.Capability {
+    internal func toMsg() -> MsgTypes_Device.Capability? {
         switch self {
         case .sendTab: return .sendTab
+        case .closeTabs: return .closeTabs
+        case .custom: return nil
         }
     }
 }
@@ -82,7 +91,11 @@ public enum DeviceCapability {
 extension Array where Element == DeviceCapability {
     internal func toCollectionMsg() -> MsgTypes_Capabilities {
         MsgTypes_Capabilities.with {
-            $0.capability = self.map { $0.toMsg() }
+            $0.capability = self.compactMap { $0.toMsg() }
+            $0.commands = self.compactMap {
+                if case let .custom(name) = $0 { return name }
+                return nil
+            }
         }
     }
 }
@@ -107,6 +120,9 @@ public struct DevicePushSubscription {
 
 public enum IncomingDeviceCommand {
     case tabReceived(Device?, [TabData])
+    case closeTabsRequested(Device?, [String])
+    case tabsClosed(Device?, closed: [String], notFound: [String])
+    case custom(Device?, command: String, payload: String)
 
     internal static func fromCollectionMsg(msg: MsgTypes_IncomingDeviceCommands) -> [IncomingDeviceCommand] {
         msg.commands.map { IncomingDeviceCommand.fromMsg(msg: $0) }
@@ -120,6 +136,21 @@ public enum IncomingDeviceCommand {
             let entries = data.entries.map { TabData(title: $0.title, url: $0.url) }
             return .tabReceived(device, entries)
         }
+        case .closeTabsRequested: do {
+            let data = msg.closeTabsRequestedData
+            let device = data.hasFrom ? Device(msg: data.from) : nil
+            return .closeTabsRequested(device, data.urls)
+        }
+        case .tabsClosed: do {
+            let data = msg.tabsClosedData
+            let device = data.hasFrom ? Device(msg: data.from) : nil
+            return .tabsClosed(device, closed: data.closed, notFound: data.notFound)
+        }
+        case .custom: do {
+            let data = msg.customData
+            let device = data.hasFrom ? Device(msg: data.from) : nil
+            return .custom(device, command: data.command, payload: data.payload)
+        }
         }
     }
 }
@@ -160,4 +191,38 @@ public enum AccountEvent {
 public struct TabData {
     public let title: String
     public let url: String
+
+    public init(title: String, url: String) {
+        self.title = title
+        self.url = url
+    }
+}
+
+/// The outcome of `sendTabs`. Tabs are identified by their index in the
+/// list passed to `sendTabs`.
+public struct SendTabsResult {
+    public let delivered: [Int]
+    public let failed: [Int]
+
+    internal init(msg: MsgTypes_SendTabsResult) {
+        delivered = msg.delivered.map { Int($0) }
+        failed = msg.failed.map { Int($0) }
+    }
+}
+
+extension Array where Element == [TabData] {
+    internal func toTabsToSendMsg() -> MsgTypes_TabsToSend {
+        MsgTypes_TabsToSend.with {
+            $0.tabs = self.map { entries in
+                MsgTypes_TabsToSend.Tab.with {
+                    $0.entries = entries.map { entry in
+                        MsgTypes_IncomingDeviceCommand.SendTabData.TabHistoryEntry.with {
+                            $0.title = entry.title
+                            $0.url = entry.url
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
@@ -94,6 +94,15 @@ public class DeviceConstellation {
                 case let .sendTab(title, url): do {
                     try self.account.sendSingleTab(targetId: targetDeviceId, title: title, url: url)
                 }
+                case let .closeTabs(urls): do {
+                    try self.account.closeTabs(targetId: targetDeviceId, urls: urls)
+                }
+                case let .closeTabsReply(closed, notFound): do {
+                    try self.account.replyToCloseTabs(targetId: targetDeviceId, closed: closed, notFound: notFound)
+                }
+                case let .custom(command, payload): do {
+                    try self.account.sendDeviceCommand(command: command, targetId: targetDeviceId, payload: payload)
+                }
                 }
             } catch {
                 FxALog.error("Error sending event to another device: \(error).")
@@ -170,4 +179,7 @@ public class DeviceConstellation {
 
 public enum DeviceEventOutgoing {
     case sendTab(title: String, url: String)
+    case closeTabs(urls: [String])
+    case closeTabsReply(closed: [String], notFound: [String])
+    case custom(command: String, payload: String)
 }
@@ -111,6 +111,30 @@ void fxa_send_tab(FirefoxAccountHandle handle,
                   const char *_Nonnull url,
                   FxAError *_Nonnull out);
 
+FxARustBuffer fxa_send_tabs(FirefoxAccountHandle handle,
+                            const char *_Nonnull targetId,
+                            uint8_t const *_Nonnull tabs_ptr,
+                            int32_t tabs_len,
+                            FxAError *_Nonnull out);
+
+void fxa_close_tabs(FirefoxAccountHandle handle,
+                    const char *_Nonnull targetId,
+                    uint8_t const *_Nonnull urls_ptr,
+                    int32_t urls_len,
+                    FxAError *_Nonnull out);
+
+void fxa_send_device_command(FirefoxAccountHandle handle,
+                             const char *_Nonnull commandName,
+                             const char *_Nonnull targetId,
+                             const char *_Nonnull payload,
+                             FxAError *_Nonnull out);
+
+void fxa_reply_to_close_tabs(FirefoxAccountHandle handle,
+                             const char *_Nonnull targetId,
+                             uint8_t const *_Nonnull reply_ptr,
+                             int32_t reply_len,
+                             FxAError *_Nonnull out);
+
 void fxa_set_device_name(FirefoxAccountHandle handle,
                          const char *_Nonnull displayName,
                          FxAError *_Nonnull out);
@@ -222,6 +222,63 @@ open class RustFxAccount {
         }
     }
 
+    open func sendTabs(targetId: String, tabs: [[TabData]]) throws -> SendTabsResult {
+        let (data, size) = msgToBuffer(msg: tabs.toTabsToSendMsg())
+        let ptr = try data.withUnsafeBytes { bytes in
+            try rustCall { err in
+                fxa_send_tabs(
+                    self.raw,
+                    targetId,
+                    bytes.bindMemory(to: UInt8.self).baseAddress!,
+                    size,
+                    err
+                )
+            }
+        }
+        defer { fxa_bytebuffer_free(ptr) }
+        let msg = try! MsgTypes_SendTabsResult(serializedData: Data(rustBuffer: ptr))
+        return SendTabsResult(msg: msg)
+    }
+
+    open func closeTabs(targetId: String, urls: [String]) throws {
+        let (data, size) = msgToBuffer(msg: MsgTypes_CloseTabsUrls.with { $0.urls = urls })
+        try data.withUnsafeBytes { bytes in
+            try rustCall { err in
+                fxa_close_tabs(
+                    self.raw,
+                    targetId,
+                    bytes.bindMemory(to: UInt8.self).baseAddress!,
+                    size,
+                    err
+                )
+            }
+        }
+    }
+
+    open func sendDeviceCommand(command: String, targetId: String, payload: String) throws {
+        try rustCall { err in
+            fxa_send_device_command(self.raw, command, targetId, payload, err)
+        }
+    }
+
+    open func replyToCloseTabs(targetId: String, closed: [String], notFound: [String]) throws {
+        let (data, size) = msgToBuffer(msg: MsgTypes_CloseTabsReply.with {
+            $0.closed = closed
+            $0.notFound = notFound
+        })
+        try data.withUnsafeBytes { bytes in
+            try rustCall { err in
+                fxa_reply_to_close_tabs(
+                    self.raw,
+                    targetId,
+                    bytes.bindMemory(to: UInt8.self).baseAddress!,
+                    size,
+                    err
+                )
+            }
+        }
+    }
+
     open func setDevicePushSubscription(endpoint: String, publicKey: String, authKey: String) throws {
         try rustCall { err in
             fxa_set_push_subscription(self.raw, endpoint, publicKey, authKey, err)
@@ -0,0 +1,32 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+pub use crate::commands::close_tabs::{CloseTabsPayload, CloseTabsReply};
+use crate::{
+    commands::close_tabs::{CloseTabsCommand, CloseTabsMessage},
+    error::*,
+    FirefoxAccount,
+};
+
+impl FirefoxAccount {
+    /// Ask another device, designated by its device ID, to close the tabs
+    /// with these URLs.
+    pub fn close_tabs(&mut self, target_device_id: &str, urls: Vec<String>) -> Result<()> {
+        let message = CloseTabsMessage::Request(CloseTabsPayload::new(urls));
+        self.invoke_device_command(&CloseTabsCommand, target_device_id, &message)
+    }
+
+    /// Tell the device that asked us to close some tabs, designated by its
+    /// device ID, which of its URLs we closed, and which ones didn't match an
+    /// open tab.
+    pub fn reply_to_close_tabs(
+        &mut self,
+        target_device_id: &str,
+        closed: Vec<String>,
+        not_found: Vec<String>,
+    ) -> Result<()> {
+        let message = CloseTabsMessage::Reply(CloseTabsReply { closed, not_found });
+        self.invoke_device_command(&CloseTabsCommand, target_device_id, &message)
+    }
+}
@@ -0,0 +1,171 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+/// The Close Tabs command asks another device to close some of its tabs.
+/// A device shows it can handle "Close Tabs" commands by advertising the
+/// "close-uri" command in its own device record.
+///
+/// The target device replies with the same command, telling the sender which
+/// of the URLs it closed. Every message has a `kind` field that tells the
+/// two apart: requests have `"kind": "request"` and a `urls` list, and
+/// replies have `"kind": "reply"` and `closed` and `notFound` lists.
+///
+/// It uses the same key exchange as Send Tab (see `send_tab.rs`), with its
+/// own one-time generated keys: the command data is a `SendTabKeysPayload`
+/// wrapping the device's public keys, and the sender uses them to encrypt
+/// the `CloseTabsPayload` containing the URLs to close.
+use super::{send_tab::SendTabKeys, CommandKeys, DeviceCommand};
+use crate::{
+    device::{Capability, Device},
+    error::*,
+    IncomingDeviceCommand,
+};
+use serde_derive::*;
+
+pub const COMMAND_NAME: &str = "https://identity.mozilla.com/cmd/close-uri/v1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloseTabsPayload {
+    /// The URLs of the tabs to close. A URL can appear more than once, if
+    /// more than one tab with that URL should be closed.
+    pub urls: Vec<String>,
+}
+
+impl CloseTabsPayload {
+    pub fn new(urls: Vec<String>) -> Self {
+        CloseTabsPayload { urls }
+    }
+}
+
+/// The reply to a close-tabs request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseTabsReply {
+    /// The requested URLs whose tabs were closed.
+    pub closed: Vec<String>,
+    /// The requested URLs that didn't match an open tab.
+    pub not_found: Vec<String>,
+}
+
+/// What we send and receive with a close-tabs command, tagged with its
+/// `kind`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub(crate) enum CloseTabsMessage {
+    Request(CloseTabsPayload),
+    Reply(CloseTabsReply),
+}
+
+/// The Close Tabs command.
+pub(crate) struct CloseTabsCommand;
+
+impl DeviceCommand for CloseTabsCommand {
+    fn name(&self) -> &str {
+        COMMAND_NAME
+    }
+
+    fn capability(&self) -> Capability {
+        Capability::CloseTabs
+    }
+
+    fn keys(&self) -> &dyn CommandKeys {
+        &SendTabKeys
+    }
+
+    fn handle(
+        &self,
+        sender: Option<Device>,
+        cleartext: &[u8],
+    ) -> Result<Vec<IncomingDeviceCommand>> {
+        Ok(vec![match serde_json::from_slice(cleartext)? {
+            CloseTabsMessage::Request(payload) => {
+                IncomingDeviceCommand::CloseTabsRequested { sender, payload }
+            }
+            CloseTabsMessage::Reply(payload) => {
+                IncomingDeviceCommand::TabsClosed { sender, payload }
+            }
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let payload = CloseTabsPayload::new(vec![
+            "https://example.com/".to_owned(),
+            "https://example.com/".to_owned(),
+            "https://mozilla.org/".to_owned(),
+        ]);
+        let cleartext = serde_json::to_vec(&CloseTabsMessage::Request(payload.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&cleartext).unwrap(),
+            serde_json::json!({
+                "kind": "request",
+                "urls": [
+                    "https://example.com/",
+                    "https://example.com/",
+                    "https://mozilla.org/",
+                ],
+            })
+        );
+        match CloseTabsCommand
+            .handle(None, &cleartext)
+            .unwrap()
+            .as_slice()
+        {
+            [IncomingDeviceCommand::CloseTabsRequested {
+                sender,
+                payload: received,
+            }] => {
+                assert!(sender.is_none());
+                assert_eq!(received, &payload);
+            }
+            commands => panic!("Unexpected commands {:?}", commands),
+        }
+        CloseTabsCommand
+            .handle(None, br#"{"entries":[]}"#)
+            .unwrap_err();
+        // Messages without a `kind` are rejected, even if their fields match.
+        CloseTabsCommand
+            .handle(None, br#"{"urls":["https://example.com/"]}"#)
+            .unwrap_err();
+        CloseTabsCommand
+            .handle(None, br#"{"kind":"reply","urls":["https://example.com/"]}"#)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_handle_reply() {
+        let reply = CloseTabsReply {
+            closed: vec!["https://example.com/".to_owned()],
+            not_found: vec!["https://mozilla.org/".to_owned()],
+        };
+        let cleartext = serde_json::to_vec(&CloseTabsMessage::Reply(reply.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&cleartext).unwrap(),
+            serde_json::json!({
+                "kind": "reply",
+                "closed": ["https://example.com/"],
+                "notFound": ["https://mozilla.org/"],
+            })
+        );
+        match CloseTabsCommand
+            .handle(None, &cleartext)
+            .unwrap()
+            .as_slice()
+        {
+            [IncomingDeviceCommand::TabsClosed {
+                sender,
+                payload: received,
+            }] => {
+                assert!(sender.is_none());
+                assert_eq!(received, &reply);
+            }
+            commands => panic!("Unexpected commands {:?}", commands),
+        }
+    }
+}
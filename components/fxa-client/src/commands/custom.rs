@@ -0,0 +1,68 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+/// Custom commands let applications add their own device commands without
+/// writing any Rust: a custom command is identified by its name, its payloads
+/// are arbitrary JSON, and they are encrypted with the same key exchange as
+/// Send Tab (see `send_tab.rs`), using the command's own keys.
+///
+/// Incoming payloads are passed to the application unchanged, in an
+/// `IncomingDeviceCommand::Custom`.
+use super::{send_tab::SendTabKeys, CommandKeys, DeviceCommand};
+
+/// A command whose payloads are JSON values that the application builds and
+/// interprets itself.
+pub struct CustomCommand {
+    name: String,
+}
+
+impl CustomCommand {
+    pub fn new(name: &str) -> Self {
+        CustomCommand {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl DeviceCommand for CustomCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn keys(&self) -> &dyn CommandKeys {
+        &SendTabKeys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{device::Capability, IncomingDeviceCommand};
+
+    #[test]
+    fn test_handle() {
+        let command = CustomCommand::new("https://example.com/cmd/ring");
+        assert_eq!(
+            command.capability(),
+            Capability::Command("https://example.com/cmd/ring".to_owned())
+        );
+        match command
+            .handle(None, br#"{"volume":11}"#)
+            .unwrap()
+            .as_slice()
+        {
+            [IncomingDeviceCommand::Custom {
+                sender,
+                command,
+                payload,
+            }] => {
+                assert!(sender.is_none());
+                assert_eq!(command, "https://example.com/cmd/ring");
+                assert_eq!(payload, &serde_json::json!({ "volume": 11 }));
+            }
+            commands => panic!("Unexpected commands {:?}", commands),
+        }
+        command.handle(None, b"not json").unwrap_err();
+    }
+}
@@ -2,4 +2,171 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+//! Device commands let a device ask another one to do something, like
+//! opening or closing tabs. A device advertises the commands it can handle
+//! in its device record, along with the data other devices need to encrypt
+//! payloads for it (usually public keys). Invoking a command stores its
+//! encrypted payload on the server, which notifies the target device.
+//!
+//! Each command is described by a `DeviceCommand`. Every `FirefoxAccount`
+//! has a `CommandRegistry` with the built-in commands, and consumers can add
+//! their own with `FirefoxAccount::register_device_command`. Registering,
+//! invoking and handling commands is then generic (see `device.rs`), so adding
+//! a command doesn't need any changes there, or in the FFI.
+
+use crate::{
+    device::{Capability, Device},
+    error::*,
+    scoped_keys::ScopedKey,
+    IncomingDeviceCommand,
+};
+use std::sync::Arc;
+
+pub mod close_tabs;
+pub mod custom;
 pub mod send_tab;
+
+/// The largest encrypted payload, in bytes, that we send with a single
+/// command. This is the size of the `payload` JSON in the `invoke_command`
+/// request, which the server stores until the target device fetches it.
+pub(crate) const MAX_COMMAND_PAYLOAD_SIZE: usize = 32 * 1024;
+
+/// A command that a device can register, and that other devices can invoke
+/// on it.
+pub trait DeviceCommand: Send + Sync {
+    /// The command name, which devices advertise in their device record.
+    fn name(&self) -> &str;
+
+    /// The capability that consumers pass to `ensure_capabilities` to
+    /// register this command.
+    fn capability(&self) -> Capability {
+        Capability::Command(self.name().to_owned())
+    }
+
+    /// The scheme used to generate this command's keys, and to encrypt
+    /// and decrypt its payloads.
+    fn keys(&self) -> &dyn CommandKeys;
+
+    /// Deserializes a decrypted payload sent by `sender`, and turns it into
+    /// incoming commands for the application. A single payload can carry
+    /// more than one command, like several tabs sent together.
+    ///
+    /// By default, the payload is passed to the application as JSON, in an
+    /// `IncomingDeviceCommand::Custom`.
+    fn handle(
+        &self,
+        sender: Option<Device>,
+        cleartext: &[u8],
+    ) -> Result<Vec<IncomingDeviceCommand>> {
+        Ok(vec![IncomingDeviceCommand::Custom {
+            sender,
+            command: self.name().to_owned(),
+            payload: serde_json::from_slice(cleartext)?,
+        }])
+    }
+}
+
+/// A key-generation and encryption scheme for command payloads. Private keys
+/// and command data are opaque strings: the private keys are persisted in the
+/// account state, and the command data is advertised in our device record.
+pub trait CommandKeys: Send + Sync {
+    /// Generates a new set of private keys.
+    fn generate(&self) -> Result<String>;
+
+    /// Returns the command data to advertise for our `private_keys`.
+    fn command_data(&self, private_keys: &str, scoped_key: &ScopedKey) -> Result<String>;
+
+    /// Encrypts `cleartext` for a device that advertised `command_data`.
+    fn encrypt(
+        &self,
+        cleartext: &[u8],
+        command_data: &str,
+        scoped_key: &ScopedKey,
+    ) -> Result<serde_json::Value>;
+
+    /// Decrypts a payload sent to us with our `private_keys`.
+    fn decrypt(&self, payload: serde_json::Value, private_keys: &str) -> Result<Vec<u8>>;
+
+    /// Checks that the command data we advertised matches our `private_keys`,
+    /// to help diagnose why a payload couldn't be decrypted.
+    fn diagnose(
+        &self,
+        private_keys: &str,
+        command_data: &str,
+        scoped_key: &ScopedKey,
+    ) -> Result<()>;
+}
+
+/// The commands an account knows how to register and handle.
+pub(crate) struct CommandRegistry {
+    commands: Vec<Arc<dyn DeviceCommand>>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self {
+            commands: vec![
+                Arc::new(send_tab::SendTabCommand),
+                Arc::new(close_tabs::CloseTabsCommand),
+            ],
+        }
+    }
+}
+
+impl CommandRegistry {
+    /// Adds `command` to the registry, replacing any command with the same
+    /// name.
+    pub(crate) fn register(&mut self, command: Arc<dyn DeviceCommand>) {
+        self.commands.retain(|c| c.name() != command.name());
+        self.commands.push(command);
+    }
+
+    pub(crate) fn find_by_name(&self, name: &str) -> Option<Arc<dyn DeviceCommand>> {
+        self.commands.iter().find(|c| c.name() == name).cloned()
+    }
+
+    pub(crate) fn find_by_capability(
+        &self,
+        capability: &Capability,
+    ) -> Option<Arc<dyn DeviceCommand>> {
+        self.commands
+            .iter()
+            .find(|c| c.capability() == *capability)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = CommandRegistry::default();
+        let builtins = registry.commands.len();
+        let names: HashSet<_> = registry.commands.iter().map(|c| c.name()).collect();
+        assert_eq!(names.len(), builtins, "Command names must be unique");
+        for capability in &[Capability::SendTab, Capability::CloseTabs] {
+            let command = registry
+                .find_by_capability(capability)
+                .expect("Should have a command");
+            assert_eq!(
+                registry
+                    .find_by_name(command.name())
+                    .map(|c| c.capability()),
+                Some(capability.clone())
+            );
+        }
+
+        let name = "https://identity.mozilla.com/cmd/unknown";
+        assert!(registry.find_by_name(name).is_none());
+        registry.register(Arc::new(custom::CustomCommand::new(name)));
+        registry.register(Arc::new(custom::CustomCommand::new(name)));
+        assert_eq!(registry.commands.len(), builtins + 1);
+        let command = registry
+            .find_by_capability(&Capability::Command(name.to_owned()))
+            .expect("Should find the registered command");
+        assert_eq!(command.name(), name);
+    }
+}
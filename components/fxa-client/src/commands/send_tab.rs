@@ -13,7 +13,13 @@
 /// uses the obtained public key to encrypt the `SendTabPayload` it created that
 /// contains the tab to send and finally forms the `EncryptedSendTabPayload` that is
 /// then sent to the target device.
-use crate::{device::Device, error::*, scoped_keys::ScopedKey, scopes};
+use super::{CommandKeys, DeviceCommand};
+use crate::{
+    device::{Capability, Device},
+    error::*,
+    scoped_keys::ScopedKey,
+    scopes, IncomingDeviceCommand,
+};
 use rc_crypto::ece::{self, Aes128GcmEceWebPush, EcKeyComponents, WebPushParams};
 use rc_crypto::ece_crypto::{RcCryptoLocalKeyPair, RcCryptoRemotePublicKey};
 use serde_derive::*;
@@ -21,54 +27,169 @@ use sync15::{EncryptedPayload, KeyBundle};
 
 pub const COMMAND_NAME: &str = "https://identity.mozilla.com/cmd/open-uri";
 
+/// The largest cleartext payload, in bytes, that we pack into a single Send
+/// Tab command. Encryption adds an 86-byte header, and a 17-byte tag and
+/// delimiter to each 4 KiB record. Base64 then grows the result by a third, so
+/// a full payload is just under 22 KiB once encrypted. That's well within
+/// `MAX_COMMAND_PAYLOAD_SIZE`, which `test_max_payload_size` checks. A tab that
+/// doesn't fit even on its own is still sent by itself, and
+/// `invoke_device_command` rejects it if it's over that limit once encrypted.
+pub(crate) const MAX_PAYLOAD_SIZE: usize = 16 * 1024;
+
+/// The serialized size, in bytes, of a tab without entries.
+const TAB_OVERHEAD: usize = r#"{"entries":[]}"#.len();
+
+/// The serialized size, in bytes, that the first additional tab adds to a
+/// payload, besides the tab itself. Each one after it adds a comma.
+const ADDITIONAL_TABS_OVERHEAD: usize = r#","additionalTabs":[]"#.len();
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EncryptedSendTabPayload {
     /// URL Safe Base 64 encrypted send-tab payload.
     encrypted: String,
 }
 
-impl EncryptedSendTabPayload {
-    pub(crate) fn decrypt(self, keys: &PrivateSendTabKeysV1) -> Result<SendTabPayload> {
-        rc_crypto::ensure_initialized();
-        let encrypted = base64::decode_config(&self.encrypted, base64::URL_SAFE_NO_PAD)?;
-        let private_key = RcCryptoLocalKeyPair::from_raw_components(&keys.p256key)?;
-        let decrypted = Aes128GcmEceWebPush::decrypt(&private_key, &keys.auth_secret, &encrypted)?;
-        Ok(serde_json::from_slice(&decrypted)?)
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+/// A tab, with the entries of its session history. The current entry of the
+/// tab is the last one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SendTabPayload {
     pub entries: Vec<TabHistoryEntry>,
 }
 
 impl SendTabPayload {
     pub fn single_tab(title: &str, url: &str) -> Self {
-        SendTabPayload {
-            entries: vec![TabHistoryEntry {
-                title: title.to_string(),
-                url: url.to_string(),
-            }],
+        Self::with_history(title, url, Vec::new())
+    }
+
+    /// A tab at `url`, with the `history` entries that came before it,
+    /// oldest first.
+    pub fn with_history(title: &str, url: &str, history: Vec<TabHistoryEntry>) -> Self {
+        let mut entries = history;
+        entries.push(TabHistoryEntry {
+            title: title.to_string(),
+            url: url.to_string(),
+        });
+        SendTabPayload { entries }
+    }
+}
+
+/// The payload of a Send Tab command, as it's sent over the wire. The first
+/// tab is flattened into the payload, so that clients that only know how to
+/// receive a single tab still open it. Newer clients open the additional tabs
+/// too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SendTabsPayload {
+    #[serde(flatten)]
+    first: SendTabPayload,
+    #[serde(
+        rename = "additionalTabs",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    additional: Vec<SendTabPayload>,
+}
+
+impl SendTabsPayload {
+    fn new(first: SendTabPayload) -> Self {
+        SendTabsPayload {
+            first,
+            additional: Vec::new(),
         }
     }
-    fn encrypt(&self, keys: PublicSendTabKeys) -> Result<EncryptedSendTabPayload> {
-        rc_crypto::ensure_initialized();
-        let bytes = serde_json::to_vec(&self)?;
-        let public_key = base64::decode_config(&keys.public_key, base64::URL_SAFE_NO_PAD)?;
-        let public_key = RcCryptoRemotePublicKey::from_raw(&public_key)?;
-        let auth_secret = base64::decode_config(&keys.auth_secret, base64::URL_SAFE_NO_PAD)?;
-        let encrypted = Aes128GcmEceWebPush::encrypt(
-            &public_key,
-            &auth_secret,
-            &bytes,
-            WebPushParams::default(),
-        )?;
-        let encrypted = base64::encode_config(&encrypted, base64::URL_SAFE_NO_PAD);
-        Ok(EncryptedSendTabPayload { encrypted })
+
+    fn into_tabs(self) -> impl Iterator<Item = SendTabPayload> {
+        std::iter::once(self.first).chain(self.additional)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Tabs packed into Send Tab payloads by `pack_tabs`. Tabs are identified by
+/// their index in the list passed to `pack_tabs`.
+#[derive(Debug, Default)]
+pub(crate) struct PackedTabs {
+    /// The payloads to send, each with the indices of the tabs it contains.
+    pub(crate) payloads: Vec<(Vec<usize>, SendTabsPayload)>,
+    /// The tabs that can't be sent, because they don't have any entries.
+    pub(crate) unsendable: Vec<usize>,
+}
+
+/// Packs `tabs` into as few payloads as possible, keeping their order, so
+/// that each payload is at most `max_size` bytes once serialized. The oldest
+/// history entries of a tab are dropped if the tab doesn't fit in a payload on
+/// its own. If it still doesn't fit with only its current entry, it gets a
+/// payload of its own, and it's up to `invoke_device_command` to decide if
+/// it's small enough to send.
+///
+/// Each history entry is serialized once, and the sizes of the tabs and
+/// payloads are worked out from there.
+pub(crate) fn pack_tabs(tabs: Vec<SendTabPayload>, max_size: usize) -> Result<PackedTabs> {
+    let mut packed = PackedTabs::default();
+    // The payload we're filling, with the indices of its tabs and its size.
+    let mut current: Option<(Vec<usize>, SendTabsPayload, usize)> = None;
+    for (index, mut tab) in tabs.into_iter().enumerate() {
+        let tab_size = match trim_history(&mut tab, max_size)? {
+            Some(tab_size) => tab_size,
+            None => {
+                packed.unsendable.push(index);
+                continue;
+            }
+        };
+        if let Some((indices, payload, size)) = &mut current {
+            let added = if payload.additional.is_empty() {
+                ADDITIONAL_TABS_OVERHEAD + tab_size
+            } else {
+                1 + tab_size
+            };
+            if *size + added <= max_size {
+                payload.additional.push(tab);
+                indices.push(index);
+                *size += added;
+                continue;
+            }
+        }
+        // This tab doesn't fit, so it starts the next payload.
+        packed.payloads.extend(
+            current
+                .take()
+                .map(|(indices, payload, _)| (indices, payload)),
+        );
+        current = Some((vec![index], SendTabsPayload::new(tab), tab_size));
+    }
+    packed
+        .payloads
+        .extend(current.map(|(indices, payload, _)| (indices, payload)));
+    Ok(packed)
+}
+
+/// Drops the oldest history entries of `tab` until it's at most `max_size`
+/// bytes once serialized, or only has its current entry left, and returns its
+/// size. Returns `None` if the tab has no entries.
+fn trim_history(tab: &mut SendTabPayload, max_size: usize) -> Result<Option<usize>> {
+    let entry_sizes = tab
+        .entries
+        .iter()
+        .map(serialized_size)
+        .collect::<Result<Vec<_>>>()?;
+    // Entries are separated by commas.
+    let mut size =
+        TAB_OVERHEAD + entry_sizes.iter().sum::<usize>() + entry_sizes.len().saturating_sub(1);
+    let mut dropped = 0;
+    while size > max_size && dropped + 1 < entry_sizes.len() {
+        size -= entry_sizes[dropped] + 1;
+        dropped += 1;
+    }
+    tab.entries.drain(..dropped);
+    Ok(if tab.entries.is_empty() {
+        None
+    } else {
+        Some(size)
+    })
+}
+
+fn serialized_size<T: serde::Serialize>(value: &T) -> Result<usize> {
+    Ok(serde_json::to_vec(value)?.len())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TabHistoryEntry {
     pub title: String,
     pub url: String,
@@ -171,12 +292,6 @@ impl PublicSendTabKeys {
         let encrypted_public_keys = self.encrypt(scoped_key)?;
         Ok(serde_json::to_string(&encrypted_public_keys)?)
     }
-    pub(crate) fn public_key(&self) -> &str {
-        &self.public_key
-    }
-    pub(crate) fn auth_secret(&self) -> &str {
-        &self.auth_secret
-    }
 }
 
 impl From<PrivateSendTabKeys> for PublicSendTabKeys {
@@ -191,19 +306,113 @@ impl From<PrivateSendTabKeys> for PublicSendTabKeys {
     }
 }
 
-pub fn build_send_command(
-    scoped_key: &ScopedKey,
-    target: &Device,
-    send_tab_payload: &SendTabPayload,
-) -> Result<serde_json::Value> {
-    let command = target
-        .available_commands
-        .get(COMMAND_NAME)
-        .ok_or_else(|| ErrorKind::UnsupportedCommand(COMMAND_NAME))?;
-    let bundle: SendTabKeysPayload = serde_json::from_str(command)?;
-    let public_keys = bundle.decrypt(scoped_key)?;
-    let encrypted_payload = send_tab_payload.encrypt(public_keys)?;
-    Ok(serde_json::to_value(&encrypted_payload)?)
+/// The Send Tab command.
+pub(crate) struct SendTabCommand;
+
+impl DeviceCommand for SendTabCommand {
+    fn name(&self) -> &str {
+        COMMAND_NAME
+    }
+
+    fn capability(&self) -> Capability {
+        Capability::SendTab
+    }
+
+    fn keys(&self) -> &dyn CommandKeys {
+        &SendTabKeys
+    }
+
+    fn handle(
+        &self,
+        sender: Option<Device>,
+        cleartext: &[u8],
+    ) -> Result<Vec<IncomingDeviceCommand>> {
+        let payload: SendTabsPayload = serde_json::from_slice(cleartext)?;
+        Ok(payload
+            .into_tabs()
+            .map(|payload| IncomingDeviceCommand::TabReceived {
+                sender: sender.clone(),
+                payload,
+            })
+            .collect())
+    }
+}
+
+/// The Send Tab key exchange. Other commands can use it too, with their own
+/// keys: each command persists its `PrivateSendTabKeys` and advertises its
+/// `SendTabKeysPayload` under its own name.
+pub struct SendTabKeys;
+
+impl CommandKeys for SendTabKeys {
+    fn generate(&self) -> Result<String> {
+        PrivateSendTabKeys::from_random()?.serialize()
+    }
+
+    fn command_data(&self, private_keys: &str, scoped_key: &ScopedKey) -> Result<String> {
+        let public_keys: PublicSendTabKeys = PrivateSendTabKeys::deserialize(private_keys)?.into();
+        public_keys.as_command_data(scoped_key)
+    }
+
+    fn encrypt(
+        &self,
+        cleartext: &[u8],
+        command_data: &str,
+        scoped_key: &ScopedKey,
+    ) -> Result<serde_json::Value> {
+        let bundle: SendTabKeysPayload = serde_json::from_str(command_data)?;
+        let keys = bundle.decrypt(scoped_key)?;
+        rc_crypto::ensure_initialized();
+        let public_key = base64::decode_config(&keys.public_key, base64::URL_SAFE_NO_PAD)?;
+        let public_key = RcCryptoRemotePublicKey::from_raw(&public_key)?;
+        let auth_secret = base64::decode_config(&keys.auth_secret, base64::URL_SAFE_NO_PAD)?;
+        let encrypted = Aes128GcmEceWebPush::encrypt(
+            &public_key,
+            &auth_secret,
+            cleartext,
+            WebPushParams::default(),
+        )?;
+        let encrypted = base64::encode_config(&encrypted, base64::URL_SAFE_NO_PAD);
+        Ok(serde_json::to_value(&EncryptedSendTabPayload {
+            encrypted,
+        })?)
+    }
+
+    fn decrypt(&self, payload: serde_json::Value, private_keys: &str) -> Result<Vec<u8>> {
+        let keys = PrivateSendTabKeys::deserialize(private_keys)?;
+        let payload: EncryptedSendTabPayload = serde_json::from_value(payload)?;
+        rc_crypto::ensure_initialized();
+        let encrypted = base64::decode_config(&payload.encrypted, base64::URL_SAFE_NO_PAD)?;
+        let private_key = RcCryptoLocalKeyPair::from_raw_components(&keys.p256key)?;
+        Ok(Aes128GcmEceWebPush::decrypt(
+            &private_key,
+            &keys.auth_secret,
+            &encrypted,
+        )?)
+    }
+
+    fn diagnose(
+        &self,
+        private_keys: &str,
+        command_data: &str,
+        scoped_key: &ScopedKey,
+    ) -> Result<()> {
+        let bundle: SendTabKeysPayload = serde_json::from_str(command_data)?;
+        let public_keys_remote = bundle.decrypt(scoped_key).map_err(|_| {
+            ErrorKind::CommandDiagnosisError("Unable to decrypt public key bundle.")
+        })?;
+
+        let public_keys_local: PublicSendTabKeys =
+            PrivateSendTabKeys::deserialize(private_keys)?.into();
+
+        if public_keys_local.public_key != public_keys_remote.public_key {
+            return Err(ErrorKind::CommandDiagnosisError("Mismatch in public key.").into());
+        }
+
+        if public_keys_local.auth_secret != public_keys_remote.auth_secret {
+            return Err(ErrorKind::CommandDiagnosisError("Mismatch in auth secret.").into());
+        }
+        Ok(())
+    }
 }
 
 fn extract_oldsync_key_components(oldsync_key: &ScopedKey) -> Result<(Vec<u8>, Vec<u8>)> {
@@ -218,3 +427,175 @@ fn extract_oldsync_key_components(oldsync_key: &ScopedKey) -> Result<(Vec<u8>, V
     let ksync = oldsync_key.key_bytes()?;
     Ok((ksync, kxcs))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oldsync_key() -> ScopedKey {
+        ScopedKey {
+            kty: "oct".to_string(),
+            scope: scopes::OLD_SYNC.to_string(),
+            k: "kMtwpVC0ZaYFJymPza8rXK_0CgCp3KMwRStwGfBRBDtL6hXRDVJgQFaoOQ2dimw0Bko5WVv2gNTy7RX5zFYZHg".to_string(),
+            kid: "1542236016429-Ox1FbJfFfwTe5t-xq4v2hQ".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_send_tab_keys() {
+        let scoped_key = oldsync_key();
+        let private_keys = SendTabKeys.generate().unwrap();
+        let command_data = SendTabKeys
+            .command_data(&private_keys, &scoped_key)
+            .unwrap();
+        SendTabKeys
+            .diagnose(&private_keys, &command_data, &scoped_key)
+            .unwrap();
+
+        let payload = SendTabPayload::single_tab("Example", "https://example.com/");
+        let cleartext = serde_json::to_vec(&payload).unwrap();
+        let encrypted = SendTabKeys
+            .encrypt(&cleartext, &command_data, &scoped_key)
+            .unwrap();
+        let decrypted = SendTabKeys
+            .decrypt(encrypted.clone(), &private_keys)
+            .unwrap();
+        match SendTabCommand.handle(None, &decrypted).unwrap().as_slice() {
+            [IncomingDeviceCommand::TabReceived { sender, payload }] => {
+                assert!(sender.is_none());
+                assert_eq!(payload.entries.len(), 1);
+                assert_eq!(payload.entries[0].title, "Example");
+                assert_eq!(payload.entries[0].url, "https://example.com/");
+            }
+            commands => panic!("Unexpected commands {:?}", commands),
+        }
+
+        let other_keys = SendTabKeys.generate().unwrap();
+        SendTabKeys.decrypt(encrypted, &other_keys).unwrap_err();
+        SendTabKeys
+            .diagnose(&other_keys, &command_data, &scoped_key)
+            .unwrap_err();
+    }
+
+    fn tab(index: usize, history_len: usize) -> SendTabPayload {
+        let history = (0..history_len)
+            .map(|i| TabHistoryEntry {
+                title: format!("Previous {}", i),
+                url: format!("https://example.com/{}/previous/{}", index, i),
+            })
+            .collect();
+        SendTabPayload::with_history(
+            &format!("Tab {}", index),
+            &format!("https://example.com/{}", index),
+            history,
+        )
+    }
+
+    #[test]
+    fn test_pack_tabs() {
+        let tabs: Vec<_> = (0..10).map(|i| tab(i, 0)).collect();
+        let single_size = serialized_size(&tabs[0]).unwrap();
+
+        // Everything fits in one payload.
+        let packed = pack_tabs(tabs.clone(), MAX_PAYLOAD_SIZE).unwrap();
+        assert!(packed.unsendable.is_empty());
+        assert_eq!(packed.payloads.len(), 1);
+        assert_eq!(packed.payloads[0].0, (0..10).collect::<Vec<_>>());
+        let received: Vec<_> = serde_json::from_value::<SendTabsPayload>(
+            serde_json::to_value(&packed.payloads[0].1).unwrap(),
+        )
+        .unwrap()
+        .into_tabs()
+        .collect();
+        assert_eq!(received, tabs);
+
+        // Only a few tabs fit in each payload, so they're split, in order.
+        let max_size = single_size * 4;
+        let packed = pack_tabs(tabs, max_size).unwrap();
+        assert!(packed.payloads.len() > 1);
+        let mut indices = Vec::new();
+        for (payload_indices, payload) in &packed.payloads {
+            assert!(serialized_size(payload).unwrap() <= max_size);
+            indices.extend(payload_indices.iter().copied());
+        }
+        assert_eq!(indices, (0..10).collect::<Vec<_>>());
+        // ...and each payload is as full as it can be.
+        for pair in packed.payloads.windows(2) {
+            let mut payload = pair[0].1.clone();
+            payload.additional.push(pair[1].1.first.clone());
+            assert!(serialized_size(&payload).unwrap() > max_size);
+        }
+
+        // Tabs that are too large lose their oldest history entries, tabs
+        // that are still too large are packed on their own, and tabs without
+        // entries can't be sent at all.
+        let mut tabs = vec![tab(0, 0), tab(1, 100), SendTabPayload { entries: vec![] }];
+        tabs[0].entries[0].url = "https://example.com/".repeat(max_size);
+        let packed = pack_tabs(tabs, max_size).unwrap();
+        assert_eq!(packed.unsendable, vec![2]);
+        assert_eq!(packed.payloads.len(), 2);
+        let (indices, payload) = &packed.payloads[0];
+        assert_eq!(indices, &vec![0]);
+        assert!(payload.additional.is_empty());
+        assert_eq!(payload.first.entries.len(), 1);
+        assert!(serialized_size(payload).unwrap() > max_size);
+        let (indices, payload) = &packed.payloads[1];
+        assert_eq!(indices, &vec![1]);
+        assert!(payload.additional.is_empty());
+        assert!(serialized_size(payload).unwrap() <= max_size);
+        let entries = &payload.first.entries;
+        assert!(entries.len() < 101);
+        assert_eq!(entries.last().unwrap().url, "https://example.com/1");
+        assert_eq!(
+            entries[0].url,
+            format!("https://example.com/1/previous/{}", 101 - entries.len())
+        );
+    }
+
+    #[test]
+    fn test_max_payload_size() {
+        let scoped_key = oldsync_key();
+        let private_keys = SendTabKeys.generate().unwrap();
+        let command_data = SendTabKeys
+            .command_data(&private_keys, &scoped_key)
+            .unwrap();
+        let cleartext = vec![b'a'; MAX_PAYLOAD_SIZE];
+        let encrypted = SendTabKeys
+            .encrypt(&cleartext, &command_data, &scoped_key)
+            .unwrap();
+        let size = serialized_size(&encrypted).unwrap();
+        assert!(size < 22 * 1024);
+        assert!(size <= crate::commands::MAX_COMMAND_PAYLOAD_SIZE);
+    }
+
+    #[test]
+    fn test_receive_multiple_tabs() {
+        let payload = serde_json::json!({
+            "entries": [{ "title": "Tab 0", "url": "https://example.com/0" }],
+            "additionalTabs": [
+                { "entries": [{ "title": "Tab 1", "url": "https://example.com/1" }] },
+                { "entries": [{ "title": "Tab 2", "url": "https://example.com/2" }] },
+            ],
+        });
+        let commands = SendTabCommand
+            .handle(None, &serde_json::to_vec(&payload).unwrap())
+            .unwrap();
+        let urls: Vec<_> = commands
+            .iter()
+            .map(|command| match command {
+                IncomingDeviceCommand::TabReceived { payload, .. } => {
+                    payload.entries[0].url.as_str()
+                }
+                command => panic!("Unexpected command {:?}", command),
+            })
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/0",
+                "https://example.com/1",
+                "https://example.com/2"
+            ]
+        );
+    }
+}
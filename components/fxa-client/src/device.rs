@@ -6,16 +6,19 @@ pub use crate::http_client::{
     DeviceLocation as Location, DeviceType as Type, GetDeviceResponse as Device, PushSubscription,
 };
 use crate::{
-    commands,
+    commands::{DeviceCommand, MAX_COMMAND_PAYLOAD_SIZE},
     error::*,
     http_client::{
         CommandData, DeviceUpdateRequest, DeviceUpdateRequestBuilder, PendingCommand,
         UpdateDeviceResponse,
     },
-    util, CachedResponse, FirefoxAccount, IncomingDeviceCommand,
+    scopes, util, CachedResponse, FirefoxAccount, IncomingDeviceCommand,
 };
 use serde_derive::*;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 // An devices response is considered fresh for `DEVICES_FRESHNESS_THRESHOLD` ms.
 const DEVICES_FRESHNESS_THRESHOLD: u64 = 60_000; // 1 minute
@@ -62,16 +65,13 @@ impl FirefoxAccount {
         let mut capabilities_set = HashSet::new();
         let mut commands = HashMap::new();
         for capability in capabilities {
-            match capability {
-                Capability::SendTab => {
-                    let send_tab_command = self.generate_send_tab_command_data()?;
-                    commands.insert(
-                        commands::send_tab::COMMAND_NAME.to_owned(),
-                        send_tab_command.to_owned(),
-                    );
-                    capabilities_set.insert(Capability::SendTab);
-                }
-            }
+            let command = self
+                .commands
+                .find_by_capability(capability)
+                .ok_or_else(|| ErrorKind::IllegalState("No command for this capability."))?;
+            let command_data = self.generate_command_data(&*command)?;
+            commands.insert(command.name().to_owned(), command_data);
+            capabilities_set.insert(capability.clone());
         }
         // Remember what capabilities we've registered, so we don't register the same ones again.
         // We write this to internal state before we've actually written the new device record,
@@ -80,6 +80,31 @@ impl FirefoxAccount {
         Ok(commands)
     }
 
+    /// Generate the data for `command` to be registered with the server,
+    /// creating the command's keys if we don't have any yet.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    fn generate_command_data(&mut self, command: &dyn DeviceCommand) -> Result<String> {
+        let keys = command.keys();
+        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
+        if let Some(private_keys) = self.state.commands_data.get(command.name()) {
+            match keys.command_data(private_keys, oldsync_key) {
+                Ok(command_data) => return Ok(command_data),
+                Err(e) => log::error!(
+                    "Could not use the {} keys ({}). Re-creating them.",
+                    command.name(),
+                    e
+                ),
+            }
+        }
+        let private_keys = keys.generate()?;
+        let command_data = keys.command_data(&private_keys, oldsync_key)?;
+        self.state
+            .commands_data
+            .insert(command.name().to_owned(), private_keys);
+        Ok(command_data)
+    }
+
     /// Initalizes our own device, most of the time this will be called right after logging-in
     /// for the first time.
     ///
@@ -103,9 +128,9 @@ impl FirefoxAccount {
 
     /// Register a set of device capabilities against the current device.
     ///
-    /// As the only capability is Send Tab now, its command is registered with the server.
+    /// The command for each capability is registered with the server.
     /// Don't forget to also call this if the Sync Keys change as they
-    /// encrypt the Send Tab command data.
+    /// encrypt the command data.
     ///
     /// **💾 This method alters the persisted account state.**
     pub fn ensure_capabilities(&mut self, capabilities: &[Capability]) -> Result<()> {
@@ -143,6 +168,61 @@ impl FirefoxAccount {
         Ok(())
     }
 
+    /// Add a device command to the ones this account knows about, replacing
+    /// any command with the same name. This lets the device register the
+    /// command by passing its capability to `initialize_device` or
+    /// `ensure_capabilities`, invoke it with `send_device_command`, and
+    /// handle it when another device invokes it.
+    pub fn register_device_command(&mut self, command: Arc<dyn DeviceCommand>) {
+        self.commands.register(command);
+    }
+
+    /// Invoke the registered command named `command_name` on another device
+    /// designated by its device ID, with a JSON `payload`.
+    pub fn send_device_command(
+        &mut self,
+        command_name: &str,
+        target_device_id: &str,
+        payload: &serde_json::Value,
+    ) -> Result<()> {
+        let command = self
+            .commands
+            .find_by_name(command_name)
+            .ok_or_else(|| ErrorKind::UnknownCommand(command_name.to_owned()))?;
+        self.invoke_device_command(&*command, target_device_id, payload)
+    }
+
+    /// Invoke `command` on another device designated by its device ID,
+    /// encrypting `payload` with the keys that device advertised. Fails with
+    /// `CommandPayloadTooLarge`, without sending anything, if the encrypted
+    /// payload would be bigger than `MAX_COMMAND_PAYLOAD_SIZE`.
+    pub(crate) fn invoke_device_command<T: serde::Serialize>(
+        &mut self,
+        command: &dyn DeviceCommand,
+        target_device_id: &str,
+        payload: &T,
+    ) -> Result<()> {
+        let cleartext = serde_json::to_vec(payload)?;
+        // Encrypting only makes the payload bigger, so we can reject one
+        // that's already too big before fetching the target's keys.
+        check_payload_size(cleartext.len())?;
+        let devices = self.get_devices(false)?;
+        let target = devices
+            .iter()
+            .find(|d| d.id == target_device_id)
+            .ok_or_else(|| ErrorKind::UnknownTargetDevice(target_device_id.to_owned()))?;
+        let command_data = target
+            .available_commands
+            .get(command.name())
+            .ok_or_else(|| ErrorKind::UnsupportedCommand(command.name().to_owned()))?;
+        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
+        let command_payload = command
+            .keys()
+            .encrypt(&cleartext, command_data, oldsync_key)?;
+        check_payload_size(command_payload.to_string().len())?;
+        self.invoke_command(command.name(), target, &command_payload)
+    }
+
     pub(crate) fn invoke_command(
         &self,
         command: &str,
@@ -171,17 +251,16 @@ impl FirefoxAccount {
     }
 
     /// Retrieve and parse a specific command designated by its index.
+    /// A single command can result in more than one incoming command, like
+    /// when several tabs are sent together.
     ///
     /// **💾 This method alters the persisted account state.**
-    pub fn fetch_device_command(&mut self, index: u64) -> Result<IncomingDeviceCommand> {
-        let mut device_commands = self.fetch_and_parse_commands(index, Some(1))?;
-        let device_command = device_commands
-            .pop()
-            .ok_or_else(|| ErrorKind::IllegalState("Index fetch came out empty."))?;
-        if !device_commands.is_empty() {
-            log::warn!("Index fetch resulted in more than 1 element");
+    pub fn fetch_device_command(&mut self, index: u64) -> Result<Vec<IncomingDeviceCommand>> {
+        let device_commands = self.fetch_and_parse_commands(index, Some(1))?;
+        if device_commands.is_empty() {
+            return Err(ErrorKind::IllegalState("Index fetch came out empty.").into());
         }
-        Ok(device_command)
+        Ok(device_commands)
     }
 
     fn fetch_and_parse_commands(
@@ -209,11 +288,11 @@ impl FirefoxAccount {
         let devices = self.get_devices(false)?;
         let parsed_commands = messages
             .into_iter()
-            .filter_map(|msg| match self.parse_command(msg.data, &devices) {
-                Ok(device_command) => Some(device_command),
+            .flat_map(|msg| match self.parse_command(msg.data, &devices) {
+                Ok(device_commands) => device_commands,
                 Err(e) => {
                     log::error!("Error while processing command: {}", e);
-                    None
+                    Vec::new()
                 }
             })
             .collect();
@@ -224,18 +303,69 @@ impl FirefoxAccount {
         &mut self,
         command_data: CommandData,
         devices: &[Device],
-    ) -> Result<IncomingDeviceCommand> {
+    ) -> Result<Vec<IncomingDeviceCommand>> {
         let sender = command_data
             .sender
             .and_then(|s| devices.iter().find(|i| i.id == s).cloned());
-        match command_data.command.as_str() {
-            commands::send_tab::COMMAND_NAME => {
-                self.handle_send_tab_command(sender, command_data.payload)
+        match self.commands.find_by_name(&command_data.command) {
+            Some(command) => self.handle_device_command(&*command, sender, command_data.payload),
+            None => Err(ErrorKind::UnknownCommand(command_data.command).into()),
+        }
+    }
+
+    fn handle_device_command(
+        &mut self,
+        command: &dyn DeviceCommand,
+        sender: Option<Device>,
+        payload: serde_json::Value,
+    ) -> Result<Vec<IncomingDeviceCommand>> {
+        let private_keys = self
+            .state
+            .commands_data
+            .get(command.name())
+            .ok_or_else(|| {
+                ErrorKind::IllegalState(
+                    "Cannot find the command keys. Has initialize_device been called before?",
+                )
+            })?;
+        match command.keys().decrypt(payload, private_keys) {
+            Ok(cleartext) => command.handle(sender, &cleartext),
+            Err(e) => {
+                log::error!(
+                    "Could not decrypt {} payload. Diagnosing then resetting its keys.",
+                    command.name()
+                );
+                match self.diagnose_remote_keys(command) {
+                    Ok(_) => log::error!("Could not find the cause of the keys issue."),
+                    Err(e) => log::error!("{}", e),
+                };
+                // Reset the keys for this command.
+                self.state.commands_data.remove(command.name());
+                self.reregister_current_capabilities()?;
+                Err(e)
             }
-            _ => Err(ErrorKind::UnknownCommand(command_data.command).into()),
         }
     }
 
+    fn diagnose_remote_keys(&mut self, command: &dyn DeviceCommand) -> Result<()> {
+        let own_device = self
+            .get_current_device()?
+            .ok_or_else(|| ErrorKind::CommandDiagnosisError("No remote device."))?;
+        let command_data = own_device
+            .available_commands
+            .get(command.name())
+            .ok_or_else(|| ErrorKind::CommandDiagnosisError("No remote command."))?;
+        let private_keys = self
+            .state
+            .commands_data
+            .get(command.name())
+            .ok_or_else(|| ErrorKind::CommandDiagnosisError("No local keys."))?;
+        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
+        command
+            .keys()
+            .diagnose(private_keys, command_data, oldsync_key)
+    }
+
     pub fn set_device_name(&mut self, name: &str) -> Result<UpdateDeviceResponse> {
         let update = DeviceUpdateRequestBuilder::new().display_name(name).build();
         self.update_device(update)
@@ -340,9 +470,20 @@ impl FirefoxAccount {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Capability {
     SendTab,
+    CloseTabs,
+    /// A command registered with `register_device_command`, designated by
+    /// its name.
+    Command(String),
+}
+
+fn check_payload_size(size: usize) -> Result<()> {
+    if size > MAX_COMMAND_PAYLOAD_SIZE {
+        return Err(ErrorKind::CommandPayloadTooLarge(size, MAX_COMMAND_PAYLOAD_SIZE).into());
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -60,8 +60,8 @@ pub enum ErrorKind {
     #[error("Unknown command: {0}")]
     UnknownCommand(String),
 
-    #[error("Send Tab diagnosis error: {0}")]
-    SendTabDiagnosisError(&'static str),
+    #[error("Command diagnosis error: {0}")]
+    CommandDiagnosisError(&'static str),
 
     #[error("Empty names")]
     EmptyOAuthScopeNames,
@@ -103,7 +103,10 @@ pub enum ErrorKind {
     HmacMismatch,
 
     #[error("Unsupported command: {0}")]
-    UnsupportedCommand(&'static str),
+    UnsupportedCommand(String),
+
+    #[error("Command payload is {0} bytes, but the limit is {1}")]
+    CommandPayloadTooLarge(usize, usize),
 
     #[error("Remote server error: '{code}' '{errno}' '{error}' '{message}' '{info}'")]
     RemoteError {
@@ -106,6 +106,7 @@ impl From<Profile> for msg_types::Profile {
 fn command_to_capability(command: &str) -> Option<msg_types::device::Capability> {
     match command {
         commands::send_tab::COMMAND_NAME => Some(msg_types::device::Capability::SendTab),
+        commands::close_tabs::COMMAND_NAME => Some(msg_types::device::Capability::CloseTabs),
         _ => None,
     }
 }
@@ -117,6 +118,7 @@ impl From<Device> for msg_types::Device {
             .keys()
             .filter_map(|c| command_to_capability(c).map(|cc| cc as i32))
             .collect();
+        let commands = d.available_commands.keys().cloned().collect();
         Self {
             id: d.common.id,
             display_name: d.common.display_name,
@@ -126,6 +128,7 @@ impl From<Device> for msg_types::Device {
             is_current_device: d.is_current_device,
             last_access_time: d.last_access_time,
             capabilities,
+            commands,
         }
     }
 }
@@ -222,6 +225,44 @@ impl From<IncomingDeviceCommand> for msg_types::IncomingDeviceCommand {
                     },
                 )),
             },
+            IncomingDeviceCommand::CloseTabsRequested { sender, payload } => {
+                use msg_types::incoming_device_command::{
+                    CloseTabsData, Data, IncomingDeviceCommandType,
+                };
+                Self {
+                    r#type: IncomingDeviceCommandType::CloseTabsRequested as i32,
+                    data: Some(Data::CloseTabsRequestedData(CloseTabsData {
+                        from: sender.map(Into::into),
+                        urls: payload.urls,
+                    })),
+                }
+            }
+            IncomingDeviceCommand::TabsClosed { sender, payload } => Self {
+                r#type: msg_types::incoming_device_command::IncomingDeviceCommandType::TabsClosed
+                    as i32,
+                data: Some(msg_types::incoming_device_command::Data::TabsClosedData(
+                    msg_types::incoming_device_command::CloseTabsReplyData {
+                        from: sender.map(Into::into),
+                        closed: payload.closed,
+                        not_found: payload.not_found,
+                    },
+                )),
+            },
+            IncomingDeviceCommand::Custom {
+                sender,
+                command,
+                payload,
+            } => Self {
+                r#type: msg_types::incoming_device_command::IncomingDeviceCommandType::Custom
+                    as i32,
+                data: Some(msg_types::incoming_device_command::Data::CustomData(
+                    msg_types::incoming_device_command::CustomData {
+                        from: sender.map(Into::into),
+                        command,
+                        payload: payload.to_string(),
+                    },
+                )),
+            },
         }
     }
 }
@@ -241,6 +282,7 @@ impl From<msg_types::device::Capability> for DeviceCapability {
     fn from(cap: msg_types::device::Capability) -> Self {
         match cap {
             msg_types::device::Capability::SendTab => DeviceCapability::SendTab,
+            msg_types::device::Capability::CloseTabs => DeviceCapability::CloseTabs,
         }
     }
 }
@@ -262,10 +304,72 @@ impl msg_types::Capabilities {
         self.capability
             .iter()
             .map(|c| msg_types::device::Capability::from_i32(*c).unwrap().into())
+            .chain(
+                self.commands
+                    .iter()
+                    .map(|name| DeviceCapability::Command(name.clone())),
+            )
             .collect()
     }
 }
 
+impl msg_types::TabsToSend {
+    /// # Safety
+    /// Deref pointer thus unsafe
+    pub unsafe fn from_protobuf_ptr(ptr: *const u8, len: i32) -> Vec<send_tab::SendTabPayload> {
+        let buffer = get_buffer(ptr, len);
+        let tabs: Result<msg_types::TabsToSend, _> = prost::Message::decode(buffer);
+        tabs.map(|t| t.tabs.into_iter().map(Into::into).collect())
+            .unwrap_or_else(|_| vec![])
+    }
+}
+
+impl From<msg_types::tabs_to_send::Tab> for send_tab::SendTabPayload {
+    fn from(tab: msg_types::tabs_to_send::Tab) -> Self {
+        Self {
+            entries: tab
+                .entries
+                .into_iter()
+                .map(|entry| send_tab::TabHistoryEntry {
+                    title: entry.title,
+                    url: entry.url,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<send_tab::SendTabsResult> for msg_types::SendTabsResult {
+    fn from(result: send_tab::SendTabsResult) -> Self {
+        Self {
+            delivered: result.delivered.into_iter().map(|i| i as u32).collect(),
+            failed: result.failed.into_iter().map(|i| i as u32).collect(),
+        }
+    }
+}
+
+impl msg_types::CloseTabsUrls {
+    /// # Safety
+    /// Deref pointer thus unsafe
+    pub unsafe fn from_protobuf_ptr(ptr: *const u8, len: i32) -> Vec<String> {
+        let buffer = get_buffer(ptr, len);
+        let urls: Result<msg_types::CloseTabsUrls, _> = prost::Message::decode(buffer);
+        urls.map(|u| u.urls).unwrap_or_else(|_| vec![])
+    }
+}
+
+impl msg_types::CloseTabsReply {
+    /// # Safety
+    /// Deref pointer thus unsafe
+    pub unsafe fn from_protobuf_ptr(ptr: *const u8, len: i32) -> (Vec<String>, Vec<String>) {
+        let buffer = get_buffer(ptr, len);
+        let reply: Result<msg_types::CloseTabsReply, _> = prost::Message::decode(buffer);
+        reply
+            .map(|r| (r.closed, r.not_found))
+            .unwrap_or_else(|_| (vec![], vec![]))
+    }
+}
+
 unsafe fn get_buffer<'a>(data: *const u8, len: i32) -> &'a [u8] {
     assert!(len >= 0, "Bad buffer len: {}", len);
     if len == 0 {
@@ -285,6 +389,8 @@ implement_into_ffi_by_delegation!(IntrospectInfo, msg_types::IntrospectInfo);
 implement_into_ffi_by_protobuf!(msg_types::Device);
 implement_into_ffi_by_delegation!(Device, msg_types::Device);
 implement_into_ffi_by_protobuf!(msg_types::Devices);
+implement_into_ffi_by_protobuf!(msg_types::SendTabsResult);
+implement_into_ffi_by_delegation!(send_tab::SendTabsResult, msg_types::SendTabsResult);
 implement_into_ffi_by_delegation!(AccountEvent, msg_types::AccountEvent);
 implement_into_ffi_by_protobuf!(msg_types::AccountEvent);
 implement_into_ffi_by_protobuf!(msg_types::AccountEvents);
@@ -6,10 +6,12 @@
 #![warn(rust_2018_idioms)]
 
 use crate::{
-    commands::send_tab::SendTabPayload,
+    commands::{
+        close_tabs::{CloseTabsPayload, CloseTabsReply},
+        send_tab::SendTabPayload,
+    },
     device::Device,
     oauth::{OAuthFlow, OAUTH_WEBCHANNEL_REDIRECT},
-    scoped_keys::ScopedKey,
     state_persistence::State,
 };
 pub use crate::{
@@ -18,6 +20,7 @@ pub use crate::{
     oauth::IntrospectInfo,
     oauth::{AccessTokenInfo, RefreshToken},
     profile::Profile,
+    scoped_keys::ScopedKey,
 };
 use serde_derive::*;
 use std::{
@@ -26,7 +29,8 @@ use std::{
 };
 use url::Url;
 
-mod commands;
+pub mod close_tabs;
+pub mod commands;
 mod config;
 pub mod device;
 pub mod error;
@@ -40,6 +44,8 @@ pub mod msg_types {
     include!("mozilla.appservices.fxaclient.protobuf.rs");
 }
 mod http_client;
+#[cfg(feature = "integration_test")]
+pub mod mock_server;
 mod oauth;
 mod profile;
 mod push;
@@ -66,16 +72,27 @@ pub struct FirefoxAccount {
     flow_store: HashMap<String, OAuthFlow>,
     attached_clients_cache: Option<CachedResponse<Vec<http_client::GetAttachedClientResponse>>>,
     devices_cache: Option<CachedResponse<Vec<http_client::GetDeviceResponse>>>,
+    commands: commands::CommandRegistry,
 }
 
 impl FirefoxAccount {
     fn from_state(state: State) -> Self {
+        // Custom commands we've registered with the server can be invoked on
+        // us before the application registers them again, so we handle them
+        // with the default handler until then.
+        let mut registry = commands::CommandRegistry::default();
+        for capability in &state.device_capabilities {
+            if let device::Capability::Command(name) = capability {
+                registry.register(Arc::new(commands::custom::CustomCommand::new(name)));
+            }
+        }
         Self {
             client: Arc::new(http_client::Client::new()),
             state,
             flow_store: HashMap::new(),
             attached_clients_cache: None,
             devices_cache: None,
+            commands: registry,
         }
     }
 
@@ -290,6 +307,21 @@ pub enum IncomingDeviceCommand {
         sender: Option<Device>,
         payload: SendTabPayload,
     },
+    CloseTabsRequested {
+        sender: Option<Device>,
+        payload: CloseTabsPayload,
+    },
+    TabsClosed {
+        sender: Option<Device>,
+        payload: CloseTabsReply,
+    },
+    /// A command registered with `FirefoxAccount::register_device_command`
+    /// that uses the default handler.
+    Custom {
+        sender: Option<Device>,
+        command: String,
+        payload: serde_json::Value,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -0,0 +1,297 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The device, device command and attached client endpoints.
+
+use super::{
+    bad_request, error, parse_body,
+    state::{random_hex, Command, Device, ServerState},
+    HandlerResult, MockResponse, CONTENT_URL, ERRNO_UNAVAILABLE_DEVICE_COMMAND,
+    ERRNO_UNKNOWN_DEVICE,
+};
+use crate::{commands::MAX_COMMAND_PAYLOAD_SIZE, http_client::PushSubscription, util};
+use rc_crypto::digest;
+use serde_derive::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use viaduct::Request;
+
+#[derive(Deserialize)]
+struct DestroyDeviceRequest {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct InvokeCommandRequest {
+    command: String,
+    target: String,
+    payload: Value,
+}
+
+impl ServerState {
+    pub(super) fn devices(&mut self, request: &Request) -> HandlerResult {
+        let refresh_token = self.refresh_token_for(request)?;
+        let uid = self.refresh_tokens[&refresh_token].uid.clone();
+        let devices = self
+            .account(&uid)
+            .devices
+            .values()
+            .map(|device| device.to_json(Some(&refresh_token)))
+            .collect::<Vec<_>>();
+        Ok(MockResponse::json(200, &devices))
+    }
+
+    /// Registers a device for the refresh token if it doesn't have one yet,
+    /// and updates the fields in the request. Fields that are `null` are
+    /// cleared, and missing fields are left alone.
+    pub(super) fn update_device(&mut self, request: &Request) -> HandlerResult {
+        let refresh_token = self.refresh_token_for(request)?;
+        let uid = self.refresh_tokens[&refresh_token].uid.clone();
+        let body: Value = parse_body(request)?;
+        let existing_id = self
+            .device_for(&refresh_token)
+            .map(|device| device.id.clone());
+        let account = self.account(&uid);
+        let is_new = existing_id.is_none();
+        let id = match existing_id {
+            Some(id) => id,
+            None => {
+                let id = random_hex(16).map_err(|e| bad_request(&e.to_string()))?;
+                account.devices.insert(
+                    id.clone(),
+                    Device {
+                        id: id.clone(),
+                        refresh_token: refresh_token.clone(),
+                        name: None,
+                        device_type: None,
+                        push_subscription: None,
+                        available_commands: HashMap::new(),
+                        last_access_time: util::now(),
+                        push_messages: Vec::new(),
+                    },
+                );
+                id
+            }
+        };
+
+        let device = account.devices.get_mut(&id).unwrap();
+        device.last_access_time = util::now();
+        if let Some(name) = body.get("name") {
+            device.name = name.as_str().map(ToOwned::to_owned);
+        }
+        if let Some(device_type) = body.get("type") {
+            device.device_type = device_type.as_str().map(ToOwned::to_owned);
+        }
+        if body.get("pushCallback").is_some() {
+            // Like the real server, the endpoint and keys are set together.
+            device.push_subscription =
+                serde_json::from_value::<PushSubscription>(body.clone()).ok();
+        }
+        if let Some(commands) = body.get("availableCommands") {
+            device.available_commands =
+                serde_json::from_value::<Option<HashMap<String, String>>>(commands.clone())
+                    .map_err(|e| bad_request(&e.to_string()))?
+                    .unwrap_or_default();
+        }
+        let response = device.to_json(None);
+        if is_new {
+            let name = device.name.clone().unwrap_or_default();
+            account.notify(
+                Some(&id),
+                "fxaccounts:device_connected",
+                Some(json!({ "deviceName": name })),
+            );
+        }
+        Ok(MockResponse::json(200, &response))
+    }
+
+    pub(super) fn destroy_device(&mut self, request: &Request) -> HandlerResult {
+        let refresh_token = self.refresh_token_for(request)?;
+        let uid = self.refresh_tokens[&refresh_token].uid.clone();
+        let body: DestroyDeviceRequest = parse_body(request)?;
+        let device_refresh_token = self
+            .account(&uid)
+            .devices
+            .get(&body.id)
+            .map(|device| device.refresh_token.clone())
+            .ok_or_else(unknown_device)?;
+        self.destroy_refresh_token(&device_refresh_token);
+        Ok(MockResponse::json(200, &json!({})))
+    }
+
+    pub(super) fn pending_commands(&mut self, request: &Request) -> HandlerResult {
+        let refresh_token = self.refresh_token_for(request)?;
+        let uid = self.refresh_tokens[&refresh_token].uid.clone();
+        let device_id = self
+            .device_for(&refresh_token)
+            .map(|device| device.id.clone())
+            .ok_or_else(unknown_device)?;
+        let query = request
+            .url
+            .query_pairs()
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        let index = query
+            .get("index")
+            .and_then(|index| index.parse::<u64>().ok())
+            .ok_or_else(|| bad_request("Missing or invalid `index`"))?;
+        let limit = match query.get("limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| bad_request("Invalid `limit`"))?,
+            None => usize::max_value(),
+        };
+        let pending = self
+            .account(&uid)
+            .commands
+            .iter()
+            .filter(|command| command.target == device_id && command.index >= index)
+            .collect::<Vec<_>>();
+        let messages = pending
+            .iter()
+            .take(limit)
+            .map(|command| {
+                json!({
+                    "index": command.index,
+                    "data": {
+                        "command": command.command,
+                        "payload": command.payload,
+                        "sender": command.sender,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        let last_index = pending
+            .iter()
+            .take(limit)
+            .last()
+            .map_or(index, |command| command.index);
+        Ok(MockResponse::json(
+            200,
+            &json!({
+                "index": last_index,
+                "last": pending.len() <= limit,
+                "messages": messages,
+            }),
+        ))
+    }
+
+    pub(super) fn invoke_command(&mut self, request: &Request) -> HandlerResult {
+        let refresh_token = self.refresh_token_for(request)?;
+        let uid = self.refresh_tokens[&refresh_token].uid.clone();
+        let sender = self
+            .device_for(&refresh_token)
+            .map(|device| device.id.clone());
+        let body: InvokeCommandRequest = parse_body(request)?;
+        if body.payload.to_string().len() > MAX_COMMAND_PAYLOAD_SIZE {
+            return Err(bad_request("Command payload too large"));
+        }
+        let account = self.account(&uid);
+        let target = account
+            .devices
+            .get(&body.target)
+            .ok_or_else(unknown_device)?;
+        if !target.available_commands.contains_key(&body.command) {
+            return Err(error(
+                400,
+                ERRNO_UNAVAILABLE_DEVICE_COMMAND,
+                "Device is unable to handle the requested command",
+            ));
+        }
+        let index = account
+            .commands
+            .last()
+            .map_or(1, |command| command.index + 1);
+        account.commands.push(Command {
+            index,
+            target: body.target.clone(),
+            command: body.command.clone(),
+            payload: body.payload,
+            sender: sender.clone(),
+        });
+        let message = json!({
+            "version": 1,
+            "command": "fxaccounts:command_received",
+            "data": {
+                "command": body.command,
+                "index": index,
+                "sender": sender.unwrap_or_default(),
+                "url": format!(
+                    "{}/auth/v1/account/device/commands?index={}&limit=1",
+                    CONTENT_URL, index
+                ),
+            },
+        });
+        let target = account.devices.get_mut(&body.target).unwrap();
+        if target.push_subscription.is_some() {
+            target.push_messages.push(message.to_string());
+        }
+        Ok(MockResponse::json(200, &json!({})))
+    }
+
+    /// Lists the account's sessions and refresh tokens, with the devices
+    /// registered for them.
+    pub(super) fn attached_clients(&mut self, request: &Request) -> HandlerResult {
+        let (session_id, uid) = self.session_for(request)?;
+        let sessions = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.uid == uid)
+            .map(|(id, session)| {
+                json!({
+                    "clientId": null,
+                    "sessionTokenId": id,
+                    "refreshTokenId": null,
+                    "deviceId": null,
+                    "deviceType": null,
+                    "isCurrentSession": *id == session_id,
+                    "name": null,
+                    "createdTime": session.created_at,
+                    "lastAccessTime": session.last_access_time,
+                    "scope": null,
+                    "userAgent": "",
+                    "os": null,
+                })
+            });
+        let account = &self.accounts[&uid];
+        let refresh_tokens = self
+            .refresh_tokens
+            .iter()
+            .filter(|(_, token)| token.uid == uid)
+            .map(|(id, token)| {
+                let device = account
+                    .devices
+                    .values()
+                    .find(|device| device.refresh_token == *id);
+                json!({
+                    "clientId": token.client_id,
+                    "sessionTokenId": null,
+                    "refreshTokenId": refresh_token_id(id),
+                    "deviceId": device.map(|device| &device.id),
+                    "deviceType": device.and_then(|device| device.device_type.as_ref()),
+                    "isCurrentSession": false,
+                    "name": device.and_then(|device| device.name.as_ref()),
+                    "createdTime": token.created_at,
+                    "lastAccessTime": token.last_access_time,
+                    "scope": token.scopes,
+                    "userAgent": "",
+                    "os": null,
+                })
+            });
+        let clients = sessions.chain(refresh_tokens).collect::<Vec<_>>();
+        Ok(MockResponse::json(200, &clients))
+    }
+}
+
+/// Like the real server, we identify refresh tokens by their hashes, so that
+/// we don't leak them to other clients.
+fn refresh_token_id(token: &str) -> Option<String> {
+    digest::digest(&digest::SHA256, token.as_bytes())
+        .ok()
+        .map(hex::encode)
+}
+
+fn unknown_device() -> MockResponse {
+    error(400, ERRNO_UNKNOWN_DEVICE, "Unknown device")
+}
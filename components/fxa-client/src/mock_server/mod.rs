@@ -0,0 +1,263 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An in-process stand-in for the Firefox Accounts servers, so that tests can
+//! drive `FirefoxAccount` flows end to end without network access or a live
+//! FxA stack. It's installed as viaduct's backend, and answers requests for
+//! `CONTENT_URL`, which serves the auth, OAuth and profile APIs. Accounts,
+//! sessions, tokens, devices and commands are kept in memory, so several
+//! `FirefoxAccount`s signed in to the same mock account act like separate
+//! devices.
+//!
+//! Tests can't click through the web content that a user would sign in with,
+//! so `MockServer` has methods that stand in for it: `authorize` completes a
+//! flow started with `begin_oauth_flow`, and `begin_pairing` and
+//! `approve_pairing` play the pairing authority for a flow started with
+//! `begin_pairing_flow`.
+
+use serde_json::json;
+use std::sync::{Mutex, Once};
+use url::Url;
+use viaduct::{header_names, Backend, Headers, Method, Request, Response};
+
+mod devices;
+mod oauth;
+mod state;
+
+use state::ServerState;
+
+pub const CONTENT_URL: &str = "https://accounts.fxa.mock";
+const CONTENT_HOST: &str = "accounts.fxa.mock";
+const TOKEN_SERVER_URL: &str = "https://token.fxa.mock";
+
+// Error numbers from https://github.com/mozilla/fxa/blob/main/packages/fxa-auth-server/docs/api.md#defined-errors.
+const ERRNO_INVALID_PARAMETER: u64 = 107;
+const ERRNO_INVALID_TOKEN: u64 = 110;
+const ERRNO_UNKNOWN_DEVICE: u64 = 123;
+const ERRNO_UNAVAILABLE_DEVICE_COMMAND: u64 = 157;
+const ERRNO_UNEXPECTED: u64 = 999;
+
+lazy_static::lazy_static! {
+    static ref SERVER: MockServer = MockServer::default();
+}
+
+static INIT_BACKEND: Once = Once::new();
+
+/// Sends all requests to the mock server, and returns it. This can be called
+/// any number of times, but viaduct only allows one backend per process, so
+/// the tests that use it can't use another backend.
+pub fn use_mock_backend() -> &'static MockServer {
+    INIT_BACKEND.call_once(|| {
+        viaduct::set_backend(&*SERVER).expect("Backend already set");
+    });
+    &SERVER
+}
+
+#[derive(Default)]
+pub struct MockServer {
+    state: Mutex<ServerState>,
+}
+
+impl MockServer {
+    /// Creates a verified account with new keys, and returns its uid. The
+    /// server is shared by all tests, so each test should use its own email.
+    pub fn create_account(&self, email: &str) -> anyhow::Result<String> {
+        self.state.lock().unwrap().create_account(email)
+    }
+
+    /// Signs `uid` in to the OAuth flow at `url`, which comes from
+    /// `begin_oauth_flow`, and returns the URL that the content server would
+    /// redirect to. Its `code` and `state` query parameters complete the flow.
+    pub fn authorize(&self, uid: &str, url: &str) -> anyhow::Result<String> {
+        self.state.lock().unwrap().authorize(uid, &Url::parse(url)?)
+    }
+
+    /// Opens a pairing channel for the account that owns `session_token`, and
+    /// returns the URL that the authority would show in a QR code.
+    pub fn begin_pairing(&self, session_token: &str) -> anyhow::Result<String> {
+        self.state.lock().unwrap().begin_pairing(session_token)
+    }
+
+    /// Approves the pairing flow at `url`, which comes from
+    /// `begin_pairing_flow`, on behalf of the authority, and returns the URL
+    /// that the supplicant would be redirected to.
+    pub fn approve_pairing(&self, url: &str) -> anyhow::Result<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .approve_pairing(&Url::parse(url)?)
+    }
+
+    /// Changes an account's display name, and tells its devices.
+    pub fn set_display_name(&self, uid: &str, display_name: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        let account = state.account(uid);
+        account.display_name = display_name.map(ToOwned::to_owned);
+        account.profile_version += 1;
+        account.notify(None, "fxaccounts:profile_updated", None);
+    }
+
+    /// Returns an account's Sync key, so that tests can check that clients
+    /// got the right one.
+    pub fn sync_key(&self, uid: &str) -> Vec<u8> {
+        self.state.lock().unwrap().account(uid).k_sync.clone()
+    }
+
+    /// Removes and returns the push messages that the server would have sent
+    /// to a device, oldest first. These can be passed to
+    /// `handle_push_message`.
+    pub fn take_push_messages(&self, device_id: &str) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .accounts
+            .values_mut()
+            .find_map(|account| account.devices.get_mut(device_id))
+            .map(|device| std::mem::take(&mut device.push_messages))
+            .unwrap_or_default()
+    }
+
+    fn handle(&self, request: &Request) -> MockResponse {
+        let mut state = self.state.lock().unwrap();
+        let response = if request.url.host_str() == Some(CONTENT_HOST) {
+            state.route(request).unwrap_or_else(|response| response)
+        } else {
+            not_found()
+        };
+        log::trace!(
+            "Mock server: {} {} => {}",
+            request.method,
+            request.url,
+            response.status
+        );
+        response
+    }
+}
+
+impl ServerState {
+    fn route(&mut self, request: &Request) -> HandlerResult {
+        match (request.method, request.url.path()) {
+            (Method::Get, "/.well-known/fxa-client-configuration") => Ok(MockResponse::json(
+                200,
+                &json!({
+                    "auth_server_base_url": format!("{}/auth", CONTENT_URL),
+                    "oauth_server_base_url": format!("{}/oauth", CONTENT_URL),
+                    "profile_server_base_url": format!("{}/profile", CONTENT_URL),
+                    "sync_tokenserver_base_url": TOKEN_SERVER_URL,
+                }),
+            )),
+            (Method::Get, "/.well-known/openid-configuration") => Ok(MockResponse::json(
+                200,
+                &json!({
+                    "authorization_endpoint": format!("{}/authorization", CONTENT_URL),
+                    "introspection_endpoint": format!("{}/oauth/v1/introspect", CONTENT_URL),
+                    "issuer": CONTENT_URL,
+                    "jwks_uri": format!("{}/oauth/v1/jwks", CONTENT_URL),
+                    "token_endpoint": format!("{}/auth/v1/oauth/token", CONTENT_URL),
+                    "userinfo_endpoint": format!("{}/profile/v1/profile", CONTENT_URL),
+                }),
+            )),
+            (Method::Post, "/auth/v1/oauth/token") => self.token(request),
+            (Method::Post, "/auth/v1/oauth/authorization") => self.authorization(request),
+            (Method::Post, "/auth/v1/session/duplicate") => self.duplicate_session(request),
+            (Method::Post, "/auth/v1/account/scoped-key-data") => self.scoped_key_data(request),
+            (Method::Get, "/auth/v1/account/attached_clients") => self.attached_clients(request),
+            (Method::Get, "/auth/v1/account/devices") => self.devices(request),
+            (Method::Post, "/auth/v1/account/device") => self.update_device(request),
+            (Method::Post, "/auth/v1/account/device/destroy") => self.destroy_device(request),
+            (Method::Get, "/auth/v1/account/device/commands") => self.pending_commands(request),
+            (Method::Post, "/auth/v1/account/devices/invoke_command") => {
+                self.invoke_command(request)
+            }
+            (Method::Post, "/oauth/v1/destroy") => self.destroy(request),
+            (Method::Post, "/oauth/v1/introspect") => self.introspect(request),
+            (Method::Get, "/profile/v1/profile") => self.profile(request),
+            _ => Err(not_found()),
+        }
+    }
+}
+
+impl Backend for MockServer {
+    fn send(&self, request: Request) -> Result<Response, viaduct::Error> {
+        viaduct::note_backend("fxa-client mock server");
+        let response = self.handle(&request);
+        Ok(Response {
+            request_method: request.method,
+            url: request.url,
+            status: response.status,
+            headers: response.headers,
+            body: response.body,
+        })
+    }
+}
+
+/// Handlers return errors as `Err`, so that they can use `?`.
+type HandlerResult = Result<MockResponse, MockResponse>;
+
+/// A response from the mock server, before we know which request it
+/// belongs to.
+pub struct MockResponse {
+    status: u16,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    fn json<T: serde::Serialize + ?Sized>(status: u16, body: &T) -> Self {
+        let mut headers = Headers::new();
+        headers
+            .insert(header_names::CONTENT_TYPE, "application/json")
+            .unwrap();
+        MockResponse {
+            status,
+            headers,
+            body: serde_json::to_vec(body).expect("Response bodies should serialize"),
+        }
+    }
+
+    fn not_modified() -> Self {
+        MockResponse {
+            status: 304,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn header(mut self, name: viaduct::HeaderName, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value.into()).unwrap();
+        self
+    }
+}
+
+/// Returns an error in the format that the FxA servers use.
+fn error(status: u16, errno: u64, message: &str) -> MockResponse {
+    let error = match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    MockResponse::json(
+        status,
+        &json!({
+            "code": status,
+            "errno": errno,
+            "error": error,
+            "message": message,
+            "info": "https://github.com/mozilla/fxa/blob/main/packages/fxa-auth-server/docs/api.md#response-format",
+        }),
+    )
+}
+
+fn bad_request(message: &str) -> MockResponse {
+    error(400, ERRNO_INVALID_PARAMETER, message)
+}
+
+fn not_found() -> MockResponse {
+    error(404, ERRNO_UNEXPECTED, "Unknown endpoint")
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(request: &Request) -> Result<T, MockResponse> {
+    let body = request.body.as_deref().unwrap_or_default();
+    serde_json::from_slice(body).map_err(|e| bad_request(&e.to_string()))
+}
@@ -0,0 +1,410 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The OAuth, session and profile endpoints, and the parts of the content
+//! server that sign users in and pair devices.
+
+use super::{
+    bad_request, error, parse_body,
+    state::{
+        random_hex, session_token_id, AccessToken, AuthorizationCode, RefreshToken, ServerState,
+        SESSION_SCOPE,
+    },
+    HandlerResult, MockResponse, CONTENT_URL, ERRNO_INVALID_TOKEN,
+};
+use crate::{oauth::OAUTH_WEBCHANNEL_REDIRECT, scoped_keys::ScopedKeysFlow, scopes, util};
+use anyhow::{anyhow, bail};
+use rc_crypto::digest;
+use serde_derive::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use url::Url;
+use viaduct::{header_names, Request};
+
+/// How long access tokens are valid for, in seconds, unless the client asks
+/// for a different TTL. We don't expire them.
+const ACCESS_TOKEN_TTL: u64 = 3600;
+
+#[derive(Deserialize)]
+#[serde(tag = "grant_type")]
+enum TokenRequest {
+    #[serde(rename = "authorization_code")]
+    UsingCode {
+        client_id: String,
+        code: String,
+        code_verifier: Option<String>,
+    },
+    #[serde(rename = "refresh_token")]
+    UsingRefreshToken {
+        client_id: String,
+        refresh_token: String,
+        scope: Option<String>,
+        ttl: Option<u64>,
+    },
+    #[serde(rename = "fxa-credentials")]
+    UsingSessionToken {
+        client_id: String,
+        scope: String,
+        access_type: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct AuthorizationRequest {
+    client_id: String,
+    scope: String,
+    state: String,
+    access_type: Option<String>,
+    code_challenge: Option<String>,
+    keys_jwe: Option<String>,
+    redirect_uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DestroyRequest {
+    token: Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenOnlyRequest {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct ScopedKeyDataRequest {
+    scope: String,
+}
+
+impl ServerState {
+    pub(super) fn token(&mut self, request: &Request) -> HandlerResult {
+        match parse_body(request)? {
+            TokenRequest::UsingCode {
+                client_id,
+                code,
+                code_verifier,
+            } => {
+                let code = match self.codes.remove(&code) {
+                    Some(code) if code.client_id == client_id => code,
+                    _ => return Err(bad_request("Unknown authorization code")),
+                };
+                if let Some(code_challenge) = &code.code_challenge {
+                    let verifier = code_verifier.unwrap_or_default();
+                    let digest = digest::digest(&digest::SHA256, verifier.as_bytes())
+                        .map_err(|e| bad_request(&e.to_string()))?;
+                    let expected = base64::encode_config(&digest, base64::URL_SAFE_NO_PAD);
+                    if &expected != code_challenge {
+                        return Err(bad_request("Incorrect code verifier"));
+                    }
+                }
+                let offline = code.access_type.as_deref() == Some("offline");
+                let mut response =
+                    self.issue_tokens(&code.uid, &client_id, code.scopes.clone(), offline, None);
+                if let Some(keys_jwe) = code.keys_jwe {
+                    response["keys_jwe"] = json!(keys_jwe);
+                }
+                if code.scopes.iter().any(|scope| scope == SESSION_SCOPE) {
+                    let session_token = self
+                        .create_session(&code.uid)
+                        .map_err(|e| bad_request(&e.to_string()))?;
+                    response["session_token"] = json!(session_token);
+                }
+                Ok(MockResponse::json(200, &response))
+            }
+            TokenRequest::UsingRefreshToken {
+                client_id,
+                refresh_token,
+                scope,
+                ttl,
+            } => {
+                let (uid, granted) = match self.refresh_tokens.get_mut(&refresh_token) {
+                    Some(token) if token.client_id == client_id => {
+                        token.last_access_time = util::now();
+                        (token.uid.clone(), token.scopes.clone())
+                    }
+                    _ => return Err(invalid_grant()),
+                };
+                let scopes = match scope {
+                    Some(scope) => split_scopes(&scope),
+                    None => granted.clone(),
+                };
+                if !scopes.iter().all(|scope| granted.contains(scope)) {
+                    return Err(bad_request("Requested scopes are not allowed"));
+                }
+                let response = self.issue_tokens(&uid, &client_id, scopes, false, ttl);
+                Ok(MockResponse::json(200, &response))
+            }
+            TokenRequest::UsingSessionToken {
+                client_id,
+                scope,
+                access_type,
+            } => {
+                let (_, uid) = self.session_for(request)?;
+                let offline = access_type.as_deref() == Some("offline");
+                let response =
+                    self.issue_tokens(&uid, &client_id, split_scopes(&scope), offline, None);
+                Ok(MockResponse::json(200, &response))
+            }
+        }
+    }
+
+    pub(super) fn authorization(&mut self, request: &Request) -> HandlerResult {
+        let (_, uid) = self.session_for(request)?;
+        let body: AuthorizationRequest = parse_body(request)?;
+        let redirect_uri = match body.redirect_uri {
+            Some(redirect_uri) => redirect_uri,
+            None => format!("{}/oauth/success/{}", CONTENT_URL, body.client_id),
+        };
+        let code = self
+            .create_code(AuthorizationCode {
+                uid,
+                client_id: body.client_id,
+                scopes: split_scopes(&body.scope),
+                access_type: body.access_type,
+                code_challenge: body.code_challenge,
+                keys_jwe: body.keys_jwe,
+            })
+            .map_err(|e| bad_request(&e.to_string()))?;
+        let redirect = redirect_url(&redirect_uri, &code, &body.state)
+            .map_err(|e| bad_request(&e.to_string()))?;
+        Ok(MockResponse::json(
+            200,
+            &json!({
+                "redirect": redirect,
+                "code": code,
+                "state": body.state,
+            }),
+        ))
+    }
+
+    pub(super) fn duplicate_session(&mut self, request: &Request) -> HandlerResult {
+        let (_, uid) = self.session_for(request)?;
+        let session_token = self
+            .create_session(&uid)
+            .map_err(|e| bad_request(&e.to_string()))?;
+        Ok(MockResponse::json(
+            200,
+            &json!({
+                "uid": uid,
+                "sessionToken": session_token,
+                "verified": true,
+                "authAt": util::now_secs(),
+            }),
+        ))
+    }
+
+    pub(super) fn scoped_key_data(&mut self, request: &Request) -> HandlerResult {
+        let (_, uid) = self.session_for(request)?;
+        let body: ScopedKeyDataRequest = parse_body(request)?;
+        let scopes = body.scope.split(' ').collect::<Vec<_>>();
+        Ok(MockResponse::json(
+            200,
+            &self.account(&uid).scoped_key_data(&scopes),
+        ))
+    }
+
+    pub(super) fn destroy(&mut self, request: &Request) -> HandlerResult {
+        let body: DestroyRequest = parse_body(request)?;
+        if let Some(token) = body.token {
+            self.access_tokens.remove(&token);
+        }
+        if let Some(refresh_token) = body.refresh_token {
+            self.destroy_refresh_token(&refresh_token);
+        }
+        Ok(MockResponse::json(200, &json!({})))
+    }
+
+    pub(super) fn introspect(&mut self, request: &Request) -> HandlerResult {
+        let body: TokenOnlyRequest = parse_body(request)?;
+        let active = self.refresh_tokens.contains_key(&body.token)
+            || self.access_tokens.contains_key(&body.token);
+        Ok(MockResponse::json(200, &json!({ "active": active })))
+    }
+
+    pub(super) fn profile(&mut self, request: &Request) -> HandlerResult {
+        let token = self.access_token_for(request)?;
+        if !token
+            .scopes
+            .iter()
+            .any(|scope| scope == scopes::PROFILE || scope.starts_with("profile:"))
+        {
+            return Err(error(
+                401,
+                ERRNO_INVALID_TOKEN,
+                "Access token doesn't have the profile scope",
+            ));
+        }
+        let uid = token.uid.clone();
+        let account = self.account(&uid);
+        let etag = account.profile_etag();
+        let if_none_match = request
+            .headers
+            .get(header_names::IF_NONE_MATCH)
+            .map(|value| value.trim_matches('"'));
+        if if_none_match == Some(etag.as_str()) {
+            return Ok(MockResponse::not_modified());
+        }
+        Ok(MockResponse::json(
+            200,
+            &json!({
+                "uid": account.uid,
+                "email": account.email,
+                "displayName": account.display_name,
+                "avatar": format!("{}/profile/v1/avatar/{}", CONTENT_URL, account.uid),
+                "avatarDefault": true,
+            }),
+        )
+        .header(header_names::ETAG, etag))
+    }
+
+    /// Plays the content server for a user who's signed in as `uid`. Like the
+    /// real content server, this encrypts the account's scoped keys to the
+    /// client's `keys_jwk`.
+    pub(super) fn authorize(&mut self, uid: &str, url: &Url) -> anyhow::Result<String> {
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+        let param = |name: &str| {
+            params
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("Missing `{}` parameter", name))
+        };
+        let client_id = param("client_id")?;
+        let scopes = split_scopes(&param("scope")?);
+        let state = param("state")?;
+        let redirect_uri = match params.get("context").map(String::as_str) {
+            Some("oauth_webchannel_v1") => OAUTH_WEBCHANNEL_REDIRECT.to_owned(),
+            _ => param("redirect_uri")?,
+        };
+        let account = self
+            .accounts
+            .get(uid)
+            .ok_or_else(|| anyhow!("Unknown account {}", uid))?;
+        let keys_jwe = match (params.get("keys_jwk"), account.scoped_keys(&scopes)) {
+            (Some(keys_jwk), Some(keys)) => {
+                let jwk = base64::decode_config(keys_jwk, base64::URL_SAFE_NO_PAD)?;
+                let keys_jwe = ScopedKeysFlow::with_random_key()?
+                    .encrypt_keys_jwe(std::str::from_utf8(&jwk)?, keys.to_string().as_bytes())?;
+                Some(keys_jwe)
+            }
+            _ => None,
+        };
+        let code = self.create_code(AuthorizationCode {
+            uid: uid.to_owned(),
+            client_id,
+            scopes,
+            access_type: params.get("access_type").cloned(),
+            code_challenge: params.get("code_challenge").cloned(),
+            keys_jwe,
+        })?;
+        redirect_url(&redirect_uri, &code, &state)
+    }
+
+    pub(super) fn begin_pairing(&mut self, session_token: &str) -> anyhow::Result<String> {
+        let session = self
+            .sessions
+            .get(&session_token_id(session_token)?)
+            .ok_or_else(|| anyhow!("Unknown session token"))?;
+        let uid = session.uid.clone();
+        let channel_id = random_hex(16)?;
+        let channel_key = random_hex(32)?;
+        self.pairing_channels.insert(channel_id.clone(), uid);
+        Ok(format!(
+            "{}/pair#channel_id={}&channel_key={}",
+            CONTENT_URL, channel_id, channel_key
+        ))
+    }
+
+    /// Completes a pairing flow. The real server relays messages between the
+    /// supplicant and the authority over the channel; we only need to check
+    /// that the channel exists, and sign the supplicant in to the authority's
+    /// account.
+    pub(super) fn approve_pairing(&mut self, url: &Url) -> anyhow::Result<String> {
+        if url.path() != "/pair/supp" {
+            bail!("Not a pairing supplicant URL: {}", url);
+        }
+        let channel_id = url
+            .fragment()
+            .and_then(|fragment| {
+                url::form_urlencoded::parse(fragment.as_bytes())
+                    .find(|(name, _)| name == "channel_id")
+                    .map(|(_, value)| value.into_owned())
+            })
+            .ok_or_else(|| anyhow!("Missing pairing channel"))?;
+        let uid = self
+            .pairing_channels
+            .remove(&channel_id)
+            .ok_or_else(|| anyhow!("Unknown pairing channel {}", channel_id))?;
+        self.authorize(&uid, url)
+    }
+
+    fn create_code(&mut self, code: AuthorizationCode) -> anyhow::Result<String> {
+        let id = random_hex(32)?;
+        self.codes.insert(id.clone(), code);
+        Ok(id)
+    }
+
+    /// Returns a token response with a new access token and, for offline
+    /// access, a new refresh token.
+    fn issue_tokens(
+        &mut self,
+        uid: &str,
+        client_id: &str,
+        scopes: Vec<String>,
+        offline: bool,
+        ttl: Option<u64>,
+    ) -> Value {
+        let scope = scopes.join(" ");
+        let access_token = random_hex(32).expect("Should generate tokens");
+        self.access_tokens.insert(
+            access_token.clone(),
+            AccessToken {
+                uid: uid.to_owned(),
+                scopes: scopes.clone(),
+            },
+        );
+        let mut response = json!({
+            "access_token": access_token,
+            "token_type": "bearer",
+            "scope": scope,
+            "expires_in": ttl.unwrap_or(ACCESS_TOKEN_TTL),
+            "auth_at": util::now_secs(),
+        });
+        if offline {
+            let refresh_token = random_hex(32).expect("Should generate tokens");
+            let now = util::now();
+            self.refresh_tokens.insert(
+                refresh_token.clone(),
+                RefreshToken {
+                    uid: uid.to_owned(),
+                    client_id: client_id.to_owned(),
+                    scopes,
+                    created_at: now,
+                    last_access_time: now,
+                },
+            );
+            response["refresh_token"] = json!(refresh_token);
+        }
+        response
+    }
+}
+
+fn split_scopes(scope: &str) -> Vec<String> {
+    scope
+        .split(' ')
+        .filter(|scope| !scope.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+fn redirect_url(redirect_uri: &str, code: &str, state: &str) -> anyhow::Result<String> {
+    let mut url = Url::parse(redirect_uri)?;
+    url.query_pairs_mut()
+        .append_pair("code", code)
+        .append_pair("state", state);
+    Ok(url.to_string())
+}
+
+fn invalid_grant() -> MockResponse {
+    error(400, ERRNO_INVALID_TOKEN, "Invalid grant")
+}
@@ -0,0 +1,341 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The accounts, tokens and devices that the mock server knows about.
+
+use super::{error, MockResponse, ERRNO_INVALID_TOKEN};
+use crate::{http_client::PushSubscription, scopes, util};
+use rc_crypto::rand;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use viaduct::{header_names, Request};
+
+/// The OAuth scope that asks for a session token alongside the refresh token,
+/// which pairing authorities and other privileged clients use.
+pub(super) const SESSION_SCOPE: &str = "https://identity.mozilla.com/tokens/session";
+
+/// The scopes that come with keys. Real FxA knows about more of these, but
+/// Sync is the only one that we use.
+const KEY_BEARING_SCOPES: &[&str] = &[scopes::OLD_SYNC];
+
+#[derive(Default)]
+pub(super) struct ServerState {
+    pub accounts: HashMap<String, Account>,
+    /// Sessions, keyed by their Hawk token id.
+    pub sessions: HashMap<String, Session>,
+    pub refresh_tokens: HashMap<String, RefreshToken>,
+    pub access_tokens: HashMap<String, AccessToken>,
+    pub codes: HashMap<String, AuthorizationCode>,
+    /// Maps the ids of pairing channels that are waiting for a supplicant to
+    /// the uids of their authorities.
+    pub pairing_channels: HashMap<String, String>,
+}
+
+pub(super) struct Account {
+    pub uid: String,
+    pub email: String,
+    pub display_name: Option<String>,
+    /// Bumped every time the profile changes, to give it a new ETag.
+    pub profile_version: u64,
+    pub k_sync: Vec<u8>,
+    pub k_xcs: Vec<u8>,
+    pub key_rotation_timestamp: u64,
+    pub devices: BTreeMap<String, Device>,
+    /// Commands sent to the account's devices, oldest first.
+    pub commands: Vec<Command>,
+}
+
+pub(super) struct Session {
+    pub uid: String,
+    pub created_at: u64,
+    pub last_access_time: u64,
+}
+
+pub(super) struct RefreshToken {
+    pub uid: String,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+    pub last_access_time: u64,
+}
+
+pub(super) struct AccessToken {
+    pub uid: String,
+    pub scopes: Vec<String>,
+}
+
+pub(super) struct AuthorizationCode {
+    pub uid: String,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub access_type: Option<String>,
+    pub code_challenge: Option<String>,
+    pub keys_jwe: Option<String>,
+}
+
+/// A device record. Like the real server, the mock ties each device to the
+/// refresh token that registered it, so destroying one destroys the other.
+pub(super) struct Device {
+    pub id: String,
+    pub refresh_token: String,
+    pub name: Option<String>,
+    pub device_type: Option<String>,
+    pub push_subscription: Option<PushSubscription>,
+    pub available_commands: HashMap<String, String>,
+    pub last_access_time: u64,
+    /// Push messages that the server would have sent to the device's push
+    /// endpoint, if it has one, waiting for the test to collect them.
+    pub push_messages: Vec<String>,
+}
+
+pub(super) struct Command {
+    pub index: u64,
+    pub target: String,
+    pub command: String,
+    pub payload: Value,
+    pub sender: Option<String>,
+}
+
+impl ServerState {
+    pub fn create_account(&mut self, email: &str) -> anyhow::Result<String> {
+        let uid = random_hex(16)?;
+        let mut k_sync = vec![0u8; 64];
+        rand::fill(&mut k_sync)?;
+        let mut k_xcs = vec![0u8; 16];
+        rand::fill(&mut k_xcs)?;
+        self.accounts.insert(
+            uid.clone(),
+            Account {
+                uid: uid.clone(),
+                email: email.to_owned(),
+                display_name: None,
+                profile_version: 0,
+                k_sync,
+                k_xcs,
+                key_rotation_timestamp: util::now(),
+                devices: BTreeMap::new(),
+                commands: Vec::new(),
+            },
+        );
+        Ok(uid)
+    }
+
+    /// Starts a new session for `uid`, and returns its session token.
+    pub fn create_session(&mut self, uid: &str) -> anyhow::Result<String> {
+        let token = random_hex(32)?;
+        let id = session_token_id(&token)?;
+        let now = util::now();
+        self.sessions.insert(
+            id,
+            Session {
+                uid: uid.to_owned(),
+                created_at: now,
+                last_access_time: now,
+            },
+        );
+        Ok(token)
+    }
+
+    /// Returns the id and uid of the session that signed a Hawk request.
+    pub fn session_for(&mut self, request: &Request) -> Result<(String, String), MockResponse> {
+        let id = hawk_id(request).ok_or_else(invalid_token)?;
+        let session = self.sessions.get_mut(id).ok_or_else(invalid_token)?;
+        session.last_access_time = util::now();
+        Ok((id.to_owned(), session.uid.clone()))
+    }
+
+    /// Returns the refresh token that a request was made with.
+    pub fn refresh_token_for(&mut self, request: &Request) -> Result<String, MockResponse> {
+        let token = bearer_token(request).ok_or_else(invalid_token)?;
+        let refresh_token = self
+            .refresh_tokens
+            .get_mut(token)
+            .ok_or_else(invalid_token)?;
+        refresh_token.last_access_time = util::now();
+        Ok(token.to_owned())
+    }
+
+    /// Returns the access token that a request was made with.
+    pub fn access_token_for(&self, request: &Request) -> Result<&AccessToken, MockResponse> {
+        let token = bearer_token(request).ok_or_else(invalid_token)?;
+        self.access_tokens.get(token).ok_or_else(invalid_token)
+    }
+
+    pub fn account(&mut self, uid: &str) -> &mut Account {
+        self.accounts
+            .get_mut(uid)
+            .expect("Tokens should belong to known accounts")
+    }
+
+    /// Returns the device registered with `refresh_token`, if there is one.
+    pub fn device_for(&self, refresh_token: &str) -> Option<&Device> {
+        let uid = &self.refresh_tokens.get(refresh_token)?.uid;
+        self.accounts[uid]
+            .devices
+            .values()
+            .find(|device| device.refresh_token == refresh_token)
+    }
+
+    /// Destroys a refresh token, along with its device, and tells the
+    /// account's other devices that it's gone.
+    pub fn destroy_refresh_token(&mut self, token: &str) {
+        let uid = match self.refresh_tokens.remove(token) {
+            Some(refresh_token) => refresh_token.uid,
+            None => return,
+        };
+        let account = self.account(&uid);
+        let device_id = account
+            .devices
+            .values()
+            .find(|device| device.refresh_token == token)
+            .map(|device| device.id.clone());
+        if let Some(device_id) = device_id {
+            account.devices.remove(&device_id);
+            account.notify(
+                None,
+                "fxaccounts:device_disconnected",
+                Some(json!({ "id": device_id })),
+            );
+        }
+    }
+}
+
+impl Account {
+    /// Returns the scoped keys for the key-bearing scopes in `scopes`, in the
+    /// format that the content server encrypts into `keys_jwe`.
+    pub fn scoped_keys(&self, scopes: &[String]) -> Option<Value> {
+        let keys = scopes
+            .iter()
+            .filter(|scope| KEY_BEARING_SCOPES.contains(&scope.as_str()))
+            .map(|scope| {
+                let key = json!({
+                    "kty": "oct",
+                    "scope": scope,
+                    "k": base64::encode_config(&self.k_sync, base64::URL_SAFE_NO_PAD),
+                    "kid": format!(
+                        "{}-{}",
+                        self.key_rotation_timestamp,
+                        base64::encode_config(&self.k_xcs, base64::URL_SAFE_NO_PAD)
+                    ),
+                });
+                (scope.clone(), key)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        if keys.is_empty() {
+            None
+        } else {
+            Some(Value::Object(keys))
+        }
+    }
+
+    /// Returns the key metadata for the key-bearing scopes in `scopes`, which
+    /// clients use to derive the key ids for the keys they're given.
+    pub fn scoped_key_data(&self, scopes: &[&str]) -> Value {
+        let data = scopes
+            .iter()
+            .filter(|scope| KEY_BEARING_SCOPES.contains(scope))
+            .map(|scope| {
+                let data = json!({
+                    "identifier": scope,
+                    "keyRotationSecret": "0".repeat(64),
+                    "keyRotationTimestamp": self.key_rotation_timestamp,
+                });
+                (scope.to_string(), data)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        Value::Object(data)
+    }
+
+    pub fn profile_etag(&self) -> String {
+        format!("{}-{}", self.uid, self.profile_version)
+    }
+
+    /// Queues a push message for all the account's devices with push
+    /// subscriptions, except `except`. Messages without any data leave out
+    /// the `data` field, like the real server.
+    pub fn notify(&mut self, except: Option<&str>, command: &str, data: Option<Value>) {
+        let mut message = json!({
+            "version": 1,
+            "command": command,
+        });
+        if let Some(data) = data {
+            message["data"] = data;
+        }
+        let message = message.to_string();
+        for device in self.devices.values_mut() {
+            if Some(device.id.as_str()) == except || device.push_subscription.is_none() {
+                continue;
+            }
+            device.push_messages.push(message.clone());
+        }
+    }
+}
+
+impl Device {
+    /// Returns the device record that the devices endpoints return.
+    pub fn to_json(&self, current_refresh_token: Option<&str>) -> Value {
+        let mut device = json!({
+            "id": self.id,
+            "name": self.name.clone().unwrap_or_default(),
+            "type": self.device_type.as_deref().unwrap_or("unknown"),
+            "availableCommands": self.available_commands,
+            "pushEndpointExpired": false,
+        });
+        if let Some(subscription) = &self.push_subscription {
+            device["pushCallback"] = json!(subscription.endpoint);
+            device["pushPublicKey"] = json!(subscription.public_key);
+            device["pushAuthKey"] = json!(subscription.auth_key);
+        }
+        if let Some(current_refresh_token) = current_refresh_token {
+            device["isCurrentDevice"] = json!(self.refresh_token == current_refresh_token);
+            device["location"] = json!({});
+            device["lastAccessTime"] = json!(self.last_access_time);
+        }
+        device
+    }
+}
+
+pub(super) fn random_hex(len: usize) -> anyhow::Result<String> {
+    let mut bytes = vec![0u8; len];
+    rand::fill(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+/// Returns the Hawk id that clients use to sign requests with a session token.
+pub(super) fn session_token_id(session_token: &str) -> anyhow::Result<String> {
+    let key = crate::http_client::derive_auth_key_from_session_token(session_token)?;
+    Ok(hex::encode(&key[0..32]))
+}
+
+/// Extracts the token id from a Hawk `Authorization` header, which looks like
+/// `Hawk id="...", ts="...", nonce="...", mac="..."`. We don't check the MAC.
+fn hawk_id(request: &Request) -> Option<&str> {
+    let header = request.headers.get(header_names::AUTHORIZATION)?;
+    if !header.starts_with("Hawk ") {
+        return None;
+    }
+    header["Hawk ".len()..].split(',').find_map(|param| {
+        let param = param.trim();
+        if param.len() > 5 && param.starts_with("id=\"") && param.ends_with('"') {
+            Some(&param[4..param.len() - 1])
+        } else {
+            None
+        }
+    })
+}
+
+fn bearer_token(request: &Request) -> Option<&str> {
+    match request.headers.get(header_names::AUTHORIZATION) {
+        Some(value) if value.starts_with("Bearer ") => Some(&value["Bearer ".len()..]),
+        _ => None,
+    }
+}
+
+fn invalid_token() -> MockResponse {
+    error(
+        401,
+        ERRNO_INVALID_TOKEN,
+        "Invalid authentication token in request signature",
+    )
+}
@@ -56,6 +56,10 @@ pub struct Device {
     pub last_access_time: ::std::option::Option<u64>,
     #[prost(enumeration="device::Capability", repeated, packed="false", tag="8")]
     pub capabilities: ::std::vec::Vec<i32>,
+    /// The names of all the commands the device advertises, including the
+    /// ones without a `Capability`.
+    #[prost(string, repeated, tag="9")]
+    pub commands: ::std::vec::Vec<std::string::String>,
 }
 pub mod device {
     #[derive(Clone, PartialEq, ::prost::Message)]
@@ -71,6 +75,7 @@ pub mod device {
     #[repr(i32)]
     pub enum Capability {
         SendTab = 1,
+        CloseTabs = 2,
     }
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
@@ -92,12 +97,15 @@ pub struct Devices {
 pub struct Capabilities {
     #[prost(enumeration="device::Capability", repeated, packed="false", tag="1")]
     pub capability: ::std::vec::Vec<i32>,
+    /// The names of registered custom commands.
+    #[prost(string, repeated, tag="2")]
+    pub commands: ::std::vec::Vec<std::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct IncomingDeviceCommand {
     #[prost(enumeration="incoming_device_command::IncomingDeviceCommandType", required, tag="1")]
     pub r#type: i32,
-    #[prost(oneof="incoming_device_command::Data", tags="2")]
+    #[prost(oneof="incoming_device_command::Data", tags="2, 3, 4, 5")]
     pub data: ::std::option::Option<incoming_device_command::Data>,
 }
 pub mod incoming_device_command {
@@ -117,16 +125,54 @@ pub mod incoming_device_command {
             pub url: std::string::String,
         }
     }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CloseTabsData {
+        #[prost(message, optional, tag="1")]
+        pub from: ::std::option::Option<super::Device>,
+        #[prost(string, repeated, tag="2")]
+        pub urls: ::std::vec::Vec<std::string::String>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CloseTabsReplyData {
+        #[prost(message, optional, tag="1")]
+        pub from: ::std::option::Option<super::Device>,
+        #[prost(string, repeated, tag="2")]
+        pub closed: ::std::vec::Vec<std::string::String>,
+        #[prost(string, repeated, tag="3")]
+        pub not_found: ::std::vec::Vec<std::string::String>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CustomData {
+        #[prost(message, optional, tag="1")]
+        pub from: ::std::option::Option<super::Device>,
+        #[prost(string, required, tag="2")]
+        pub command: std::string::String,
+        /// JSON.
+        #[prost(string, required, tag="3")]
+        pub payload: std::string::String,
+    }
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum IncomingDeviceCommandType {
         /// `data` set to `tab_received_data`.
         TabReceived = 1,
+        /// `data` set to `close_tabs_requested_data`.
+        CloseTabsRequested = 2,
+        /// `data` set to `tabs_closed_data`.
+        TabsClosed = 3,
+        /// `data` set to `custom_data`.
+        Custom = 4,
     }
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Data {
         #[prost(message, tag="2")]
         TabReceivedData(SendTabData),
+        #[prost(message, tag="3")]
+        CloseTabsRequestedData(CloseTabsData),
+        #[prost(message, tag="4")]
+        TabsClosedData(CloseTabsReplyData),
+        #[prost(message, tag="5")]
+        CustomData(CustomData),
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -134,6 +180,38 @@ pub struct IncomingDeviceCommands {
     #[prost(message, repeated, tag="1")]
     pub commands: ::std::vec::Vec<IncomingDeviceCommand>,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloseTabsUrls {
+    #[prost(string, repeated, tag="1")]
+    pub urls: ::std::vec::Vec<std::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloseTabsReply {
+    #[prost(string, repeated, tag="1")]
+    pub closed: ::std::vec::Vec<std::string::String>,
+    #[prost(string, repeated, tag="2")]
+    pub not_found: ::std::vec::Vec<std::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TabsToSend {
+    #[prost(message, repeated, tag="1")]
+    pub tabs: ::std::vec::Vec<tabs_to_send::Tab>,
+}
+pub mod tabs_to_send {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Tab {
+        #[prost(message, repeated, tag="1")]
+        pub entries: ::std::vec::Vec<super::incoming_device_command::send_tab_data::TabHistoryEntry>,
+    }
+}
+/// Tabs are identified by their index in `TabsToSend.tabs`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SendTabsResult {
+    #[prost(uint32, repeated, packed="false", tag="1")]
+    pub delivered: ::std::vec::Vec<u32>,
+    #[prost(uint32, repeated, packed="false", tag="2")]
+    pub failed: ::std::vec::Vec<u32>,
+}
 /// This is basically an enum with associated values,
 /// but it's a bit harder to model in proto2.
 #[derive(Clone, PartialEq, ::prost::Message)]
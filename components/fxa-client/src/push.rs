@@ -30,8 +30,11 @@ impl FirefoxAccount {
         match payload {
             PushPayload::CommandReceived(CommandReceivedPushPayload { index, .. }) => {
                 if cfg!(target_os = "ios") {
-                    self.fetch_device_command(index)
-                        .map(|cmd| vec![AccountEvent::IncomingDeviceCommand(Box::new(cmd))])
+                    self.fetch_device_command(index).map(|cmds| {
+                        cmds.into_iter()
+                            .map(|cmd| AccountEvent::IncomingDeviceCommand(Box::new(cmd)))
+                            .collect()
+                    })
                 } else {
                     self.poll_device_commands().map(|cmds| {
                         cmds.into_iter()
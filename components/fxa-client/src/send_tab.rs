@@ -4,108 +4,66 @@
 
 pub use crate::commands::send_tab::{SendTabPayload, TabHistoryEntry};
 use crate::{
-    commands::send_tab::{
-        self, EncryptedSendTabPayload, PrivateSendTabKeys, PublicSendTabKeys, SendTabKeysPayload,
-    },
+    commands::send_tab::{self, SendTabCommand},
     error::*,
-    http_client::GetDeviceResponse,
-    scopes, FirefoxAccount, IncomingDeviceCommand,
+    FirefoxAccount,
 };
 
-impl FirefoxAccount {
-    /// Generate the Send Tab command to be registered with the server.
-    ///
-    /// **💾 This method alters the persisted account state.**
-    pub(crate) fn generate_send_tab_command_data(&mut self) -> Result<String> {
-        let own_keys = self.load_or_generate_keys()?;
-        let public_keys: PublicSendTabKeys = own_keys.into();
-        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
-        public_keys.as_command_data(&oldsync_key)
-    }
-
-    fn load_or_generate_keys(&mut self) -> Result<PrivateSendTabKeys> {
-        if let Some(s) = self.state.commands_data.get(send_tab::COMMAND_NAME) {
-            match PrivateSendTabKeys::deserialize(s) {
-                Ok(keys) => return Ok(keys),
-                Err(_) => log::error!("Could not deserialize Send Tab keys. Re-creating them."),
-            }
-        }
-        let keys = PrivateSendTabKeys::from_random()?;
-        self.state
-            .commands_data
-            .insert(send_tab::COMMAND_NAME.to_owned(), keys.serialize()?);
-        Ok(keys)
-    }
+/// The outcome of sending several tabs at once with
+/// [`FirefoxAccount::send_tabs`]. Tabs are identified by their index in the
+/// list that was passed to `send_tabs`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SendTabsResult {
+    /// The tabs that were sent to the target device.
+    pub delivered: Vec<usize>,
+    /// The tabs that couldn't be sent, either because they're too large even
+    /// without their history, or because the command carrying them failed.
+    pub failed: Vec<usize>,
+}
 
+impl FirefoxAccount {
     /// Send a single tab to another device designated by its device ID.
     pub fn send_tab(&mut self, target_device_id: &str, title: &str, url: &str) -> Result<()> {
-        let devices = self.get_devices(false)?;
-        let target = devices
-            .iter()
-            .find(|d| d.id == target_device_id)
-            .ok_or_else(|| ErrorKind::UnknownTargetDevice(target_device_id.to_owned()))?;
         let payload = SendTabPayload::single_tab(title, url);
-        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
-        let command_payload = send_tab::build_send_command(&oldsync_key, target, &payload)?;
-        self.invoke_command(send_tab::COMMAND_NAME, target, &command_payload)
+        self.invoke_device_command(&SendTabCommand, target_device_id, &payload)
     }
 
-    pub(crate) fn handle_send_tab_command(
+    /// Send several tabs to another device designated by its device ID.
+    ///
+    /// The tabs are packed into as few Send Tab commands as the size limits
+    /// allow, so sending a whole window usually results in a single push
+    /// message for the target device. Clients that only know how to receive
+    /// a single tab open the first tab of each command.
+    ///
+    /// Returns an error if none of the tabs could be sent because of an
+    /// error; otherwise, reports which tabs were delivered.
+    pub fn send_tabs(
         &mut self,
-        sender: Option<GetDeviceResponse>,
-        payload: serde_json::Value,
-    ) -> Result<IncomingDeviceCommand> {
-        let send_tab_key: PrivateSendTabKeys =
-            match self.state.commands_data.get(send_tab::COMMAND_NAME) {
-                Some(s) => PrivateSendTabKeys::deserialize(s)?,
-                None => {
-                    return Err(ErrorKind::IllegalState(
-                        "Cannot find send-tab keys. Has initialize_device been called before?",
-                    )
-                    .into());
+        target_device_id: &str,
+        tabs: Vec<SendTabPayload>,
+    ) -> Result<SendTabsResult> {
+        let packed = send_tab::pack_tabs(tabs, send_tab::MAX_PAYLOAD_SIZE)?;
+        let mut result = SendTabsResult {
+            delivered: Vec::new(),
+            failed: packed.unsendable,
+        };
+        let mut last_error = None;
+        for (indices, payload) in packed.payloads {
+            match self.invoke_device_command(&SendTabCommand, target_device_id, &payload) {
+                Ok(()) => result.delivered.extend(indices),
+                Err(e) => {
+                    log::error!("Could not send {} tabs: {}", indices.len(), e);
+                    result.failed.extend(indices);
+                    last_error = Some(e);
                 }
-            };
-        let encrypted_payload: EncryptedSendTabPayload = serde_json::from_value(payload)?;
-        match encrypted_payload.decrypt(&send_tab_key) {
-            Ok(payload) => Ok(IncomingDeviceCommand::TabReceived { sender, payload }),
-            Err(e) => {
-                log::error!("Could not decrypt Send Tab payload. Diagnosing then resetting the Send Tab keys.");
-                match self.diagnose_remote_keys(send_tab_key) {
-                    Ok(_) => log::error!("Could not find the cause of the Send Tab keys issue."),
-                    Err(e) => log::error!("{}", e),
-                };
-                // Reset the Send Tab keys.
-                self.state.commands_data.remove(send_tab::COMMAND_NAME);
-                self.reregister_current_capabilities()?;
-                Err(e)
             }
         }
-    }
-
-    fn diagnose_remote_keys(&mut self, local_send_tab_key: PrivateSendTabKeys) -> Result<()> {
-        let own_device = &mut self
-            .get_current_device()?
-            .ok_or_else(|| ErrorKind::SendTabDiagnosisError("No remote device."))?;
-
-        let command = own_device
-            .available_commands
-            .get(send_tab::COMMAND_NAME)
-            .ok_or_else(|| ErrorKind::SendTabDiagnosisError("No remote command."))?;
-        let bundle: SendTabKeysPayload = serde_json::from_str(command)?;
-        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
-        let public_keys_remote = bundle.decrypt(oldsync_key).map_err(|_| {
-            ErrorKind::SendTabDiagnosisError("Unable to decrypt public key bundle.")
-        })?;
-
-        let public_keys_local: PublicSendTabKeys = local_send_tab_key.into();
-
-        if public_keys_local.public_key() != public_keys_remote.public_key() {
-            return Err(ErrorKind::SendTabDiagnosisError("Mismatch in public key.").into());
-        }
-
-        if public_keys_local.auth_secret() != public_keys_remote.auth_secret() {
-            return Err(ErrorKind::SendTabDiagnosisError("Mismatch in auth secret.").into());
+        match last_error {
+            Some(e) if result.delivered.is_empty() => Err(e),
+            _ => {
+                result.failed.sort_unstable();
+                Ok(result)
+            }
         }
-        Ok(())
     }
 }
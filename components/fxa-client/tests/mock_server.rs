@@ -0,0 +1,546 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! End-to-end tests for `FirefoxAccount`, against the mock FxA server.
+
+use fxa_client::{
+    commands::custom::CustomCommand,
+    device::{Capability, PushSubscription, Type as DeviceType},
+    error::ErrorKind,
+    mock_server::{use_mock_backend, MockServer, CONTENT_URL},
+    scopes,
+    send_tab::{SendTabPayload, TabHistoryEntry},
+    AccountEvent, Config, FirefoxAccount, IncomingDeviceCommand,
+};
+use std::{collections::HashMap, sync::Arc};
+use url::Url;
+
+const CLIENT_ID: &str = "3c49430b43dfba77";
+const REDIRECT_URI: &str = "https://accounts.fxa.mock/oauth/success/3c49430b43dfba77";
+const SESSION_SCOPE: &str = "https://identity.mozilla.com/tokens/session";
+
+fn new_account(email: &str) -> (&'static MockServer, String) {
+    let server = use_mock_backend();
+    let uid = server.create_account(email).expect("Should create account");
+    (server, uid)
+}
+
+fn new_fxa() -> FirefoxAccount {
+    FirefoxAccount::with_config(Config::new(CONTENT_URL, CLIENT_ID, REDIRECT_URI))
+}
+
+/// Completes an OAuth flow with the code and state from a redirect URL.
+fn complete_flow(fxa: &mut FirefoxAccount, redirect: &str) {
+    let redirect = Url::parse(redirect).expect("Should redirect to a valid URL");
+    let params = redirect
+        .query_pairs()
+        .into_owned()
+        .collect::<HashMap<_, _>>();
+    fxa.complete_oauth_flow(&params["code"], &params["state"])
+        .expect("Should complete OAuth flow");
+}
+
+fn sign_in(server: &MockServer, uid: &str, scopes: &[&str]) -> FirefoxAccount {
+    let mut fxa = new_fxa();
+    let url = fxa
+        .begin_oauth_flow(scopes)
+        .expect("Should begin OAuth flow");
+    let redirect = server.authorize(uid, &url).expect("Should authorize");
+    complete_flow(&mut fxa, &redirect);
+    fxa
+}
+
+/// Signs in and registers a device that can receive and close tabs.
+fn sign_in_device(server: &MockServer, uid: &str, name: &str) -> FirefoxAccount {
+    let mut fxa = sign_in(server, uid, &[scopes::PROFILE, scopes::OLD_SYNC]);
+    fxa.initialize_device(
+        name,
+        DeviceType::Desktop,
+        &[Capability::SendTab, Capability::CloseTabs],
+    )
+    .expect("Should initialize device");
+    fxa.set_push_subscription(&PushSubscription {
+        endpoint: format!("https://push.fxa.mock/{}", name),
+        public_key: "BCgkOpjCgXWi8CjEBUb-ukg3ozHJRaQeQNd3Qz4dNm1wzjQgNJ7TfwPe5NfE9uTk".into(),
+        auth_key: "r9RmRhH-8T-7P9Ue-RL4AA".into(),
+    })
+    .expect("Should set push subscription");
+    fxa
+}
+
+#[test]
+fn test_oauth_flow() {
+    let (server, uid) = new_account("oauth@example.com");
+    let mut fxa = sign_in(server, &uid, &[scopes::PROFILE, scopes::OLD_SYNC]);
+
+    let token = fxa
+        .get_access_token(scopes::OLD_SYNC, None)
+        .expect("Should get access token");
+    let key = token.key.expect("Should get Sync key");
+    assert_eq!(key.key_bytes().unwrap(), server.sync_key(&uid));
+
+    let token = fxa
+        .get_access_token(scopes::PROFILE, None)
+        .expect("Should get access token");
+    assert!(token.key.is_none(), "Profile scope shouldn't have a key");
+
+    assert!(fxa.check_authorization_status().unwrap().active);
+    assert!(
+        fxa.get_session_token().is_err(),
+        "Shouldn't get a session token without asking for one"
+    );
+}
+
+#[test]
+fn test_profile() {
+    let (server, uid) = new_account("profile@example.com");
+    let mut fxa = sign_in(server, &uid, &[scopes::PROFILE]);
+
+    let profile = fxa.get_profile(true).expect("Should fetch profile");
+    assert_eq!(profile.uid, uid);
+    assert_eq!(profile.email, "profile@example.com");
+    assert_eq!(profile.display_name, None);
+
+    // The profile hasn't changed, so the server should tell us to use the
+    // cached one.
+    let profile = fxa.get_profile(true).expect("Should revalidate profile");
+    assert_eq!(profile.email, "profile@example.com");
+
+    server.set_display_name(&uid, Some("Test User"));
+    let profile = fxa.get_profile(true).expect("Should fetch new profile");
+    assert_eq!(profile.display_name.as_deref(), Some("Test User"));
+}
+
+#[test]
+fn test_devices() {
+    let (server, uid) = new_account("devices@example.com");
+    let mut laptop = sign_in_device(server, &uid, "Laptop");
+    let mut phone = sign_in_device(server, &uid, "Phone");
+
+    let laptop_id = laptop.get_current_device_id().unwrap();
+    let events = server
+        .take_push_messages(&laptop_id)
+        .into_iter()
+        .flat_map(|message| laptop.handle_push_message(&message).unwrap())
+        .collect::<Vec<_>>();
+    match events.as_slice() {
+        [AccountEvent::DeviceConnected { device_name }] => assert_eq!(device_name, "Phone"),
+        _ => panic!("Should tell the laptop that the phone connected"),
+    }
+
+    let devices = phone.get_devices(true).expect("Should fetch devices");
+    assert_eq!(devices.len(), 2);
+    let current = devices
+        .iter()
+        .find(|device| device.is_current_device)
+        .expect("Should include the current device");
+    assert_eq!(current.display_name, "Phone");
+
+    phone.set_device_name("Renamed Phone").unwrap();
+    let devices = laptop.get_devices(true).expect("Should fetch devices");
+    assert!(devices
+        .iter()
+        .any(|device| device.display_name == "Renamed Phone"));
+}
+
+#[test]
+fn test_send_tab() {
+    let (server, uid) = new_account("send-tab@example.com");
+    let mut sender = sign_in_device(server, &uid, "Sender");
+    let mut receiver = sign_in_device(server, &uid, "Receiver");
+    let sender_id = sender.get_current_device_id().unwrap();
+    let receiver_id = receiver.get_current_device_id().unwrap();
+    server.take_push_messages(&sender_id);
+
+    sender
+        .send_tab(&receiver_id, "Example", "https://example.com/")
+        .expect("Should send tab");
+
+    let messages = server.take_push_messages(&receiver_id);
+    assert_eq!(messages.len(), 1, "Should push the command to the receiver");
+    let events = receiver
+        .handle_push_message(&messages[0])
+        .expect("Should handle push message");
+    match events.as_slice() {
+        [AccountEvent::IncomingDeviceCommand(command)] => match command.as_ref() {
+            IncomingDeviceCommand::TabReceived { sender, payload } => {
+                assert_eq!(sender.as_ref().map(|s| s.id.as_str()), Some(&*sender_id));
+                assert_eq!(payload.entries.len(), 1);
+                assert_eq!(payload.entries[0].title, "Example");
+                assert_eq!(payload.entries[0].url, "https://example.com/");
+            }
+            command => panic!("Unexpected command {:?}", command),
+        },
+        _ => panic!("Should receive one command"),
+    }
+
+    assert!(
+        receiver.poll_device_commands().unwrap().is_empty(),
+        "Should have handled all commands"
+    );
+
+    // Devices can also poll for commands if they missed the push message.
+    receiver
+        .send_tab(&sender_id, "Reply", "https://example.org/")
+        .expect("Should send tab");
+    let commands = sender.poll_device_commands().expect("Should poll commands");
+    match commands.as_slice() {
+        [IncomingDeviceCommand::TabReceived { payload, .. }] => {
+            assert_eq!(payload.entries[0].url, "https://example.org/");
+        }
+        _ => panic!("Should receive one command"),
+    }
+}
+
+/// Returns the current URL of each tab received with `commands`.
+fn received_urls(commands: &[IncomingDeviceCommand]) -> Vec<String> {
+    commands
+        .iter()
+        .map(|command| match command {
+            IncomingDeviceCommand::TabReceived { payload, .. } => {
+                payload.entries.last().unwrap().url.clone()
+            }
+            command => panic!("Unexpected command {:?}", command),
+        })
+        .collect()
+}
+
+#[test]
+fn test_send_tabs() {
+    let (server, uid) = new_account("send-tabs@example.com");
+    let mut sender = sign_in_device(server, &uid, "Sender");
+    let mut receiver = sign_in_device(server, &uid, "Receiver");
+    let sender_id = sender.get_current_device_id().unwrap();
+    let receiver_id = receiver.get_current_device_id().unwrap();
+    server.take_push_messages(&sender_id);
+
+    // A few tabs fit in a single command.
+    let tabs = vec![
+        SendTabPayload::single_tab("One", "https://example.com/1"),
+        SendTabPayload::with_history(
+            "Two",
+            "https://example.com/2",
+            vec![TabHistoryEntry {
+                title: "Before two".to_owned(),
+                url: "https://example.com/before-2".to_owned(),
+            }],
+        ),
+        SendTabPayload::single_tab("Three", "https://example.com/3"),
+    ];
+    let result = sender
+        .send_tabs(&receiver_id, tabs)
+        .expect("Should send tabs");
+    assert_eq!(result.delivered, vec![0, 1, 2]);
+    assert!(result.failed.is_empty());
+
+    let messages = server.take_push_messages(&receiver_id);
+    assert_eq!(messages.len(), 1, "Should send all tabs in one command");
+    let commands: Vec<_> = receiver
+        .handle_push_message(&messages[0])
+        .expect("Should handle push message")
+        .into_iter()
+        .map(|event| match event {
+            AccountEvent::IncomingDeviceCommand(command) => *command,
+            event => panic!("Unexpected event {:?}", event),
+        })
+        .collect();
+    assert_eq!(
+        received_urls(&commands),
+        vec![
+            "https://example.com/1",
+            "https://example.com/2",
+            "https://example.com/3"
+        ]
+    );
+    match &commands[1] {
+        IncomingDeviceCommand::TabReceived { sender, payload } => {
+            assert_eq!(sender.as_ref().map(|s| s.id.as_str()), Some(&*sender_id));
+            assert_eq!(payload.entries[0].url, "https://example.com/before-2");
+        }
+        command => panic!("Unexpected command {:?}", command),
+    }
+
+    // Lots of large tabs are split across several commands.
+    let urls: Vec<_> = (0..40)
+        .map(|i| format!("https://example.com/{}/{}", i, "a".repeat(1000)))
+        .collect();
+    let tabs = urls
+        .iter()
+        .map(|url| SendTabPayload::single_tab("Large", url))
+        .collect();
+    let result = sender
+        .send_tabs(&receiver_id, tabs)
+        .expect("Should send tabs");
+    assert_eq!(result.delivered, (0..40).collect::<Vec<_>>());
+    assert!(result.failed.is_empty());
+    let messages = server.take_push_messages(&receiver_id);
+    assert!(messages.len() > 1, "Should split the tabs");
+    assert!(messages.len() < 40, "Should pack several tabs per command");
+    let commands = receiver
+        .poll_device_commands()
+        .expect("Should poll commands");
+    assert_eq!(received_urls(&commands), urls);
+
+    // A tab that's too large to send fails on its own, without being sent.
+    let tabs = vec![
+        SendTabPayload::single_tab("Small", "https://example.com/small"),
+        SendTabPayload::single_tab(
+            "Huge",
+            &format!("https://example.com/{}", "a".repeat(64 * 1024)),
+        ),
+        SendTabPayload::single_tab("Also small", "https://example.com/also-small"),
+    ];
+    let result = sender
+        .send_tabs(&receiver_id, tabs)
+        .expect("Should send the small tabs");
+    assert_eq!(result.delivered, vec![0, 2]);
+    assert_eq!(result.failed, vec![1]);
+    let messages = server.take_push_messages(&receiver_id);
+    assert_eq!(messages.len(), 2, "Should only send the small tabs");
+    let commands = receiver
+        .poll_device_commands()
+        .expect("Should poll commands");
+    assert_eq!(
+        received_urls(&commands),
+        vec![
+            "https://example.com/small",
+            "https://example.com/also-small"
+        ]
+    );
+}
+
+#[test]
+fn test_close_tabs() {
+    let (server, uid) = new_account("close-tabs@example.com");
+    let mut sender = sign_in_device(server, &uid, "Sender");
+    let mut receiver = sign_in_device(server, &uid, "Receiver");
+    let sender_id = sender.get_current_device_id().unwrap();
+    let receiver_id = receiver.get_current_device_id().unwrap();
+    server.take_push_messages(&sender_id);
+
+    let devices = sender.get_devices(true).expect("Should fetch devices");
+    let receiver_device = devices
+        .iter()
+        .find(|device| device.id == receiver_id)
+        .expect("Should include the receiver");
+    assert!(receiver_device
+        .available_commands
+        .contains_key("https://identity.mozilla.com/cmd/close-uri/v1"));
+
+    sender
+        .close_tabs(
+            &receiver_id,
+            vec![
+                "https://example.com/".to_owned(),
+                "https://example.org/".to_owned(),
+            ],
+        )
+        .expect("Should close tabs");
+
+    let messages = server.take_push_messages(&receiver_id);
+    assert_eq!(messages.len(), 1, "Should push the command to the receiver");
+    let events = receiver
+        .handle_push_message(&messages[0])
+        .expect("Should handle push message");
+    match events.as_slice() {
+        [AccountEvent::IncomingDeviceCommand(command)] => match command.as_ref() {
+            IncomingDeviceCommand::CloseTabsRequested { sender, payload } => {
+                assert_eq!(sender.as_ref().map(|s| s.id.as_str()), Some(&*sender_id));
+                assert_eq!(
+                    payload.urls,
+                    vec!["https://example.com/", "https://example.org/"]
+                );
+            }
+            command => panic!("Unexpected command {:?}", command),
+        },
+        _ => panic!("Should receive one command"),
+    }
+
+    receiver
+        .reply_to_close_tabs(
+            &sender_id,
+            vec!["https://example.com/".to_owned()],
+            vec!["https://example.org/".to_owned()],
+        )
+        .expect("Should reply to the sender");
+
+    let messages = server.take_push_messages(&sender_id);
+    assert_eq!(messages.len(), 1, "Should push the reply to the sender");
+    let events = sender
+        .handle_push_message(&messages[0])
+        .expect("Should handle push message");
+    match events.as_slice() {
+        [AccountEvent::IncomingDeviceCommand(command)] => match command.as_ref() {
+            IncomingDeviceCommand::TabsClosed { sender, payload } => {
+                assert_eq!(sender.as_ref().map(|s| s.id.as_str()), Some(&*receiver_id));
+                assert_eq!(payload.closed, vec!["https://example.com/"]);
+                assert_eq!(payload.not_found, vec!["https://example.org/"]);
+            }
+            command => panic!("Unexpected command {:?}", command),
+        },
+        _ => panic!("Should receive one reply"),
+    }
+}
+
+#[test]
+fn test_pairing() {
+    let (server, uid) = new_account("pairing@example.com");
+    let mut authority = sign_in(
+        server,
+        &uid,
+        &[scopes::PROFILE, scopes::OLD_SYNC, SESSION_SCOPE],
+    );
+    let session_token = authority
+        .get_session_token()
+        .expect("Authority should have a session token");
+    let pairing_url = server
+        .begin_pairing(&session_token)
+        .expect("Should begin pairing");
+
+    let mut supplicant = new_fxa();
+    let url = supplicant
+        .begin_pairing_flow(&pairing_url, &[scopes::PROFILE, scopes::OLD_SYNC])
+        .expect("Should begin pairing flow");
+    let redirect = server
+        .approve_pairing(&url)
+        .expect("Should approve pairing");
+    complete_flow(&mut supplicant, &redirect);
+
+    let key = supplicant
+        .get_access_token(scopes::OLD_SYNC, None)
+        .expect("Should get access token")
+        .key
+        .expect("Should get Sync key");
+    assert_eq!(key.key_bytes().unwrap(), server.sync_key(&uid));
+    assert_eq!(supplicant.get_profile(true).unwrap().uid, uid);
+    assert!(
+        server.approve_pairing(&url).is_err(),
+        "Pairing channels should only be used once"
+    );
+
+    // The authority can see the new client.
+    let clients = authority
+        .get_attached_clients()
+        .expect("Should fetch attached clients");
+    assert_eq!(
+        clients
+            .iter()
+            .filter(|client| client.client_id.as_deref() == Some(CLIENT_ID))
+            .count(),
+        2,
+        "Should list both OAuth clients"
+    );
+    assert!(clients.iter().any(|client| client.is_current_session));
+}
+
+#[test]
+fn test_authorize_code_using_session_token() {
+    let (server, uid) = new_account("session@example.com");
+    let authority = sign_in(server, &uid, &[scopes::PROFILE, SESSION_SCOPE]);
+    let code = authority
+        .authorize_code_using_session_token(CLIENT_ID, scopes::PROFILE, "state", "online")
+        .expect("Should authorize code");
+    assert!(!code.is_empty());
+}
+
+#[test]
+fn test_disconnect() {
+    let (server, uid) = new_account("disconnect@example.com");
+    let mut kept = sign_in_device(server, &uid, "Kept");
+    let mut removed = sign_in_device(server, &uid, "Removed");
+    let kept_id = kept.get_current_device_id().unwrap();
+    let removed_id = removed.get_current_device_id().unwrap();
+    server.take_push_messages(&kept_id);
+
+    removed.disconnect();
+
+    let devices = kept.get_devices(true).expect("Should fetch devices");
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].id, kept_id);
+
+    let messages = server.take_push_messages(&kept_id);
+    assert_eq!(messages.len(), 1);
+    let events = kept.handle_push_message(&messages[0]).unwrap();
+    match events.as_slice() {
+        [AccountEvent::DeviceDisconnected {
+            device_id,
+            is_local_device,
+        }] => {
+            assert_eq!(device_id, &removed_id);
+            assert!(!is_local_device);
+        }
+        _ => panic!("Should tell the other device about the disconnect"),
+    }
+}
+
+#[test]
+fn test_custom_command() {
+    const RING: &str = "https://example.com/cmd/ring";
+    let (server, uid) = new_account("custom-command@example.com");
+    let mut sender = sign_in_device(server, &uid, "Sender");
+    let mut receiver = sign_in_device(server, &uid, "Receiver");
+    let sender_id = sender.get_current_device_id().unwrap();
+    let receiver_id = receiver.get_current_device_id().unwrap();
+
+    sender
+        .send_device_command(RING, &receiver_id, &serde_json::json!({ "volume": 11 }))
+        .expect_err("Should not invoke an unregistered command");
+
+    for fxa in &mut [&mut sender, &mut receiver] {
+        fxa.register_device_command(Arc::new(CustomCommand::new(RING)));
+        fxa.ensure_capabilities(&[
+            Capability::SendTab,
+            Capability::CloseTabs,
+            Capability::Command(RING.to_owned()),
+        ])
+        .expect("Should register the custom command");
+    }
+    server.take_push_messages(&sender_id);
+    server.take_push_messages(&receiver_id);
+
+    sender
+        .send_device_command(RING, &receiver_id, &serde_json::json!({ "volume": 11 }))
+        .expect("Should invoke the custom command");
+
+    let messages = server.take_push_messages(&receiver_id);
+    assert_eq!(messages.len(), 1, "Should push the command to the receiver");
+    let events = receiver
+        .handle_push_message(&messages[0])
+        .expect("Should handle push message");
+    match events.as_slice() {
+        [AccountEvent::IncomingDeviceCommand(command)] => match command.as_ref() {
+            IncomingDeviceCommand::Custom {
+                sender,
+                command,
+                payload,
+            } => {
+                assert_eq!(sender.as_ref().map(|s| s.id.as_str()), Some(&*sender_id));
+                assert_eq!(command, RING);
+                assert_eq!(payload, &serde_json::json!({ "volume": 11 }));
+            }
+            command => panic!("Unexpected command {:?}", command),
+        },
+        _ => panic!("Should receive one command"),
+    }
+
+    // The first payload is too big before it's encrypted, and the second
+    // only after.
+    for size in &[64 * 1024, 30 * 1024] {
+        let err = sender
+            .send_device_command(
+                RING,
+                &receiver_id,
+                &serde_json::json!({ "message": "x".repeat(*size) }),
+            )
+            .expect_err("Should not invoke a command with a large payload");
+        match err.kind() {
+            ErrorKind::CommandPayloadTooLarge(..) => {}
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+    }
+    assert!(
+        server.take_push_messages(&receiver_id).is_empty(),
+        "Should not push large payloads"
+    );
+}
@@ -261,6 +261,25 @@ class DatabaseLoginsStorage(private val dbPath: String) : AutoCloseable, LoginsS
         }
     }
 
+    @Throws(LoginsStorageException::class)
+    override fun importCsv(data: String): JSONObject {
+        return writeQueryCounters.measure {
+            val json = rustCallWithLock { raw, error ->
+                PasswordSyncAdapter.INSTANCE.sync15_passwords_import_csv(raw, data, error)
+            }.getAndConsumeRustString()
+            JSONObject(json)
+        }
+    }
+
+    @Throws(LoginsStorageException::class)
+    override fun exportCsv(): String {
+        return readQueryCounters.measure {
+            rustCallWithLock { raw, error ->
+                PasswordSyncAdapter.INSTANCE.sync15_passwords_export_csv(raw, error)
+            }.getAndConsumeRustString()
+        }
+    }
+
     @Throws(LoginsStorageException::class)
     override fun update(login: ServerPassword) {
         return writeQueryCounters.measure {
@@ -188,6 +188,27 @@ interface LoginsStorage : AutoCloseable {
     @Throws(LoginsStorageException::class)
     fun importLogins(logins: Array<ServerPassword>): JSONObject
 
+    /**
+     * Imports logins from a CSV file exported by Firefox desktop, Chrome,
+     * Bitwarden, 1Password or LastPass. Columns are matched by their headers.
+     *
+     * Returns a JSON object with the import `metrics`, and the `row_errors`
+     * for rows that couldn't be imported, by `line`.
+     *
+     * @throws [InvalidCsvException] if the file has no header, or is missing a required column.
+     * @throws [LoginsStorageException] On unexpected errors (IO failure, rust panics, etc)
+     */
+    @Throws(LoginsStorageException::class)
+    fun importCsv(data: String): JSONObject
+
+    /**
+     * Exports all logins as a CSV file, in the same format as Firefox desktop.
+     *
+     * @throws [LoginsStorageException] On unexpected errors (IO failure, rust panics, etc)
+     */
+    @Throws(LoginsStorageException::class)
+    fun exportCsv(): String
+
     /**
      * Updates the fields in the provided record.
      *
@@ -54,6 +54,13 @@ class RequestFailedException(msg: String) : LoginsStorageException(msg)
  */
 class InterruptedException(msg: String) : LoginsStorageException(msg)
 
+/**
+ * This error is emitted if a CSV file can't be imported, because it's
+ * missing a header row or a required column. Rows that can't be imported
+ * are reported in the import result instead.
+ */
+class InvalidCsvException(msg: String) : LoginsStorageException(msg)
+
 /**
  * A reason a login may be invalid
  */
@@ -75,6 +75,10 @@ internal interface PasswordSyncAdapter : Library {
     // Returns a JSON string containing import metrics
     fun sync15_passwords_import(handle: LoginsDbHandle, data: Pointer, len: Int, error: RustError.ByReference): Pointer?
 
+    // Returns a JSON string containing the import metrics and row errors
+    fun sync15_passwords_import_csv(handle: LoginsDbHandle, data: String, error: RustError.ByReference): Pointer?
+    fun sync15_passwords_export_csv(handle: LoginsDbHandle, error: RustError.ByReference): Pointer?
+
     fun sync15_passwords_destroy_string(p: Pointer)
     fun sync15_passwords_destroy_buffer(b: RustBuffer.ByValue)
 
@@ -14,6 +14,7 @@ import mozilla.appservices.logins.LoginsStorageException
 import mozilla.appservices.logins.NoSuchRecordException
 import mozilla.appservices.logins.RequestFailedException
 import mozilla.appservices.logins.InterruptedException
+import mozilla.appservices.logins.InvalidCsvException
 import mozilla.appservices.logins.SyncAuthInvalidException
 import mozilla.appservices.logins.getAndConsumeRustString
 import mozilla.appservices.logins.getRustString
@@ -51,6 +52,7 @@ open class RustError : Structure() {
             4 -> return InvalidKeyException(message)
             5 -> return RequestFailedException(message)
             6 -> return InterruptedException(message)
+            8 -> return InvalidCsvException(message)
 
             64 -> return InvalidRecordException(message, InvalidLoginReason.EMPTY_ORIGIN)
             65 -> return InvalidRecordException(message, InvalidLoginReason.EMPTY_PASSWORD)
@@ -404,6 +404,33 @@ class DatabaseLoginsStorageTest {
         finishAndClose(test)
     }
 
+    @Test
+    fun testImportExportCsv() {
+        val test = getTestStore()
+        test.unlock(encryptionKey)
+
+        val csv = test.exportCsv()
+        assertTrue(csv.startsWith("\"url\",\"username\",\"password\""))
+        assertTrue(csv.contains("https://www.example.org"))
+
+        test.wipeLocal()
+        val result = test.importCsv(csv)
+        assertEquals(2, result.getJSONObject("metrics").getInt("num_succeeded"))
+        assertEquals(0, result.getJSONArray("row_errors").length())
+        assertEquals(2, test.list().size)
+        assertNotNull(test.get("bbbbbbbbbbbb"))
+
+        val partial = test.importCsv("url,username,password\nhttps://www.example.net,user,\n")
+        assertEquals(1, partial.getJSONArray("row_errors").length())
+        assertEquals(2, partial.getJSONArray("row_errors").getJSONObject(0).getInt("line"))
+
+        expectException(InvalidCsvException::class.java) {
+            test.importCsv("username,password\nuser,hunter2\n")
+        }
+
+        finishAndClose(test)
+    }
+
     @Test
     fun testEnsureValid() {
         val test = getTestStore()
@@ -217,6 +217,29 @@ pub extern "C" fn sync15_passwords_sync(
     })
 }
 
+#[no_mangle]
+pub extern "C" fn sync15_passwords_validate(
+    handle: u64,
+    key_id: FfiStr<'_>,
+    access_token: FfiStr<'_>,
+    sync_key: FfiStr<'_>,
+    tokenserver_url: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("sync15_passwords_validate");
+    ENGINES.call_with_result(error, handle, |state| -> Result<_> {
+        let ping = state.lock().unwrap().validate(
+            &sync15::Sync15StorageClientInit {
+                key_id: key_id.into_string(),
+                access_token: access_token.into_string(),
+                tokenserver_url: parse_url(tokenserver_url.as_str())?,
+            },
+            &sync15::KeyBundle::from_ksync_base64(sync_key.as_str())?,
+        )?;
+        Ok(ping)
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn sync15_passwords_touch(handle: u64, id: FfiStr<'_>, error: &mut ExternError) {
     log::debug!("sync15_passwords_touch");
@@ -414,6 +437,28 @@ pub unsafe extern "C" fn sync15_passwords_import(
     })
 }
 
+/// Imports logins from the contents of a CSV file, returning the
+/// `CsvImportResult` as JSON.
+#[no_mangle]
+pub extern "C" fn sync15_passwords_import_csv(
+    handle: u64,
+    data: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("sync15_passwords_import_csv");
+    ENGINES.call_with_result(error, handle, |state| -> Result<String> {
+        let import_result = state.lock().unwrap().import_csv(data.as_str())?;
+        let result = serde_json::to_string(&import_result)?;
+        Ok(result)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync15_passwords_export_csv(handle: u64, error: &mut ExternError) -> *mut c_char {
+    log::debug!("sync15_passwords_export_csv");
+    ENGINES.call_with_result(error, handle, |state| state.lock().unwrap().export_csv())
+}
+
 /// # Safety
 /// Deref pointer, thus unsafe
 #[no_mangle]
@@ -45,6 +45,10 @@ public enum LoginsStoreError: LocalizedError {
     /// database was invalid.
     case invalidSalt(message: String)
 
+    /// This error is emitted if a CSV file passed to `importCsv` is missing
+    /// a header row or a required column.
+    case invalidCsv(message: String)
+
     /// Our implementation of the localizedError protocol -- (This shows up in Sentry)
     public var errorDescription: String? {
         switch self {
@@ -68,6 +72,8 @@ public enum LoginsStoreError: LocalizedError {
             return "LoginsStoreError.interrupted: \(message)"
         case let .invalidSalt(message):
             return "LoginsStoreError.invalidSalt: \(message)"
+        case let .invalidCsv(message):
+            return "LoginsStoreError.invalidCsv: \(message)"
         }
     }
 
@@ -126,6 +132,9 @@ public enum LoginsStoreError: LocalizedError {
         case Sync15Passwords_InvalidSaltError:
             return .invalidSalt(message: String(freeingRustString: message!))
 
+        case Sync15Passwords_InvalidCsvError:
+            return .invalidCsv(message: String(freeingRustString: message!))
+
         default:
             return .unspecified(message: String(freeingRustString: message!))
         }
@@ -224,6 +224,25 @@ open class LoginsStorage {
         }
     }
 
+    /// Compare the logins on the server with the local logins, without
+    /// changing anything. Returns the sync telemetry "ping" as a JSON string,
+    /// with the problems in the `validation` section of the `passwords`
+    /// engine.
+    open func validate(unlockInfo: SyncUnlockInfo) throws -> String {
+        return try queue.sync {
+            let engine = try self.getUnlocked()
+            let ptr = try LoginsStoreError.unwrap { err in
+                sync15_passwords_validate(engine,
+                                          unlockInfo.kid,
+                                          unlockInfo.fxaAccessToken,
+                                          unlockInfo.syncKey,
+                                          unlockInfo.tokenserverURL,
+                                          err)
+            }
+            return String(freeingRustString: ptr)
+        }
+    }
+
     /// Delete all locally stored login sync metadata. It's unclear if
     /// there's ever a reason for users to call this
     open func reset() throws {
@@ -329,6 +348,34 @@ open class LoginsStorage {
         }
     }
 
+    /// Import logins from a CSV file exported by Firefox desktop, Chrome,
+    /// Bitwarden, 1Password or LastPass. Columns are matched by their headers.
+    ///
+    /// Returns a JSON string with the import `metrics`, and the `row_errors`
+    /// for rows that couldn't be imported, by `line`. Throws
+    /// `LoginStoreError.InvalidCsv` if the file has no header, or is missing
+    /// a required column.
+    open func importCsv(data: String) throws -> String {
+        return try queue.sync {
+            let engine = try self.getUnlocked()
+            let ptr = try LoginsStoreError.unwrap { err in
+                sync15_passwords_import_csv(engine, data, err)
+            }
+            return String(freeingRustString: ptr)
+        }
+    }
+
+    /// Export all logins as a CSV file, in the same format as Firefox desktop.
+    open func exportCsv() throws -> String {
+        return try queue.sync {
+            let engine = try self.getUnlocked()
+            let ptr = try LoginsStoreError.unwrap { err in
+                sync15_passwords_export_csv(engine, err)
+            }
+            return String(freeingRustString: ptr)
+        }
+    }
+
     /// Update `login` in the database. If `login.id` does not refer to a known
     /// login, then this throws `LoginStoreError.NoSuchRecord`.
     open func update(login: LoginRecord) throws {
@@ -16,6 +16,7 @@ typedef enum Sync15PasswordsErrorCode {
     Sync15Passwords_NetworkError     = 5,
     Sync15Passwords_InterruptedError = 6,
     Sync15Passwords_InvalidSaltError = 7,
+    Sync15Passwords_InvalidCsvError  = 8,
 
     Sync15Passwords_InvalidLogin_EmptyOrigin = 64 + 0,
     Sync15Passwords_InvalidLogin_EmptyPassword = 64 + 1,
@@ -91,6 +92,13 @@ char *_Nullable sync15_passwords_sync(Sync15PasswordEngineHandle handle,
                                       char const *_Nonnull token_server_url,
                                       Sync15PasswordsError *_Nonnull error);
 
+char *_Nullable sync15_passwords_validate(Sync15PasswordEngineHandle handle,
+                                          char const *_Nonnull key_id,
+                                          char const *_Nonnull access_token,
+                                          char const *_Nonnull sync_key,
+                                          char const *_Nonnull token_server_url,
+                                          Sync15PasswordsError *_Nonnull error);
+
 void sync15_passwords_wipe(Sync15PasswordEngineHandle handle,
                            Sync15PasswordsError *_Nonnull error);
 
@@ -135,6 +143,13 @@ void sync15_passwords_update(Sync15PasswordEngineHandle handle,
                              int32_t len,
                              Sync15PasswordsError *_Nonnull error);
 
+char *_Nullable sync15_passwords_import_csv(Sync15PasswordEngineHandle handle,
+                                            char const *_Nonnull data,
+                                            Sync15PasswordsError *_Nonnull error);
+
+char *_Nullable sync15_passwords_export_csv(Sync15PasswordEngineHandle handle,
+                                            Sync15PasswordsError *_Nonnull error);
+
 void sync15_passwords_destroy_buffer(Sync15PasswordsRustBuffer bb);
 
 void sync15_passwords_destroy_string(char const *_Nonnull str);
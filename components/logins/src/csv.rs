@@ -0,0 +1,452 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reading and writing logins as CSV files.
+//!
+//! Most browsers and password managers can export saved logins as CSV, but
+//! they don't agree on column names or order. We use the header row to find
+//! the columns we understand, so files exported by Firefox desktop, Chrome,
+//! Bitwarden, 1Password and LastPass can all be imported without any
+//! configuration. Unknown columns, like notes and folders, are ignored.
+//!
+//! We write the same format as Firefox desktop, which keeps enough metadata
+//! for a lossless round trip.
+
+use crate::db::MigrationMetrics;
+use crate::error::*;
+use crate::login::Login;
+use serde_derive::*;
+use std::mem;
+use sync_guid::Guid;
+
+/// The header names we recognize for each login field, lowercased. The first
+/// name is used in error messages.
+const URL_COLUMNS: &[&str] = &["url", "login_uri", "website"];
+const USERNAME_COLUMNS: &[&str] = &["username", "login_username"];
+const PASSWORD_COLUMNS: &[&str] = &["password", "login_password"];
+const HTTP_REALM_COLUMNS: &[&str] = &["httprealm"];
+const FORM_ACTION_ORIGIN_COLUMNS: &[&str] = &["formactionorigin"];
+const GUID_COLUMNS: &[&str] = &["guid"];
+const TIME_CREATED_COLUMNS: &[&str] = &["timecreated"];
+const TIME_LAST_USED_COLUMNS: &[&str] = &["timelastused"];
+const TIME_PASSWORD_CHANGED_COLUMNS: &[&str] = &["timepasswordchanged"];
+// Bitwarden exports secure notes, cards and identities in the same file.
+const TYPE_COLUMNS: &[&str] = &["type"];
+
+// LastPass exports secure notes with this URL.
+const LASTPASS_SECURE_NOTE_URL: &str = "http://sn";
+
+const EXPORT_HEADER: &[&str] = &[
+    "url",
+    "username",
+    "password",
+    "httpRealm",
+    "formActionOrigin",
+    "guid",
+    "timeCreated",
+    "timeLastUsed",
+    "timePasswordChanged",
+];
+
+/// The outcome of importing a CSV file.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct CsvImportResult {
+    pub metrics: MigrationMetrics,
+    /// The rows that weren't imported, in the order they appear in the file.
+    pub row_errors: Vec<CsvRowError>,
+}
+
+/// A row of a CSV file that couldn't be imported.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CsvRowError {
+    /// The line that the row starts on, counting from 1. This is the same as
+    /// the row number in a spreadsheet, unless a field contains a newline.
+    pub line: usize,
+    /// A label for the error, as returned by `Error::label`. This doesn't
+    /// include the row's data, which might be sensitive.
+    pub error: String,
+}
+
+impl CsvRowError {
+    pub(crate) fn new(line: usize, error: &Error) -> Self {
+        CsvRowError {
+            line,
+            error: error.label().into(),
+        }
+    }
+}
+
+/// A login read from a CSV file, before it's been fixed up.
+#[derive(Debug)]
+pub(crate) struct CsvLogin {
+    pub line: usize,
+    pub login: Login,
+}
+
+/// The positions of the columns we understand in a CSV file.
+struct Columns {
+    url: usize,
+    username: Option<usize>,
+    password: usize,
+    http_realm: Option<usize>,
+    form_action_origin: Option<usize>,
+    guid: Option<usize>,
+    time_created: Option<usize>,
+    time_last_used: Option<usize>,
+    time_password_changed: Option<usize>,
+    kind: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> Result<Self> {
+        let names = header
+            .iter()
+            .map(|name| name.trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let find = |candidates: &[&str]| {
+            names
+                .iter()
+                .position(|name| candidates.contains(&name.as_str()))
+        };
+        let required = |candidates: &[&str]| {
+            find(candidates).ok_or_else(|| {
+                ErrorKind::InvalidCsv(format!("Missing a `{}` column", candidates[0]))
+            })
+        };
+        Ok(Columns {
+            url: required(URL_COLUMNS)?,
+            username: find(USERNAME_COLUMNS),
+            password: required(PASSWORD_COLUMNS)?,
+            http_realm: find(HTTP_REALM_COLUMNS),
+            form_action_origin: find(FORM_ACTION_ORIGIN_COLUMNS),
+            guid: find(GUID_COLUMNS),
+            time_created: find(TIME_CREATED_COLUMNS),
+            time_last_used: find(TIME_LAST_USED_COLUMNS),
+            time_password_changed: find(TIME_PASSWORD_CHANGED_COLUMNS),
+            kind: find(TYPE_COLUMNS),
+        })
+    }
+
+    /// Returns the login in `fields`, or `None` if the row is something other
+    /// than a login, like a secure note. Missing fields are treated as empty.
+    fn to_login(&self, fields: &[String]) -> Option<Login> {
+        let get = |index: Option<usize>| {
+            index
+                .and_then(|index| fields.get(index))
+                .map(|field| field.as_str())
+                .unwrap_or_default()
+        };
+        let kind = get(self.kind);
+        let url = get(Some(self.url)).trim();
+        if (!kind.is_empty() && kind != "login") || url == LASTPASS_SECURE_NOTE_URL {
+            return None;
+        }
+        // Every login needs a target. Only Firefox exports them, so we use the
+        // origin for form logins from everywhere else, like desktop does.
+        let http_realm = Some(get(self.http_realm)).filter(|realm| !realm.is_empty());
+        let form_submit_url = match (http_realm, get(self.form_action_origin)) {
+            (Some(_), _) => None,
+            (None, "") => Some(url),
+            (None, origin) => Some(origin),
+        };
+        // Desktop GUIDs are wrapped in braces, which the sync server doesn't
+        // allow, so we replace them with new ones when we insert the login.
+        let guid = Guid::new(get(self.guid));
+        let timestamp = |index| get(index).trim().parse::<i64>().unwrap_or_default().max(0);
+        Some(Login {
+            guid: if guid.is_valid_for_sync_server() {
+                guid
+            } else {
+                Guid::empty()
+            },
+            hostname: url.into(),
+            form_submit_url: form_submit_url.map(Into::into),
+            http_realm: http_realm.map(Into::into),
+            username: get(self.username).into(),
+            password: get(Some(self.password)).into(),
+            time_created: timestamp(self.time_created),
+            time_last_used: timestamp(self.time_last_used),
+            time_password_changed: timestamp(self.time_password_changed),
+            ..Login::default()
+        })
+    }
+}
+
+/// Reads the logins from a CSV file with a header row. Returns an error if
+/// the file can't be parsed, or doesn't have URL and password columns.
+pub(crate) fn read_logins(data: &str) -> Result<Vec<CsvLogin>> {
+    let mut records = parse_records(data)?.into_iter();
+    let columns = match records.next() {
+        Some((_, header)) => Columns::from_header(&header)?,
+        None => throw!(ErrorKind::InvalidCsv("The file is empty".into())),
+    };
+    Ok(records
+        .filter_map(|(line, fields)| {
+            columns
+                .to_login(&fields)
+                .map(|login| CsvLogin { line, login })
+        })
+        .collect())
+}
+
+/// Writes `logins` as a CSV file, with a header row.
+pub(crate) fn write_logins(logins: &[Login]) -> String {
+    let mut csv = String::new();
+    write_record(&mut csv, EXPORT_HEADER.iter().copied());
+    for login in logins {
+        write_record(
+            &mut csv,
+            [
+                login.hostname.as_str(),
+                login.username.as_str(),
+                login.password.as_str(),
+                login.http_realm.as_deref().unwrap_or_default(),
+                login.form_submit_url.as_deref().unwrap_or_default(),
+                login.guid.as_str(),
+                &login.time_created.to_string(),
+                &login.time_last_used.to_string(),
+                &login.time_password_changed.to_string(),
+            ]
+            .iter()
+            .copied(),
+        );
+    }
+    csv
+}
+
+fn write_record<'a>(csv: &mut String, fields: impl Iterator<Item = &'a str>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+        // Always quoting is simpler, and matches desktop.
+        csv.push('"');
+        csv.push_str(&field.replace('"', "\"\""));
+        csv.push('"');
+    }
+    csv.push_str("\r\n");
+}
+
+/// Splits `data` into records of fields, as described in RFC 4180, and
+/// returns each with the line it starts on. Quoted fields can contain commas,
+/// newlines and doubled quotes. We're lenient about line endings, stray
+/// quotes in unquoted fields, and blank lines, which are skipped.
+fn parse_records(data: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let data = data.trim_start_matches('\u{feff}');
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = line;
+    let mut quoted_line = None;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted_line.is_some() {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted_line = None,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted_line = Some(line),
+            ',' => record.push(mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' | '\n' => {
+                record.push(mem::take(&mut field));
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push((record_line, mem::take(&mut record)));
+                }
+                record.clear();
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if let Some(quoted_line) = quoted_line {
+        throw!(ErrorKind::InvalidCsv(format!(
+            "Unterminated quoted field on line {}",
+            quoted_line
+        )));
+    }
+    if !record.is_empty() || !field.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logins(data: &str) -> Vec<(usize, String, String, String)> {
+        read_logins(data)
+            .unwrap()
+            .into_iter()
+            .map(|record| {
+                (
+                    record.line,
+                    record.login.hostname,
+                    record.login.username,
+                    record.login.password,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse_records(
+            "\u{feff}a,b,c\r\n\"quoted, with comma\",\"multi\nline\",\"say \"\"hi\"\"\"\n\n1,,\n",
+        )
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                (1, vec!["a".to_string(), "b".into(), "c".into()]),
+                (
+                    2,
+                    vec![
+                        "quoted, with comma".into(),
+                        "multi\nline".into(),
+                        "say \"hi\"".into()
+                    ]
+                ),
+                (5, vec!["1".into(), "".into(), "".into()]),
+            ]
+        );
+        // No trailing newline.
+        assert_eq!(
+            parse_records("a,b").unwrap(),
+            vec![(1, vec!["a".to_string(), "b".into()])]
+        );
+        match parse_records("a,\"b\nc").unwrap_err().kind() {
+            ErrorKind::InvalidCsv(message) => {
+                assert_eq!(message, "Unterminated quoted field on line 1")
+            }
+            e => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_read_firefox() {
+        let records = read_logins(
+            "\"url\",\"username\",\"password\",\"httpRealm\",\"formActionOrigin\",\"guid\",\"timeCreated\",\"timeLastUsed\",\"timePasswordChanged\"\r\n\
+             \"https://example.com\",\"alice\",\"hunter2\",,\"https://example.com\",\"{5ec0d12f-1b62-4e1c-9b3b-0d3a0e4ef1a1}\",\"1585000000000\",\"1585000100000\",\"1585000200000\"\r\n\
+             \"https://proxy.example.com\",\"bob\",\"secret\",\"My Realm\",,\"aaaaaaaaaaaa\",\"1585000300000\",\"1585000300000\",\"1585000300000\"\r\n",
+        )
+        .unwrap();
+        assert_eq!(records.len(), 2);
+        let form = &records[0].login;
+        assert_eq!(form.hostname, "https://example.com");
+        assert_eq!(form.form_submit_url.as_deref(), Some("https://example.com"));
+        assert_eq!(form.http_realm, None);
+        assert!(form.guid.is_empty());
+        assert_eq!(form.time_created, 1_585_000_000_000);
+        assert_eq!(form.time_last_used, 1_585_000_100_000);
+        assert_eq!(form.time_password_changed, 1_585_000_200_000);
+        let auth = &records[1].login;
+        assert_eq!(auth.form_submit_url, None);
+        assert_eq!(auth.http_realm.as_deref(), Some("My Realm"));
+        assert_eq!(auth.guid, "aaaaaaaaaaaa");
+    }
+
+    #[test]
+    fn test_read_other_managers() {
+        // Chrome
+        assert_eq!(
+            logins("name,url,username,password\nexample.com,https://example.com/login,alice,pw\n"),
+            vec![(
+                2,
+                "https://example.com/login".into(),
+                "alice".into(),
+                "pw".into()
+            )]
+        );
+        // Bitwarden
+        assert_eq!(
+            logins(
+                "folder,favorite,type,name,notes,fields,login_uri,login_username,login_password,login_totp\n\
+                 ,,note,A note,Some text,,,,,\n\
+                 Work,1,login,Example,,,https://example.com,alice,pw,\n"
+            ),
+            vec![(3, "https://example.com".into(), "alice".into(), "pw".into())]
+        );
+        // 1Password
+        assert_eq!(
+            logins("Title,Url,Username,Password,OTPAuth,Favorite,Archived,Tags,Notes\nExample,https://example.com,alice,pw,,false,false,,\n"),
+            vec![(2, "https://example.com".into(), "alice".into(), "pw".into())]
+        );
+        // LastPass
+        assert_eq!(
+            logins(
+                "url,username,password,totp,extra,name,grouping,fav\n\
+                 http://sn,,,,\"NoteType:Server\nHostname:x\",Server,,0\n\
+                 https://example.com/,alice,pw,,,Example,Shopping,0\n"
+            ),
+            vec![(
+                4,
+                "https://example.com/".into(),
+                "alice".into(),
+                "pw".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_read_bad_header() {
+        for data in &["", "name,username,password\n", "url,username\n"] {
+            match read_logins(data).unwrap_err().kind() {
+                ErrorKind::InvalidCsv(_) => {}
+                e => panic!("Unexpected error {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let logins = vec![
+            Login {
+                guid: "aaaaaaaaaaaa".into(),
+                hostname: "https://example.com".into(),
+                form_submit_url: Some("https://example.com".into()),
+                username: "alice".into(),
+                password: "a \"quoted\", multi\nline password".into(),
+                time_created: 1,
+                time_last_used: 2,
+                time_password_changed: 3,
+                ..Login::default()
+            },
+            Login {
+                guid: "bbbbbbbbbbbb".into(),
+                hostname: "https://proxy.example.com".into(),
+                http_realm: Some("My Realm".into()),
+                username: "bob".into(),
+                password: "secret".into(),
+                ..Login::default()
+            },
+        ];
+        let csv = write_logins(&logins);
+        assert!(csv.starts_with(
+            "\"url\",\"username\",\"password\",\"httpRealm\",\"formActionOrigin\",\"guid\",\"timeCreated\",\"timeLastUsed\",\"timePasswordChanged\"\r\n"
+        ));
+        let read = read_logins(&csv)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.login)
+            .collect::<Vec<_>>();
+        assert_eq!(read, logins);
+    }
+}
@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::csv::{self, CsvImportResult, CsvRowError};
 use crate::error::*;
 use crate::login::{LocalLogin, Login, MirrorLogin, SyncLoginData, SyncStatus};
 use crate::schema;
@@ -16,14 +17,15 @@ use rusqlite::{
 use serde_derive::*;
 use sql_support::{self, ConnExt};
 use sql_support::{SqlInterruptHandle, SqlInterruptScope};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::ops::Deref;
 use std::path::Path;
 use std::sync::{atomic::AtomicUsize, Arc};
 use std::time::{Duration, Instant, SystemTime};
 use sync15::{
-    extract_v1_state, telemetry, CollSyncIds, CollectionRequest, IncomingChangeset,
-    OutgoingChangeset, Payload, ServerTimestamp, Store, StoreSyncAssociation,
+    extract_v1_state, telemetry, CollSyncIds, CollectionRequest, DryRun, IncomingChangeset,
+    OutgoingChangeset, Payload, PlannedAction, ProblemKind, RecordPlan, ServerTimestamp, Store,
+    StoreSyncAssociation, ValidationReport,
 };
 use sync_guid::Guid;
 use url::{Host, Url};
@@ -48,6 +50,49 @@ pub struct MigrationMetrics {
     errors: Vec<String>,
 }
 
+impl MigrationMetrics {
+    /// Builds metrics for an import of `num_processed` logins, from the
+    /// duration and error labels of the fixup and insert phases. Each
+    /// error label counts as one failed login.
+    fn new(
+        num_processed: u64,
+        (fixup_duration, fixup_errors): (Duration, Vec<String>),
+        (insert_duration, insert_errors): (Duration, Vec<String>),
+    ) -> Self {
+        let num_failed_fixup = fixup_errors.len() as u64;
+        let num_failed_insert = insert_errors.len() as u64;
+        let num_post_fixup = num_processed - num_failed_fixup;
+        let num_failed = num_failed_fixup + num_failed_insert;
+        let mut all_errors = Vec::new();
+        all_errors.extend(fixup_errors.clone());
+        all_errors.extend(insert_errors.clone());
+        MigrationMetrics {
+            fixup_phase: MigrationPhaseMetrics {
+                num_processed,
+                num_succeeded: num_post_fixup,
+                num_failed: num_failed_fixup,
+                total_duration: fixup_duration.as_millis(),
+                errors: fixup_errors,
+            },
+            insert_phase: MigrationPhaseMetrics {
+                num_processed: num_post_fixup,
+                num_succeeded: num_post_fixup - num_failed_insert,
+                num_failed: num_failed_insert,
+                total_duration: insert_duration.as_millis(),
+                errors: insert_errors,
+            },
+            num_processed,
+            num_succeeded: num_processed - num_failed,
+            num_failed,
+            total_duration: fixup_duration
+                .checked_add(insert_duration)
+                .unwrap_or_else(|| Duration::new(0, 0))
+                .as_millis(),
+            errors: all_errors,
+        }
+    }
+}
+
 pub struct LoginDb {
     pub db: Connection,
     interrupt_counter: Arc<AtomicUsize>,
@@ -497,9 +542,18 @@ impl LoginDb {
     }
 
     pub fn add(&self, login: Login) -> Result<Login> {
-        let mut login = self.fixup_and_check_for_dupes(login)?;
+        let login = self.fixup_and_check_for_dupes(login)?;
 
         let tx = self.unchecked_transaction()?;
+        let login = self.insert_new_login(login)?;
+        tx.commit()?;
+        Ok(login)
+    }
+
+    /// Inserts a login that has already been fixed up and checked for dupes,
+    /// filling in a GUID and metadata if they're missing. Callers are
+    /// responsible for starting a transaction.
+    fn insert_new_login(&self, mut login: Login) -> Result<Login> {
         let now_ms = util::system_time_ms_i64(SystemTime::now());
 
         // Allow an empty GUID to be passed to indicate that we should generate
@@ -585,7 +639,6 @@ impl LoginDb {
             );
             throw!(ErrorKind::DuplicateGuid(login.guid.into_string()));
         }
-        tx.commit()?;
         Ok(login)
     }
 
@@ -638,8 +691,6 @@ impl LoginDb {
             new = SyncStatus::New as u8
         );
         let import_start_total_logins: u64 = logins.len() as u64;
-        let mut num_failed_fixup: u64 = 0;
-        let mut num_failed_insert: u64 = 0;
         let mut fixup_phase_duration = Duration::new(0, 0);
         let mut fixup_errors: Vec<String> = Vec::new();
         let mut insert_errors: Vec<String> = Vec::new();
@@ -664,7 +715,6 @@ impl LoginDb {
                 Err(e) => {
                     log::warn!("Skipping login {} as it is invalid ({}).", login.guid, e);
                     fixup_errors.push(e.label().into());
-                    num_failed_fixup += 1;
                     continue;
                 }
             };
@@ -698,46 +748,20 @@ impl LoginDb {
                 Err(e) => {
                     log::warn!("Could not import {} ({}).", old_guid, e);
                     insert_errors.push(Error::from(e).label().into());
-                    num_failed_insert += 1;
                 }
             };
         }
         tx.commit()?;
 
-        let num_post_fixup = import_start_total_logins - num_failed_fixup;
-        let num_failed = num_failed_fixup + num_failed_insert;
         let insert_phase_duration = import_start
             .elapsed()
             .checked_sub(fixup_phase_duration)
             .unwrap_or_else(|| Duration::new(0, 0));
-        let mut all_errors = Vec::new();
-        all_errors.extend(fixup_errors.clone());
-        all_errors.extend(insert_errors.clone());
-
-        let metrics = MigrationMetrics {
-            fixup_phase: MigrationPhaseMetrics {
-                num_processed: import_start_total_logins,
-                num_succeeded: num_post_fixup,
-                num_failed: num_failed_fixup,
-                total_duration: fixup_phase_duration.as_millis(),
-                errors: fixup_errors,
-            },
-            insert_phase: MigrationPhaseMetrics {
-                num_processed: num_post_fixup,
-                num_succeeded: num_post_fixup - num_failed_insert,
-                num_failed: num_failed_insert,
-                total_duration: insert_phase_duration.as_millis(),
-                errors: insert_errors,
-            },
-            num_processed: import_start_total_logins,
-            num_succeeded: import_start_total_logins - num_failed,
-            num_failed,
-            total_duration: fixup_phase_duration
-                .checked_add(insert_phase_duration)
-                .unwrap_or_else(|| Duration::new(0, 0))
-                .as_millis(),
-            errors: all_errors,
-        };
+        let metrics = MigrationMetrics::new(
+            import_start_total_logins,
+            (fixup_phase_duration, fixup_errors),
+            (insert_phase_duration, insert_errors),
+        );
         log::info!(
             "Finished importing logins with the following metrics: {:#?}",
             metrics
@@ -745,6 +769,64 @@ impl LoginDb {
         Ok(metrics)
     }
 
+    /// Imports logins from a CSV file exported by Firefox, Chrome, or another
+    /// password manager. Unlike `import_multiple`, this adds to any existing
+    /// logins. Rows that are invalid or duplicate existing logins are skipped,
+    /// and reported by line in the result.
+    pub fn import_csv(&self, data: &str) -> Result<CsvImportResult> {
+        let records = csv::read_logins(data)?;
+        let tx = self.unchecked_transaction()?;
+        let mut fixup_phase_duration = Duration::new(0, 0);
+        let mut insert_phase_duration = Duration::new(0, 0);
+        let mut fixup_errors: Vec<String> = Vec::new();
+        let mut insert_errors: Vec<String> = Vec::new();
+        let mut row_errors = Vec::new();
+        let num_processed = records.len() as u64;
+
+        for record in records {
+            let fixup_start = Instant::now();
+            let maybe_fixed_login = self.fixup_and_check_for_dupes(record.login);
+            fixup_phase_duration += fixup_start.elapsed();
+            let login = match maybe_fixed_login {
+                Ok(login) => login,
+                Err(e) => {
+                    log::warn!("Skipping line {} as it is invalid ({}).", record.line, e);
+                    fixup_errors.push(e.label().into());
+                    row_errors.push(CsvRowError::new(record.line, &e));
+                    continue;
+                }
+            };
+            let insert_start = Instant::now();
+            let inserted = self.insert_new_login(login);
+            insert_phase_duration += insert_start.elapsed();
+            if let Err(e) = inserted {
+                log::warn!("Could not import line {} ({}).", record.line, e);
+                insert_errors.push(e.label().into());
+                row_errors.push(CsvRowError::new(record.line, &e));
+            }
+        }
+        tx.commit()?;
+
+        let metrics = MigrationMetrics::new(
+            num_processed,
+            (fixup_phase_duration, fixup_errors),
+            (insert_phase_duration, insert_errors),
+        );
+        log::info!(
+            "Finished importing CSV logins with the following metrics: {:#?}",
+            metrics
+        );
+        Ok(CsvImportResult {
+            metrics,
+            row_errors,
+        })
+    }
+
+    /// Exports all logins as a CSV file, in the same format as Firefox desktop.
+    pub fn export_csv(&self) -> Result<String> {
+        Ok(csv::write_logins(&self.get_all()?))
+    }
+
     pub fn update(&self, login: Login) -> Result<()> {
         let login = self.fixup_and_check_for_dupes(login)?;
 
@@ -1074,40 +1156,50 @@ impl LoginDb {
             } else {
                 log::debug!("Processing inbound deletion (always prefer)");
                 plan.plan_delete(record.guid.clone());
+                plan.actions
+                    .push(RecordPlan::new(record.guid.clone(), PlannedAction::Delete));
                 continue;
             };
             let upstream_time = record.inbound.1;
-            match (record.mirror.take(), record.local.take()) {
+            let action = match (record.mirror.take(), record.local.take()) {
                 (Some(mirror), Some(local)) => {
                     log::debug!("  Conflict between remote and local, Resolving with 3WM");
                     plan.plan_three_way_merge(local, mirror, upstream, upstream_time, server_now);
                     telem.reconciled(1);
+                    PlannedAction::Conflict
                 }
                 (Some(_mirror), None) => {
                     log::debug!("  Forwarding mirror to remote");
                     plan.plan_mirror_update(upstream, upstream_time);
                     telem.applied(1);
+                    PlannedAction::Apply
                 }
                 (None, Some(local)) => {
                     log::debug!("  Conflicting record without shared parent, using newer");
                     plan.plan_two_way_merge(&local.login, (upstream, upstream_time));
                     telem.reconciled(1);
+                    PlannedAction::Conflict
                 }
                 (None, None) => {
-                    if let Some(dupe) = self.find_dupe(&upstream)? {
+                    let action = if let Some(dupe) = self.find_dupe(&upstream)? {
                         log::debug!(
                             "  Incoming record {} was is a dupe of local record {}",
                             upstream.guid,
                             dupe.guid
                         );
                         plan.plan_two_way_merge(&dupe, (upstream, upstream_time));
+                        PlannedAction::Conflict
                     } else {
                         log::debug!("  No dupe found, inserting into mirror");
                         plan.plan_mirror_insert(upstream, upstream_time, false);
-                    }
+                        PlannedAction::Apply
+                    };
                     telem.applied(1);
+                    action
                 }
-            }
+            };
+            plan.actions
+                .push(RecordPlan::new(record.guid.clone(), action));
         }
         Ok(plan)
     }
@@ -1168,6 +1260,86 @@ impl LoginDb {
         Ok(self.fetch_outgoing(inbound.timestamp, scope)?)
     }
 
+    fn do_apply_incoming_dry_run(
+        &self,
+        inbound: IncomingChangeset,
+        telem: &mut telemetry::Engine,
+        scope: &SqlInterruptScope,
+    ) -> Result<DryRun> {
+        let mut incoming_telemetry = telemetry::EngineIncoming::new();
+        let data = self.fetch_login_data(&inbound.changes, &mut incoming_telemetry, scope)?;
+        let mut plan = {
+            let result = self.reconcile(data, inbound.timestamp, &mut incoming_telemetry, scope);
+            telem.incoming(incoming_telemetry);
+            result
+        }?;
+        // Execute the plan so that we can see what we'd upload, then throw
+        // it all away.
+        let tx = self.db.unchecked_transaction()?;
+        plan.execute(&tx, scope)?;
+        let outgoing = self.fetch_outgoing(inbound.timestamp, scope)?;
+        tx.rollback()?;
+        Ok(DryRun {
+            incoming: std::mem::take(&mut plan.actions),
+            outgoing,
+        })
+    }
+
+    /// Compares the server's records with the logins that the user sees:
+    /// local changes where we have them, and the mirror otherwise. Local
+    /// changes that haven't been uploaded yet show up as problems, too.
+    fn do_validate(
+        &self,
+        records: Vec<(Payload, ServerTimestamp)>,
+        scope: &SqlInterruptScope,
+    ) -> Result<ValidationReport> {
+        let mut report = ValidationReport::new(VALIDATION_VERSION);
+        // Sorted, so that logins missing on the server are reported in a
+        // stable order.
+        let mut local = BTreeMap::new();
+        {
+            let mut stmt = self.db.prepare(&GET_ALL_SQL)?;
+            let rows = stmt.query_and_then(NO_PARAMS, Login::from_row)?;
+            for login in rows {
+                let login = login?;
+                local.insert(login.guid.clone(), login);
+            }
+        }
+        scope.err_if_interrupted()?;
+
+        let mut seen = HashSet::with_capacity(records.len());
+        for (payload, ts) in records {
+            if !seen.insert(payload.id.clone()) {
+                report.problem(ProblemKind::Duplicate, payload.id);
+                continue;
+            }
+            report.checked += 1;
+            let data = match SyncLoginData::from_payload(payload, ts) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!("Failed to deserialize record: {}", e);
+                    continue;
+                }
+            };
+            match (data.inbound.0, local.remove(&data.guid)) {
+                (Some(remote), Some(local)) => {
+                    report.difference(data.guid, login_differences(&remote, &local));
+                }
+                (Some(_), None) => {
+                    report.problem(ProblemKind::ClientMissing, data.guid);
+                }
+                (None, Some(_)) => {
+                    report.problem(ProblemKind::ServerMissing, data.guid);
+                }
+                (None, None) => {}
+            }
+        }
+        for (guid, _) in local {
+            report.problem(ProblemKind::ServerMissing, guid);
+        }
+        Ok(report)
+    }
+
     fn put_meta(&self, key: &str, value: &dyn ToSql) -> Result<()> {
         self.execute_named_cached(
             "REPLACE INTO loginsSyncMeta (key, value) VALUES (:key, :value)",
@@ -1267,6 +1439,29 @@ impl<'a> Store for LoginStore<'a> {
         Ok(self.db.do_apply_incoming(inbound, telem, &self.scope)?)
     }
 
+    fn apply_incoming_dry_run(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<DryRun> {
+        assert_eq!(inbound.len(), 1, "logins only requests one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        Ok(self
+            .db
+            .do_apply_incoming_dry_run(inbound, telem, &self.scope)?)
+    }
+
+    fn supports_validation(&self) -> bool {
+        true
+    }
+
+    fn validate(
+        &self,
+        records: Vec<(Payload, ServerTimestamp)>,
+    ) -> anyhow::Result<ValidationReport> {
+        Ok(self.db.do_validate(records, &self.scope)?)
+    }
+
     fn sync_finished(
         &self,
         new_timestamp: ServerTimestamp,
@@ -1313,6 +1508,39 @@ impl<'a> Store for LoginStore<'a> {
     }
 }
 
+/// The version of the logins validator, for telemetry. Bump this when the
+/// validator changes what it reports.
+const VALIDATION_VERSION: u32 = 1;
+
+/// Returns the names of the fields that differ between two versions of a
+/// login. Timestamps and usage counts aren't compared, since they're
+/// expected to drift.
+fn login_differences(remote: &Login, local: &Login) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if remote.hostname != local.hostname {
+        fields.push("hostname");
+    }
+    if remote.form_submit_url != local.form_submit_url {
+        fields.push("formSubmitURL");
+    }
+    if remote.http_realm != local.http_realm {
+        fields.push("httpRealm");
+    }
+    if remote.username != local.username {
+        fields.push("username");
+    }
+    if remote.password != local.password {
+        fields.push("password");
+    }
+    if remote.username_field != local.username_field {
+        fields.push("usernameField");
+    }
+    if remote.password_field != local.password_field {
+        fields.push("passwordField");
+    }
+    fields
+}
+
 lazy_static! {
     static ref GET_ALL_SQL: String = format!(
         "SELECT {common_cols} FROM loginsL WHERE is_deleted = 0
@@ -1350,6 +1578,7 @@ lazy_static! {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sync15::ValidationProblem;
     #[test]
     fn test_bad_record() {
         let db = LoginDb::open_in_memory(Some("testing")).unwrap();
@@ -1396,6 +1625,172 @@ mod tests {
         assert_eq!(res[1].guid, "dummy_000003");
     }
 
+    #[test]
+    fn test_apply_incoming_dry_run() {
+        let db = LoginDb::open_in_memory(Some("testing")).unwrap();
+        db.add(Login {
+            guid: "dummy_000001".into(),
+            hostname: "https://www.example.com".into(),
+            http_realm: Some("https://www.example.com".into()),
+            username: "test".into(),
+            password: "test".into(),
+            ..Login::default()
+        })
+        .unwrap();
+
+        let mut inbound = IncomingChangeset::new("passwords", ServerTimestamp(10000));
+        inbound.changes = vec![
+            // Changed locally and remotely.
+            (
+                Payload::from_json(serde_json::json!({
+                    "id": "dummy_000001",
+                    "hostname": "https://www.example.com",
+                    "httpRealm": "https://www.example.com",
+                    "username": "test",
+                    "password": "remote",
+                }))
+                .unwrap(),
+                ServerTimestamp(10000),
+            ),
+            // New remotely.
+            (
+                Payload::from_json(serde_json::json!({
+                    "id": "dummy_000002",
+                    "hostname": "https://www.example.org",
+                    "formSubmitURL": "https://www.example.org/submit",
+                    "username": "test",
+                    "password": "test",
+                }))
+                .unwrap(),
+                ServerTimestamp(10000),
+            ),
+            // Deleted remotely.
+            (
+                Payload::new_tombstone("dummy_000003"),
+                ServerTimestamp(10000),
+            ),
+        ];
+
+        let store = LoginStore::new(&db);
+        let mut telem = telemetry::Engine::new("passwords");
+        let dry_run = store
+            .apply_incoming_dry_run(vec![inbound], &mut telem)
+            .unwrap();
+        assert_eq!(
+            dry_run.incoming,
+            vec![
+                RecordPlan::new("dummy_000001", PlannedAction::Conflict),
+                RecordPlan::new("dummy_000002", PlannedAction::Apply),
+                RecordPlan::new("dummy_000003", PlannedAction::Delete),
+            ]
+        );
+        let outgoing = dry_run
+            .outgoing
+            .changes
+            .iter()
+            .map(|payload| payload.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(outgoing, vec!["dummy_000001"]);
+
+        // Nothing should have changed.
+        let mirror_count: i64 = db.query_one("SELECT COUNT(*) FROM loginsM").unwrap();
+        assert_eq!(mirror_count, 0);
+        let all = db.get_all().unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].password, "test");
+    }
+
+    fn login_payload(guid: &str, password: &str) -> Payload {
+        Payload::from_json(serde_json::json!({
+            "id": guid,
+            "hostname": "https://www.example.com",
+            "httpRealm": "https://www.example.com",
+            "username": guid,
+            "password": password,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let db = LoginDb::open_in_memory(Some("testing")).unwrap();
+        let store = LoginStore::new(&db);
+        let mut inbound = IncomingChangeset::new("passwords", ServerTimestamp(10000));
+        inbound.changes = [
+            "dummy_000001",
+            "dummy_000002",
+            "dummy_000003",
+            "dummy_000004",
+        ]
+        .iter()
+        .map(|guid| (login_payload(guid, "test"), ServerTimestamp(10000)))
+        .collect();
+        let mut telem = telemetry::Engine::new("passwords");
+        store.apply_incoming(vec![inbound], &mut telem).unwrap();
+
+        // Local changes that haven't been uploaded yet are compared, too.
+        let mut changed = db.get_by_id("dummy_000003").unwrap().unwrap();
+        changed.password = "changed".into();
+        db.update(changed).unwrap();
+        db.add(Login {
+            guid: "dummy_000010".into(),
+            hostname: "https://www.example.org".into(),
+            http_realm: Some("https://www.example.org".into()),
+            username: "test".into(),
+            password: "test".into(),
+            ..Login::default()
+        })
+        .unwrap();
+
+        let ts = ServerTimestamp(10000);
+        let report = store
+            .validate(vec![
+                (login_payload("dummy_000001", "test"), ts),
+                (login_payload("dummy_000001", "test"), ts),
+                (login_payload("dummy_000002", "remote"), ts),
+                (login_payload("dummy_000003", "test"), ts),
+                (login_payload("dummy_000005", "test"), ts),
+                (Payload::new_tombstone("dummy_000006"), ts),
+            ])
+            .unwrap();
+        assert_eq!(report.checked, 5);
+        assert_eq!(
+            report.problems,
+            vec![
+                ValidationProblem {
+                    kind: ProblemKind::Duplicate,
+                    id: "dummy_000001".into(),
+                    fields: vec![],
+                },
+                ValidationProblem {
+                    kind: ProblemKind::Difference,
+                    id: "dummy_000002".into(),
+                    fields: vec!["password"],
+                },
+                ValidationProblem {
+                    kind: ProblemKind::Difference,
+                    id: "dummy_000003".into(),
+                    fields: vec!["password"],
+                },
+                ValidationProblem {
+                    kind: ProblemKind::ClientMissing,
+                    id: "dummy_000005".into(),
+                    fields: vec![],
+                },
+                ValidationProblem {
+                    kind: ProblemKind::ServerMissing,
+                    id: "dummy_000004".into(),
+                    fields: vec![],
+                },
+                ValidationProblem {
+                    kind: ProblemKind::ServerMissing,
+                    id: "dummy_000010".into(),
+                    fields: vec![],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_check_valid_with_no_dupes() {
         let db = LoginDb::open_in_memory(Some("testing")).unwrap();
@@ -1911,6 +2306,89 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_import_csv() {
+        let db = LoginDb::open_in_memory(Some("testing")).unwrap();
+        db.add(Login {
+            hostname: "https://www.example.com".into(),
+            form_submit_url: Some("https://www.example.com".into()),
+            username: "existing".into(),
+            password: "password".into(),
+            ..Login::default()
+        })
+        .unwrap();
+
+        let result = db
+            .import_csv(
+                "name,url,username,password\n\
+                 Example,https://www.example.com/login,new,password\n\
+                 Example,https://www.example.com/login,existing,password\n\
+                 No password,https://www.example2.com/login,new,\n\
+                 Example,https://www.example.com/signin,new,password2\n\
+                 Bad URL,not a url,new,password\n",
+            )
+            .unwrap();
+        assert_eq!(
+            result.row_errors,
+            vec![
+                CsvRowError {
+                    line: 3,
+                    error: "InvalidLogin::DuplicateLogin".into(),
+                },
+                CsvRowError {
+                    line: 4,
+                    error: "InvalidLogin::EmptyPassword".into(),
+                },
+                // Duplicates within the file are caught, too.
+                CsvRowError {
+                    line: 5,
+                    error: "InvalidLogin::DuplicateLogin".into(),
+                },
+                CsvRowError {
+                    line: 6,
+                    error: "InvalidLogin::IllegalFieldValue".into(),
+                },
+            ]
+        );
+        let metrics = &result.metrics;
+        assert_eq!(metrics.num_processed, 5);
+        assert_eq!(metrics.num_succeeded, 1);
+        assert_eq!(metrics.num_failed, 4);
+        assert_eq!(metrics.fixup_phase.num_failed, 4);
+        assert_eq!(metrics.insert_phase.num_processed, 1);
+        assert_eq!(metrics.insert_phase.num_succeeded, 1);
+
+        let mut logins = db.get_all().unwrap();
+        logins.sort_by(|a, b| a.username.cmp(&b.username));
+        assert_eq!(logins.len(), 2);
+        let imported = &logins[1];
+        assert_eq!(imported.hostname, "https://www.example.com");
+        assert_eq!(
+            imported.form_submit_url.as_deref(),
+            Some("https://www.example.com")
+        );
+        assert_eq!(imported.username, "new");
+        assert!(imported.guid.is_valid_for_sync_server());
+        assert_ne!(imported.time_created, 0);
+
+        // Exported logins can be imported into another store as-is.
+        let other = LoginDb::open_in_memory(Some("testing")).unwrap();
+        let result = other.import_csv(&db.export_csv().unwrap()).unwrap();
+        assert!(result.row_errors.is_empty());
+        let mut exported = other.get_all().unwrap();
+        exported.sort_by(|a, b| a.username.cmp(&b.username));
+        assert_eq!(exported, logins);
+
+        match db
+            .import_csv("name,username,password\n")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::InvalidCsv(_) => {}
+            e => panic!("Unexpected error {:?}", e),
+        }
+    }
+
     #[test]
     fn test_open_with_salt_create_db() {
         let dir = tempdir::TempDir::new("open_with_salt").unwrap();
@@ -1,6 +1,7 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use crate::csv::CsvImportResult;
 use crate::db::{LoginDb, LoginStore, MigrationMetrics};
 use crate::error::*;
 use crate::login::Login;
@@ -8,7 +9,7 @@ use std::cell::Cell;
 use std::path::Path;
 use sync15::{
     sync_multiple, telemetry, KeyBundle, MemoryCachedState, StoreSyncAssociation,
-    Sync15StorageClientInit,
+    Sync15StorageClientInit, SyncRequestInfo,
 };
 
 // This isn't really an engine in the firefox sync15 desktop sense -- it's
@@ -97,6 +98,14 @@ impl PasswordEngine {
         self.db.import_multiple(logins)
     }
 
+    pub fn import_csv(&self, data: &str) -> Result<CsvImportResult> {
+        self.db.import_csv(data)
+    }
+
+    pub fn export_csv(&self) -> Result<String> {
+        self.db.export_csv()
+    }
+
     pub fn disable_mem_security(&self) -> Result<()> {
         self.db.disable_mem_security()
     }
@@ -120,6 +129,33 @@ impl PasswordEngine {
         &self,
         storage_init: &Sync15StorageClientInit,
         root_sync_key: &KeyBundle,
+    ) -> Result<telemetry::SyncTelemetryPing> {
+        self.sync_with_info(storage_init, root_sync_key, None)
+    }
+
+    /// Compares the logins on the server with the local logins, without
+    /// changing anything. The problems are reported in the `validation`
+    /// section of the returned ping's `passwords` engine.
+    pub fn validate(
+        &self,
+        storage_init: &Sync15StorageClientInit,
+        root_sync_key: &KeyBundle,
+    ) -> Result<telemetry::SyncTelemetryPing> {
+        self.sync_with_info(
+            storage_init,
+            root_sync_key,
+            Some(SyncRequestInfo {
+                validate: true,
+                ..SyncRequestInfo::default()
+            }),
+        )
+    }
+
+    fn sync_with_info(
+        &self,
+        storage_init: &Sync15StorageClientInit,
+        root_sync_key: &KeyBundle,
+        req_info: Option<SyncRequestInfo<'_>>,
     ) -> Result<telemetry::SyncTelemetryPing> {
         // migrate our V1 state - this needn't live for long.
         self.db.migrate_global_state()?;
@@ -135,7 +171,7 @@ impl PasswordEngine {
             storage_init,
             root_sync_key,
             &store.scope,
-            None,
+            req_info,
         );
         // We always update the state - sync_multiple does the right thing
         // if it needs to be dropped (ie, they will be None or contain Nones etc)
@@ -31,6 +31,9 @@ pub enum ErrorKind {
     #[error("The provided salt is invalid")]
     InvalidSalt,
 
+    #[error("Invalid CSV file: {0}")]
+    InvalidCsv(String),
+
     #[error("Error synchronizing: {0}")]
     SyncAdapterError(#[from] sync15::Error),
 
@@ -89,6 +92,7 @@ impl Error {
             ErrorKind::NoSuchRecord(_) => "NoSuchRecord",
             ErrorKind::NonEmptyTable => "NonEmptyTable",
             ErrorKind::InvalidSalt => "InvalidSalt",
+            ErrorKind::InvalidCsv(_) => "InvalidCsv",
             ErrorKind::SyncAdapterError(_) => "SyncAdapterError",
             ErrorKind::JsonError(_) => "JsonError",
             ErrorKind::UrlParseError(_) => "UrlParseError",
@@ -39,6 +39,10 @@ pub mod error_codes {
     /// An invalid salt was provided.
     pub const INVALID_SALT: i32 = 7;
 
+    /// A CSV file couldn't be imported, because it's missing a header or a
+    /// required column.
+    pub const INVALID_CSV: i32 = 8;
+
     // Skip a bunch of spaces to make it clear these are part of a group,
     // even as more and more errors get added. We're only exposing the
     // InvalidLogin items that can actually be triggered, the others
@@ -111,6 +115,11 @@ fn get_code(err: &Error) -> ErrorCode {
             ErrorCode::new(error_codes::INVALID_SALT)
         }
 
+        ErrorKind::InvalidCsv(_) => {
+            log::warn!("Invalid CSV file");
+            ErrorCode::new(error_codes::INVALID_CSV)
+        }
+
         err => {
             log::error!("Unexpected error: {:?}", err);
             ErrorCode::new(error_codes::UNEXPECTED)
@@ -9,6 +9,7 @@
 mod error;
 mod login;
 
+mod csv;
 mod db;
 mod engine;
 pub mod schema;
@@ -18,8 +19,10 @@ mod util;
 mod ffi;
 
 // Mostly exposed for the sync manager.
+pub use crate::csv::{CsvImportResult, CsvRowError};
 pub use crate::db::LoginDb;
 pub use crate::db::LoginStore;
+pub use crate::db::MigrationMetrics;
 pub use crate::engine::*;
 pub use crate::error::*;
 pub use crate::login::*;
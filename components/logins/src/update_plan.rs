@@ -8,7 +8,7 @@ use crate::util;
 use rusqlite::{named_params, Connection};
 use sql_support::SqlInterruptScope;
 use std::time::SystemTime;
-use sync15::ServerTimestamp;
+use sync15::{RecordPlan, ServerTimestamp};
 use sync_guid::Guid;
 
 #[derive(Default, Debug, Clone)]
@@ -19,6 +19,8 @@ pub(crate) struct UpdatePlan {
     // the bool is the `is_overridden` flag, the i64 is ServerTimestamp in millis
     pub mirror_inserts: Vec<(Login, i64, bool)>,
     pub mirror_updates: Vec<(Login, i64)>,
+    // What we planned for each incoming record, for dry runs.
+    pub actions: Vec<RecordPlan>,
 }
 
 impl UpdatePlan {
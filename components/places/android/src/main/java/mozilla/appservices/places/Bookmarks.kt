@@ -99,7 +99,12 @@ data class BookmarkItem(
      * Note that the bookmark storage layer treats NULL and the
      * empty string as equivalent in titles.
      */
-    val title: String
+    val title: String,
+
+    /**
+     * The URL of the bookmarked page's icon, if we have one stored.
+     */
+    val iconUrl: String? = null
 ) : BookmarkTreeNode() {
     override val type get() = BookmarkType.Bookmark
 }
@@ -491,7 +496,8 @@ internal fun unpackProtobuf(msg: MsgTypes.BookmarkNode): BookmarkTreeNode {
                     dateAdded = dateAdded,
                     lastModified = lastModified,
                     title = title,
-                    url = msg.url
+                    url = msg.url,
+                    iconUrl = if (msg.hasIconUrl()) { msg.iconUrl } else { null }
             )
         }
 
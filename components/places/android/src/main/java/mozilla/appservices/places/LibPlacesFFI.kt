@@ -285,6 +285,35 @@ internal interface LibPlacesFFI : Library {
         error: RustError.ByReference
     )
 
+    fun places_set_icons_for_page(
+        handle: PlacesConnectionHandle,
+        pageUrl: String,
+        data: Pointer,
+        len: Int,
+        error: RustError.ByReference
+    )
+
+    fun places_set_root_icon(
+        handle: PlacesConnectionHandle,
+        data: Pointer,
+        len: Int,
+        error: RustError.ByReference
+    )
+
+    fun places_get_icon_for_page(
+        handle: PlacesConnectionHandle,
+        pageUrl: String,
+        preferredWidth: Int,
+        error: RustError.ByReference
+    ): RustBuffer.ByValue
+
+    fun places_get_icon(
+        handle: PlacesConnectionHandle,
+        iconUrl: String,
+        preferredWidth: Int,
+        error: RustError.ByReference
+    ): RustBuffer.ByValue
+
     /** Destroy strings returned from libplaces_ffi calls. */
     fun places_destroy_string(s: Pointer)
 
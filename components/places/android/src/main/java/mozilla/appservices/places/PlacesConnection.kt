@@ -4,6 +4,7 @@
 
 package mozilla.appservices.places
 
+import com.google.protobuf.ByteString
 import com.sun.jna.Native
 import com.sun.jna.Pointer
 import com.sun.jna.StringArray
@@ -487,6 +488,32 @@ open class PlacesReaderConnection internal constructor(connHandle: Long) :
         }
     }
 
+    override fun getIconForPage(pageUrl: String, preferredWidth: Int): Icon? {
+        val rustBuf = rustCall { err ->
+            LibPlacesFFI.INSTANCE.places_get_icon_for_page(this.handle.get(), pageUrl, preferredWidth, err)
+        }
+        try {
+            return rustBuf.asCodedInputStream()?.let { stream ->
+                Icon.fromMessage(MsgTypes.Icon.parseFrom(stream))
+            }
+        } finally {
+            LibPlacesFFI.INSTANCE.places_destroy_bytebuffer(rustBuf)
+        }
+    }
+
+    override fun getIcon(iconUrl: String, preferredWidth: Int): Icon? {
+        val rustBuf = rustCall { err ->
+            LibPlacesFFI.INSTANCE.places_get_icon(this.handle.get(), iconUrl, preferredWidth, err)
+        }
+        try {
+            return rustBuf.asCodedInputStream()?.let { stream ->
+                Icon.fromMessage(MsgTypes.Icon.parseFrom(stream))
+            }
+        } finally {
+            LibPlacesFFI.INSTANCE.places_destroy_bytebuffer(rustBuf)
+        }
+    }
+
     private val readQueryCounters: PlacesManagerCounterMetrics by lazy {
         PlacesManagerCounterMetrics(
             PlacesManagerMetrics.readQueryCount,
@@ -672,6 +699,28 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
         }
     }
 
+    override fun setIconsForPage(pageUrl: String, icons: List<Icon>) {
+        val builder = MsgTypes.IconList.newBuilder()
+        icons.forEach { builder.addIcons(it.toMessage()) }
+        val (nioBuf, len) = builder.build().toNioDirectBuffer()
+        writeQueryCounters.measure {
+            rustCall { err ->
+                val ptr = Native.getDirectBufferPointer(nioBuf)
+                LibPlacesFFI.INSTANCE.places_set_icons_for_page(this.handle.get(), pageUrl, ptr, len, err)
+            }
+        }
+    }
+
+    override fun setRootIcon(icon: Icon) {
+        val (nioBuf, len) = icon.toMessage().toNioDirectBuffer()
+        writeQueryCounters.measure {
+            rustCall { err ->
+                val ptr = Native.getDirectBufferPointer(nioBuf)
+                LibPlacesFFI.INSTANCE.places_set_root_icon(this.handle.get(), ptr, len, err)
+            }
+        }
+    }
+
     @Synchronized
     override fun close() {
         // If our API is still around, do nothing.
@@ -921,6 +970,29 @@ interface ReadableHistoryConnection : InterruptibleConnection {
      * @param excludeTypes List of visit types to exclude.
      */
     fun getVisitCount(excludeTypes: List<VisitType> = listOf()): Long
+
+    /**
+     * Returns the stored icon for a page that best fits [preferredWidth]: the
+     * smallest icon that's at least that wide, or the largest if they're all
+     * too small. Pages without icons of their own fall back to the root icon
+     * (`/favicon.ico`) for their origin, if we have one.
+     *
+     * @param pageUrl The URL of the page.
+     * @param preferredWidth The width, in pixels, that the icon will be shown at.
+     * @return The icon, or null if we don't have one for the page.
+     */
+    fun getIconForPage(pageUrl: String, preferredWidth: Int): Icon?
+
+    /**
+     * Returns the stored icon with the given URL that best fits [preferredWidth].
+     * This is useful for the `iconUrl` of bookmarks and search results, and
+     * for pages that aren't in places, like synced tabs.
+     *
+     * @param iconUrl The URL of the icon.
+     * @param preferredWidth The width, in pixels, that the icon will be shown at.
+     * @return The icon, or null if we don't have one with that URL.
+     */
+    fun getIcon(iconUrl: String, preferredWidth: Int): Icon?
 }
 
 interface WritableHistoryConnection : ReadableHistoryConnection {
@@ -1040,6 +1112,21 @@ interface WritableHistoryConnection : ReadableHistoryConnection {
      * @param url The chosen URL string
      */
     fun acceptResult(searchString: String, url: String)
+
+    /**
+     * Replaces the icons for a page. Icons that no other page uses are
+     * removed, except for root icons.
+     *
+     * @param pageUrl The URL of the page.
+     * @param icons The page's icons, usually in different sizes.
+     */
+    fun setIconsForPage(pageUrl: String, icons: List<Icon>)
+
+    /**
+     * Stores the root icon for an origin, without associating it with any
+     * page. The icon's URL must be `/favicon.ico` at the root of the origin.
+     */
+    fun setRootIcon(icon: Icon)
 }
 
 class InterruptHandle internal constructor(raw: RawPlacesInterruptHandle) : AutoCloseable {
@@ -1161,7 +1248,8 @@ data class SearchResult(
     val url: String,
     val title: String,
     val frecency: Long,
-    val reasons: List<SearchResultReason>
+    val reasons: List<SearchResultReason>,
+    val iconUrl: String? = null
 ) {
     companion object {
         internal fun fromMessage(msg: MsgTypes.SearchResultMessage): SearchResult {
@@ -1171,7 +1259,8 @@ data class SearchResult(
                 frecency = msg.frecency,
                 reasons = msg.reasonsList.map {
                     SearchResultReason.fromMessage(it)
-                }
+                },
+                iconUrl = if (msg.hasIconUrl()) { msg.iconUrl } else { null }
             )
         }
         internal fun fromCollectionMessage(msg: MsgTypes.SearchResultList): List<SearchResult> {
@@ -1182,6 +1271,56 @@ data class SearchResult(
     }
 }
 
+/**
+ * A favicon for a page. Set with `WritableHistoryConnection.setIconsForPage`
+ * and `setRootIcon`, and read with `ReadableHistoryConnection.getIconForPage`
+ * and `getIcon`.
+ */
+class Icon(
+    /**
+     * The URL the icon was fetched from.
+     */
+    val url: String,
+
+    /**
+     * The width of the icon in pixels, or 0 if the size isn't known.
+     */
+    val width: Int,
+
+    val mimeType: String?,
+
+    val data: ByteArray,
+
+    /**
+     * When the icon should be fetched again, in milliseconds since the epoch.
+     * Expired icons are still returned, since an old icon is better than no
+     * icon when we're offline.
+     */
+    val expires: Long
+) {
+    internal fun toMessage(): MsgTypes.Icon {
+        val builder = MsgTypes.Icon.newBuilder()
+            .setUrl(url)
+            .setWidth(width)
+            .setData(ByteString.copyFrom(data))
+            .setExpires(expires)
+        mimeType?.let { builder.setMimeType(it) }
+        return builder.build()
+    }
+
+    companion object {
+        internal fun fromMessage(msg: MsgTypes.Icon): Icon {
+            return Icon(
+                url = msg.url,
+                width = msg.width,
+                mimeType = if (msg.hasMimeType()) { msg.mimeType } else { null },
+                data = msg.data.toByteArray(),
+                expires = msg.expires
+            )
+        }
+    }
+}
+
 /**
  * Information about a top frecent site. Returned by `PlacesAPI.getTopFrecentSiteInfos`.
  */
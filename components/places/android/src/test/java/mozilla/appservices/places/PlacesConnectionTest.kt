@@ -14,7 +14,9 @@ import org.mozilla.appservices.places.GleanMetrics.PlacesManager as PlacesManage
 import org.robolectric.RobolectricTestRunner
 import org.robolectric.annotation.Config
 import org.junit.Test
+import org.junit.Assert.assertArrayEquals
 import org.junit.Assert.assertEquals
+import org.junit.Assert.assertNull
 import org.junit.Assert.fail
 import org.junit.Before
 
@@ -306,6 +308,38 @@ class PlacesConnectionTest {
         assertEquals(folder.parentGUID, BookmarkRoot.Unfiled.id)
     }
 
+    @Test
+    fun testIcons() {
+        val url = "https://www.example.com/page"
+        db.createBookmarkItem(parentGUID = BookmarkRoot.Unfiled.id, url = url, title = "example")
+        assertNull(db.getIconForPage(url, 16))
+
+        db.setIconsForPage(url, listOf(
+                Icon("https://www.example.com/small.png", 16, "image/png", byteArrayOf(1, 2), 1000),
+                Icon("https://www.example.com/large.png", 64, "image/png", byteArrayOf(3, 4), 1000)
+        ))
+        val small = db.getIconForPage(url, 8)!!
+        assertEquals("https://www.example.com/small.png", small.url)
+        assertEquals(16, small.width)
+        assertEquals("image/png", small.mimeType)
+        assertArrayEquals(byteArrayOf(1, 2), small.data)
+        assertEquals(1000L, small.expires)
+        assertEquals("https://www.example.com/large.png", db.getIconForPage(url, 32)!!.url)
+
+        // Bookmarks reference their page's largest icon.
+        val iconUrl = db.getBookmarksWithURL(url)[0].iconUrl!!
+        assertEquals("https://www.example.com/large.png", iconUrl)
+        assertArrayEquals(byteArrayOf(3, 4), db.getIcon(iconUrl, 64)!!.data)
+
+        // Other pages on the origin fall back to the root icon.
+        db.setRootIcon(Icon("https://www.example.com/favicon.ico", 16, null, byteArrayOf(5), 1000))
+        val root = db.getIconForPage("https://www.example.com/other", 16)!!
+        assertEquals("https://www.example.com/favicon.ico", root.url)
+        assertNull(root.mimeType)
+
+        assertNull(db.getIconForPage("not a url", 16))
+    }
+
     @Test
     fun testHistoryMetricsGathering() {
         assert(!PlacesManagerMetrics.writeQueryTime.testHasValue())
@@ -87,6 +87,26 @@ fn bench_search_frecent(c: &mut Criterion) {
         )
         .unwrap()
     });
+    db_bench!(c, "search_frecent multiple words", |db: test_db| {
+        search_frecent(
+            &db,
+            SearchParams {
+                search_string: "mozilla central".into(),
+                limit: 10,
+            },
+        )
+        .unwrap()
+    });
+    db_bench!(c, "search_frecent middle of word", |db: test_db| {
+        search_frecent(
+            &db,
+            SearchParams {
+                search_string: "zilla".into(),
+                limit: 10,
+            },
+        )
+        .unwrap()
+    });
     db_bench!(c, "search_frecent origin", |db: test_db| {
         search_frecent(
             &db,
@@ -154,6 +154,21 @@ fn run_native_import(db: &PlacesDb, filename: String) -> Result<()> {
     do_import(db, root)
 }
 
+fn run_html_import(db: &PlacesDb, filename: String) -> Result<()> {
+    println!("html import from {}", filename);
+    let html = std::fs::read_to_string(filename)?;
+    places::import::import_netscape_html(db, &html)?;
+    println!("Import finished!");
+    Ok(())
+}
+
+fn run_html_export(db: &PlacesDb, filename: String) -> Result<()> {
+    println!("html export to {}", filename);
+    let html = places::import::export_netscape_html(db)?;
+    std::fs::write(filename, html)?;
+    Ok(())
+}
+
 fn run_native_export(db: &PlacesDb, filename: String) -> Result<()> {
     println!("export to {}", filename);
 
@@ -376,6 +391,22 @@ enum Command {
         /// Imports bookmarks from a desktop export
         input_file: String,
     },
+
+    #[structopt(name = "import-html-bookmarks")]
+    /// Import bookmarks from a `bookmarks.html` file exported by any browser
+    ImportHtmlBookmarks {
+        #[structopt(name = "input-file", long, short = "i")]
+        /// The name of the file to read.
+        input_file: String,
+    },
+
+    #[structopt(name = "export-html-bookmarks")]
+    /// Exports bookmarks as a `bookmarks.html` file, which any browser can import
+    ExportHtmlBookmarks {
+        #[structopt(name = "output-file", long, short = "o")]
+        /// The name of the output file where the html will be written.
+        output_file: String,
+    },
 }
 
 fn main() -> Result<()> {
@@ -411,5 +442,7 @@ fn main() -> Result<()> {
         Command::ImportBookmarks { input_file } => run_native_import(&db, input_file),
         Command::ImportIosBookmarks { input_file } => run_ios_import(&api, input_file),
         Command::ImportDesktopBookmarks { input_file } => run_desktop_import(&db, input_file),
+        Command::ImportHtmlBookmarks { input_file } => run_html_import(&db, input_file),
+        Command::ExportHtmlBookmarks { output_file } => run_html_export(&db, output_file),
     }
 }
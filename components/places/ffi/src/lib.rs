@@ -719,6 +719,92 @@ pub extern "C" fn bookmarks_get_recent(
     })
 }
 
+/// # Safety
+/// Deref pointer, thus unsafe
+#[no_mangle]
+pub unsafe extern "C" fn places_set_icons_for_page(
+    handle: u64,
+    page_url: FfiStr<'_>,
+    data: *const u8,
+    len: i32,
+    error: &mut ExternError,
+) {
+    log::debug!("places_set_icons_for_page");
+    use places::msg_types::IconList;
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let page_url = parse_url(page_url.as_str())?;
+        let buffer = get_buffer(data, len);
+        let icons: IconList = prost::Message::decode(buffer)?;
+        let icons = icons
+            .icons
+            .into_iter()
+            .map(|icon| icon.into_icon())
+            .collect::<places::Result<Vec<_>>>()?;
+        storage::icons::set_icons_for_page(conn, &page_url, &icons)?;
+        Ok(())
+    })
+}
+
+/// # Safety
+/// Deref pointer, thus unsafe
+#[no_mangle]
+pub unsafe extern "C" fn places_set_root_icon(
+    handle: u64,
+    data: *const u8,
+    len: i32,
+    error: &mut ExternError,
+) {
+    log::debug!("places_set_root_icon");
+    use places::msg_types::Icon;
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let buffer = get_buffer(data, len);
+        let icon: Icon = prost::Message::decode(buffer)?;
+        storage::icons::set_root_icon(conn, &icon.into_icon()?)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_get_icon_for_page(
+    handle: u64,
+    page_url: FfiStr<'_>,
+    preferred_width: i32,
+    error: &mut ExternError,
+) -> ByteBuffer {
+    log::debug!("places_get_icon_for_page");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        Ok(match parse_url(page_url.as_str()) {
+            Ok(url) => {
+                storage::icons::get_icon_for_page(conn, &url, preferred_width.max(0) as u32)?
+            }
+            Err(e) => {
+                // There are no icons for the page if its URL is invalid.
+                log::warn!("Invalid URL passed to places_get_icon_for_page, {}", e);
+                None
+            }
+        })
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_get_icon(
+    handle: u64,
+    icon_url: FfiStr<'_>,
+    preferred_width: i32,
+    error: &mut ExternError,
+) -> ByteBuffer {
+    log::debug!("places_get_icon");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        Ok(match parse_url(icon_url.as_str()) {
+            Ok(url) => storage::icons::get_icon(conn, &url, preferred_width.max(0) as u32)?,
+            Err(e) => {
+                log::warn!("Invalid URL passed to places_get_icon, {}", e);
+                None
+            }
+        })
+    })
+}
+
 define_string_destructor!(places_destroy_string);
 define_bytebuffer_destructor!(places_destroy_bytebuffer);
 define_handle_map_deleter!(APIS, places_api_destroy);
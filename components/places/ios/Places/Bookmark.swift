@@ -140,15 +140,22 @@ public class BookmarkItem: BookmarkNode {
      */
     public let title: String
 
+    /**
+     * The URL of the bookmarked page's icon, if we have one stored.
+     */
+    public let iconURL: String?
+
     public init(guid: String,
                 dateAdded: Int64,
                 lastModified: Int64,
                 parentGUID: String?,
                 position: UInt32,
                 url: String,
-                title: String) {
+                title: String,
+                iconURL: String? = nil) {
         self.url = url
         self.title = title
+        self.iconURL = iconURL
         super.init(
             type: .bookmark,
             guid: guid,
@@ -235,7 +242,8 @@ internal func unpackProtobuf(msg: MsgTypes_BookmarkNode) -> BookmarkNode {
             parentGUID: parentGUID,
             position: position,
             url: msg.url,
-            title: title
+            title: title,
+            iconURL: msg.hasIconURL ? msg.iconURL : nil
         )
     case .separator:
         return BookmarkSeparator(
@@ -278,7 +286,8 @@ internal func unpackProtobufItemList(msg: MsgTypes_BookmarkNodeList) -> [Bookmar
             parentGUID: node.parentGuid,
             position: node.position,
             url: node.url,
-            title: node.hasTitle ? node.title : ""
+            title: node.hasTitle ? node.title : "",
+            iconURL: node.hasIconURL ? node.iconURL : nil
         )
     }
 }
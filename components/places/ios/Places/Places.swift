@@ -516,6 +516,81 @@ public class PlacesReadConnection {
         }
     }
 
+    /**
+     * Returns the icon for the provided page URL that best matches
+     * `preferredWidth`, falling back to the root icon of the page's origin
+     * (for example `/favicon.ico`) if the page has no icons of its own.
+     *
+     * - Parameter url: The URL of the page.
+     * - Parameter preferredWidth: The desired width of the icon, in pixels.
+     *
+     * - Returns: The icon, or nil if we have no icon for this page.
+     *
+     * - Throws:
+     *     - `PlacesError.databaseInterrupted`: If a call is made to `interrupt()` on this
+     *                                          object from another thread.
+     *     - `PlacesError.connUseAfterAPIClosed`: If the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.databaseBusy`: If this query times out with a SQLITE_BUSY error.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func getIconForPage(url: String, preferredWidth: UInt32) throws -> Icon? {
+        return try queue.sync {
+            try self.checkApi()
+            let buffer = try PlacesError.unwrap { error in
+                places_get_icon_for_page(self.handle, url, Int32(clamping: preferredWidth), error)
+            }
+            if buffer.data == nil {
+                return nil
+            }
+            defer { places_destroy_bytebuffer(buffer) }
+            let msg = try MsgTypes_Icon(serializedData: Data(placesRustBuffer: buffer))
+            return Icon(msg: msg)
+        }
+    }
+
+    /**
+     * Returns the icon stored for the provided icon URL that best matches
+     * `preferredWidth`.
+     *
+     * - Parameter url: The URL of the icon.
+     * - Parameter preferredWidth: The desired width of the icon, in pixels.
+     *
+     * - Returns: The icon, or nil if we have no icon with this URL.
+     *
+     * - Throws:
+     *     - `PlacesError.databaseInterrupted`: If a call is made to `interrupt()` on this
+     *                                          object from another thread.
+     *     - `PlacesError.connUseAfterAPIClosed`: If the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.databaseBusy`: If this query times out with a SQLITE_BUSY error.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func getIcon(url: String, preferredWidth: UInt32) throws -> Icon? {
+        return try queue.sync {
+            try self.checkApi()
+            let buffer = try PlacesError.unwrap { error in
+                places_get_icon(self.handle, url, Int32(clamping: preferredWidth), error)
+            }
+            if buffer.data == nil {
+                return nil
+            }
+            defer { places_destroy_bytebuffer(buffer) }
+            let msg = try MsgTypes_Icon(serializedData: Data(placesRustBuffer: buffer))
+            return Icon(msg: msg)
+        }
+    }
+
     /**
      * Attempt to interrupt a long-running operation which may be
      * happening concurrently. If the operation is interrupted,
@@ -816,6 +891,70 @@ public class PlacesWriteConnection: PlacesReadConnection {
     }
 
     // Remove the boilerplate common for all insertion messages
+    /**
+     * Store the icons for a page, replacing any icons previously stored for
+     * it. Icons that are no longer used by any page are removed.
+     *
+     * - Parameter url: The URL of the page.
+     * - Parameter icons: The icons for the page. Passing an empty list
+     *                    removes the page's icons.
+     *
+     * - Throws:
+     *     - `PlacesError.urlParseError`: If `url` or the URL of one of the icons is invalid.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func setIconsForPage(url: String, icons: [Icon]) throws {
+        return try queue.sync {
+            try self.checkApi()
+            var msg = MsgTypes_IconList()
+            msg.icons = icons.map { $0.toMsg() }
+            let data = try! msg.serializedData()
+            let size = Int32(data.count)
+            try data.withUnsafeBytes { bytes in
+                try PlacesError.unwrap { error in
+                    places_set_icons_for_page(self.handle, url, bytes.bindMemory(to: UInt8.self).baseAddress!, size, error)
+                }
+            }
+        }
+    }
+
+    /**
+     * Store a root icon, such as `/favicon.ico`, which is used for every
+     * page on the icon's origin that has no icons of its own.
+     *
+     * - Parameter icon: The root icon.
+     *
+     * - Throws:
+     *     - `PlacesError.urlParseError`: If the URL of the icon is invalid.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func setRootIcon(icon: Icon) throws {
+        return try queue.sync {
+            try self.checkApi()
+            let data = try! icon.toMsg().serializedData()
+            let size = Int32(data.count)
+            try data.withUnsafeBytes { bytes in
+                try PlacesError.unwrap { error in
+                    places_set_root_icon(self.handle, bytes.bindMemory(to: UInt8.self).baseAddress!, size, error)
+                }
+            }
+        }
+    }
+
     private func insertionMsg(type: BookmarkNodeType,
                               parentGUID: String,
                               position: UInt32?) -> MsgTypes_BookmarkNode {
@@ -829,6 +968,51 @@ public class PlacesWriteConnection: PlacesReadConnection {
     }
 }
 
+/**
+ * An icon (favicon, touch icon, etc) for a page.
+ */
+public struct Icon {
+    /// The URL the icon was fetched from.
+    public let url: String
+    /// The width of the icon in pixels, or 0 if the size isn't known.
+    public let width: UInt32
+    /// The MIME type of the icon, if known.
+    public let mimeType: String?
+    /// The raw icon data.
+    public let data: Data
+    /// When the icon should be fetched again, in milliseconds since the epoch.
+    /// Expired icons are still returned.
+    public let expires: Int64
+
+    public init(url: String, width: UInt32, mimeType: String?, data: Data, expires: Int64) {
+        self.url = url
+        self.width = width
+        self.mimeType = mimeType
+        self.data = data
+        self.expires = expires
+    }
+
+    internal init(msg: MsgTypes_Icon) {
+        self.init(url: msg.url,
+                  width: msg.width,
+                  mimeType: msg.hasMimeType ? msg.mimeType : nil,
+                  data: msg.data,
+                  expires: msg.expires)
+    }
+
+    internal func toMsg() -> MsgTypes_Icon {
+        var msg = MsgTypes_Icon()
+        msg.url = url
+        msg.width = width
+        if let mimeType = mimeType {
+            msg.mimeType = mimeType
+        }
+        msg.data = data
+        msg.expires = expires
+        return msg
+    }
+}
+
 // Wrapper around rust interrupt handle.
 private class InterruptHandle {
     let ptr: OpaquePointer
@@ -191,6 +191,29 @@ uint8_t bookmarks_delete(PlacesConnectionHandle handle,
 void bookmarks_reset(PlacesAPIHandle handle,
                      PlacesRustError *_Nonnull out_err);
 
+// MARK: Icon APIs
+
+void places_set_icons_for_page(PlacesConnectionHandle handle,
+                               char const *_Nonnull page_url,
+                               uint8_t const *_Nonnull data,
+                               int32_t len,
+                               PlacesRustError *_Nonnull out_err);
+
+void places_set_root_icon(PlacesConnectionHandle handle,
+                          uint8_t const *_Nonnull data,
+                          int32_t len,
+                          PlacesRustError *_Nonnull out_err);
+
+PlacesRustBuffer places_get_icon_for_page(PlacesConnectionHandle handle,
+                                          char const *_Nonnull page_url,
+                                          int32_t preferred_width,
+                                          PlacesRustError *_Nonnull out_err);
+
+PlacesRustBuffer places_get_icon(PlacesConnectionHandle handle,
+                                 char const *_Nonnull icon_url,
+                                 int32_t preferred_width,
+                                 PlacesRustError *_Nonnull out_err);
+
 // MARK: memory/lifecycle management
 
 void places_api_return_write_conn(PlacesAPIHandle api,
@@ -237,3 +237,60 @@ CREATE TABLE IF NOT EXISTS moz_keywords(
                      ON DELETE RESTRICT,
     keyword TEXT NOT NULL UNIQUE
 );
+
+-- These tables hold favicons. Like Desktop, icons are stored separately from
+-- the pages that use them, since most pages on a site share the same icon,
+-- and an icon can come in several sizes. Pages in `moz_pages_w_icons` don't
+-- need to be in `moz_places`; for example, synced tabs can have icons for
+-- pages that we've never visited.
+CREATE TABLE IF NOT EXISTS moz_icons(
+    id INTEGER PRIMARY KEY,
+    icon_url TEXT NOT NULL,
+    icon_url_hash INTEGER NOT NULL,
+    -- The width of the icon in pixels, or 0 if we don't know the size.
+    width INTEGER NOT NULL DEFAULT 0,
+    -- Root icons are `/favicon.ico` at the root of an origin, and are used for
+    -- any page on that origin that doesn't have its own icon. We keep them
+    -- around until they expire, even if no pages refer to them.
+    root INTEGER NOT NULL DEFAULT 0,
+    expire_ms INTEGER NOT NULL DEFAULT 0,
+    mime_type TEXT,
+    data BLOB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS moz_icons_iconurlhashindex ON moz_icons(icon_url_hash);
+
+CREATE TABLE IF NOT EXISTS moz_pages_w_icons(
+    id INTEGER PRIMARY KEY,
+    page_url TEXT NOT NULL,
+    page_url_hash INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS moz_pages_w_icons_urlhashindex ON moz_pages_w_icons(page_url_hash);
+
+CREATE TABLE IF NOT EXISTS moz_icons_to_pages(
+    page_id INTEGER NOT NULL REFERENCES moz_pages_w_icons(id)
+                             ON DELETE CASCADE,
+    icon_id INTEGER NOT NULL REFERENCES moz_icons(id)
+                             ON DELETE CASCADE,
+    PRIMARY KEY(page_id, icon_id)
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS moz_icons_to_pages_iconindex ON moz_icons_to_pages(icon_id);
+
+-- A full-text index over pages, used for autocomplete. Each row's `rowid` is
+-- the `id` of the page in `moz_places`. This table is kept current by the
+-- shared triggers; see `index_places_fts` in `schema.rs` for how the columns
+-- are filled in.
+CREATE VIRTUAL TABLE IF NOT EXISTS moz_places_fts USING fts5(
+    url,
+    title,
+    -- The titles of all bookmarks for the page, separated by spaces.
+    bookmark_titles,
+    -- All tags for the page, separated by spaces.
+    tags,
+    keyword,
+    -- Speeds up prefix queries for short prefixes, which are common when
+    -- the user has only typed the first few characters of a word.
+    prefix = '2 3'
+);
@@ -17,6 +17,8 @@ BEGIN
                          host = get_host_and_port(NEW.url) AND
                          rev_host = reverse_host(get_host_and_port(NEW.url)))
     WHERE id = NEW.id;
+
+    {index_new_place_fts};
 END;
 
 -- Note that while we create tombstones manually, we rely on this trigger to
@@ -177,6 +179,58 @@ BEGIN
     WHERE OLD.frecency > 0;
 END;
 
+CREATE TEMP TRIGGER moz_places_afterdelete_trigger_fts
+AFTER DELETE ON moz_places
+FOR EACH ROW
+BEGIN
+    DELETE FROM moz_places_fts WHERE rowid = OLD.id;
+END;
+
+-- Reindexes a page when its URL or title changes. Adding or removing a
+-- bookmark, tag, or keyword for a page also changes its foreign count, via
+-- the triggers below, so this keeps the bookmark titles, tags, and keyword
+-- in the index current, too.
+CREATE TEMP TRIGGER moz_places_afterupdate_fts_trigger
+AFTER UPDATE OF url, title, foreign_count ON moz_places
+FOR EACH ROW
+BEGIN
+    DELETE FROM moz_places_fts WHERE rowid = NEW.id;
+    {index_new_place_fts};
+END;
+
+-- Renaming a bookmark doesn't change the foreign count, so we need to
+-- reindex the page separately.
+CREATE TEMP TRIGGER moz_bookmarks_afterupdate_fts_trigger
+AFTER UPDATE OF title ON moz_bookmarks
+FOR EACH ROW WHEN NEW.fk NOT NULL
+BEGIN
+    DELETE FROM moz_places_fts WHERE rowid = NEW.fk;
+    {index_new_bookmark_fts};
+END;
+
+-- Removes icons for pages that are deleted from places. Deleting the page
+-- cascades to `moz_icons_to_pages`, which in turn removes any icons that
+-- aren't used by other pages.
+CREATE TEMP TRIGGER moz_places_afterdelete_trigger_icons
+AFTER DELETE ON moz_places
+FOR EACH ROW
+BEGIN
+    DELETE FROM moz_pages_w_icons
+    WHERE page_url_hash = OLD.url_hash
+      AND page_url = OLD.url;
+END;
+
+CREATE TEMP TRIGGER moz_icons_to_pages_afterdelete_trigger
+AFTER DELETE ON moz_icons_to_pages
+FOR EACH ROW
+BEGIN
+    DELETE FROM moz_icons
+    WHERE id = OLD.icon_id
+      AND NOT root
+      AND NOT EXISTS(SELECT 1 FROM moz_icons_to_pages
+                     WHERE icon_id = OLD.icon_id);
+END;
+
 -- This trigger corresponds to the previous trigger
 -- (moz_places_afterdelete_trigger_origins).  It runs on deletes on
 -- moz_updateoriginsdelete_temp -- logically, after deletes on moz_places.
@@ -6,6 +6,7 @@ use crate::db::PlacesDb;
 use crate::error::Result;
 pub use crate::match_impl::{MatchBehavior, SearchBehavior};
 use crate::msg_types::{SearchResultMessage, SearchResultReason};
+use crate::storage::icons::{get_icon_url_for_page, icon_url_from_row, icon_url_sql};
 use rusqlite::{types::ToSql, Row};
 use serde_derive::*;
 use sql_support::{maybe_log_plan, ConnExt};
@@ -51,8 +52,6 @@ pub struct SearchParams {
 /// A provider can be anything that returns URL suggestions: Places history
 /// and bookmarks, synced tabs, search engine suggestions, and search keywords.
 pub fn search_frecent(conn: &PlacesDb, params: SearchParams) -> Result<Vec<SearchResult>> {
-    // TODO: Tokenize the query.
-
     // Try to find the first heuristic result. Desktop tries extensions,
     // search engine aliases, origins, URLs, search engine domains, and
     // preloaded sites, before trying to fall back to fixing up the URL,
@@ -70,9 +69,11 @@ pub fn search_frecent(conn: &PlacesDb, params: SearchParams) -> Result<Vec<Searc
                 MatchBehavior::Anywhere,
                 SearchBehavior::default(),
             ),
+            // Suggestions match on word boundaries using the full-text
+            // index first, then anywhere if there aren't enough.
             &Suggestions::with_behavior(
                 &params.search_string,
-                MatchBehavior::Anywhere,
+                MatchBehavior::BoundaryAnywhere,
                 SearchBehavior::default(),
             ),
         ],
@@ -245,7 +246,7 @@ impl SearchResult {
             search_string,
             url,
             title,
-            icon_url: None,
+            icon_url: icon_url_from_row(row)?,
             frecency,
             reasons,
         })
@@ -273,7 +274,7 @@ impl SearchResult {
             search_string,
             url,
             title,
-            icon_url: None,
+            icon_url: icon_url_from_row(row)?,
             frecency,
             reasons,
         })
@@ -344,6 +345,7 @@ impl From<SearchResult> for SearchResultMessage {
         Self {
             url: res.url.into_string(),
             title: res.title,
+            icon_url: res.icon_url.map(Url::into_string),
             frecency: res.frecency,
             reasons: res
                 .reasons
@@ -443,7 +445,7 @@ const ORIGIN_SQL: &str = "
 
 impl<'query> Matcher for OriginOrUrl<'query> {
     fn search(&self, conn: &PlacesDb, _: u32) -> Result<Vec<SearchResult>> {
-        Ok(if looks_like_origin(self.query) {
+        let mut results = if looks_like_origin(self.query) {
             query_flat_rows_and_then_named(
                 conn,
                 ORIGIN_SQL,
@@ -480,7 +482,14 @@ impl<'query> Matcher for OriginOrUrl<'query> {
             )?
         } else {
             vec![]
-        })
+        };
+        // Origin and URL matches are trimmed to the part of the URL that
+        // matched, so we can't look up their icons in the query. There's at
+        // most one of them, though.
+        for result in &mut results {
+            result.icon_url = get_icon_url_for_page(conn, &result.url)?;
+        }
+        Ok(results)
     }
 }
 
@@ -508,7 +517,8 @@ impl<'query> Matcher for Adaptive<'query> {
     fn search(&self, conn: &PlacesDb, max_results: u32) -> Result<Vec<SearchResult>> {
         Ok(query_flat_rows_and_then_named(
             conn,
-            "
+            &format!(
+                "
             SELECT h.url as url,
                    h.title as title,
                    EXISTS(SELECT 1 FROM moz_bookmarks
@@ -524,7 +534,8 @@ impl<'query> Matcher for Adaptive<'query> {
                    h.id as id,
                    NULL AS open_count,
                    h.frecency as frecency,
-                   :searchString AS searchString
+                   :searchString AS searchString,
+                   {icon_url} AS iconUrl
             FROM (
               SELECT ROUND(MAX(use_count) * (1 + (input = :searchString)), 1) AS rank,
                      place_id
@@ -539,6 +550,8 @@ impl<'query> Matcher for Adaptive<'query> {
                                      NULL, :matchBehavior, :searchBehavior)
             ORDER BY rank DESC, h.frecency DESC
             LIMIT :maxResults",
+                icon_url = icon_url_sql("h.url")
+            ),
             &[
                 (":searchString", &self.query),
                 (":matchBehavior", &self.match_behavior),
@@ -568,13 +581,34 @@ impl<'query> Suggestions<'query> {
             search_behavior,
         }
     }
-}
 
-impl<'query> Matcher for Suggestions<'query> {
-    fn search(&self, conn: &PlacesDb, max_results: u32) -> Result<Vec<SearchResult>> {
-        Ok(query_flat_rows_and_then_named(
+    /// Returns `true` if the full-text index can find every page that matches
+    /// the query. The index only matches words from the start, so it can't
+    /// find matches in the middle of words, or only at the start of the title
+    /// or URL.
+    fn uses_index(&self) -> bool {
+        match self.match_behavior {
+            MatchBehavior::Boundary | MatchBehavior::BoundaryAnywhere => true,
+            _ => false,
+        }
+    }
+
+    /// Finds pages where a word starts with each word in the query, using the
+    /// full-text index.
+    fn search_index(
+        &self,
+        conn: &PlacesDb,
+        fts_query: &str,
+        max_results: u32,
+    ) -> Result<Vec<SearchResult>> {
+        // The index has already matched the words, so we only pass
+        // `AUTOCOMPLETE_MATCH` an empty search string, to check the search
+        // behavior. Matching pages are ranked by how well they match the
+        // query, and by frecency for pages that match equally well.
+        query_flat_rows_and_then_named(
             conn,
-            "
+            &format!(
+                "
             SELECT h.url, h.title,
                    EXISTS(SELECT 1 FROM moz_bookmarks
                           WHERE fk = h.id) AS bookmarked,
@@ -587,7 +621,63 @@ impl<'query> Matcher for Suggestions<'query> {
                    h.visit_count_local + h.visit_count_remote AS visit_count,
                    h.typed as typed,
                    h.id as id,
-                   NULL AS open_count, h.frecency, :searchString AS searchString
+                   NULL AS open_count, h.frecency, :searchString AS searchString,
+                   {icon_url} AS iconUrl
+            FROM moz_places_fts f
+            JOIN moz_places h ON h.id = f.rowid
+            WHERE moz_places_fts MATCH :ftsQuery
+              AND h.frecency > 0
+              AND AUTOCOMPLETE_MATCH('', h.url,
+                                     IFNULL(btitle, h.title), f.tags,
+                                     visit_count, h.typed,
+                                     bookmarked, NULL,
+                                     :matchBehavior, :searchBehavior)
+              AND (+h.visit_count_local > 0 OR +h.visit_count_remote > 0)
+            ORDER BY f.rank, h.frecency DESC, h.id DESC
+            LIMIT :maxResults",
+                icon_url = icon_url_sql("h.url")
+            ),
+            &[
+                (":searchString", &self.query),
+                (
+                    ":ftsQuery",
+                    &with_fts_columns(fts_query, self.search_behavior),
+                ),
+                (":matchBehavior", &self.match_behavior),
+                (":searchBehavior", &self.search_behavior),
+                (":maxResults", &max_results),
+            ],
+            SearchResult::from_suggestion_row,
+        )
+    }
+
+    /// Finds pages that match the query using `AUTOCOMPLETE_MATCH`. This
+    /// checks every page in history, so it's only used for queries that the
+    /// index can't answer.
+    fn search_all(
+        &self,
+        conn: &PlacesDb,
+        match_behavior: MatchBehavior,
+        max_results: u32,
+    ) -> Result<Vec<SearchResult>> {
+        query_flat_rows_and_then_named(
+            conn,
+            &format!(
+                "
+            SELECT h.url, h.title,
+                   EXISTS(SELECT 1 FROM moz_bookmarks
+                          WHERE fk = h.id) AS bookmarked,
+                   (SELECT title FROM moz_bookmarks
+                    WHERE fk = h.id AND
+                          title NOT NULL
+                    ORDER BY lastModified DESC
+                    LIMIT 1) AS btitle,
+                   NULL AS tags,
+                   h.visit_count_local + h.visit_count_remote AS visit_count,
+                   h.typed as typed,
+                   h.id as id,
+                   NULL AS open_count, h.frecency, :searchString AS searchString,
+                   {icon_url} AS iconUrl
             FROM moz_places h
             WHERE h.frecency > 0
               AND AUTOCOMPLETE_MATCH(:searchString, h.url,
@@ -598,14 +688,105 @@ impl<'query> Matcher for Suggestions<'query> {
               AND (+h.visit_count_local > 0 OR +h.visit_count_remote > 0)
             ORDER BY h.frecency DESC, h.id DESC
             LIMIT :maxResults",
+                icon_url = icon_url_sql("h.url")
+            ),
             &[
                 (":searchString", &self.query),
-                (":matchBehavior", &self.match_behavior),
+                (":matchBehavior", &match_behavior),
                 (":searchBehavior", &self.search_behavior),
                 (":maxResults", &max_results),
             ],
             SearchResult::from_suggestion_row,
-        )?)
+        )
+    }
+}
+
+impl<'query> Matcher for Suggestions<'query> {
+    fn search(&self, conn: &PlacesDb, max_results: u32) -> Result<Vec<SearchResult>> {
+        let fts_query = match to_fts_query(self.query) {
+            Some(fts_query) if self.uses_index() => fts_query,
+            // The index can't answer this query, either because of the match
+            // behavior, or because the query doesn't have any words.
+            _ => return self.search_all(conn, self.match_behavior, max_results),
+        };
+        let mut results = self.search_index(conn, &fts_query, max_results)?;
+        if self.match_behavior == MatchBehavior::BoundaryAnywhere
+            && results.len() < max_results as usize
+        {
+            // This behavior matches anywhere if there aren't enough matches
+            // on word boundaries, like `refox` for `firefox`. These come
+            // after the index matches, and might include them again, so we
+            // skip the ones we already have.
+            let more = self.search_all(conn, MatchBehavior::Anywhere, max_results)?;
+            for result in more {
+                if results.len() >= max_results as usize {
+                    break;
+                }
+                if !results.iter().any(|r| r.url == result.url) {
+                    results.push(result);
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Restricts a `moz_places_fts` query to the columns that `AUTOCOMPLETE_MATCH`
+/// checks for the `TITLE` and `URL` search behaviors.
+fn with_fts_columns(fts_query: &str, search_behavior: SearchBehavior) -> String {
+    const TITLE_COLUMNS: &str = "{title bookmark_titles tags}";
+    match (
+        search_behavior.contains(SearchBehavior::TITLE),
+        search_behavior.contains(SearchBehavior::URL),
+    ) {
+        (true, true) => format!(
+            "{} : ({}) AND url : ({})",
+            TITLE_COLUMNS, fts_query, fts_query
+        ),
+        (true, false) => format!("{} : ({})", TITLE_COLUMNS, fts_query),
+        (false, true) => format!("url : ({})", fts_query),
+        (false, false) => fts_query.to_owned(),
+    }
+}
+
+/// Converts a search string into a query for the `moz_places_fts` index.
+///
+/// Each word in the search string matches any word in the index that starts
+/// with it, and text in double quotes matches as a phrase. Pages must match
+/// all words and phrases, in any column. An unterminated phrase is treated as
+/// a prefix, since the user is probably still typing it.
+///
+/// Returns `None` if there's nothing to search for.
+fn to_fts_query(search_string: &str) -> Option<String> {
+    fn push_term(terms: &mut Vec<String>, term: &str, prefix: bool) {
+        // The FTS tokenizer drops everything that isn't a letter or a digit,
+        // so a term without any of those wouldn't match anything.
+        if term.chars().any(char::is_alphanumeric) {
+            terms.push(format!("\"{}\"{}", term, if prefix { "*" } else { "" }));
+        }
+    }
+
+    let mut terms = Vec::new();
+    let mut in_phrase = false;
+    let mut term_start = 0;
+    for (index, c) in search_string.char_indices() {
+        if c == '"' {
+            // A quote ends the current word or phrase, and starts a new one.
+            let term = &search_string[term_start..index];
+            push_term(&mut terms, term, !in_phrase);
+            in_phrase = !in_phrase;
+            term_start = index + 1;
+        } else if c.is_whitespace() && !in_phrase {
+            push_term(&mut terms, &search_string[term_start..index], true);
+            term_start = index + c.len_utf8();
+        }
+    }
+    push_term(&mut terms, &search_string[term_start..], true);
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
     }
 }
 
@@ -662,6 +843,308 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fts_query() {
+        assert_eq!(to_fts_query(""), None);
+        assert_eq!(to_fts_query("  - / "), None);
+        assert_eq!(to_fts_query("moz"), Some(r#""moz"*"#.into()));
+        assert_eq!(
+            to_fts_query(" mozilla  firefox "),
+            Some(r#""mozilla"* "firefox"*"#.into())
+        );
+        assert_eq!(
+            to_fts_query("hg.mozilla.org/mozilla-central"),
+            Some(r#""hg.mozilla.org/mozilla-central"*"#.into())
+        );
+        assert_eq!(
+            to_fts_query(r#"release "firefox nightly" notes"#),
+            Some(r#""release"* "firefox nightly" "notes"*"#.into())
+        );
+        assert_eq!(
+            to_fts_query(r#"mozilla "firefox night"#),
+            Some(r#""mozilla"* "firefox night"*"#.into())
+        );
+        assert_eq!(to_fts_query(r#"a"b""c"#), Some(r#""a"* "b" "c"*"#.into()));
+    }
+
+    #[test]
+    fn search_fts() -> Result<()> {
+        use crate::storage::bookmarks::{
+            insert_bookmark, update_bookmark, BookmarkPosition, BookmarkRootGuid,
+            InsertableBookmark, UpdatableBookmark,
+        };
+        use crate::storage::tags::tag_url;
+
+        let conn = new_mem_connection();
+        for (url, title) in &[
+            ("https://www.mozilla.org/firefox/", "Firefox Browser"),
+            (
+                "https://www.mozilla.org/firefox/nightly/",
+                "Firefox Nightly",
+            ),
+            ("https://example.com/browsers", "Comparing web browsers"),
+        ] {
+            apply_observation(
+                &conn,
+                VisitObservation::new(Url::parse(url)?)
+                    .with_title(title.to_string())
+                    .with_visit_type(VisitTransition::Typed)
+                    .with_at(Timestamp::now()),
+            )?;
+        }
+        let search = |query: &str| -> Result<Vec<String>> {
+            let mut urls = Suggestions::with_behavior(
+                query,
+                MatchBehavior::BoundaryAnywhere,
+                SearchBehavior::default(),
+            )
+            .search(&conn, 10)?
+            .into_iter()
+            .map(|result| result.url.into_string())
+            .collect::<Vec<_>>();
+            urls.sort();
+            Ok(urls)
+        };
+
+        // Prefixes of words in titles and URLs match.
+        assert_eq!(
+            search("brows")?,
+            vec![
+                "https://example.com/browsers",
+                "https://www.mozilla.org/firefox/",
+            ]
+        );
+        assert_eq!(
+            search("fire night")?,
+            vec!["https://www.mozilla.org/firefox/nightly/"]
+        );
+        assert_eq!(
+            search(r#""firefox browser""#)?,
+            vec!["https://www.mozilla.org/firefox/"]
+        );
+        assert!(search(r#""browser firefox""#)?.is_empty());
+        // Words in the middle of other words match after the index matches.
+        assert_eq!(
+            search("refox")?,
+            vec![
+                "https://www.mozilla.org/firefox/",
+                "https://www.mozilla.org/firefox/nightly/",
+            ]
+        );
+
+        // Bookmark titles, tags and keywords are indexed as they change.
+        let url = Url::parse("https://example.com/browsers")?;
+        let guid = insert_bookmark(
+            &conn,
+            &InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Unfiled.into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                url: url.clone(),
+                title: Some("Reading list".into()),
+            }
+            .into(),
+        )?;
+        assert_eq!(search("reading")?, vec![url.as_str()]);
+        update_bookmark(
+            &conn,
+            &guid,
+            &UpdatableBookmark {
+                title: Some("Research".into()),
+                ..Default::default()
+            }
+            .into(),
+        )?;
+        assert!(search("reading")?.is_empty());
+        assert_eq!(search("research")?, vec![url.as_str()]);
+
+        tag_url(&conn, &url, "comparisons")?;
+        assert_eq!(search("comparisons")?, vec![url.as_str()]);
+
+        conn.execute_named_cached(
+            "INSERT INTO moz_keywords(place_id, keyword)
+             SELECT id, 'cmp' FROM moz_places WHERE url = :url",
+            &[(":url", &url.as_str())],
+        )?;
+        assert_eq!(search("cmp")?, vec![url.as_str()]);
+        Ok(())
+    }
+
+    #[test]
+    fn search_fts_matches_full_scan() -> Result<()> {
+        let conn = new_mem_connection();
+        for (url, title, visit_type) in &[
+            (
+                "https://www.mozilla.org/firefox/",
+                "Firefox Browser",
+                VisitTransition::Typed,
+            ),
+            (
+                "https://www.mozilla.org/firefox/nightly/",
+                "Firefox Nightly",
+                VisitTransition::Link,
+            ),
+            (
+                "https://example.com/browsers",
+                "Comparing web browsers",
+                VisitTransition::Typed,
+            ),
+            (
+                "https://blog.mozilla.org/",
+                "Mozilla Blog",
+                VisitTransition::Link,
+            ),
+            (
+                "https://example.org/news/firefox",
+                "News about the web",
+                VisitTransition::Link,
+            ),
+            (
+                "https://example.net/dashes",
+                "Before -- after",
+                VisitTransition::Typed,
+            ),
+        ] {
+            apply_observation(
+                &conn,
+                VisitObservation::new(Url::parse(url)?)
+                    .with_title(title.to_string())
+                    .with_visit_type(*visit_type)
+                    .with_at(Timestamp::now()),
+            )?;
+        }
+        let urls = |results: Vec<SearchResult>| {
+            let mut urls = results
+                .into_iter()
+                .map(|result| result.url.into_string())
+                .collect::<Vec<_>>();
+            urls.sort();
+            urls
+        };
+
+        // The index and `AUTOCOMPLETE_MATCH` should find the same pages for
+        // queries that the index can answer.
+        for search_behavior in &[
+            SearchBehavior::default(),
+            SearchBehavior::HISTORY | SearchBehavior::TITLE,
+            SearchBehavior::HISTORY | SearchBehavior::URL,
+            SearchBehavior::HISTORY | SearchBehavior::TITLE | SearchBehavior::URL,
+            SearchBehavior::TYPED | SearchBehavior::RESTRICT,
+        ] {
+            for query in &[
+                "mozilla",
+                "fire",
+                "fire night",
+                "brows",
+                "web",
+                "example fire",
+                "news",
+                "nothing",
+            ] {
+                let suggestions =
+                    Suggestions::with_behavior(query, MatchBehavior::Boundary, *search_behavior);
+                let indexed = suggestions.search_index(&conn, &to_fts_query(query).unwrap(), 10)?;
+                let scanned = suggestions.search_all(&conn, MatchBehavior::Boundary, 10)?;
+                assert_eq!(
+                    urls(indexed),
+                    urls(scanned),
+                    "Query {:?} with {:?}",
+                    query,
+                    search_behavior
+                );
+            }
+        }
+
+        // Queries without any words, and match behaviors that the index
+        // can't answer, check every page.
+        let search = |query: &str, match_behavior: MatchBehavior| -> Result<Vec<String>> {
+            Ok(urls(
+                Suggestions::with_behavior(query, match_behavior, SearchBehavior::default())
+                    .search(&conn, 10)?,
+            ))
+        };
+        assert_eq!(
+            search("--", MatchBehavior::Boundary)?,
+            vec!["https://example.net/dashes"]
+        );
+        assert_eq!(
+            search("log", MatchBehavior::Anywhere)?,
+            vec!["https://blog.mozilla.org/"]
+        );
+        assert!(search("log", MatchBehavior::Boundary)?.is_empty());
+        assert_eq!(
+            search("comp", MatchBehavior::Beginning)?,
+            vec!["https://example.com/browsers"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn search_icon_urls() -> Result<()> {
+        use crate::storage::icons::{set_icons_for_page, set_root_icon, Icon};
+
+        let conn = new_mem_connection();
+        for url in &["https://example.com/a", "https://example.org/a"] {
+            apply_observation(
+                &conn,
+                VisitObservation::new(Url::parse(url)?)
+                    .with_title("Example page".to_string())
+                    .with_visit_type(VisitTransition::Typed)
+                    .with_at(Timestamp::now()),
+            )?;
+        }
+        let icon = |url: &str| -> Result<Icon> {
+            Ok(Icon {
+                url: Url::parse(url)?,
+                width: 16,
+                mime_type: None,
+                data: vec![1, 2, 3],
+                expires: Timestamp::now(),
+            })
+        };
+        set_icons_for_page(
+            &conn,
+            &Url::parse("https://example.com/a")?,
+            &[icon("https://example.com/icon.png")?],
+        )?;
+        set_root_icon(&conn, &icon("https://example.org/favicon.ico")?)?;
+
+        let mut results = search_frecent(
+            &conn,
+            SearchParams {
+                // This doesn't look like an origin or URL, so we only get
+                // suggestions.
+                search_string: "example page".into(),
+                limit: 10,
+            },
+        )?
+        .into_iter()
+        .map(|result| {
+            (
+                result.url.into_string(),
+                result.icon_url.map(Url::into_string),
+            )
+        })
+        .collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "https://example.com/a".to_string(),
+                    Some("https://example.com/icon.png".to_string())
+                ),
+                (
+                    "https://example.org/a".to_string(),
+                    Some("https://example.org/favicon.ico".to_string())
+                ),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn search() {
         let conn = new_mem_connection();
@@ -0,0 +1,1040 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Bookmark repair asks other clients to reupload bookmarks that are missing
+//! from the server, using commands in the clients collection. This follows
+//! the same protocol as Desktop:
+//!
+//! 1. After merging, if the remote tree has folders with missing children,
+//!    or items with missing parents, the requestor picks another client, and
+//!    sends it a `repairRequest` command with the missing IDs.
+//! 2. The responder applies the command by flagging the requested items that
+//!    it has for reupload, so that its next bookmarks sync uploads them. Once
+//!    they're uploaded, it sends a `repairResponse` back to the requestor.
+//! 3. The requestor removes the reuploaded IDs from its list. If some IDs are
+//!    still missing, or the responder doesn't answer in time, it asks the
+//!    next client, until it runs out of clients to ask.
+//!
+//! Commands are sent and applied by the clients engine, which only runs when
+//! syncing through the sync manager. The repair state is persisted in
+//! `moz_meta`, because each step happens in a different sync. Every step
+//! records a telemetry event with the repair's flow ID.
+
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::{bookmarks::BookmarkRootGuid, delete_meta, get_meta, put_meta};
+use crate::types::Timestamp;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_derive::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use sync15::clients::{
+    ClientData, Command, CommandStatus, DeviceType, RepairRequest, RepairResponse,
+};
+use sync15::telemetry;
+use sync_guid::Guid as SyncGuid;
+
+/// The meta key for the repair that we started, if any.
+pub(crate) const REQUESTOR_META_KEY: &str = "bookmarks_repair_requestor";
+/// The meta key for our responses to other clients' repair requests.
+pub(crate) const RESPONDER_META_KEY: &str = "bookmarks_repair_responder";
+/// The meta key for the time we last started a repair.
+pub(crate) const LAST_REPAIR_META_KEY: &str = "bookmarks_last_repair_time";
+
+const COLLECTION_NAME: &str = "bookmarks";
+
+/// The only kind of repair request that Desktop supports, and the only one
+/// that we send or handle.
+const UPLOAD_REQUEST: &str = "upload";
+
+/// How long we wait for a client to respond to a repair request, before
+/// giving up and asking the next client.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+/// How long we wait after starting a repair before starting another one, so
+/// that a tree that repairs can't fix doesn't make other clients reupload on
+/// every sync.
+const MIN_REPAIR_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A repair that we started, persisted until it finishes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Requestor {
+    flow_id: String,
+    /// The IDs that are still missing from the server.
+    ids: Vec<String>,
+    /// The clients that we've asked, including the current target.
+    asked_clients: Vec<String>,
+    /// The client that we're asking now, or `None` if we need to pick one.
+    target: Option<Target>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Target {
+    client_id: String,
+    request: RepairRequest,
+    /// When the server accepted our request, or `None` if we haven't sent it
+    /// yet.
+    sent_at: Option<Timestamp>,
+}
+
+/// A repair request from another client, persisted until we've uploaded the
+/// requested items and sent our response.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Responder {
+    requestor: String,
+    flow_id: String,
+    /// The requested IDs that we have, and flagged for reupload.
+    ids: Vec<String>,
+    /// The response to send, once the items are uploaded.
+    response: Option<RepairResponse>,
+}
+
+/// Starts, continues, and responds to bookmark repairs for a bookmarks store.
+pub(crate) struct BookmarkRepair<'a> {
+    db: &'a PlacesDb,
+    // The clients from the last clients engine sync, which we pick repair
+    // targets from. This is `None` if we're syncing without the clients
+    // engine.
+    client_data: RefCell<Option<ClientData>>,
+    // Events that we recorded outside `Store::apply_incoming`, where we don't
+    // have the engine telemetry. These are recorded in the next call to
+    // `record_telemetry`.
+    events: RefCell<Vec<telemetry::Event>>,
+}
+
+impl<'a> BookmarkRepair<'a> {
+    pub(crate) fn new(db: &'a PlacesDb) -> Self {
+        Self {
+            db,
+            client_data: RefCell::default(),
+            events: RefCell::default(),
+        }
+    }
+
+    pub(crate) fn set_client_data(&self, client_data: ClientData) {
+        self.client_data.replace(Some(client_data));
+    }
+
+    /// Moves the events recorded since the last call into `telem`.
+    pub(crate) fn record_telemetry(&self, telem: &mut telemetry::Engine) {
+        for event in self.events.borrow_mut().drain(..) {
+            telem.event(event);
+        }
+    }
+
+    /// Starts a repair for `missing_ids`, unless a repair is already in
+    /// progress, or we started one recently. This is called after merging.
+    pub(crate) fn start(&self, missing_ids: Vec<SyncGuid>) -> Result<()> {
+        if missing_ids.is_empty() || self.requestor()?.is_some() {
+            return Ok(());
+        }
+        let now = Timestamp::now();
+        if let Some(last_repair) = get_meta::<i64>(self.db, LAST_REPAIR_META_KEY)? {
+            let since_last_repair = now.duration_since(Timestamp(last_repair as u64));
+            if since_last_repair.map_or(true, |d| d < MIN_REPAIR_INTERVAL) {
+                log::info!(
+                    "Not repairing {} missing bookmarks; we started a repair recently",
+                    missing_ids.len()
+                );
+                return Ok(());
+            }
+        }
+        put_meta(self.db, LAST_REPAIR_META_KEY, &(now.as_millis() as i64))?;
+
+        let repair = Requestor {
+            flow_id: SyncGuid::random().into_string(),
+            ids: missing_ids.into_iter().map(SyncGuid::into_string).collect(),
+            asked_clients: Vec::new(),
+            target: None,
+        };
+        log::info!(
+            "Starting repair {} for {} missing bookmarks",
+            repair.flow_id,
+            repair.ids.len()
+        );
+        self.record(
+            flow_event("repair", "started", &repair.flow_id)
+                .extra("numIDs", repair.ids.len().to_string()),
+        );
+        self.continue_repair(repair)
+    }
+
+    /// Checks on the repair in progress, if any, and asks another client if
+    /// the current one went away, or didn't respond in time. This is called
+    /// after merging.
+    pub(crate) fn check_progress(&self) -> Result<()> {
+        let mut repair = match self.requestor()? {
+            Some(repair) => repair,
+            None => return Ok(()),
+        };
+        if let Some(target) = &repair.target {
+            let reason = if !self.has_client(&target.client_id) {
+                Some("missing")
+            } else if target.sent_at.map_or(false, |sent_at| {
+                Timestamp::now()
+                    .duration_since(sent_at)
+                    .map_or(false, |d| d > RESPONSE_TIMEOUT)
+            }) {
+                Some("timeout")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                log::warn!(
+                    "Abandoning repair request to {}: {}",
+                    target.client_id,
+                    reason
+                );
+                self.record(
+                    flow_event("repair", "abandon", &repair.flow_id).extra("reason", reason.into()),
+                );
+                repair.target = None;
+            }
+        }
+        self.continue_repair(repair)
+    }
+
+    /// Returns the repair request for our target, if we haven't sent it yet,
+    /// and responses for the repair requests that we've handled.
+    pub(crate) fn outgoing_commands(&self) -> Result<HashMap<String, HashSet<Command>>> {
+        let mut commands: HashMap<String, HashSet<Command>> = HashMap::new();
+        if let Some(Requestor {
+            target: Some(target),
+            ..
+        }) = self.requestor()?
+        {
+            if target.sent_at.is_none() {
+                commands
+                    .entry(target.client_id)
+                    .or_default()
+                    .insert(Command::RepairRequest(target.request));
+            }
+        }
+        for responder in self.responders()? {
+            if let Some(response) = responder.response {
+                commands
+                    .entry(responder.requestor)
+                    .or_default()
+                    .insert(Command::RepairResponse(response));
+            }
+        }
+        Ok(commands)
+    }
+
+    /// Notes which of our outgoing commands the server accepted.
+    pub(crate) fn commands_sent(&self, sent: &HashMap<String, HashSet<Command>>) -> Result<()> {
+        let was_sent = |client_id: &str, command: Command| {
+            sent.get(client_id)
+                .map_or(false, |commands| commands.contains(&command))
+        };
+
+        if let Some(mut repair) = self.requestor()? {
+            if let Some(target) = &mut repair.target {
+                if target.sent_at.is_none()
+                    && was_sent(
+                        &target.client_id,
+                        Command::RepairRequest(target.request.clone()),
+                    )
+                {
+                    target.sent_at = Some(Timestamp::now());
+                    self.record(
+                        flow_event("repair", "request", &repair.flow_id)
+                            .extra("numIDs", target.request.ids.len().to_string()),
+                    );
+                    self.set_requestor(Some(&repair))?;
+                }
+            }
+        }
+
+        let responders = self.responders()?;
+        let mut remaining = Vec::with_capacity(responders.len());
+        for responder in responders {
+            match &responder.response {
+                Some(response)
+                    if was_sent(
+                        &responder.requestor,
+                        Command::RepairResponse(response.clone()),
+                    ) =>
+                {
+                    self.record(
+                        flow_event("repairResponse", "finished", &responder.flow_id)
+                            .extra("numIDs", responder.ids.len().to_string()),
+                    );
+                }
+                _ => remaining.push(responder),
+            }
+        }
+        self.set_responders(&remaining)
+    }
+
+    /// Applies a repair request or response from another client.
+    pub(crate) fn apply_command(&self, command: &Command) -> Result<CommandStatus> {
+        match command {
+            Command::RepairRequest(request) if request.collection == COLLECTION_NAME => {
+                self.respond(request)
+            }
+            Command::RepairResponse(response) if response.collection == COLLECTION_NAME => {
+                self.handle_response(response)
+            }
+            _ => Ok(CommandStatus::Unsupported),
+        }
+    }
+
+    /// Prepares responses for the requests whose items we just uploaded.
+    /// This is called when the bookmarks sync finishes.
+    pub(crate) fn uploaded(&self) -> Result<()> {
+        let local_client_id = match &*self.client_data.borrow() {
+            Some(client_data) => client_data.local_client_id.clone(),
+            None => return Ok(()),
+        };
+        let mut responders = self.responders()?;
+        let mut changed = false;
+        for responder in &mut responders {
+            if responder.response.is_none() {
+                responder.response = Some(RepairResponse {
+                    collection: COLLECTION_NAME.into(),
+                    request: UPLOAD_REQUEST.into(),
+                    client_id: local_client_id.clone(),
+                    ids: responder.ids.clone(),
+                    flow_id: responder.flow_id.clone(),
+                });
+                changed = true;
+            }
+        }
+        if changed {
+            self.set_responders(&responders)?;
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: &RepairRequest) -> Result<CommandStatus> {
+        if request.request != UPLOAD_REQUEST {
+            log::warn!("Ignoring unknown repair request {}", request.request);
+            self.record(
+                flow_event("repairResponse", "aborted", &request.flow_id)
+                    .extra("reason", "unknownRequest".into()),
+            );
+            return Ok(CommandStatus::Ignored);
+        }
+
+        let tx = self.db.begin_transaction()?;
+        let ids = flag_for_reupload(self.db, &request.ids)?;
+        log::info!(
+            "Reuploading {} of {} bookmarks for repair {}",
+            ids.len(),
+            request.ids.len(),
+            request.flow_id
+        );
+        self.record(
+            flow_event("repairResponse", "uploading", &request.flow_id)
+                .extra("numIDs", ids.len().to_string()),
+        );
+        let mut responders = self.responders()?;
+        // A client might send the same request again if it didn't see our
+        // response, in which case we replace the old one.
+        responders.retain(|r| r.flow_id != request.flow_id);
+        responders.push(Responder {
+            requestor: request.requestor.clone(),
+            flow_id: request.flow_id.clone(),
+            ids,
+            response: None,
+        });
+        self.set_responders(&responders)?;
+        tx.commit()?;
+
+        Ok(CommandStatus::Applied)
+    }
+
+    fn handle_response(&self, response: &RepairResponse) -> Result<CommandStatus> {
+        let mut repair = match self.requestor()? {
+            Some(repair) if repair.flow_id == response.flow_id => repair,
+            _ => {
+                log::info!("Ignoring response for unknown repair {}", response.flow_id);
+                return Ok(CommandStatus::Ignored);
+            }
+        };
+        self.record(
+            flow_event("repair", "response", &repair.flow_id)
+                .extra("numIDs", response.ids.len().to_string()),
+        );
+        let repaired: HashSet<&str> = response.ids.iter().map(String::as_str).collect();
+        repair.ids.retain(|id| !repaired.contains(id.as_str()));
+        if repair
+            .target
+            .as_ref()
+            .map_or(false, |target| target.client_id == response.client_id)
+        {
+            repair.target = None;
+        }
+        self.continue_repair(repair)?;
+        Ok(CommandStatus::Applied)
+    }
+
+    /// Finishes the repair if there's nothing left to ask for, or asks the
+    /// next client if we aren't waiting on one already, then saves the
+    /// repair.
+    fn continue_repair(&self, mut repair: Requestor) -> Result<()> {
+        if repair.ids.is_empty() {
+            log::info!("Finished repair {}", repair.flow_id);
+            self.record(flow_event("repair", "finished", &repair.flow_id));
+            return self.set_requestor(None);
+        }
+        if repair.target.is_none() {
+            // We can only pick a client after the clients engine syncs; until
+            // then, we save the repair, and pick one on a later sync.
+            let client_data = self.client_data.borrow();
+            if let Some(client_data) = &*client_data {
+                match next_client(client_data, &repair.asked_clients) {
+                    Some(client_id) => {
+                        repair.asked_clients.push(client_id.clone());
+                        repair.target = Some(Target {
+                            client_id,
+                            request: RepairRequest {
+                                collection: COLLECTION_NAME.into(),
+                                request: UPLOAD_REQUEST.into(),
+                                requestor: client_data.local_client_id.clone(),
+                                ids: repair.ids.clone(),
+                                flow_id: repair.flow_id.clone(),
+                            },
+                            sent_at: None,
+                        });
+                    }
+                    None => {
+                        log::warn!(
+                            "No more clients to ask for {} missing bookmarks",
+                            repair.ids.len()
+                        );
+                        self.record(
+                            flow_event("repair", "aborted", &repair.flow_id)
+                                .extra("reason", "noClients".into())
+                                .extra("numIDs", repair.ids.len().to_string()),
+                        );
+                        return self.set_requestor(None);
+                    }
+                }
+            }
+        }
+        self.set_requestor(Some(&repair))
+    }
+
+    /// Returns `true` if `client_id` was in the clients collection on the last
+    /// sync, or if we don't know.
+    fn has_client(&self, client_id: &str) -> bool {
+        self.client_data
+            .borrow()
+            .as_ref()
+            .map_or(true, |client_data| {
+                client_data.recent_clients.contains_key(client_id)
+            })
+    }
+
+    fn record(&self, event: telemetry::Event) {
+        self.events.borrow_mut().push(event);
+    }
+
+    fn requestor(&self) -> Result<Option<Requestor>> {
+        get_json_meta(self.db, REQUESTOR_META_KEY)
+    }
+
+    fn set_requestor(&self, repair: Option<&Requestor>) -> Result<()> {
+        match repair {
+            Some(repair) => put_json_meta(self.db, REQUESTOR_META_KEY, repair),
+            None => delete_meta(self.db, REQUESTOR_META_KEY),
+        }
+    }
+
+    fn responders(&self) -> Result<Vec<Responder>> {
+        Ok(get_json_meta(self.db, RESPONDER_META_KEY)?.unwrap_or_default())
+    }
+
+    fn set_responders(&self, responders: &[Responder]) -> Result<()> {
+        if responders.is_empty() {
+            delete_meta(self.db, RESPONDER_META_KEY)
+        } else {
+            put_json_meta(self.db, RESPONDER_META_KEY, responders)
+        }
+    }
+}
+
+/// Picks the next client to ask for a repair. Desktop clients always handle
+/// repair requests, so we ask them first.
+fn next_client(client_data: &ClientData, asked_clients: &[String]) -> Option<String> {
+    let mut candidates = client_data
+        .recent_clients
+        .iter()
+        .filter(|(id, _)| {
+            **id != client_data.local_client_id && !asked_clients.iter().any(|c| c == *id)
+        })
+        .map(|(id, client)| (client.device_type != Some(DeviceType::Desktop), id))
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.into_iter().next().map(|(_, id)| id.clone())
+}
+
+/// Flags the local items with the given IDs for reupload, and returns the
+/// IDs that we have.
+fn flag_for_reupload(db: &PlacesDb, ids: &[String]) -> Result<Vec<String>> {
+    let root_guid = BookmarkRootGuid::Root.as_guid();
+    let mut found = Vec::new();
+    sql_support::each_chunk(ids, |chunk, _| -> Result<()> {
+        let mut stmt = db.prepare(&format!(
+            "SELECT guid FROM moz_bookmarks
+             WHERE guid IN ({vars}) AND
+                   guid <> '{root_guid}'",
+            vars = sql_support::repeat_sql_vars(chunk.len()),
+            root_guid = root_guid.as_str()
+        ))?;
+        let guids = stmt.query_and_then(chunk, |row| -> Result<String> { Ok(row.get(0)?) })?;
+        for guid in guids {
+            found.push(guid?);
+        }
+        db.execute(
+            &format!(
+                "UPDATE moz_bookmarks SET
+                   syncChangeCounter = syncChangeCounter + 1
+                 WHERE guid IN ({vars}) AND
+                       guid <> '{root_guid}'",
+                vars = sql_support::repeat_sql_vars(chunk.len()),
+                root_guid = root_guid.as_str()
+            ),
+            chunk,
+        )?;
+        Ok(())
+    })?;
+    Ok(found)
+}
+
+fn flow_event(object: &'static str, method: &'static str, flow_id: &str) -> telemetry::Event {
+    let event = telemetry::Event::new(object, method);
+    // Flow IDs from other clients should be GUIDs, but we don't want a
+    // malformed one to trip the length check for extra values.
+    if flow_id.len() <= 85 {
+        event.extra("flowID", flow_id.into())
+    } else {
+        event
+    }
+}
+
+fn get_json_meta<T: DeserializeOwned>(db: &PlacesDb, key: &str) -> Result<Option<T>> {
+    Ok(match get_meta::<String>(db, key)? {
+        Some(json) => Some(serde_json::from_str(&json)?),
+        None => None,
+    })
+}
+
+fn put_json_meta<T: Serialize + ?Sized>(db: &PlacesDb, key: &str, value: &T) -> Result<()> {
+    put_meta(db, key, &serde_json::to_string(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_api;
+    use crate::bookmark_sync::store::BookmarksStore;
+    use crate::storage::bookmarks::get_raw_bookmark;
+    use interrupt_support::NeverInterrupts;
+    use serde_json::{json, Value};
+    use sync15::clients::{self, CommandProcessor, RemoteClient, Settings};
+    use sync15::{IncomingChangeset, InfoConfiguration, Payload, ServerTimestamp, Store};
+
+    fn client_data() -> ClientData {
+        let mut recent_clients = HashMap::new();
+        for (id, device_type) in &[
+            ("deviceAAAAAA", DeviceType::Desktop),
+            ("deviceBBBBBB", DeviceType::Mobile),
+            ("deviceCCCCCC", DeviceType::Desktop),
+        ] {
+            recent_clients.insert(
+                id.to_string(),
+                RemoteClient {
+                    fxa_device_id: Some(id.to_string()),
+                    device_name: id.to_string(),
+                    device_type: Some(*device_type),
+                },
+            );
+        }
+        ClientData {
+            local_client_id: "deviceAAAAAA".into(),
+            recent_clients,
+        }
+    }
+
+    fn apply_incoming(store: &BookmarksStore<'_>, records: Value) -> telemetry::Engine {
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(0));
+        if let Value::Array(records) = records {
+            for record in records {
+                let payload = Payload::from_json(record).unwrap();
+                incoming.changes.push((payload, ServerTimestamp(0)));
+            }
+        }
+        let mut telem = telemetry::Engine::new("bookmarks");
+        let outgoing = store
+            .apply_incoming(vec![incoming], &mut telem)
+            .expect("Should apply incoming records");
+        let outgoing_ids = outgoing
+            .changes
+            .iter()
+            .map(|p| p.id.clone())
+            .collect::<Vec<_>>();
+        store
+            .sync_finished(ServerTimestamp(0), outgoing_ids)
+            .expect("Should push synced changes back to the store");
+        telem
+    }
+
+    /// Returns the events in `telem`, as "object/method" strings.
+    fn events(telem: telemetry::Engine) -> Vec<String> {
+        let mut sync = telemetry::SyncTelemetry::new();
+        sync.engine(telem);
+        let mut ping = telemetry::SyncTelemetryPing::new();
+        ping.sync(sync);
+        let ping = serde_json::to_value(&ping).unwrap();
+        ping["events"]
+            .as_array()
+            .map(|events| {
+                events
+                    .iter()
+                    .map(|e| {
+                        format!(
+                            "{}/{}",
+                            e["object"].as_str().unwrap(),
+                            e["method"].as_str().unwrap()
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Hands commands from the clients engine to the bookmarks store, like
+    /// the sync manager does.
+    struct StoreProcessor<'a, 'b> {
+        settings: Settings,
+        store: &'a BookmarksStore<'b>,
+    }
+
+    impl<'a, 'b> CommandProcessor for StoreProcessor<'a, 'b> {
+        fn settings(&self) -> &Settings {
+            &self.settings
+        }
+
+        fn fetch_outgoing_commands(&self) -> anyhow::Result<HashSet<Command>> {
+            Ok(HashSet::new())
+        }
+
+        fn fetch_outgoing_client_commands(
+            &self,
+        ) -> anyhow::Result<HashMap<String, HashSet<Command>>> {
+            self.store.fetch_outgoing_commands()
+        }
+
+        fn commands_sent(&self, sent: &HashMap<String, HashSet<Command>>) -> anyhow::Result<()> {
+            self.store.commands_sent(sent)
+        }
+
+        fn apply_incoming_command(&self, command: Command) -> anyhow::Result<CommandStatus> {
+            self.store.apply_incoming_command(&command)
+        }
+    }
+
+    fn clients_incoming(records: Value) -> IncomingChangeset {
+        let mut incoming = IncomingChangeset::new("clients", ServerTimestamp(0));
+        if let Value::Array(records) = records {
+            for record in records {
+                let payload = Payload::from_json(record).unwrap();
+                incoming.changes.push((payload, ServerTimestamp(0)));
+            }
+        }
+        incoming
+    }
+
+    fn sorted(mut ids: Vec<String>) -> Vec<String> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_requestor() -> Result<()> {
+        let api = new_mem_api();
+        let syncer = api.open_sync_connection()?;
+        let interrupt_scope = syncer.begin_interrupt_scope();
+        let store = BookmarksStore::new(&syncer, &interrupt_scope);
+        store
+            .prepare_for_sync(&client_data)
+            .expect("Should prepare for sync");
+
+        // The server is missing a child of unfiled, and the parent of
+        // `bookmark2___`.
+        let telem = apply_incoming(
+            &store,
+            json!([{
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "dateAdded": 0,
+                "title": "unfiled",
+                "children": ["bookmark1___", "missingChild"],
+            }, {
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 1",
+                "bmkUri": "http://example.com/1",
+            }, {
+                "id": "bookmark2___",
+                "type": "bookmark",
+                "parentid": "missingParnt",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 2",
+                "bmkUri": "http://example.com/2",
+            }]),
+        );
+        assert_eq!(events(telem), vec!["repair/started"]);
+
+        let repair = BookmarkRepair::new(&syncer);
+        repair.set_client_data(client_data());
+
+        // We should ask the other desktop client first.
+        let commands = repair.outgoing_commands()?;
+        assert_eq!(commands.len(), 1);
+        let request = match commands["deviceCCCCCC"].iter().next() {
+            Some(Command::RepairRequest(request)) => request.clone(),
+            command => panic!("Should send a repair request; got {:?}", command),
+        };
+        assert_eq!(request.collection, "bookmarks");
+        assert_eq!(request.request, "upload");
+        assert_eq!(request.requestor, "deviceAAAAAA");
+        assert_eq!(
+            sorted(request.ids.clone()),
+            vec!["missingChild", "missingParnt"]
+        );
+
+        // Once the request is sent, we shouldn't send it again.
+        repair.commands_sent(&commands)?;
+        assert!(repair.outgoing_commands()?.is_empty());
+
+        // Responses for other repairs are ignored.
+        let mut response = RepairResponse {
+            collection: "bookmarks".into(),
+            request: "upload".into(),
+            client_id: "deviceCCCCCC".into(),
+            ids: vec!["missingChild".into()],
+            flow_id: "unknownFlow_".into(),
+        };
+        assert_eq!(
+            repair.apply_command(&Command::RepairResponse(response.clone()))?,
+            CommandStatus::Ignored
+        );
+
+        // The first client only has one of the missing items, so we should
+        // ask the next client for the other.
+        response.flow_id = request.flow_id.clone();
+        assert_eq!(
+            repair.apply_command(&Command::RepairResponse(response))?,
+            CommandStatus::Applied
+        );
+        let commands = repair.outgoing_commands()?;
+        assert_eq!(commands.len(), 1);
+        match commands["deviceBBBBBB"].iter().next() {
+            Some(Command::RepairRequest(next_request)) => {
+                assert_eq!(next_request.flow_id, request.flow_id);
+                assert_eq!(next_request.ids, vec!["missingParnt"]);
+            }
+            command => panic!("Should send a repair request; got {:?}", command),
+        }
+        repair.commands_sent(&commands)?;
+
+        let response = RepairResponse {
+            collection: "bookmarks".into(),
+            request: "upload".into(),
+            client_id: "deviceBBBBBB".into(),
+            ids: vec!["missingParnt".into()],
+            flow_id: request.flow_id.clone(),
+        };
+        assert_eq!(
+            repair.apply_command(&Command::RepairResponse(response))?,
+            CommandStatus::Applied
+        );
+        assert!(repair.requestor()?.is_none());
+        assert!(repair.outgoing_commands()?.is_empty());
+
+        let mut telem = telemetry::Engine::new("bookmarks");
+        repair.record_telemetry(&mut telem);
+        assert_eq!(
+            events(telem),
+            vec![
+                "repair/request",
+                "repair/response",
+                "repair/request",
+                "repair/response",
+                "repair/finished",
+            ]
+        );
+
+        // We started a repair recently, so we shouldn't start another one.
+        repair.start(vec!["missingChild".into()])?;
+        assert!(repair.requestor()?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_requestor_no_clients() -> Result<()> {
+        let api = new_mem_api();
+        let syncer = api.open_sync_connection()?;
+        let repair = BookmarkRepair::new(&syncer);
+        repair.set_client_data(ClientData {
+            local_client_id: "deviceAAAAAA".into(),
+            recent_clients: HashMap::new(),
+        });
+
+        repair.start(vec!["missingChild".into()])?;
+        assert!(repair.requestor()?.is_none());
+
+        let mut telem = telemetry::Engine::new("bookmarks");
+        repair.record_telemetry(&mut telem);
+        assert_eq!(events(telem), vec!["repair/started", "repair/aborted"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_responder() -> Result<()> {
+        let api = new_mem_api();
+        let syncer = api.open_sync_connection()?;
+        let interrupt_scope = syncer.begin_interrupt_scope();
+        let store = BookmarksStore::new(&syncer, &interrupt_scope);
+        store
+            .prepare_for_sync(&|| ClientData {
+                local_client_id: "deviceCCCCCC".into(),
+                ..client_data()
+            })
+            .expect("Should prepare for sync");
+
+        apply_incoming(
+            &store,
+            json!([{
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "dateAdded": 0,
+                "title": "unfiled",
+                "children": ["bookmark1___"],
+            }, {
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 1",
+                "bmkUri": "http://example.com/1",
+            }]),
+        );
+        let bookmark =
+            get_raw_bookmark(&syncer, &"bookmark1___".into())?.expect("Should have bookmark");
+        assert_eq!(bookmark.sync_change_counter, 0);
+
+        let request = RepairRequest {
+            collection: "bookmarks".into(),
+            request: "upload".into(),
+            requestor: "deviceAAAAAA".into(),
+            ids: vec!["bookmark1___".into(), "missingChild".into()],
+            flow_id: "flooooooooow".into(),
+        };
+        assert_eq!(
+            store
+                .apply_incoming_command(&Command::RepairRequest(request))
+                .expect("Should apply repair request"),
+            CommandStatus::Applied
+        );
+        let bookmark =
+            get_raw_bookmark(&syncer, &"bookmark1___".into())?.expect("Should have bookmark");
+        assert!(bookmark.sync_change_counter > 0);
+
+        // We shouldn't respond until we've uploaded the bookmark.
+        assert!(store
+            .fetch_outgoing_commands()
+            .expect("Should fetch commands")
+            .is_empty());
+        let telem = apply_incoming(&store, json!([]));
+        assert_eq!(events(telem), vec!["repairResponse/uploading"]);
+        let bookmark =
+            get_raw_bookmark(&syncer, &"bookmark1___".into())?.expect("Should have bookmark");
+        assert_eq!(bookmark.sync_change_counter, 0);
+
+        let commands = store
+            .fetch_outgoing_commands()
+            .expect("Should fetch commands");
+        assert_eq!(commands.len(), 1);
+        let expected: HashSet<_> = [Command::RepairResponse(RepairResponse {
+            collection: "bookmarks".into(),
+            request: "upload".into(),
+            client_id: "deviceCCCCCC".into(),
+            ids: vec!["bookmark1___".into()],
+            flow_id: "flooooooooow".into(),
+        })]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(commands["deviceAAAAAA"], expected);
+
+        store
+            .commands_sent(&commands)
+            .expect("Should note sent commands");
+        assert!(store
+            .fetch_outgoing_commands()
+            .expect("Should fetch commands")
+            .is_empty());
+
+        // Requests for other collections aren't ours to handle.
+        let request = RepairRequest {
+            collection: "history".into(),
+            request: "upload".into(),
+            requestor: "deviceAAAAAA".into(),
+            ids: Vec::new(),
+            flow_id: "flooooooooow".into(),
+        };
+        assert_eq!(
+            store
+                .apply_incoming_command(&Command::RepairRequest(request))
+                .expect("Should not apply history repair request"),
+            CommandStatus::Unsupported
+        );
+
+        Ok(())
+    }
+    #[test]
+    fn test_responder_desktop_request() -> Result<()> {
+        let api = new_mem_api();
+        let syncer = api.open_sync_connection()?;
+        let interrupt_scope = syncer.begin_interrupt_scope();
+        let store = BookmarksStore::new(&syncer, &interrupt_scope);
+        store
+            .prepare_for_sync(&|| ClientData {
+                local_client_id: "deviceCCCCCC".into(),
+                ..client_data()
+            })
+            .expect("Should prepare for sync");
+
+        apply_incoming(
+            &store,
+            json!([{
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "dateAdded": 0,
+                "title": "unfiled",
+                "children": ["bookmark1___"],
+            }, {
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 1",
+                "bmkUri": "http://example.com/1",
+            }]),
+        );
+
+        let processor = StoreProcessor {
+            settings: Settings {
+                fxa_device_id: "deviceCCCCCC".into(),
+                device_name: "Laptop".into(),
+                device_type: DeviceType::Desktop,
+            },
+            store: &store,
+        };
+        let mut engine = clients::Engine::new(&processor, &NeverInterrupts);
+        let config = InfoConfiguration::default();
+        let requestor_record = json!({
+            "id": "deviceAAAAAA",
+            "name": "Desktop",
+            "type": "desktop",
+            "commands": [],
+            "fxaDeviceId": "deviceAAAAAA",
+            "protocols": ["1.5"],
+        });
+
+        // This is what Desktop writes into our client record: the request is
+        // an object, not a string.
+        let outgoing = engine
+            .apply_incoming(
+                clients_incoming(json!([requestor_record.clone(), {
+                    "id": "deviceCCCCCC",
+                    "name": "Laptop",
+                    "type": "desktop",
+                    "commands": [{
+                        "command": "repairRequest",
+                        "args": [{
+                            "collection": "bookmarks",
+                            "request": "upload",
+                            "requestor": "deviceAAAAAA",
+                            "ids": ["bookmark1___", "missingChild"],
+                            "flowID": "flooooooooow",
+                        }],
+                        "flowID": "flooooooooow",
+                    }],
+                    "fxaDeviceId": "deviceCCCCCC",
+                    "protocols": ["1.5"],
+                }])),
+                &config,
+                false,
+            )
+            .expect("Should apply desktop repair request");
+
+        // We applied the request, so our new record shouldn't have it.
+        let own_record = outgoing
+            .changes
+            .iter()
+            .find(|p| p.id.as_str() == "deviceCCCCCC")
+            .expect("Should reupload our client record");
+        assert!(own_record.data.get("commands").is_none());
+        let bookmark =
+            get_raw_bookmark(&syncer, &"bookmark1___".into())?.expect("Should have bookmark");
+        assert!(bookmark.sync_change_counter > 0);
+
+        let telem = apply_incoming(&store, json!([]));
+        assert_eq!(events(telem), vec!["repairResponse/uploading"]);
+
+        // The next clients sync should send Desktop an object, too.
+        let outgoing = engine
+            .apply_incoming(
+                clients_incoming(json!([requestor_record, {
+                    "id": "deviceCCCCCC",
+                    "name": "Laptop",
+                    "type": "desktop",
+                    "commands": [],
+                    "fxaDeviceId": "deviceCCCCCC",
+                    "protocols": ["1.5"],
+                }])),
+                &config,
+                false,
+            )
+            .expect("Should send repair response");
+        let requestor_record = outgoing
+            .changes
+            .iter()
+            .find(|p| p.id.as_str() == "deviceAAAAAA")
+            .expect("Should write response into requestor's record");
+        assert_eq!(
+            requestor_record.data["commands"],
+            json!([{
+                "command": "repairResponse",
+                "args": [{
+                    "collection": "bookmarks",
+                    "request": "upload",
+                    "clientID": "deviceCCCCCC",
+                    "ids": ["bookmark1___"],
+                    "flowID": "flooooooooow",
+                }],
+                "flowID": "flooooooooow",
+            }])
+        );
+
+        Ok(())
+    }
+}
@@ -7,6 +7,7 @@ use super::record::{
     BookmarkItemRecord, BookmarkRecord, BookmarkRecordId, FolderRecord, QueryRecord,
     SeparatorRecord,
 };
+use super::repair::BookmarkRepair;
 use super::{SyncedBookmarkKind, SyncedBookmarkValidity};
 use crate::api::places_api::ConnectionType;
 use crate::db::PlacesDb;
@@ -26,13 +27,15 @@ use dogear::{
 };
 use rusqlite::{Row, NO_PARAMS};
 use sql_support::{self, ConnExt, SqlInterruptScope};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use sync15::{
-    telemetry, CollSyncIds, CollectionRequest, IncomingChangeset, OutgoingChangeset, Payload,
-    ServerTimestamp, Store, StoreSyncAssociation,
+    clients::{ClientData, Command, CommandStatus},
+    telemetry, CollSyncIds, CollectionRequest, DryRun, IncomingChangeset, OutgoingChangeset,
+    Payload, PlannedAction, RecordPlan, ServerTimestamp, Store, StoreSyncAssociation,
+    ValidationReport,
 };
 use sync_guid::Guid as SyncGuid;
 pub const LAST_SYNC_META_KEY: &str = "bookmarks_last_sync_time";
@@ -63,12 +66,17 @@ where
 pub struct BookmarksStore<'a> {
     pub db: &'a PlacesDb,
     interruptee: &'a SqlInterruptScope,
+    repair: BookmarkRepair<'a>,
 }
 
 impl<'a> BookmarksStore<'a> {
     pub fn new(db: &'a PlacesDb, interruptee: &'a SqlInterruptScope) -> Self {
         assert_eq!(db.conn_type(), ConnectionType::Sync);
-        Self { db, interruptee }
+        Self {
+            db,
+            interruptee,
+            repair: BookmarkRepair::new(db),
+        }
     }
 
     fn stage_incoming(
@@ -100,6 +108,34 @@ impl<'a> BookmarksStore<'a> {
         Ok(timestamp)
     }
 
+    /// Stages, merges, and fetches outgoing records like `apply_incoming`,
+    /// but doesn't commit, so that the caller can roll everything back.
+    fn plan_incoming(
+        &self,
+        inbound: IncomingChangeset,
+        telem: &mut telemetry::Engine,
+    ) -> Result<DryRun> {
+        let timestamp = inbound.timestamp;
+        let mut incoming_telemetry = telemetry::EngineIncoming::new();
+        let applicator = IncomingApplicator::new(&self.db);
+        for incoming in inbound.changes {
+            applicator.apply_payload(incoming.0, incoming.1)?;
+            incoming_telemetry.applied(1);
+            self.interruptee.err_if_interrupted()?;
+        }
+        delete_pending_temp_tables(&self.db)?;
+        telem.incoming(incoming_telemetry);
+
+        let mut merger = Merger::with_telemetry(&self, timestamp, telem);
+        merger.set_external_transaction(true);
+        merger.record_plans();
+        merger.merge()?;
+        let incoming = merger.take_plans();
+
+        let outgoing = self.fetch_outgoing_records(timestamp)?;
+        Ok(DryRun { incoming, outgoing })
+    }
+
     fn has_changes(&self) -> Result<bool> {
         // In the first subquery, we check incoming items with needsMerge = true
         // except the tombstones who don't correspond to any local bookmark because
@@ -907,6 +943,24 @@ impl<'a> Store for BookmarksStore<'a> {
         "bookmarks".into()
     }
 
+    fn prepare_for_sync(&self, get_client_data: &dyn Fn() -> ClientData) -> anyhow::Result<()> {
+        self.repair.set_client_data(get_client_data());
+        Ok(())
+    }
+
+    fn fetch_outgoing_commands(&self) -> anyhow::Result<HashMap<String, HashSet<Command>>> {
+        Ok(self.repair.outgoing_commands()?)
+    }
+
+    fn commands_sent(&self, sent: &HashMap<String, HashSet<Command>>) -> anyhow::Result<()> {
+        self.repair.commands_sent(sent)?;
+        Ok(())
+    }
+
+    fn apply_incoming_command(&self, command: &Command) -> anyhow::Result<CommandStatus> {
+        Ok(self.repair.apply_command(command)?)
+    }
+
     fn apply_incoming(
         &self,
         inbound: Vec<IncomingChangeset>,
@@ -928,11 +982,46 @@ impl<'a> Store for BookmarksStore<'a> {
         let mut merger = Merger::with_telemetry(&self, timestamp, telem);
         merger.merge()?;
 
+        // If the server is missing items, ask other clients to reupload them.
+        let missing_ids = merger.missing_ids()?;
+        self.repair.check_progress()?;
+        self.repair.start(missing_ids)?;
+        self.repair.record_telemetry(telem);
+
         // Finally, stage outgoing items.
         let outgoing = self.fetch_outgoing_records(timestamp)?;
         Ok(outgoing)
     }
 
+    fn apply_incoming_dry_run(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<DryRun> {
+        assert_eq!(inbound.len(), 1, "bookmarks only requests one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        // Everything happens in one transaction, which we always roll back.
+        let tx = self.db.begin_transaction()?;
+        let result = self.plan_incoming(inbound, telem);
+        tx.rollback()?;
+        Ok(result?)
+    }
+
+    fn supports_validation(&self) -> bool {
+        true
+    }
+
+    fn validate(
+        &self,
+        records: Vec<(Payload, ServerTimestamp)>,
+    ) -> anyhow::Result<ValidationReport> {
+        Ok(super::validation::validate(
+            self.db,
+            self.interruptee,
+            records,
+        )?)
+    }
+
     fn sync_finished(
         &self,
         new_timestamp: ServerTimestamp,
@@ -940,6 +1029,7 @@ impl<'a> Store for BookmarksStore<'a> {
     ) -> anyhow::Result<()> {
         self.push_synced_items(new_timestamp, records_synced)?;
         self.update_frecencies()?;
+        self.repair.uploaded()?;
         self.db.pragma_update(None, "wal_checkpoint", &"PASSIVE")?;
         Ok(())
     }
@@ -1014,6 +1104,9 @@ impl<'a> Store for BookmarksStore<'a> {
 #[derive(Default)]
 struct Driver {
     validation: RefCell<telemetry::Validation>,
+    // Whether the remote tree has missing children or parents, which we can
+    // ask other clients to repair.
+    remote_tree_broken: Cell<bool>,
 }
 
 impl dogear::Driver for Driver {
@@ -1039,6 +1132,12 @@ impl dogear::Driver for Driver {
                     stats.problems.parent_child_disagreements,
                 )
                 .problem("missingChildren", stats.problems.missing_children);
+            self.remote_tree_broken.set(
+                stats.problems.orphans
+                    + stats.problems.missing_parent_guids
+                    + stats.problems.missing_children
+                    > 0,
+            );
         }
     }
 }
@@ -1054,6 +1153,11 @@ pub(crate) struct Merger<'a> {
     // turns it on, to avoid accidentally enabling unintentionally.
     external_transaction: bool,
     telem: Option<&'a mut telemetry::Engine>,
+    // The actions that we planned for incoming items, if we're recording
+    // them for a dry run.
+    plans: Option<Vec<RecordPlan>>,
+    // Whether the last merge found missing items in the remote tree.
+    remote_tree_broken: bool,
 }
 
 impl<'a> Merger<'a> {
@@ -1064,6 +1168,8 @@ impl<'a> Merger<'a> {
             local_time: Timestamp::now(),
             external_transaction: false,
             telem: None,
+            plans: None,
+            remote_tree_broken: false,
         }
     }
 
@@ -1078,6 +1184,8 @@ impl<'a> Merger<'a> {
             local_time: Timestamp::now(),
             external_transaction: false,
             telem: Some(telem),
+            plans: None,
+            remote_tree_broken: false,
         }
     }
 
@@ -1093,6 +1201,8 @@ impl<'a> Merger<'a> {
             local_time,
             external_transaction: false,
             telem: None,
+            plans: None,
+            remote_tree_broken: false,
         }
     }
 
@@ -1104,6 +1214,16 @@ impl<'a> Merger<'a> {
         self.external_transaction = v;
     }
 
+    /// Records the actions that `apply()` takes for incoming items, so that
+    /// dry runs can report them.
+    pub(crate) fn record_plans(&mut self) {
+        self.plans = Some(Vec::new());
+    }
+
+    pub(crate) fn take_plans(&mut self) -> Vec<RecordPlan> {
+        self.plans.take().unwrap_or_default()
+    }
+
     pub(crate) fn merge(&mut self) -> Result<()> {
         use dogear::Store;
         if !self.store.has_changes()? {
@@ -1116,12 +1236,42 @@ impl<'a> Merger<'a> {
         log::debug!("merge completed");
 
         // Record telemetry in all cases, even if the merge fails.
+        self.remote_tree_broken = driver.remote_tree_broken.get();
         if let Some(ref mut telem) = self.telem {
             telem.validation(driver.validation.into_inner());
         }
         result
     }
 
+    /// Returns the IDs of items that are missing from the server, if the last
+    /// merge found any problems with the remote tree. These are children that
+    /// folders on the server reference, but that we don't have records for,
+    /// and parents of items on the server that don't exist.
+    pub(crate) fn missing_ids(&self) -> Result<Vec<SyncGuid>> {
+        if !self.remote_tree_broken {
+            return Ok(Vec::new());
+        }
+        let sql = "SELECT s.guid
+                   FROM moz_bookmarks_synced_structure s
+                   LEFT JOIN moz_bookmarks_synced v ON v.guid = s.guid
+                   WHERE v.guid IS NULL
+                   UNION
+                   SELECT v.parentGuid
+                   FROM moz_bookmarks_synced v
+                   LEFT JOIN moz_bookmarks_synced p ON p.guid = v.parentGuid
+                   WHERE NOT v.isDeleted AND
+                         v.parentGuid NOT NULL AND
+                         p.guid IS NULL";
+        let mut stmt = self.store.db.prepare(sql)?;
+        let mut results = stmt.query(NO_PARAMS)?;
+        let mut ids = Vec::new();
+        while let Some(row) = results.next()? {
+            self.store.interruptee.err_if_interrupted()?;
+            ids.push(row.get::<_, SyncGuid>(0)?);
+        }
+        Ok(ids)
+    }
+
     /// Prepares synced bookmarks for merging.
     fn prepare(&self) -> Result<()> {
         // Sync and Fennec associate keywords with bookmarks, and don't sync
@@ -1490,6 +1640,25 @@ impl<'a> dogear::Store for Merger<'a> {
             return Ok(());
         }
 
+        if let Some(plans) = &mut self.plans {
+            plans.extend(
+                ops.apply_remote_items.iter().map(|op| {
+                    RecordPlan::new(op.remote_node().guid.as_str(), PlannedAction::Apply)
+                }),
+            );
+            plans.extend(
+                ops.delete_local_items.iter().map(|op| {
+                    RecordPlan::new(op.local_node().guid.as_str(), PlannedAction::Delete)
+                }),
+            );
+            // Changing a GUID means we deduped a local item with a remote one.
+            plans.extend(
+                ops.change_guids.iter().map(|op| {
+                    RecordPlan::new(op.merged_node.guid.as_str(), PlannedAction::Conflict)
+                }),
+            );
+        }
+
         let tx = if !self.external_transaction {
             Some(self.store.db.begin_transaction()?)
         } else {
@@ -2103,6 +2272,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_apply_incoming_dry_run() -> Result<()> {
+        let api = new_mem_api();
+        let syncer = api.open_sync_connection()?;
+        let interrupt_scope = syncer.begin_interrupt_scope();
+        let store = BookmarksStore::new(&syncer, &interrupt_scope);
+
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(0));
+        for record in vec![
+            json!({
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "parentName": "Unfiled Bookmarks",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Some bookmark",
+                "bmkUri": "http://example.com",
+            }),
+            json!({
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "parentName": "",
+                "dateAdded": 0,
+                "title": "Unfiled Bookmarks",
+                "children": ["bookmark1___"],
+            }),
+        ] {
+            let payload = Payload::from_json(record).unwrap();
+            incoming.changes.push((payload, ServerTimestamp(0)));
+        }
+
+        let dry_run = store
+            .apply_incoming_dry_run(vec![incoming], &mut telemetry::Engine::new("bookmarks"))
+            .expect("Should plan incoming records");
+        assert!(dry_run
+            .incoming
+            .contains(&RecordPlan::new("bookmark1___", PlannedAction::Apply)));
+        assert!(!dry_run.outgoing.changes.is_empty());
+
+        // The bookmark shouldn't have been staged or applied.
+        assert!(get_raw_bookmark(&syncer, &"bookmark1___".into())?.is_none());
+        let staged = syncer.query_one::<bool>(
+            "SELECT EXISTS(SELECT 1 FROM moz_bookmarks_synced
+                           WHERE guid = 'bookmark1___')",
+        )?;
+        assert!(!staged);
+        Ok(())
+    }
+
     #[test]
     fn test_apply_complex_bookmark_tags() -> Result<()> {
         let api = new_mem_api();
@@ -0,0 +1,515 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Compares the bookmarks on the server with the local tree, like Desktop's
+//! bookmark validator.
+//!
+//! The server tree is checked for structural problems first: records whose
+//! parents are missing, or that aren't in their parents' children, folders
+//! with missing children, and cycles. Then, each server record is compared
+//! with the local item. Local items with changes that we haven't uploaded
+//! yet are skipped, since the next sync will fix them.
+
+use super::record::{BookmarkItemRecord, BookmarkRecordId};
+use super::SyncedBookmarkKind;
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::bookmarks::BookmarkRootGuid;
+use crate::types::{BookmarkType, SyncStatus};
+use sql_support::{ConnExt, SqlInterruptScope};
+use std::collections::{HashMap, HashSet};
+use sync15::{Payload, ProblemKind, ServerTimestamp, ValidationReport};
+use sync_guid::Guid as SyncGuid;
+use url::Url;
+
+/// The version of the bookmarks validator, for telemetry. Bump this when
+/// the validator changes what it reports.
+const VALIDATION_VERSION: u32 = 1;
+
+/// The parts of a server record that we check.
+struct ServerItem {
+    kind: SyncedBookmarkKind,
+    parent_guid: Option<SyncGuid>,
+    title: String,
+    url: Option<String>,
+    children: Vec<SyncGuid>,
+}
+
+impl ServerItem {
+    fn from_record(record: BookmarkItemRecord) -> (SyncGuid, ServerItem) {
+        let (record_id, item) = match record {
+            BookmarkItemRecord::Bookmark(b) => (
+                b.record_id,
+                ServerItem {
+                    kind: SyncedBookmarkKind::Bookmark,
+                    parent_guid: b.parent_record_id.map(Into::into),
+                    title: b.title.unwrap_or_default(),
+                    // Other clients might not have normalized the URL.
+                    url: b.url.map(|url| match Url::parse(&url) {
+                        Ok(url) => url.into_string(),
+                        Err(_) => url,
+                    }),
+                    children: Vec::new(),
+                },
+            ),
+            BookmarkItemRecord::Query(q) => (
+                q.record_id,
+                ServerItem {
+                    kind: SyncedBookmarkKind::Query,
+                    parent_guid: q.parent_record_id.map(Into::into),
+                    title: q.title.unwrap_or_default(),
+                    // We rewrite tag queries when we apply them, so their
+                    // URLs won't match.
+                    url: None,
+                    children: Vec::new(),
+                },
+            ),
+            BookmarkItemRecord::Folder(f) => (
+                f.record_id,
+                ServerItem {
+                    kind: SyncedBookmarkKind::Folder,
+                    parent_guid: f.parent_record_id.map(Into::into),
+                    title: f.title.unwrap_or_default(),
+                    url: None,
+                    children: f.children.into_iter().map(Into::into).collect(),
+                },
+            ),
+            BookmarkItemRecord::Livemark(l) => (
+                l.record_id,
+                ServerItem {
+                    kind: SyncedBookmarkKind::Livemark,
+                    parent_guid: l.parent_record_id.map(Into::into),
+                    title: l.title.unwrap_or_default(),
+                    url: None,
+                    children: Vec::new(),
+                },
+            ),
+            BookmarkItemRecord::Separator(s) => (
+                s.record_id,
+                ServerItem {
+                    kind: SyncedBookmarkKind::Separator,
+                    parent_guid: s.parent_record_id.map(Into::into),
+                    title: String::new(),
+                    url: None,
+                    children: Vec::new(),
+                },
+            ),
+        };
+        (record_id.into(), item)
+    }
+
+    /// Returns `true` if the local item has the same kind. Queries are
+    /// stored as bookmarks, and livemarks as folders.
+    fn kind_matches(&self, local_type: BookmarkType) -> bool {
+        match (self.kind, local_type) {
+            (SyncedBookmarkKind::Bookmark, BookmarkType::Bookmark)
+            | (SyncedBookmarkKind::Query, BookmarkType::Bookmark)
+            | (SyncedBookmarkKind::Folder, BookmarkType::Folder)
+            | (SyncedBookmarkKind::Livemark, BookmarkType::Folder)
+            | (SyncedBookmarkKind::Separator, BookmarkType::Separator) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The parts of a local item that we check.
+struct LocalItem {
+    item_type: BookmarkType,
+    parent_guid: Option<SyncGuid>,
+    title: String,
+    url: Option<String>,
+    children: Vec<SyncGuid>,
+    /// `false` if the item has changes that we haven't uploaded yet.
+    synced: bool,
+}
+
+impl LocalItem {
+    /// Returns the names of the fields that differ between this item and
+    /// the server record.
+    fn differences(&self, remote: &ServerItem) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if !remote.kind_matches(self.item_type) {
+            fields.push("type");
+        }
+        if remote.parent_guid != self.parent_guid {
+            fields.push("parentid");
+        }
+        if remote.title != self.title {
+            fields.push("title");
+        }
+        if remote.url.is_some() && remote.url != self.url {
+            fields.push("bmkUri");
+        }
+        if remote.children != self.children {
+            fields.push("children");
+        }
+        fields
+    }
+}
+
+/// Validates `records`, which are all the records in the bookmarks
+/// collection on the server, against the local tree.
+pub(crate) fn validate(
+    db: &PlacesDb,
+    scope: &SqlInterruptScope,
+    records: Vec<(Payload, ServerTimestamp)>,
+) -> Result<ValidationReport> {
+    let mut report = ValidationReport::new(VALIDATION_VERSION);
+
+    let mut seen = HashSet::with_capacity(records.len());
+    let mut server_items = HashMap::with_capacity(records.len());
+    let mut tombstones = Vec::new();
+    for (payload, _) in records {
+        let guid: SyncGuid = BookmarkRecordId::from_payload_id(payload.id.clone()).into();
+        if !seen.insert(guid.clone()) {
+            report.problem(ProblemKind::Duplicate, guid);
+            continue;
+        }
+        report.checked += 1;
+        if payload.is_tombstone() {
+            tombstones.push(guid);
+            continue;
+        }
+        match payload.into_record::<BookmarkItemRecord>() {
+            Ok(record) => {
+                let (guid, item) = ServerItem::from_record(record);
+                server_items.insert(guid, item);
+            }
+            Err(e) => log::warn!("Failed to deserialize bookmark record: {}", e),
+        }
+    }
+    scope.err_if_interrupted()?;
+
+    check_server_tree(&server_items, &mut report);
+    scope.err_if_interrupted()?;
+
+    let mut local_items = fetch_local_items(db)?;
+    scope.err_if_interrupted()?;
+
+    for (guid, remote) in &server_items {
+        match local_items.remove(guid) {
+            Some(local) => {
+                if local.synced {
+                    report.difference(guid.clone(), local.differences(remote));
+                }
+            }
+            None => {
+                report.problem(ProblemKind::ClientMissing, guid.clone());
+            }
+        }
+    }
+    for guid in tombstones {
+        if let Some(local) = local_items.remove(&guid) {
+            if local.synced {
+                report.problem(ProblemKind::ServerMissing, guid);
+            }
+        }
+    }
+    for (guid, local) in local_items {
+        // The Places root isn't synced.
+        if local.synced && guid != BookmarkRootGuid::Root.as_guid() {
+            report.problem(ProblemKind::ServerMissing, guid);
+        }
+    }
+    Ok(report)
+}
+
+/// Checks that the server records form a tree.
+fn check_server_tree(server_items: &HashMap<SyncGuid, ServerItem>, report: &mut ValidationReport) {
+    let root_guid = BookmarkRootGuid::Root.as_guid();
+    for (guid, item) in server_items {
+        match &item.parent_guid {
+            // The Places root isn't on the server, but the user content
+            // roots still point to it.
+            Some(parent_guid) if *parent_guid == root_guid => {}
+            Some(parent_guid) => match server_items.get(parent_guid) {
+                Some(parent) => {
+                    if !parent.children.contains(guid) {
+                        report.problem(ProblemKind::Orphan, guid.clone());
+                    }
+                }
+                None => {
+                    report.problem(ProblemKind::MissingParent, guid.clone());
+                }
+            },
+            None => {
+                report.problem(ProblemKind::MissingParent, guid.clone());
+            }
+        }
+        for child_guid in &item.children {
+            if !server_items.contains_key(child_guid) {
+                report.problem(ProblemKind::MissingChild, child_guid.clone());
+            }
+        }
+        if in_cycle(server_items, guid) {
+            report.problem(ProblemKind::Cycle, guid.clone());
+        }
+    }
+}
+
+/// Returns `true` if following the parents of the record `guid` leads back
+/// to it.
+fn in_cycle(server_items: &HashMap<SyncGuid, ServerItem>, guid: &SyncGuid) -> bool {
+    let mut ancestors = HashSet::new();
+    let mut parent_guid = server_items
+        .get(guid)
+        .and_then(|item| item.parent_guid.as_ref());
+    while let Some(ancestor_guid) = parent_guid {
+        if ancestor_guid == guid {
+            return true;
+        }
+        if !ancestors.insert(ancestor_guid) {
+            // We found a cycle that doesn't include this record.
+            return false;
+        }
+        parent_guid = server_items
+            .get(ancestor_guid)
+            .and_then(|item| item.parent_guid.as_ref());
+    }
+    false
+}
+
+fn fetch_local_items(db: &PlacesDb) -> Result<HashMap<SyncGuid, LocalItem>> {
+    let rows = db.query_rows_and_then_named(
+        &format!(
+            "SELECT b.guid, p.guid AS parentGuid, b.type, b.title, h.url,
+                    b.syncStatus = {sync_status} AND
+                        b.syncChangeCounter = 0 AS synced
+             FROM moz_bookmarks b
+             LEFT JOIN moz_bookmarks p ON p.id = b.parent
+             LEFT JOIN moz_places h ON h.id = b.fk
+             ORDER BY b.parent, b.position",
+            sync_status = SyncStatus::Normal as u8
+        ),
+        &[],
+        |row| -> Result<(SyncGuid, LocalItem)> {
+            Ok((
+                row.get("guid")?,
+                LocalItem {
+                    item_type: row.get("type")?,
+                    parent_guid: row.get("parentGuid")?,
+                    title: row.get::<_, Option<String>>("title")?.unwrap_or_default(),
+                    url: row.get("url")?,
+                    children: Vec::new(),
+                    synced: row.get("synced")?,
+                },
+            ))
+        },
+    )?;
+    // Rows are ordered by position, so children are added in order.
+    let mut children: HashMap<SyncGuid, Vec<SyncGuid>> = HashMap::new();
+    for (guid, item) in &rows {
+        if let Some(parent_guid) = &item.parent_guid {
+            children
+                .entry(parent_guid.clone())
+                .or_default()
+                .push(guid.clone());
+        }
+    }
+    Ok(rows
+        .into_iter()
+        .map(|(guid, mut item)| {
+            item.children = children.remove(&guid).unwrap_or_default();
+            (guid, item)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_api;
+    use crate::bookmark_sync::store::BookmarksStore;
+    use serde_json::json;
+    use sync15::{telemetry, IncomingChangeset, Store};
+
+    fn ids(report: &ValidationReport, kind: ProblemKind) -> Vec<&str> {
+        let mut ids = report
+            .ids(kind)
+            .into_iter()
+            .map(SyncGuid::as_str)
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let api = new_mem_api();
+        let syncer = api.open_sync_connection()?;
+        let interrupt_scope = syncer.begin_interrupt_scope();
+        let store = BookmarksStore::new(&syncer, &interrupt_scope);
+
+        let records = vec![
+            json!({
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "dateAdded": 0,
+                "title": "unfiled",
+                "children": ["bookmark1___", "bookmark2___", "folder1_____"],
+            }),
+            json!({
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 1",
+                "bmkUri": "http://example.com/1",
+            }),
+            json!({
+                "id": "bookmark2___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 2",
+                "bmkUri": "http://example.com/2",
+            }),
+            json!({
+                "id": "folder1_____",
+                "type": "folder",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Folder 1",
+                "children": ["bookmark3___"],
+            }),
+            json!({
+                "id": "bookmark3___",
+                "type": "bookmark",
+                "parentid": "folder1_____",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 3",
+                "bmkUri": "http://example.com/3",
+            }),
+        ];
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(0));
+        for record in records {
+            let payload = Payload::from_json(record).unwrap();
+            incoming.changes.push((payload, ServerTimestamp(0)));
+        }
+        let outgoing = store
+            .apply_incoming(vec![incoming], &mut telemetry::Engine::new("bookmarks"))
+            .expect("Should apply incoming records");
+        let outgoing_ids = outgoing
+            .changes
+            .iter()
+            .map(|p| p.id.clone())
+            .collect::<Vec<_>>();
+        store
+            .sync_finished(ServerTimestamp(0), outgoing_ids)
+            .expect("Should push synced changes back to the store");
+
+        // The roots on the server are the ones we just uploaded, but the
+        // other records have changed.
+        let ts = ServerTimestamp(1000);
+        let mut server = outgoing
+            .changes
+            .into_iter()
+            .map(|payload| (payload, ts))
+            .collect::<Vec<_>>();
+        for record in vec![
+            // Changed title.
+            json!({
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 1 (changed)",
+                "bmkUri": "http://example.com/1",
+            }),
+            // Duplicate.
+            json!({
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 1",
+                "bmkUri": "http://example.com/1",
+            }),
+            // `bookmark2___` is missing, and `bookmark3___` isn't in its
+            // parent's children.
+            json!({
+                "id": "folder1_____",
+                "type": "folder",
+                "parentid": "unfiled",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Folder 1",
+                "children": [],
+            }),
+            json!({
+                "id": "bookmark3___",
+                "type": "bookmark",
+                "parentid": "folder1_____",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 3",
+                "bmkUri": "http://example.com/3",
+            }),
+            // New on the server, with a missing parent.
+            json!({
+                "id": "bookmark4___",
+                "type": "bookmark",
+                "parentid": "missing_____",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "Bookmark 4",
+                "bmkUri": "http://example.com/4",
+            }),
+            // New on the server, and each other's parents.
+            json!({
+                "id": "cycleA______",
+                "type": "folder",
+                "parentid": "cycleB______",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "A",
+                "children": ["cycleB______"],
+            }),
+            json!({
+                "id": "cycleB______",
+                "type": "folder",
+                "parentid": "cycleA______",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "B",
+                "children": ["cycleA______"],
+            }),
+        ] {
+            server.push((Payload::from_json(record).unwrap(), ts));
+        }
+
+        let report = store.validate(server).expect("Should validate");
+        assert_eq!(ids(&report, ProblemKind::Duplicate), vec!["bookmark1___"]);
+        assert_eq!(ids(&report, ProblemKind::Orphan), vec!["bookmark3___"]);
+        assert_eq!(
+            ids(&report, ProblemKind::MissingParent),
+            vec!["bookmark4___"]
+        );
+        assert_eq!(
+            ids(&report, ProblemKind::MissingChild),
+            vec!["bookmark2___"]
+        );
+        assert_eq!(
+            ids(&report, ProblemKind::Cycle),
+            vec!["cycleA______", "cycleB______"]
+        );
+        assert_eq!(
+            ids(&report, ProblemKind::ClientMissing),
+            vec!["bookmark4___", "cycleA______", "cycleB______"]
+        );
+        assert_eq!(
+            ids(&report, ProblemKind::ServerMissing),
+            vec!["bookmark2___"]
+        );
+        let differences = report
+            .problems
+            .iter()
+            .filter(|p| p.kind == ProblemKind::Difference)
+            .map(|p| (p.id.as_str(), p.fields.clone()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(differences.get("bookmark1___"), Some(&vec!["title"]));
+        assert_eq!(differences.get("folder1_____"), Some(&vec!["children"]));
+        assert_eq!(differences.len(), 2);
+
+        let telem = serde_json::to_value(&report.to_telemetry()).unwrap();
+        assert_eq!(telem["version"], VALIDATION_VERSION);
+        Ok(())
+    }
+}
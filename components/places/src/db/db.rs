@@ -194,6 +194,12 @@ fn define_functions(c: &Connection) -> Result<()> {
         FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
         sql_fns::reverse_host,
     )?;
+    c.create_scalar_function(
+        "root_icon_url",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        sql_fns::root_icon_url,
+    )?;
     c.create_scalar_function(
         "autocomplete_match",
         10,
@@ -348,6 +354,15 @@ pub(crate) mod sql_fns {
         Ok(host_and_port.to_owned())
     }
 
+    #[inline(never)]
+    pub fn root_icon_url(ctx: &Context<'_>) -> Result<Option<String>> {
+        let href = get_raw_opt_str(ctx, "root_icon_url", 0)?;
+        Ok(href
+            .and_then(|href| url::Url::parse(href).ok())
+            .and_then(|url| crate::storage::icons::root_icon_url(&url))
+            .map(url::Url::into_string))
+    }
+
     #[inline(never)]
     pub fn strip_prefix_and_userinfo(ctx: &Context<'_>) -> Result<String> {
         let href = get_raw_str(ctx, "strip_prefix_and_userinfo", 0)?;
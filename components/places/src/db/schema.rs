@@ -18,7 +18,7 @@ use crate::types::SyncStatus;
 use rusqlite::NO_PARAMS;
 use sql_support::ConnExt;
 
-const VERSION: i64 = 12;
+const VERSION: i64 = 14;
 
 // Shared schema and temp tables for the read-write and Sync connections.
 const CREATE_SHARED_SCHEMA_SQL: &str = include_str!("../../sql/create_shared_schema.sql");
@@ -38,6 +38,8 @@ lazy_static::lazy_static! {
             include_str!("../../sql/create_shared_triggers.sql"),
             increase_frecency_stats = update_origin_frecency_stats("+"),
             decrease_frecency_stats = update_origin_frecency_stats("-"),
+            index_new_place_fts = index_places_fts("h.id = NEW.id"),
+            index_new_bookmark_fts = index_places_fts("h.id = NEW.fk"),
         )
     };
 }
@@ -76,6 +78,30 @@ fn update_origin_frecency_stats(op: &str) -> String {
     )
 }
 
+/// Returns a statement that adds the pages matching `filter` to the full-text
+/// index. This doesn't remove existing rows for the pages, so callers need to
+/// do that first when reindexing.
+fn index_places_fts(filter: &str) -> String {
+    format!(
+        "
+        INSERT INTO moz_places_fts(rowid, url, title, bookmark_titles, tags, keyword)
+        SELECT
+            h.id,
+            h.url,
+            IFNULL(h.title, ''),
+            IFNULL((SELECT group_concat(b.title, ' ') FROM moz_bookmarks b
+                    WHERE b.fk = h.id), ''),
+            IFNULL((SELECT group_concat(t.tag, ' ') FROM moz_tags t
+                    JOIN moz_tags_relation r ON r.tag_id = t.id
+                    WHERE r.place_id = h.id), ''),
+            IFNULL((SELECT k.keyword FROM moz_keywords k
+                    WHERE k.place_id = h.id), '')
+        FROM moz_places h
+        WHERE {filter}",
+        filter = filter,
+    )
+}
+
 fn get_current_schema_version(db: &PlacesDb) -> Result<i64> {
     Ok(db.query_one::<i64>("PRAGMA user_version")?)
 }
@@ -266,6 +292,62 @@ fn upgrade(db: &PlacesDb, from: i64) -> Result<()> {
         ],
         || Ok(()),
     )?;
+    migration(
+        db,
+        12,
+        13,
+        &[
+            // Add tables for favicons.
+            "CREATE TABLE IF NOT EXISTS moz_icons(
+                 id INTEGER PRIMARY KEY,
+                 icon_url TEXT NOT NULL,
+                 icon_url_hash INTEGER NOT NULL,
+                 width INTEGER NOT NULL DEFAULT 0,
+                 root INTEGER NOT NULL DEFAULT 0,
+                 expire_ms INTEGER NOT NULL DEFAULT 0,
+                 mime_type TEXT,
+                 data BLOB NOT NULL
+             )",
+            "CREATE INDEX IF NOT EXISTS moz_icons_iconurlhashindex
+             ON moz_icons(icon_url_hash)",
+            "CREATE TABLE IF NOT EXISTS moz_pages_w_icons(
+                 id INTEGER PRIMARY KEY,
+                 page_url TEXT NOT NULL,
+                 page_url_hash INTEGER NOT NULL
+             )",
+            "CREATE INDEX IF NOT EXISTS moz_pages_w_icons_urlhashindex
+             ON moz_pages_w_icons(page_url_hash)",
+            "CREATE TABLE IF NOT EXISTS moz_icons_to_pages(
+                 page_id INTEGER NOT NULL REFERENCES moz_pages_w_icons(id)
+                                          ON DELETE CASCADE,
+                 icon_id INTEGER NOT NULL REFERENCES moz_icons(id)
+                                          ON DELETE CASCADE,
+                 PRIMARY KEY(page_id, icon_id)
+             ) WITHOUT ROWID",
+            "CREATE INDEX IF NOT EXISTS moz_icons_to_pages_iconindex
+             ON moz_icons_to_pages(icon_id)",
+        ],
+        || Ok(()),
+    )?;
+    migration(
+        db,
+        13,
+        14,
+        &[
+            // Add a full-text index for autocomplete, and index all existing
+            // pages.
+            "CREATE VIRTUAL TABLE IF NOT EXISTS moz_places_fts USING fts5(
+                 url,
+                 title,
+                 bookmark_titles,
+                 tags,
+                 keyword,
+                 prefix = '2 3'
+             )",
+            &index_places_fts("1"),
+        ],
+        || Ok(()),
+    )?;
     // Add more migrations here...
 
     if get_current_schema_version(db)? == VERSION {
@@ -75,6 +75,9 @@ pub enum ErrorKind {
 
     #[error("Database version {0} is not supported")]
     UnsupportedDatabaseVersion(i64),
+
+    #[error("Error importing bookmarks: {0}")]
+    ImportError(String),
 }
 
 error_support::define_error! {
@@ -125,6 +128,12 @@ pub enum InvalidPlaceInfo {
     // Like Urls, a tag is considered private info, so the value isn't in the error.
     #[error("The tag value is invalid")]
     InvalidTag,
+
+    // Only returned when storing a root icon with a URL that isn't
+    // `/favicon.ico` at the root of an origin.
+    #[error("Not a root icon URL")]
+    InvalidIconUrl,
+
     #[error("Cannot change the '{0}' property of a bookmark of type {1:?}")]
     IllegalChange(&'static str, BookmarkType),
 
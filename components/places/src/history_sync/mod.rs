@@ -11,7 +11,7 @@ mod plan;
 pub mod record;
 pub mod store;
 
-const MAX_INCOMING_PLACES: usize = 5000;
+const INCOMING_PAGE_SIZE: usize = 1000;
 const MAX_OUTGOING_PLACES: usize = 5000;
 const MAX_VISITS: usize = 20;
 pub const HISTORY_TTL: u32 = 5_184_000; // 60 days in milliseconds
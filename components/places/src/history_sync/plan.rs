@@ -19,7 +19,10 @@ use interrupt_support::Interruptee;
 use std::collections::HashSet;
 use std::time::{SystemTime, UNIX_EPOCH};
 use sync15::telemetry;
-use sync15::{IncomingChangeset, OutgoingChangeset, Payload};
+use sync15::{
+    DryRun, IncomingChangeset, OutgoingChangeset, Payload, PlannedAction, RecordPlan,
+    ServerTimestamp,
+};
 use sync_guid::Guid as SyncGuid;
 use url::Url;
 
@@ -178,9 +181,57 @@ pub fn apply_plan(
     telem: &mut telemetry::EngineIncoming,
     interruptee: &impl Interruptee,
 ) -> Result<OutgoingChangeset> {
+    let timestamp = inbound.timestamp;
+    apply_incoming_changes(db, inbound.changes, telem, interruptee)?;
+    fetch_outgoing_changeset(db, timestamp)
+}
+
+/// Works out what `apply_plan` would do, by applying the incoming records
+/// and building the outgoing changeset in a transaction that we roll back.
+pub fn dry_run_plan(
+    db: &PlacesDb,
+    inbound: IncomingChangeset,
+    telem: &mut telemetry::EngineIncoming,
+    interruptee: &impl Interruptee,
+) -> Result<DryRun> {
+    let timestamp = inbound.timestamp;
+    let plans = plan_incoming_changes(db, inbound.changes, telem, interruptee)?;
+    let incoming = plans
+        .iter()
+        .filter_map(|(guid, plan)| match plan {
+            IncomingPlan::Delete => Some(RecordPlan::new(guid.clone(), PlannedAction::Delete)),
+            IncomingPlan::Apply { .. } => Some(RecordPlan::new(guid.clone(), PlannedAction::Apply)),
+            _ => None,
+        })
+        .collect();
+
+    let tx = db.begin_transaction()?;
+    let outgoing = plans
+        .into_iter()
+        .try_for_each(|(guid, plan)| {
+            interruptee.err_if_interrupted()?;
+            apply_incoming_record(db, guid, plan, telem)
+        })
+        .and_then(|_| {
+            delete_pending_temp_tables(db)?;
+            build_outgoing_changeset(db, timestamp)
+        });
+    tx.rollback()?;
+    Ok(DryRun {
+        incoming,
+        outgoing: outgoing?,
+    })
+}
+
+fn plan_incoming_changes(
+    db: &PlacesDb,
+    changes: Vec<(Payload, ServerTimestamp)>,
+    telem: &mut telemetry::EngineIncoming,
+    interruptee: &impl Interruptee,
+) -> Result<Vec<(SyncGuid, IncomingPlan)>> {
     // for a first-cut, let's do this in the most naive way possible...
-    let mut plans: Vec<(SyncGuid, IncomingPlan)> = Vec::with_capacity(inbound.changes.len());
-    for incoming in inbound.changes {
+    let mut plans: Vec<(SyncGuid, IncomingPlan)> = Vec::with_capacity(changes.len());
+    for incoming in changes {
         interruptee.err_if_interrupted()?;
         let item = match HistorySyncRecord::from_payload(incoming.0) {
             Ok(item) => item,
@@ -199,55 +250,76 @@ pub fn apply_plan(
         let guid = item.guid.clone();
         plans.push((guid, plan));
     }
+    Ok(plans)
+}
+
+fn apply_incoming_record(
+    db: &PlacesDb,
+    guid: SyncGuid,
+    plan: IncomingPlan,
+    telem: &mut telemetry::EngineIncoming,
+) -> Result<()> {
+    match &plan {
+        IncomingPlan::Skip => {
+            log::trace!("incoming: skipping item {:?}", guid);
+            // XXX - should we `telem.reconciled(1);` here?
+        }
+        IncomingPlan::Invalid(err) => {
+            log::warn!(
+                "incoming: record {:?} skipped because it is invalid: {}",
+                guid,
+                err
+            );
+            telem.failed(1);
+        }
+        IncomingPlan::Failed(err) => {
+            log::error!("incoming: record {:?} failed to apply: {}", guid, err);
+            telem.failed(1);
+        }
+        IncomingPlan::Delete => {
+            log::trace!("incoming: deleting {:?}", guid);
+            apply_synced_deletion(&db, &guid)?;
+            telem.applied(1);
+        }
+        IncomingPlan::Apply {
+            url,
+            new_title,
+            visits,
+        } => {
+            log::trace!(
+                "incoming: will apply {:?}: url={:?}, title={:?}, to_add={:?}",
+                guid,
+                url,
+                new_title,
+                visits
+            );
+            apply_synced_visits(&db, &guid, &url, new_title, visits)?;
+            telem.applied(1);
+        }
+        IncomingPlan::Reconciled => {
+            telem.reconciled(1);
+            log::trace!("incoming: reconciled {:?}", guid);
+            apply_synced_reconciliation(&db, &guid)?;
+        }
+    };
+    Ok(())
+}
+
+/// Plans and applies incoming records, without building the outgoing
+/// changeset. Used directly when applying a paged download a page at a time.
+pub fn apply_incoming_changes(
+    db: &PlacesDb,
+    changes: Vec<(Payload, ServerTimestamp)>,
+    telem: &mut telemetry::EngineIncoming,
+    interruptee: &impl Interruptee,
+) -> Result<()> {
+    let plans = plan_incoming_changes(db, changes, telem, interruptee)?;
 
     let mut tx = db.begin_transaction()?;
 
-    let mut outgoing = OutgoingChangeset::new("history", inbound.timestamp);
     for (guid, plan) in plans {
         interruptee.err_if_interrupted()?;
-        match &plan {
-            IncomingPlan::Skip => {
-                log::trace!("incoming: skipping item {:?}", guid);
-                // XXX - should we `telem.reconciled(1);` here?
-            }
-            IncomingPlan::Invalid(err) => {
-                log::warn!(
-                    "incoming: record {:?} skipped because it is invalid: {}",
-                    guid,
-                    err
-                );
-                telem.failed(1);
-            }
-            IncomingPlan::Failed(err) => {
-                log::error!("incoming: record {:?} failed to apply: {}", guid, err);
-                telem.failed(1);
-            }
-            IncomingPlan::Delete => {
-                log::trace!("incoming: deleting {:?}", guid);
-                apply_synced_deletion(&db, &guid)?;
-                telem.applied(1);
-            }
-            IncomingPlan::Apply {
-                url,
-                new_title,
-                visits,
-            } => {
-                log::trace!(
-                    "incoming: will apply {:?}: url={:?}, title={:?}, to_add={:?}",
-                    guid,
-                    url,
-                    new_title,
-                    visits
-                );
-                apply_synced_visits(&db, &guid, &url, new_title, visits)?;
-                telem.applied(1);
-            }
-            IncomingPlan::Reconciled => {
-                telem.reconciled(1);
-                log::trace!("incoming: reconciled {:?}", guid);
-                apply_synced_reconciliation(&db, &guid)?;
-            }
-        };
+        apply_incoming_record(db, guid, plan, telem)?;
         if tx.should_commit() {
             // Trigger frecency and origin updates before committing the
             // transaction, so that our origins table is consistent even
@@ -260,11 +332,29 @@ pub fn apply_plan(
     // frecency and origin updates.
     delete_pending_temp_tables(db)?;
     tx.commit()?;
+    log::info!("incoming: {}", serde_json::to_string(&telem).unwrap());
+    Ok(())
+}
+
+fn fetch_outgoing_changeset(
+    db: &PlacesDb,
+    timestamp: ServerTimestamp,
+) -> Result<OutgoingChangeset> {
     // It might make sense for fetch_outgoing to manage its own
     // begin_transaction - even though doesn't seem a large bottleneck
     // at this time, the fact we hold a single transaction for the entire call
     // really is used only for performance, so it's certainly a candidate.
     let tx = db.begin_transaction()?;
+    let outgoing = build_outgoing_changeset(db, timestamp)?;
+    tx.commit()?;
+    Ok(outgoing)
+}
+
+fn build_outgoing_changeset(
+    db: &PlacesDb,
+    timestamp: ServerTimestamp,
+) -> Result<OutgoingChangeset> {
+    let mut outgoing = OutgoingChangeset::new("history", timestamp);
     let mut out_infos = fetch_outgoing(db, MAX_OUTGOING_PLACES, MAX_VISITS)?;
 
     for (guid, out_record) in out_infos.drain() {
@@ -277,9 +367,6 @@ pub fn apply_plan(
         log::trace!("outgoing {:?}", payload);
         outgoing.changes.push(payload);
     }
-    tx.commit()?;
-
-    log::info!("incoming: {}", serde_json::to_string(&telem).unwrap());
     Ok(outgoing)
 }
 
@@ -537,6 +624,53 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_dry_run_plan() -> Result<()> {
+        let _ = env_logger::try_init();
+        let db = PlacesDb::open_in_memory(ConnectionType::Sync)?;
+        let guid1 = SyncGuid::random();
+        let ts1: Timestamp = (SystemTime::now() - Duration::new(5, 0)).into();
+
+        let guid2 = SyncGuid::random();
+        let ts2: Timestamp = SystemTime::now().into();
+        let url = Url::parse("https://example.com")?;
+
+        // The same records as `test_apply_dupe_no_local_visits`.
+        let mut incoming = IncomingChangeset::new("history", ServerTimestamp(0i64));
+        for (guid, ts) in &[(&guid1, ts1), (&guid2, ts2)] {
+            let payload = Payload::from_json(json!({
+                "id": guid,
+                "title": "title",
+                "histUri": url.as_str(),
+                "sortindex": 0,
+                "ttl": 100,
+                "visits": [ {"date": ServerVisitTimestamp::from(*ts), "type": 1}]
+            }))?;
+            incoming.changes.push((payload, ServerTimestamp(0i64)));
+        }
+
+        let dry_run = dry_run_plan(
+            &db,
+            incoming,
+            &mut telemetry::EngineIncoming::new(),
+            &NeverInterrupts,
+        )?;
+        assert_eq!(
+            dry_run.incoming,
+            vec![
+                RecordPlan::new(guid1.clone(), PlannedAction::Apply),
+                RecordPlan::new(guid2, PlannedAction::Apply),
+            ]
+        );
+        assert_eq!(dry_run.outgoing.changes.len(), 1);
+        assert_eq!(dry_run.outgoing.changes[0].id, guid1);
+
+        // ...But nothing should have been written.
+        assert!(fetch_visits(&db, &url, 3)?.is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_apply_dupe_local_unsynced_visits() -> Result<()> {
         // There's a chance the server ends up with different records but
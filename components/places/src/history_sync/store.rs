@@ -12,13 +12,13 @@ use sql_support::SqlInterruptScope;
 use std::ops::Deref;
 use sync15::telemetry;
 use sync15::{
-    extract_v1_state, CollSyncIds, CollectionRequest, IncomingChangeset, OutgoingChangeset,
-    ServerTimestamp, Store, StoreSyncAssociation,
+    extract_v1_state, CollSyncIds, CollectionRequest, DryRun, IncomingChangeset, OutgoingChangeset,
+    RequestOrder, ServerTimestamp, Store, StoreSyncAssociation,
 };
 use sync_guid::Guid;
 
-use super::plan::{apply_plan, finish_plan};
-use super::MAX_INCOMING_PLACES;
+use super::plan::{apply_incoming_changes, apply_plan, dry_run_plan, finish_plan};
+use super::INCOMING_PAGE_SIZE;
 
 pub const LAST_SYNC_META_KEY: &str = "history_last_sync_time";
 // Note that all engines in this crate should use a *different* meta key
@@ -65,6 +65,42 @@ impl<'a> HistoryStore<'a> {
         Ok(outgoing)
     }
 
+    fn do_apply_incoming_dry_run(
+        &self,
+        inbound: IncomingChangeset,
+        telem: &mut telemetry::Engine,
+    ) -> Result<DryRun> {
+        let mut incoming_telemetry = telemetry::EngineIncoming::new();
+        let result = dry_run_plan(&self.db, inbound, &mut incoming_telemetry, self.interruptee);
+        telem.incoming(incoming_telemetry);
+        result
+    }
+
+    fn do_stage_incoming(
+        &self,
+        page: &mut IncomingChangeset,
+        high_water_mark: ServerTimestamp,
+        telem: &mut telemetry::Engine,
+    ) -> Result<()> {
+        let mut incoming_telemetry = telemetry::EngineIncoming::new();
+        let result = apply_incoming_changes(
+            &self.db,
+            std::mem::take(&mut page.changes),
+            &mut incoming_telemetry,
+            self.interruptee,
+        );
+        telem.incoming(incoming_telemetry);
+        result?;
+        // Records are downloaded oldest first, so if we're interrupted, the
+        // next sync can pick up from here. Several records can share the same
+        // modified time, so back off by a millisecond to make sure we don't
+        // skip any that didn't fit in this page. Applying the ones we've
+        // already seen again is harmless.
+        let resume_from = (high_water_mark.as_millis() - 1).max(0);
+        self.put_meta(LAST_SYNC_META_KEY, &resume_from)?;
+        Ok(())
+    }
+
     fn do_sync_finished(
         &self,
         new_timestamp: ServerTimestamp,
@@ -137,6 +173,28 @@ impl<'a> Store for HistoryStore<'a> {
         Ok(self.do_apply_incoming(inbound, telem)?)
     }
 
+    fn apply_incoming_dry_run(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<DryRun> {
+        assert_eq!(inbound.len(), 1, "history only requests one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        Ok(self.do_apply_incoming_dry_run(inbound, telem)?)
+    }
+
+    fn stage_incoming(
+        &self,
+        page: &mut IncomingChangeset,
+        high_water_mark: ServerTimestamp,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<bool> {
+        // History records don't depend on each other, so we apply each page
+        // as it arrives instead of holding the whole collection in memory.
+        self.do_stage_incoming(page, high_water_mark, telem)?;
+        Ok(true)
+    }
+
     fn sync_finished(
         &self,
         new_timestamp: ServerTimestamp,
@@ -160,7 +218,8 @@ impl<'a> Store for HistoryStore<'a> {
             vec![CollectionRequest::new("history")
                 .full()
                 .newer_than(since)
-                .limit(MAX_INCOMING_PLACES)]
+                .sort_by(RequestOrder::Oldest)
+                .paged(INCOMING_PAGE_SIZE)]
         })
     }
 
@@ -184,3 +243,53 @@ impl<'a> Store for HistoryStore<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_sync::ServerVisitTimestamp;
+    use crate::types::Timestamp;
+    use serde_json::json;
+    use std::time::SystemTime;
+    use sync15::Payload;
+
+    #[test]
+    fn test_stage_incoming_resumes() -> Result<()> {
+        let _ = env_logger::try_init();
+        let db = PlacesDb::open_in_memory(ConnectionType::Sync)?;
+        let interruptee = db.begin_interrupt_scope();
+        let store = HistoryStore::new(&db, &interruptee);
+
+        let now: Timestamp = SystemTime::now().into();
+        let mut page = IncomingChangeset::new("history", ServerTimestamp(5_000));
+        let payload = Payload::from_json(json!({
+            "id": "aaaaaaaaaaaa",
+            "title": "title",
+            "histUri": "http://example.com",
+            "visits": [ {"date": ServerVisitTimestamp::from(now), "type": 1}]
+        }))
+        .unwrap();
+        page.changes.push((payload, ServerTimestamp(2_000)));
+
+        let mut telem = telemetry::Engine::new("history");
+        assert!(store
+            .stage_incoming(&mut page, ServerTimestamp(2_000), &mut telem)
+            .expect("should stage"));
+
+        // The page should have been applied...
+        assert!(crate::storage::history::url_to_guid(
+            &db,
+            &url::Url::parse("http://example.com").unwrap()
+        )?
+        .is_some());
+
+        // ...and an interrupted sync should resume from the last page.
+        let requests = store
+            .get_collection_requests(ServerTimestamp(5_000))
+            .expect("should get requests");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].newer, Some(ServerTimestamp(1_999)));
+        assert!(requests[0].paged);
+        Ok(())
+    }
+}
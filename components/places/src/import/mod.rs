@@ -9,3 +9,5 @@ pub use fennec::import_history as import_fennec_history;
 pub use fennec::import_pinned_sites as import_fennec_pinned_sites;
 pub mod ios_bookmarks;
 pub use ios_bookmarks::import_ios_bookmarks;
+pub mod netscape_html;
+pub use netscape_html::{export_netscape_html, import_netscape_html};
@@ -0,0 +1,868 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Import and export of bookmarks in the Netscape bookmark file format, better
+//! known as `bookmarks.html`, which is what every desktop browser can import
+//! and export.
+//!
+//! The format is a loose, never-closed subset of HTML. Folders are `<H3>`
+//! headings, each followed by a `<DL>` list of their children; bookmarks are
+//! `<A>` links, and separators are `<HR>`s. Everything else (`<DT>`, `<p>`,
+//! `<DD>` descriptions, and so on) is ignored. We recognize the attributes
+//! Firefox desktop writes:
+//!
+//! - `ADD_DATE` and `LAST_MODIFIED`, in seconds since the epoch.
+//! - `TAGS`, a comma-separated list of tags, and `SHORTCUTURL`, the keyword.
+//! - `PERSONAL_TOOLBAR_FOLDER` and `UNFILED_BOOKMARKS_FOLDER`, which mark the
+//!   folders for the toolbar and "Other Bookmarks" roots. Chrome also marks
+//!   its bookmarks bar with `PERSONAL_TOOLBAR_FOLDER`. Everything not in a
+//!   marked folder belongs to the menu.
+//!
+//! Desktop doesn't export mobile bookmarks, so we mark the mobile root with our
+//! own `MOBILE_BOOKMARKS_FOLDER` attribute. Other browsers ignore it, and
+//! import the mobile bookmarks as a regular folder.
+//!
+//! Importing doesn't replace any existing bookmarks: the imported items are
+//! appended to the roots they belong to. Queries (`place:` URLs), and
+//! bookmarks with URLs we can't parse, are skipped.
+
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::bookmarks::{
+    fetch_tree, insert_tree_in_tx, BookmarkNode, BookmarkRootGuid, BookmarkTreeNode, FetchDepth,
+    FolderNode, SeparatorNode,
+};
+use crate::storage::tags::{get_tags_for_url, tag_url_in_tx, validate_tag};
+use crate::storage::URL_LENGTH_MAX;
+use crate::types::Timestamp;
+use sql_support::ConnExt;
+use std::fmt::Write;
+use std::iter::Peekable;
+use url::Url;
+
+const TOOLBAR_ATTR: &str = "PERSONAL_TOOLBAR_FOLDER";
+const UNFILED_ATTR: &str = "UNFILED_BOOKMARKS_FOLDER";
+const MOBILE_ATTR: &str = "MOBILE_BOOKMARKS_FOLDER";
+
+/// How deeply `<DL>` lists can nest. Parsing, converting and inserting the
+/// tree all recurse once per level, so we refuse files that nest deeper than
+/// any real bookmark tree instead of overflowing the stack.
+const MAX_LIST_DEPTH: usize = 100;
+
+/// Imports the bookmarks in `html`, a Netscape bookmark file, appending them
+/// to the existing bookmarks. The import happens in a single transaction, so
+/// if it fails or is interrupted, none of the bookmarks are imported.
+pub fn import_netscape_html(db: &PlacesDb, html: &str) -> Result<()> {
+    let items = parse_list(&mut Tokenizer::new(html).peekable(), 0)?;
+    let mut importer = Importer::default();
+    let menu = importer.convert(items);
+    let roots = vec![
+        (BookmarkRootGuid::Menu, menu),
+        (BookmarkRootGuid::Toolbar, importer.toolbar),
+        (BookmarkRootGuid::Unfiled, importer.unfiled),
+        (BookmarkRootGuid::Mobile, importer.mobile),
+    ];
+    let scope = db.begin_interrupt_scope();
+    let tx = db.begin_transaction()?;
+    for (root, children) in roots {
+        if children.is_empty() {
+            continue;
+        }
+        log::debug!("Importing {} items into {}", children.len(), root.as_str());
+        insert_tree_in_tx(
+            db,
+            &FolderNode {
+                guid: Some(root.as_guid()),
+                children,
+                ..Default::default()
+            },
+        )?;
+        scope.err_if_interrupted()?;
+    }
+
+    // Tags and keywords belong to URLs, not bookmarks, so we can only add
+    // them once the bookmarks exist.
+    for extra in importer.extras {
+        scope.err_if_interrupted()?;
+        for tag in extra.tags {
+            let tag = match validate_tag(&tag).ensure_valid() {
+                Ok(tag) => tag,
+                Err(e) => {
+                    log::warn!("Skipping invalid tag for imported bookmark: {}", e);
+                    continue;
+                }
+            };
+            tag_url_in_tx(db, &extra.url, tag)?;
+        }
+        if let Some(keyword) = extra.keyword {
+            // A URL can only have one keyword, and a keyword can only be
+            // used for one URL, so the imported keyword replaces both.
+            db.execute_named_cached(
+                "DELETE FROM moz_keywords
+                 WHERE keyword = :keyword OR
+                       place_id = (SELECT id FROM moz_places
+                                   WHERE url_hash = hash(:url) AND url = :url)",
+                &[(":keyword", &keyword), (":url", &extra.url.as_str())],
+            )?;
+            db.execute_named_cached(
+                "INSERT INTO moz_keywords(keyword, place_id)
+                 SELECT :keyword, id FROM moz_places
+                 WHERE url_hash = hash(:url) AND url = :url",
+                &[(":keyword", &keyword), (":url", &extra.url.as_str())],
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Exports all bookmarks as a Netscape bookmark file, in the same layout as
+/// Firefox desktop.
+pub fn export_netscape_html(db: &PlacesDb) -> Result<String> {
+    let root = match fetch_tree(db, &BookmarkRootGuid::Root.into(), &FetchDepth::Deepest)? {
+        Some((BookmarkTreeNode::Folder(root), _, _)) => root,
+        _ => {
+            return Err(InvalidPlaceInfo::NoSuchGuid(BookmarkRootGuid::Root.as_str().into()).into())
+        }
+    };
+    let mut exporter = Exporter {
+        db,
+        out: String::new(),
+    };
+    exporter.out.push_str(HEADER);
+    exporter.out.push_str("<DL><p>\n");
+    // The menu's children go at the top level; the other roots are written
+    // as marked folders after them.
+    for root_guid in &[
+        BookmarkRootGuid::Menu,
+        BookmarkRootGuid::Toolbar,
+        BookmarkRootGuid::Unfiled,
+        BookmarkRootGuid::Mobile,
+    ] {
+        let folder = root.children.iter().find_map(|child| match child {
+            BookmarkTreeNode::Folder(f) if f.guid.as_ref() == Some(root_guid.guid()) => Some(f),
+            _ => None,
+        });
+        let folder = match folder {
+            Some(folder) => folder,
+            None => continue,
+        };
+        match root_guid {
+            BookmarkRootGuid::Menu => exporter.write_children(folder, 1)?,
+            BookmarkRootGuid::Toolbar => {
+                exporter.write_folder(folder, "Bookmarks Toolbar", Some(TOOLBAR_ATTR), 1)?
+            }
+            BookmarkRootGuid::Unfiled => {
+                exporter.write_folder(folder, "Other Bookmarks", Some(UNFILED_ATTR), 1)?
+            }
+            BookmarkRootGuid::Mobile => {
+                exporter.write_folder(folder, "Mobile Bookmarks", Some(MOBILE_ATTR), 1)?
+            }
+            BookmarkRootGuid::Root => unreachable!(),
+        }
+    }
+    exporter.out.push_str("</DL>\n");
+    Ok(exporter.out)
+}
+
+const HEADER: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<meta http-equiv="Content-Security-Policy"
+      content="default-src 'self'; script-src 'none'; img-src data: *; object-src 'none'"></meta>
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks Menu</H1>
+
+"#;
+
+struct Exporter<'a> {
+    db: &'a PlacesDb,
+    out: String,
+}
+
+impl<'a> Exporter<'a> {
+    fn write_children(&mut self, folder: &FolderNode, depth: usize) -> Result<()> {
+        for child in &folder.children {
+            match child {
+                BookmarkTreeNode::Bookmark(b) => self.write_bookmark(b, depth)?,
+                BookmarkTreeNode::Separator(_) => {
+                    self.indent(depth);
+                    self.out.push_str("<HR>\n");
+                }
+                BookmarkTreeNode::Folder(f) => {
+                    let title = f.title.as_deref().unwrap_or_default();
+                    self.write_folder(f, title, None, depth)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_folder(
+        &mut self,
+        folder: &FolderNode,
+        title: &str,
+        root_attr: Option<&str>,
+        depth: usize,
+    ) -> Result<()> {
+        self.indent(depth);
+        self.out.push_str("<DT><H3");
+        self.write_dates(folder.date_added, folder.last_modified);
+        if let Some(attr) = root_attr {
+            write!(self.out, r#" {}="true""#, attr).unwrap();
+        }
+        writeln!(self.out, ">{}</H3>", escape(title)).unwrap();
+        self.indent(depth);
+        self.out.push_str("<DL><p>\n");
+        self.write_children(folder, depth + 1)?;
+        self.indent(depth);
+        self.out.push_str("</DL><p>\n");
+        Ok(())
+    }
+
+    fn write_bookmark(&mut self, bookmark: &BookmarkNode, depth: usize) -> Result<()> {
+        self.indent(depth);
+        write!(
+            self.out,
+            r#"<DT><A HREF="{}""#,
+            escape(bookmark.url.as_str())
+        )
+        .unwrap();
+        self.write_dates(bookmark.date_added, bookmark.last_modified);
+        if let Some(keyword) = self.keyword_for_url(&bookmark.url)? {
+            write!(self.out, r#" SHORTCUTURL="{}""#, escape(&keyword)).unwrap();
+        }
+        let tags = get_tags_for_url(self.db, &bookmark.url)?;
+        if !tags.is_empty() {
+            write!(self.out, r#" TAGS="{}""#, escape(&tags.join(","))).unwrap();
+        }
+        let title = bookmark.title.as_deref().unwrap_or_default();
+        writeln!(self.out, ">{}</A>", escape(title)).unwrap();
+        Ok(())
+    }
+
+    fn write_dates(&mut self, date_added: Option<Timestamp>, last_modified: Option<Timestamp>) {
+        if let Some(date_added) = date_added {
+            write!(self.out, r#" ADD_DATE="{}""#, date_added.as_millis() / 1000).unwrap();
+        }
+        if let Some(last_modified) = last_modified {
+            write!(
+                self.out,
+                r#" LAST_MODIFIED="{}""#,
+                last_modified.as_millis() / 1000
+            )
+            .unwrap();
+        }
+    }
+
+    fn keyword_for_url(&self, url: &Url) -> Result<Option<String>> {
+        Ok(self.db.try_query_row(
+            "SELECT k.keyword FROM moz_keywords k
+             JOIN moz_places h ON h.id = k.place_id
+             WHERE h.url_hash = hash(:url) AND h.url = :url",
+            &[(":url", &url.as_str())],
+            |row| row.get::<_, String>(0),
+            true,
+        )?)
+    }
+
+    fn indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str("    ");
+        }
+    }
+}
+
+/// Escapes `s` for use in both text and attribute values.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Decodes the character references in `s`. Unknown or malformed references
+/// are left as they are.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                name if name.starts_with("#x") || name.starts_with("#X") => {
+                    u32::from_str_radix(&name[2..], 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                }
+                name if name.starts_with('#') => {
+                    name[1..].parse::<u32>().ok().and_then(std::char::from_u32)
+                }
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A start tag, with its name and attribute names in upper case.
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    End(String),
+    Text(String),
+}
+
+fn attr(attrs: &[(String, String)], name: &str) -> Option<String> {
+    attrs
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value.clone())
+}
+
+/// Splits a document into tags and text, skipping comments and declarations.
+struct Tokenizer<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Self { rest: html }
+    }
+
+    fn skip_past(&mut self, pat: &str) {
+        self.rest = match self.rest.find(pat) {
+            Some(i) => &self.rest[i + pat.len()..],
+            None => "",
+        };
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let end = self.rest.find(|c| !f(c)).unwrap_or_else(|| self.rest.len());
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        taken
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn tag(&mut self) -> Token {
+        // Skip the `<`.
+        self.rest = &self.rest[1..];
+        let is_end = self.rest.starts_with('/');
+        if is_end {
+            self.rest = &self.rest[1..];
+        }
+        let name = self
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .to_uppercase();
+        let mut attrs = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest.is_empty() {
+                break;
+            }
+            if self.rest.starts_with('>') {
+                self.rest = &self.rest[1..];
+                break;
+            }
+            let attr_name = self
+                .take_while(|c| !c.is_whitespace() && c != '=' && c != '>' && c != '/')
+                .to_uppercase();
+            if attr_name.is_empty() {
+                // A stray `=`, or the `/` in a self-closing `/>`.
+                self.rest = &self.rest[1..];
+                continue;
+            }
+            self.skip_whitespace();
+            let value = if self.rest.starts_with('=') {
+                self.rest = &self.rest[1..];
+                self.skip_whitespace();
+                match self.rest.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => {
+                        self.rest = &self.rest[1..];
+                        let value = self.take_while(|c| c != quote);
+                        if !self.rest.is_empty() {
+                            self.rest = &self.rest[1..];
+                        }
+                        value
+                    }
+                    _ => self.take_while(|c| !c.is_whitespace() && c != '>'),
+                }
+            } else {
+                ""
+            };
+            attrs.push((attr_name, unescape(value)));
+        }
+        if is_end {
+            Token::End(name)
+        } else {
+            Token::Start { name, attrs }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            if self.rest.starts_with("<!--") {
+                self.skip_past("-->");
+            } else if self.rest.starts_with("<!") || self.rest.starts_with("<?") {
+                self.skip_past(">");
+            } else if self.rest.starts_with('<') {
+                return Some(self.tag());
+            } else {
+                let text = self.take_while(|c| c != '<');
+                return Some(Token::Text(unescape(text)));
+            }
+        }
+    }
+}
+
+/// A parsed item, before it's converted into a `BookmarkTreeNode`.
+#[derive(Debug)]
+enum Item {
+    Bookmark {
+        attrs: Vec<(String, String)>,
+        title: String,
+    },
+    Separator,
+    Folder {
+        attrs: Vec<(String, String)>,
+        title: String,
+        children: Vec<Item>,
+    },
+}
+
+/// Parses items until the end of the current `<DL>` list, or the end of the
+/// document. `depth` is the number of lists we're already in.
+fn parse_list(
+    tokens: &mut Peekable<impl Iterator<Item = Token>>,
+    depth: usize,
+) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    // A folder is followed by the list of its children, but that list might
+    // be missing if the folder is empty.
+    let mut pending_folder: Option<Item> = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Start { name, attrs } => match name.as_str() {
+                "H3" => {
+                    items.extend(pending_folder.take());
+                    pending_folder = Some(Item::Folder {
+                        attrs,
+                        title: parse_text(tokens, "H3"),
+                        children: Vec::new(),
+                    });
+                }
+                "DL" => {
+                    if depth >= MAX_LIST_DEPTH {
+                        return Err(ErrorKind::ImportError(format!(
+                            "Folders are nested more than {} deep",
+                            MAX_LIST_DEPTH
+                        ))
+                        .into());
+                    }
+                    let list = parse_list(tokens, depth + 1)?;
+                    match &mut pending_folder {
+                        Some(Item::Folder { children, .. }) => {
+                            *children = list;
+                            items.extend(pending_folder.take());
+                        }
+                        // A list without a heading, like the top-level list.
+                        _ => items.extend(list),
+                    }
+                }
+                "A" => {
+                    items.extend(pending_folder.take());
+                    items.push(Item::Bookmark {
+                        attrs,
+                        title: parse_text(tokens, "A"),
+                    });
+                }
+                "HR" => {
+                    items.extend(pending_folder.take());
+                    items.push(Item::Separator);
+                }
+                _ => {}
+            },
+            Token::End(name) if name == "DL" => break,
+            _ => {}
+        }
+    }
+    items.extend(pending_folder.take());
+    Ok(items)
+}
+
+/// Collects the text up to the end tag `end`, ignoring any other tags.
+fn parse_text(tokens: &mut Peekable<impl Iterator<Item = Token>>, end: &str) -> String {
+    let mut text = String::new();
+    while let Some(token) = tokens.peek() {
+        match token {
+            Token::Text(t) => text.push_str(t),
+            Token::End(name) if name == end => {
+                tokens.next();
+                break;
+            }
+            // Don't swallow the next item if the end tag is missing.
+            Token::Start { name, .. } if ["A", "H3", "DL", "HR", "DT"].contains(&name.as_str()) => {
+                break
+            }
+            _ => {}
+        }
+        tokens.next();
+    }
+    text.trim().to_owned()
+}
+
+/// Tags and keywords for an imported bookmark's URL.
+struct UrlExtras {
+    url: Url,
+    tags: Vec<String>,
+    keyword: Option<String>,
+}
+
+#[derive(Default)]
+struct Importer {
+    toolbar: Vec<BookmarkTreeNode>,
+    unfiled: Vec<BookmarkTreeNode>,
+    mobile: Vec<BookmarkTreeNode>,
+    extras: Vec<UrlExtras>,
+}
+
+impl Importer {
+    fn convert(&mut self, items: Vec<Item>) -> Vec<BookmarkTreeNode> {
+        let mut nodes = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Item::Bookmark { attrs, title } => {
+                    if let Some(node) = self.convert_bookmark(attrs, title) {
+                        nodes.push(node.into());
+                    }
+                }
+                Item::Separator => nodes.push(SeparatorNode::default().into()),
+                Item::Folder {
+                    attrs,
+                    title,
+                    children,
+                } => {
+                    let children = self.convert(children);
+                    // The children of a root's folder go in the root itself,
+                    // wherever the folder is.
+                    let root = if attr(&attrs, TOOLBAR_ATTR).is_some() {
+                        &mut self.toolbar
+                    } else if attr(&attrs, UNFILED_ATTR).is_some() {
+                        &mut self.unfiled
+                    } else if attr(&attrs, MOBILE_ATTR).is_some() {
+                        &mut self.mobile
+                    } else {
+                        nodes.push(
+                            FolderNode {
+                                guid: None,
+                                date_added: parse_date(&attrs, "ADD_DATE"),
+                                last_modified: parse_date(&attrs, "LAST_MODIFIED"),
+                                title: non_empty(title),
+                                children,
+                            }
+                            .into(),
+                        );
+                        continue;
+                    };
+                    root.extend(children);
+                }
+            }
+        }
+        nodes
+    }
+
+    fn convert_bookmark(
+        &mut self,
+        attrs: Vec<(String, String)>,
+        title: String,
+    ) -> Option<BookmarkNode> {
+        let href = attr(&attrs, "HREF")?;
+        let url = match Url::parse(&href) {
+            Ok(url) => url,
+            Err(e) => {
+                log::warn!("Skipping bookmark with invalid URL: {}", e);
+                return None;
+            }
+        };
+        if url.scheme() == "place" {
+            log::debug!("Skipping query");
+            return None;
+        }
+        if url.as_str().len() > URL_LENGTH_MAX {
+            log::warn!("Skipping bookmark with overlong URL");
+            return None;
+        }
+        let tags = attr(&attrs, "TAGS")
+            .map(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let keyword = attr(&attrs, "SHORTCUTURL").and_then(non_empty);
+        if !tags.is_empty() || keyword.is_some() {
+            self.extras.push(UrlExtras {
+                url: url.clone(),
+                tags,
+                keyword,
+            });
+        }
+        Some(BookmarkNode {
+            guid: None,
+            date_added: parse_date(&attrs, "ADD_DATE"),
+            last_modified: parse_date(&attrs, "LAST_MODIFIED"),
+            title: non_empty(title),
+            url,
+        })
+    }
+}
+
+fn parse_date(attrs: &[(String, String)], name: &str) -> Option<Timestamp> {
+    let secs = attr(attrs, name)?.trim().parse::<u64>().ok()?;
+    secs.checked_mul(1000).map(Timestamp)
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::bookmarks::insert_tree;
+
+    #[test]
+    fn test_tokenizer() {
+        let tokens = Tokenizer::new(
+            r#"<!DOCTYPE x><!-- a <comment> --><DT><a href="https://example.com/?a=1&amp;b=2" FOLDED
+                add_date=123>Fish &amp; chips &#x1F41F;</A>"#,
+        )
+        .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Start {
+                    name: "DT".into(),
+                    attrs: vec![],
+                },
+                Token::Start {
+                    name: "A".into(),
+                    attrs: vec![
+                        ("HREF".into(), "https://example.com/?a=1&b=2".into()),
+                        ("FOLDED".into(), "".into()),
+                        ("ADD_DATE".into(), "123".into()),
+                    ],
+                },
+                Token::Text("Fish & chips \u{1F41F}".into()),
+                Token::End("A".into()),
+            ]
+        );
+
+        let tokens = Tokenizer::new(r#"<HR/><IMG SRC="a.png" />"#).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Start {
+                    name: "HR".into(),
+                    attrs: vec![],
+                },
+                Token::Start {
+                    name: "IMG".into(),
+                    attrs: vec![("SRC".into(), "a.png".into())],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a &lt;b&gt; &quot;c&#39;"), "a <b> \"c'");
+        assert_eq!(unescape("&unknown; & &#xZZ;"), "&unknown; & &#xZZ;");
+        assert_eq!(unescape(&escape("<\"'&>")), "<\"'&>");
+    }
+
+    #[test]
+    fn test_empty_folder_and_missing_end_tags() -> Result<()> {
+        let conn = new_mem_connection();
+        import_netscape_html(
+            &conn,
+            r#"<DL><p>
+                <DT><H3>Empty</H3>
+                <DT><A HREF="https://example.com/1">One
+                <DT><H3>Full</H3>
+                <DL><p>
+                    <DT><A HREF="https://example.com/2">Two</A>
+                    <DD>A description that isn't imported
+                </DL><p>
+                <DT><A HREF="place:sort=8">Most Visited</A>
+                <DT><A HREF="not a url">Invalid</A>
+            </DL>"#,
+        )?;
+        let (menu, _, _) =
+            fetch_tree(&conn, &BookmarkRootGuid::Menu.into(), &FetchDepth::Deepest)?.unwrap();
+        let menu = match menu {
+            BookmarkTreeNode::Folder(f) => f,
+            _ => panic!("should be a folder"),
+        };
+        let titles = menu
+            .children
+            .iter()
+            .map(|child| match child {
+                BookmarkTreeNode::Folder(f) => {
+                    format!("{} ({})", f.title.as_deref().unwrap(), f.children.len())
+                }
+                BookmarkTreeNode::Bookmark(b) => b.title.clone().unwrap(),
+                BookmarkTreeNode::Separator(_) => "-".into(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Empty (0)", "One", "Full (1)"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deeply_nested_folders() -> Result<()> {
+        fn nested(depth: usize) -> String {
+            "<DL><p><DT><H3>Folder</H3>".repeat(depth) + &"</DL><p>".repeat(depth)
+        }
+        fn menu_depth(conn: &PlacesDb) -> Result<usize> {
+            let (mut node, _, _) =
+                fetch_tree(conn, &BookmarkRootGuid::Menu.into(), &FetchDepth::Deepest)?.unwrap();
+            let mut depth = 0;
+            while let BookmarkTreeNode::Folder(f) = node {
+                match f.children.into_iter().next() {
+                    Some(child) => node = child,
+                    None => break,
+                }
+                depth += 1;
+            }
+            Ok(depth)
+        }
+
+        let conn = new_mem_connection();
+        import_netscape_html(&conn, &nested(MAX_LIST_DEPTH))?;
+        assert_eq!(menu_depth(&conn)?, MAX_LIST_DEPTH);
+
+        let conn = new_mem_connection();
+        for depth in &[MAX_LIST_DEPTH + 1, 100_000] {
+            match import_netscape_html(&conn, &nested(*depth))
+                .unwrap_err()
+                .kind()
+            {
+                ErrorKind::ImportError(_) => {}
+                err => panic!("Unexpected error {:?}", err),
+            }
+        }
+        assert_eq!(menu_depth(&conn)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_import_rolls_back() -> Result<()> {
+        let conn = new_mem_connection();
+        // Make adding keywords fail, after the bookmarks and tags are added.
+        conn.execute_batch(
+            "CREATE TEMP TRIGGER fail_keywords BEFORE INSERT ON moz_keywords
+             BEGIN
+                 SELECT RAISE(ABORT, 'no keywords');
+             END",
+        )?;
+        let result = import_netscape_html(
+            &conn,
+            r#"<DL><p>
+                <DT><H3>Folder</H3>
+                <DL><p>
+                    <DT><A HREF="https://example.com/1" TAGS="a,b">One</A>
+                    <DT><A HREF="https://example.com/2" SHORTCUTURL="two">Two</A>
+                </DL><p>
+            </DL>"#,
+        );
+        assert!(result.is_err());
+
+        let (menu, _, _) =
+            fetch_tree(&conn, &BookmarkRootGuid::Menu.into(), &FetchDepth::Deepest)?.unwrap();
+        match menu {
+            BookmarkTreeNode::Folder(f) => assert!(f.children.is_empty()),
+            _ => panic!("should be a folder"),
+        }
+        let url = Url::parse("https://example.com/1")?;
+        assert!(get_tags_for_url(&conn, &url)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_escapes() -> Result<()> {
+        let conn = new_mem_connection();
+        insert_tree(
+            &conn,
+            &FolderNode {
+                guid: Some(BookmarkRootGuid::Toolbar.into()),
+                children: vec![BookmarkNode {
+                    guid: None,
+                    date_added: Some(Timestamp(1_500_000_000_000)),
+                    last_modified: Some(Timestamp(1_500_000_001_000)),
+                    title: Some("<script>\"&\"</script>".into()),
+                    url: Url::parse("https://example.com/?a=1&b=\"2\"")?,
+                }
+                .into()],
+                ..Default::default()
+            },
+        )?;
+        let html = export_netscape_html(&conn)?;
+        assert!(html.contains(r#"PERSONAL_TOOLBAR_FOLDER="true">Bookmarks Toolbar</H3>"#));
+        assert!(html.contains(
+            r#"<DT><A HREF="https://example.com/?a=1&amp;b=%222%22" ADD_DATE="1500000000" LAST_MODIFIED="1500000001">&lt;script&gt;&quot;&amp;&quot;&lt;/script&gt;</A>"#
+        ));
+        Ok(())
+    }
+}
@@ -154,6 +154,14 @@ pub struct BookmarkNode {
     /// Leaving this out is equivalent to false.
     #[prost(bool, optional, tag="11")]
     pub have_child_nodes: ::std::option::Option<bool>,
+    ///*
+    /// The URL of the bookmarked page's icon. Only present for type =
+    /// `BookmarkType::Bookmark`.
+    ///
+    /// - Returned on reads if we have an icon for the page.
+    /// - Ignored for insertion and update.
+    #[prost(string, optional, tag="12")]
+    pub icon_url: ::std::option::Option<std::string::String>,
 }
 ///* An array of bookmark nodes, since we can't represent that directly 
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -171,6 +179,8 @@ pub struct SearchResultMessage {
     pub frecency: i64,
     #[prost(enumeration="SearchResultReason", repeated, tag="4")]
     pub reasons: ::std::vec::Vec<i32>,
+    #[prost(string, optional, tag="5")]
+    pub icon_url: ::std::option::Option<std::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SearchResultList {
@@ -189,6 +199,25 @@ pub struct TopFrecentSiteInfos {
     #[prost(message, repeated, tag="1")]
     pub infos: ::std::vec::Vec<TopFrecentSiteInfo>,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Icon {
+    #[prost(string, required, tag="1")]
+    pub url: std::string::String,
+    /// 0 if the size isn't known.
+    #[prost(uint32, required, tag="2")]
+    pub width: u32,
+    #[prost(string, optional, tag="3")]
+    pub mime_type: ::std::option::Option<std::string::String>,
+    #[prost(bytes, required, tag="4")]
+    pub data: std::vec::Vec<u8>,
+    #[prost(int64, required, tag="5")]
+    pub expires: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IconList {
+    #[prost(message, repeated, tag="1")]
+    pub icons: ::std::vec::Vec<Icon>,
+}
 /// Protobuf allows nesting these, but prost behaves weirdly if we do.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
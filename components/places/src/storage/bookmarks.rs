@@ -2,9 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use super::icons::icon_url_sql;
 use super::RowId;
 use super::{delete_meta, put_meta};
 use super::{fetch_page_info, new_page_info};
+use crate::bookmark_sync::repair::{LAST_REPAIR_META_KEY, REQUESTOR_META_KEY, RESPONDER_META_KEY};
 use crate::bookmark_sync::store::{
     COLLECTION_SYNCID_META_KEY, GLOBAL_SYNCID_META_KEY, LAST_SYNC_META_KEY,
 };
@@ -1092,6 +1094,13 @@ pub fn delete_everything(db: &PlacesDb) -> Result<()> {
 }
 
 pub fn insert_tree(db: &PlacesDb, tree: &FolderNode) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    insert_tree_in_tx(db, tree)?;
+    tx.commit()?;
+    Ok(())
+}
+
+pub(crate) fn insert_tree_in_tx(db: &PlacesDb, tree: &FolderNode) -> Result<()> {
     let parent_guid = match &tree.guid {
         Some(guid) => guid,
         None => return Err(InvalidPlaceInfo::InvalidParent("<no guid>".into()).into()),
@@ -1100,13 +1109,11 @@ pub fn insert_tree(db: &PlacesDb, tree: &FolderNode) -> Result<()> {
     let mut insert_infos: Vec<InsertableItem> = Vec::new();
     add_subtree_infos(&parent_guid, tree, &mut insert_infos);
     log::info!("insert_tree inserting {} records", insert_infos.len());
-    let tx = db.begin_transaction()?;
 
     for insertable in insert_infos {
         insert_bookmark_in_tx(db, &insertable)?;
     }
     super::delete_pending_temp_tables(db)?;
-    tx.commit()?;
     Ok(())
 }
 
@@ -1125,6 +1132,7 @@ struct FetchedTreeRow {
     date_added: Timestamp,
     last_modified: Timestamp,
     url: Option<String>,
+    icon_url: Option<String>,
 }
 
 impl FetchedTreeRow {
@@ -1146,6 +1154,7 @@ impl FetchedTreeRow {
             date_added: row.get("dateAdded")?,
             last_modified: row.get("lastModified")?,
             url,
+            icon_url: row.get("iconUrl")?,
         })
     }
 }
@@ -1175,10 +1184,40 @@ pub fn fetch_tree(
     db: &PlacesDb,
     item_guid: &SyncGuid,
     target_depth: &FetchDepth,
+) -> Result<Option<(BookmarkTreeNode, Option<SyncGuid>, u32)>> {
+    fetch_tree_impl(db, item_guid, target_depth, None)
+}
+
+/// Like `fetch_tree`, but also returns the URLs of the bookmarks' icons,
+/// keyed by bookmark GUID.
+#[allow(clippy::type_complexity)]
+pub(crate) fn fetch_tree_with_icon_urls(
+    db: &PlacesDb,
+    item_guid: &SyncGuid,
+    target_depth: &FetchDepth,
+) -> Result<
+    Option<(
+        BookmarkTreeNode,
+        Option<SyncGuid>,
+        u32,
+        HashMap<SyncGuid, Url>,
+    )>,
+> {
+    let mut icon_urls = HashMap::new();
+    let tree = fetch_tree_impl(db, item_guid, target_depth, Some(&mut icon_urls))?;
+    Ok(tree.map(|(tree, parent_guid, position)| (tree, parent_guid, position, icon_urls)))
+}
+
+fn fetch_tree_impl(
+    db: &PlacesDb,
+    item_guid: &SyncGuid,
+    target_depth: &FetchDepth,
+    mut icon_urls: Option<&mut HashMap<SyncGuid, Url>>,
 ) -> Result<Option<(BookmarkTreeNode, Option<SyncGuid>, u32)>> {
     // XXX - this needs additional work for tags - unlike desktop, there's no
     // "tags" folder, but instead a couple of tables to join on.
-    let sql = r#"
+    let sql = format!(
+        r#"
         WITH RECURSIVE
         descendants(fk, level, type, id, guid, parent, parentGuid, position,
                     title, dateAdded, lastModified) AS (
@@ -1194,12 +1233,7 @@ pub fn fetch_tree(
           JOIN descendants ON b2.parent = descendants.id) -- AND b2.id <> :tags_folder)
         SELECT d.level, d.id, d.guid, d.parent, d.parentGuid, d.type,
                d.position, NULLIF(d.title, '') AS title, d.dateAdded,
-               d.lastModified, h.url
---               (SELECT icon_url FROM moz_icons i
---                      JOIN moz_icons_to_pages ON icon_id = i.id
---                      JOIN moz_pages_w_icons pi ON page_id = pi.id
---                      WHERE pi.page_url_hash = hash(h.url) AND pi.page_url = h.url
---                      ORDER BY width DESC LIMIT 1) AS iconuri,
+               d.lastModified, h.url, {icon_url} AS iconUrl
 --               (SELECT GROUP_CONCAT(t.title, ',')
 --                FROM moz_bookmarks b2
 --                JOIN moz_bookmarks t ON t.id = +b2.parent AND t.parent = :tags_folder
@@ -1214,11 +1248,25 @@ pub fn fetch_tree(
         FROM descendants d
         LEFT JOIN moz_bookmarks b3 ON b3.id = d.parent
         LEFT JOIN moz_places h ON h.id = d.fk
-        ORDER BY d.level, d.parent, d.position"#;
+        ORDER BY d.level, d.parent, d.position"#,
+        // Only look up icons if the caller wants them.
+        icon_url = if icon_urls.is_some() {
+            icon_url_sql("h.url")
+        } else {
+            "NULL".into()
+        }
+    );
 
     let scope = db.begin_interrupt_scope();
 
-    let mut stmt = db.conn().prepare(sql)?;
+    let mut stmt = db.conn().prepare(&sql)?;
+    let mut add_icon_url = |row: &FetchedTreeRow| {
+        if let (Some(icon_urls), Some(icon_url)) = (icon_urls.as_mut(), &row.icon_url) {
+            if let Ok(icon_url) = Url::parse(icon_url) {
+                icon_urls.insert(row.guid.clone(), icon_url);
+            }
+        }
+    };
 
     let mut results =
         stmt.query_and_then_named(&[(":item_guid", item_guid)], FetchedTreeRow::from_row)?;
@@ -1230,6 +1278,7 @@ pub fn fetch_tree(
     let mut root = match results.next() {
         Some(result) => {
             let row = result?;
+            add_icon_url(&row);
             parent_guid = row.parent_guid.clone();
             position = row.position;
             match row.node_type {
@@ -1280,6 +1329,7 @@ pub fn fetch_tree(
                 break;
             }
         }
+        add_icon_url(&row);
         let node = match row.node_type {
             BookmarkType::Bookmark => match &row.url {
                 Some(url_str) => match Url::parse(&url_str) {
@@ -1451,6 +1501,12 @@ fn reset_in_tx(db: &PlacesDb, assoc: &StoreSyncAssociation) -> Result<()> {
     // from the server.
     put_meta(db, LAST_SYNC_META_KEY, &0)?;
 
+    // Forget about any repairs in progress, since the server tree they were
+    // for is gone.
+    delete_meta(db, REQUESTOR_META_KEY)?;
+    delete_meta(db, RESPONDER_META_KEY)?;
+    delete_meta(db, LAST_REPAIR_META_KEY)?;
+
     // Clear the sync ID if we're signing out, or set it to whatever the
     // server gave us if we're signing in.
     match assoc {
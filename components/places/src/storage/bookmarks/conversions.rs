@@ -72,6 +72,7 @@ impl From<PublicNode> for msg_types::BookmarkNode {
             last_modified: Some(n.last_modified.0 as i64),
             title: n.title,
             url: n.url.map(url::Url::into_string),
+            icon_url: n.icon_url.map(url::Url::into_string),
             parent_guid: n.parent_guid.map(|g| g.into_string()),
             position: Some(n.position),
             child_guids: n.child_guids.map_or(vec![], |child_guids| {
@@ -103,6 +104,7 @@ impl From<RawBookmark> for PublicNode {
             last_modified: rb.date_modified,
             url: rb.url,
             title: rb.title,
+            icon_url: None,
             child_guids: None,
             child_nodes: None,
         }
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use super::super::bookmarks::FetchDepth;
+use super::super::icons::{get_icon_url_for_page, icon_url_from_row, icon_url_sql};
 use super::*;
 use crate::msg_types::BookmarkNode as ProtoBookmark;
 
@@ -19,6 +20,9 @@ pub struct PublicNode {
     pub last_modified: Timestamp,
     pub url: Option<Url>,
     pub title: Option<String>,
+    /// The URL of the bookmarked page's icon, if we have one. Only present for
+    /// bookmarks.
+    pub icon_url: Option<Url>,
     pub child_guids: Option<Vec<SyncGuid>>,
     pub child_nodes: Option<Vec<PublicNode>>,
 }
@@ -36,6 +40,7 @@ impl Default for PublicNode {
             last_modified: Timestamp(0),
             url: None,
             title: None,
+            icon_url: None,
             child_guids: None,
             child_nodes: None,
         }
@@ -49,12 +54,15 @@ impl PartialEq for PublicNode {
             && self.guid == other.guid
             && self.parent_guid == other.parent_guid
             && self.url == other.url
+            && self.icon_url == other.icon_url
             && self.child_guids == other.child_guids
             && self.child_nodes == other.child_nodes
     }
 }
 
 pub fn fetch_bookmarks_by_url(db: &PlacesDb, url: &Url) -> Result<Vec<PublicNode>> {
+    // All the bookmarks are for the same page, so they have the same icon.
+    let icon_url = get_icon_url_for_page(db, url)?;
     let nodes = get_raw_bookmarks_for_url(db, url)?
         .into_iter()
         .map(|rb| {
@@ -72,6 +80,7 @@ pub fn fetch_bookmarks_by_url(db: &PlacesDb, url: &Url) -> Result<Vec<PublicNode
                 last_modified: rb.date_modified,
                 url: rb.url,
                 title: rb.title,
+                icon_url: icon_url.clone(),
                 child_guids: None,
                 child_nodes: None,
             }
@@ -80,6 +89,17 @@ pub fn fetch_bookmarks_by_url(db: &PlacesDb, url: &Url) -> Result<Vec<PublicNode
     Ok(nodes)
 }
 
+/// Fills in the icon URLs for `node` and its children, from the URLs that
+/// `fetch_tree_with_icon_urls` returned.
+fn fill_icon_urls(node: &mut PublicNode, icon_urls: &HashMap<SyncGuid, Url>) {
+    node.icon_url = icon_urls.get(&node.guid).cloned();
+    if let Some(children) = node.child_nodes.as_mut() {
+        for child in children {
+            fill_icon_urls(child, icon_urls);
+        }
+    }
+}
+
 /// This is similar to fetch_tree, but does not recursively fetch children of
 /// folders.
 ///
@@ -150,11 +170,10 @@ pub fn fetch_public_tree_with_depth(
     target_depth: &FetchDepth,
 ) -> Result<Option<PublicNode>> {
     let _tx = db.begin_transaction()?;
-    let (tree, parent_guid, position) =
-        if let Some((tree, parent_guid, position)) = fetch_tree(db, item_guid, target_depth)? {
-            (tree, parent_guid, position)
-        } else {
-            return Ok(None);
+    let (tree, parent_guid, position, icon_urls) =
+        match fetch_tree_with_icon_urls(db, item_guid, target_depth)? {
+            Some(tree) => tree,
+            None => return Ok(None),
         };
 
     // `position` and `parent_guid` will be handled for the children of
@@ -166,12 +185,13 @@ pub fn fetch_public_tree_with_depth(
         proto.parent_guid = parent_guid;
         proto.position = position;
     }
+    fill_icon_urls(&mut proto, &icon_urls);
     Ok(Some(proto))
 }
 
 pub fn search_bookmarks(db: &PlacesDb, search: &str, limit: u32) -> Result<Vec<PublicNode>> {
     let scope = db.begin_interrupt_scope();
-    Ok(db.query_rows_into_cached(
+    let nodes = db.query_rows_into_cached(
         &SEARCH_QUERY,
         &[(":search", &search), (":limit", &limit)],
         |row| -> Result<_> {
@@ -188,11 +208,13 @@ pub fn search_bookmarks(db: &PlacesDb, search: &str, limit: u32) -> Result<Vec<P
                     .get::<_, Option<String>>("url")?
                     .map(|href| url::Url::parse(&href))
                     .transpose()?,
+                icon_url: icon_url_from_row(row)?,
                 child_guids: None,
                 child_nodes: None,
             })
         },
-    )?)
+    )?;
+    Ok(nodes)
 }
 
 pub fn recent_bookmarks(db: &PlacesDb, limit: u32) -> Result<Vec<PublicNode>> {
@@ -205,7 +227,8 @@ pub fn recent_bookmarks(db: &PlacesDb, limit: u32) -> Result<Vec<PublicNode>> {
             b.dateAdded,
             b.lastModified,
             NULLIF(b.title, '') AS title,
-            h.url AS url
+            h.url AS url,
+            {icon_url} AS iconUrl
         FROM moz_bookmarks b
         JOIN moz_bookmarks p ON p.id = b.parent
         JOIN moz_places h ON h.id = b.fk
@@ -213,27 +236,28 @@ pub fn recent_bookmarks(db: &PlacesDb, limit: u32) -> Result<Vec<PublicNode>> {
         ORDER BY b.dateAdded DESC
         LIMIT :limit",
         bookmark_type = BookmarkType::Bookmark as u8,
+        icon_url = icon_url_sql("h.url"),
     );
-    Ok(
-        db.query_rows_into_cached(&sql, &[(":limit", &limit)], |row| -> Result<_> {
-            scope.err_if_interrupted()?;
-            Ok(PublicNode {
-                node_type: BookmarkType::Bookmark,
-                guid: row.get("guid")?,
-                parent_guid: row.get("parentGuid")?,
-                position: row.get("position")?,
-                date_added: row.get("dateAdded")?,
-                last_modified: row.get("lastModified")?,
-                title: row.get("title")?,
-                url: row
-                    .get::<_, Option<String>>("url")?
-                    .map(|href| url::Url::parse(&href))
-                    .transpose()?,
-                child_guids: None,
-                child_nodes: None,
-            })
-        })?,
-    )
+    let nodes = db.query_rows_into_cached(&sql, &[(":limit", &limit)], |row| -> Result<_> {
+        scope.err_if_interrupted()?;
+        Ok(PublicNode {
+            node_type: BookmarkType::Bookmark,
+            guid: row.get("guid")?,
+            parent_guid: row.get("parentGuid")?,
+            position: row.get("position")?,
+            date_added: row.get("dateAdded")?,
+            last_modified: row.get("lastModified")?,
+            title: row.get("title")?,
+            url: row
+                .get::<_, Option<String>>("url")?
+                .map(|href| url::Url::parse(&href))
+                .transpose()?,
+            icon_url: icon_url_from_row(row)?,
+            child_guids: None,
+            child_nodes: None,
+        })
+    })?;
+    Ok(nodes)
 }
 
 lazy_static::lazy_static! {
@@ -246,7 +270,8 @@ lazy_static::lazy_static! {
             b.lastModified,
             -- Note we return null for titles with an empty string.
             NULLIF(b.title, '') AS title,
-            h.url AS url
+            h.url AS url,
+            {icon_url} AS iconUrl
         FROM moz_bookmarks b
         JOIN moz_bookmarks p ON p.id = b.parent
         JOIN moz_places h ON h.id = b.fk
@@ -271,6 +296,7 @@ lazy_static::lazy_static! {
         bookmark_type = BookmarkType::Bookmark as u8,
         match_bhvr = crate::match_impl::MatchBehavior::Anywhere as u32,
         search_bhvr = crate::match_impl::SearchBehavior::BOOKMARK.bits(),
+        icon_url = icon_url_sql("h.url"),
     );
 }
 #[cfg(test)]
@@ -324,6 +350,7 @@ mod test {
                 url: Some(url.clone()),
                 parent_guid: Some(BookmarkRootGuid::Unfiled.into()),
                 position: 1,
+                icon_url: None,
                 child_guids: None,
                 child_nodes: None,
                 // Ignored by our PartialEq
@@ -340,6 +367,7 @@ mod test {
                 url: Some(url),
                 parent_guid: Some(BookmarkRootGuid::Unfiled.into()),
                 position: 3,
+                icon_url: None,
                 child_guids: None,
                 child_nodes: None,
                 // Ignored by our PartialEq
@@ -564,6 +592,88 @@ mod test {
         Ok(())
     }
     #[test]
+    fn test_icon_urls() -> Result<()> {
+        use crate::storage::icons::{set_icons_for_page, set_root_icon, Icon};
+
+        let conns = new_mem_connections();
+        let _ = env_logger::try_init();
+        insert_json_tree(
+            &conns.write,
+            json!({
+                "guid": String::from(BookmarkRootGuid::Unfiled.as_str()),
+                "children": [
+                    {
+                        "guid": "bookmark1___",
+                        "url": "https://www.example1.com/",
+                        "title": "has icon",
+                    },
+                    {
+                        "guid": "bookmark2___",
+                        "url": "https://www.example2.com/",
+                        "title": "no icon",
+                    },
+                ]
+            }),
+        );
+        let icon_url = Url::parse("https://www.example1.com/icon.png")?;
+        set_icons_for_page(
+            &conns.write,
+            &Url::parse("https://www.example1.com/")?,
+            &[Icon {
+                url: icon_url.clone(),
+                width: 16,
+                mime_type: None,
+                data: vec![1, 2, 3],
+                expires: Timestamp::now(),
+            }],
+        )?;
+
+        let unfiled = fetch_public_tree(&conns.read, BookmarkRootGuid::Unfiled.guid())?.unwrap();
+        assert_eq!(unfiled.icon_url, None);
+        let children = unfiled.child_nodes.unwrap();
+        assert_eq!(children[0].icon_url.as_ref(), Some(&icon_url));
+        assert_eq!(children[1].icon_url, None);
+
+        let bmks = search_bookmarks(&conns.read, "has icon", 10)?;
+        assert_eq!(bmks.len(), 1);
+        assert_eq!(bmks[0].icon_url.as_ref(), Some(&icon_url));
+
+        // Pages without their own icons fall back to their origin's root icon.
+        let root_icon_url = Url::parse("https://www.example2.com/favicon.ico")?;
+        set_root_icon(
+            &conns.write,
+            &Icon {
+                url: root_icon_url.clone(),
+                width: 16,
+                mime_type: None,
+                data: vec![4, 5, 6],
+                expires: Timestamp::now(),
+            },
+        )?;
+        let unfiled = fetch_public_tree(&conns.read, BookmarkRootGuid::Unfiled.guid())?.unwrap();
+        let children = unfiled.child_nodes.unwrap();
+        assert_eq!(children[0].icon_url.as_ref(), Some(&icon_url));
+        assert_eq!(children[1].icon_url.as_ref(), Some(&root_icon_url));
+
+        let mut recent = recent_bookmarks(&conns.read, 10)?
+            .into_iter()
+            .map(|b| (b.guid, b.icon_url))
+            .collect::<Vec<_>>();
+        recent.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            recent,
+            vec![
+                (SyncGuid::from("bookmark1___"), Some(icon_url)),
+                (SyncGuid::from("bookmark2___"), Some(root_icon_url.clone())),
+            ]
+        );
+
+        let bmks = fetch_bookmarks_by_url(&conns.read, &Url::parse("https://www.example2.com/")?)?;
+        assert_eq!(bmks.len(), 1);
+        assert_eq!(bmks[0].icon_url, Some(root_icon_url));
+        Ok(())
+    }
+    #[test]
     fn test_recent() -> Result<()> {
         let conns = new_mem_connections();
         let _ = env_logger::try_init();
@@ -622,6 +732,7 @@ mod test {
                 url: Some(Url::parse("https://www.example5.com/").unwrap()),
                 parent_guid: Some(BookmarkRootGuid::Unfiled.into()),
                 position: 5,
+                icon_url: None,
                 child_guids: None,
                 child_nodes: None,
                 // Ignored by our PartialEq
@@ -638,6 +749,7 @@ mod test {
                 url: Some(Url::parse("https://www.example4.com/").unwrap()),
                 parent_guid: Some(BookmarkRootGuid::Unfiled.into()),
                 position: 3,
+                icon_url: None,
                 child_guids: None,
                 child_nodes: None,
                 // Ignored by our PartialEq
@@ -654,6 +766,7 @@ mod test {
                 url: Some(Url::parse("https://www.example3.com/").unwrap()),
                 parent_guid: Some(BookmarkRootGuid::Unfiled.into()),
                 position: 2,
+                icon_url: None,
                 child_guids: None,
                 child_nodes: None,
                 // Ignored by our PartialEq
@@ -384,6 +384,15 @@ fn wipe_local_in_tx(db: &PlacesDb) -> Result<()> {
         "DELETE FROM moz_historyvisit_tombstones",
         "DELETE FROM moz_origins
          WHERE id NOT IN (SELECT origin_id FROM moz_places)",
+        // Icons for pages in places are removed along with the pages, but
+        // we also need to remove icons for pages that were never in places,
+        // and root icons, which aren't tied to a page.
+        "DELETE FROM moz_pages_w_icons AS p WHERE NOT EXISTS(
+             SELECT 1 FROM moz_places h
+             WHERE h.url_hash = p.page_url_hash AND h.url = p.page_url)",
+        "DELETE FROM moz_icons AS i WHERE NOT EXISTS(
+             SELECT 1 FROM moz_icons_to_pages ip
+             WHERE ip.icon_id = i.id)",
         &format!(
             r#"UPDATE moz_places SET
                 frecency = (CASE WHEN url_hash BETWEEN hash("place", "prefix_lo") AND
@@ -71,7 +71,14 @@ pub fn validate_tag(tag: &str) -> ValidatedTag<'_> {
 pub fn tag_url(db: &PlacesDb, url: &Url, tag: &str) -> Result<()> {
     let tag = validate_tag(&tag).ensure_valid()?;
     let tx = db.begin_transaction()?;
+    tag_url_in_tx(db, url, tag)?;
+    tx.commit()?;
+    Ok(())
+}
 
+/// Like `tag_url`, but for a tag that's already been validated, from inside
+/// a transaction.
+pub(crate) fn tag_url_in_tx(db: &PlacesDb, url: &Url, tag: &str) -> Result<()> {
     // This function will not create a new place.
     // Fetch the place id, so we (a) avoid creating a new tag when we aren't
     // going to reference it and (b) to avoid a sub-query.
@@ -91,7 +98,6 @@ pub fn tag_url(db: &PlacesDb, url: &Url, tag: &str) -> Result<()> {
          VALUES((SELECT id FROM moz_tags WHERE tag = :tag), :place_id)",
         &[(":tag", &tag), (":place_id", &place_id)],
     )?;
-    tx.commit()?;
     Ok(())
 }
 
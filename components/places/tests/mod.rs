@@ -5,3 +5,4 @@
 mod fennec_bookmarks;
 mod fennec_history;
 mod ios_bookmarks;
+mod netscape_html;
@@ -0,0 +1,271 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use places::{
+    api::places_api::{ConnectionType, PlacesApi},
+    import::{export_netscape_html, import_netscape_html},
+    storage::{
+        bookmarks::{
+            bookmarks_get_url_for_keyword, fetch_tree, insert_tree, BookmarkNode, BookmarkRootGuid,
+            BookmarkTreeNode, FetchDepth, FolderNode,
+        },
+        tags::get_tags_for_url,
+    },
+    PlacesDb, Result, Timestamp,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use url::Url;
+
+static API_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn new_db() -> Result<PlacesDb> {
+    let name = format!(
+        "netscape-html-{}",
+        API_COUNTER.fetch_add(1, Ordering::SeqCst)
+    );
+    PlacesApi::new_memory(&name)?.open_connection(ConnectionType::ReadWrite)
+}
+
+/// Returns a line for each item under `root`, indented by depth, with its
+/// dates in seconds, URL and tags.
+fn outline(db: &PlacesDb, root: BookmarkRootGuid) -> Result<Vec<String>> {
+    fn describe(
+        db: &PlacesDb,
+        node: &BookmarkTreeNode,
+        depth: usize,
+        lines: &mut Vec<String>,
+    ) -> Result<()> {
+        let indent = "  ".repeat(depth);
+        let dates = |added: Option<Timestamp>, modified: Option<Timestamp>| {
+            format!(
+                "[{}, {}]",
+                added.unwrap().as_millis() / 1000,
+                modified.unwrap().as_millis() / 1000
+            )
+        };
+        match node {
+            BookmarkTreeNode::Bookmark(b) => {
+                let mut tags = get_tags_for_url(db, &b.url)?;
+                tags.sort();
+                lines.push(format!(
+                    "{}{} <{}> {} {:?}",
+                    indent,
+                    b.title.as_deref().unwrap_or_default(),
+                    b.url,
+                    dates(b.date_added, b.last_modified),
+                    tags
+                ));
+            }
+            BookmarkTreeNode::Separator(_) => lines.push(format!("{}---", indent)),
+            BookmarkTreeNode::Folder(f) => {
+                lines.push(format!(
+                    "{}{}/ {}",
+                    indent,
+                    f.title.as_deref().unwrap_or_default(),
+                    dates(f.date_added, f.last_modified)
+                ));
+                for child in &f.children {
+                    describe(db, child, depth + 1, lines)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    let (tree, _, _) = fetch_tree(db, &root.into(), &FetchDepth::Deepest)?.unwrap();
+    let mut lines = Vec::new();
+    match tree {
+        BookmarkTreeNode::Folder(f) => {
+            for child in &f.children {
+                describe(db, child, 0, &mut lines)?;
+            }
+        }
+        _ => panic!("roots should be folders"),
+    }
+    Ok(lines)
+}
+
+/// Like `outline`, but without dates, for exports that don't have them.
+fn titles(db: &PlacesDb, root: BookmarkRootGuid) -> Result<Vec<String>> {
+    fn describe(node: &BookmarkTreeNode, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        match node {
+            BookmarkTreeNode::Bookmark(b) => lines.push(format!(
+                "{}{} <{}>",
+                indent,
+                b.title.as_deref().unwrap_or_default(),
+                b.url
+            )),
+            BookmarkTreeNode::Separator(_) => lines.push(format!("{}---", indent)),
+            BookmarkTreeNode::Folder(f) => {
+                lines.push(format!(
+                    "{}{}/",
+                    indent,
+                    f.title.as_deref().unwrap_or_default()
+                ));
+                for child in &f.children {
+                    describe(child, depth + 1, lines);
+                }
+            }
+        }
+    }
+    let (tree, _, _) = fetch_tree(db, &root.into(), &FetchDepth::Deepest)?.unwrap();
+    let mut lines = Vec::new();
+    if let BookmarkTreeNode::Folder(f) = tree {
+        for child in &f.children {
+            describe(child, 0, &mut lines);
+        }
+    }
+    Ok(lines)
+}
+
+fn keyword_url(db: &PlacesDb, keyword: &str) -> Result<Option<String>> {
+    Ok(bookmarks_get_url_for_keyword(db, keyword)?.map(|url| url.into_string()))
+}
+
+#[test]
+fn test_import_firefox() -> Result<()> {
+    let db = new_db()?;
+    import_netscape_html(&db, include_str!("./bookmarks_firefox.html"))?;
+
+    assert_eq!(
+        outline(&db, BookmarkRootGuid::Menu)?,
+        vec![
+            r#"Firefox & You <https://www.mozilla.org/en-US/firefox/> [1585000000, 1585000100] ["browsers", "mozilla"]"#,
+            "---",
+            "Mozilla Firefox/ [1585000200, 1585000300]",
+            r#"  Get Help <https://support.mozilla.org/en-US/products/firefox> [1585000400, 1585000400] []"#,
+            r#"  About Us <https://www.mozilla.org/en-US/about/> [1585000500, 1585000500] ["mozilla"]"#,
+        ]
+    );
+    // The "Most Visited" query isn't imported.
+    assert_eq!(
+        outline(&db, BookmarkRootGuid::Toolbar)?,
+        vec![
+            r#"MDN Web Docs <https://developer.mozilla.org/> [1585000700, 1585000800] []"#,
+            "Empty folder/ [1585000900, 1585000900]",
+        ]
+    );
+    assert_eq!(
+        outline(&db, BookmarkRootGuid::Unfiled)?,
+        vec![r#"Example <unfiled> <https://example.com/?q=a&b=c> [1585001000, 1585001000] []"#]
+    );
+    assert!(outline(&db, BookmarkRootGuid::Mobile)?.is_empty());
+
+    assert_eq!(
+        keyword_url(&db, "fx")?.as_deref(),
+        Some("https://www.mozilla.org/en-US/firefox/")
+    );
+    assert_eq!(
+        keyword_url(&db, "mdn")?.as_deref(),
+        Some("https://developer.mozilla.org/")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_import_chrome() -> Result<()> {
+    let db = new_db()?;
+    import_netscape_html(&db, include_str!("./bookmarks_chrome.html"))?;
+
+    assert_eq!(
+        titles(&db, BookmarkRootGuid::Toolbar)?,
+        vec![
+            "Rust Programming Language <https://www.rust-lang.org/>",
+            "News/",
+            "  Hacker News <https://news.ycombinator.com/>",
+        ]
+    );
+    // Desktop also imports Chrome's "Other bookmarks" as a folder in the menu.
+    assert_eq!(
+        titles(&db, BookmarkRootGuid::Menu)?,
+        vec![
+            "Other bookmarks/",
+            "  Wikipedia <https://www.wikipedia.org/>"
+        ]
+    );
+    assert!(titles(&db, BookmarkRootGuid::Unfiled)?.is_empty());
+
+    // Chrome only writes `LAST_MODIFIED` for folders.
+    let (toolbar, _, _) =
+        fetch_tree(&db, &BookmarkRootGuid::Toolbar.into(), &FetchDepth::Deepest)?.unwrap();
+    let children = match toolbar {
+        BookmarkTreeNode::Folder(f) => f.children,
+        _ => panic!("roots should be folders"),
+    };
+    match &children[1] {
+        BookmarkTreeNode::Folder(f) => {
+            assert_eq!(f.date_added, Some(Timestamp(1_596_043_450_000)));
+            assert_eq!(f.last_modified, Some(Timestamp(1_596_043_460_000)));
+        }
+        _ => panic!("should be a folder"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_import_safari() -> Result<()> {
+    let db = new_db()?;
+    import_netscape_html(&db, include_str!("./bookmarks_safari.html"))?;
+
+    // Safari doesn't mark any folders, so everything goes in the menu.
+    assert_eq!(
+        titles(&db, BookmarkRootGuid::Menu)?,
+        vec![
+            "Favourites/",
+            "  Apple <https://www.apple.com/>",
+            "  iCloud <https://www.icloud.com/>",
+            "Bookmarks Menu/",
+            "Travel/",
+            "  OpenStreetMap <https://www.openstreetmap.org/>",
+            "WebKit <https://webkit.org/>",
+            "Reading List/",
+            "  An article <https://example.com/article>",
+        ]
+    );
+    assert!(titles(&db, BookmarkRootGuid::Toolbar)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_round_trip() -> Result<()> {
+    let db = new_db()?;
+    import_netscape_html(&db, include_str!("./bookmarks_firefox.html"))?;
+    insert_tree(
+        &db,
+        &FolderNode {
+            guid: Some(BookmarkRootGuid::Mobile.into()),
+            children: vec![BookmarkNode {
+                guid: None,
+                date_added: Some(Timestamp(1_585_002_000_000)),
+                last_modified: Some(Timestamp(1_585_002_000_000)),
+                title: Some("On the go".into()),
+                url: Url::parse("https://example.com/mobile")?,
+            }
+            .into()],
+            ..Default::default()
+        },
+    )?;
+    let html = export_netscape_html(&db)?;
+
+    let imported = new_db()?;
+    import_netscape_html(&imported, &html)?;
+    for root in &[
+        BookmarkRootGuid::Menu,
+        BookmarkRootGuid::Toolbar,
+        BookmarkRootGuid::Unfiled,
+        BookmarkRootGuid::Mobile,
+    ] {
+        assert_eq!(outline(&imported, *root)?, outline(&db, *root)?);
+    }
+    assert_eq!(outline(&imported, BookmarkRootGuid::Mobile)?.len(), 1);
+    assert_eq!(
+        keyword_url(&imported, "fx")?.as_deref(),
+        Some("https://www.mozilla.org/en-US/firefox/")
+    );
+    assert_eq!(
+        keyword_url(&imported, "mdn")?.as_deref(),
+        Some("https://developer.mozilla.org/")
+    );
+    Ok(())
+}
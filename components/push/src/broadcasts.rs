@@ -0,0 +1,243 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Keep track of the broadcasts that we're subscribed to.
+//!
+//! Broadcasts (sometimes called "Megaphone") let the push server tell every
+//! client about a change at once, without a push subscription per client.
+//! Remote Settings uses them to announce new versions of its collections.
+//! We send the broadcast IDs that we're subscribed to, with the last version
+//! that we saw for each, when we connect, and the server tells us about any
+//! newer versions, then and whenever they change.
+//!
+//! The versions that we've seen are saved in push storage, so that we only
+//! tell listeners about changes once, even across restarts.
+
+use std::collections::HashMap;
+
+use crate::communications::{BroadcastValue, Connection};
+use crate::error;
+use crate::storage::Storage;
+
+/// Receives new versions of a broadcast.
+pub trait BroadcastListener: Send {
+    fn broadcast_changed(&self, broadcast_id: &str, version: &str);
+}
+
+#[derive(Default)]
+pub struct BroadcastHandler {
+    listeners: HashMap<String, Vec<Box<dyn BroadcastListener>>>,
+}
+
+impl BroadcastHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a listener for new versions of a broadcast. This doesn't
+    /// subscribe to the broadcast; use `subscribe` for that.
+    pub fn add_listener(&mut self, broadcast_id: &str, listener: Box<dyn BroadcastListener>) {
+        self.listeners
+            .entry(broadcast_id.to_owned())
+            .or_default()
+            .push(listener);
+    }
+
+    /// Returns `true` if there were any listeners for this broadcast.
+    pub fn remove_listeners(&mut self, broadcast_id: &str) -> bool {
+        self.listeners.remove(broadcast_id).is_some()
+    }
+
+    /// Subscribes `conn` to the broadcasts saved in `store`, so that we send
+    /// them when we connect. Call this before making any other requests.
+    pub fn restore(&self, store: &dyn Storage, conn: &dyn Connection) -> error::Result<()> {
+        let saved = store.get_broadcasts()?;
+        if !saved.is_empty() {
+            conn.broadcast_subscribe(BroadcastValue::from(&saved))?;
+        }
+        Ok(())
+    }
+
+    /// Subscribes to broadcasts, starting from the given versions. The
+    /// server will tell us if it has newer ones.
+    pub fn subscribe(
+        &self,
+        store: &dyn Storage,
+        conn: &dyn Connection,
+        broadcasts: &HashMap<String, String>,
+    ) -> error::Result<()> {
+        store.put_broadcasts(broadcasts)?;
+        conn.broadcast_subscribe(BroadcastValue::from(broadcasts))?;
+        Ok(())
+    }
+
+    /// Forgets a broadcast, so that we don't send it when we connect. The
+    /// protocol doesn't have a way to unsubscribe, so the server may keep
+    /// sending it until we reconnect, but we won't tell listeners about it.
+    pub fn unsubscribe(
+        &self,
+        store: &dyn Storage,
+        conn: &dyn Connection,
+        broadcast_id: &str,
+    ) -> error::Result<bool> {
+        conn.broadcast_unsubscribe(broadcast_id)?;
+        store.delete_broadcast(broadcast_id)
+    }
+
+    /// Checks `conn` for new versions of the broadcasts that we're
+    /// subscribed to, tells the listeners about them, and saves them.
+    /// Returns the broadcasts that changed.
+    ///
+    /// Connections learn about new versions as they read from the server, so
+    /// embedders should call this after `read_notifications`.
+    pub fn handle_broadcasts(
+        &self,
+        store: &dyn Storage,
+        conn: &dyn Connection,
+    ) -> error::Result<HashMap<String, String>> {
+        let saved = store.get_broadcasts()?;
+        let changes = conn
+            .broadcasts()?
+            .into_versions()?
+            .into_iter()
+            .filter(|(id, version)| {
+                saved
+                    .get(id)
+                    .map_or(false, |saved_version| saved_version != version)
+            })
+            .collect::<HashMap<_, _>>();
+        // Listeners go first, so that if we crash before saving, they'll
+        // hear about the change again, instead of missing it.
+        for (id, version) in &changes {
+            log::debug!("Broadcast {} changed to {}", id, version);
+            if let Some(listeners) = self.listeners.get(id) {
+                for listener in listeners {
+                    listener.broadcast_changed(id, version);
+                }
+            }
+        }
+        store.put_broadcasts(&changes)?;
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communications::mock::MockConnection;
+    use crate::storage::Store;
+    use std::sync::{Arc, Mutex};
+
+    const MONITOR_CHANGES: &str = "remote-settings/monitor_changes";
+
+    /// Collects the changes that it's told about.
+    #[derive(Clone, Default)]
+    struct TestListener(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl BroadcastListener for TestListener {
+        fn broadcast_changed(&self, broadcast_id: &str, version: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((broadcast_id.to_owned(), version.to_owned()));
+        }
+    }
+
+    impl TestListener {
+        fn take(&self) -> Vec<(String, String)> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    fn versions(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(id, version)| ((*id).to_owned(), (*version).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_subscribe_and_change() {
+        let store = Store::open_in_memory().unwrap();
+        let conn = MockConnection::new();
+        let listener = TestListener::default();
+        let mut handler = BroadcastHandler::new();
+        handler.add_listener(MONITOR_CHANGES, Box::new(listener.clone()));
+
+        handler
+            .subscribe(
+                &store,
+                &conn,
+                &versions(&[(MONITOR_CHANGES, "v1"), ("other", "v5")]),
+            )
+            .unwrap();
+        assert_eq!(
+            store.get_broadcasts().unwrap(),
+            versions(&[(MONITOR_CHANGES, "v1"), ("other", "v5")])
+        );
+        assert!(handler.handle_broadcasts(&store, &conn).unwrap().is_empty());
+        assert!(listener.take().is_empty());
+
+        conn.server_changed(MONITOR_CHANGES, "v2");
+        conn.server_changed("other", "v6");
+        // We don't tell listeners about broadcasts that we aren't subscribed to.
+        conn.server_changed("unknown", "v1");
+        assert_eq!(
+            handler.handle_broadcasts(&store, &conn).unwrap(),
+            versions(&[(MONITOR_CHANGES, "v2"), ("other", "v6")])
+        );
+        assert_eq!(
+            listener.take(),
+            vec![(MONITOR_CHANGES.to_owned(), "v2".to_owned())]
+        );
+        assert_eq!(
+            store.get_broadcasts().unwrap(),
+            versions(&[(MONITOR_CHANGES, "v2"), ("other", "v6")])
+        );
+
+        // Changes are only reported once.
+        assert!(handler.handle_broadcasts(&store, &conn).unwrap().is_empty());
+        assert!(listener.take().is_empty());
+
+        assert!(handler.unsubscribe(&store, &conn, "other").unwrap());
+        assert_eq!(
+            conn.broadcasts().unwrap(),
+            BroadcastValue::from(&versions(&[(MONITOR_CHANGES, "v2")]))
+        );
+        conn.server_changed("other", "v7");
+        assert!(handler.handle_broadcasts(&store, &conn).unwrap().is_empty());
+
+        assert!(handler.remove_listeners(MONITOR_CHANGES));
+        conn.server_changed(MONITOR_CHANGES, "v3");
+        assert_eq!(handler.handle_broadcasts(&store, &conn).unwrap().len(), 1);
+        assert!(listener.take().is_empty());
+    }
+
+    #[test]
+    fn test_restore() {
+        let store = Store::open_in_memory().unwrap();
+        store
+            .put_broadcasts(&versions(&[(MONITOR_CHANGES, "v1")]))
+            .unwrap();
+
+        // A new connection picks up where the last one left off, so the
+        // server can tell us what changed while we were away.
+        let conn = MockConnection::new();
+        let listener = TestListener::default();
+        let mut handler = BroadcastHandler::new();
+        handler.add_listener(MONITOR_CHANGES, Box::new(listener.clone()));
+        handler.restore(&store, &conn).unwrap();
+        assert_eq!(
+            conn.broadcasts().unwrap(),
+            BroadcastValue::from(&versions(&[(MONITOR_CHANGES, "v1")]))
+        );
+
+        conn.server_changed(MONITOR_CHANGES, "v4");
+        handler.handle_broadcasts(&store, &conn).unwrap();
+        assert_eq!(
+            listener.take(),
+            vec![(MONITOR_CHANGES.to_owned(), "v4".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An in-memory `Connection` for tests, which acts like a push server that
+//! accepts every request.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use super::{AckCode, BroadcastValue, Connection, IncomingNotification, RegisterResponse};
+use crate::error::{self, ErrorKind::CommunicationError};
+
+pub const MOCK_UAID: &str = "deadbeefdeadbeefdeadbeefdeadbeef";
+pub const MOCK_SECRET: &str = "mock-secret";
+
+#[derive(Default)]
+pub struct MockConnection {
+    pub uaid: Option<String>,
+    pub token: Option<String>,
+    channels: RefCell<BTreeSet<String>>,
+    broadcasts: RefCell<HashMap<String, String>>,
+    acks: RefCell<Vec<(String, String, AckCode)>>,
+    fail_acks: Cell<bool>,
+}
+
+impl MockConnection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The notifications that we've acked, in order.
+    pub fn acks(&self) -> Vec<(String, String, AckCode)> {
+        self.acks.borrow().clone()
+    }
+
+    /// Makes acks fail, like they would if the connection dropped.
+    pub fn fail_acks(&self, fail: bool) {
+        self.fail_acks.set(fail);
+    }
+
+    /// Pretends that the server sent a new version of a broadcast.
+    pub fn server_changed(&self, broadcast_id: &str, version: &str) {
+        self.broadcasts
+            .borrow_mut()
+            .insert(broadcast_id.to_owned(), version.to_owned());
+    }
+}
+
+impl Connection for MockConnection {
+    fn uaid(&self) -> Option<String> {
+        self.uaid.clone()
+    }
+
+    fn subscribe(
+        &mut self,
+        channel_id: &str,
+        _app_server_key: Option<&str>,
+    ) -> error::Result<RegisterResponse> {
+        // Like the real server, we only hand out a secret with a new UAID.
+        let secret = if self.uaid.is_none() {
+            self.uaid = Some(MOCK_UAID.to_owned());
+            Some(MOCK_SECRET.to_owned())
+        } else {
+            None
+        };
+        self.channels.borrow_mut().insert(channel_id.to_owned());
+        Ok(RegisterResponse {
+            uaid: self.uaid.clone().unwrap(),
+            channel_id: channel_id.to_owned(),
+            secret,
+            endpoint: format!("https://push.example.com/wpush/v2/{}", channel_id),
+            senderid: None,
+        })
+    }
+
+    fn unsubscribe(&self, channel_id: Option<&str>) -> error::Result<bool> {
+        let mut channels = self.channels.borrow_mut();
+        Ok(match channel_id {
+            Some(channel_id) => channels.remove(channel_id),
+            None => {
+                channels.clear();
+                true
+            }
+        })
+    }
+
+    fn update(&mut self, new_token: &str) -> error::Result<bool> {
+        self.token = Some(new_token.to_owned());
+        Ok(true)
+    }
+
+    fn channel_list(&self) -> error::Result<Vec<String>> {
+        Ok(self.channels.borrow().iter().cloned().collect())
+    }
+
+    fn verify_connection(&self, channels: &[String]) -> error::Result<bool> {
+        Ok(*self.channels.borrow() == channels.iter().cloned().collect())
+    }
+
+    fn broadcast_subscribe(&self, broadcast: BroadcastValue) -> error::Result<BroadcastValue> {
+        let mut broadcasts = self.broadcasts.borrow_mut();
+        broadcasts.extend(broadcast.into_versions()?);
+        Ok(BroadcastValue::from(&*broadcasts))
+    }
+
+    fn broadcast_unsubscribe(&self, broadcast_id: &str) -> error::Result<bool> {
+        Ok(self.broadcasts.borrow_mut().remove(broadcast_id).is_some())
+    }
+
+    fn broadcasts(&self) -> error::Result<BroadcastValue> {
+        Ok(BroadcastValue::from(&*self.broadcasts.borrow()))
+    }
+
+    /// Nothing ever arrives.
+    fn read_notifications(&self, _timeout: Duration) -> error::Result<Vec<IncomingNotification>> {
+        Ok(Vec::new())
+    }
+
+    fn ack(&self, channel_id: &str, message_id: &str, code: AckCode) -> error::Result<()> {
+        if self.fail_acks.get() {
+            return Err(CommunicationError("Mock connection dropped".to_owned()).into());
+        }
+        self.acks
+            .borrow_mut()
+            .push((channel_id.to_owned(), message_id.to_owned(), code));
+        Ok(())
+    }
+}
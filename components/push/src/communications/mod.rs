@@ -5,7 +5,8 @@
 //! Server Communications.
 //!
 //! Handles however communication to and from the remote Push Server should be done. For Desktop
-//! this will be over Websocket. For mobile, it will probably be calls into the local operating
+//! and other platforms without an OS push service, this is over a WebSocket (see
+//! `ConnectWebSocket`). For mobile, it will probably be calls into the local operating
 //! system and HTTPS to the web push server.
 //!
 //! In the future, it could be using gRPC and QUIC, or quantum relay.
@@ -13,6 +14,8 @@
 use serde_derive::*;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
 use url::Url;
 use viaduct::{header_names, status_codes, Headers, Request};
 
@@ -23,6 +26,15 @@ use crate::error::{
 };
 use crate::storage::Store;
 
+#[cfg(test)]
+pub(crate) mod mock;
+mod socket;
+mod websocket;
+
+pub use websocket::{
+    connect_websocket, ConnectWebSocket, IncomingNotification, NotificationHeaders,
+};
+
 #[derive(Debug)]
 pub struct RegisterResponse {
     /// The UAID associated with the request
@@ -42,17 +54,73 @@ pub struct RegisterResponse {
 }
 
 #[serde(untagged)]
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BroadcastValue {
     Value(String),
     Nested(HashMap<String, BroadcastValue>),
 }
 
+impl BroadcastValue {
+    /// Flattens a map of broadcast IDs to versions.
+    pub fn into_versions(self) -> error::Result<HashMap<String, String>> {
+        let broadcasts = match self {
+            BroadcastValue::Nested(broadcasts) => broadcasts,
+            BroadcastValue::Value(_) => {
+                return Err(CommunicationError("Expected broadcast IDs".to_owned()).into())
+            }
+        };
+        broadcasts
+            .into_iter()
+            .map(|(id, version)| match version {
+                BroadcastValue::Value(version) => Ok((id, version)),
+                BroadcastValue::Nested(_) => {
+                    Err(CommunicationError(format!("Invalid version for {}", id)).into())
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<&HashMap<String, String>> for BroadcastValue {
+    fn from(versions: &HashMap<String, String>) -> Self {
+        BroadcastValue::Nested(
+            versions
+                .iter()
+                .map(|(id, version)| (id.clone(), BroadcastValue::Value(version.clone())))
+                .collect(),
+        )
+    }
+}
+
+/// Tells the server what happened to a notification, so that it can stop
+/// sending it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AckCode {
+    /// The message was delivered to the application.
+    Delivered,
+    /// The message couldn't be decrypted.
+    DecryptionError,
+    /// The message wasn't delivered for some other reason.
+    NotDelivered,
+}
+
+impl AckCode {
+    fn code(self) -> u16 {
+        match self {
+            AckCode::Delivered => 100,
+            AckCode::DecryptionError => 101,
+            AckCode::NotDelivered => 102,
+        }
+    }
+}
+
 /// A new communication link to the Autopush server
 pub trait Connection {
-    // get the connection UAID
     // TODO [conv]: reset_uaid(). This causes all known subscriptions to be reset.
 
+    /// The UAID that the server assigned to us, if we have one.
+    fn uaid(&self) -> Option<String>;
+
     /// send a new subscription request to the server, get back the server registration response.
     fn subscribe(
         &mut self,
@@ -73,14 +141,23 @@ pub trait Connection {
     /// This should be performed once a day.
     fn verify_connection(&self, channels: &[String]) -> error::Result<bool>;
 
-    /// Add one or more new broadcast subscriptions.
+    /// Add one or more new broadcast subscriptions, and return all the
+    /// broadcasts that we're subscribed to.
     fn broadcast_subscribe(&self, broadcast: BroadcastValue) -> error::Result<BroadcastValue>;
 
-    /// get the list of broadcasts
+    /// Stop sending a broadcast when we connect, and ignore new versions of
+    /// it. Returns `true` if we were subscribed to it.
+    fn broadcast_unsubscribe(&self, broadcast_id: &str) -> error::Result<bool>;
+
+    /// get the list of broadcasts, with the latest version that we know of
+    /// for each.
     fn broadcasts(&self) -> error::Result<BroadcastValue>;
 
-    //impl TODO: Handle a Ping response with updated Broadcasts.
-    //impl TODO: Handle an incoming Notification
+    /// Wait up to `timeout` for notifications from the server.
+    fn read_notifications(&self, timeout: Duration) -> error::Result<Vec<IncomingNotification>>;
+
+    /// Tell the server what happened to a notification.
+    fn ack(&self, channel_id: &str, message_id: &str, code: AckCode) -> error::Result<()>;
 }
 
 /// Connect to the Autopush server via the HTTP interface
@@ -88,6 +165,7 @@ pub struct ConnectHttp {
     pub options: PushConfiguration,
     pub uaid: Option<String>,
     pub auth: Option<String>, // Server auth token
+    broadcasts: Mutex<HashMap<String, String>>,
 }
 
 // Connect to the Autopush server
@@ -104,7 +182,10 @@ pub fn connect(
         );
     };
     if options.socket_protocol.is_some() {
-        return Err(CommunicationError("Unsupported".to_owned()).into());
+        return Err(CommunicationError(
+            "Use `connect_websocket` for socket connections".to_owned(),
+        )
+        .into());
     };
     if options.bridge_type.is_some() && options.registration_id.is_none() {
         return Err(CommunicationError(
@@ -116,6 +197,7 @@ pub fn connect(
         uaid,
         options,
         auth,
+        broadcasts: Mutex::new(HashMap::new()),
     };
 
     Ok(connection)
@@ -139,6 +221,10 @@ impl ConnectHttp {
 }
 
 impl Connection for ConnectHttp {
+    fn uaid(&self) -> Option<String> {
+        self.uaid.clone()
+    }
+
     /// send a new subscription request to the server, get back the server registration response.
     fn subscribe(
         &mut self,
@@ -384,14 +470,35 @@ impl Connection for ConnectHttp {
             .collect())
     }
 
-    // Add one or more new broadcast subscriptions.
-    fn broadcast_subscribe(&self, _broadcast: BroadcastValue) -> error::Result<BroadcastValue> {
-        Err(CommunicationError("Unsupported".to_string()).into())
+    /// Add one or more new broadcast subscriptions. The push server only
+    /// sends broadcasts over WebSockets, so bridged connections just
+    /// remember the versions that we know about, and never see new ones.
+    fn broadcast_subscribe(&self, broadcast: BroadcastValue) -> error::Result<BroadcastValue> {
+        let mut broadcasts = self.broadcasts.lock().unwrap();
+        broadcasts.extend(broadcast.into_versions()?);
+        Ok(BroadcastValue::from(&*broadcasts))
+    }
+
+    fn broadcast_unsubscribe(&self, broadcast_id: &str) -> error::Result<bool> {
+        Ok(self
+            .broadcasts
+            .lock()
+            .unwrap()
+            .remove(broadcast_id)
+            .is_some())
     }
 
     // get the list of broadcasts
     fn broadcasts(&self) -> error::Result<BroadcastValue> {
-        Err(CommunicationError("Unsupported".to_string()).into())
+        Ok(BroadcastValue::from(&*self.broadcasts.lock().unwrap()))
+    }
+
+    /// Bridged notifications arrive through the OS push service instead.
+    fn read_notifications(&self, _timeout: Duration) -> error::Result<Vec<IncomingNotification>> {
+        Err(
+            CommunicationError("Bridged connections receive notifications from the OS".to_owned())
+                .into(),
+        )
     }
 
     /// Verify that the server and client both have matching channel information. A "false"
@@ -421,8 +528,11 @@ impl Connection for ConnectHttp {
         Ok(true)
     }
 
-    //impl TODO: Handle a Ping response with updated Broadcasts.
-    //impl TODO: Handle an incoming Notification
+    /// Bridged notifications are acknowledged when the OS push service
+    /// accepts them, so there's nothing to tell the server.
+    fn ack(&self, _channel_id: &str, _message_id: &str, _code: AckCode) -> error::Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
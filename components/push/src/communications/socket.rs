@@ -0,0 +1,677 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A small, blocking WebSocket (RFC 6455) client.
+//!
+//! This only implements what the autopush protocol needs: the opening
+//! handshake, unfragmented and fragmented text messages, pings, pongs and
+//! closes. Extensions like compression aren't supported, and we never ask for
+//! them.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use rc_crypto::rand;
+use url::Url;
+
+use crate::error::{
+    self,
+    ErrorKind::{CommunicationError, CommunicationServerError},
+};
+
+/// Appended to the handshake key before hashing it, to prove that the server
+/// understood the handshake. See RFC 6455, section 1.3.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Autopush messages are small JSON objects, so anything bigger than this
+/// means that the server is misbehaving.
+const MAX_MESSAGE_SIZE: u64 = 1 << 20;
+
+/// The most that we'll read while waiting for the end of the handshake
+/// response headers.
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+
+/// How long we wait for the rest of a frame once it's started arriving, and
+/// for writes to complete.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// The other side closed the connection. We've already answered it.
+    Close,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(feature = "websocket-tls")]
+    Tls(native_tls::TlsStream<TcpStream>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Stream::Plain(stream) => stream,
+            #[cfg(feature = "websocket-tls")]
+            Stream::Tls(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "websocket-tls")]
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "websocket-tls")]
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            #[cfg(feature = "websocket-tls")]
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+pub(crate) struct WebSocket {
+    stream: Stream,
+    /// Clients mask the frames that they send, and servers don't.
+    is_client: bool,
+}
+
+impl WebSocket {
+    /// Opens a connection to a `ws://` or `wss://` URL, and asks the server
+    /// to speak `protocol` over it.
+    pub fn connect(url: &Url, protocol: &str, timeout: Duration) -> error::Result<Self> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| CommunicationError(format!("Missing host in {}", url)))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| CommunicationError(format!("Missing port in {}", url)))?;
+        let tcp = open_tcp(host, port, timeout)?;
+        let stream = match url.scheme() {
+            "ws" => Stream::Plain(tcp),
+            #[cfg(feature = "websocket-tls")]
+            "wss" => {
+                let connector = native_tls::TlsConnector::new()
+                    .map_err(|e| CommunicationError(format!("TLS error: {}", e)))?;
+                let stream = connector
+                    .connect(host, tcp)
+                    .map_err(|e| CommunicationError(format!("TLS handshake failed: {}", e)))?;
+                Stream::Tls(stream)
+            }
+            #[cfg(not(feature = "websocket-tls"))]
+            "wss" => {
+                return Err(CommunicationError(
+                    "`wss` connections need the `websocket-tls` feature".to_owned(),
+                )
+                .into())
+            }
+            scheme => {
+                return Err(CommunicationError(format!("Unsupported scheme {}", scheme)).into())
+            }
+        };
+        let mut socket = WebSocket {
+            stream,
+            is_client: true,
+        };
+        socket.handshake(url, host, protocol)?;
+        Ok(socket)
+    }
+
+    fn handshake(&mut self, url: &Url, host: &str, protocol: &str) -> error::Result<()> {
+        let mut key = [0u8; 16];
+        rand::fill(&mut key).map_err(|e| CommunicationError(e.to_string()))?;
+        let key = base64::encode(&key);
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_owned(),
+        };
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned(),
+        };
+        let request = format!(
+            "GET {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Protocol: {}\r\n\
+             \r\n",
+            path, host, key, protocol
+        );
+        self.stream
+            .write_all(request.as_bytes())
+            .map_err(io_error)?;
+        self.stream.flush().map_err(io_error)?;
+
+        let response = read_headers(&mut self.stream)?;
+        let mut lines = response.lines();
+        let status_line = lines.next().unwrap_or_default();
+        if status_line.split_whitespace().nth(1) != Some("101") {
+            return Err(CommunicationServerError(format!(
+                "Server refused WebSocket upgrade: {}",
+                status_line
+            ))
+            .into());
+        }
+        let headers = parse_headers(lines);
+        let upgrade = header(&headers, "upgrade").unwrap_or_default();
+        if !upgrade.eq_ignore_ascii_case("websocket") {
+            return Err(
+                CommunicationServerError(format!("Unexpected upgrade: {}", upgrade)).into(),
+            );
+        }
+        if header(&headers, "sec-websocket-accept") != Some(accept_key(&key)?.as_str()) {
+            return Err(CommunicationServerError("Invalid Sec-WebSocket-Accept".to_owned()).into());
+        }
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for the next message, and returns `None` if
+    /// nothing arrived. Pings are answered before they're returned.
+    pub fn read(&mut self, timeout: Duration) -> error::Result<Option<Message>> {
+        // `set_read_timeout` rejects zero durations.
+        let timeout = timeout.max(Duration::from_millis(1));
+        self.stream
+            .tcp()
+            .set_read_timeout(Some(timeout))
+            .map_err(io_error)?;
+        let mut first = [0u8; 1];
+        let result = self.stream.read(&mut first);
+        self.stream
+            .tcp()
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .map_err(io_error)?;
+        match result {
+            Ok(0) => return Err(closed()),
+            Ok(_) => (),
+            Err(ref e) if is_timeout(e) => return Ok(None),
+            Err(e) => return Err(io_error(e)),
+        }
+
+        let mut message: Option<(u8, Vec<u8>)> = None;
+        let mut first = first[0];
+        loop {
+            let (fin, opcode, payload) = self.read_frame(first)?;
+            if opcode & 0x8 != 0 {
+                // Control frames can't be fragmented, but they can arrive
+                // between the fragments of a message. See RFC 6455,
+                // section 5.4.
+                if !fin || payload.len() > 125 {
+                    return Err(protocol_error("Invalid control frame"));
+                }
+                let control = match opcode {
+                    OPCODE_PING => {
+                        self.write_frame(OPCODE_PONG, &payload)?;
+                        Message::Ping(payload)
+                    }
+                    OPCODE_PONG => Message::Pong(payload),
+                    OPCODE_CLOSE => {
+                        // Echo the status code back, as RFC 6455 asks us to.
+                        // The connection is going away, so ignore failures.
+                        let _ = self.write_frame(OPCODE_CLOSE, &payload[..payload.len().min(2)]);
+                        return Ok(Some(Message::Close));
+                    }
+                    _ => return Err(protocol_error("Unexpected frame")),
+                };
+                if message.is_none() {
+                    return Ok(Some(control));
+                }
+                // We've answered it, so keep reading the rest of the message.
+            } else {
+                match opcode {
+                    OPCODE_TEXT | OPCODE_BINARY if message.is_none() => {
+                        message = Some((opcode, payload));
+                    }
+                    OPCODE_CONTINUATION if message.is_some() => {
+                        let (_, data) = message.as_mut().unwrap();
+                        if (data.len() + payload.len()) as u64 > MAX_MESSAGE_SIZE {
+                            return Err(protocol_error("Message too large"));
+                        }
+                        data.extend_from_slice(&payload);
+                    }
+                    _ => return Err(protocol_error("Unexpected frame")),
+                }
+                if fin {
+                    break;
+                }
+            }
+            let mut next = [0u8; 1];
+            self.stream.read_exact(&mut next).map_err(io_error)?;
+            first = next[0];
+        }
+        Ok(match message {
+            Some((OPCODE_TEXT, data)) => Some(Message::Text(
+                String::from_utf8(data).map_err(|_| protocol_error("Invalid UTF-8"))?,
+            )),
+            Some((_, data)) => Some(Message::Binary(data)),
+            None => None,
+        })
+    }
+
+    pub fn send_text(&mut self, text: &str) -> error::Result<()> {
+        self.write_frame(OPCODE_TEXT, text.as_bytes())
+    }
+
+    pub fn send_ping(&mut self, payload: &[u8]) -> error::Result<()> {
+        self.write_frame(OPCODE_PING, payload)
+    }
+
+    /// Tells the other side that we're going away, and closes the socket.
+    pub fn close(mut self) {
+        // 1000 is a normal closure.
+        let _ = self.write_frame(OPCODE_CLOSE, &1000u16.to_be_bytes());
+        let _ = self.stream.tcp().shutdown(std::net::Shutdown::Both);
+    }
+
+    /// Reads the rest of a frame that starts with `first`, and returns its
+    /// FIN bit, opcode, and unmasked payload.
+    fn read_frame(&mut self, first: u8) -> error::Result<(bool, u8, Vec<u8>)> {
+        if first & 0x70 != 0 {
+            return Err(protocol_error("Unexpected reserved bits"));
+        }
+        let fin = first & 0x80 != 0;
+        let opcode = first & 0x0F;
+        let mut second = [0u8; 1];
+        self.stream.read_exact(&mut second).map_err(io_error)?;
+        let masked = second[0] & 0x80 != 0;
+        if masked == self.is_client {
+            return Err(protocol_error("Unexpected frame masking"));
+        }
+        let len = match second[0] & 0x7F {
+            126 => {
+                let mut len = [0u8; 2];
+                self.stream.read_exact(&mut len).map_err(io_error)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                self.stream.read_exact(&mut len).map_err(io_error)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        if len > MAX_MESSAGE_SIZE {
+            return Err(protocol_error("Message too large"));
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            self.stream.read_exact(&mut mask).map_err(io_error)?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        self.stream.read_exact(&mut payload).map_err(io_error)?;
+        if masked {
+            apply_mask(&mut payload, mask);
+        }
+        Ok((fin, opcode, payload))
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> error::Result<()> {
+        self.write_fragment(true, opcode, payload)
+    }
+
+    /// Writes a frame that's part of a message if `fin` is false, or the
+    /// last (or only) frame of a message if it's true.
+    fn write_fragment(&mut self, fin: bool, opcode: u8, payload: &[u8]) -> error::Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(if fin { 0x80 } else { 0 } | opcode);
+        let mask_bit = if self.is_client { 0x80 } else { 0 };
+        if payload.len() < 126 {
+            frame.push(mask_bit | payload.len() as u8);
+        } else if payload.len() <= usize::from(u16::max_value()) {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        } else {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        }
+        if self.is_client {
+            let mut mask = [0u8; 4];
+            rand::fill(&mut mask).map_err(|e| CommunicationError(e.to_string()))?;
+            frame.extend_from_slice(&mask);
+            let start = frame.len();
+            frame.extend_from_slice(payload);
+            apply_mask(&mut frame[start..], mask);
+        } else {
+            frame.extend_from_slice(payload);
+        }
+        self.stream.write_all(&frame).map_err(io_error)?;
+        self.stream.flush().map_err(io_error)
+    }
+
+    /// Answers the handshake for a client connection, so that tests can
+    /// stand in for the push server.
+    #[cfg(test)]
+    pub fn accept(tcp: TcpStream, protocol: &str) -> error::Result<Self> {
+        tcp.set_read_timeout(Some(IO_TIMEOUT)).map_err(io_error)?;
+        let mut stream = Stream::Plain(tcp);
+        let request = read_headers(&mut stream)?;
+        let headers = parse_headers(request.lines().skip(1));
+        let key = header(&headers, "sec-websocket-key")
+            .ok_or_else(|| protocol_error("Missing Sec-WebSocket-Key"))?;
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\
+             Sec-WebSocket-Protocol: {}\r\n\
+             \r\n",
+            accept_key(key)?,
+            protocol
+        );
+        stream.write_all(response.as_bytes()).map_err(io_error)?;
+        Ok(WebSocket {
+            stream,
+            is_client: false,
+        })
+    }
+}
+
+fn open_tcp(host: &str, port: u16, timeout: Duration) -> error::Result<TcpStream> {
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| CommunicationError(format!("Could not resolve {}: {}", host, e)))?;
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_nodelay(true).map_err(io_error)?;
+                stream
+                    .set_write_timeout(Some(IO_TIMEOUT))
+                    .map_err(io_error)?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) => CommunicationError(format!("Could not connect to {}: {}", host, e)).into(),
+        None => CommunicationError(format!("No addresses for {}", host)).into(),
+    })
+}
+
+/// Reads an HTTP message up to the blank line after its headers. We read a
+/// byte at a time, so that we don't consume any frames that follow.
+fn read_headers(stream: &mut Stream) -> error::Result<String> {
+    let mut buf = Vec::new();
+    let mut byte = [0u8; 1];
+    while !buf.ends_with(b"\r\n\r\n") {
+        if buf.len() >= MAX_HANDSHAKE_SIZE {
+            return Err(protocol_error("Handshake too large"));
+        }
+        if stream.read(&mut byte).map_err(io_error)? == 0 {
+            return Err(closed());
+        }
+        buf.push(byte[0]);
+    }
+    String::from_utf8(buf).map_err(|_| protocol_error("Invalid handshake"))
+}
+
+/// Splits header lines into lowercased names and trimmed values.
+fn parse_headers<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<(String, &'a str)> {
+    lines
+        .filter_map(|line| {
+            let colon = line.find(':')?;
+            Some((
+                line[..colon].trim().to_ascii_lowercase(),
+                line[colon + 1..].trim(),
+            ))
+        })
+        .collect()
+}
+
+fn header<'a>(headers: &[(String, &'a str)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name == name)
+        .map(|(_, value)| *value)
+}
+
+fn accept_key(key: &str) -> error::Result<String> {
+    let hash = sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes());
+    Ok(base64::encode(&hash))
+}
+
+/// SHA-1, as described in RFC 3174. The handshake is the only thing that
+/// uses it, and it isn't there for security, so we implement it here instead
+/// of exposing a broken hash from `rc_crypto`.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+    let mut hash = [0u8; 20];
+    for (bytes, state) in hash.chunks_mut(4).zip(&h) {
+        bytes.copy_from_slice(&state.to_be_bytes());
+    }
+    hash
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    // Unix reports read timeouts as `WouldBlock`, and Windows as `TimedOut`.
+    e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+fn io_error(e: io::Error) -> error::Error {
+    CommunicationError(format!("WebSocket I/O error: {}", e)).into()
+}
+
+fn closed() -> error::Error {
+    CommunicationError("WebSocket closed".to_owned()).into()
+}
+
+fn protocol_error(message: &str) -> error::Error {
+    CommunicationServerError(format!("WebSocket protocol error: {}", message)).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // Autopush only accepts `wss` connections, so the default build needs to
+    // support them. We can't finish a TLS handshake without a certificate,
+    // but a server that hangs up should fail the handshake, instead of the
+    // `wss` scheme being refused before we connect.
+    #[test]
+    fn test_default_features_support_wss() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            drop(tcp);
+        });
+        let url = Url::parse(&format!("wss://127.0.0.1:{}/", port)).unwrap();
+        let err = match WebSocket::connect(&url, "push-notification", TIMEOUT) {
+            Ok(_) => panic!("Shouldn't connect without TLS on the server"),
+            Err(err) => err.to_string(),
+        };
+        assert!(
+            err.contains("TLS handshake failed"),
+            "Should try a TLS handshake: {}",
+            err
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // The example from RFC 6455, section 1.3.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==").unwrap(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_sha1() {
+        // Test vectors from RFC 3174, section 7.3, and FIPS 180-2.
+        assert_eq!(
+            hex::encode(sha1(b"")),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+        assert_eq!(
+            hex::encode(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex::encode(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(
+            hex::encode(sha1("a".repeat(1_000_000).as_bytes())),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+
+    #[test]
+    fn test_control_frames_between_fragments() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("ws://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let mut socket = WebSocket::accept(tcp, "test").unwrap();
+            socket
+                .write_fragment(false, OPCODE_TEXT, b"Hello, ")
+                .unwrap();
+            socket.write_frame(OPCODE_PING, b"ping").unwrap();
+            socket
+                .write_fragment(false, OPCODE_CONTINUATION, b"fragmented ")
+                .unwrap();
+            socket.write_frame(OPCODE_PONG, b"pong").unwrap();
+            socket
+                .write_fragment(true, OPCODE_CONTINUATION, b"world")
+                .unwrap();
+            // The client answers the ping while it's reading the message.
+            assert_eq!(
+                socket.read(TIMEOUT).unwrap(),
+                Some(Message::Pong(b"ping".to_vec()))
+            );
+            assert_eq!(socket.read(TIMEOUT).unwrap(), Some(Message::Close));
+        });
+
+        let mut socket = WebSocket::connect(&url, "test", TIMEOUT).unwrap();
+        assert_eq!(
+            socket.read(TIMEOUT).unwrap(),
+            Some(Message::Text("Hello, fragmented world".to_owned()))
+        );
+        socket.close();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("ws://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let mut socket = WebSocket::accept(tcp, "test").unwrap();
+            // Echo text messages until the client closes.
+            loop {
+                match socket.read(TIMEOUT).unwrap() {
+                    Some(Message::Text(text)) => socket.send_text(&text).unwrap(),
+                    Some(Message::Ping(_)) => (),
+                    Some(Message::Close) => break,
+                    other => panic!("Unexpected message {:?}", other),
+                }
+            }
+        });
+
+        let mut socket = WebSocket::connect(&url, "test", TIMEOUT).unwrap();
+        // Exercise all three payload length encodings.
+        for len in &[5, 300, 70_000] {
+            let text = "x".repeat(*len);
+            socket.send_text(&text).unwrap();
+            assert_eq!(socket.read(TIMEOUT).unwrap(), Some(Message::Text(text)));
+        }
+        socket.send_ping(b"hi").unwrap();
+        assert_eq!(
+            socket.read(TIMEOUT).unwrap(),
+            Some(Message::Pong(b"hi".to_vec()))
+        );
+        assert_eq!(socket.read(Duration::from_millis(10)).unwrap(), None);
+        socket.close();
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,917 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Connect to the Autopush server over a WebSocket, like desktop Firefox does.
+//!
+//! This is for platforms without an OS push service. Instead of bridging
+//! through FCM or APNs, we keep a connection open to the push server, and it
+//! sends us notifications directly. See
+//! https://autopush.readthedocs.io/en/latest/protocol.html for the protocol.
+//!
+//! The connection is opened lazily, and reopened when it drops. Failed
+//! attempts back off exponentially, starting at `INITIAL_RETRY_INTERVAL` and
+//! doubling up to the ping interval. Embedders should call
+//! `read_notifications` in a loop, which also keeps the connection alive, and
+//! `ack` the notifications that it returns.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_derive::*;
+use serde_json::{json, Value};
+use url::Url;
+
+use super::socket::{Message, WebSocket};
+use super::{AckCode, BroadcastValue, Connection, RegisterResponse};
+use crate::config::PushConfiguration;
+use crate::error::{
+    self,
+    ErrorKind::{AlreadyRegisteredError, CommunicationError, CommunicationServerError},
+};
+use crate::storage::Store;
+
+/// The WebSocket subprotocol that Autopush speaks.
+const PUSH_PROTOCOL: &str = "push-notification";
+
+/// How long we wait for the server to answer a request or a ping before we
+/// give up on the connection.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long we wait to reconnect after the first failed attempt.
+const INITIAL_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A notification from the push server. The payload, if there is one, is
+/// still encrypted.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct IncomingNotification {
+    #[serde(rename = "channelID")]
+    pub channel_id: String,
+
+    /// Identifies the message when we acknowledge it.
+    pub version: String,
+
+    /// The base64url-encoded, encrypted payload.
+    #[serde(default)]
+    pub data: Option<String>,
+
+    /// The encryption parameters for `data`.
+    #[serde(default)]
+    pub headers: Option<NotificationHeaders>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct NotificationHeaders {
+    /// The content encoding: "aes128gcm", or the older "aesgcm".
+    pub encoding: Option<String>,
+    /// The `Encryption` header, with the salt for "aesgcm".
+    pub encryption: Option<String>,
+    /// The `Crypto-Key` header, with the sender's public key for "aesgcm".
+    pub crypto_key: Option<String>,
+    pub encryption_key: Option<String>,
+}
+
+/// Connect to the Autopush server via the WebSocket interface
+pub struct ConnectWebSocket {
+    pub options: PushConfiguration,
+    url: Url,
+    state: Mutex<State>,
+}
+
+/// Everything that changes as we talk to the server. The `Connection` trait
+/// takes `&self` for most methods, so this lives behind a lock.
+struct State {
+    uaid: Option<String>,
+    /// Set when the server gives us a new UAID, which means that it's
+    /// forgotten our subscriptions.
+    uaid_reset: bool,
+    /// The broadcast IDs that we're subscribed to, and the last version that
+    /// we saw for each.
+    broadcasts: HashMap<String, String>,
+    socket: Option<WebSocket>,
+    /// Notifications that arrived while we were waiting for something else.
+    pending: VecDeque<IncomingNotification>,
+    /// The number of connection attempts that have failed in a row.
+    failures: u32,
+    retry_at: Option<Instant>,
+    last_activity: Instant,
+    ping_sent: Option<Instant>,
+}
+
+/// The replies to our requests. Notifications and broadcasts can arrive at
+/// any time, so we handle those as we read them.
+enum Reply {
+    Hello {
+        uaid: String,
+        status: u16,
+    },
+    Register {
+        channel_id: String,
+        status: u16,
+        endpoint: Option<String>,
+    },
+    Unregister {
+        channel_id: String,
+        status: u16,
+    },
+    Ping,
+}
+
+/// Connect to the Autopush server over a WebSocket. This doesn't open the
+/// connection yet; that happens on the first request.
+pub fn connect_websocket(
+    options: PushConfiguration,
+    uaid: Option<String>,
+) -> error::Result<ConnectWebSocket> {
+    let protocol = match options.socket_protocol.as_deref() {
+        Some(protocol @ "ws") | Some(protocol @ "wss") => protocol,
+        Some(protocol) => {
+            return Err(CommunicationError(format!("Unsupported protocol {}", protocol)).into())
+        }
+        None => return Err(CommunicationError("No socket protocol set".to_owned()).into()),
+    };
+    if options.http_protocol.is_some() {
+        return Err(
+            CommunicationError("Both socket and HTTP protocols cannot be set.".to_owned()).into(),
+        );
+    }
+    let url = Url::parse(&format!("{}://{}/", protocol, options.server_host))?;
+    Ok(ConnectWebSocket {
+        options,
+        url,
+        state: Mutex::new(State {
+            uaid,
+            uaid_reset: false,
+            broadcasts: HashMap::new(),
+            socket: None,
+            pending: VecDeque::new(),
+            failures: 0,
+            retry_at: None,
+            last_activity: Instant::now(),
+            ping_sent: None,
+        }),
+    })
+}
+
+impl ConnectWebSocket {
+    /// Closes the connection. It'll be reopened by the next request.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(socket) = state.socket.take() {
+            socket.close();
+        }
+    }
+
+    fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.options.ping_interval)
+    }
+
+    /// Connects if we aren't already, unless we're still backing off from a
+    /// failed attempt.
+    fn ensure_connected(&self, state: &mut State) -> error::Result<()> {
+        if state.socket.is_some() {
+            return Ok(());
+        }
+        if let Some(retry_at) = state.retry_at {
+            let now = Instant::now();
+            if retry_at > now {
+                return Err(CommunicationError(format!(
+                    "Waiting {}s to reconnect",
+                    (retry_at - now).as_secs()
+                ))
+                .into());
+            }
+        }
+        self.open(state)
+    }
+
+    fn open(&self, state: &mut State) -> error::Result<()> {
+        match self.hello(state) {
+            Ok(()) => {
+                state.failures = 0;
+                state.retry_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                state.disconnect();
+                state.failures += 1;
+                let interval = retry_interval(state.failures, self.ping_interval());
+                log::warn!(
+                    "Couldn't connect to push server; retrying in {}s: {}",
+                    interval.as_secs(),
+                    e
+                );
+                state.retry_at = Some(Instant::now() + interval);
+                Err(e)
+            }
+        }
+    }
+
+    fn hello(&self, state: &mut State) -> error::Result<()> {
+        log::debug!("Connecting to {}", self.url);
+        state.socket = Some(WebSocket::connect(
+            &self.url,
+            PUSH_PROTOCOL,
+            REQUEST_TIMEOUT,
+        )?);
+        state.last_activity = Instant::now();
+        state.ping_sent = None;
+        let mut message = json!({
+            "messageType": "hello",
+            "use_webpush": true,
+            "broadcasts": state.broadcasts,
+        });
+        if let Some(uaid) = &state.uaid {
+            message["uaid"] = json!(uaid);
+        }
+        state.send_text(&message.to_string())?;
+        let (uaid, status) = state.wait_for(|reply| match reply {
+            Reply::Hello { uaid, status } => Some((uaid, status)),
+            _ => None,
+        })?;
+        if status != 200 {
+            return Err(CommunicationServerError(format!("Hello failed: {}", status)).into());
+        }
+        if state.uaid.is_some() && state.uaid.as_deref() != Some(&*uaid) {
+            log::warn!("Push server assigned a new UAID; subscriptions were dropped");
+            state.uaid_reset = true;
+        }
+        state.uaid = Some(uaid);
+        Ok(())
+    }
+}
+
+impl State {
+    fn disconnect(&mut self) {
+        self.socket = None;
+        self.ping_sent = None;
+    }
+
+    fn send_text(&mut self, text: &str) -> error::Result<()> {
+        let socket = self
+            .socket
+            .as_mut()
+            .ok_or_else(|| CommunicationError("Not connected".to_owned()))?;
+        let result = socket.send_text(text);
+        if result.is_err() {
+            self.disconnect();
+        }
+        result
+    }
+
+    /// Sends a request, and waits for the server's reply.
+    fn request<T>(
+        &mut self,
+        message: &Value,
+        matches: impl FnMut(Reply) -> Option<T>,
+    ) -> error::Result<T> {
+        self.send_text(&message.to_string())?;
+        self.wait_for(matches)
+    }
+
+    /// Reads messages until `matches` picks out a reply, or the server takes
+    /// too long to send one.
+    fn wait_for<T>(&mut self, mut matches: impl FnMut(Reply) -> Option<T>) -> error::Result<T> {
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some(result) = self.read(deadline)?.and_then(&mut matches) {
+                return Ok(result);
+            }
+        }
+        self.disconnect();
+        Err(CommunicationServerError("Timed out waiting for the push server".to_owned()).into())
+    }
+
+    /// Reads one message, waiting until `until` at the latest. Notifications
+    /// and broadcasts are handled here; other replies are returned. If the
+    /// connection fails, we drop it, so that the next request reconnects.
+    fn read(&mut self, until: Instant) -> error::Result<Option<Reply>> {
+        let socket = match self.socket.as_mut() {
+            Some(socket) => socket,
+            None => return Err(CommunicationError("Not connected".to_owned()).into()),
+        };
+        let timeout = until.saturating_duration_since(Instant::now());
+        let text = match socket.read(timeout) {
+            Ok(Some(Message::Text(text))) => text,
+            Ok(Some(Message::Close)) => {
+                self.disconnect();
+                return Err(CommunicationError("Push server closed the connection".into()).into());
+            }
+            Ok(Some(_)) => {
+                self.last_activity = Instant::now();
+                return Ok(None);
+            }
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                self.disconnect();
+                return Err(e);
+            }
+        };
+        self.last_activity = Instant::now();
+        let message: Value = match serde_json::from_str(&text) {
+            Ok(message) => message,
+            Err(e) => {
+                log::warn!("Ignoring invalid message from push server: {}", e);
+                return Ok(None);
+            }
+        };
+        self.handle_message(message)
+    }
+
+    fn handle_message(&mut self, message: Value) -> error::Result<Option<Reply>> {
+        // The server answers pings with an empty object.
+        if message
+            .as_object()
+            .map_or(false, |fields| fields.is_empty())
+        {
+            return Ok(Some(Reply::Ping));
+        }
+        let status = message["status"].as_u64().unwrap_or(0) as u16;
+        let channel_id = message["channelID"]
+            .as_str()
+            .map(Store::normalize_uuid)
+            .unwrap_or_default();
+        let message_type = message["messageType"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+        let reply = match message_type.as_str() {
+            "hello" => {
+                self.update_broadcasts(&message["broadcasts"]);
+                Reply::Hello {
+                    uaid: message["uaid"].as_str().unwrap_or_default().to_owned(),
+                    status,
+                }
+            }
+            "register" => Reply::Register {
+                channel_id,
+                status,
+                endpoint: message["pushEndpoint"].as_str().map(ToOwned::to_owned),
+            },
+            "unregister" => Reply::Unregister { channel_id, status },
+            "ping" => Reply::Ping,
+            "broadcast" => {
+                self.update_broadcasts(&message["broadcasts"]);
+                return Ok(None);
+            }
+            "notification" => {
+                match serde_json::from_value::<IncomingNotification>(message) {
+                    Ok(mut notification) => {
+                        notification.channel_id = channel_id;
+                        self.pending.push_back(notification);
+                    }
+                    Err(e) => log::warn!("Ignoring invalid notification: {}", e),
+                }
+                return Ok(None);
+            }
+            message_type => {
+                log::debug!("Ignoring {:?} message from push server", message_type);
+                return Ok(None);
+            }
+        };
+        Ok(Some(reply))
+    }
+
+    /// Records new broadcast versions from the server. The server reports
+    /// broadcasts that it doesn't know about in a nested `errors` object, and
+    /// can report ones that we've unsubscribed from until we reconnect.
+    fn update_broadcasts(&mut self, broadcasts: &Value) {
+        let broadcasts = match broadcasts.as_object() {
+            Some(broadcasts) => broadcasts,
+            None => return,
+        };
+        for (id, version) in broadcasts {
+            match (self.broadcasts.get_mut(id), version.as_str()) {
+                (Some(saved), Some(version)) => *saved = version.to_owned(),
+                (None, Some(_)) => log::debug!("Ignoring unsubscribed broadcast {}", id),
+                (_, None) => log::warn!("Push server reported broadcast {}: {}", id, version),
+            }
+        }
+    }
+}
+
+impl Connection for ConnectWebSocket {
+    /// The UAID that the server assigned to us, if we've connected.
+    fn uaid(&self) -> Option<String> {
+        self.state.lock().unwrap().uaid.clone()
+    }
+
+    /// send a new subscription request to the server, get back the server registration response.
+    fn subscribe(
+        &mut self,
+        channel_id: &str,
+        app_server_key: Option<&str>,
+    ) -> error::Result<RegisterResponse> {
+        let mut state = self.state.lock().unwrap();
+        self.ensure_connected(&mut state)?;
+        let mut message = json!({
+            "messageType": "register",
+            "channelID": channel_id,
+        });
+        if let Some(key) = app_server_key {
+            message["key"] = json!(key);
+        }
+        let normalized_id = Store::normalize_uuid(channel_id);
+        let (status, endpoint) = state.request(&message, |reply| match reply {
+            Reply::Register {
+                channel_id,
+                status,
+                endpoint,
+            } if channel_id == normalized_id => Some((status, endpoint)),
+            _ => None,
+        })?;
+        match (status, endpoint) {
+            (200, Some(endpoint)) => Ok(RegisterResponse {
+                uaid: state.uaid.clone().unwrap(),
+                channel_id: normalized_id,
+                secret: None,
+                endpoint,
+                senderid: None,
+            }),
+            (409, _) => Err(AlreadyRegisteredError.into()),
+            (status, _) => {
+                Err(CommunicationServerError(format!("Register failed: {}", status)).into())
+            }
+        }
+    }
+
+    /// Drop a channel and stop receiving updates. Without a channel, this
+    /// drops the UAID, so that the server forgets all our channels, and gives
+    /// us a new UAID when we reconnect.
+    fn unsubscribe(&self, channel_id: Option<&str>) -> error::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        let channel_id = match channel_id {
+            Some(channel_id) => channel_id,
+            None => {
+                state.uaid = None;
+                if let Some(socket) = state.socket.take() {
+                    socket.close();
+                }
+                return Ok(true);
+            }
+        };
+        self.ensure_connected(&mut state)?;
+        let message = json!({
+            "messageType": "unregister",
+            "channelID": channel_id,
+        });
+        let normalized_id = Store::normalize_uuid(channel_id);
+        let status = state.request(&message, |reply| match reply {
+            Reply::Unregister { channel_id, status } if channel_id == normalized_id => Some(status),
+            _ => None,
+        })?;
+        if status != 200 {
+            return Err(CommunicationServerError(format!("Unregister failed: {}", status)).into());
+        }
+        Ok(true)
+    }
+
+    /// WebSocket connections don't go through an OS push service, so there's
+    /// no token to update.
+    fn update(&mut self, _new_token: &str) -> error::Result<bool> {
+        Err(CommunicationError("WebSocket connections don't use a native token".to_owned()).into())
+    }
+
+    /// The WebSocket protocol doesn't have a way to ask for our channels.
+    fn channel_list(&self) -> error::Result<Vec<String>> {
+        Err(CommunicationError("Unsupported".to_string()).into())
+    }
+
+    /// Verify that the server still knows about our channels. The server
+    /// checks our UAID when we connect, and gives us a new one if it's lost
+    /// our channels, so this returns `false` if that's happened since the
+    /// last time we checked.
+    fn verify_connection(&self, _channels: &[String]) -> error::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        self.ensure_connected(&mut state)?;
+        Ok(!std::mem::take(&mut state.uaid_reset))
+    }
+
+    /// Add one or more new broadcast subscriptions.
+    fn broadcast_subscribe(&self, broadcast: BroadcastValue) -> error::Result<BroadcastValue> {
+        let new_broadcasts = broadcast.into_versions()?;
+        let mut state = self.state.lock().unwrap();
+        state.broadcasts.extend(new_broadcasts.clone());
+        // If we're not connected, we'll send them with our next hello.
+        if state.socket.is_some() {
+            let message = json!({
+                "messageType": "broadcast_subscribe",
+                "broadcasts": new_broadcasts,
+            });
+            state.send_text(&message.to_string())?;
+        }
+        Ok(BroadcastValue::from(&state.broadcasts))
+    }
+
+    /// Forgets a broadcast, so that we don't send it with our next hello.
+    /// The protocol doesn't have a way to unsubscribe, so the server may
+    /// keep telling us about it until we reconnect.
+    fn broadcast_unsubscribe(&self, broadcast_id: &str) -> error::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        Ok(state.broadcasts.remove(broadcast_id).is_some())
+    }
+
+    /// get the list of broadcasts
+    fn broadcasts(&self) -> error::Result<BroadcastValue> {
+        Ok(BroadcastValue::from(&self.state.lock().unwrap().broadcasts))
+    }
+
+    /// Waits up to `timeout` for notifications, and returns the ones that
+    /// arrived. This keeps the connection alive while it waits: it pings the
+    /// server when the connection is idle, and reconnects if it drops.
+    ///
+    /// An empty list means that nothing arrived in time. Errors mean that
+    /// we couldn't reconnect; calling this again will wait out the backoff
+    /// before trying again.
+    ///
+    /// Other requests wait for this to return, so embedders that make them
+    /// from other threads should use short timeouts.
+    fn read_notifications(&self, timeout: Duration) -> error::Result<Vec<IncomingNotification>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if !state.pending.is_empty() {
+                return Ok(state.pending.drain(..).collect());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(Vec::new());
+            }
+            if state.socket.is_none() {
+                if let Some(retry_at) = state.retry_at {
+                    if retry_at > now {
+                        // Don't hold the lock while we sleep, so that other
+                        // requests can fail fast instead of waiting for us.
+                        drop(state);
+                        thread::sleep(retry_at.min(deadline) - now);
+                        state = self.state.lock().unwrap();
+                        continue;
+                    }
+                }
+                self.open(&mut state)?;
+                continue;
+            }
+            if let Some(ping_sent) = state.ping_sent {
+                if now >= ping_sent + REQUEST_TIMEOUT {
+                    log::warn!("Push server didn't answer our ping; reconnecting");
+                    state.disconnect();
+                    continue;
+                }
+            } else if now >= state.last_activity + self.ping_interval() {
+                if let Err(e) = state.send_text("{}") {
+                    log::info!("Lost connection to push server; reconnecting: {}", e);
+                    continue;
+                }
+                state.ping_sent = Some(now);
+            }
+            let wake_at = match state.ping_sent {
+                Some(ping_sent) => ping_sent + REQUEST_TIMEOUT,
+                None => state.last_activity + self.ping_interval(),
+            };
+            match state.read(wake_at.min(deadline)) {
+                Ok(Some(Reply::Ping)) => state.ping_sent = None,
+                Ok(_) => (),
+                Err(e) => log::info!("Lost connection to push server; reconnecting: {}", e),
+            }
+        }
+    }
+
+    /// Tells the server that we've handled a notification. If we've
+    /// disconnected since it arrived, there's no need: the server will send
+    /// it again when we reconnect.
+    fn ack(&self, channel_id: &str, message_id: &str, code: AckCode) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.socket.is_none() {
+            log::debug!("Not connected; the server will resend {}", message_id);
+            return Ok(());
+        }
+        let message = json!({
+            "messageType": "ack",
+            "updates": [{
+                "channelID": channel_id,
+                "version": message_id,
+                "code": code.code(),
+            }],
+        });
+        state.send_text(&message.to_string())
+    }
+}
+
+/// How long to wait before reconnecting after `failures` failed attempts in
+/// a row. This doubles with each failure, up to `max`.
+fn retry_interval(failures: u32, max: Duration) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    (INITIAL_RETRY_INTERVAL * 2u32.pow(exponent)).min(max.max(INITIAL_RETRY_INTERVAL))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    const UAID: &str = "abad1dea00000000aabbccdd00000000";
+    const CHID: &str = "deadbeef00000000decafbad00000000";
+    const DASHED_CHID: &str = "deadbeef-0000-0000-deca-fbad00000000";
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// One side of a connection to the stand-in push server.
+    struct Peer(WebSocket);
+
+    impl Peer {
+        /// Returns the next message from the client.
+        fn expect(&mut self) -> Value {
+            loop {
+                match self.0.read(TIMEOUT).unwrap() {
+                    Some(Message::Text(text)) => return serde_json::from_str(&text).unwrap(),
+                    Some(Message::Ping(_)) => continue,
+                    other => panic!("Unexpected message {:?}", other),
+                }
+            }
+        }
+
+        fn expect_hello(&mut self, uaid: &str) -> Value {
+            let hello = self.expect();
+            assert_eq!(hello["messageType"], "hello");
+            assert_eq!(hello["use_webpush"], true);
+            self.send(json!({
+                "messageType": "hello",
+                "uaid": uaid,
+                "status": 200,
+                "use_webpush": true,
+                "broadcasts": {},
+            }));
+            hello
+        }
+
+        fn send(&mut self, message: Value) {
+            self.0.send_text(&message.to_string()).unwrap();
+        }
+    }
+
+    type Handler = Box<dyn FnOnce(&mut Peer) + Send>;
+
+    /// Starts a stand-in push server that accepts one connection for each
+    /// handler, in order, and closes it when the handler returns.
+    fn serve(handlers: Vec<Handler>) -> (ConnectWebSocket, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = PushConfiguration {
+            server_host: listener.local_addr().unwrap().to_string(),
+            socket_protocol: Some("ws".to_owned()),
+            http_protocol: None,
+            bridge_type: None,
+            registration_id: None,
+            ..Default::default()
+        };
+        let server = thread::spawn(move || {
+            for handler in handlers {
+                let (tcp, _) = listener.accept().unwrap();
+                let mut peer = Peer(WebSocket::accept(tcp, PUSH_PROTOCOL).unwrap());
+                handler(&mut peer);
+            }
+        });
+        (connect_websocket(config, None).unwrap(), server)
+    }
+
+    fn notification(version: &str) -> Value {
+        json!({
+            "messageType": "notification",
+            "channelID": DASHED_CHID,
+            "version": version,
+            "data": "LsuUOBKVQRY6-l7_Ajo-Ag",
+            "headers": {
+                "encoding": "aes128gcm",
+            },
+        })
+    }
+
+    #[test]
+    fn test_subscribe_and_notify() {
+        let (mut conn, server) = serve(vec![Box::new(|peer: &mut Peer| {
+            let hello = peer.expect_hello(UAID);
+            assert!(hello.get("uaid").is_none());
+
+            let register = peer.expect();
+            assert_eq!(register["messageType"], "register");
+            assert_eq!(register["channelID"], CHID);
+            assert_eq!(register["key"], "some-key");
+            // Notifications can arrive before the reply.
+            peer.send(notification("v1"));
+            peer.send(json!({
+                "messageType": "register",
+                "channelID": DASHED_CHID,
+                "status": 200,
+                "pushEndpoint": "https://push.example.com/wpush/v2/abc",
+            }));
+
+            let ack = peer.expect();
+            assert_eq!(
+                ack,
+                json!({
+                    "messageType": "ack",
+                    "updates": [{ "channelID": CHID, "version": "v1", "code": 100 }],
+                })
+            );
+
+            let unregister = peer.expect();
+            assert_eq!(unregister["messageType"], "unregister");
+            peer.send(json!({
+                "messageType": "unregister",
+                "channelID": DASHED_CHID,
+                "status": 200,
+            }));
+        })]);
+
+        let response = conn.subscribe(CHID, Some("some-key")).unwrap();
+        assert_eq!(response.uaid, UAID);
+        assert_eq!(response.channel_id, CHID);
+        assert_eq!(response.endpoint, "https://push.example.com/wpush/v2/abc");
+        assert_eq!(conn.uaid().as_deref(), Some(UAID));
+
+        let notifications = conn.read_notifications(TIMEOUT).unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].channel_id, CHID);
+        assert_eq!(notifications[0].version, "v1");
+        assert_eq!(
+            notifications[0]
+                .headers
+                .as_ref()
+                .unwrap()
+                .encoding
+                .as_deref(),
+            Some("aes128gcm")
+        );
+        conn.ack(CHID, "v1", AckCode::Delivered).unwrap();
+
+        assert!(conn.unsubscribe(Some(CHID)).unwrap());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let (conn, server) = serve(vec![
+            Box::new(|peer: &mut Peer| {
+                peer.expect_hello(UAID);
+                // Drop the connection.
+            }),
+            Box::new(|peer: &mut Peer| {
+                let hello = peer.expect_hello(UAID);
+                assert_eq!(hello["uaid"], UAID);
+                peer.send(notification("v2"));
+                // Wait for the client to close.
+                assert_eq!(peer.0.read(TIMEOUT).unwrap(), Some(Message::Close));
+            }),
+        ]);
+
+        assert!(conn.verify_connection(&[]).unwrap());
+        // The first connection drops while we're waiting, so we reconnect
+        // right away, and get the notification from the second one.
+        let notifications = conn.read_notifications(TIMEOUT).unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].version, "v2");
+        assert!(conn.verify_connection(&[]).unwrap());
+        conn.close();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_uaid_reset() {
+        let (conn, server) = serve(vec![
+            Box::new(|peer: &mut Peer| {
+                peer.expect_hello(UAID);
+            }),
+            Box::new(|peer: &mut Peer| {
+                peer.expect_hello("feedface00000000aabbccdd00000000");
+                assert_eq!(peer.0.read(TIMEOUT).unwrap(), Some(Message::Close));
+            }),
+        ]);
+
+        assert!(conn.verify_connection(&[]).unwrap());
+        // Reconnect after the first connection drops.
+        assert!(conn
+            .read_notifications(Duration::from_millis(500))
+            .unwrap()
+            .is_empty());
+        assert!(!conn.verify_connection(&[]).unwrap());
+        assert!(conn.verify_connection(&[]).unwrap());
+        assert_eq!(
+            conn.uaid().as_deref(),
+            Some("feedface00000000aabbccdd00000000")
+        );
+        conn.close();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_ping_and_broadcasts() {
+        let (mut conn, server) = serve(vec![Box::new(|peer: &mut Peer| {
+            let hello = peer.expect_hello(UAID);
+            assert_eq!(
+                hello["broadcasts"],
+                json!({ "remote-settings/monitor_changes": "v0" })
+            );
+
+            let ping = peer.expect();
+            assert_eq!(ping, json!({}));
+            peer.send(json!({
+                "messageType": "broadcast",
+                "broadcasts": { "remote-settings/monitor_changes": "v1" },
+            }));
+            peer.send(json!({}));
+
+            let subscribe = peer.expect();
+            assert_eq!(
+                subscribe,
+                json!({
+                    "messageType": "broadcast_subscribe",
+                    "broadcasts": { "other": "v5" },
+                })
+            );
+        })]);
+        conn.options.ping_interval = 1;
+
+        let mut broadcasts = HashMap::new();
+        broadcasts.insert(
+            "remote-settings/monitor_changes".to_owned(),
+            BroadcastValue::Value("v0".to_owned()),
+        );
+        conn.broadcast_subscribe(BroadcastValue::Nested(broadcasts))
+            .unwrap();
+        // Connecting sends the hello, then we ping once the connection has
+        // been idle for a second.
+        assert!(conn.verify_connection(&[]).unwrap());
+        assert!(conn
+            .read_notifications(Duration::from_millis(1500))
+            .unwrap()
+            .is_empty());
+
+        let mut broadcasts = HashMap::new();
+        broadcasts.insert("other".to_owned(), BroadcastValue::Value("v5".to_owned()));
+        let all = match conn
+            .broadcast_subscribe(BroadcastValue::Nested(broadcasts))
+            .unwrap()
+        {
+            BroadcastValue::Nested(all) => all,
+            BroadcastValue::Value(_) => panic!("Should return all broadcasts"),
+        };
+        assert_eq!(all.len(), 2);
+        match &all["remote-settings/monitor_changes"] {
+            BroadcastValue::Value(version) => assert_eq!(version, "v1"),
+            BroadcastValue::Nested(_) => panic!("Should update the version"),
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_broadcast_unsubscribe() {
+        let (conn, server) = serve(vec![Box::new(|peer: &mut Peer| {
+            let hello = peer.expect_hello(UAID);
+            assert_eq!(
+                hello["broadcasts"],
+                json!({ "remote-settings/monitor_changes": "v0" })
+            );
+            // The server doesn't know that we've unsubscribed.
+            peer.send(json!({
+                "messageType": "broadcast",
+                "broadcasts": { "other": "v6" },
+            }));
+            assert_eq!(peer.0.read(TIMEOUT).unwrap(), Some(Message::Close));
+        })]);
+
+        let mut broadcasts = HashMap::new();
+        broadcasts.insert(
+            "remote-settings/monitor_changes".to_owned(),
+            "v0".to_owned(),
+        );
+        broadcasts.insert("other".to_owned(), "v5".to_owned());
+        conn.broadcast_subscribe(BroadcastValue::from(&broadcasts))
+            .unwrap();
+        assert!(conn.broadcast_unsubscribe("other").unwrap());
+        assert!(!conn.broadcast_unsubscribe("other").unwrap());
+
+        assert!(conn.verify_connection(&[]).unwrap());
+        assert!(conn
+            .read_notifications(Duration::from_millis(500))
+            .unwrap()
+            .is_empty());
+        broadcasts.remove("other");
+        assert_eq!(
+            conn.broadcasts().unwrap(),
+            BroadcastValue::from(&broadcasts)
+        );
+        conn.close();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_retry_interval() {
+        let max = Duration::from_secs(60);
+        assert_eq!(retry_interval(1, max), Duration::from_secs(5));
+        assert_eq!(retry_interval(2, max), Duration::from_secs(10));
+        assert_eq!(retry_interval(3, max), Duration::from_secs(20));
+        assert_eq!(retry_interval(5, max), max);
+        assert_eq!(retry_interval(100, max), max);
+    }
+}
@@ -113,7 +113,7 @@ pub fn get_bytes(size: usize) -> error::Result<Vec<u8>> {
 /// Sub values have the form of `label=value`. Due to a bug in some push providers, treat ',' and ';' as
 /// equivalent.
 /// @param string: the string to search,
-fn extract_value(string: Option<&str>, target: &str) -> Option<Vec<u8>> {
+pub(crate) fn extract_value(string: Option<&str>, target: &str) -> Option<Vec<u8>> {
     if let Some(val) = string {
         if !val.contains(&format!("{}=", target)) {
             log::debug!("No sub-value found for {}", target);
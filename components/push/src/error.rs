@@ -56,6 +56,17 @@ pub enum ErrorKind {
     /// A failure to parse a URL.
     #[error("URL parse error: {0:?}")]
     UrlParseError(#[from] url::ParseError),
+
+    /// The push service no longer accepts messages for a subscription.
+    #[error("Subscription expired or unsubscribed: {0}")]
+    SubscriptionGoneError(String),
+
+    #[error("Push message payload too large")]
+    PayloadTooLargeError,
+
+    /// The push service wants us to slow down, optionally for a number of seconds.
+    #[error("Rate limited by the push service, retry after {0:?} seconds")]
+    RateLimitedError(Option<u64>),
 }
 
 // Note, be sure to duplicate errors in the Kotlin side
@@ -74,6 +85,10 @@ impl ErrorKind {
             ErrorKind::TranscodingError(_) => 31,
             ErrorKind::RecordNotFoundError(_, _) => 32,
             ErrorKind::UrlParseError(_) => 33,
+            // Only returned by `sender`, which isn't exposed over the FFI.
+            ErrorKind::SubscriptionGoneError(_) => 34,
+            ErrorKind::PayloadTooLargeError => 35,
+            ErrorKind::RateLimitedError(_) => 36,
         };
         ffi_support::ErrorCode::new(code)
     }
@@ -5,11 +5,14 @@
 #![allow(unknown_lints)]
 #![warn(rust_2018_idioms)]
 
+pub mod broadcasts;
 pub mod communications;
 pub mod config;
 pub mod crypto;
 pub mod error;
 pub mod ffi;
+pub mod notifier;
+pub mod sender;
 pub mod storage;
 pub mod subscriber;
 
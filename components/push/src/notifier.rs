@@ -5,19 +5,29 @@
 //! Process the incoming notification
 //!
 //! Workhorse function that handles incoming notifications, processing them into PushMessages,
-//! and distributing them to the appropriate handlers via the internal event bus.
+//! and distributing them to the handlers registered for their subscription's scope.
 //!
-//! Called from the Connection Manager.
+//! Called from the Connection Manager, which acks each notification with the outcome, so
+//! that the server can stop sending it.
 
-pub struct NotifierError;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use storage::ChannelID;
+use crate::communications::{AckCode, Connection, IncomingNotification};
+use crate::crypto::{extract_value, Crypto, Cryptography, Key};
+use crate::error::{self, ErrorKind};
+use crate::storage::{ChannelID, Storage};
+
+/// How many message IDs we remember, so that we can drop messages that the
+/// server delivers more than once.
+const MAX_SEEN_MESSAGES: usize = 500;
 
 /// Incoming WebPush Notification
 pub struct Notification {
     /// Associated channel ID
     pub channel_id: ChannelID,
-    /// Raw body of the incoming notification
+    /// Identifies the message, so that we can drop duplicates. Autopush calls this the "version".
+    pub message_id: String,
+    /// Raw body of the incoming notification. Empty if the notification doesn't have a payload.
     pub body: Vec<u8>,
     /// Encoding from Content-Encoding
     pub con: String,
@@ -27,19 +37,370 @@ pub struct Notification {
     pub dh: Option<Vec<u8>>,
 }
 
+impl Notification {
+    /// Decodes a notification from a WebSocket connection.
+    pub fn from_incoming(incoming: IncomingNotification) -> error::Result<Self> {
+        let headers = incoming.headers.unwrap_or_default();
+        let body = match incoming.data {
+            Some(data) => base64::decode_config(&data, base64::URL_SAFE_NO_PAD).map_err(|e| {
+                ErrorKind::TranscodingError(format!("Could not parse incoming body: {:?}", e))
+            })?,
+            None => Vec::new(),
+        };
+        Ok(Notification {
+            channel_id: incoming.channel_id,
+            message_id: incoming.version,
+            body,
+            con: headers.encoding.unwrap_or_default(),
+            salt: extract_value(headers.encryption.as_deref(), "salt"),
+            dh: extract_value(headers.crypto_key.as_deref(), "dh"),
+        })
+    }
+}
+
 /// Outbound Push Message
+#[derive(Clone, Debug, PartialEq)]
 pub struct PushMessage {
     pub channel_id: ChannelID,
+    /// The scope of the subscription that the message was sent to.
+    pub scope: String,
+    pub message_id: String,
+    /// The decrypted payload, or empty if the notification didn't have one.
     pub body: Vec<u8>,
 }
 
+/// Receives the messages sent to subscriptions for a scope.
+pub trait MessageHandler: Send {
+    fn handle_message(&self, message: &PushMessage) -> error::Result<()>;
+}
+
 pub trait Notifier {
     /// process notification, broadcast, etc.
-    fn process_notification(notification: Notification) -> Result<PushMessage, NotifierError>;
+    ///
+    /// Returns `None` if we've already processed a message with the same ID.
+    fn process_notification(
+        &mut self,
+        store: &dyn Storage,
+        notification: Notification,
+    ) -> error::Result<Option<PushMessage>>;
     // fetch sub data
     // decrypt the notification (if required)
     // route to proper handler (DOM, system)
     // the PushMessage result is handed off to the DOM or internal service
 }
 
-pub struct NotifHandler {}
+#[derive(Default)]
+pub struct NotifHandler {
+    handlers: HashMap<String, Box<dyn MessageHandler>>,
+    /// The IDs of the messages that we've delivered, and the order that we
+    /// delivered them in, so that we can forget the oldest ones.
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+}
+
+impl NotifHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler for messages sent to subscriptions with this
+    /// scope, replacing any handler that's already registered.
+    pub fn register_handler(&mut self, scope: &str, handler: Box<dyn MessageHandler>) {
+        self.handlers.insert(scope.to_owned(), handler);
+    }
+
+    /// Returns `true` if there was a handler for this scope.
+    pub fn unregister_handler(&mut self, scope: &str) -> bool {
+        self.handlers.remove(scope).is_some()
+    }
+
+    /// Processes a notification, and acks it on `conn` with the outcome.
+    /// Duplicates are acked as delivered, since we've already handled them.
+    ///
+    /// Failing to ack only means that the server will send the message again,
+    /// so we log it and still return what happened to the message.
+    pub fn handle_notification(
+        &mut self,
+        store: &dyn Storage,
+        conn: &dyn Connection,
+        notification: Notification,
+    ) -> error::Result<Option<PushMessage>> {
+        let channel_id = notification.channel_id.clone();
+        let message_id = notification.message_id.clone();
+        let result = self.process_notification(store, notification);
+        let code = match &result {
+            Ok(_) => AckCode::Delivered,
+            Err(e) => {
+                log::warn!("Couldn't deliver push message {}: {}", message_id, e);
+                match e.kind() {
+                    ErrorKind::CryptoError(_) | ErrorKind::TranscodingError(_) => {
+                        AckCode::DecryptionError
+                    }
+                    _ => AckCode::NotDelivered,
+                }
+            }
+        };
+        if let Err(e) = conn.ack(&channel_id, &message_id, code) {
+            log::warn!("Couldn't ack push message {}: {}", message_id, e);
+        }
+        result
+    }
+
+    fn mark_seen(&mut self, message_id: &str) {
+        if !self.seen.insert(message_id.to_owned()) {
+            return;
+        }
+        self.seen_order.push_back(message_id.to_owned());
+        if self.seen_order.len() > MAX_SEEN_MESSAGES {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl Notifier for NotifHandler {
+    fn process_notification(
+        &mut self,
+        store: &dyn Storage,
+        notification: Notification,
+    ) -> error::Result<Option<PushMessage>> {
+        if self.seen.contains(&notification.message_id) {
+            log::debug!("Dropping duplicate message {}", notification.message_id);
+            return Ok(None);
+        }
+        let record = store
+            .get_record_by_chid(&notification.channel_id)?
+            .ok_or_else(|| {
+                ErrorKind::RecordNotFoundError(String::new(), notification.channel_id.clone())
+            })?;
+        let body = if notification.body.is_empty() {
+            Vec::new()
+        } else {
+            let key = Key::deserialize(&record.key)?;
+            match notification.con.to_lowercase().as_str() {
+                "aes128gcm" => Crypto::decrypt_aes128gcm(&key, &notification.body)?,
+                "aesgcm" => Crypto::decrypt_aesgcm(
+                    &key,
+                    &notification.body,
+                    notification.salt,
+                    notification.dh,
+                )?,
+                _ => {
+                    return Err(ErrorKind::CryptoError(format!(
+                        "Unknown Content Encoding {:?}",
+                        notification.con
+                    ))
+                    .into())
+                }
+            }
+        };
+        let handler = self.handlers.get(&record.scope).ok_or_else(|| {
+            ErrorKind::GeneralError(format!("No handler for scope {:?}", record.scope))
+        })?;
+        let message = PushMessage {
+            channel_id: record.channel_id,
+            scope: record.scope,
+            message_id: notification.message_id,
+            body,
+        };
+        handler.handle_message(&message)?;
+        self.mark_seen(&message.message_id);
+        Ok(Some(message))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communications::{mock::MockConnection, NotificationHeaders};
+    use crate::storage::{PushRecord, Store};
+    use rc_crypto::ece;
+    use std::sync::{Arc, Mutex};
+
+    const CHID: &str = "deadbeef00000000decafbad00000000";
+    const SCOPE: &str = "https://example.com/";
+
+    /// Collects the messages that it's given.
+    #[derive(Clone, Default)]
+    struct TestHandler(Arc<Mutex<Vec<PushMessage>>>);
+
+    impl MessageHandler for TestHandler {
+        fn handle_message(&self, message: &PushMessage) -> error::Result<()> {
+            self.0.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    fn store_with_key(key: Key) -> Store {
+        let store = Store::open_in_memory().unwrap();
+        let record = PushRecord::new("uaid", CHID, "https://push.example.com/", SCOPE, key);
+        store.put_record(&record).unwrap();
+        store
+    }
+
+    fn encrypted(key: &Key, message_id: &str, plaintext: &[u8]) -> Notification {
+        let mut salt = vec![0u8; 16];
+        rc_crypto::rand::fill(&mut salt).unwrap();
+        Notification {
+            channel_id: CHID.to_owned(),
+            message_id: message_id.to_owned(),
+            body: ece::encrypt(key.public_key(), &key.auth, &salt, plaintext).unwrap(),
+            con: "aes128gcm".to_owned(),
+            salt: None,
+            dh: None,
+        }
+    }
+
+    #[test]
+    fn test_deliver_and_dedupe() {
+        let key = Crypto::generate_key().unwrap();
+        let store = store_with_key(key.clone());
+        let conn = MockConnection::new();
+        let handler = TestHandler::default();
+        let mut notifier = NotifHandler::new();
+        notifier.register_handler(SCOPE, Box::new(handler.clone()));
+
+        let message = notifier
+            .handle_notification(&store, &conn, encrypted(&key, "m1", b"hello"))
+            .unwrap()
+            .expect("Should deliver the message");
+        assert_eq!(message.scope, SCOPE);
+        assert_eq!(message.body, b"hello");
+
+        // Redelivering the message acks it again, but doesn't hand it to
+        // the handler.
+        let duplicate = notifier
+            .handle_notification(&store, &conn, encrypted(&key, "m1", b"hello"))
+            .unwrap();
+        assert!(duplicate.is_none());
+        assert_eq!(handler.0.lock().unwrap().len(), 1);
+        assert_eq!(
+            conn.acks(),
+            vec![
+                (CHID.to_owned(), "m1".to_owned(), AckCode::Delivered),
+                (CHID.to_owned(), "m1".to_owned(), AckCode::Delivered),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aesgcm() {
+        // The test vectors from the crypto tests.
+        let key = Crypto::test_key(
+            "qJkxxWGVVxy7BKvraNY3hg8Gs-Y8qi0lRaXWJ3R3aJ8",
+            "BBcJdfs1GtMyymFTtty6lIGWRFXrEtJP40Df0gOvRDR4D8CKVgqE6vlYR7tCYksIRdKD1MxDPhQVmKLnzuife50",
+            "LsuUOBKVQRY6-l7_Ajo-Ag",
+        );
+        let store = store_with_key(key);
+        let conn = MockConnection::new();
+        let handler = TestHandler::default();
+        let mut notifier = NotifHandler::new();
+        notifier.register_handler(SCOPE, Box::new(handler.clone()));
+
+        let notification = Notification::from_incoming(IncomingNotification {
+            channel_id: CHID.to_owned(),
+            version: "m1".to_owned(),
+            data: Some("BNKu5uTFhjyS-06eECU9-6O61int3Rr7ARbm-xPhFuyDO5sfxVs-HywGaVonvzkarvfvXE9IRT_YNA81Og2uSqDasdMuw\
+                        qm1zd0O3f7049IkQep3RJ2pEZTy5DqvI7kwMLDLzea9nroq3EMH5hYhvQtQgtKXeWieEL_3yVDQVg".to_owned()),
+            headers: Some(NotificationHeaders {
+                encoding: Some("aesgcm".to_owned()),
+                encryption: Some("salt=tSf2qu43C9BD0zkvRW5eUg".to_owned()),
+                crypto_key: Some("keyid=foo;dh=BMOebOMWSRisAhWpRK9ZPszJC8BL9MiWvLZBoBU6pG6Kh6vUFSW4BHFMh0b83xCg3_7IgfQZXwmVuyu27vwiv5c".to_owned()),
+                encryption_key: None,
+            }),
+        })
+        .unwrap();
+        let message = notifier
+            .handle_notification(&store, &conn, notification)
+            .unwrap()
+            .unwrap();
+        assert!(String::from_utf8(message.body)
+            .unwrap()
+            .starts_with("Amidst the mists"));
+    }
+
+    #[test]
+    fn test_failures() {
+        let key = Crypto::generate_key().unwrap();
+        let other_key = Crypto::generate_key().unwrap();
+        let store = store_with_key(key.clone());
+        let conn = MockConnection::new();
+        let mut notifier = NotifHandler::new();
+
+        // No handler for the scope.
+        let err = notifier
+            .handle_notification(&store, &conn, encrypted(&key, "m1", b"hello"))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::GeneralError(_)));
+
+        // Encrypted with the wrong key.
+        notifier.register_handler(SCOPE, Box::new(TestHandler::default()));
+        let err = notifier
+            .handle_notification(&store, &conn, encrypted(&other_key, "m2", b"hello"))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::CryptoError(_)));
+
+        // Unknown channel.
+        let mut notification = encrypted(&key, "m3", b"hello");
+        notification.channel_id = "feedface00000000decafbad00000000".to_owned();
+        let err = notifier
+            .handle_notification(&store, &conn, notification)
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::RecordNotFoundError(_, _)));
+
+        let codes = conn
+            .acks()
+            .iter()
+            .map(|(_, _, code)| *code)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            vec![
+                AckCode::NotDelivered,
+                AckCode::DecryptionError,
+                AckCode::NotDelivered
+            ]
+        );
+
+        // Failed messages weren't marked as seen, so they can be delivered
+        // when the server sends them again.
+        assert!(notifier
+            .handle_notification(&store, &conn, encrypted(&key, "m1", b"hello"))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_ack_failure() {
+        let key = Crypto::generate_key().unwrap();
+        let store = store_with_key(key.clone());
+        let conn = MockConnection::new();
+        conn.fail_acks(true);
+        let handler = TestHandler::default();
+        let mut notifier = NotifHandler::new();
+        notifier.register_handler(SCOPE, Box::new(handler.clone()));
+
+        // The message was delivered, even though we couldn't tell the
+        // server.
+        let message = notifier
+            .handle_notification(&store, &conn, encrypted(&key, "m1", b"hello"))
+            .unwrap()
+            .expect("Should deliver the message");
+        assert_eq!(message.body, b"hello");
+        assert_eq!(handler.0.lock().unwrap().len(), 1);
+        assert!(conn.acks().is_empty());
+
+        // When the server sends it again, we drop it and ack it.
+        conn.fail_acks(false);
+        assert!(notifier
+            .handle_notification(&store, &conn, encrypted(&key, "m1", b"hello"))
+            .unwrap()
+            .is_none());
+        assert_eq!(handler.0.lock().unwrap().len(), 1);
+        assert_eq!(
+            conn.acks(),
+            vec![(CHID.to_owned(), "m1".to_owned(), AckCode::Delivered)]
+        );
+    }
+}
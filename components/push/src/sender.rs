@@ -0,0 +1,434 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Sends Web Push messages to subscriptions.
+//!
+//! Payloads are encrypted with `aes128gcm` (RFC 8291), and requests are
+//! signed with VAPID (RFC 8292), so that the push service can identify the
+//! application server that sent them. This is the sending half of what
+//! `notifier` receives.
+
+use crate::error::{self, ErrorKind::*, Result};
+use rc_crypto::{
+    ece,
+    signature::{EcKey, EcdsaKeyPair, ECDSA_P256_SHA256_FIXED},
+};
+use serde_derive::*;
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+use viaduct::{header_names, status_codes, Request, Response};
+
+/// How long the push service should keep a message for an offline device,
+/// in seconds, if the caller doesn't say otherwise.
+pub const DEFAULT_TTL: u64 = 24 * 60 * 60;
+
+/// How long a VAPID token is valid for. RFC 8292 allows up to 24 hours; we
+/// stay well under that to allow for clock skew.
+const VAPID_EXPIRY: u64 = 12 * 60 * 60;
+
+/// Topics replace pending messages with the same topic, and are limited to
+/// 32 characters from the URL-safe base64 alphabet.
+const MAX_TOPIC_LENGTH: usize = 32;
+
+/// A push subscription, as handed out by `PushSubscription.toJSON()` or
+/// `PushManager::subscribe`. The keys are URL-safe base64-encoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// How urgently the push service should deliver a message. Devices on
+/// battery may delay low-urgency messages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Urgency {
+    VeryLow,
+    Low,
+    Normal,
+    High,
+}
+
+impl Urgency {
+    fn as_str(self) -> &'static str {
+        match self {
+            Urgency::VeryLow => "very-low",
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::High => "high",
+        }
+    }
+}
+
+/// A message to send to a subscription.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    /// The plaintext payload. Messages without a payload wake the receiver
+    /// without telling it anything.
+    pub payload: Option<Vec<u8>>,
+    /// How long, in seconds, the push service should hold on to the message.
+    pub ttl: u64,
+    pub urgency: Option<Urgency>,
+    /// Replaces any undelivered message with the same topic.
+    pub topic: Option<String>,
+}
+
+impl Default for Message {
+    fn default() -> Self {
+        Self {
+            payload: None,
+            ttl: DEFAULT_TTL,
+            urgency: None,
+            topic: None,
+        }
+    }
+}
+
+impl Message {
+    pub fn new(payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            payload: Some(payload.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A VAPID key pair, and the contact information sent with every request.
+pub struct Vapid {
+    key_pair: EcdsaKeyPair,
+    subject: String,
+}
+
+impl Vapid {
+    /// Generates a new key pair. `subject` is a `mailto:` or `https:` URL
+    /// that the push service operator can use to contact the sender.
+    pub fn generate(subject: &str) -> Result<Self> {
+        let key_pair = EcdsaKeyPair::generate(&ECDSA_P256_SHA256_FIXED)
+            .map_err(|e| CryptoError(format!("Could not generate VAPID key: {:?}", e)))?;
+        Ok(Self {
+            key_pair,
+            subject: subject.to_owned(),
+        })
+    }
+
+    /// Imports a key pair previously returned by `export`. Subscriptions
+    /// created with an application server key only accept messages signed
+    /// with that key, so senders should persist it.
+    pub fn import(key: &EcKey, subject: &str) -> Result<Self> {
+        let key_pair = EcdsaKeyPair::import(key)
+            .map_err(|e| CryptoError(format!("Could not import VAPID key: {:?}", e)))?;
+        Ok(Self {
+            key_pair,
+            subject: subject.to_owned(),
+        })
+    }
+
+    pub fn export(&self) -> Result<EcKey> {
+        self.key_pair
+            .export()
+            .map_err(|e| CryptoError(format!("Could not export VAPID key: {:?}", e)).into())
+    }
+
+    /// The URL-safe base64-encoded public key. This is the
+    /// `applicationServerKey` to pass when subscribing.
+    pub fn public_key(&self) -> String {
+        base64::encode_config(self.key_pair.public_key(), base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Returns a signed JWT for requests to `endpoint`, expiring at
+    /// `expires_at` seconds since the epoch.
+    pub fn sign(&self, endpoint: &Url, expires_at: u64) -> Result<String> {
+        let header = json!({"typ": "JWT", "alg": "ES256"});
+        let claims = json!({
+            "aud": endpoint.origin().ascii_serialization(),
+            "exp": expires_at,
+            "sub": self.subject,
+        });
+        let signing_input = format!(
+            "{}.{}",
+            base64::encode_config(&header.to_string(), base64::URL_SAFE_NO_PAD),
+            base64::encode_config(&claims.to_string(), base64::URL_SAFE_NO_PAD),
+        );
+        let signature = self
+            .key_pair
+            .sign(signing_input.as_bytes())
+            .map_err(|e| CryptoError(format!("Could not sign VAPID token: {:?}", e)))?;
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            base64::encode_config(signature.as_ref(), base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    fn authorization(&self, endpoint: &Url) -> Result<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| GeneralError(format!("Bad system time: {:?}", e)))?;
+        let token = self.sign(endpoint, now.as_secs() + VAPID_EXPIRY)?;
+        Ok(format!("vapid t={}, k={}", token, self.public_key()))
+    }
+}
+
+/// Sends messages to push subscriptions.
+#[derive(Default)]
+pub struct Sender {
+    vapid: Option<Vapid>,
+}
+
+impl Sender {
+    /// Creates a sender. Without VAPID keys, messages can only be sent to
+    /// subscriptions created without an application server key.
+    pub fn new(vapid: Option<Vapid>) -> Self {
+        Self { vapid }
+    }
+
+    pub fn vapid(&self) -> Option<&Vapid> {
+        self.vapid.as_ref()
+    }
+
+    /// Encrypts and sends `message` to `subscription`. Returns the URL of
+    /// the message on the push service, if it gave us one.
+    ///
+    /// A `SubscriptionGoneError` means the subscription has expired or
+    /// been unsubscribed, and the caller should stop sending to it.
+    pub fn send(&self, subscription: &Subscription, message: &Message) -> Result<Option<String>> {
+        let request = self.build_request(subscription, message)?;
+        let response = request
+            .send()
+            .map_err(|e| CommunicationError(format!("Could not send push message: {}", e)))?;
+        check_response(response)
+    }
+
+    pub(crate) fn build_request(
+        &self,
+        subscription: &Subscription,
+        message: &Message,
+    ) -> Result<Request> {
+        let endpoint = Url::parse(&subscription.endpoint)?;
+        let mut request = Request::post(endpoint.clone())
+            .header("ttl", message.ttl.to_string())
+            .map_err(header_error)?;
+        if let Some(urgency) = message.urgency {
+            request = request
+                .header("urgency", urgency.as_str())
+                .map_err(header_error)?;
+        }
+        if let Some(topic) = &message.topic {
+            if !is_valid_topic(topic) {
+                return Err(GeneralError(format!("Invalid topic: {:?}", topic)).into());
+            }
+            request = request.header("topic", topic).map_err(header_error)?;
+        }
+        if let Some(vapid) = &self.vapid {
+            request = request
+                .header(header_names::AUTHORIZATION, vapid.authorization(&endpoint)?)
+                .map_err(header_error)?;
+        }
+        if let Some(payload) = &message.payload {
+            let body = encrypt(subscription, payload)?;
+            request = request
+                .header(header_names::CONTENT_TYPE, "application/octet-stream")
+                .and_then(|r| r.header("content-encoding", "aes128gcm"))
+                .map_err(header_error)?
+                .body(body);
+        }
+        Ok(request)
+    }
+}
+
+fn header_error(e: viaduct::Error) -> error::Error {
+    GeneralError(format!("Invalid header: {:?}", e)).into()
+}
+
+fn is_valid_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && topic.len() <= MAX_TOPIC_LENGTH
+        && topic
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Encrypts `payload` for the subscription's keys, with a random salt.
+fn encrypt(subscription: &Subscription, payload: &[u8]) -> Result<Vec<u8>> {
+    let p256dh = decode_key(&subscription.p256dh)?;
+    let auth = decode_key(&subscription.auth)?;
+    let salt = crate::crypto::get_bytes(16)?;
+    rc_crypto::ensure_initialized();
+    ece::encrypt(&p256dh, &auth, &salt, payload)
+        .map_err(|e| CryptoError(format!("Could not encrypt payload: {:?}", e)).into())
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>> {
+    base64::decode_config(key.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(|e| TranscodingError(format!("Could not decode subscription key: {:?}", e)).into())
+}
+
+fn check_response(response: Response) -> Result<Option<String>> {
+    if response.is_success() {
+        return Ok(response.headers.get("location").map(ToOwned::to_owned));
+    }
+    let message = format!(
+        "{} : {:?}",
+        response.status,
+        String::from_utf8_lossy(&response.body)
+    );
+    Err(match response.status {
+        status_codes::NOT_FOUND | status_codes::GONE => SubscriptionGoneError(message),
+        status_codes::REQUEST_ENTITY_TOO_LARGE => PayloadTooLargeError,
+        status_codes::TOO_MANY_REQUESTS => {
+            RateLimitedError(response.headers.try_get(header_names::RETRY_AFTER))
+        }
+        _ if response.is_server_error() => {
+            CommunicationServerError(format!("Push service error {}", message))
+        }
+        _ => CommunicationError(format!("Unhandled client error {}", message)),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Crypto, Cryptography};
+    use mockito::{mock, server_address};
+    use rc_crypto::signature::UnparsedPublicKey;
+
+    const SUBJECT: &str = "mailto:push@example.com";
+
+    fn subscription(endpoint: &str) -> (Subscription, crate::crypto::Key) {
+        let key = Crypto::generate_key().unwrap();
+        let subscription = Subscription {
+            endpoint: endpoint.to_owned(),
+            p256dh: base64::encode_config(key.public_key(), base64::URL_SAFE_NO_PAD),
+            auth: base64::encode_config(&key.auth, base64::URL_SAFE_NO_PAD),
+        };
+        (subscription, key)
+    }
+
+    #[test]
+    fn test_vapid_token() {
+        let vapid = Vapid::generate(SUBJECT).unwrap();
+        let endpoint = Url::parse("https://push.example.com/wpush/v2/abc?x=1").unwrap();
+        let token = vapid.sign(&endpoint, 1_600_000_000).unwrap();
+        let parts = token.split('.').collect::<Vec<_>>();
+        assert_eq!(parts.len(), 3);
+
+        let decode = |part: &str| base64::decode_config(part, base64::URL_SAFE_NO_PAD).unwrap();
+        let header: serde_json::Value = serde_json::from_slice(&decode(parts[0])).unwrap();
+        assert_eq!(header["alg"], "ES256");
+        let claims: serde_json::Value = serde_json::from_slice(&decode(parts[1])).unwrap();
+        assert_eq!(claims["aud"], "https://push.example.com");
+        assert_eq!(claims["exp"], 1_600_000_000);
+        assert_eq!(claims["sub"], SUBJECT);
+
+        let public_key = decode(&vapid.public_key());
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &public_key)
+            .verify(
+                format!("{}.{}", parts[0], parts[1]).as_bytes(),
+                &decode(parts[2]),
+            )
+            .expect("Token should be signed with the VAPID key");
+
+        // Exported keys should sign the same way.
+        let imported = Vapid::import(&vapid.export().unwrap(), SUBJECT).unwrap();
+        assert_eq!(imported.public_key(), vapid.public_key());
+    }
+
+    #[test]
+    fn test_build_request() {
+        let (subscription, key) = subscription("https://push.example.com/wpush/v2/abc");
+        let sender = Sender::new(Some(Vapid::generate(SUBJECT).unwrap()));
+        let message = Message {
+            ttl: 60,
+            urgency: Some(Urgency::VeryLow),
+            topic: Some("tabs".to_owned()),
+            ..Message::new("Hello, world!")
+        };
+        let request = sender.build_request(&subscription, &message).unwrap();
+        assert_eq!(request.headers.get("ttl"), Some("60"));
+        assert_eq!(request.headers.get("urgency"), Some("very-low"));
+        assert_eq!(request.headers.get("topic"), Some("tabs"));
+        assert_eq!(request.headers.get("content-encoding"), Some("aes128gcm"));
+        let authorization = request.headers.get(header_names::AUTHORIZATION).unwrap();
+        assert!(authorization.starts_with("vapid t="));
+        assert!(authorization.ends_with(&format!(", k={}", sender.vapid().unwrap().public_key())));
+
+        let body = request.body.unwrap();
+        let plaintext = Crypto::decrypt_aes128gcm(&key, &body).unwrap();
+        assert_eq!(plaintext, b"Hello, world!");
+
+        // Messages without a payload or VAPID keys are just pings.
+        let request = Sender::default()
+            .build_request(&subscription, &Message::default())
+            .unwrap();
+        assert_eq!(request.headers.get("ttl"), Some("86400"));
+        assert!(request.headers.get(header_names::AUTHORIZATION).is_none());
+        assert!(request.headers.get("content-encoding").is_none());
+        assert!(request.body.is_none());
+
+        for topic in &["", "not a topic", "0123456789abcdef0123456789abcdefg"] {
+            let message = Message {
+                topic: Some((*topic).to_owned()),
+                ..Message::default()
+            };
+            assert!(Sender::default()
+                .build_request(&subscription, &message)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        viaduct_reqwest::use_reqwest_backend();
+        let sender = Sender::new(Some(Vapid::generate(SUBJECT).unwrap()));
+        let endpoint = |path: &str| format!("http://{}{}", server_address(), path);
+
+        // mockito forces task serialization, so we check each response in turn.
+        {
+            let ap_mock = mock("POST", "/wpush/v2/ok")
+                .match_header("ttl", "86400")
+                .match_header("content-encoding", "aes128gcm")
+                .with_status(201)
+                .with_header("location", "https://push.example.com/m/abc")
+                .create();
+            let (subscription, _) = subscription(&endpoint("/wpush/v2/ok"));
+            let location = sender.send(&subscription, &Message::new("Hello")).unwrap();
+            ap_mock.assert();
+            assert_eq!(location.as_deref(), Some("https://push.example.com/m/abc"));
+        }
+        let send_error = |status: usize, headers: &[(&str, &str)]| {
+            let path = format!("/wpush/v2/{}", status);
+            let mut ap_mock = mock("POST", path.as_str()).with_status(status);
+            for (name, value) in headers {
+                ap_mock = ap_mock.with_header(name, value);
+            }
+            let ap_mock = ap_mock.create();
+            let (subscription, _) = subscription(&endpoint(&path));
+            let err = sender
+                .send(&subscription, &Message::new("Hello"))
+                .unwrap_err();
+            ap_mock.assert();
+            err
+        };
+        assert!(matches!(
+            send_error(410, &[]).kind(),
+            SubscriptionGoneError(_)
+        ));
+        assert!(matches!(
+            send_error(404, &[]).kind(),
+            SubscriptionGoneError(_)
+        ));
+        assert!(matches!(send_error(413, &[]).kind(), PayloadTooLargeError));
+        assert!(matches!(
+            send_error(429, &[("retry-after", "120")]).kind(),
+            RateLimitedError(Some(120))
+        ));
+        assert!(matches!(send_error(400, &[]).kind(), CommunicationError(_)));
+        assert!(matches!(
+            send_error(503, &[]).kind(),
+            CommunicationServerError(_)
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
-use std::{ops::Deref, path::Path};
+use std::{collections::HashMap, ops::Deref, path::Path};
 
 use rusqlite::Connection;
 use sql_support::ConnExt;
@@ -10,8 +10,6 @@ use crate::error::{ErrorKind, Result};
 
 use super::{record::PushRecord, schema};
 
-// TODO: Add broadcasts storage
-
 pub trait Storage {
     fn get_record(&self, uaid: &str, chid: &str) -> Result<Option<PushRecord>>;
 
@@ -32,6 +30,16 @@ pub trait Storage {
     fn get_meta(&self, key: &str) -> Result<Option<String>>;
 
     fn set_meta(&self, key: &str, value: &str) -> Result<()>;
+
+    /// Returns the broadcast IDs that we're subscribed to, and the last
+    /// version that we saw for each.
+    fn get_broadcasts(&self) -> Result<HashMap<String, String>>;
+
+    /// Adds or updates the versions for these broadcast IDs.
+    fn put_broadcasts(&self, broadcasts: &HashMap<String, String>) -> Result<()>;
+
+    /// Unsubscribes from a broadcast. Returns `false` if we weren't subscribed.
+    fn delete_broadcast(&self, broadcast_id: &str) -> Result<bool>;
 }
 
 pub struct PushDb {
@@ -39,9 +47,11 @@ pub struct PushDb {
 }
 
 impl PushDb {
-    pub fn with_connection(db: Connection) -> Result<Self> {
+    pub fn with_connection(mut db: Connection) -> Result<Self> {
         // XXX: consider the init_test_logging call in other components
-        schema::init(&db)?;
+        let tx = db.transaction()?;
+        schema::init(&tx)?;
+        tx.commit()?;
         Ok(Self { db })
     }
 
@@ -230,6 +240,32 @@ impl Storage for PushDb {
         self.execute_named_cached(query, &[(":k", &key), (":v", &value)])?;
         Ok(())
     }
+
+    fn get_broadcasts(&self) -> Result<HashMap<String, String>> {
+        let rows = self.query_rows_and_then_named(
+            "SELECT broadcast_id, version FROM broadcasts",
+            &[],
+            |row| -> Result<(String, String)> { Ok((row.get(0)?, row.get(1)?)) },
+        )?;
+        Ok(rows.into_iter().collect())
+    }
+
+    fn put_broadcasts(&self, broadcasts: &HashMap<String, String>) -> Result<()> {
+        let query =
+            "INSERT or REPLACE into broadcasts (broadcast_id, version) values (:id, :version)";
+        for (id, version) in broadcasts {
+            self.execute_named_cached(query, &[(":id", id), (":version", version)])?;
+        }
+        Ok(())
+    }
+
+    fn delete_broadcast(&self, broadcast_id: &str) -> Result<bool> {
+        let affected_rows = self.execute_named(
+            "DELETE FROM broadcasts WHERE broadcast_id = :id",
+            &[(":id", &broadcast_id)],
+        )?;
+        Ok(affected_rows == 1)
+    }
 }
 
 #[cfg(test)]
@@ -240,6 +276,7 @@ mod test {
     use super::PushDb;
     use crate::crypto::get_bytes;
     use crate::storage::{db::Storage, record::PushRecord};
+    use std::collections::HashMap;
 
     const DUMMY_UAID: &str = "abad1dea00000000aabbccdd00000000";
 
@@ -338,6 +375,37 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn broadcasts() -> Result<()> {
+        let db = get_db()?;
+        assert!(db.get_broadcasts()?.is_empty());
+        let mut broadcasts = HashMap::new();
+        broadcasts.insert(
+            "remote-settings/monitor_changes".to_owned(),
+            "v1".to_owned(),
+        );
+        broadcasts.insert("other".to_owned(), "v5".to_owned());
+        db.put_broadcasts(&broadcasts)?;
+        assert_eq!(db.get_broadcasts()?, broadcasts);
+
+        // Putting the same IDs again updates their versions.
+        let mut update = HashMap::new();
+        update.insert("other".to_owned(), "v6".to_owned());
+        db.put_broadcasts(&update)?;
+        broadcasts.insert("other".to_owned(), "v6".to_owned());
+        assert_eq!(db.get_broadcasts()?, broadcasts);
+
+        assert!(db.delete_broadcast("other")?);
+        assert!(!db.delete_broadcast("other")?);
+        broadcasts.remove("other");
+        assert_eq!(db.get_broadcasts()?, broadcasts);
+
+        // Resetting the UAID doesn't affect broadcasts.
+        db.delete_all_records(DUMMY_UAID)?;
+        assert_eq!(db.get_broadcasts()?, broadcasts);
+        Ok(())
+    }
+
     #[test]
     fn dash() -> Result<()> {
         let db = get_db()?;
@@ -1,12 +1,35 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
-use rusqlite::Connection;
+
+//! Push schema
+//! ===========
+//!
+//! `schema.sql` always holds the current schema, which is what new databases
+//! are created with. Existing databases are brought up to date by `upgrade`,
+//! which runs the migrations for each version after the one the database was
+//! created with, in order.
+//!
+//! Versions:
+//!
+//! - 1: The `push_record` table.
+//! - 2: Adds the `meta_data` table, used to store the UAID and auth token.
+//! - 3: Adds the `broadcasts` table, used to store the broadcast IDs that
+//!   we're subscribed to, and their last known versions.
+//!
+//! When changing the schema, bump `VERSION`, update `schema.sql`, and add an
+//! `upgrade_from_N` function for the previous version. The push server is the
+//! source of truth for subscriptions, so if we find a database we can't
+//! upgrade (for example, one from a newer version of the app that was since
+//! downgraded), we drop everything and start over, rather than crashing: the
+//! app will resubscribe once it notices its subscriptions are gone.
+
+use rusqlite::{Connection, NO_PARAMS};
 use sql_support::ConnExt;
 
 use crate::error::Result;
 
-const VERSION: i64 = 2;
+const VERSION: i64 = 3;
 
 const CREATE_TABLE_PUSH_SQL: &str = include_str!("schema.sql");
 
@@ -25,29 +48,54 @@ pub fn init(db: &Connection) -> Result<()> {
     let user_version = db.query_one::<i64>("PRAGMA user_version")?;
     if user_version == 0 {
         create(db)?;
-    } else if user_version != VERSION {
-        if user_version < VERSION {
-            upgrade(db, user_version)?;
-        } else {
-            log::warn!(
-                "Loaded future schema version {} (we only understand version {}). \
-                 Optimistically ",
-                user_version,
-                VERSION
-            )
-        }
+    } else if user_version < VERSION {
+        upgrade(db, user_version)?;
+    } else if user_version > VERSION {
+        log::warn!(
+            "Loaded future schema version {} (we only understand version {}). \
+             Recreating the database",
+            user_version,
+            VERSION
+        );
+        recreate(db)?;
     }
     Ok(())
 }
 
 fn upgrade(db: &Connection, from: i64) -> Result<()> {
     log::debug!("Upgrading schema from {} to {}", from, VERSION);
-    match from {
-        VERSION => Ok(()),
-        0 => create(db),
-        1 => create(db),
-        _ => panic!("sorry, no upgrades yet - delete your db!"),
+    if from < 1 {
+        log::warn!("Unknown schema version {}, recreating the database", from);
+        return recreate(db);
+    }
+    if from < 2 {
+        upgrade_from_1(db)?;
+    }
+    if from < 3 {
+        upgrade_from_2(db)?;
     }
+    db.execute_batch(&format!("PRAGMA user_version = {}", VERSION))?;
+    Ok(())
+}
+
+fn upgrade_from_1(db: &Connection) -> Result<()> {
+    db.execute_batch(
+        "CREATE TABLE IF NOT EXISTS meta_data (
+             key   TEXT PRIMARY KEY,
+             value      NOT NULL
+         ) WITHOUT ROWID;",
+    )?;
+    Ok(())
+}
+
+fn upgrade_from_2(db: &Connection) -> Result<()> {
+    db.execute_batch(
+        "CREATE TABLE IF NOT EXISTS broadcasts (
+             broadcast_id TEXT PRIMARY KEY,
+             version      TEXT NOT NULL
+         ) WITHOUT ROWID;",
+    )?;
+    Ok(())
 }
 
 pub fn create(db: &Connection) -> Result<()> {
@@ -60,3 +108,143 @@ pub fn create(db: &Connection) -> Result<()> {
 
     Ok(())
 }
+
+/// Drops all tables, including any we don't know about, and creates the
+/// current schema from scratch.
+fn recreate(db: &Connection) -> Result<()> {
+    let tables = db.query_rows_and_then_named::<String, crate::error::Error, _>(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        &[],
+        |row| Ok(row.get(0)?),
+    )?;
+    for table in tables {
+        db.execute(&format!("DROP TABLE \"{}\"", table), NO_PARAMS)?;
+    }
+    create(db)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::db::{PushDb, Storage};
+
+    const CREATE_V1_SCHEMA_SQL: &str = "
+        CREATE TABLE push_record (
+            uaid               TEXT     NOT NULL,
+            channel_id         TEXT     NOT NULL UNIQUE,
+            endpoint           TEXT     NOT NULL UNIQUE,
+            scope              TEXT     NOT NULL,
+            key                TEXT     NOT NULL,
+            ctime              INTEGER  NOT NULL,
+            app_server_key     TEXT,
+            native_id          TEXT,
+            PRIMARY KEY (uaid, channel_id)
+        );
+        INSERT INTO push_record (uaid, channel_id, endpoint, scope, key, ctime)
+        VALUES ('abad1dea00000000aabbccdd00000000', 'deadbeef00000000decafbad00000000',
+                'https://example.com/update', 'https://example.com/', 'key', 0);
+        PRAGMA user_version = 1;
+    ";
+
+    fn open_with(sql: &str) -> PushDb {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(sql).unwrap();
+        PushDb::with_connection(conn).expect("should open the database")
+    }
+
+    fn user_version(db: &PushDb) -> i64 {
+        db.query_one::<i64>("PRAGMA user_version").unwrap()
+    }
+
+    #[test]
+    fn test_create() {
+        let db = open_with("");
+        assert_eq!(user_version(&db), VERSION);
+        create(&db).expect("should allow running twice");
+    }
+
+    #[test]
+    fn test_upgrade_from_1() {
+        let db = open_with(CREATE_V1_SCHEMA_SQL);
+        assert_eq!(user_version(&db), VERSION);
+        // Existing subscriptions are kept...
+        assert!(db
+            .get_record_by_chid("deadbeef00000000decafbad00000000")
+            .unwrap()
+            .is_some());
+        // ...and we can use the new meta table.
+        db.set_meta("uaid", "abad1dea00000000aabbccdd00000000")
+            .unwrap();
+        assert_eq!(
+            db.get_meta("uaid").unwrap().as_deref(),
+            Some("abad1dea00000000aabbccdd00000000")
+        );
+        // ...and the broadcasts table.
+        assert!(db.get_broadcasts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_upgrade_from_2() {
+        let db = open_with(&format!(
+            "{}
+             CREATE TABLE meta_data (
+                 key   TEXT PRIMARY KEY,
+                 value      NOT NULL
+             ) WITHOUT ROWID;
+             INSERT INTO meta_data (key, value)
+             VALUES ('uaid', 'abad1dea00000000aabbccdd00000000');
+             PRAGMA user_version = 2;",
+            CREATE_V1_SCHEMA_SQL
+        ));
+        assert_eq!(user_version(&db), VERSION);
+        assert_eq!(
+            db.get_meta("uaid").unwrap().as_deref(),
+            Some("abad1dea00000000aabbccdd00000000")
+        );
+        let mut broadcasts = std::collections::HashMap::new();
+        broadcasts.insert(
+            "remote-settings/monitor_changes".to_owned(),
+            "v1".to_owned(),
+        );
+        db.put_broadcasts(&broadcasts).unwrap();
+        assert_eq!(db.get_broadcasts().unwrap(), broadcasts);
+    }
+
+    #[test]
+    fn test_future_version() {
+        let db = open_with(&format!(
+            "{}
+             CREATE TABLE something_new (id INTEGER PRIMARY KEY);
+             PRAGMA user_version = {};",
+            CREATE_V1_SCHEMA_SQL,
+            VERSION + 1
+        ));
+        assert_eq!(user_version(&db), VERSION);
+        assert!(db
+            .get_record_by_chid("deadbeef00000000decafbad00000000")
+            .unwrap()
+            .is_none());
+        assert!(!db
+            .query_one::<bool>(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'something_new')"
+            )
+            .unwrap());
+        db.set_meta("uaid", "abad1dea00000000aabbccdd00000000")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let db = open_with(&format!(
+            "{}
+             PRAGMA user_version = -1;",
+            CREATE_V1_SCHEMA_SQL
+        ));
+        assert_eq!(user_version(&db), VERSION);
+        assert!(db
+            .get_record_by_chid("deadbeef00000000decafbad00000000")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -22,3 +22,10 @@ IF NOT EXISTS meta_data
     key                TEXT    PRIMARY KEY,
     value                      NOT NULL
 ) without ROWID;
+
+CREATE TABLE
+IF NOT EXISTS broadcasts
+(
+    broadcast_id       TEXT    PRIMARY KEY,
+    version            TEXT    NOT NULL
+) without ROWID;
@@ -6,17 +6,26 @@
 //!
 //! "privileged" system calls may require additional handling and should be flagged as such.
 
-use crate::communications::{connect, ConnectHttp, Connection, RegisterResponse};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::broadcasts::BroadcastHandler;
+use crate::communications::{connect, connect_websocket, AckCode, Connection, RegisterResponse};
 use crate::config::PushConfiguration;
 use crate::crypto::{Crypto, Cryptography, KeyV1 as Key};
+use crate::notifier::{NotifHandler, Notification, PushMessage};
 use crate::storage::{PushRecord, Storage, Store};
 
 use crate::error::{self, ErrorKind, Result};
 
 pub struct PushManager {
     config: PushConfiguration,
-    pub conn: ConnectHttp,
+    /// Talks to the server over a WebSocket if `socket_protocol` is set, or
+    /// through the OS push service otherwise.
+    pub conn: Box<dyn Connection + Send>,
     pub store: Store,
+    pub notifier: NotifHandler,
+    pub broadcasts: BroadcastHandler,
 }
 
 impl PushManager {
@@ -27,11 +36,19 @@ impl PushManager {
             Store::open_in_memory()?
         };
         let uaid = store.get_meta("uaid")?;
+        let conn: Box<dyn Connection + Send> = if config.socket_protocol.is_some() {
+            Box::new(connect_websocket(config.clone(), uaid)?)
+        } else {
+            Box::new(connect(config.clone(), uaid, store.get_meta("auth")?)?)
+        };
         let pm = PushManager {
-            config: config.clone(),
-            conn: connect(config, uaid, store.get_meta("auth")?)?,
+            config,
+            conn,
             store,
+            notifier: NotifHandler::new(),
+            broadcasts: BroadcastHandler::new(),
         };
+        pm.broadcasts.restore(&pm.store, &*pm.conn)?;
         Ok(pm)
     }
 
@@ -42,9 +59,10 @@ impl PushManager {
         scope: &str,
         server_key: Option<&str>,
     ) -> Result<(RegisterResponse, Key)> {
-        let reg_token = self.config.registration_id.clone().unwrap();
+        // WebSocket connections don't have a native token.
+        let reg_token = self.config.registration_id.clone();
         let subscription_key: Key;
-        if let Some(uaid) = self.conn.uaid.clone() {
+        if let Some(uaid) = self.conn.uaid() {
             // Don't fetch the connection from the server if we've already got one.
             if let Some(record) = self.store.get_record(&uaid, channel_id)? {
                 return Ok((
@@ -53,7 +71,7 @@ impl PushManager {
                         channel_id: record.channel_id,
                         endpoint: record.endpoint,
                         secret: self.store.get_meta("auth")?,
-                        senderid: Some(reg_token),
+                        senderid: reg_token,
                     },
                     Key::deserialize(&record.key)?,
                 ));
@@ -78,7 +96,7 @@ impl PushManager {
             subscription_key.clone(),
         );
         record.app_server_key = server_key.map(|v| v.to_owned());
-        record.native_id = Some(reg_token);
+        record.native_id = reg_token;
         self.store.put_record(&record)?;
         // store the meta information if we've not yet done that.
         if self.store.get_meta("uaid")?.is_none() {
@@ -92,43 +110,42 @@ impl PushManager {
 
     // XXX: maybe -> Result<()> instead
     pub fn unsubscribe(&self, channel_id: Option<&str>) -> Result<bool> {
-        if self.conn.uaid.is_none() {
-            return Err(ErrorKind::GeneralError("No subscriptions created yet.".into()).into());
-        }
-        let uaid = self.conn.uaid.as_ref().unwrap();
+        let uaid = self
+            .conn
+            .uaid()
+            .ok_or_else(|| ErrorKind::GeneralError("No subscriptions created yet.".into()))?;
         Ok(if let Some(chid) = channel_id {
-            self.conn.unsubscribe(channel_id)? && self.store.delete_record(uaid, chid)?
+            self.conn.unsubscribe(channel_id)? && self.store.delete_record(&uaid, chid)?
         } else {
             false
         })
     }
 
     pub fn unsubscribe_all(&self) -> Result<bool> {
-        if self.conn.uaid.is_none() {
-            return Err(ErrorKind::GeneralError("No subscriptions created yet.".into()).into());
-        }
-        let uaid = self.conn.uaid.as_ref().unwrap();
+        let uaid = self
+            .conn
+            .uaid()
+            .ok_or_else(|| ErrorKind::GeneralError("No subscriptions created yet.".into()))?;
         Ok({
-            self.store.delete_all_records(uaid)?;
+            self.store.delete_all_records(&uaid)?;
             self.conn.unsubscribe(None)?
         })
     }
 
     pub fn update(&mut self, new_token: &str) -> error::Result<bool> {
-        if self.conn.uaid.is_none() {
-            return Err(ErrorKind::GeneralError("No subscriptions created yet.".into()).into());
-        }
+        let uaid = self
+            .conn
+            .uaid()
+            .ok_or_else(|| ErrorKind::GeneralError("No subscriptions created yet.".into()))?;
         let result = self.conn.update(&new_token)?;
-        self.store
-            .update_native_id(self.conn.uaid.as_ref().unwrap(), new_token)?;
+        self.store.update_native_id(&uaid, new_token)?;
         Ok(result)
     }
 
     pub fn verify_connection(&mut self) -> Result<Vec<PushRecord>> {
         let uaid = self
             .conn
-            .uaid
-            .clone()
+            .uaid()
             .ok_or_else(|| ErrorKind::GeneralError("No subscriptions created yet.".into()))?;
 
         let channels = self.store.get_channel_list(&uaid)?;
@@ -168,6 +185,66 @@ impl PushManager {
             .map_err(|e| ErrorKind::TranscodingError(format!("{:?}", e)).into())
     }
 
+    /// Decrypts an incoming notification, and hands it to the handler
+    /// registered with `notifier` for its subscription's scope. Returns
+    /// `None` if we've already handled the message.
+    pub fn handle_notification(
+        &mut self,
+        notification: Notification,
+    ) -> Result<Option<PushMessage>> {
+        self.notifier
+            .handle_notification(&self.store, &*self.conn, notification)
+    }
+
+    /// Waits up to `timeout` for notifications on a WebSocket connection,
+    /// hands them to their handlers, and tells the broadcast listeners about
+    /// new versions. Returns the messages that were delivered; the others
+    /// are logged and acked, so that the server knows what happened.
+    pub fn read_notifications(&mut self, timeout: Duration) -> Result<Vec<PushMessage>> {
+        let mut messages = Vec::new();
+        for incoming in self.conn.read_notifications(timeout)? {
+            let (channel_id, message_id) = (incoming.channel_id.clone(), incoming.version.clone());
+            let notification = match Notification::from_incoming(incoming) {
+                Ok(notification) => notification,
+                Err(e) => {
+                    log::warn!("Couldn't decode push message {}: {}", message_id, e);
+                    if let Err(e) =
+                        self.conn
+                            .ack(&channel_id, &message_id, AckCode::DecryptionError)
+                    {
+                        log::warn!("Couldn't ack push message {}: {}", message_id, e);
+                    }
+                    continue;
+                }
+            };
+            // `handle_notification` logs the messages that it can't deliver.
+            if let Ok(Some(message)) = self.handle_notification(notification) {
+                messages.push(message);
+            }
+        }
+        self.handle_broadcasts()?;
+        Ok(messages)
+    }
+
+    /// Subscribes to broadcasts, starting from the given versions. Register
+    /// listeners for them with `broadcasts.add_listener`.
+    pub fn broadcast_subscribe(&self, broadcasts: &HashMap<String, String>) -> Result<()> {
+        self.broadcasts
+            .subscribe(&self.store, &*self.conn, broadcasts)
+    }
+
+    /// Unsubscribes from a broadcast. Returns `true` if we were subscribed.
+    pub fn broadcast_unsubscribe(&self, broadcast_id: &str) -> Result<bool> {
+        self.broadcasts
+            .unsubscribe(&self.store, &*self.conn, broadcast_id)
+    }
+
+    /// Tells the broadcast listeners about any new versions, and returns
+    /// the broadcasts that changed.
+    pub fn handle_broadcasts(&self) -> Result<HashMap<String, String>> {
+        self.broadcasts.handle_broadcasts(&self.store, &*self.conn)
+    }
+
     pub fn get_record_by_chid(
         &self,
         chid: &str,
@@ -206,6 +283,48 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn broadcasts() -> Result<()> {
+        let test_config = PushConfiguration {
+            sender_id: "test".to_owned(),
+            ..Default::default()
+        };
+        let pm = PushManager::new(test_config)?;
+        let mut broadcasts = HashMap::new();
+        broadcasts.insert(
+            "remote-settings/monitor_changes".to_owned(),
+            "v1".to_owned(),
+        );
+        pm.broadcast_subscribe(&broadcasts)?;
+        assert_eq!(pm.store.get_broadcasts()?, broadcasts);
+        assert_eq!(pm.conn.broadcasts()?.into_versions()?, broadcasts);
+        // Bridged connections don't hear about new versions.
+        assert!(pm.handle_broadcasts()?.is_empty());
+
+        assert!(pm.broadcast_unsubscribe("remote-settings/monitor_changes")?);
+        assert!(pm.store.get_broadcasts()?.is_empty());
+        assert!(pm.conn.broadcasts()?.into_versions()?.is_empty());
+        assert!(!pm.broadcast_unsubscribe("remote-settings/monitor_changes")?);
+        Ok(())
+    }
+
+    #[test]
+    fn websocket() -> Result<()> {
+        let test_config = PushConfiguration {
+            server_host: "localhost:1".to_owned(),
+            socket_protocol: Some("ws".to_owned()),
+            http_protocol: None,
+            bridge_type: None,
+            registration_id: None,
+            ..Default::default()
+        };
+        // The connection is opened lazily, so there's no UAID yet.
+        let pm = PushManager::new(test_config)?;
+        assert!(pm.conn.uaid().is_none());
+        assert!(pm.unsubscribe(None).is_err());
+        Ok(())
+    }
+
     #[test]
     fn full() -> Result<()> {
         use rc_crypto::ece;
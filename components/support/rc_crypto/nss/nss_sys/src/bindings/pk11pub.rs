@@ -110,6 +110,18 @@ extern "C" {
         attr: CK_ATTRIBUTE_TYPE,
         item: *mut SECItem,
     ) -> SECStatus;
+    pub fn PK11_SignatureLen(key: *mut SECKEYPrivateKey) -> c_int;
+    pub fn PK11_Sign(
+        key: *mut SECKEYPrivateKey,
+        sig: *mut SECItem,
+        hash: *const SECItem,
+    ) -> SECStatus;
+    pub fn PK11_Verify(
+        key: *mut SECKEYPublicKey,
+        sig: *const SECItem,
+        hash: *const SECItem,
+        wincx: *mut c_void,
+    ) -> SECStatus;
     pub fn PK11_CreatePBEV2AlgorithmID(
         pbeAlgTag: u32,    /* SECOidTag */
         cipherAlgTag: u32, /* SECOidTag */
@@ -0,0 +1,76 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    ec::{Curve, PrivateKey, PublicKey},
+    error::*,
+    pk11::context::{hash_buf, HashAlgorithm},
+    util::{ensure_nss_initialized, map_nss_secstatus, ScopedPtr},
+};
+use std::{
+    convert::TryFrom,
+    os::raw::{c_uchar, c_uint},
+    ptr,
+};
+
+fn digest_for_curve(curve: Curve) -> HashAlgorithm {
+    match curve {
+        Curve::P256 => HashAlgorithm::SHA256,
+    }
+}
+
+/// Signs `data` with ECDSA, using the hash function that matches the key's
+/// curve (SHA-256 for P-256).
+///
+/// NSS returns the signature as the fixed-length concatenation of `r` and `s`,
+/// which is the encoding used by JWS and WebCrypto.
+pub fn ecdsa_sign(priv_key: &PrivateKey, data: &[u8]) -> Result<Vec<u8>> {
+    ensure_nss_initialized();
+    let digest = hash_buf(&digest_for_curve(priv_key.curve()), data)?;
+    let digest_item = nss_sys::SECItem {
+        type_: nss_sys::SECItemType::siBuffer as u32,
+        data: digest.as_ptr() as *mut c_uchar,
+        len: c_uint::try_from(digest.len())?,
+    };
+    let signature_len = unsafe { nss_sys::PK11_SignatureLen(priv_key.as_mut_ptr()) };
+    if signature_len <= 0 {
+        return Err(ErrorKind::InternalError.into());
+    }
+    let mut signature = vec![0u8; usize::try_from(signature_len)?];
+    let mut signature_item = nss_sys::SECItem {
+        type_: nss_sys::SECItemType::siBuffer as u32,
+        data: signature.as_mut_ptr(),
+        len: c_uint::try_from(signature.len())?,
+    };
+    map_nss_secstatus(|| unsafe {
+        nss_sys::PK11_Sign(priv_key.as_mut_ptr(), &mut signature_item, &digest_item)
+    })?;
+    signature.truncate(usize::try_from(signature_item.len)?);
+    Ok(signature)
+}
+
+/// Verifies an ECDSA `signature` over `data`, in the same `r || s` format
+/// that `ecdsa_sign` produces.
+pub fn ecdsa_verify(pub_key: &PublicKey, data: &[u8], signature: &[u8]) -> Result<()> {
+    ensure_nss_initialized();
+    let digest = hash_buf(&digest_for_curve(pub_key.curve()), data)?;
+    let digest_item = nss_sys::SECItem {
+        type_: nss_sys::SECItemType::siBuffer as u32,
+        data: digest.as_ptr() as *mut c_uchar,
+        len: c_uint::try_from(digest.len())?,
+    };
+    let signature_item = nss_sys::SECItem {
+        type_: nss_sys::SECItemType::siBuffer as u32,
+        data: signature.as_ptr() as *mut c_uchar,
+        len: c_uint::try_from(signature.len())?,
+    };
+    map_nss_secstatus(|| unsafe {
+        nss_sys::PK11_Verify(
+            pub_key.as_mut_ptr(),
+            &signature_item,
+            &digest_item,
+            ptr::null_mut(),
+        )
+    })
+}
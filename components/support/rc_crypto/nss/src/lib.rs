@@ -9,6 +9,7 @@ mod util;
 pub mod aes;
 pub mod ec;
 pub mod ecdh;
+pub mod ecdsa;
 mod error;
 pub mod pbkdf2;
 pub mod pk11;
@@ -0,0 +1,172 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// This file contains code that was copied from the ring crate which is under
+// the ISC license, reproduced below:
+
+// Copyright 2015-2017 Brian Smith.
+
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR ANY
+// SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN ACTION
+// OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF OR IN
+// CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use crate::error::*;
+pub use ec::{Curve, EcKey};
+use nss::{ec, ecdsa};
+
+/// An ECDSA signature algorithm.
+#[derive(Debug, PartialEq)]
+pub struct EcdsaAlgorithm {
+    pub(crate) curve_id: ec::Curve,
+}
+
+/// ECDSA signatures using the P-256 curve and SHA-256, encoded as the
+/// fixed-length concatenation of `r` and `s`. This is the `ES256` algorithm
+/// used by JWS.
+pub static ECDSA_P256_SHA256_FIXED: EcdsaAlgorithm = EcdsaAlgorithm {
+    curve_id: ec::Curve::P256,
+};
+
+/// A key pair for signing.
+pub struct EcdsaKeyPair {
+    alg: &'static EcdsaAlgorithm,
+    private_key: ec::PrivateKey,
+    public_key: Vec<u8>,
+}
+
+impl EcdsaKeyPair {
+    /// Generate a new key pair for the given algorithm.
+    pub fn generate(alg: &'static EcdsaAlgorithm) -> Result<Self> {
+        let (private_key, public_key) = ec::generate_keypair(alg.curve_id)?;
+        Ok(Self {
+            alg,
+            private_key,
+            public_key: public_key.to_bytes()?,
+        })
+    }
+
+    /// Import a key pair previously exported with `export`.
+    pub fn import(ec_key: &EcKey) -> Result<Self> {
+        let alg = match ec_key.curve() {
+            Curve::P256 => &ECDSA_P256_SHA256_FIXED,
+        };
+        let private_key = ec::PrivateKey::import(ec_key)?;
+        Ok(Self {
+            alg,
+            private_key,
+            public_key: ec_key.public_key().to_vec(),
+        })
+    }
+
+    pub fn export(&self) -> Result<EcKey> {
+        Ok(self.private_key.export()?)
+    }
+
+    #[inline]
+    pub fn algorithm(&self) -> &'static EcdsaAlgorithm {
+        self.alg
+    }
+
+    /// The public key, as an uncompressed point.
+    #[inline]
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Sign `message` with the private key.
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        Ok(Signature(ecdsa::ecdsa_sign(&self.private_key, message)?))
+    }
+}
+
+/// An ECDSA signature.
+#[derive(Clone, Debug)]
+pub struct Signature(Vec<u8>);
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// An unparsed, possibly malformed, public key for signature verification.
+pub struct UnparsedPublicKey<'a> {
+    alg: &'static EcdsaAlgorithm,
+    bytes: &'a [u8],
+}
+
+impl<'a> UnparsedPublicKey<'a> {
+    pub fn new(alg: &'static EcdsaAlgorithm, bytes: &'a [u8]) -> Self {
+        Self { alg, bytes }
+    }
+
+    /// Verify that `signature` is a valid signature of `message`.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<()> {
+        let public_key = ec::PublicKey::from_bytes(self.alg.curve_id, self.bytes)?;
+        Ok(ecdsa::ecdsa_verify(&public_key, message, signature)?)
+    }
+
+    pub fn algorithm(&self) -> &'static EcdsaAlgorithm {
+        self.alg
+    }
+
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"Sign me, please";
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key_pair = EcdsaKeyPair::generate(&ECDSA_P256_SHA256_FIXED).unwrap();
+        let signature = key_pair.sign(MESSAGE).unwrap();
+        assert_eq!(signature.as_ref().len(), 64);
+
+        let public_key = UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, key_pair.public_key());
+        assert!(public_key.verify(MESSAGE, signature.as_ref()).is_ok());
+        assert!(public_key
+            .verify(b"Sign me, please!", signature.as_ref())
+            .is_err());
+
+        let mut tampered = signature.as_ref().to_vec();
+        tampered[0] ^= 1;
+        assert!(public_key.verify(MESSAGE, &tampered).is_err());
+    }
+
+    #[test]
+    fn test_import_export() {
+        let key_pair = EcdsaKeyPair::generate(&ECDSA_P256_SHA256_FIXED).unwrap();
+        let exported = key_pair.export().unwrap();
+        let imported = EcdsaKeyPair::import(&exported).unwrap();
+        assert_eq!(imported.public_key(), key_pair.public_key());
+
+        let signature = imported.sign(MESSAGE).unwrap();
+        let public_key = UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, key_pair.public_key());
+        assert!(public_key.verify(MESSAGE, signature.as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_with_wrong_key() {
+        let key_pair = EcdsaKeyPair::generate(&ECDSA_P256_SHA256_FIXED).unwrap();
+        let other = EcdsaKeyPair::generate(&ECDSA_P256_SHA256_FIXED).unwrap();
+        let signature = key_pair.sign(MESSAGE).unwrap();
+        let public_key = UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, other.public_key());
+        assert!(public_key.verify(MESSAGE, signature.as_ref()).is_err());
+        let malformed = UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, b"not a key");
+        assert!(malformed.verify(MESSAGE, signature.as_ref()).is_err());
+    }
+}
@@ -56,3 +56,99 @@ impl DeviceType {
         }
     }
 }
+
+/// A command sent from one client to another through the clients collection.
+/// Commands that we don't know about are represented as `Custom`, so that we
+/// can round-trip them.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Command {
+    /// Erases all local data.
+    WipeAll,
+    /// Erases all local data for a specific engine.
+    Wipe(String),
+    /// Resets local sync state for all engines.
+    ResetAll,
+    /// Resets local sync state for a specific engine.
+    Reset(String),
+    /// Asks a client to open a URI. Older desktop versions send tabs this
+    /// way, instead of using FxA device commands.
+    DisplayUri {
+        uri: String,
+        /// The client ID of the sender.
+        sender_id: String,
+        title: String,
+    },
+    /// Asks a client to reupload records that are missing or broken on the
+    /// server.
+    RepairRequest(RepairRequest),
+    /// Tells the client that asked for a repair which records we reuploaded.
+    RepairResponse(RepairResponse),
+    /// Any other command, with its optional flow ID. Each argument is kept
+    /// as serialized JSON, since it can be any JSON value.
+    Custom {
+        name: String,
+        args: Vec<String>,
+        flow_id: Option<String>,
+    },
+}
+
+impl Command {
+    /// Returns the flow ID that identifies this command in telemetry, if it
+    /// has one.
+    pub fn flow_id(&self) -> Option<&str> {
+        match self {
+            Command::RepairRequest(request) => Some(request.flow_id.as_str()),
+            Command::RepairResponse(response) => Some(response.flow_id.as_str()),
+            Command::Custom { flow_id, .. } => flow_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// The argument of a `repairRequest` command. Desktop sends this as an
+/// object, which is the only argument of the command.
+#[derive(
+    Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize,
+)]
+pub struct RepairRequest {
+    /// The collection to repair, like "bookmarks".
+    pub collection: String,
+    /// What we want the other client to do. The only request that desktop
+    /// supports is "upload".
+    pub request: String,
+    /// The client ID of the client that wants the repair.
+    pub requestor: String,
+    /// The IDs of the records to reupload.
+    pub ids: Vec<String>,
+    #[serde(rename = "flowID")]
+    pub flow_id: String,
+}
+
+/// The argument of a `repairResponse` command.
+#[derive(
+    Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize,
+)]
+pub struct RepairResponse {
+    pub collection: String,
+    pub request: String,
+    /// The client ID of the client that reuploaded the records.
+    #[serde(rename = "clientID")]
+    pub client_id: String,
+    /// The IDs of the records that were reuploaded. This may be a subset of
+    /// the requested IDs, if some of them don't exist locally.
+    pub ids: Vec<String>,
+    #[serde(rename = "flowID")]
+    pub flow_id: String,
+}
+
+/// Indicates if a command was applied successfully, ignored, or not supported.
+/// Applied and ignored commands are removed from our client record, and never
+/// retried. Unsupported commands are put back into our record, and retried on
+/// subsequent syncs. This is to handle clients adding support for new data
+/// types.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CommandStatus {
+    Applied,
+    Ignored,
+    Unsupported,
+}
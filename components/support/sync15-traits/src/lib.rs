@@ -11,14 +11,16 @@ pub mod request;
 mod server_timestamp;
 mod store;
 pub mod telemetry;
+mod validation;
 
 pub use bridged_engine::{ApplyResults, BridgedEngine, IncomingEnvelope, OutgoingEnvelope};
 pub use changeset::{IncomingChangeset, OutgoingChangeset, RecordChangeset};
 pub use payload::Payload;
 pub use request::{CollectionRequest, RequestOrder};
 pub use server_timestamp::ServerTimestamp;
-pub use store::{CollSyncIds, Store, StoreSyncAssociation};
+pub use store::{CollSyncIds, DryRun, PlannedAction, RecordPlan, Store, StoreSyncAssociation};
 pub use sync_guid::Guid;
+pub use validation::{ProblemKind, ValidationProblem, ValidationReport};
 
 // For skip_serializing_if
 pub(crate) fn skip_if_default<T: PartialEq + Default>(v: &T) -> bool {
@@ -15,6 +15,11 @@ pub struct CollectionRequest {
     pub order: Option<RequestOrder>,
     pub commit: bool,
     pub batch: Option<String>,
+    pub offset: Option<String>,
+    /// If true, the records are fetched a page (of `limit` records) at a time,
+    /// following the server's `X-Weave-Next-Offset` header until there are no
+    /// more records to fetch.
+    pub paged: bool,
 }
 
 impl CollectionRequest {
@@ -33,6 +38,8 @@ impl CollectionRequest {
             order: None,
             commit: false,
             batch: None,
+            offset: None,
+            paged: false,
         }
     }
 
@@ -76,6 +83,23 @@ impl CollectionRequest {
         self
     }
 
+    #[inline]
+    pub fn offset(mut self, offset: Option<String>) -> CollectionRequest {
+        self.offset = offset;
+        self
+    }
+
+    /// Fetch the records in pages of `page_size` records. Stores which want
+    /// to resume an interrupted download should also sort by
+    /// `RequestOrder::Oldest`, so the last record seen in each page is a
+    /// high-water mark.
+    #[inline]
+    pub fn paged(mut self, page_size: usize) -> CollectionRequest {
+        self.paged = true;
+        self.limit = page_size;
+        self
+    }
+
     #[inline]
     pub fn batch(mut self, batch: Option<String>) -> CollectionRequest {
         self.batch = batch;
@@ -121,6 +145,9 @@ impl CollectionRequest {
         if let Some(o) = self.order {
             pairs.append_pair("sort", o.as_str());
         }
+        if let Some(offset) = &self.offset {
+            pairs.append_pair("offset", offset);
+        }
         pairs.finish();
     }
 
@@ -3,10 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::{
-    client::ClientData, telemetry, CollectionRequest, Guid, IncomingChangeset, OutgoingChangeset,
-    ServerTimestamp,
+    client::{ClientData, Command, CommandStatus},
+    telemetry, CollectionRequest, Guid, IncomingChangeset, OutgoingChangeset, Payload,
+    ServerTimestamp, ValidationReport,
 };
 use anyhow::Result;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct CollSyncIds {
@@ -23,6 +25,46 @@ pub enum StoreSyncAssociation {
     Connected(CollSyncIds),
 }
 
+/// What a sync would do with a record. See `Store::apply_incoming_dry_run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlannedAction {
+    /// An incoming record would be applied locally.
+    Apply,
+    /// A local record or tombstone would be uploaded.
+    Upload,
+    /// A local record would be deleted, because of an incoming tombstone.
+    Delete,
+    /// An incoming record would be merged with a record that changed locally,
+    /// or that it duplicates.
+    Conflict,
+}
+
+/// The action a sync would take for one record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordPlan {
+    pub id: Guid,
+    pub action: PlannedAction,
+}
+
+impl RecordPlan {
+    pub fn new(id: impl Into<Guid>, action: PlannedAction) -> Self {
+        Self {
+            id: id.into(),
+            action,
+        }
+    }
+}
+
+/// The result of `Store::apply_incoming_dry_run`.
+#[derive(Debug, Clone)]
+pub struct DryRun {
+    /// The action for each incoming record that would change anything
+    /// locally.
+    pub incoming: Vec<RecordPlan>,
+    /// The records that would be uploaded.
+    pub outgoing: OutgoingChangeset,
+}
+
 /// Low-level store functionality. Stores that need custom reconciliation logic
 /// should use this.
 ///
@@ -48,6 +90,33 @@ pub trait Store {
         Ok(())
     }
 
+    /// Returns commands that this store wants to send to other clients, keyed
+    /// by the client ID of the target. The clients engine writes them into
+    /// the targets' client records, unless they're already there. Like
+    /// `prepare_for_sync`, this is only called if a command processor is
+    /// registered, and before the clients engine syncs, so stores usually
+    /// queue commands during one sync and send them on the next.
+    fn fetch_outgoing_commands(&self) -> Result<HashMap<String, HashSet<Command>>> {
+        Ok(HashMap::new())
+    }
+
+    /// Called after the clients engine uploads commands to other clients,
+    /// with the commands that made it to the server. `sent` includes commands
+    /// from all stores, so stores should only look at their own.
+    fn commands_sent(&self, _sent: &HashMap<String, HashSet<Command>>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Applies a command sent to this client by another client, like a
+    /// request to reupload records. Commands for wiping and resetting stores
+    /// are handled by the command processor, and never passed here.
+    ///
+    /// The default implementation returns `CommandStatus::Unsupported`, so
+    /// the command stays in our client record.
+    fn apply_incoming_command(&self, _command: &Command) -> Result<CommandStatus> {
+        Ok(CommandStatus::Unsupported)
+    }
+
     /// `inbound` is a vector to support the case where
     /// `get_collection_requests` returned multiple requests. The changesets are
     /// in the same order as the requests were -- e.g. if `vec![req_a, req_b]`
@@ -60,6 +129,69 @@ pub trait Store {
         telem: &mut telemetry::Engine,
     ) -> Result<OutgoingChangeset>;
 
+    /// Called with each page of records as it's downloaded, for collection
+    /// requests which are `paged`. A store that returns `true` has taken care
+    /// of the page (for example, by writing it to a staging table or applying
+    /// it directly), and should persist `high_water_mark` so that
+    /// `get_collection_requests` can resume an interrupted download from it.
+    /// Those records won't be passed to `apply_incoming` again, so the
+    /// changeset passed there for this request will only contain the records
+    /// of pages that weren't taken.
+    ///
+    /// `high_water_mark` is the modified time of the last record in the page,
+    /// which is only meaningful if the request was sorted oldest first. Stores
+    /// that take the page can move its records out, instead of cloning them.
+    ///
+    /// The default implementation returns `false`, so all pages are kept in
+    /// memory and passed to `apply_incoming` once the download is complete.
+    fn stage_incoming(
+        &self,
+        _page: &mut IncomingChangeset,
+        _high_water_mark: ServerTimestamp,
+        _telem: &mut telemetry::Engine,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Works out what `apply_incoming` would do with `inbound`, without
+    /// changing anything. Stores usually do this by reconciling as normal,
+    /// inside a transaction that they roll back before returning. Dry runs
+    /// don't call `stage_incoming` or `sync_finished`, and nothing is
+    /// uploaded.
+    ///
+    /// The default implementation fails, for stores that don't support dry
+    /// runs.
+    fn apply_incoming_dry_run(
+        &self,
+        _inbound: Vec<IncomingChangeset>,
+        _telem: &mut telemetry::Engine,
+    ) -> Result<DryRun> {
+        anyhow::bail!(
+            "The {} store doesn't support dry runs",
+            self.collection_name()
+        )
+    }
+
+    /// Returns true if the store implements `validate`. Stores that don't
+    /// are skipped when validating, without downloading their records.
+    fn supports_validation(&self) -> bool {
+        false
+    }
+
+    /// Compares `records`, which are all the records in the collection on
+    /// the server, with the local data, and reports any problems. This
+    /// shouldn't change anything locally.
+    ///
+    /// The default implementation fails, for stores that don't have a
+    /// validator. Stores that override it should also override
+    /// `supports_validation`.
+    fn validate(&self, _records: Vec<(Payload, ServerTimestamp)>) -> Result<ValidationReport> {
+        anyhow::bail!(
+            "The {} store doesn't support validation",
+            self.collection_name()
+        )
+    }
+
     fn sync_finished(
         &self,
         new_timestamp: ServerTimestamp,
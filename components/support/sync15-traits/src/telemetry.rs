@@ -73,6 +73,16 @@ impl Stopwatch {
     }
 }
 
+impl Stopwatch {
+    /// How long it took, in milliseconds, or 0 if it hasn't finished.
+    fn took(&self) -> u64 {
+        match self {
+            Stopwatch::Started(_, _) => 0,
+            Stopwatch::Finished(c) => c.took,
+        }
+    }
+}
+
 impl Serialize for Stopwatch {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -385,6 +395,16 @@ impl EngineOutgoing {
     pub fn failed(&mut self, n: usize) {
         self.failed += n;
     }
+
+    #[inline]
+    pub fn get_sent(&self) -> usize {
+        self.sent
+    }
+
+    #[inline]
+    pub fn get_failed(&self) -> usize {
+        self.failed
+    }
 }
 
 /// One engine's sync.
@@ -407,6 +427,11 @@ pub struct Engine {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     validation: Option<Validation>,
+
+    // Events aren't part of the engine in the ping; they're moved to the
+    // ping's list of events when the sync is added to it.
+    #[serde(skip)]
+    events: Vec<Event>,
 }
 
 impl Engine {
@@ -418,12 +443,23 @@ impl Engine {
             outgoing: Vec::new(),
             failure: None,
             validation: None,
+            events: Vec::new(),
         }
     }
 
+    /// Records incoming telemetry. Stores which apply incoming records a page
+    /// at a time may call this more than once, in which case the counts are
+    /// added together.
     pub fn incoming(&mut self, inc: EngineIncoming) {
-        assert!(self.incoming.is_none());
-        self.incoming = Some(inc);
+        match &mut self.incoming {
+            Some(existing) => {
+                existing.applied += inc.applied;
+                existing.failed += inc.failed;
+                existing.new_failed += inc.new_failed;
+                existing.reconciled += inc.reconciled;
+            }
+            None => self.incoming = Some(inc),
+        }
     }
 
     pub fn outgoing(&mut self, out: EngineOutgoing) {
@@ -450,15 +486,47 @@ impl Engine {
         self.validation = Some(v);
     }
 
+    /// Records an event that happened while syncing this engine, like a
+    /// bookmark repair request.
+    pub fn event(&mut self, e: Event) {
+        self.events.push(e);
+    }
+
     fn finished(&mut self) {
         self.when_took = self.when_took.finished();
     }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// How long the engine took to sync, in milliseconds. This is 0 until
+    /// the engine is added to a `SyncTelemetry`.
+    pub fn get_took(&self) -> u64 {
+        self.when_took.took()
+    }
+
+    pub fn get_incoming(&self) -> Option<&EngineIncoming> {
+        self.incoming.as_ref()
+    }
+
+    /// Returns the outgoing counts for each batch that was posted.
+    pub fn get_outgoing(&self) -> &[EngineOutgoing] {
+        &self.outgoing
+    }
+
+    pub fn get_failure(&self) -> Option<&SyncFailure> {
+        self.failure.as_ref()
+    }
 }
 
 #[derive(Debug, Default, Serialize)]
 pub struct Validation {
     version: u32,
 
+    #[serde(skip_serializing_if = "crate::skip_if_default")]
+    checked: usize,
+
     #[serde(skip_serializing_if = "Vec::is_empty")]
     problems: Vec<Problem>,
 
@@ -481,6 +549,16 @@ impl Validation {
         }
         self
     }
+
+    pub fn checked(&mut self, checked: usize) -> &mut Self {
+        self.checked = checked;
+        self
+    }
+
+    pub fn failure(&mut self, failure: SyncFailure) -> &mut Self {
+        self.failure = Some(failure);
+        self
+    }
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -521,6 +599,24 @@ mod engine_tests {
         );
     }
 
+    #[test]
+    fn test_incoming_multiple() {
+        let mut e = Engine::new("TestEngine");
+        let mut i = EngineIncoming::new();
+        i.applied(1);
+        i.failed(2);
+        e.incoming(i);
+        let mut i = EngineIncoming::new();
+        i.applied(3);
+        i.reconciled(1);
+        e.incoming(i);
+        e.finished();
+        assert_json(
+            &e,
+            serde_json::json!({"name": "TestEngine", "when": 0.0, "incoming": {"applied": 4, "failed": 2, "reconciled": 1}}),
+        );
+    }
+
     #[test]
     fn test_outgoing() {
         let mut o = EngineOutgoing::new();
@@ -604,6 +700,14 @@ impl SyncTelemetry {
     pub fn finished(&mut self) {
         self.when_took = self.when_took.finished();
     }
+
+    pub fn get_engines(&self) -> &[Engine] {
+        &self.engines
+    }
+
+    pub fn get_failure(&self) -> Option<&SyncFailure> {
+        self.failure.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -734,12 +838,19 @@ impl SyncTelemetryPing {
 
     pub fn sync(&mut self, mut s: SyncTelemetry) {
         s.finished();
+        for engine in &mut s.engines {
+            self.events.append(&mut engine.events);
+        }
         self.syncs.push(s);
     }
 
     pub fn event(&mut self, e: Event) {
         self.events.push(e);
     }
+
+    pub fn get_syncs(&self) -> &[SyncTelemetry] {
+        &self.syncs
+    }
 }
 
 ffi_support::implement_into_ffi_by_json!(SyncTelemetryPing);
@@ -774,4 +885,32 @@ mod ping_tests {
             }),
         );
     }
+
+    #[test]
+    fn test_engine_events() {
+        let mut engine = Engine::new("bookmarks");
+        engine.event(Event::new("repair", "started").extra("flowID", "flow".into()));
+        let mut s = SyncTelemetry::new();
+        s.engine(engine);
+        let mut p = SyncTelemetryPing::new();
+        p.sync(s);
+        assert_json(
+            &p,
+            serde_json::json!({
+                "events": [{
+                    "object": "repair",
+                    "method": "started",
+                    "extra": {"flowID": "flow"},
+                }],
+                "syncs": [{
+                    "engines": [{
+                        "name": "bookmarks", "when": 0.0
+                    }],
+                    "when": 0.0
+                }],
+                "uid": null,
+                "version": 1
+            }),
+        );
+    }
 }
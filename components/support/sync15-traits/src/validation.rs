@@ -0,0 +1,172 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Types for reporting the differences between the records on the server and
+//! the local data. See `Store::validate`.
+
+use crate::{telemetry, Guid};
+
+/// A kind of problem found by a validator. The names match the ones that
+/// Desktop reports in the telemetry `validation` section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProblemKind {
+    /// A server record isn't listed in its parent's children, so other
+    /// clients can't tell where it goes.
+    Orphan,
+    /// A server record's parent isn't on the server.
+    MissingParent,
+    /// A server record lists a child that isn't on the server. The problem's
+    /// `id` is the child's.
+    MissingChild,
+    /// Following a server record's parents leads back to the record.
+    Cycle,
+    /// The server has more than one record with the same ID.
+    Duplicate,
+    /// A record that we think is on the server isn't there.
+    ServerMissing,
+    /// A record on the server doesn't exist locally.
+    ClientMissing,
+    /// A record on the server doesn't match the local record.
+    Difference,
+}
+
+impl ProblemKind {
+    /// All kinds, in the order they're reported.
+    pub const ALL: [ProblemKind; 8] = [
+        ProblemKind::Orphan,
+        ProblemKind::MissingParent,
+        ProblemKind::MissingChild,
+        ProblemKind::Cycle,
+        ProblemKind::Duplicate,
+        ProblemKind::ServerMissing,
+        ProblemKind::ClientMissing,
+        ProblemKind::Difference,
+    ];
+
+    /// The name to use for this kind of problem in telemetry.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProblemKind::Orphan => "orphans",
+            ProblemKind::MissingParent => "missingParents",
+            ProblemKind::MissingChild => "missingChildren",
+            ProblemKind::Cycle => "cycles",
+            ProblemKind::Duplicate => "duplicates",
+            ProblemKind::ServerMissing => "serverMissing",
+            ProblemKind::ClientMissing => "clientMissing",
+            ProblemKind::Difference => "differences",
+        }
+    }
+}
+
+/// A problem with one record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationProblem {
+    pub kind: ProblemKind,
+    pub id: Guid,
+    /// For `ProblemKind::Difference`, the names of the fields that don't
+    /// match. Empty for other kinds.
+    pub fields: Vec<&'static str>,
+}
+
+/// The result of `Store::validate`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// The version of the validator, so that we can tell telemetry from
+    /// older validators apart.
+    pub version: u32,
+    /// The number of server records that were checked.
+    pub checked: usize,
+    pub problems: Vec<ValidationProblem>,
+}
+
+impl ValidationReport {
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            ..Self::default()
+        }
+    }
+
+    /// Records a problem with the record `id`.
+    pub fn problem(&mut self, kind: ProblemKind, id: impl Into<Guid>) -> &mut Self {
+        self.problems.push(ValidationProblem {
+            kind,
+            id: id.into(),
+            fields: Vec::new(),
+        });
+        self
+    }
+
+    /// Records that the record `id` on the server doesn't match the local
+    /// record. Does nothing if `fields` is empty.
+    pub fn difference(&mut self, id: impl Into<Guid>, fields: Vec<&'static str>) -> &mut Self {
+        if !fields.is_empty() {
+            self.problems.push(ValidationProblem {
+                kind: ProblemKind::Difference,
+                id: id.into(),
+                fields,
+            });
+        }
+        self
+    }
+
+    /// Returns the number of problems of this kind.
+    pub fn count(&self, kind: ProblemKind) -> usize {
+        self.problems.iter().filter(|p| p.kind == kind).count()
+    }
+
+    /// Returns the IDs of the records with problems of this kind.
+    pub fn ids(&self, kind: ProblemKind) -> Vec<&Guid> {
+        self.problems
+            .iter()
+            .filter(|p| p.kind == kind)
+            .map(|p| &p.id)
+            .collect()
+    }
+
+    /// Returns `true` if the server and local data agree.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Summarizes the report for the telemetry `validation` section.
+    pub fn to_telemetry(&self) -> telemetry::Validation {
+        let mut validation = telemetry::Validation::with_version(self.version);
+        validation.checked(self.checked);
+        for kind in ProblemKind::ALL.iter() {
+            validation.problem(kind.name(), self.count(*kind));
+        }
+        validation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_telemetry() {
+        let mut report = ValidationReport::new(1);
+        report.checked = 3;
+        report
+            .problem(ProblemKind::Orphan, "aaaaaaaaaaaa")
+            .problem(ProblemKind::Orphan, "bbbbbbbbbbbb")
+            .difference("cccccccccccc", vec!["title", "url"])
+            .difference("dddddddddddd", vec![]);
+        assert!(!report.is_ok());
+        assert_eq!(report.count(ProblemKind::Orphan), 2);
+        assert_eq!(report.count(ProblemKind::Difference), 1);
+        assert_eq!(
+            serde_json::to_value(&report.to_telemetry()).unwrap(),
+            serde_json::json!({
+                "version": 1,
+                "checked": 3,
+                "problems": [
+                    {"name": "orphans", "count": 2},
+                    {"name": "differences", "count": 1},
+                ],
+            })
+        );
+    }
+}
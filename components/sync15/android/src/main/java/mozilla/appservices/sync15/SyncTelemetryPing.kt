@@ -298,13 +298,14 @@ data class OutgoingInfo(
 
 data class ValidationInfo(
     val version: Int,
+    val checked: Int,
     val problems: List<ProblemInfo>,
     val failureReason: FailureReason?
 ) {
     companion object {
         fun fromJSON(jsonObject: JSONObject): ValidationInfo {
             val problems = unwrapFromJSON(jsonObject) {
-                it.getJSONArray("outgoing")
+                it.getJSONArray("problems")
             }?.let {
                 ProblemInfo.fromJSONArray(it)
             } ?: emptyList()
@@ -315,6 +316,7 @@ data class ValidationInfo(
             }
             return ValidationInfo(
                 version = jsonObject.getInt("version"),
+                checked = intOrZero(jsonObject, "checked"),
                 problems = problems,
                 failureReason = failureReason
             )
@@ -324,6 +326,9 @@ data class ValidationInfo(
     fun toJSON(): JSONObject {
         var result = JSONObject()
         result.put("version", version)
+        if (checked > 0) {
+            result.put("checked", checked)
+        }
         if (!problems.isEmpty()) {
             result.put("problems", JSONArray().apply {
                 problems.forEach {
@@ -332,7 +337,7 @@ data class ValidationInfo(
             })
         }
         failureReason?.let {
-            result.put("failueReason", it.toJSON())
+            result.put("failureReason", it.toJSON())
         }
         return result
     }
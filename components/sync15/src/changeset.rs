@@ -9,6 +9,7 @@ use crate::key_bundle::KeyBundle;
 use crate::request::{CollectionRequest, NormalResponseHandler, UploadInfo};
 use crate::util::ServerTimestamp;
 use crate::CollState;
+use interrupt_support::Interruptee;
 use std::borrow::Cow;
 
 pub use sync15_traits::{IncomingChangeset, OutgoingChangeset, RecordChangeset};
@@ -55,6 +56,81 @@ pub fn fetch_incoming(
     Ok(result)
 }
 
+/// Fetches the records for a paged collection request a page at a time,
+/// following the server's `X-Weave-Next-Offset` header until there are no
+/// more pages. Each decrypted page is passed to `on_page` as it arrives, along
+/// with the modified time of the last record in the page, so that callers can
+/// persist it as a high-water mark.
+///
+/// Offsets are only valid while the collection stays the same, so every page
+/// after the first is requested with `X-If-Unmodified-Since` set to the
+/// collection's last modified time from the first page. If another client
+/// writes to the collection in the meantime, the server fails the request with
+/// a 412, and we abort the download. Pages that were already passed to
+/// `on_page` are kept, so stores that persisted the high-water mark pick up
+/// from there on the next sync.
+///
+/// Returns the collection's last modified time, as reported by the server.
+pub fn fetch_incoming_paged(
+    client: &Sync15StorageClient,
+    state: &mut CollState,
+    collection_request: &CollectionRequest,
+    interruptee: &dyn Interruptee,
+    mut on_page: impl FnMut(IncomingChangeset, ServerTimestamp) -> Result<()>,
+) -> Result<ServerTimestamp> {
+    let collection = collection_request.collection.clone();
+    let mut request = collection_request.clone();
+    let mut high_water_mark = collection_request.newer.unwrap_or_default();
+    let mut num_pages = 0;
+    let mut unmodified_since = None;
+    loop {
+        interruptee.err_if_interrupted()?;
+        let (resp, next_offset) = client.get_encrypted_records_page(&request, unmodified_since)?;
+        let (records, timestamp) = match resp {
+            Sync15ClientResponse::Success {
+                record,
+                last_modified,
+                ..
+            } => (record, last_modified),
+            other @ Sync15ClientResponse::Error(ErrorResponse::PreconditionFailed { .. }) => {
+                log::warn!(
+                    "{} changed after downloading {} pages; aborting the download",
+                    collection,
+                    num_pages
+                );
+                return Err(other.create_storage_error().into());
+            }
+            other => return Err(other.create_storage_error().into()),
+        };
+        unmodified_since.get_or_insert(timestamp);
+        num_pages += 1;
+        let mut page = IncomingChangeset::new(collection.clone(), timestamp);
+        page.changes.reserve(records.len());
+        for record in records {
+            // See `fetch_incoming` for why we don't handle HMAC errors here.
+            let decrypted = record.decrypt(&state.key)?;
+            if decrypted.modified > high_water_mark {
+                high_water_mark = decrypted.modified;
+            }
+            page.changes.push(decrypted.into_timestamped_payload());
+        }
+        log::debug!(
+            "Downloaded page {} of {} ({} records)",
+            num_pages,
+            collection,
+            page.changes.len()
+        );
+        on_page(page, high_water_mark)?;
+        match next_offset {
+            Some(offset) => request = request.offset(Some(offset)),
+            None => {
+                state.last_modified = timestamp;
+                return Ok(timestamp);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CollectionUpdate<'a> {
     client: &'a Sync15StorageClient,
@@ -113,8 +189,9 @@ impl<'a> CollectionUpdate<'a> {
     }
 
     /// Returns a list of the IDs that failed if allowed_dropped_records is true, otherwise
-    /// returns an empty vec.
-    pub fn upload(self) -> error::Result<UploadInfo> {
+    /// returns an empty vec. Checks `interruptee` before each record is queued,
+    /// so an interrupted upload stops between batches.
+    pub fn upload(self, interruptee: &dyn Interruptee) -> error::Result<UploadInfo> {
         let mut failed = vec![];
         let mut q = self.client.new_post_queue(
             &self.collection,
@@ -124,6 +201,7 @@ impl<'a> CollectionUpdate<'a> {
         )?;
 
         for record in self.to_update.into_iter() {
+            interruptee.err_if_interrupted()?;
             let enqueued = q.enqueue(&record)?;
             if !enqueued && self.fully_atomic {
                 return Err(ErrorKind::RecordTooLargeError.into());
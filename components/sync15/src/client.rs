@@ -36,7 +36,7 @@ pub enum Sync15ClientResponse<T> {
 fn parse_seconds(seconds_str: &str) -> Option<u32> {
     let secs = seconds_str.parse::<f64>().ok()?.ceil();
     // Note: u32 doesn't impl TryFrom<f64> :(
-    if !secs.is_finite() || secs < 0.0 || secs >= f64::from(u32::max_value()) {
+    if secs.is_finite() && secs >= 0.0 && secs < f64::from(u32::max_value()) {
         Some(secs as u32)
     } else {
         log::warn!("invalid backoff value: {}", secs);
@@ -285,6 +285,29 @@ impl Sync15StorageClient {
         self.collection_request(Method::Get, collection_request)
     }
 
+    /// Fetches a single page of records for a paged collection request,
+    /// returning the response along with the offset of the next page, if the
+    /// server has more records. If `xius` is given, the server fails the
+    /// request with a 412 if the collection changed since then.
+    pub fn get_encrypted_records_page(
+        &self,
+        collection_request: &CollectionRequest,
+        xius: Option<ServerTimestamp>,
+    ) -> error::Result<(Sync15ClientResponse<Vec<EncryptedBso>>, Option<String>)> {
+        let url = collection_request.build_url(Url::parse(&self.tsc.api_endpoint()?)?)?;
+        let mut req = self.build_request(Method::Get, url)?;
+        if let Some(xius) = xius {
+            req = req.header(header_names::X_IF_UNMODIFIED_SINCE, format!("{}", xius))?;
+        }
+        let resp = self.send_request(req)?;
+        let next_offset = resp
+            .headers
+            .get(header_names::X_WEAVE_NEXT_OFFSET)
+            .map(ToString::to_string);
+        let result = Sync15ClientResponse::from_response(resp, &self.backoff)?;
+        Ok((result, next_offset))
+    }
+
     #[inline]
     fn authorized(&self, req: Request) -> error::Result<Request> {
         let hawk_header_value = self.tsc.authorization(&req)?;
@@ -311,6 +334,16 @@ impl Sync15StorageClient {
         self.exec_request(self.build_request(method, url)?, false)
     }
 
+    fn send_request(&self, req: Request) -> error::Result<Response> {
+        log::trace!(
+            "request: {} {} ({:?})",
+            req.method,
+            req.url.path(),
+            req.url.query()
+        );
+        Ok(req.send()?)
+    }
+
     fn exec_request<T>(
         &self,
         req: Request,
@@ -319,14 +352,7 @@ impl Sync15StorageClient {
     where
         for<'a> T: serde::de::Deserialize<'a>,
     {
-        log::trace!(
-            "request: {} {} ({:?})",
-            req.method,
-            req.url.path(),
-            req.url.query()
-        );
-        let resp = req.send()?;
-
+        let resp = self.send_request(req)?;
         let result = Sync15ClientResponse::from_response(resp, &self.backoff)?;
         match result {
             Sync15ClientResponse::Success { .. } => Ok(result),
@@ -449,4 +475,43 @@ mod test {
         // Compile will fail if not send.
         ensure_send::<Sync15StorageClient>();
     }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds("0"), Some(0));
+        assert_eq!(parse_seconds("30"), Some(30));
+        assert_eq!(parse_seconds("1.5"), Some(2));
+        assert_eq!(parse_seconds("-1"), None);
+        assert_eq!(parse_seconds("inf"), None);
+        assert_eq!(parse_seconds("1e100"), None);
+        assert_eq!(parse_seconds("soon"), None);
+    }
+
+    #[test]
+    fn test_backoff_headers() {
+        let mut headers = viaduct::Headers::new();
+        headers
+            .insert(header_names::X_WEAVE_BACKOFF, "600")
+            .unwrap()
+            .insert(header_names::RETRY_AFTER, "30.5")
+            .unwrap();
+        let resp = Response {
+            request_method: Method::Get,
+            url: Url::parse("https://example.com/1.5/123/storage/bookmarks").unwrap(),
+            status: 503,
+            headers,
+            body: Vec::new(),
+        };
+        let backoff = new_backoff_listener();
+        let result = Sync15ClientResponse::<serde_json::Value>::from_response(resp, &backoff)
+            .expect("Should handle the response");
+        match result {
+            Sync15ClientResponse::Error(ErrorResponse::ServerError { status, .. }) => {
+                assert_eq!(status, 503)
+            }
+            _ => panic!("Should be a server error"),
+        }
+        assert_eq!(backoff.get_backoff_secs(), 600);
+        assert_eq!(backoff.retry_after_secs.load(Ordering::SeqCst), 31);
+    }
 }
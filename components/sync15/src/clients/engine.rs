@@ -33,6 +33,8 @@ struct Driver<'a> {
     interruptee: &'a dyn Interruptee,
     config: &'a InfoConfiguration,
     recent_clients: HashMap<String, RemoteClient>,
+    /// Commands that we added to other clients' records, keyed by client ID.
+    sent_commands: HashMap<String, HashSet<Command>>,
 }
 
 impl<'a> Driver<'a> {
@@ -46,6 +48,7 @@ impl<'a> Driver<'a> {
             interruptee,
             config,
             recent_clients: HashMap::new(),
+            sent_commands: HashMap::new(),
         }
     }
 
@@ -63,6 +66,8 @@ impl<'a> Driver<'a> {
 
         self.interruptee.err_if_interrupted()?;
         let outgoing_commands = self.command_processor.fetch_outgoing_commands()?;
+        let mut outgoing_client_commands =
+            self.command_processor.fetch_outgoing_client_commands()?;
 
         let mut has_own_client_record = false;
 
@@ -135,7 +140,11 @@ impl<'a> Driver<'a> {
 
                 // Bail if we don't have any outgoing commands to write into
                 // the other client's record.
-                if outgoing_commands.is_empty() {
+                let mut commands_for_client = outgoing_commands.clone();
+                if let Some(commands) = outgoing_client_commands.remove(&client.id) {
+                    commands_for_client.extend(commands);
+                }
+                if commands_for_client.is_empty() {
                     continue;
                 }
 
@@ -146,16 +155,19 @@ impl<'a> Driver<'a> {
                     .iter()
                     .filter_map(|c| c.as_command())
                     .collect();
-                let mut new_outgoing_commands = outgoing_commands
+                let mut new_outgoing_commands = commands_for_client
                     .difference(&current_commands)
                     .cloned()
                     .collect::<Vec<_>>();
                 // Sort, to ensure deterministic ordering for tests.
                 new_outgoing_commands.sort();
                 let mut new_client = client.clone();
-                new_client
-                    .commands
-                    .extend(new_outgoing_commands.into_iter().map(CommandRecord::from));
+                new_client.commands.extend(
+                    new_outgoing_commands
+                        .iter()
+                        .cloned()
+                        .map(CommandRecord::from),
+                );
                 if new_client.commands.len() == client.commands.len() {
                     continue;
                 }
@@ -168,6 +180,22 @@ impl<'a> Driver<'a> {
                     self.memcache_max_record_payload_size(),
                 )?;
 
+                // Remember which of our commands survived the truncation, so
+                // that we can tell the command processor once they're
+                // uploaded.
+                new_outgoing_commands.truncate(
+                    new_client
+                        .commands
+                        .len()
+                        .saturating_sub(client.commands.len()),
+                );
+                if !new_outgoing_commands.is_empty() {
+                    self.sent_commands.insert(
+                        client.id.clone(),
+                        new_outgoing_commands.into_iter().collect(),
+                    );
+                }
+
                 // We want to ensure the TTL for all records we write, which
                 // may not be true for incoming ones - so make sure it is.
                 new_client.ttl = CLIENTS_TTL;
@@ -175,6 +203,10 @@ impl<'a> Driver<'a> {
             }
         }
 
+        for client_id in outgoing_client_commands.keys() {
+            log::warn!("Not sending commands to unknown client {}", client_id);
+        }
+
         // Upload a record for our own client, if we didn't replace it already.
         if !has_own_client_record {
             let current_client_record = self.current_client_record();
@@ -232,6 +264,9 @@ pub struct Engine<'a> {
     pub command_processor: &'a dyn CommandProcessor,
     pub interruptee: &'a dyn Interruptee,
     pub recent_clients: HashMap<String, RemoteClient>,
+    /// Commands that we added to other clients' records during the last
+    /// `apply_incoming`, keyed by client ID.
+    sent_commands: HashMap<String, HashSet<Command>>,
 }
 
 impl<'a> Engine<'a> {
@@ -245,6 +280,7 @@ impl<'a> Engine<'a> {
             command_processor,
             interruptee,
             recent_clients: HashMap::new(),
+            sent_commands: HashMap::new(),
         }
     }
 
@@ -287,21 +323,15 @@ impl<'a> Engine<'a> {
 
         let inbound = self.fetch_incoming(&storage_client, &mut coll_state)?;
 
-        let mut driver = Driver::new(
-            self.command_processor,
-            self.interruptee,
-            &global_state.config,
-        );
-
-        let outgoing = driver.sync(inbound, should_refresh_client)?;
-        self.recent_clients = driver.recent_clients;
+        let outgoing = self.apply_incoming(inbound, &global_state.config, should_refresh_client)?;
+        let mut sent_commands = std::mem::take(&mut self.sent_commands);
 
         coll_state.last_modified = outgoing.timestamp;
 
         self.interruptee.err_if_interrupted()?;
         let upload_info =
             CollectionUpdate::new_from_changeset(&storage_client, &coll_state, outgoing, true)?
-                .upload()?;
+                .upload(self.interruptee)?;
 
         log::info!(
             "Upload success ({} records success, {} records failed)",
@@ -309,10 +339,38 @@ impl<'a> Engine<'a> {
             upload_info.failed_ids.len()
         );
 
+        // Commands in records that the server rejected weren't sent.
+        sent_commands.retain(|client_id, _| {
+            upload_info
+                .successful_ids
+                .iter()
+                .any(|id| id.as_str() == client_id.as_str())
+        });
+        if !sent_commands.is_empty() {
+            self.command_processor.commands_sent(&sent_commands)?;
+        }
+
         log::info!("Finished syncing clients");
         Ok(())
     }
 
+    /// Applies incoming client records, including any commands for our own
+    /// client, and returns the records to upload. This doesn't talk to the
+    /// server, so stores can also use it to test how they handle commands
+    /// from other clients.
+    pub fn apply_incoming(
+        &mut self,
+        inbound: IncomingChangeset,
+        config: &InfoConfiguration,
+        should_refresh_client: bool,
+    ) -> Result<OutgoingChangeset> {
+        let mut driver = Driver::new(self.command_processor, self.interruptee, config);
+        let outgoing = driver.sync(inbound, should_refresh_client)?;
+        self.recent_clients = driver.recent_clients;
+        self.sent_commands = driver.sent_commands;
+        Ok(outgoing)
+    }
+
     fn fetch_incoming(
         &self,
         storage_client: &Sync15StorageClient,
@@ -345,7 +403,7 @@ impl<'a> Engine<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::clients::{CommandStatus, DeviceType, Settings};
+    use crate::clients::{CommandStatus, DeviceType, RepairRequest, Settings};
     use crate::util::ServerTimestamp;
     use anyhow::Result;
     use interrupt_support::NeverInterrupts;
@@ -356,6 +414,7 @@ mod tests {
     struct TestProcessor {
         settings: Settings,
         outgoing_commands: HashSet<Command>,
+        outgoing_client_commands: HashMap<String, HashSet<Command>>,
     }
 
     impl CommandProcessor for TestProcessor {
@@ -364,20 +423,26 @@ mod tests {
         }
 
         fn apply_incoming_command(&self, command: Command) -> Result<CommandStatus> {
-            Ok(if let Command::Reset(name) = command {
-                if name == "forms" {
-                    CommandStatus::Unsupported
-                } else {
-                    CommandStatus::Applied
+            Ok(match command {
+                Command::Reset(name) => {
+                    if name == "forms" {
+                        CommandStatus::Unsupported
+                    } else {
+                        CommandStatus::Applied
+                    }
                 }
-            } else {
-                CommandStatus::Ignored
+                Command::DisplayUri { .. } | Command::Custom { .. } => CommandStatus::Unsupported,
+                _ => CommandStatus::Ignored,
             })
         }
 
         fn fetch_outgoing_commands(&self) -> Result<HashSet<Command>> {
             Ok(self.outgoing_commands.clone())
         }
+
+        fn fetch_outgoing_client_commands(&self) -> Result<HashMap<String, HashSet<Command>>> {
+            Ok(self.outgoing_client_commands.clone())
+        }
     }
 
     fn inbound_from_clients(clients: Value) -> IncomingChangeset {
@@ -411,6 +476,7 @@ mod tests {
             .iter()
             .cloned()
             .collect(),
+            outgoing_client_commands: HashMap::new(),
         };
 
         let config = InfoConfiguration::default();
@@ -544,6 +610,127 @@ mod tests {
         } else {
             unreachable!("`expected_clients` must be an array of client records")
         }
+
+        let mut expected_sent = HashMap::new();
+        expected_sent.insert(
+            "deviceBBBBBB".to_string(),
+            [Command::Wipe("bookmarks".into())]
+                .iter()
+                .cloned()
+                .collect::<HashSet<_>>(),
+        );
+        expected_sent.insert(
+            "deviceCCCCCC".to_string(),
+            [
+                Command::Wipe("bookmarks".into()),
+                Command::Reset("history".into()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+        );
+        assert_eq!(driver.sent_commands, expected_sent);
+    }
+
+    #[test]
+    fn test_clients_sync_client_commands() {
+        let request = RepairRequest {
+            collection: "bookmarks".into(),
+            request: "upload".into(),
+            requestor: "deviceAAAAAA".into(),
+            ids: vec!["bookmarkAAAA".into()],
+            flow_id: "flooooooooow".into(),
+        };
+        let mut outgoing_client_commands = HashMap::new();
+        outgoing_client_commands.insert(
+            "deviceBBBBBB".to_string(),
+            [Command::RepairRequest(request.clone())]
+                .iter()
+                .cloned()
+                .collect::<HashSet<_>>(),
+        );
+        outgoing_client_commands.insert(
+            "deviceCCCCCC".to_string(),
+            [Command::Custom {
+                name: "logout".into(),
+                args: Vec::new(),
+                flow_id: None,
+            }]
+            .iter()
+            .cloned()
+            .collect(),
+        );
+        outgoing_client_commands.insert(
+            "deviceDDDDDD".to_string(),
+            [Command::ResetAll].iter().cloned().collect(),
+        );
+        let processor = TestProcessor {
+            settings: Settings {
+                fxa_device_id: "deviceAAAAAA".into(),
+                device_name: "Laptop".into(),
+                device_type: DeviceType::Desktop,
+            },
+            outgoing_commands: HashSet::new(),
+            outgoing_client_commands,
+        };
+
+        let config = InfoConfiguration::default();
+
+        let mut driver = Driver::new(&processor, &NeverInterrupts, &config);
+
+        let inbound = inbound_from_clients(json!([{
+            "id": "deviceBBBBBB",
+            "name": "iPhone",
+            "type": "mobile",
+            "commands": [],
+            "fxaDeviceId": "iPhooooooone",
+            "protocols": ["1.5"],
+            "ttl": CLIENTS_TTL,
+        }, {
+            "id": "deviceCCCCCC",
+            "name": "Fenix",
+            "type": "mobile",
+            "commands": [{
+                "command": "logout",
+                "args": [],
+            }],
+            "fxaDeviceId": "deviceCCCCCC",
+            "ttl": CLIENTS_TTL,
+        }, {
+            "id": "deviceAAAAAA",
+            "name": "Laptop",
+            "type": "desktop",
+            "commands": [],
+            "fxaDeviceId": "deviceAAAAAA",
+            "protocols": ["1.5"],
+            "ttl": CLIENTS_TTL,
+        }]));
+
+        let outgoing = driver.sync(inbound, false).expect("Should sync clients");
+
+        // Fenix already has our command, and the client we don't know about
+        // doesn't get a record, so we should only write the repair request
+        // into the iPhone's record.
+        assert_eq!(outgoing.changes.len(), 1);
+        let record: ClientRecord = outgoing.changes[0].clone().into_record().unwrap();
+        assert_eq!(record.id, "deviceBBBBBB");
+        assert_eq!(record.commands.len(), 1);
+        assert_eq!(record.commands[0].name, "repairRequest");
+        assert_eq!(record.commands[0].flow_id.as_deref(), Some("flooooooooow"));
+        assert_eq!(
+            record.commands[0].as_command(),
+            Some(Command::RepairRequest(request.clone()))
+        );
+
+        let mut expected_sent = HashMap::new();
+        expected_sent.insert(
+            "deviceBBBBBB".to_string(),
+            [Command::RepairRequest(request)]
+                .iter()
+                .cloned()
+                .collect::<HashSet<_>>(),
+        );
+        assert_eq!(driver.sent_commands, expected_sent);
     }
 
     #[test]
@@ -555,6 +742,7 @@ mod tests {
                 device_type: DeviceType::Desktop,
             },
             outgoing_commands: [].iter().cloned().collect(),
+            outgoing_client_commands: HashMap::new(),
         };
 
         let config = InfoConfiguration::default();
@@ -624,6 +812,7 @@ mod tests {
                 device_type: DeviceType::Desktop,
             },
             outgoing_commands: HashSet::new(),
+            outgoing_client_commands: HashMap::new(),
         };
 
         let config = InfoConfiguration::default();
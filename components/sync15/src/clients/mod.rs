@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 mod engine;
 mod record;
@@ -10,14 +10,17 @@ mod ser;
 
 use anyhow::Result;
 pub use engine::Engine;
-pub use sync15_traits::client::{ClientData, DeviceType, RemoteClient};
+pub use sync15_traits::client::{
+    ClientData, Command, CommandStatus, DeviceType, RemoteClient, RepairRequest, RepairResponse,
+};
 
 // These are what desktop uses.
 const CLIENTS_TTL: u32 = 1_814_400; // 21 days
 pub(crate) const CLIENTS_TTL_REFRESH: u64 = 604_800; // 7 days
 
 /// A command processor applies incoming commands like wipes and resets for all
-/// stores, and returns commands to send to other clients. It also manages
+/// stores, and returns commands to send to other clients. Stores can also
+/// send each other commands through it, like requests to repair bookmarks. It also manages
 /// settings like the device name and type, which is stored in the special
 /// `clients` collection.
 ///
@@ -34,6 +37,22 @@ pub trait CommandProcessor {
     /// commands couldn't be fetched, and halts the sync.
     fn fetch_outgoing_commands(&self) -> Result<HashSet<Command>>;
 
+    /// Fetches commands to send to specific clients, keyed by client ID.
+    /// Commands for clients that aren't in the clients collection are
+    /// dropped. An error return value halts the sync.
+    fn fetch_outgoing_client_commands(&self) -> Result<HashMap<String, HashSet<Command>>> {
+        Ok(HashMap::new())
+    }
+
+    /// Called after we upload commands to other clients, with the commands
+    /// that the server accepted, keyed by client ID. This includes commands
+    /// from both `fetch_outgoing_commands` and
+    /// `fetch_outgoing_client_commands`, but not commands that were already
+    /// in the other clients' records.
+    fn commands_sent(&self, _sent: &HashMap<String, HashSet<Command>>) -> Result<()> {
+        Ok(())
+    }
+
     /// Applies a command sent to this client from another client. This method
     /// should return a `CommandStatus` indicating whether the command was
     /// processed.
@@ -45,18 +64,6 @@ pub trait CommandProcessor {
     fn apply_incoming_command(&self, command: Command) -> Result<CommandStatus>;
 }
 
-/// Indicates if a command was applied successfully, ignored, or not supported.
-/// Applied and ignored commands are removed from our client record, and never
-/// retried. Unsupported commands are put back into our record, and retried on
-/// subsequent syncs. This is to handle clients adding support for new data
-/// types.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-pub enum CommandStatus {
-    Applied,
-    Ignored,
-    Unsupported,
-}
-
 impl From<&record::ClientRecord> for RemoteClient {
     fn from(record: &record::ClientRecord) -> RemoteClient {
         RemoteClient {
@@ -80,15 +87,3 @@ pub struct Settings {
     /// The type of this client: mobile, tablet, desktop, or other.
     pub device_type: DeviceType,
 }
-
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub enum Command {
-    /// Erases all local data.
-    WipeAll,
-    /// Erases all local data for a specific engine.
-    Wipe(String),
-    /// Resets local sync state for all engines.
-    ResetAll,
-    /// Resets local sync state for a specific engine.
-    Reset(String),
-}
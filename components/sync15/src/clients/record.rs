@@ -3,11 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use serde_derive::*;
+use serde_json::Value;
 
-use super::Command;
+use super::{Command, RepairRequest, RepairResponse};
 
 /// The serialized form of a client record.
-#[derive(Clone, Debug, Eq, Deserialize, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClientRecord {
     #[serde(rename = "id")]
@@ -66,7 +67,7 @@ pub struct ClientRecord {
 }
 
 /// The serialized form of a client command.
-#[derive(Clone, Debug, Eq, Deserialize, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CommandRecord {
     /// The command name. This is a string, not an enum, because we want to
@@ -74,30 +75,58 @@ pub struct CommandRecord {
     #[serde(rename = "command")]
     pub name: String,
 
-    /// Extra, command-specific arguments. Note that we must send an empty
-    /// array if the command expects no arguments.
+    /// Extra, command-specific arguments. Most commands take strings, but
+    /// repair commands take objects. Note that we must send an empty array if
+    /// the command expects no arguments.
     #[serde(default)]
-    pub args: Vec<String>,
+    pub args: Vec<Value>,
 
-    /// Some commands, like repair, send a "flow ID" that other cliennts can
-    /// record in their telemetry. We don't currently send commands with
-    /// flow IDs, but we round-trip them.
+    /// Some commands, like repair, send a "flow ID" that other clients can
+    /// record in their telemetry.
     #[serde(default, rename = "flowID", skip_serializing_if = "Option::is_none")]
     pub flow_id: Option<String>,
 }
 
 impl CommandRecord {
-    /// Converts a serialized command into one that we can apply. Returns `None`
-    /// if we don't support the command.
+    /// Converts a serialized command into one that we can apply. Commands
+    /// that we don't know about are returned as `Command::Custom`. Returns
+    /// `None` if the command is malformed, like a `wipeEngine` without an
+    /// engine name.
     pub fn as_command(&self) -> Option<Command> {
         match self.name.as_str() {
-            "wipeEngine" => self.args.get(0).map(|e| Command::Wipe(e.into())),
+            "wipeEngine" => self.string_arg(0).map(|e| Command::Wipe(e.into())),
             "wipeAll" => Some(Command::WipeAll),
-            "resetEngine" => self.args.get(0).map(|e| Command::Reset(e.into())),
+            "resetEngine" => self.string_arg(0).map(|e| Command::Reset(e.into())),
             "resetAll" => Some(Command::ResetAll),
-            _ => None,
+            "displayURI" => match (self.string_arg(0), self.string_arg(1), self.string_arg(2)) {
+                (Some(uri), Some(sender_id), Some(title)) => Some(Command::DisplayUri {
+                    uri: uri.into(),
+                    sender_id: sender_id.into(),
+                    title: title.into(),
+                }),
+                _ => None,
+            },
+            "repairRequest" => self
+                .args
+                .get(0)
+                .and_then(|arg| serde_json::from_value::<RepairRequest>(arg.clone()).ok())
+                .map(Command::RepairRequest),
+            "repairResponse" => self
+                .args
+                .get(0)
+                .and_then(|arg| serde_json::from_value::<RepairResponse>(arg.clone()).ok())
+                .map(Command::RepairResponse),
+            _ => Some(Command::Custom {
+                name: self.name.clone(),
+                args: self.args.iter().map(Value::to_string).collect(),
+                flow_id: self.flow_id.clone(),
+            }),
         }
     }
+
+    fn string_arg(&self, index: usize) -> Option<&str> {
+        self.args.get(index).and_then(Value::as_str)
+    }
 }
 
 impl From<Command> for CommandRecord {
@@ -105,7 +134,7 @@ impl From<Command> for CommandRecord {
         match command {
             Command::Wipe(engine) => CommandRecord {
                 name: "wipeEngine".into(),
-                args: vec![engine],
+                args: vec![engine.into()],
                 flow_id: None,
             },
             Command::WipeAll => CommandRecord {
@@ -115,7 +144,7 @@ impl From<Command> for CommandRecord {
             },
             Command::Reset(engine) => CommandRecord {
                 name: "resetEngine".into(),
-                args: vec![engine],
+                args: vec![engine.into()],
                 flow_id: None,
             },
             Command::ResetAll => CommandRecord {
@@ -123,6 +152,39 @@ impl From<Command> for CommandRecord {
                 args: Vec::new(),
                 flow_id: None,
             },
+            Command::DisplayUri {
+                uri,
+                sender_id,
+                title,
+            } => CommandRecord {
+                name: "displayURI".into(),
+                args: vec![uri.into(), sender_id.into(), title.into()],
+                flow_id: None,
+            },
+            // Desktop expects repair commands to have a single argument,
+            // which is the request or response object.
+            Command::RepairRequest(request) => CommandRecord {
+                name: "repairRequest".into(),
+                flow_id: Some(request.flow_id.clone()),
+                args: vec![serde_json::json!(request)],
+            },
+            Command::RepairResponse(response) => CommandRecord {
+                name: "repairResponse".into(),
+                flow_id: Some(response.flow_id.clone()),
+                args: vec![serde_json::json!(response)],
+            },
+            Command::Custom {
+                name,
+                args,
+                flow_id,
+            } => CommandRecord {
+                name,
+                args: args
+                    .into_iter()
+                    .map(|arg| serde_json::from_str(&arg).unwrap_or(Value::String(arg)))
+                    .collect(),
+                flow_id,
+            },
         }
     }
 }
@@ -155,4 +217,80 @@ mod tests {
         let bso = crate::CleartextBso::from_payload(p, "clients");
         assert_eq!(bso.ttl, Some(123));
     }
+
+    #[test]
+    fn test_command_round_trip() {
+        let request = RepairRequest {
+            collection: "bookmarks".into(),
+            request: "upload".into(),
+            requestor: "deviceAAAAAA".into(),
+            ids: vec!["bookmarkAAAA".into(), "bookmarkBBBB".into()],
+            flow_id: "flooooooooow".into(),
+        };
+        let commands = vec![
+            Command::Wipe("bookmarks".into()),
+            Command::ResetAll,
+            Command::DisplayUri {
+                uri: "https://example.com".into(),
+                sender_id: "deviceBBBBBB".into(),
+                title: "Example".into(),
+            },
+            Command::RepairRequest(request.clone()),
+            Command::RepairResponse(RepairResponse {
+                collection: "bookmarks".into(),
+                request: "upload".into(),
+                client_id: "deviceBBBBBB".into(),
+                ids: vec!["bookmarkAAAA".into()],
+                flow_id: "flooooooooow".into(),
+            }),
+            Command::Custom {
+                name: "logout".into(),
+                args: Vec::new(),
+                flow_id: Some("flooooooooow".into()),
+            },
+            Command::Custom {
+                name: "future".into(),
+                args: vec![r#""uri""#.into(), r#"{"some":["object"]}"#.into()],
+                flow_id: None,
+            },
+        ];
+        for command in commands {
+            let record = CommandRecord::from(command.clone());
+            assert_eq!(record.flow_id.as_deref(), command.flow_id());
+            assert_eq!(record.as_command(), Some(command));
+        }
+
+        // Desktop sends repair requests as an object argument.
+        let record: CommandRecord = serde_json::from_value(serde_json::json!({
+            "command": "repairRequest",
+            "args": [{
+                "collection": "bookmarks",
+                "request": "upload",
+                "requestor": "deviceAAAAAA",
+                "ids": ["bookmarkAAAA", "bookmarkBBBB"],
+                "flowID": "flooooooooow",
+            }],
+            "flowID": "flooooooooow",
+        }))
+        .unwrap();
+        assert_eq!(record.as_command(), Some(Command::RepairRequest(request)));
+        assert_eq!(
+            serde_json::to_value(&record).unwrap()["args"][0]["requestor"],
+            "deviceAAAAAA"
+        );
+
+        // Malformed commands can't be applied.
+        let record = CommandRecord {
+            name: "repairRequest".into(),
+            args: vec!["not an object".into()],
+            flow_id: None,
+        };
+        assert_eq!(record.as_command(), None);
+        let record = CommandRecord {
+            name: "wipeEngine".into(),
+            args: vec![serde_json::json!(["bookmarks"])],
+            flow_id: None,
+        };
+        assert_eq!(record.as_command(), None);
+    }
 }
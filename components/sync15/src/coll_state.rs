@@ -47,6 +47,7 @@ pub enum LocalCollState {
 pub struct LocalCollStateMachine<'state> {
     global_state: &'state GlobalState,
     root_key: &'state KeyBundle,
+    allow_reset: bool,
 }
 
 impl<'state> LocalCollStateMachine<'state> {
@@ -93,6 +94,14 @@ impl<'state> LocalCollStateMachine<'state> {
 
             LocalCollState::NoSuchCollection => unreachable!("the collection is unknown"),
 
+            LocalCollState::SyncIdChanged { .. } if !self.allow_reset => {
+                log::info!(
+                    "Not resetting {} store, since resets aren't allowed",
+                    store.collection_name()
+                );
+                Err(error::ErrorKind::SetupRequired.into())
+            }
+
             LocalCollState::SyncIdChanged { ids } => {
                 let assoc = StoreSyncAssociation::Connected(ids);
                 log::info!("Resetting {} store", store.collection_name());
@@ -157,6 +166,23 @@ impl<'state> LocalCollStateMachine<'state> {
         let mut gingerbread_man = Self {
             global_state,
             root_key,
+            allow_reset: true,
+        };
+        gingerbread_man.run_and_run_as_farst_as_you_can(store)
+    }
+
+    /// Like `get_state`, but fails with `SetupRequired` instead of resetting
+    /// the store if its sync IDs changed. Used for dry runs, which shouldn't
+    /// change anything.
+    pub fn get_state_without_reset(
+        store: &dyn Store,
+        global_state: &'state GlobalState,
+        root_key: &'state KeyBundle,
+    ) -> error::Result<Option<CollState>> {
+        let mut gingerbread_man = Self {
+            global_state,
+            root_key,
+            allow_reset: false,
         };
         gingerbread_man.run_and_run_as_farst_as_you_can(store)
     }
@@ -315,6 +341,23 @@ mod tests {
         assert_eq!(store.get_num_resets(), 1);
     }
 
+    #[test]
+    fn test_known_wrong_state_without_reset() {
+        let root_key = KeyBundle::new_random().expect("should work");
+        let gs = get_global_state(&root_key);
+        let store = TestStore::new(
+            "bookmarks",
+            StoreSyncAssociation::Connected(CollSyncIds {
+                global: "syncIDXXXXXX".into(),
+                coll: "syncIDYYYYYY".into(),
+            }),
+        );
+        let err = LocalCollStateMachine::get_state_without_reset(&store, &gs, &root_key)
+            .expect_err("should fail instead of resetting");
+        assert!(matches!(err.kind(), error::ErrorKind::SetupRequired));
+        assert_eq!(store.get_num_resets(), 0);
+    }
+
     #[test]
     fn test_known_good_state() {
         let root_key = KeyBundle::new_random().expect("should work");
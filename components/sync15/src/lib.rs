@@ -35,10 +35,13 @@ pub use crate::collection_keys::CollectionKeys;
 pub use crate::error::{Error, ErrorKind, Result};
 pub use crate::key_bundle::KeyBundle;
 pub use crate::migrate_state::extract_v1_state;
-pub use crate::request::CollectionRequest;
+pub use crate::request::{CollectionRequest, InfoConfiguration, RequestOrder};
 pub use crate::state::{GlobalState, SetupStateMachine};
 pub use crate::status::{ServiceStatus, SyncResult};
-pub use crate::sync::{synchronize, Store};
+pub use crate::sync::{
+    plan_sync, synchronize, validate, DryRun, PlannedAction, ProblemKind, RecordPlan, Store,
+    ValidationProblem, ValidationReport,
+};
 pub use crate::sync_multiple::{
     sync_multiple, sync_multiple_with_command_processor, MemoryCachedState, SyncRequestInfo,
 };
@@ -546,6 +546,18 @@ mod test {
             .unwrap();
         assert_eq!(complex.as_str(),
             "https://example.com/sync/storage/specific?full=1&limit=10&older=9876.54&newer=1234.56&sort=oldest");
+
+        let paged = CollectionRequest::new("paged")
+            .full()
+            .sort_by(RequestOrder::Oldest)
+            .paged(100)
+            .offset(Some("1234:100".into()))
+            .build_url(Url::parse("https://example.com/sync").unwrap())
+            .unwrap();
+        assert_eq!(
+            paged.as_str(),
+            "https://example.com/sync/storage/paged?full=1&limit=100&sort=oldest&offset=1234%3A100"
+        );
     }
 
     #[derive(Debug, Clone)]
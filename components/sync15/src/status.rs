@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::error::{Error, ErrorKind, ErrorResponse};
+use crate::sync::{RecordPlan, ValidationReport};
 use crate::telemetry::SyncTelemetryPing;
 use std::collections::HashMap;
 use std::time::{Duration, SystemTime};
@@ -49,6 +50,9 @@ impl ServiceStatus {
                 _ => ServiceStatus::ServiceError,
             },
 
+            // We stopped waiting for a response because we were interrupted.
+            ErrorKind::RequestError(viaduct::Error::Interrupted) => ServiceStatus::Interrupted,
+
             // Network errors.
             ErrorKind::RequestError(_)
             | ErrorKind::UnexpectedStatus(_)
@@ -77,6 +81,15 @@ pub struct SyncResult {
     /// Note that we expect the `String` to be replaced with an enum later.
     pub engine_results: HashMap<String, Result<(), Error>>,
 
+    /// The changes that each engine would make, if this was a dry run.
+    /// Empty otherwise.
+    pub engine_plans: HashMap<String, Vec<RecordPlan>>,
+
+    /// The problems that each engine found, if we validated instead of
+    /// syncing. Empty otherwise. Engines that couldn't be validated are
+    /// missing.
+    pub engine_validations: HashMap<String, ValidationReport>,
+
     pub telemetry: SyncTelemetryPing,
 
     pub next_sync_after: Option<std::time::SystemTime>,
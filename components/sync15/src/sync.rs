@@ -5,14 +5,18 @@
 use crate::changeset::CollectionUpdate;
 use crate::client::Sync15StorageClient;
 use crate::clients;
-use crate::coll_state::LocalCollStateMachine;
+use crate::coll_state::{CollState, LocalCollStateMachine};
 use crate::error::Error;
 use crate::key_bundle::KeyBundle;
+use crate::request::{CollectionRequest, RequestOrder};
 use crate::state::GlobalState;
 use crate::telemetry;
 use interrupt_support::Interruptee;
 
-pub use sync15_traits::{IncomingChangeset, Store};
+pub use sync15_traits::{
+    DryRun, IncomingChangeset, PlannedAction, ProblemKind, RecordPlan, Store, ValidationProblem,
+    ValidationReport,
+};
 
 pub fn synchronize(
     client: &Sync15StorageClient,
@@ -67,32 +71,14 @@ pub fn synchronize_with_clients_engine(
         store.prepare_for_sync(&|| clients.get_client_data())?;
     }
 
-    let collection_requests = store.get_collection_requests(coll_state.last_modified)?;
-    let incoming = if collection_requests.is_empty() {
-        log::info!("skipping incoming for {} - not needed.", collection);
-        vec![IncomingChangeset::new(collection, coll_state.last_modified)]
-    } else {
-        assert_eq!(collection_requests.last().unwrap().collection, collection);
-
-        let count = collection_requests.len();
-        collection_requests
-            .into_iter()
-            .enumerate()
-            .map(|(idx, collection_request)| {
-                interruptee.err_if_interrupted()?;
-                let incoming_changes =
-                    crate::changeset::fetch_incoming(client, &mut coll_state, &collection_request)?;
-
-                log::info!(
-                    "Downloaded {} remote changes (request {} of {})",
-                    incoming_changes.changes.len(),
-                    idx,
-                    count,
-                );
-                Ok(incoming_changes)
-            })
-            .collect::<Result<Vec<_>, Error>>()?
-    };
+    let incoming = fetch_incoming_for_store(
+        client,
+        &mut coll_state,
+        store,
+        true,
+        telem_engine,
+        interruptee,
+    )?;
 
     let new_timestamp = incoming.last().expect("must have >= 1").timestamp;
     let mut outgoing = store.apply_incoming(incoming, telem_engine)?;
@@ -106,7 +92,7 @@ pub fn synchronize_with_clients_engine(
     log::info!("Uploading {} outgoing changes", outgoing.changes.len());
     let upload_info =
         CollectionUpdate::new_from_changeset(client, &coll_state, outgoing, fully_atomic)?
-            .upload()?;
+            .upload(interruptee)?;
 
     log::info!(
         "Upload success ({} records success, {} records failed)",
@@ -125,3 +111,203 @@ pub fn synchronize_with_clients_engine(
     log::info!("Sync finished!");
     Ok(())
 }
+
+/// Works out what syncing `store` would do, without changing anything
+/// locally or on the server. Records are downloaded and reconciled as
+/// usual, but the store rolls back its changes, and nothing is uploaded.
+/// Returns the action for each record that would change.
+pub fn plan_sync(
+    client: &Sync15StorageClient,
+    global_state: &GlobalState,
+    root_sync_key: &KeyBundle,
+    store: &dyn Store,
+    telem_engine: &mut telemetry::Engine,
+    interruptee: &dyn Interruptee,
+) -> Result<Vec<RecordPlan>, Error> {
+    let collection = store.collection_name();
+    log::info!("Planning sync for collection {}", collection);
+
+    let mut coll_state =
+        match LocalCollStateMachine::get_state_without_reset(store, global_state, root_sync_key)? {
+            Some(coll_state) => coll_state,
+            None => {
+                log::warn!("can't plan a sync for the {} collection", collection);
+                return Ok(vec![]);
+            }
+        };
+
+    let incoming = fetch_incoming_for_store(
+        client,
+        &mut coll_state,
+        store,
+        false,
+        telem_engine,
+        interruptee,
+    )?;
+    let dry_run = store.apply_incoming_dry_run(incoming, telem_engine)?;
+
+    let mut plans = dry_run.incoming;
+    plans.extend(
+        dry_run
+            .outgoing
+            .changes
+            .into_iter()
+            .map(|payload| RecordPlan::new(payload.id, PlannedAction::Upload)),
+    );
+    log::info!("Planned {} changes for {}", plans.len(), collection);
+    Ok(plans)
+}
+
+/// How many records to download at once when validating.
+const VALIDATION_PAGE_SIZE: usize = 1000;
+
+/// Downloads every record in `store`'s collection, and asks the store to
+/// compare them with its local data. Nothing is changed locally or on the
+/// server. The summary is recorded in `telem_engine`'s `validation` section,
+/// so callers should pass a new engine, not the one for a sync.
+///
+/// Returns `None` if the collection can't be validated, because the store
+/// doesn't have a validator, the collection is declined, or the store's sync
+/// IDs don't match the server's.
+pub fn validate(
+    client: &Sync15StorageClient,
+    global_state: &GlobalState,
+    root_sync_key: &KeyBundle,
+    store: &dyn Store,
+    telem_engine: &mut telemetry::Engine,
+    interruptee: &dyn Interruptee,
+) -> Result<Option<ValidationReport>, Error> {
+    let collection = store.collection_name();
+    if !store.supports_validation() {
+        log::info!("The {} store doesn't support validation", collection);
+        return Ok(None);
+    }
+    log::info!("Validating collection {}", collection);
+
+    let mut coll_state =
+        match LocalCollStateMachine::get_state_without_reset(store, global_state, root_sync_key)? {
+            Some(coll_state) => coll_state,
+            None => {
+                log::warn!("can't validate the {} collection", collection);
+                return Ok(None);
+            }
+        };
+
+    let request = CollectionRequest::new(collection.clone())
+        .full()
+        .sort_by(RequestOrder::Oldest)
+        .paged(VALIDATION_PAGE_SIZE);
+    let result = fetch_incoming_pages(
+        client,
+        &mut coll_state,
+        &request,
+        store,
+        false,
+        telem_engine,
+        interruptee,
+    )
+    .and_then(|incoming| {
+        interruptee.err_if_interrupted()?;
+        Ok(store.validate(incoming.changes)?)
+    });
+    match result {
+        Ok(report) => {
+            log::info!(
+                "Found {} problems in {} records for {}",
+                report.problems.len(),
+                report.checked,
+                collection
+            );
+            telem_engine.validation(report.to_telemetry());
+            Ok(Some(report))
+        }
+        Err(e) => {
+            let mut validation = telemetry::Validation::with_version(0);
+            validation.failure(telemetry::SyncFailure::from(&e));
+            telem_engine.validation(validation);
+            Err(e)
+        }
+    }
+}
+
+/// Downloads everything `store` asks for, returning a changeset for each
+/// request. If `stage` is false, paged requests aren't offered to
+/// `Store::stage_incoming`, so all records end up in the changesets.
+fn fetch_incoming_for_store(
+    client: &Sync15StorageClient,
+    coll_state: &mut CollState,
+    store: &dyn Store,
+    stage: bool,
+    telem_engine: &mut telemetry::Engine,
+    interruptee: &dyn Interruptee,
+) -> Result<Vec<IncomingChangeset>, Error> {
+    let collection = store.collection_name();
+    let collection_requests = store.get_collection_requests(coll_state.last_modified)?;
+    Ok(if collection_requests.is_empty() {
+        log::info!("skipping incoming for {} - not needed.", collection);
+        vec![IncomingChangeset::new(collection, coll_state.last_modified)]
+    } else {
+        assert_eq!(collection_requests.last().unwrap().collection, collection);
+
+        let count = collection_requests.len();
+        collection_requests
+            .into_iter()
+            .enumerate()
+            .map(|(idx, collection_request)| {
+                interruptee.err_if_interrupted()?;
+                let incoming_changes = if collection_request.paged {
+                    fetch_incoming_pages(
+                        client,
+                        coll_state,
+                        &collection_request,
+                        store,
+                        stage,
+                        telem_engine,
+                        interruptee,
+                    )?
+                } else {
+                    crate::changeset::fetch_incoming(client, coll_state, &collection_request)?
+                };
+
+                log::info!(
+                    "Downloaded {} remote changes (request {} of {})",
+                    incoming_changes.changes.len(),
+                    idx,
+                    count,
+                );
+                Ok(incoming_changes)
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+    })
+}
+
+/// Downloads a paged collection request, offering each page to the store as
+/// it arrives. Pages the store doesn't stage itself are buffered, and returned
+/// as a single changeset for `apply_incoming`. If `stage` is false, every
+/// page is buffered.
+fn fetch_incoming_pages(
+    client: &Sync15StorageClient,
+    coll_state: &mut CollState,
+    collection_request: &CollectionRequest,
+    store: &dyn Store,
+    stage: bool,
+    telem_engine: &mut telemetry::Engine,
+    interruptee: &dyn Interruptee,
+) -> Result<IncomingChangeset, Error> {
+    let mut buffered = vec![];
+    let timestamp = crate::changeset::fetch_incoming_paged(
+        client,
+        coll_state,
+        collection_request,
+        interruptee,
+        |mut page, high_water_mark| {
+            if !stage || !store.stage_incoming(&mut page, high_water_mark, telem_engine)? {
+                buffered.extend(page.changes);
+            }
+            Ok(())
+        },
+    )?;
+    let mut result = IncomingChangeset::new(collection_request.collection.clone(), timestamp);
+    result.changes = buffered;
+    Ok(result)
+}
@@ -134,6 +134,8 @@ pub fn sync_multiple_with_command_processor(
         declined: None,
         next_sync_after: None,
         engine_results: HashMap::with_capacity(stores.len()),
+        engine_plans: HashMap::new(),
+        engine_validations: HashMap::new(),
         telemetry: telemetry::SyncTelemetryPing::new(),
     };
     let backoff = crate::client::new_backoff_listener();
@@ -151,6 +153,8 @@ pub fn sync_multiple_with_command_processor(
         mem_cached_state,
         saw_auth_error: false,
         ignore_soft_backoff: req_info.is_user_action,
+        dry_run: req_info.dry_run,
+        validate: req_info.validate,
     };
     match driver.sync() {
         Ok(()) => {
@@ -184,6 +188,15 @@ pub fn sync_multiple_with_command_processor(
 pub struct SyncRequestInfo<'a> {
     pub engines_to_state_change: Option<&'a HashMap<String, bool>>,
     pub is_user_action: bool,
+    /// If true, we download and reconcile records as usual, but roll back
+    /// local changes and skip uploads, and report the planned changes in
+    /// `SyncResult::engine_plans` instead.
+    pub dry_run: bool,
+    /// If true, we compare every record on the server with the local data
+    /// instead of syncing, and report the problems in
+    /// `SyncResult::engine_validations` and the telemetry `validation`
+    /// section. Like dry runs, nothing is changed locally or on the server.
+    pub validate: bool,
 }
 
 // The sync multiple driver
@@ -199,6 +212,8 @@ struct SyncMultipleDriver<'info, 'res, 'pgs, 'mcs> {
     persisted_global_state: &'pgs mut Option<String>,
     mem_cached_state: &'mcs mut MemoryCachedState,
     ignore_soft_backoff: bool,
+    dry_run: bool,
+    validate: bool,
     saw_auth_error: bool,
 }
 
@@ -228,7 +243,14 @@ impl<'info, 'res, 'pgs, 'mcs> SyncMultipleDriver<'info, 'res, 'pgs, 'mcs> {
         // store failing.
         self.result.service_status = ServiceStatus::Ok;
 
-        let clients_engine = if let Some(command_processor) = self.command_processor {
+        // The clients engine uploads our own record and processes commands
+        // as it syncs, so we skip it for dry runs and validation.
+        let command_processor = if self.is_read_only() {
+            None
+        } else {
+            self.command_processor
+        };
+        let clients_engine = if let Some(command_processor) = command_processor {
             log::info!("Synchronizing clients engine");
             let should_refresh = self.mem_cached_state.should_refresh_client();
             let mut engine = clients::Engine::new(command_processor, self.interruptee);
@@ -277,6 +299,11 @@ impl<'info, 'res, 'pgs, 'mcs> SyncMultipleDriver<'info, 'res, 'pgs, 'mcs> {
         Ok(())
     }
 
+    /// Returns true if we mustn't change anything locally or on the server.
+    fn is_read_only(&self) -> bool {
+        self.dry_run || self.validate
+    }
+
     fn was_interrupted(&mut self) -> bool {
         if self.interruptee.was_interrupted() {
             log::info!("Interrupted, bailing out");
@@ -311,16 +338,46 @@ impl<'info, 'res, 'pgs, 'mcs> SyncMultipleDriver<'info, 'res, 'pgs, 'mcs> {
             log::info!("Syncing {} engine!", name);
 
             let mut telem_engine = telemetry::Engine::new(&*name);
-            let result = sync::synchronize_with_clients_engine(
-                &client_info.client,
-                &global_state,
-                self.root_sync_key,
-                clients,
-                *store,
-                true,
-                &mut telem_engine,
-                self.interruptee,
-            );
+            let result = if self.validate {
+                sync::validate(
+                    &client_info.client,
+                    &global_state,
+                    self.root_sync_key,
+                    *store,
+                    &mut telem_engine,
+                    self.interruptee,
+                )
+                .map(|report| {
+                    if let Some(report) = report {
+                        self.result
+                            .engine_validations
+                            .insert(name.to_string(), report);
+                    }
+                })
+            } else if self.dry_run {
+                sync::plan_sync(
+                    &client_info.client,
+                    &global_state,
+                    self.root_sync_key,
+                    *store,
+                    &mut telem_engine,
+                    self.interruptee,
+                )
+                .map(|plans| {
+                    self.result.engine_plans.insert(name.to_string(), plans);
+                })
+            } else {
+                sync::synchronize_with_clients_engine(
+                    &client_info.client,
+                    &global_state,
+                    self.root_sync_key,
+                    clients,
+                    *store,
+                    true,
+                    &mut telem_engine,
+                    self.interruptee,
+                )
+            };
 
             match result {
                 Ok(()) => log::info!("Sync of {} was successful!", name),
@@ -358,15 +415,27 @@ impl<'info, 'res, 'pgs, 'mcs> SyncMultipleDriver<'info, 'res, 'pgs, 'mcs> {
     ) -> result::Result<GlobalState, Error> {
         let last_state = mem::replace(&mut self.mem_cached_state.last_global_state, None);
 
-        let mut state_machine = SetupStateMachine::for_full_sync(
-            &client_info.client,
-            &self.root_sync_key,
-            pgs,
-            self.engines_to_state_change,
-            self.interruptee,
-        );
+        let mut state_machine = if self.is_read_only() {
+            // Dry runs and validation mustn't upload a fresh `meta/global` or `crypto/keys`,
+            // or change which engines are enabled.
+            log::info!("Advancing state machine to ready (read-only)");
+            SetupStateMachine::for_readonly_sync(
+                &client_info.client,
+                &self.root_sync_key,
+                pgs,
+                self.interruptee,
+            )
+        } else {
+            log::info!("Advancing state machine to ready (full)");
+            SetupStateMachine::for_full_sync(
+                &client_info.client,
+                &self.root_sync_key,
+                pgs,
+                self.engines_to_state_change,
+                self.interruptee,
+            )
+        };
 
-        log::info!("Advancing state machine to ready (full)");
         let res = state_machine.run_to_ready(last_state);
         // Grab this now even though we don't need it until later to avoid a
         // lifetime issue
@@ -387,7 +456,11 @@ impl<'info, 'res, 'pgs, 'mcs> SyncMultipleDriver<'info, 'res, 'pgs, 'mcs> {
         );
 
         if let Some(c) = changes {
-            self.wipe_or_reset_engines(c, &client_info.client)?;
+            if self.is_read_only() {
+                log::info!("Not wiping or resetting engines during a read-only sync");
+            } else {
+                self.wipe_or_reset_engines(c, &client_info.client)?;
+            }
         }
         let state = match res {
             Err(e) => {
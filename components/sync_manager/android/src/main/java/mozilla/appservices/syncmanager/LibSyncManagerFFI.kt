@@ -6,6 +6,7 @@ package mozilla.appservices.syncmanager
 
 import com.sun.jna.Library
 import com.sun.jna.Pointer
+import com.sun.jna.PointerType
 import mozilla.appservices.support.native.RustBuffer
 import mozilla.appservices.support.native.loadIndirect
 import org.mozilla.appservices.syncmanager.BuildConfig
@@ -19,14 +20,43 @@ internal interface LibSyncManagerFFI : Library {
     fun sync_manager_set_places(handle: PlacesApiHandle, error: RustError.ByReference)
     fun sync_manager_set_logins(handle: LoginsDbHandle, error: RustError.ByReference)
     fun sync_manager_set_tabs(handle: TabsApiHandle, error: RustError.ByReference)
+    fun sync_manager_set_webext_storage(handle: WebExtStorageHandle, error: RustError.ByReference)
     fun sync_manager_disconnect(error: RustError.ByReference)
 
+    fun sync_manager_new_interrupt_handle(error: RustError.ByReference): RawSyncManagerInterruptHandle?
+    fun sync_manager_interrupt(handle: RawSyncManagerInterruptHandle, error: RustError.ByReference)
+
     fun sync_manager_sync(data: Pointer, len: Int, error: RustError.ByReference): RustBuffer.ByValue
 
+    fun sync_manager_validate(data: Pointer, len: Int, error: RustError.ByReference): RustBuffer.ByValue
+
+    fun sync_manager_scheduler_set_policy(
+        singleDeviceIntervalMs: Long,
+        idleIntervalMs: Long,
+        activeIntervalMs: Long,
+        localChangeDelayMs: Long,
+        localChangeMaxDelayMs: Long,
+        errorRetryDelayMs: Long,
+        error: RustError.ByReference
+    )
+    fun sync_manager_scheduler_set_active(active: Byte, error: RustError.ByReference)
+    fun sync_manager_scheduler_set_multi_device(multiDevice: Byte, error: RustError.ByReference)
+    fun sync_manager_scheduler_note_local_change(error: RustError.ByReference)
+    fun sync_manager_scheduler_should_sync(error: RustError.ByReference): Byte
+    fun sync_manager_scheduler_time_until_next_sync(error: RustError.ByReference): Long
+
+    fun sync_manager_open_history(path: String, error: RustError.ByReference)
+    fun sync_manager_get_history(limit: Int, error: RustError.ByReference): RustBuffer.ByValue
+    fun sync_manager_clear_history(error: RustError.ByReference)
+
     fun sync_manager_destroy_string(s: Pointer)
     fun sync_manager_destroy_bytebuffer(bb: RustBuffer.ByValue)
+    fun sync_manager_interrupt_handle_destroy(obj: RawSyncManagerInterruptHandle)
 }
 
 internal typealias PlacesApiHandle = Long
 internal typealias LoginsDbHandle = Long
 internal typealias TabsApiHandle = Long
+internal typealias WebExtStorageHandle = Long
+
+internal class RawSyncManagerInterruptHandle : PointerType()
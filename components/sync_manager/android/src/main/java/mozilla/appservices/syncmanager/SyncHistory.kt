@@ -0,0 +1,104 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.syncmanager
+
+/**
+ * How syncing one engine went, as recorded in the sync history.
+ */
+data class EngineSyncHistory(
+    val name: String,
+
+    /**
+     * How long the engine took to sync, in milliseconds.
+     */
+    val took: Long,
+
+    val incomingApplied: Int,
+    val incomingFailed: Int,
+    val incomingNewFailed: Int,
+    val incomingReconciled: Int,
+    val outgoingSent: Int,
+    val outgoingFailed: Int,
+
+    /**
+     * A description of the error, or null if the engine synced without
+     * errors.
+     */
+    val failure: String?
+) {
+    companion object {
+        internal fun fromProtobuf(pb: MsgTypes.EngineSyncHistory): EngineSyncHistory {
+            return EngineSyncHistory(
+                name = pb.name,
+                took = pb.took,
+                incomingApplied = pb.incomingApplied,
+                incomingFailed = pb.incomingFailed,
+                incomingNewFailed = pb.incomingNewFailed,
+                incomingReconciled = pb.incomingReconciled,
+                outgoingSent = pb.outgoingSent,
+                outgoingFailed = pb.outgoingFailed,
+                failure = if (pb.hasFailure()) pb.failure else null
+            )
+        }
+    }
+}
+
+/**
+ * A sync, as recorded in the sync history. See `SyncManager.getHistory`.
+ */
+data class SyncHistoryEntry(
+    /**
+     * When the sync started, in milliseconds since the unix epoch.
+     */
+    val startedAt: Long,
+
+    /**
+     * How long the sync took, in milliseconds.
+     */
+    val took: Long,
+
+    /**
+     * Why we synced, or null if the reason isn't one that we know about.
+     */
+    val reason: SyncReason?,
+
+    val status: SyncServiceStatus,
+
+    /**
+     * A description of the error, if the sync failed as a whole. Engines
+     * can fail even if this is null.
+     */
+    val failure: String?,
+
+    /**
+     * The next time we were allowed to sync, in milliseconds since the unix
+     * epoch. See `SyncResult.nextSyncAllowedAt`.
+     */
+    val nextSyncAllowedAt: Long?,
+
+    val engines: List<EngineSyncHistory>
+) {
+    companion object {
+        internal fun fromProtobuf(pb: MsgTypes.SyncHistoryEntry): SyncHistoryEntry {
+            val reason = when (pb.reason) {
+                MsgTypes.SyncReason.SCHEDULED -> SyncReason.SCHEDULED
+                MsgTypes.SyncReason.USER -> SyncReason.USER
+                MsgTypes.SyncReason.PRE_SLEEP -> SyncReason.PRE_SLEEP
+                MsgTypes.SyncReason.STARTUP -> SyncReason.STARTUP
+                MsgTypes.SyncReason.ENABLED_CHANGE -> SyncReason.ENABLED_CHANGE
+                else -> null
+            }
+            return SyncHistoryEntry(
+                startedAt = pb.startedAt,
+                took = pb.took,
+                reason = reason,
+                status = SyncServiceStatus.fromProtobuf(pb.status),
+                failure = if (pb.hasFailure()) pb.failure else null,
+                nextSyncAllowedAt = if (pb.hasNextSyncAllowedAt()) pb.nextSyncAllowedAt else null,
+                engines = pb.enginesList.map { EngineSyncHistory.fromProtobuf(it) }
+            )
+        }
+    }
+}
@@ -5,10 +5,19 @@
 package mozilla.appservices.syncmanager
 
 import com.sun.jna.Native
+import com.sun.jna.Pointer
+import mozilla.appservices.support.native.RustBuffer
 import mozilla.appservices.support.native.toNioDirectBuffer
 
 object SyncManager {
 
+    // The manager lives as long as the process, so this is never destroyed.
+    private val interruptHandle: RawSyncManagerInterruptHandle by lazy {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_new_interrupt_handle(err)
+        }!!
+    }
+
     /**
      * Point the manager at the implementation of `PlacesApi` to use.
      *
@@ -45,6 +54,18 @@ object SyncManager {
         }
     }
 
+    /**
+     * Point the manager at the `storage.sync` store for WebExtensions to use.
+     *
+     * @param webExtStorageHandle A value returned by `WebExtStorage.getHandle()`
+     * @throws [UnsupportedEngine] If the manager was not compiled with WebExtension storage support.
+     */
+    fun setWebExtStorage(webExtStorageHandle: Long) {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_set_webext_storage(webExtStorageHandle, err)
+        }
+    }
+
     /**
      * Disconnect this device from sync. This essentially clears shared state having to do with
      * sync, as well as each engine's sync-specific local state.
@@ -54,15 +75,56 @@ object SyncManager {
             LibSyncManagerFFI.INSTANCE.sync_manager_disconnect(err)
         }
     }
+    /**
+     * Interrupt an in-progress sync. This cancels any pending network requests,
+     * interrupts the engines' database connections, and stops the sync before
+     * the next engine or batch of records. The interrupted sync returns a
+     * [SyncResult] with the [SyncServiceStatus.INTERRUPTED] status, which only
+     * includes the engines that finished before it was interrupted.
+     *
+     * This is safe to call from any thread. It has no effect if there's no sync
+     * in progress, and doesn't affect any later syncs.
+     */
+    fun interrupt() {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_interrupt(interruptHandle, err)
+        }
+    }
+
     /**
      * Perform a sync.
      */
     fun sync(params: SyncParams): SyncResult {
+        return callWithParams(params) { ptr, len, err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_sync(ptr, len, err)
+        }
+    }
+
+    /**
+     * Download every record for the engines in [params], and compare them
+     * with the local data, without changing anything locally or on the
+     * server. The problems that each engine found are reported in the
+     * `validation` section of its telemetry, in [SyncResult.telemetry].
+     * [SyncParams.dryRun] is ignored.
+     *
+     * Like dry runs, validations aren't reported to the scheduler or recorded
+     * in the sync history.
+     */
+    fun validate(params: SyncParams): SyncResult {
+        return callWithParams(params) { ptr, len, err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_validate(ptr, len, err)
+        }
+    }
+
+    private fun callWithParams(
+        params: SyncParams,
+        callback: (Pointer, Int, RustError.ByReference) -> RustBuffer.ByValue
+    ): SyncResult {
         val buf = params.toProtobuf()
         val (nioBuf, len) = buf.toNioDirectBuffer()
         val rustBuf = rustCall { err ->
             val ptr = Native.getDirectBufferPointer(nioBuf)
-            LibSyncManagerFFI.INSTANCE.sync_manager_sync(ptr, len, err)
+            callback(ptr, len, err)
         }
 
         try {
@@ -72,6 +134,117 @@ object SyncManager {
             LibSyncManagerFFI.INSTANCE.sync_manager_destroy_bytebuffer(rustBuf)
         }
     }
+
+    /**
+     * Set how often the scheduler should sync. See [SyncPolicy].
+     *
+     * The scheduler decides when the next sync is due, based on the policy,
+     * what it's been told about the user, and the results of the syncs that
+     * [sync] performed. The application still owns the timers: it should call
+     * [timeUntilNextSync] to decide when to wake up, and [shouldSync] before
+     * syncing. Unlike the other methods here, the scheduler methods don't
+     * wait for a sync in progress.
+     */
+    fun setSyncPolicy(policy: SyncPolicy) {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_scheduler_set_policy(
+                policy.singleDeviceInterval,
+                policy.idleInterval,
+                policy.activeInterval,
+                policy.localChangeDelay,
+                policy.localChangeMaxDelay,
+                policy.errorRetryDelay,
+                err
+            )
+        }
+    }
+
+    /**
+     * Tell the scheduler whether the user is using the application. We sync
+     * more often while they are, if there are other devices on the account.
+     */
+    fun setUserActive(active: Boolean) {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_scheduler_set_active(if (active) 1 else 0, err)
+        }
+    }
+
+    /**
+     * Tell the scheduler whether there are other devices on the account.
+     */
+    fun setMultiDevice(multiDevice: Boolean) {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_scheduler_set_multi_device(if (multiDevice) 1 else 0, err)
+        }
+    }
+
+    /**
+     * Tell the scheduler that the user changed something that should be
+     * synced, so that it syncs soon.
+     */
+    fun noteLocalChange() {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_scheduler_note_local_change(err)
+        }
+    }
+
+    /**
+     * Returns true if a sync is due, and no sync is in progress.
+     */
+    fun shouldSync(): Boolean {
+        return rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_scheduler_should_sync(err)
+        }.toInt() == 1
+    }
+
+    /**
+     * Returns how long to wait before the next sync, in milliseconds, or 0
+     * if a sync is due now. This accounts for any backoff that the server
+     * asked for.
+     */
+    fun timeUntilNextSync(): Long {
+        return rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_scheduler_time_until_next_sync(err)
+        }
+    }
+
+    /**
+     * Start recording syncs in the database at [path], so that they can be
+     * shown with [getHistory]. Syncs aren't recorded until this is called.
+     */
+    fun openHistory(path: String) {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_open_history(path, err)
+        }
+    }
+
+    /**
+     * Get up to [limit] of the most recent syncs, newest first. Only the
+     * last 100 syncs are kept. This waits for any sync in progress to finish.
+     */
+    fun getHistory(limit: Int = 100): List<SyncHistoryEntry> {
+        val rustBuf = rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_get_history(limit, err)
+        }
+
+        try {
+            val stream = rustBuf.asCodedInputStream()
+            return MsgTypes.SyncHistory.parseFrom(stream).entriesList.map {
+                SyncHistoryEntry.fromProtobuf(it)
+            }
+        } finally {
+            LibSyncManagerFFI.INSTANCE.sync_manager_destroy_bytebuffer(rustBuf)
+        }
+    }
+
+    /**
+     * Forget all the syncs recorded in the history.
+     */
+    fun clearHistory() {
+        rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_clear_history(err)
+        }
+    }
 }
 
 internal inline fun <U> rustCall(callback: (RustError.ByReference) -> U): U {
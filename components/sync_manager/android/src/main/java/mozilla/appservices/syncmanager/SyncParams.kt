@@ -112,7 +112,14 @@ data class SyncParams(
     /**
      * The information used to populate a client record for this device.
      */
-    val deviceSettings: DeviceSettings
+    val deviceSettings: DeviceSettings,
+
+    /**
+     * If true, download and reconcile records as usual, but don't change
+     * anything locally or on the server. The changes that each engine would
+     * make are reported in `SyncResult.plans`.
+     */
+    val dryRun: Boolean = false
 ) {
     @Suppress("ComplexMethod")
     internal fun toProtobuf(): MsgTypes.SyncParams {
@@ -151,6 +158,7 @@ data class SyncParams(
             DeviceType.VR -> MsgTypes.DeviceType.VR
             DeviceType.TV -> MsgTypes.DeviceType.TV
         }
+        builder.dryRun = this.dryRun
 
         return builder.build()
     }
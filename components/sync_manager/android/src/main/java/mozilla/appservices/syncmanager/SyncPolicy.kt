@@ -0,0 +1,44 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.syncmanager
+
+/**
+ * How often the scheduler should sync. All the durations are in
+ * milliseconds. The defaults match desktop Firefox.
+ */
+data class SyncPolicy(
+    /**
+     * How often to sync if there are no other devices on the account.
+     */
+    val singleDeviceInterval: Long = 24 * 60 * 60 * 1000L,
+
+    /**
+     * How often to sync if there are other devices, and the user is idle.
+     */
+    val idleInterval: Long = 60 * 60 * 1000L,
+
+    /**
+     * How often to sync if there are other devices, and the user is active.
+     */
+    val activeInterval: Long = 10 * 60 * 1000L,
+
+    /**
+     * How long to wait after a local change before syncing, in case more
+     * changes follow.
+     */
+    val localChangeDelay: Long = 5 * 1000L,
+
+    /**
+     * The longest to put off syncing local changes if the user keeps making
+     * them.
+     */
+    val localChangeMaxDelay: Long = 90 * 1000L,
+
+    /**
+     * How long to wait before retrying after the first error. The delay
+     * doubles with each error after that, up to the regular interval.
+     */
+    val errorRetryDelay: Long = 60 * 1000L
+)
@@ -41,8 +41,73 @@ enum class SyncServiceStatus {
      * Some other error occurred.
      */
     OTHER_ERROR,
+
+    /**
+     * The sync was interrupted by `SyncManager.interrupt`. Engines that
+     * finished syncing before the interruption are still reported.
+     */
+    INTERRUPTED;
+
+    companion object {
+        internal fun fromProtobuf(status: MsgTypes.ServiceStatus): SyncServiceStatus {
+            return when (status) {
+                MsgTypes.ServiceStatus.OK -> OK
+                MsgTypes.ServiceStatus.NETWORK_ERROR -> NETWORK_ERROR
+                MsgTypes.ServiceStatus.SERVICE_ERROR -> SERVICE_ERROR
+                MsgTypes.ServiceStatus.AUTH_ERROR -> AUTH_ERROR
+                MsgTypes.ServiceStatus.BACKED_OFF -> BACKED_OFF
+                MsgTypes.ServiceStatus.OTHER_ERROR -> OTHER_ERROR
+                MsgTypes.ServiceStatus.INTERRUPTED -> INTERRUPTED
+                else -> OTHER_ERROR // impossible *sigh*
+            }
+        }
+    }
+}
+
+/**
+ * What a dry run found that syncing would do to a record.
+ */
+enum class PlannedAction {
+    /**
+     * The incoming record would be applied locally.
+     */
+    APPLY,
+
+    /**
+     * The local record would be uploaded.
+     */
+    UPLOAD,
+
+    /**
+     * The local record would be deleted.
+     */
+    DELETE,
+
+    /**
+     * The incoming record conflicts with a local change, and would be merged.
+     */
+    CONFLICT;
+
+    companion object {
+        internal fun fromProtobuf(action: MsgTypes.PlannedAction): PlannedAction {
+            return when (action) {
+                MsgTypes.PlannedAction.APPLY -> APPLY
+                MsgTypes.PlannedAction.UPLOAD -> UPLOAD
+                MsgTypes.PlannedAction.DELETE -> DELETE
+                MsgTypes.PlannedAction.CONFLICT -> CONFLICT
+            }
+        }
+    }
 }
 
+/**
+ * A change that a dry run found that syncing would make.
+ */
+data class RecordPlan(
+    val id: String,
+    val action: PlannedAction
+)
+
 /**
  * The result of a sync.
  */
@@ -98,7 +163,13 @@ data class SyncResult(
     /**
      * A bundle of telemetry information recorded during this sync.
      */
-    val telemetry: SyncTelemetryPing?
+    val telemetry: SyncTelemetryPing?,
+
+    /**
+     * For dry runs, the changes that each engine would make, keyed by engine
+     * name. Empty for other syncs.
+     */
+    val plans: Map<String, List<RecordPlan>> = emptyMap()
 ) {
     companion object {
         @Suppress("ComplexMethod")
@@ -128,24 +199,21 @@ data class SyncResult(
                 null
             }
 
-            val status = when (pb.status) {
-                MsgTypes.ServiceStatus.OK -> SyncServiceStatus.OK
-                MsgTypes.ServiceStatus.NETWORK_ERROR -> SyncServiceStatus.NETWORK_ERROR
-                MsgTypes.ServiceStatus.SERVICE_ERROR -> SyncServiceStatus.SERVICE_ERROR
-                MsgTypes.ServiceStatus.AUTH_ERROR -> SyncServiceStatus.AUTH_ERROR
-                MsgTypes.ServiceStatus.BACKED_OFF -> SyncServiceStatus.BACKED_OFF
-                MsgTypes.ServiceStatus.OTHER_ERROR -> SyncServiceStatus.OTHER_ERROR
-                else -> SyncServiceStatus.OTHER_ERROR // impossible *sigh*
+            val plans = pb.plansMap.mapValues { (_, plan) ->
+                plan.recordsList.map {
+                    RecordPlan(it.id, PlannedAction.fromProtobuf(it.action))
+                }
             }
 
             return SyncResult(
-                status = status,
+                status = SyncServiceStatus.fromProtobuf(pb.status),
                 failures = failures,
                 successful = successful,
                 declined = declined,
                 telemetry = telemetry,
                 nextSyncAllowedAt = nextSyncAllowedAt,
-                persistedState = pb.persistedState
+                persistedState = pb.persistedState,
+                plans = plans
             )
         }
     }
@@ -8,8 +8,10 @@
 // the closure is small.
 #![allow(clippy::redundant_closure)]
 
-use ffi_support::{ExternError, HandleError};
-use sync_manager::Result as MgrResult;
+use ffi_support::{ExternError, FfiStr, HandleError};
+use std::convert::TryFrom;
+use std::time::Duration;
+use sync_manager::{Result as MgrResult, SyncPolicy};
 
 #[no_mangle]
 pub extern "C" fn sync_manager_set_places(_places_api_handle: u64, error: &mut ExternError) {
@@ -48,6 +50,19 @@ pub extern "C" fn sync_manager_set_tabs(_tabs_handle: u64, error: &mut ExternErr
     })
 }
 
+#[no_mangle]
+pub extern "C" fn sync_manager_set_webext_storage(_store_handle: u64, error: &mut ExternError) {
+    ffi_support::call_with_result(error, || -> MgrResult<()> {
+        log::debug!("sync_manager_set_webext_storage");
+        let api = webext_storage_ffi::STORES
+            .get_u64(_store_handle, |api| -> Result<_, HandleError> {
+                Ok(std::sync::Arc::clone(api))
+            })?;
+        sync_manager::set_webext_storage(api);
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn sync_manager_disconnect(error: &mut ExternError) {
     ffi_support::call_with_output(error, || {
@@ -56,6 +71,23 @@ pub extern "C" fn sync_manager_disconnect(error: &mut ExternError) {
     });
 }
 
+#[no_mangle]
+pub extern "C" fn sync_manager_new_interrupt_handle(
+    error: &mut ExternError,
+) -> *mut sync_manager::SyncManagerInterruptHandle {
+    log::debug!("sync_manager_new_interrupt_handle");
+    ffi_support::call_with_output(error, sync_manager::new_interrupt_handle)
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_interrupt(
+    handle: &sync_manager::SyncManagerInterruptHandle,
+    error: &mut ExternError,
+) {
+    log::debug!("sync_manager_interrupt");
+    ffi_support::call_with_output(error, || handle.interrupt())
+}
+
 unsafe fn get_buffer<'a>(data: *const u8, len: i32) -> &'a [u8] {
     assert!(len >= 0, "Bad buffer len: {}", len);
     if len == 0 {
@@ -82,5 +114,125 @@ pub unsafe extern "C" fn sync_manager_sync(
     })
 }
 
+/// # Safety
+/// Reads pointer, thus unsafe.
+#[no_mangle]
+pub unsafe extern "C" fn sync_manager_validate(
+    params_data: *const u8,
+    params_len: i32,
+    error: &mut ExternError,
+) -> ffi_support::ByteBuffer {
+    ffi_support::call_with_result(error, || {
+        log::debug!("sync_manager_validate");
+        let buffer = get_buffer(params_data, params_len);
+        let params: sync_manager::msg_types::SyncParams = prost::Message::decode(buffer)?;
+        sync_manager::validate(params)
+    })
+}
+
+/// Negative durations are treated as zero.
+fn millis_to_duration(millis: i64) -> Duration {
+    Duration::from_millis(u64::try_from(millis).unwrap_or_default())
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_scheduler_set_policy(
+    single_device_interval_ms: i64,
+    idle_interval_ms: i64,
+    active_interval_ms: i64,
+    local_change_delay_ms: i64,
+    local_change_max_delay_ms: i64,
+    error_retry_delay_ms: i64,
+    error: &mut ExternError,
+) {
+    ffi_support::call_with_output(error, || {
+        log::debug!("sync_manager_scheduler_set_policy");
+        let policy = SyncPolicy {
+            single_device_interval: millis_to_duration(single_device_interval_ms),
+            idle_interval: millis_to_duration(idle_interval_ms),
+            active_interval: millis_to_duration(active_interval_ms),
+            local_change_delay: millis_to_duration(local_change_delay_ms),
+            local_change_max_delay: millis_to_duration(local_change_max_delay_ms),
+            error_retry_delay: millis_to_duration(error_retry_delay_ms),
+        };
+        sync_manager::with_scheduler(|scheduler| scheduler.set_policy(policy))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_scheduler_set_active(active: u8, error: &mut ExternError) {
+    ffi_support::call_with_output(error, || {
+        log::debug!("sync_manager_scheduler_set_active");
+        sync_manager::with_scheduler(|scheduler| scheduler.set_active(active != 0))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_scheduler_set_multi_device(
+    multi_device: u8,
+    error: &mut ExternError,
+) {
+    ffi_support::call_with_output(error, || {
+        log::debug!("sync_manager_scheduler_set_multi_device");
+        sync_manager::with_scheduler(|scheduler| scheduler.set_multi_device(multi_device != 0))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_scheduler_note_local_change(error: &mut ExternError) {
+    ffi_support::call_with_output(error, || {
+        log::debug!("sync_manager_scheduler_note_local_change");
+        sync_manager::with_scheduler(|scheduler| scheduler.note_local_change())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_scheduler_should_sync(error: &mut ExternError) -> u8 {
+    ffi_support::call_with_output(error, || {
+        log::debug!("sync_manager_scheduler_should_sync");
+        sync_manager::with_scheduler(|scheduler| scheduler.should_sync())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_scheduler_time_until_next_sync(error: &mut ExternError) -> i64 {
+    ffi_support::call_with_output(error, || {
+        log::debug!("sync_manager_scheduler_time_until_next_sync");
+        let wait = sync_manager::with_scheduler(|scheduler| scheduler.time_until_next_sync());
+        i64::try_from(wait.as_millis()).unwrap_or(i64::MAX)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_open_history(path: FfiStr<'_>, error: &mut ExternError) {
+    ffi_support::call_with_result(error, || {
+        log::debug!("sync_manager_open_history");
+        sync_manager::open_history(path.as_str())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_get_history(
+    limit: u32,
+    error: &mut ExternError,
+) -> ffi_support::ByteBuffer {
+    ffi_support::call_with_result(error, || {
+        log::debug!("sync_manager_get_history");
+        sync_manager::sync_history(limit)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_clear_history(error: &mut ExternError) {
+    ffi_support::call_with_result(error, || {
+        log::debug!("sync_manager_clear_history");
+        sync_manager::clear_sync_history()
+    })
+}
+
 ffi_support::define_string_destructor!(sync_manager_destroy_string);
 ffi_support::define_bytebuffer_destructor!(sync_manager_destroy_bytebuffer);
+ffi_support::define_box_destructor!(
+    sync_manager::SyncManagerInterruptHandle,
+    sync_manager_interrupt_handle_destroy
+);
@@ -0,0 +1,160 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! `SyncEngine` implementations for the components the manager knows about.
+
+use crate::error::*;
+use crate::interrupt::SyncInterruptScope;
+use crate::registry::SyncEngine;
+use logins::PasswordEngine;
+use places::{bookmark_sync::store::BookmarksStore, history_sync::store::HistoryStore, PlacesApi};
+use std::sync::{Arc, Mutex, Weak};
+use tabs::TabsEngine;
+use webext_storage::store::Store as WebExtStorageStore;
+
+pub(crate) const LOGINS_ENGINE: &str = "passwords";
+pub(crate) const HISTORY_ENGINE: &str = "history";
+pub(crate) const BOOKMARKS_ENGINE: &str = "bookmarks";
+pub(crate) const TABS_ENGINE: &str = "tabs";
+pub(crate) const WEBEXT_STORAGE_ENGINE: &str = webext_storage::COLLECTION_NAME;
+
+fn upgrade<T>(weak: &Weak<T>, collections: &[&str]) -> Result<Arc<T>> {
+    weak.upgrade()
+        .ok_or_else(|| ErrorKind::ConnectionClosed(collections.join(", ")).into())
+}
+
+/// Provides the history and bookmarks stores, which share a single sync
+/// connection.
+pub(crate) struct PlacesEngine(pub Weak<PlacesApi>);
+
+impl SyncEngine for PlacesEngine {
+    fn is_open(&self) -> bool {
+        self.0.upgrade().is_some()
+    }
+
+    fn with_stores(
+        &self,
+        collections: &[&str],
+        scope: &SyncInterruptScope,
+        f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+    ) -> Result<()> {
+        let places = upgrade(&self.0, collections)?;
+        let conn = match places.open_sync_connection() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::warn!("Unable to open the places sync connection: {}", e);
+                return f(&[]);
+            }
+        };
+        scope.add_sql_interrupt_handle(conn.new_interrupt_handle());
+        let interruptee = scope.interruptee();
+        let mut stores: Vec<Box<dyn sync15::Store>> = vec![];
+        for &collection in collections {
+            match collection {
+                HISTORY_ENGINE => stores.push(Box::new(HistoryStore::new(&conn, interruptee))),
+                BOOKMARKS_ENGINE => stores.push(Box::new(BookmarksStore::new(&conn, interruptee))),
+                _ => return Err(ErrorKind::UnknownEngine(collection.into()).into()),
+            }
+        }
+        let store_refs: Vec<&dyn sync15::Store> = stores.iter().map(|s| &**s).collect();
+        f(&store_refs)
+    }
+
+    fn wipe(&self, collection: &str) -> Result<()> {
+        let places = upgrade(&self.0, &[collection])?;
+        match collection {
+            HISTORY_ENGINE => places.wipe_history()?,
+            BOOKMARKS_ENGINE => places.wipe_bookmarks()?,
+            _ => return Err(ErrorKind::UnknownEngine(collection.into()).into()),
+        }
+        Ok(())
+    }
+
+    fn reset(&self, collection: &str) -> Result<()> {
+        let places = upgrade(&self.0, &[collection])?;
+        match collection {
+            HISTORY_ENGINE => places.reset_history()?,
+            BOOKMARKS_ENGINE => places.reset_bookmarks()?,
+            _ => return Err(ErrorKind::UnknownEngine(collection.into()).into()),
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct LoginsEngine(pub Weak<Mutex<PasswordEngine>>);
+
+impl SyncEngine for LoginsEngine {
+    fn is_open(&self) -> bool {
+        self.0.upgrade().is_some()
+    }
+
+    fn with_stores(
+        &self,
+        collections: &[&str],
+        scope: &SyncInterruptScope,
+        f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+    ) -> Result<()> {
+        let logins = upgrade(&self.0, collections)?;
+        let logins = logins.lock().expect("poisoned logins mutex");
+        scope.add_sql_interrupt_handle(logins.new_interrupt_handle());
+        let store = logins::LoginStore::new(&logins.db);
+        f(&[&store as &dyn sync15::Store])
+    }
+
+    fn wipe(&self, collection: &str) -> Result<()> {
+        let logins = upgrade(&self.0, &[collection])?;
+        let logins = logins.lock().expect("poisoned logins mutex");
+        logins.wipe()?;
+        Ok(())
+    }
+
+    fn reset(&self, collection: &str) -> Result<()> {
+        let logins = upgrade(&self.0, &[collection])?;
+        let logins = logins.lock().expect("poisoned logins mutex");
+        logins.reset()?;
+        Ok(())
+    }
+}
+
+pub(crate) struct TabsSyncEngine(pub Weak<Mutex<TabsEngine>>);
+
+impl SyncEngine for TabsSyncEngine {
+    fn is_open(&self) -> bool {
+        self.0.upgrade().is_some()
+    }
+
+    fn with_stores(
+        &self,
+        collections: &[&str],
+        scope: &SyncInterruptScope,
+        f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+    ) -> Result<()> {
+        let tabs = upgrade(&self.0, collections)?;
+        let tabs = tabs.lock().expect("poisoned tabs mutex");
+        scope.add_sql_interrupt_handle(tabs.new_interrupt_handle());
+        let store = tabs::TabsStore::new(&tabs.storage);
+        f(&[&store as &dyn sync15::Store])
+    }
+}
+
+pub(crate) struct WebExtStorageEngine(pub Weak<Mutex<WebExtStorageStore>>);
+
+impl SyncEngine for WebExtStorageEngine {
+    fn is_open(&self) -> bool {
+        self.0.upgrade().is_some()
+    }
+
+    fn with_stores(
+        &self,
+        collections: &[&str],
+        scope: &SyncInterruptScope,
+        f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+    ) -> Result<()> {
+        let store = upgrade(&self.0, collections)?;
+        let store = store.lock().expect("poisoned webext storage mutex");
+        scope.add_sql_interrupt_handle(store.interrupt_handle());
+        let sync_store = store.sync_store();
+        f(&[&sync_store as &dyn sync15::Store])
+    }
+}
@@ -29,6 +29,12 @@ pub enum ErrorKind {
     LoginsError(#[from] logins::Error),
     #[error("Places error: {0}")]
     PlacesError(#[from] places::Error),
+    #[error("WebExtension storage error: {0}")]
+    WebExtStorageError(#[from] webext_storage::error::Error),
+    #[error("Store error: {0}")]
+    StoreError(#[from] anyhow::Error),
+    #[error("Error executing SQL: {0}")]
+    SqlError(#[from] rusqlite::Error),
 }
 
 error_support::define_error! {
@@ -41,5 +47,8 @@ error_support::define_error! {
         (JsonError, serde_json::Error),
         (LoginsError, logins::Error),
         (PlacesError, places::Error),
+        (WebExtStorageError, webext_storage::error::Error),
+        (StoreError, anyhow::Error),
+        (SqlError, rusqlite::Error),
     }
 }
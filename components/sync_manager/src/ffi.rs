@@ -47,3 +47,5 @@ impl From<Error> for ExternError {
 
 ffi_support::implement_into_ffi_by_protobuf!(crate::msg_types::SyncResult);
 ffi_support::implement_into_ffi_by_protobuf!(crate::msg_types::SyncParams);
+ffi_support::implement_into_ffi_by_protobuf!(crate::msg_types::SyncHistory);
+ffi_support::implement_into_ffi_by_pointer!(crate::SyncManagerInterruptHandle);
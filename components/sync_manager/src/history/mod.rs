@@ -0,0 +1,496 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A persisted log of recent syncs, for diagnosing sync problems.
+//!
+//! After each sync, the manager records why it synced, how it went, and
+//! the counts from the telemetry for each engine. Only the last
+//! `MAX_ENTRIES` syncs are kept. The history is meant to be shown on support
+//! pages like `about:sync`, so it doesn't include any synced data.
+
+mod schema;
+
+use crate::error::*;
+use crate::msg_types::{EngineSyncHistory, ServiceStatus, SyncHistoryEntry, SyncResult};
+use rusqlite::{named_params, Connection, Row};
+use sql_support::ConnExt;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::time::Duration;
+use sync15::telemetry::{SyncFailure, SyncTelemetryPing};
+
+/// The number of syncs to keep. Older syncs are removed when new ones are
+/// recorded.
+pub const MAX_ENTRIES: u32 = 100;
+
+pub struct SyncHistoryStore {
+    db: Connection,
+}
+
+impl SyncHistoryStore {
+    fn with_connection(db: Connection) -> Result<Self> {
+        // Android doesn't have a tmp partition, so temp files need to stay
+        // in memory.
+        db.set_pragma("temp_store", 2)?;
+        let tx = db.unchecked_transaction()?;
+        schema::init(&tx)?;
+        tx.commit()?;
+        Ok(Self { db })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// Adds a sync to the history, and removes the oldest syncs if there are
+    /// more than `MAX_ENTRIES`.
+    pub fn record(&self, entry: &SyncHistoryEntry) -> Result<()> {
+        let tx = self.db.unchecked_transaction()?;
+        tx.execute_named_cached(
+            "INSERT INTO sync_history(started_at, took, reason, status, failure,
+                                      next_sync_allowed_at)
+             VALUES(:started_at, :took, :reason, :status, :failure,
+                    :next_sync_allowed_at)",
+            named_params! {
+                ":started_at": entry.started_at,
+                ":took": entry.took,
+                ":reason": entry.reason,
+                ":status": entry.status,
+                ":failure": entry.failure,
+                ":next_sync_allowed_at": entry.next_sync_allowed_at,
+            },
+        )?;
+        let sync_id = tx.last_insert_rowid();
+        for engine in &entry.engines {
+            tx.execute_named_cached(
+                "INSERT INTO sync_history_engines(
+                     sync_id, name, took, incoming_applied, incoming_failed,
+                     incoming_new_failed, incoming_reconciled, outgoing_sent,
+                     outgoing_failed, failure)
+                 VALUES(:sync_id, :name, :took, :incoming_applied,
+                        :incoming_failed, :incoming_new_failed,
+                        :incoming_reconciled, :outgoing_sent, :outgoing_failed,
+                        :failure)",
+                named_params! {
+                    ":sync_id": sync_id,
+                    ":name": engine.name,
+                    ":took": engine.took,
+                    ":incoming_applied": engine.incoming_applied,
+                    ":incoming_failed": engine.incoming_failed,
+                    ":incoming_new_failed": engine.incoming_new_failed,
+                    ":incoming_reconciled": engine.incoming_reconciled,
+                    ":outgoing_sent": engine.outgoing_sent,
+                    ":outgoing_failed": engine.outgoing_failed,
+                    ":failure": engine.failure,
+                },
+            )?;
+        }
+        // If there are fewer than `MAX_ENTRIES` syncs, the subquery is
+        // `NULL`, and nothing is removed.
+        tx.execute_named_cached(
+            "DELETE FROM sync_history
+             WHERE id <= (SELECT id FROM sync_history
+                          ORDER BY id DESC
+                          LIMIT 1 OFFSET :max_entries)",
+            named_params! { ":max_entries": MAX_ENTRIES },
+        )?;
+        tx.execute_cached(
+            "DELETE FROM sync_history_engines
+             WHERE sync_id NOT IN (SELECT id FROM sync_history)",
+            rusqlite::NO_PARAMS,
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns up to `limit` of the most recent syncs, newest first.
+    pub fn recent(&self, limit: u32) -> Result<Vec<SyncHistoryEntry>> {
+        let syncs = self.db.query_rows_and_then_named_cached(
+            "SELECT id, started_at, took, reason, status, failure,
+                    next_sync_allowed_at
+             FROM sync_history
+             ORDER BY id DESC
+             LIMIT :limit",
+            named_params! { ":limit": limit },
+            |row| -> Result<_> { Ok((row.get::<_, i64>("id")?, entry_from_row(row)?)) },
+        )?;
+        let mut entries = Vec::with_capacity(syncs.len());
+        for (sync_id, mut entry) in syncs {
+            entry.engines = self.db.query_rows_and_then_named_cached(
+                "SELECT name, took, incoming_applied, incoming_failed,
+                        incoming_new_failed, incoming_reconciled,
+                        outgoing_sent, outgoing_failed, failure
+                 FROM sync_history_engines
+                 WHERE sync_id = :sync_id
+                 ORDER BY name",
+                named_params! { ":sync_id": sync_id },
+                engine_from_row,
+            )?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Removes all syncs from the history.
+    pub fn clear(&self) -> Result<()> {
+        self.db.execute_all(&[
+            "DELETE FROM sync_history_engines",
+            "DELETE FROM sync_history",
+        ])?;
+        Ok(())
+    }
+}
+
+fn entry_from_row(row: &Row<'_>) -> Result<SyncHistoryEntry> {
+    Ok(SyncHistoryEntry {
+        started_at: row.get("started_at")?,
+        took: row.get("took")?,
+        reason: row.get("reason")?,
+        status: row.get("status")?,
+        failure: row.get("failure")?,
+        next_sync_allowed_at: row.get("next_sync_allowed_at")?,
+        engines: Vec::new(),
+    })
+}
+
+fn engine_from_row(row: &Row<'_>) -> Result<EngineSyncHistory> {
+    Ok(EngineSyncHistory {
+        name: row.get("name")?,
+        took: row.get("took")?,
+        incoming_applied: row.get("incoming_applied")?,
+        incoming_failed: row.get("incoming_failed")?,
+        incoming_new_failed: row.get("incoming_new_failed")?,
+        incoming_reconciled: row.get("incoming_reconciled")?,
+        outgoing_sent: row.get("outgoing_sent")?,
+        outgoing_failed: row.get("outgoing_failed")?,
+        failure: row.get("failure")?,
+    })
+}
+
+/// Builds the history entry for a sync that started at `started_at`
+/// (in milliseconds since the Unix epoch), and took `took`. The counts come
+/// from the sync's `telemetry`, if it got far enough to record any.
+pub(crate) fn entry_for_sync(
+    reason: i32,
+    started_at: i64,
+    took: Duration,
+    result: &Result<SyncResult>,
+    telemetry: Option<&SyncTelemetryPing>,
+) -> SyncHistoryEntry {
+    let took = i64::try_from(took.as_millis()).unwrap_or(i64::MAX);
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            // The sync failed before it started, so there's no telemetry.
+            return SyncHistoryEntry {
+                started_at,
+                took,
+                reason,
+                status: status_for_error(e) as i32,
+                failure: Some(e.to_string()),
+                next_sync_allowed_at: None,
+                engines: Vec::new(),
+            };
+        }
+    };
+
+    let mut failure = None;
+    let mut engines: HashMap<String, EngineSyncHistory> = HashMap::new();
+    for sync in telemetry
+        .map(SyncTelemetryPing::get_syncs)
+        .unwrap_or_default()
+    {
+        if let Some(reason) = sync.get_failure() {
+            failure = Some(describe_failure(reason));
+        }
+        for telem in sync.get_engines() {
+            let engine = engines
+                .entry(telem.get_name().to_owned())
+                .or_insert_with(|| EngineSyncHistory {
+                    name: telem.get_name().to_owned(),
+                    ..EngineSyncHistory::default()
+                });
+            engine.took += i64::try_from(telem.get_took()).unwrap_or(i64::MAX);
+            if let Some(incoming) = telem.get_incoming() {
+                engine.incoming_applied += incoming.get_applied();
+                engine.incoming_failed += incoming.get_failed();
+                engine.incoming_new_failed += incoming.get_new_failed();
+                engine.incoming_reconciled += incoming.get_reconciled();
+            }
+            for outgoing in telem.get_outgoing() {
+                engine.outgoing_sent += u32::try_from(outgoing.get_sent()).unwrap_or(u32::MAX);
+                engine.outgoing_failed += u32::try_from(outgoing.get_failed()).unwrap_or(u32::MAX);
+            }
+            if let Some(reason) = telem.get_failure() {
+                engine.failure = Some(describe_failure(reason));
+            }
+        }
+    }
+    // The error messages in the results are more useful than the failure
+    // reasons in the telemetry, so they win.
+    for (name, error) in &result.results {
+        let engine = engines
+            .entry(name.clone())
+            .or_insert_with(|| EngineSyncHistory {
+                name: name.clone(),
+                ..EngineSyncHistory::default()
+            });
+        if !error.is_empty() {
+            engine.failure = Some(error.clone());
+        }
+    }
+    let mut engines = engines
+        .into_iter()
+        .map(|(_, engine)| engine)
+        .collect::<Vec<_>>();
+    engines.sort_by(|a, b| a.name.cmp(&b.name));
+
+    SyncHistoryEntry {
+        started_at,
+        took,
+        reason,
+        status: result.status,
+        failure,
+        next_sync_allowed_at: result.next_sync_allowed_at,
+        engines,
+    }
+}
+
+/// Picks the status for a sync that failed before it started, like the
+/// status that `sync15` would have reported if it had failed later.
+fn status_for_error(e: &Error) -> ServiceStatus {
+    match e.kind() {
+        ErrorKind::Sync15Error(e) => sync15::ServiceStatus::from_err(e).into(),
+        ErrorKind::InterruptedError(_) => ServiceStatus::Interrupted,
+        _ => ServiceStatus::OtherError,
+    }
+}
+
+fn describe_failure(failure: &SyncFailure) -> String {
+    match failure {
+        SyncFailure::Shutdown => "Shutdown".to_owned(),
+        SyncFailure::Other { error } | SyncFailure::Unexpected { error } => error.clone(),
+        SyncFailure::Auth { from } => format!("Authentication error from {}", from),
+        SyncFailure::Http { code } => format!("HTTP error {}", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg_types::SyncReason;
+    use sync15::telemetry::{Engine, EngineIncoming, EngineOutgoing, SyncTelemetry};
+
+    fn sync_result() -> SyncResult {
+        SyncResult {
+            status: ServiceStatus::Ok as i32,
+            results: [
+                ("bookmarks".to_string(), "".to_string()),
+                ("history".to_string(), "Network error".to_string()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            have_declined: true,
+            declined: vec![],
+            next_sync_allowed_at: Some(2_000),
+            persisted_state: "".to_string(),
+            telemetry_json: None,
+            plans: Default::default(),
+        }
+    }
+
+    fn ping(engines: Vec<Engine>, failure: Option<SyncFailure>) -> SyncTelemetryPing {
+        let mut sync = SyncTelemetry::new();
+        for engine in engines {
+            sync.engine(engine);
+        }
+        if let Some(failure) = failure {
+            sync.failure(failure);
+        }
+        let mut ping = SyncTelemetryPing::new();
+        ping.sync(sync);
+        ping
+    }
+
+    #[test]
+    fn test_entry_for_sync() {
+        let mut bookmarks = Engine::new("bookmarks");
+        let mut incoming = EngineIncoming::new();
+        incoming.applied(3);
+        incoming.new_failed(1);
+        incoming.reconciled(2);
+        bookmarks.incoming(incoming);
+        let mut outgoing = EngineOutgoing::new();
+        outgoing.sent(4);
+        bookmarks.outgoing(outgoing);
+        let mut outgoing = EngineOutgoing::new();
+        outgoing.sent(2);
+        outgoing.failed(1);
+        bookmarks.outgoing(outgoing);
+        let mut history = Engine::new("history");
+        history.failure(SyncFailure::Http { code: 500 });
+        let telemetry = ping(vec![bookmarks, history], None);
+
+        let mut entry = entry_for_sync(
+            SyncReason::User as i32,
+            1_000,
+            Duration::from_millis(25),
+            &Ok(sync_result()),
+            Some(&telemetry),
+        );
+        // The engine times come from the clock.
+        for engine in &mut entry.engines {
+            assert!(engine.took < 1_000);
+            engine.took = 0;
+        }
+        assert_eq!(
+            entry,
+            SyncHistoryEntry {
+                started_at: 1_000,
+                took: 25,
+                reason: SyncReason::User as i32,
+                status: ServiceStatus::Ok as i32,
+                failure: None,
+                next_sync_allowed_at: Some(2_000),
+                engines: vec![
+                    EngineSyncHistory {
+                        name: "bookmarks".to_string(),
+                        took: 0,
+                        incoming_applied: 3,
+                        incoming_failed: 0,
+                        incoming_new_failed: 1,
+                        incoming_reconciled: 2,
+                        outgoing_sent: 6,
+                        outgoing_failed: 1,
+                        failure: None,
+                    },
+                    EngineSyncHistory {
+                        name: "history".to_string(),
+                        failure: Some("Network error".to_string()),
+                        ..EngineSyncHistory::default()
+                    },
+                ],
+            }
+        );
+
+        // Engines without an error message keep the telemetry's reason.
+        let mut result = sync_result();
+        result.results.remove("history");
+        let mut history = Engine::new("history");
+        history.failure(SyncFailure::Http { code: 500 });
+        let telemetry = ping(
+            vec![history],
+            Some(SyncFailure::Auth {
+                from: "tokenserver",
+            }),
+        );
+        let entry = entry_for_sync(
+            SyncReason::User as i32,
+            1_000,
+            Duration::from_millis(25),
+            &Ok(result),
+            Some(&telemetry),
+        );
+        assert_eq!(
+            entry.failure.as_deref(),
+            Some("Authentication error from tokenserver")
+        );
+        assert_eq!(entry.engines[1].name, "history");
+        assert_eq!(entry.engines[1].failure.as_deref(), Some("HTTP error 500"));
+    }
+
+    #[test]
+    fn test_entry_for_failed_sync() {
+        let entry = entry_for_sync(
+            SyncReason::Scheduled as i32,
+            1_000,
+            Duration::from_millis(1),
+            &Err(ErrorKind::UnknownEngine("foo".to_string()).into()),
+            None,
+        );
+        assert_eq!(entry.status, ServiceStatus::OtherError as i32);
+        assert_eq!(entry.failure.as_deref(), Some("Unknown engine: foo"));
+        assert!(entry.engines.is_empty());
+
+        let entry = entry_for_sync(
+            SyncReason::Scheduled as i32,
+            1_000,
+            Duration::from_millis(1),
+            &Err(sync15::Error::from(sync15::ErrorKind::TokenserverHttpError(401)).into()),
+            None,
+        );
+        assert_eq!(entry.status, ServiceStatus::AuthError as i32);
+
+        let entry = entry_for_sync(
+            SyncReason::Scheduled as i32,
+            1_000,
+            Duration::from_millis(1),
+            &Err(sync15::Error::from(sync15::ErrorKind::TokenserverHttpError(503)).into()),
+            None,
+        );
+        assert_eq!(entry.status, ServiceStatus::ServiceError as i32);
+
+        let entry = entry_for_sync(
+            SyncReason::Scheduled as i32,
+            1_000,
+            Duration::from_millis(1),
+            &Err(interrupt_support::Interrupted.into()),
+            None,
+        );
+        assert_eq!(entry.status, ServiceStatus::Interrupted as i32);
+    }
+
+    #[test]
+    fn test_record_and_prune() {
+        let store = SyncHistoryStore::open_in_memory().unwrap();
+        assert!(store.recent(10).unwrap().is_empty());
+
+        let telemetry = ping(
+            vec![Engine::new("bookmarks")],
+            Some(SyncFailure::Other {
+                error: "oops".to_string(),
+            }),
+        );
+        let entry = entry_for_sync(
+            SyncReason::User as i32,
+            1_000,
+            Duration::from_millis(25),
+            &Ok(sync_result()),
+            Some(&telemetry),
+        );
+        store.record(&entry).unwrap();
+        assert_eq!(store.recent(10).unwrap(), vec![entry.clone()]);
+
+        for i in 1..=MAX_ENTRIES {
+            store
+                .record(&SyncHistoryEntry {
+                    started_at: 1_000 + i64::from(i),
+                    ..entry.clone()
+                })
+                .unwrap();
+        }
+        let recent = store.recent(MAX_ENTRIES + 10).unwrap();
+        assert_eq!(recent.len(), MAX_ENTRIES as usize);
+        assert_eq!(recent[0].started_at, 1_000 + i64::from(MAX_ENTRIES));
+        assert_eq!(recent[recent.len() - 1].started_at, 1_001);
+        assert_eq!(recent[0].engines, entry.engines);
+        assert_eq!(
+            store
+                .db
+                .query_one::<i64>("SELECT COUNT(*) FROM sync_history_engines")
+                .unwrap(),
+            i64::from(MAX_ENTRIES * 2)
+        );
+        assert_eq!(store.recent(2).unwrap().len(), 2);
+
+        store.clear().unwrap();
+        assert!(store.recent(10).unwrap().is_empty());
+    }
+}
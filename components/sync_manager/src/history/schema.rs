@@ -0,0 +1,123 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Sync history schema v1
+//! ======================
+//!
+//! There are two tables:
+//!
+//! - `sync_history`: One row for each sync, including syncs that we skipped
+//!   because of a backoff, and syncs that failed before they started. Rows
+//!   are ordered by `id`, which only increases.
+//!
+//! - `sync_history_engines`: One row for each engine that we tried to sync
+//!   during a sync, with the counts from its telemetry. `sync_id` is the `id`
+//!   of the sync in `sync_history`.
+//!
+//! The history is only used for diagnostics, so if we find a database we
+//! don't understand, we drop it and start over.
+
+use crate::error::*;
+use rusqlite::Connection;
+use sql_support::ConnExt;
+
+pub const VERSION: i64 = 1;
+
+const CREATE_SYNC_HISTORY_TABLE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS sync_history (
+        id                   INTEGER PRIMARY KEY AUTOINCREMENT,
+        -- Milliseconds since the Unix epoch.
+        started_at           INTEGER NOT NULL,
+        took                 INTEGER NOT NULL,
+        -- A `SyncReason` and a `ServiceStatus`.
+        reason               INTEGER NOT NULL,
+        status               INTEGER NOT NULL,
+        failure              TEXT,
+        next_sync_allowed_at INTEGER
+    )
+";
+
+const CREATE_SYNC_HISTORY_ENGINES_TABLE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS sync_history_engines (
+        sync_id             INTEGER NOT NULL,
+        name                TEXT NOT NULL,
+        took                INTEGER NOT NULL DEFAULT 0,
+        incoming_applied    INTEGER NOT NULL DEFAULT 0,
+        incoming_failed     INTEGER NOT NULL DEFAULT 0,
+        incoming_new_failed INTEGER NOT NULL DEFAULT 0,
+        incoming_reconciled INTEGER NOT NULL DEFAULT 0,
+        outgoing_sent       INTEGER NOT NULL DEFAULT 0,
+        outgoing_failed     INTEGER NOT NULL DEFAULT 0,
+        failure             TEXT,
+        PRIMARY KEY (sync_id, name)
+    ) WITHOUT ROWID
+";
+
+const DROP_TABLES_SQL: &str = "
+    DROP TABLE IF EXISTS sync_history;
+    DROP TABLE IF EXISTS sync_history_engines;
+";
+
+pub(crate) fn init(db: &Connection) -> Result<()> {
+    let user_version = db.query_one::<i64>("PRAGMA user_version")?;
+    if user_version == 0 {
+        return create(db);
+    }
+    if user_version != VERSION {
+        log::warn!(
+            "Found sync history schema version {} (we only understand version {}); \
+             starting over",
+            user_version,
+            VERSION
+        );
+        db.execute_batch(DROP_TABLES_SQL)?;
+        return create(db);
+    }
+    Ok(())
+}
+
+pub(crate) fn create(db: &Connection) -> Result<()> {
+    log::debug!("Creating sync history schema");
+    db.execute_all(&[
+        CREATE_SYNC_HISTORY_TABLE_SQL,
+        CREATE_SYNC_HISTORY_ENGINES_TABLE_SQL,
+        set_version_sql().as_str(),
+    ])?;
+    Ok(())
+}
+
+fn set_version_sql() -> String {
+    format!("PRAGMA user_version = {version}", version = VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_schema_twice() {
+        let db = Connection::open_in_memory().unwrap();
+        init(&db).unwrap();
+        create(&db).expect("should allow running twice");
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let db = Connection::open_in_memory().unwrap();
+        init(&db).unwrap();
+        db.execute_all(&[
+            "INSERT INTO sync_history (started_at, took, reason, status)
+             VALUES (1, 1, 1, 1)",
+            "PRAGMA user_version = 100",
+        ])
+        .unwrap();
+        init(&db).unwrap();
+        assert_eq!(db.query_one::<i64>("PRAGMA user_version").unwrap(), VERSION);
+        assert_eq!(
+            db.query_one::<i64>("SELECT COUNT(*) FROM sync_history")
+                .unwrap(),
+            0
+        );
+    }
+}
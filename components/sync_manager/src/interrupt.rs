@@ -0,0 +1,131 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Interrupting syncs from another thread.
+//!
+//! A sync spends most of its time either waiting on the network, or running
+//! queries against the engines' databases. Interrupting it means bumping a
+//! counter that `sync15` checks between engines and batches, interrupting
+//! `viaduct`'s handle for the sync so that we stop waiting on any in-flight
+//! HTTP requests, and interrupting the SQLite connections the engines are
+//! using.
+
+use sql_support::{SqlInterruptHandle, SqlInterruptScope};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+/// State shared between a `SyncManagerInterruptHandle` and the syncs it can
+/// interrupt.
+#[derive(Default)]
+pub(crate) struct InterruptState {
+    counter: Arc<AtomicUsize>,
+    /// Handles for the connections used by the current sync, if any.
+    handles: Mutex<Vec<SqlInterruptHandle>>,
+    /// The handle for the current sync's HTTP requests, if any.
+    requests: Mutex<Option<viaduct::RequestInterruptHandle>>,
+}
+
+/// Interrupts the sync that's currently in progress, if there is one. This
+/// is safe to call from any thread, and doesn't wait for the sync to finish.
+#[derive(Clone)]
+pub struct SyncManagerInterruptHandle(pub(crate) Arc<InterruptState>);
+
+impl SyncManagerInterruptHandle {
+    pub fn interrupt(&self) {
+        self.0.counter.fetch_add(1, Ordering::SeqCst);
+        for handle in self.0.handles.lock().unwrap().iter() {
+            handle.interrupt();
+        }
+        if let Some(requests) = &*self.0.requests.lock().unwrap() {
+            requests.interrupt();
+        }
+    }
+}
+
+/// The scope of a single sync. Interrupting the manager after the scope is
+/// created interrupts the sync; interrupting it before has no effect.
+pub struct SyncInterruptScope {
+    scope: SqlInterruptScope,
+    requests: viaduct::RequestInterruptHandle,
+    state: Arc<InterruptState>,
+}
+
+impl SyncInterruptScope {
+    pub(crate) fn new(state: Arc<InterruptState>) -> Self {
+        let requests = viaduct::RequestInterruptHandle::new();
+        *state.requests.lock().unwrap() = Some(requests.clone());
+        Self {
+            scope: SqlInterruptScope::new(state.counter.clone()),
+            requests,
+            state,
+        }
+    }
+
+    /// A scope that can't be interrupted, used for wipes and resets outside
+    /// of a sync.
+    pub(crate) fn uninterruptible() -> Self {
+        Self::new(Arc::default())
+    }
+
+    /// The interruptee to pass to stores and `sync15`.
+    pub fn interruptee(&self) -> &SqlInterruptScope {
+        &self.scope
+    }
+
+    /// The handle to pass to `viaduct::with_interrupt_handle`, so that
+    /// interrupting the sync stops waiting for responses.
+    pub(crate) fn request_interrupt_handle(&self) -> viaduct::RequestInterruptHandle {
+        self.requests.clone()
+    }
+
+    /// Registers a handle for a connection that the sync is about to use, so
+    /// that interrupting the sync also interrupts any queries running on it.
+    /// Engines should call this from `SyncEngine::with_stores`.
+    pub fn add_sql_interrupt_handle(&self, handle: SqlInterruptHandle) {
+        let mut handles = self.state.handles.lock().unwrap();
+        // We may have been interrupted before the engine got its connection.
+        if self.scope.was_interrupted() {
+            handle.interrupt();
+        }
+        handles.push(handle);
+    }
+}
+
+impl Drop for SyncInterruptScope {
+    fn drop(&mut self) {
+        // The connections may be closed once the sync is done, so don't hold
+        // on to their handles.
+        self.state.handles.lock().unwrap().clear();
+        self.state.requests.lock().unwrap().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interrupt_support::Interruptee;
+
+    #[test]
+    fn test_interrupt_scope() {
+        let state = Arc::new(InterruptState::default());
+        let handle = SyncManagerInterruptHandle(state.clone());
+
+        // Interrupting before a sync starts doesn't affect it.
+        handle.interrupt();
+        let scope = SyncInterruptScope::new(state.clone());
+        assert!(!scope.interruptee().was_interrupted());
+        assert!(!scope.request_interrupt_handle().was_interrupted());
+
+        handle.interrupt();
+        assert!(scope.interruptee().was_interrupted());
+        assert!(scope.request_interrupt_handle().was_interrupted());
+        drop(scope);
+
+        let scope = SyncInterruptScope::new(state);
+        assert!(!scope.interruptee().was_interrupted());
+        assert!(!scope.request_interrupt_handle().was_interrupted());
+    }
+}
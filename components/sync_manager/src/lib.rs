@@ -5,25 +5,46 @@
 #![allow(unknown_lints)]
 #![warn(rust_2018_idioms)]
 
+mod engines;
 pub mod error;
 mod ffi;
+pub mod history;
+mod interrupt;
 mod manager;
+mod registry;
+pub mod scheduler;
 
 pub use error::{Error, ErrorKind, Result};
+pub use interrupt::{SyncInterruptScope, SyncManagerInterruptHandle};
+pub use registry::{EngineCapabilities, SyncEngine};
+pub use scheduler::{Clock, SyncPolicy, SyncScheduler, SystemClock};
 
 pub mod msg_types {
     include!("mozilla.appservices.syncmanager.protobuf.rs");
 }
 
+use interrupt::InterruptState;
 use logins::PasswordEngine;
 use manager::SyncManager;
 use places::PlacesApi;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use tabs::TabsEngine;
+use webext_storage::store::Store as WebExtStorageStore;
 
 lazy_static::lazy_static! {
-    static ref MANAGER: Mutex<SyncManager> = Mutex::new(SyncManager::new());
+    // Kept outside of the manager, since the manager is locked for the whole
+    // sync.
+    static ref INTERRUPT_STATE: Arc<InterruptState> = Arc::default();
+    static ref SCHEDULER: Arc<Mutex<SyncScheduler>> = Arc::new(Mutex::new(SyncScheduler::new(
+        SyncPolicy::default(),
+        Box::new(SystemClock),
+    )));
+    static ref MANAGER: Mutex<SyncManager> = Mutex::new(SyncManager::new(
+        INTERRUPT_STATE.clone(),
+        SCHEDULER.clone(),
+    ));
 }
 
 pub fn set_places(places: Arc<PlacesApi>) {
@@ -41,6 +62,21 @@ pub fn set_tabs(tabs: Arc<Mutex<TabsEngine>>) {
     manager.set_tabs(tabs);
 }
 
+pub fn set_webext_storage(store: Arc<Mutex<WebExtStorageStore>>) {
+    let mut manager = MANAGER.lock().unwrap();
+    manager.set_webext_storage(store);
+}
+
+/// Registers a custom engine under `collection`. See `SyncEngine` for details.
+pub fn register_engine(
+    collection: &str,
+    capabilities: EngineCapabilities,
+    engine: Arc<dyn SyncEngine>,
+) {
+    let mut manager = MANAGER.lock().unwrap();
+    manager.register_engine(collection, capabilities, engine);
+}
+
 pub fn disconnect() {
     let mut manager = MANAGER.lock().unwrap();
     manager.disconnect();
@@ -66,7 +102,50 @@ pub fn reset_all() -> Result<()> {
     manager.reset_all()
 }
 
+/// Returns a handle that can be used to interrupt a sync from another thread.
+/// Interrupting only affects the sync in progress, not any later syncs.
+pub fn new_interrupt_handle() -> SyncManagerInterruptHandle {
+    SyncManagerInterruptHandle(INTERRUPT_STATE.clone())
+}
+
 pub fn sync(params: msg_types::SyncParams) -> Result<msg_types::SyncResult> {
     let mut manager = MANAGER.lock().unwrap();
     manager.sync(params)
 }
+
+/// Compares the records on the server with the local data for the engines in
+/// `params`, without changing anything. See `SyncManager::validate`.
+pub fn validate(params: msg_types::SyncParams) -> Result<msg_types::SyncResult> {
+    let mut manager = MANAGER.lock().unwrap();
+    manager.validate(params)
+}
+
+/// Calls `f` with the scheduler that `sync` reports to, to find out when the
+/// next sync is due, or tell it about the user and local changes. Unlike the
+/// other functions here, this doesn't wait for a sync in progress.
+pub fn with_scheduler<T>(f: impl FnOnce(&mut SyncScheduler) -> T) -> T {
+    let mut scheduler = SCHEDULER.lock().unwrap();
+    f(&mut scheduler)
+}
+
+/// Starts recording syncs in the database at `path`. See `history` for
+/// details.
+pub fn open_history(path: impl AsRef<Path>) -> Result<()> {
+    let mut manager = MANAGER.lock().unwrap();
+    manager.open_history(path)
+}
+
+/// Returns up to `limit` of the most recent syncs, newest first. Returns an
+/// empty history if `open_history` hasn't been called. Like the other
+/// functions here, this waits for any sync in progress to finish.
+pub fn sync_history(limit: u32) -> Result<msg_types::SyncHistory> {
+    let manager = MANAGER.lock().unwrap();
+    Ok(msg_types::SyncHistory {
+        entries: manager.history(limit)?,
+    })
+}
+
+pub fn clear_sync_history() -> Result<()> {
+    let manager = MANAGER.lock().unwrap();
+    manager.clear_history()
+}
@@ -2,25 +2,32 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::engines::{
+    LoginsEngine, PlacesEngine, TabsSyncEngine, WebExtStorageEngine, BOOKMARKS_ENGINE,
+    HISTORY_ENGINE, LOGINS_ENGINE, TABS_ENGINE, WEBEXT_STORAGE_ENGINE,
+};
 use crate::error::*;
-use crate::msg_types::{DeviceType, ServiceStatus, SyncParams, SyncReason, SyncResult};
-use crate::{reset, reset_all, wipe, wipe_all};
+use crate::history::{self, SyncHistoryStore};
+use crate::interrupt::{InterruptState, SyncInterruptScope};
+use crate::msg_types::{
+    DeviceType, EngineSyncPlan, PlannedAction, RecordPlan, ServiceStatus, SyncHistoryEntry,
+    SyncParams, SyncReason, SyncResult,
+};
+use crate::registry::{with_all_stores, EngineCapabilities, EngineRegistry, SyncEngine};
+use crate::scheduler::SyncScheduler;
 use logins::PasswordEngine;
-use places::{bookmark_sync::store::BookmarksStore, history_sync::store::HistoryStore, PlacesApi};
+use places::PlacesApi;
 use std::collections::{HashMap, HashSet};
-use std::sync::{atomic::AtomicUsize, Arc, Mutex, Weak};
-use std::time::SystemTime;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 use sync15::{
     self,
     clients::{self, Command, CommandProcessor, CommandStatus, Settings},
-    MemoryCachedState,
+    telemetry, MemoryCachedState, StoreSyncAssociation,
 };
 use tabs::TabsEngine;
-
-const LOGINS_ENGINE: &str = "passwords";
-const HISTORY_ENGINE: &str = "history";
-const BOOKMARKS_ENGINE: &str = "bookmarks";
-const TABS_ENGINE: &str = "tabs";
+use webext_storage::store::Store as WebExtStorageStore;
 
 // Casts aren't allowed in `match` arms, so we can't directly match
 // `SyncParams.device_type`, which is an `i32`, against `DeviceType`
@@ -35,173 +42,181 @@ const DEVICE_TYPE_TV: i32 = DeviceType::Tv as i32;
 
 pub struct SyncManager {
     mem_cached_state: Option<MemoryCachedState>,
-    places: Weak<PlacesApi>,
-    logins: Weak<Mutex<PasswordEngine>>,
-    tabs: Weak<Mutex<TabsEngine>>,
+    engines: EngineRegistry,
+    interrupt_state: Arc<InterruptState>,
+    scheduler: Arc<Mutex<SyncScheduler>>,
+    history: Option<SyncHistoryStore>,
 }
 
 impl SyncManager {
-    pub fn new() -> Self {
+    /// Creates a manager whose syncs are interrupted by the handles for
+    /// `interrupt_state`. The state is owned by the caller, so that syncs can
+    /// be interrupted without locking the manager. Likewise, `scheduler`
+    /// hears about every sync, and can be asked when the next one is due
+    /// while a sync is running.
+    pub fn new(interrupt_state: Arc<InterruptState>, scheduler: Arc<Mutex<SyncScheduler>>) -> Self {
         Self {
             mem_cached_state: None,
-            places: Weak::new(),
-            logins: Weak::new(),
-            tabs: Weak::new(),
+            engines: EngineRegistry::default(),
+            interrupt_state,
+            scheduler,
+            history: None,
+        }
+    }
+
+    /// Opens the database at `path` to record syncs in. Until this is
+    /// called, syncs aren't recorded.
+    pub fn open_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.history = Some(SyncHistoryStore::open(path)?);
+        Ok(())
+    }
+
+    /// Returns up to `limit` of the most recent syncs, newest first.
+    pub fn history(&self, limit: u32) -> Result<Vec<SyncHistoryEntry>> {
+        match &self.history {
+            Some(history) => history.recent(limit),
+            None => Ok(Vec::new()),
         }
     }
 
+    pub fn clear_history(&self) -> Result<()> {
+        match &self.history {
+            Some(history) => history.clear(),
+            None => Ok(()),
+        }
+    }
+
+    /// Registers `engine` under `collection`, replacing any engine already
+    /// registered under that name. Registered engines are synced when
+    /// requested, and are wiped and reset according to `capabilities`.
+    pub fn register_engine(
+        &mut self,
+        collection: &str,
+        capabilities: EngineCapabilities,
+        engine: Arc<dyn SyncEngine>,
+    ) {
+        self.engines.register(collection, capabilities, engine);
+    }
+
     pub fn set_places(&mut self, places: Arc<PlacesApi>) {
-        self.places = Arc::downgrade(&places);
+        let engine: Arc<dyn SyncEngine> = Arc::new(PlacesEngine(Arc::downgrade(&places)));
+        self.register_engine(HISTORY_ENGINE, EngineCapabilities::ALL, engine.clone());
+        self.register_engine(BOOKMARKS_ENGINE, EngineCapabilities::ALL, engine);
     }
 
     pub fn set_logins(&mut self, logins: Arc<Mutex<PasswordEngine>>) {
-        self.logins = Arc::downgrade(&logins);
+        self.register_engine(
+            LOGINS_ENGINE,
+            EngineCapabilities::ALL,
+            Arc::new(LoginsEngine(Arc::downgrade(&logins))),
+        );
     }
 
     pub fn set_tabs(&mut self, tabs: Arc<Mutex<TabsEngine>>) {
-        self.tabs = Arc::downgrade(&tabs);
+        // Local tabs come from the application, so there's nothing for us to
+        // wipe.
+        self.register_engine(
+            TABS_ENGINE,
+            EngineCapabilities {
+                wipe: false,
+                reset: true,
+            },
+            Arc::new(TabsSyncEngine(Arc::downgrade(&tabs))),
+        );
+    }
+
+    pub fn set_webext_storage(&mut self, store: Arc<Mutex<WebExtStorageStore>>) {
+        self.register_engine(
+            WEBEXT_STORAGE_ENGINE,
+            EngineCapabilities::ALL,
+            Arc::new(WebExtStorageEngine(Arc::downgrade(&store))),
+        );
     }
 
     pub fn wipe(&mut self, engine: &str) -> Result<()> {
-        match engine {
-            "logins" => {
-                if let Some(logins) = self
-                    .logins
-                    .upgrade()
-                    .as_ref()
-                    .map(|l| l.lock().expect("poisoned logins mutex"))
-                {
-                    logins.wipe()?;
-                    Ok(())
-                } else {
-                    Err(ErrorKind::ConnectionClosed(engine.into()).into())
-                }
-            }
-            "bookmarks" => {
-                if let Some(places) = self.places.upgrade() {
-                    places.wipe_bookmarks()?;
-                    Ok(())
-                } else {
-                    Err(ErrorKind::ConnectionClosed(engine.into()).into())
-                }
-            }
-            "history" => {
-                if let Some(places) = self.places.upgrade() {
-                    places.wipe_history()?;
-                    Ok(())
-                } else {
-                    Err(ErrorKind::ConnectionClosed(engine.into()).into())
-                }
-            }
-            _ => Err(ErrorKind::UnknownEngine(engine.into()).into()),
-        }
+        self.engines.wipe(engine)
     }
 
     pub fn wipe_all(&mut self) -> Result<()> {
-        if let Some(logins) = self
-            .logins
-            .upgrade()
-            .as_ref()
-            .map(|l| l.lock().expect("poisoned logins mutex"))
-        {
-            logins.wipe()?;
-        }
-        if let Some(places) = self.places.upgrade() {
-            places.wipe_bookmarks()?;
-            places.wipe_history()?;
-        }
-        Ok(())
+        self.engines.wipe_all()
     }
 
     pub fn reset(&mut self, engine: &str) -> Result<()> {
-        match engine {
-            "logins" => {
-                if let Some(logins) = self
-                    .logins
-                    .upgrade()
-                    .as_ref()
-                    .map(|l| l.lock().expect("poisoned logins mutex"))
-                {
-                    logins.reset()?;
-                    Ok(())
-                } else {
-                    Err(ErrorKind::ConnectionClosed(engine.into()).into())
-                }
-            }
-            "bookmarks" | "history" => {
-                if let Some(places) = self.places.upgrade() {
-                    if engine == "bookmarks" {
-                        places.reset_bookmarks()?;
-                    } else {
-                        places.reset_history()?;
-                    }
-                    Ok(())
-                } else {
-                    Err(ErrorKind::ConnectionClosed(engine.into()).into())
-                }
-            }
-            _ => Err(ErrorKind::UnknownEngine(engine.into()).into()),
-        }
+        self.engines.reset(engine)
     }
 
     pub fn reset_all(&mut self) -> Result<()> {
-        if let Some(logins) = self
-            .logins
-            .upgrade()
-            .as_ref()
-            .map(|l| l.lock().expect("poisoned logins mutex"))
-        {
-            logins.reset()?;
-        }
-        if let Some(places) = self.places.upgrade() {
-            places.reset_bookmarks()?;
-            places.reset_history()?;
-        }
-        Ok(())
+        self.engines.reset_all()
     }
 
     pub fn disconnect(&mut self) {
-        if let Some(logins) = self
-            .logins
-            .upgrade()
-            .as_ref()
-            .map(|l| l.lock().expect("poisoned logins mutex"))
+        self.engines.disconnect()
+    }
+
+    pub fn sync(&mut self, params: SyncParams) -> Result<SyncResult> {
+        let reason = params.reason;
+        let dry_run = params.dry_run.unwrap_or(false);
+        let started_at = system_time_to_millis(Some(SystemTime::now())).unwrap_or_default();
+        let start = Instant::now();
+        // Dry runs don't change anything, so we leave them out of the
+        // schedule and the history.
+        if !dry_run {
+            self.scheduler.lock().unwrap().sync_started();
+        }
+        let (result, telemetry) = match self.sync_or_back_off(params, false) {
+            Ok((result, telemetry)) => (Ok(result), telemetry),
+            Err(e) => (Err(e), None),
+        };
+        if dry_run {
+            return result;
+        }
         {
-            if let Err(e) = logins.reset() {
-                log::error!("Failed to reset logins: {}", e);
+            let mut scheduler = self.scheduler.lock().unwrap();
+            match &result {
+                Ok(result) => scheduler.sync_finished(result),
+                Err(_) => scheduler.sync_failed(),
             }
-        } else {
-            log::warn!("Unable to reset logins, be sure to call set_logins before disconnect if this is surprising");
         }
-
-        if let Some(places) = self.places.upgrade() {
-            if let Err(e) = places.reset_bookmarks() {
-                log::error!("Failed to reset bookmarks: {}", e);
-            }
-            if let Err(e) = places.reset_history() {
-                log::error!("Failed to reset history: {}", e);
+        if let Some(store) = &self.history {
+            let entry = history::entry_for_sync(
+                reason,
+                started_at,
+                start.elapsed(),
+                &result,
+                telemetry.as_ref(),
+            );
+            // The history is only for diagnostics, so it shouldn't fail the
+            // sync.
+            if let Err(e) = store.record(&entry) {
+                log::warn!("Failed to record sync in the history: {}", e);
             }
-        } else {
-            log::warn!("Unable to reset places, be sure to call set_places before disconnect if this is surprising");
         }
+        result
     }
 
-    pub fn sync(&mut self, params: SyncParams) -> Result<SyncResult> {
-        let mut have_engines = vec![];
-        let places = self.places.upgrade();
-        let tabs = self.tabs.upgrade();
-        let logins = self.logins.upgrade();
-        if places.is_some() {
-            have_engines.push(HISTORY_ENGINE);
-            have_engines.push(BOOKMARKS_ENGINE);
-        }
-        if logins.is_some() {
-            have_engines.push(LOGINS_ENGINE);
-        }
-        if tabs.is_some() {
-            have_engines.push(TABS_ENGINE);
-        }
-        check_engine_list(&params.engines_to_sync, &have_engines)?;
+    /// Downloads every record for the engines in `params`, and compares them
+    /// with the local data, without changing anything. The problems that
+    /// each engine found are in the `validation` section of the engine's
+    /// telemetry, in `SyncResult.telemetry_json`. `params.dry_run` is
+    /// ignored. Like dry runs, validations aren't scheduled or recorded in
+    /// the history, but they do respect the server's backoff.
+    pub fn validate(&mut self, params: SyncParams) -> Result<SyncResult> {
+        let (result, _) = self.sync_or_back_off(params, true)?;
+        Ok(result)
+    }
+
+    /// Syncs, or validates if `validate` is true, and returns the telemetry
+    /// along with the result, unless we're backing off.
+    fn sync_or_back_off(
+        &mut self,
+        params: SyncParams,
+        validate: bool,
+    ) -> Result<(SyncResult, Option<telemetry::SyncTelemetryPing>)> {
+        check_engine_list(
+            &params.engines_to_sync,
+            &self.engines.registered(),
+            &self.engines.open(),
+        )?;
 
         let next_sync_after = self
             .mem_cached_state
@@ -209,14 +224,15 @@ impl SyncManager {
             .and_then(|mcs| mcs.get_next_sync_after());
         if !backoff_in_effect(next_sync_after, &params) {
             log::info!("No backoff in effect (or we decided to ignore it), starting sync");
-            self.do_sync(params)
+            let (result, telemetry) = self.do_sync(params, validate)?;
+            Ok((result, Some(telemetry)))
         } else {
             let ts = system_time_to_millis(next_sync_after);
             log::warn!(
                 "Backoff still in effect (until {:?}), bailing out early",
                 ts
             );
-            Ok(SyncResult {
+            let result = SyncResult {
                 status: ServiceStatus::BackedOff as i32,
                 results: Default::default(),
                 have_declined: false,
@@ -225,77 +241,28 @@ impl SyncManager {
                 persisted_state: params.persisted_state.unwrap_or_default(),
                 // It would be nice to record telemetry here.
                 telemetry_json: None,
-            })
+                plans: HashMap::new(),
+            };
+            Ok((result, None))
         }
     }
 
-    fn do_sync(&mut self, mut params: SyncParams) -> Result<SyncResult> {
-        let mut places = self.places.upgrade();
-        let logins = self.logins.upgrade();
-        let tabs = self.tabs.upgrade();
-
+    fn do_sync(
+        &mut self,
+        mut params: SyncParams,
+        validate: bool,
+    ) -> Result<(SyncResult, telemetry::SyncTelemetryPing)> {
         let key_bundle = sync15::KeyBundle::from_ksync_base64(&params.acct_sync_key)?;
         let tokenserver_url = url::Url::parse(&params.acct_tokenserver_url)?;
 
-        let bookmarks_sync = should_sync(&params, BOOKMARKS_ENGINE) && places.is_some();
-        let history_sync = should_sync(&params, HISTORY_ENGINE) && places.is_some();
-        let logins_sync = should_sync(&params, LOGINS_ENGINE) && logins.is_some();
-        let tabs_sync = should_sync(&params, TABS_ENGINE) && tabs.is_some();
-
-        let places_conn = if bookmarks_sync || history_sync {
-            places
-                .as_mut()
-                .expect("trying to sync an engine that has not been configured")
-                .open_sync_connection()
-                .ok()
-        } else {
-            None
-        };
-        let l = if logins_sync {
-            logins.as_ref().map(|l| l.lock().expect("poisoned mutex"))
-        } else {
-            None
-        };
-        let t = if tabs_sync {
-            tabs.as_ref().map(|t| t.lock().expect("poisoned mutex"))
-        } else {
-            None
-        };
+        let groups = self
+            .engines
+            .open_groups(|collection| should_sync(&params, collection));
 
-        // TODO(issue 1684) this isn't ideal, we should have real support for interruption.
-        let p = Arc::new(AtomicUsize::new(0));
-        let interruptee = sql_support::SqlInterruptScope::new(p);
+        let scope = SyncInterruptScope::new(self.interrupt_state.clone());
 
         let mut mem_cached_state = self.mem_cached_state.take().unwrap_or_default();
         let mut disk_cached_state = params.persisted_state.take();
-        // `sync_multiple` takes a &[&dyn Store], but we need something to hold
-        // ownership of our stores.
-        let mut stores: Vec<Box<dyn sync15::Store>> = vec![];
-
-        if let Some(pc) = places_conn.as_ref() {
-            assert!(
-                history_sync || bookmarks_sync,
-                "Should have already checked"
-            );
-            if history_sync {
-                stores.push(Box::new(HistoryStore::new(pc, &interruptee)))
-            }
-            if bookmarks_sync {
-                stores.push(Box::new(BookmarksStore::new(pc, &interruptee)))
-            }
-        }
-
-        if let Some(le) = l.as_ref() {
-            assert!(logins_sync, "Should have already checked");
-            stores.push(Box::new(logins::LoginStore::new(&le.db)));
-        }
-
-        if let Some(tbs) = t.as_ref() {
-            assert!(tabs_sync, "Should have already checked");
-            stores.push(Box::new(tabs::TabsStore::new(&tbs.storage)));
-        }
-
-        let store_refs: Vec<&dyn sync15::Store> = stores.iter().map(|s| &**s).collect();
 
         let client_init = sync15::Sync15StorageClientInit {
             key_id: params.acct_key_id.clone(),
@@ -309,8 +276,8 @@ impl SyncManager {
         };
 
         let settings = Settings {
-            fxa_device_id: params.fxa_device_id,
-            device_name: params.device_name,
+            fxa_device_id: params.fxa_device_id.clone(),
+            device_name: params.device_name.clone(),
             device_type: match params.device_type {
                 DEVICE_TYPE_DESKTOP => clients::DeviceType::Desktop,
                 DEVICE_TYPE_MOBILE => clients::DeviceType::Mobile,
@@ -326,20 +293,47 @@ impl SyncManager {
                 }
             },
         };
-        let c = SyncClient::new(settings);
-        let result = sync15::sync_multiple_with_command_processor(
-            Some(&c),
-            &store_refs,
-            &mut disk_cached_state,
-            &mut mem_cached_state,
-            &client_init,
-            &key_bundle,
-            &interruptee,
-            Some(sync15::SyncRequestInfo {
-                engines_to_state_change: engines_to_change,
-                is_user_action: params.reason == (SyncReason::User as i32),
-            }),
-        );
+        let engines = &self.engines;
+        let mut result = None;
+        with_all_stores(&groups, &scope, &[], &mut |all_stores| {
+            // Engines give us stores for all their collections, but we only
+            // sync the ones we were asked to. The others are only used to
+            // apply commands from other clients.
+            let store_refs = all_stores
+                .iter()
+                .copied()
+                .filter(|store| should_sync(&params, &store.collection_name()))
+                .collect::<Vec<_>>();
+            let c = SyncClient {
+                settings: &settings,
+                engines,
+                stores: &store_refs,
+                all_stores,
+            };
+            // Interrupting the sync should also cancel any requests we're
+            // waiting on.
+            let sync_result =
+                viaduct::with_interrupt_handle(scope.request_interrupt_handle(), || {
+                    sync15::sync_multiple_with_command_processor(
+                        Some(&c),
+                        &store_refs,
+                        &mut disk_cached_state,
+                        &mut mem_cached_state,
+                        &client_init,
+                        &key_bundle,
+                        scope.interruptee(),
+                        Some(sync15::SyncRequestInfo {
+                            engines_to_state_change: engines_to_change,
+                            is_user_action: params.reason == (SyncReason::User as i32),
+                            dry_run: params.dry_run.unwrap_or(false),
+                            validate,
+                        }),
+                    )
+                });
+            result = Some(sync_result);
+            Ok(())
+        })?;
+        let result = result.expect("Should have called the sync callback");
         self.mem_cached_state = Some(mem_cached_state);
 
         log::info!("Sync finished with status {:?}", result.service_status);
@@ -377,7 +371,22 @@ impl SyncManager {
         // unserializable type.
         let telemetry_json = serde_json::to_string(&result.telemetry).unwrap();
 
-        Ok(SyncResult {
+        let plans = result
+            .engine_plans
+            .into_iter()
+            .map(|(e, plans)| {
+                let records = plans
+                    .into_iter()
+                    .map(|plan| RecordPlan {
+                        id: plan.id.into(),
+                        action: PlannedAction::from(plan.action) as i32,
+                    })
+                    .collect();
+                (e, EngineSyncPlan { records })
+            })
+            .collect();
+
+        let sync_result = SyncResult {
             status,
             results,
             have_declined: result.declined.is_some(),
@@ -385,7 +394,9 @@ impl SyncManager {
             next_sync_allowed_at: system_time_to_millis(result.next_sync_after),
             persisted_state: disk_cached_state.unwrap_or_default(),
             telemetry_json: Some(telemetry_json),
-        })
+            plans,
+        };
+        Ok((sync_result, result.telemetry))
     }
 }
 
@@ -427,12 +438,23 @@ impl From<sync15::ServiceStatus> for ServiceStatus {
             ServiceError => ServiceStatus::ServiceError,
             AuthenticationError => ServiceStatus::AuthError,
             BackedOff => ServiceStatus::BackedOff,
-            Interrupted => ServiceStatus::OtherError, // Eh...
+            Interrupted => ServiceStatus::Interrupted,
             OtherError => ServiceStatus::OtherError,
         }
     }
 }
 
+impl From<sync15::PlannedAction> for PlannedAction {
+    fn from(action: sync15::PlannedAction) -> Self {
+        match action {
+            sync15::PlannedAction::Apply => PlannedAction::Apply,
+            sync15::PlannedAction::Upload => PlannedAction::Upload,
+            sync15::PlannedAction::Delete => PlannedAction::Delete,
+            sync15::PlannedAction::Conflict => PlannedAction::Conflict,
+        }
+    }
+}
+
 fn system_time_to_millis(st: Option<SystemTime>) -> Option<i64> {
     use std::convert::TryFrom;
     let d = st?.duration_since(std::time::UNIX_EPOCH).ok()?;
@@ -444,14 +466,14 @@ fn should_sync(p: &SyncParams, engine: &str) -> bool {
     p.sync_all_engines || p.engines_to_sync.iter().any(|e| e == engine)
 }
 
-fn check_engine_list(list: &[String], have_engines: &[&str]) -> Result<()> {
+fn check_engine_list(list: &[String], registered: &[&str], have_engines: &[&str]) -> Result<()> {
     log::trace!(
         "Checking engines requested ({:?}) vs local engines ({:?})",
         list,
         have_engines
     );
     for e in list {
-        if [BOOKMARKS_ENGINE, HISTORY_ENGINE, LOGINS_ENGINE, TABS_ENGINE].contains(&e.as_ref()) {
+        if registered.contains(&e.as_ref()) {
             if !have_engines.iter().any(|engine| e == engine) {
                 return Err(ErrorKind::UnsupportedFeature(e.to_string()).into());
             }
@@ -462,30 +484,105 @@ fn check_engine_list(list: &[String], have_engines: &[&str]) -> Result<()> {
     Ok(())
 }
 
-struct SyncClient(Settings);
+/// Applies commands from other clients to the registered engines. Commands
+/// are processed during a sync, while the engines being synced are locked, so
+/// commands for those engines go through their stores instead. This includes
+/// collections of those engines that we aren't syncing, like bookmarks when
+/// only syncing history, since places only allows one sync connection.
+struct SyncClient<'a> {
+    settings: &'a Settings,
+    engines: &'a EngineRegistry,
+    /// The stores that we're syncing.
+    stores: &'a [&'a dyn sync15::Store],
+    /// The stores for all collections of the engines that we're syncing.
+    all_stores: &'a [&'a dyn sync15::Store],
+}
+
+impl<'a> SyncClient<'a> {
+    fn syncing_store(&self, collection: &str) -> Option<&dyn sync15::Store> {
+        self.all_stores
+            .iter()
+            .copied()
+            .find(|store| store.collection_name() == collection)
+    }
+
+    fn wipe(&self, collection: &str) -> Result<()> {
+        match self.syncing_store(collection) {
+            Some(store) => {
+                self.engines.check(collection, |c| c.wipe)?;
+                store.wipe()?;
+                Ok(())
+            }
+            None => self.engines.wipe(collection),
+        }
+    }
+
+    fn wipe_all(&self) -> Result<()> {
+        for collection in self.engines.open_capable(|c| c.wipe) {
+            self.wipe(collection)?;
+        }
+        Ok(())
+    }
+
+    fn reset(&self, collection: &str) -> Result<()> {
+        match self.syncing_store(collection) {
+            Some(store) => {
+                self.engines.check(collection, |c| c.reset)?;
+                store.reset(&StoreSyncAssociation::Disconnected)?;
+                Ok(())
+            }
+            None => self.engines.reset(collection),
+        }
+    }
+
+    fn reset_all(&self) -> Result<()> {
+        for collection in self.engines.open_capable(|c| c.reset) {
+            self.reset(collection)?;
+        }
+        Ok(())
+    }
 
-impl SyncClient {
-    pub fn new(settings: Settings) -> SyncClient {
-        SyncClient(settings)
+    /// Offers a command that isn't a wipe or reset to the stores being
+    /// synced, until one of them handles it. Repair commands only go to the
+    /// store for their collection.
+    fn apply_store_command(&self, command: &Command) -> anyhow::Result<CommandStatus> {
+        let collection = match command {
+            Command::RepairRequest(request) => Some(request.collection.as_str()),
+            Command::RepairResponse(response) => Some(response.collection.as_str()),
+            _ => None,
+        };
+        for store in self.stores {
+            if collection.map_or(false, |c| store.collection_name() != c) {
+                continue;
+            }
+            match store.apply_incoming_command(command)? {
+                CommandStatus::Unsupported => {}
+                status => return Ok(status),
+            }
+        }
+        Ok(CommandStatus::Unsupported)
     }
 }
 
-impl CommandProcessor for SyncClient {
+impl<'a> CommandProcessor for SyncClient<'a> {
     fn settings(&self) -> &Settings {
-        &self.0
+        self.settings
     }
 
     fn apply_incoming_command(&self, command: Command) -> anyhow::Result<CommandStatus> {
         let result = match command {
-            Command::Wipe(engine) => wipe(&engine),
-            Command::WipeAll => wipe_all(),
-            Command::Reset(engine) => reset(&engine),
-            Command::ResetAll => reset_all(),
+            Command::Wipe(engine) => self.wipe(&engine),
+            Command::WipeAll => self.wipe_all(),
+            Command::Reset(engine) => self.reset(&engine),
+            Command::ResetAll => self.reset_all(),
+            command => return self.apply_store_command(&command),
         };
         match result {
             Ok(()) => Ok(CommandStatus::Applied),
             Err(err) => match err.kind() {
-                ErrorKind::UnknownEngine(_) => Ok(CommandStatus::Unsupported),
+                ErrorKind::UnknownEngine(_) | ErrorKind::UnsupportedFeature(_) => {
+                    Ok(CommandStatus::Unsupported)
+                }
                 _ => Err(err.into()),
             },
         }
@@ -494,4 +591,64 @@ impl CommandProcessor for SyncClient {
     fn fetch_outgoing_commands(&self) -> anyhow::Result<HashSet<Command>> {
         Ok(HashSet::new())
     }
+
+    fn fetch_outgoing_client_commands(&self) -> anyhow::Result<HashMap<String, HashSet<Command>>> {
+        let mut commands: HashMap<String, HashSet<Command>> = HashMap::new();
+        for store in self.stores {
+            for (client_id, store_commands) in store.fetch_outgoing_commands()? {
+                commands
+                    .entry(client_id)
+                    .or_default()
+                    .extend(store_commands);
+            }
+        }
+        Ok(commands)
+    }
+
+    fn commands_sent(&self, sent: &HashMap<String, HashSet<Command>>) -> anyhow::Result<()> {
+        for store in self.stores {
+            store.commands_sent(sent)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scheduler::{SyncPolicy, SystemClock};
+
+    #[test]
+    fn test_sync_feeds_scheduler() {
+        let scheduler = Arc::new(Mutex::new(SyncScheduler::new(
+            SyncPolicy::default(),
+            Box::new(SystemClock),
+        )));
+        let mut manager = SyncManager::new(Arc::default(), scheduler.clone());
+        assert!(scheduler.lock().unwrap().should_sync());
+
+        // Dry runs don't count.
+        let params = SyncParams {
+            engines_to_sync: vec!["unknown".to_string()],
+            dry_run: Some(true),
+            ..SyncParams::default()
+        };
+        manager.sync(params.clone()).unwrap_err();
+        assert!(scheduler.lock().unwrap().should_sync());
+
+        // Nor do validations.
+        manager.validate(params.clone()).unwrap_err();
+        assert!(scheduler.lock().unwrap().should_sync());
+
+        // Failed syncs are retried after a delay.
+        manager
+            .sync(SyncParams {
+                dry_run: None,
+                ..params
+            })
+            .unwrap_err();
+        let scheduler = scheduler.lock().unwrap();
+        assert!(!scheduler.should_sync());
+        assert!(scheduler.time_until_next_sync() <= SyncPolicy::default().error_retry_delay);
+    }
 }
@@ -26,6 +26,23 @@ pub struct SyncParams {
     pub device_name: std::string::String,
     #[prost(enumeration="DeviceType", required, tag="12")]
     pub device_type: i32,
+    /// If true, download and reconcile as usual, but don't change anything
+    /// locally or on the server. The planned changes are reported in
+    /// `SyncResult.plans`.
+    #[prost(bool, optional, tag="13")]
+    pub dry_run: ::std::option::Option<bool>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordPlan {
+    #[prost(string, required, tag="1")]
+    pub id: std::string::String,
+    #[prost(enumeration="PlannedAction", required, tag="2")]
+    pub action: i32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EngineSyncPlan {
+    #[prost(message, repeated, tag="1")]
+    pub records: ::std::vec::Vec<RecordPlan>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SyncResult {
@@ -45,6 +62,60 @@ pub struct SyncResult {
     pub persisted_state: std::string::String,
     #[prost(string, optional, tag="7")]
     pub telemetry_json: ::std::option::Option<std::string::String>,
+    /// Only set for dry runs.
+    #[prost(map="string, message", tag="8")]
+    pub plans: ::std::collections::HashMap<std::string::String, EngineSyncPlan>,
+}
+/// The outcome of syncing one engine, as recorded in the sync history.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EngineSyncHistory {
+    #[prost(string, required, tag="1")]
+    pub name: std::string::String,
+    /// Milliseconds.
+    #[prost(int64, required, tag="2")]
+    pub took: i64,
+    #[prost(uint32, required, tag="3")]
+    pub incoming_applied: u32,
+    #[prost(uint32, required, tag="4")]
+    pub incoming_failed: u32,
+    #[prost(uint32, required, tag="5")]
+    pub incoming_new_failed: u32,
+    #[prost(uint32, required, tag="6")]
+    pub incoming_reconciled: u32,
+    #[prost(uint32, required, tag="7")]
+    pub outgoing_sent: u32,
+    #[prost(uint32, required, tag="8")]
+    pub outgoing_failed: u32,
+    /// Not set if the engine synced without errors.
+    #[prost(string, optional, tag="9")]
+    pub failure: ::std::option::Option<std::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SyncHistoryEntry {
+    /// Milliseconds since the Unix epoch.
+    #[prost(int64, required, tag="1")]
+    pub started_at: i64,
+    /// Milliseconds.
+    #[prost(int64, required, tag="2")]
+    pub took: i64,
+    #[prost(enumeration="SyncReason", required, tag="3")]
+    pub reason: i32,
+    #[prost(enumeration="ServiceStatus", required, tag="4")]
+    pub status: i32,
+    /// Not set if the sync didn't fail as a whole. Individual engines may
+    /// still have failed.
+    #[prost(string, optional, tag="5")]
+    pub failure: ::std::option::Option<std::string::String>,
+    #[prost(int64, optional, tag="6")]
+    pub next_sync_allowed_at: ::std::option::Option<i64>,
+    #[prost(message, repeated, tag="7")]
+    pub engines: ::std::vec::Vec<EngineSyncHistory>,
+}
+/// The most recent syncs, newest first.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SyncHistory {
+    #[prost(message, repeated, tag="1")]
+    pub entries: ::std::vec::Vec<SyncHistoryEntry>,
 }
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
@@ -73,4 +144,13 @@ pub enum ServiceStatus {
     AuthError = 4,
     BackedOff = 5,
     OtherError = 6,
+    Interrupted = 7,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum PlannedAction {
+    Apply = 1,
+    Upload = 2,
+    Delete = 3,
+    Conflict = 4,
 }
@@ -0,0 +1,376 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::*;
+use crate::interrupt::SyncInterruptScope;
+use std::sync::Arc;
+use sync15::StoreSyncAssociation;
+
+/// An engine that the manager can sync, wipe and reset.
+///
+/// Engines are registered under one or more collection names with
+/// `SyncManager::register_engine`. The same engine can be registered under
+/// several names if it provides stores for more than one collection; places,
+/// for example, provides both history and bookmarks over a single connection.
+///
+/// The manager only keeps weak references to the storage behind the built-in
+/// engines, so an engine can be closed at any time. `is_open` should return
+/// `false` once it is.
+pub trait SyncEngine: Send + Sync {
+    /// Returns `true` if the engine's storage is still open.
+    fn is_open(&self) -> bool;
+
+    /// Calls `f` with a store for each of `collections`, in the same order.
+    /// `collections` is never empty, and only contains names the engine was
+    /// registered under. Any locks or connections the stores need should be
+    /// held for the duration of the call, and engines should register an
+    /// interrupt handle for each connection with
+    /// `SyncInterruptScope::add_sql_interrupt_handle`, so that interrupting
+    /// the sync also interrupts any queries the stores are running.
+    fn with_stores(
+        &self,
+        collections: &[&str],
+        scope: &SyncInterruptScope,
+        f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+    ) -> Result<()>;
+
+    /// Deletes all local data for `collection`, including data that was never
+    /// synced. The default implementation calls `sync15::Store::wipe`.
+    fn wipe(&self, collection: &str) -> Result<()> {
+        let scope = SyncInterruptScope::uninterruptible();
+        self.with_stores(&[collection], &scope, &mut |stores| {
+            for store in stores {
+                store.wipe()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Discards all sync metadata for `collection`, so that the next sync is
+    /// treated as a first sync. The default implementation calls
+    /// `sync15::Store::reset`.
+    fn reset(&self, collection: &str) -> Result<()> {
+        let scope = SyncInterruptScope::uninterruptible();
+        self.with_stores(&[collection], &scope, &mut |stores| {
+            for store in stores {
+                store.reset(&StoreSyncAssociation::Disconnected)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// What the manager may do with a registered engine, other than syncing it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EngineCapabilities {
+    /// The engine can be wiped, either directly or by a command from another
+    /// client.
+    pub wipe: bool,
+    /// The engine's sync metadata can be reset, either directly, by a command
+    /// from another client, or when disconnecting.
+    pub reset: bool,
+}
+
+impl EngineCapabilities {
+    pub const ALL: EngineCapabilities = EngineCapabilities {
+        wipe: true,
+        reset: true,
+    };
+}
+
+#[derive(Clone)]
+struct RegisteredEngine {
+    collection: String,
+    capabilities: EngineCapabilities,
+    engine: Arc<dyn SyncEngine>,
+}
+
+/// The engines known to the manager, in the order they were registered, which
+/// is also the order they're synced in. Cloning a registry is cheap, and the
+/// clone shares the same engines.
+#[derive(Clone, Default)]
+pub(crate) struct EngineRegistry {
+    engines: Vec<RegisteredEngine>,
+}
+
+impl EngineRegistry {
+    /// Registers `engine` under `collection`, replacing any engine that was
+    /// already registered under that name.
+    pub fn register(
+        &mut self,
+        collection: &str,
+        capabilities: EngineCapabilities,
+        engine: Arc<dyn SyncEngine>,
+    ) {
+        let registered = RegisteredEngine {
+            collection: collection.to_owned(),
+            capabilities,
+            engine,
+        };
+        match self.engines.iter_mut().find(|e| e.collection == collection) {
+            Some(existing) => *existing = registered,
+            None => self.engines.push(registered),
+        }
+    }
+
+    /// Returns the names of all registered collections.
+    pub fn registered(&self) -> Vec<&str> {
+        self.engines.iter().map(|e| e.collection.as_str()).collect()
+    }
+
+    /// Returns the names of all collections whose engines are still open.
+    pub fn open(&self) -> Vec<&str> {
+        self.engines
+            .iter()
+            .filter(|e| e.engine.is_open())
+            .map(|e| e.collection.as_str())
+            .collect()
+    }
+
+    /// Returns the open engines with at least one collection accepted by
+    /// `should_sync`, grouped so that engines registered under several names
+    /// are only asked for their stores once. Each group lists all the engine's
+    /// collections, including ones that we aren't syncing, so that commands
+    /// for those collections can use the same stores and connection as the
+    /// sync.
+    pub fn open_groups<'a>(
+        &'a self,
+        should_sync: impl Fn(&str) -> bool,
+    ) -> Vec<(&'a dyn SyncEngine, Vec<&'a str>)> {
+        let mut groups: Vec<(&'a dyn SyncEngine, Vec<&'a str>)> = Vec::new();
+        for e in &self.engines {
+            if !e.engine.is_open() {
+                continue;
+            }
+            let engine = &*e.engine;
+            match groups.iter_mut().find(|(g, _)| same_engine(*g, engine)) {
+                Some((_, collections)) => collections.push(&e.collection),
+                None => groups.push((engine, vec![&e.collection])),
+            }
+        }
+        groups.retain(|(_, collections)| collections.iter().any(|c| should_sync(c)));
+        groups
+    }
+
+    pub fn wipe(&self, collection: &str) -> Result<()> {
+        let e = self.find_capable(collection, |c| c.wipe)?;
+        if !e.engine.is_open() {
+            return Err(ErrorKind::ConnectionClosed(collection.into()).into());
+        }
+        e.engine.wipe(collection)
+    }
+
+    pub fn wipe_all(&self) -> Result<()> {
+        for collection in self.open_capable(|c| c.wipe) {
+            self.wipe(collection)?;
+        }
+        Ok(())
+    }
+
+    pub fn reset(&self, collection: &str) -> Result<()> {
+        let e = self.find_capable(collection, |c| c.reset)?;
+        if !e.engine.is_open() {
+            return Err(ErrorKind::ConnectionClosed(collection.into()).into());
+        }
+        e.engine.reset(collection)
+    }
+
+    pub fn reset_all(&self) -> Result<()> {
+        for collection in self.open_capable(|c| c.reset) {
+            self.reset(collection)?;
+        }
+        Ok(())
+    }
+
+    /// Resets every engine that supports it, logging instead of failing, so
+    /// that one broken engine doesn't stop the others from being reset.
+    pub fn disconnect(&self) {
+        for e in &self.engines {
+            if !e.capabilities.reset {
+                continue;
+            }
+            if !e.engine.is_open() {
+                log::warn!(
+                    "Unable to reset {}, be sure to register it before disconnect if this is surprising",
+                    e.collection
+                );
+                continue;
+            }
+            if let Err(err) = e.engine.reset(&e.collection) {
+                log::error!("Failed to reset {}: {}", e.collection, err);
+            }
+        }
+    }
+
+    /// Returns an error if `collection` isn't registered, or its engine doesn't
+    /// have the capability checked by `capable`.
+    pub fn check(
+        &self,
+        collection: &str,
+        capable: impl Fn(&EngineCapabilities) -> bool,
+    ) -> Result<()> {
+        self.find_capable(collection, capable).map(|_| ())
+    }
+
+    /// Returns the names of all open collections whose engines have the
+    /// capability checked by `capable`.
+    pub fn open_capable(&self, capable: impl Fn(&EngineCapabilities) -> bool) -> Vec<&str> {
+        self.engines
+            .iter()
+            .filter(|e| capable(&e.capabilities) && e.engine.is_open())
+            .map(|e| e.collection.as_str())
+            .collect()
+    }
+
+    fn find_capable(
+        &self,
+        collection: &str,
+        capable: impl Fn(&EngineCapabilities) -> bool,
+    ) -> Result<&RegisteredEngine> {
+        let e = self
+            .engines
+            .iter()
+            .find(|e| e.collection == collection)
+            .ok_or_else(|| ErrorKind::UnknownEngine(collection.into()))?;
+        if !capable(&e.capabilities) {
+            return Err(ErrorKind::UnsupportedFeature(collection.into()).into());
+        }
+        Ok(e)
+    }
+}
+
+/// Compares engines by address only. Vtable pointers for the same type aren't
+/// guaranteed to be unique, so we can't compare the fat pointers.
+fn same_engine(a: &dyn SyncEngine, b: &dyn SyncEngine) -> bool {
+    std::ptr::eq(
+        a as *const dyn SyncEngine as *const u8,
+        b as *const dyn SyncEngine as *const u8,
+    )
+}
+
+/// Calls `f` with the stores for all of `groups`, asking each engine for its
+/// stores in turn.
+pub(crate) fn with_all_stores(
+    groups: &[(&dyn SyncEngine, Vec<&str>)],
+    scope: &SyncInterruptScope,
+    stores: &[&dyn sync15::Store],
+    f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+) -> Result<()> {
+    match groups.split_first() {
+        None => f(stores),
+        Some(((engine, collections), rest)) => {
+            engine.with_stores(collections, scope, &mut |engine_stores| {
+                let mut all: Vec<&dyn sync15::Store> = stores.to_vec();
+                all.extend_from_slice(engine_stores);
+                with_all_stores(rest, scope, &all, &mut *f)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct TestEngine {
+        closed: AtomicBool,
+        wiped: Mutex<Vec<String>>,
+        reset: Mutex<Vec<String>>,
+    }
+
+    impl SyncEngine for TestEngine {
+        fn is_open(&self) -> bool {
+            !self.closed.load(Ordering::SeqCst)
+        }
+
+        fn with_stores(
+            &self,
+            _collections: &[&str],
+            _scope: &SyncInterruptScope,
+            f: &mut dyn FnMut(&[&dyn sync15::Store]) -> Result<()>,
+        ) -> Result<()> {
+            f(&[])
+        }
+
+        fn wipe(&self, collection: &str) -> Result<()> {
+            self.wiped.lock().unwrap().push(collection.to_owned());
+            Ok(())
+        }
+
+        fn reset(&self, collection: &str) -> Result<()> {
+            self.reset.lock().unwrap().push(collection.to_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_wipe_and_reset() {
+        let engine = Arc::new(TestEngine::default());
+        let mut registry = EngineRegistry::default();
+        registry.register("one", EngineCapabilities::ALL, engine.clone());
+        registry.register(
+            "two",
+            EngineCapabilities {
+                wipe: false,
+                reset: true,
+            },
+            engine.clone(),
+        );
+
+        registry.wipe("one").unwrap();
+        assert!(matches!(
+            registry.wipe("two").unwrap_err().kind(),
+            ErrorKind::UnsupportedFeature(_)
+        ));
+        assert!(matches!(
+            registry.wipe("three").unwrap_err().kind(),
+            ErrorKind::UnknownEngine(_)
+        ));
+        registry.wipe_all().unwrap();
+        assert_eq!(*engine.wiped.lock().unwrap(), vec!["one", "one"]);
+
+        registry.reset_all().unwrap();
+        registry.disconnect();
+        assert_eq!(
+            *engine.reset.lock().unwrap(),
+            vec!["one", "two", "one", "two"]
+        );
+
+        engine.closed.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            registry.reset("one").unwrap_err().kind(),
+            ErrorKind::ConnectionClosed(_)
+        ));
+        assert_eq!(registry.registered(), vec!["one", "two"]);
+        assert!(registry.open().is_empty());
+    }
+
+    #[test]
+    fn test_open_groups() {
+        let a = Arc::new(TestEngine::default());
+        let b = Arc::new(TestEngine::default());
+        let mut registry = EngineRegistry::default();
+        registry.register("a1", EngineCapabilities::ALL, a.clone());
+        registry.register("b", EngineCapabilities::ALL, b.clone());
+        registry.register("a2", EngineCapabilities::ALL, a);
+        // Registering again replaces the engine, but keeps its position.
+        registry.register("b", EngineCapabilities::ALL, b);
+
+        let groups = registry.open_groups(|_| true);
+        let names = groups.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>();
+        assert_eq!(names, vec![vec!["a1", "a2"], vec!["b"]]);
+
+        // Engines keep all their collections if we're syncing any of them.
+        let groups = registry.open_groups(|c| c != "a1");
+        let names = groups.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>();
+        assert_eq!(names, vec![vec!["a1", "a2"], vec!["b"]]);
+
+        let groups = registry.open_groups(|c| c == "b");
+        let names = groups.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>();
+        assert_eq!(names, vec![vec!["b"]]);
+    }
+}
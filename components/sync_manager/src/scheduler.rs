@@ -0,0 +1,446 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Deciding when to sync.
+//!
+//! The scheduler doesn't run syncs itself: the application still owns the
+//! timers (or `WorkManager` jobs, or whatever else it uses), and asks the
+//! scheduler when the next sync should happen. After each sync, the
+//! application passes the result back, so that the scheduler can honor any
+//! backoff from the server, and retry after errors.
+//!
+//! How often we sync depends on how the account is being used, like desktop
+//! Firefox's `SyncScheduler`:
+//!
+//! - If this is the only device on the account, there's nothing to sync
+//!   with, so we sync rarely, to back up local changes.
+//! - If there are other devices, we sync more often while the user is
+//!   active, and less often while they're idle.
+//! - Local changes trigger a sync soon after they're made, once the user
+//!   stops making them for a bit.
+//! - Errors are retried with an exponential backoff, capped at the regular
+//!   interval.
+//! - Nothing happens before the time the server asked us to wait for, via
+//!   the `X-Weave-Backoff` and `Retry-After` headers that `sync15` tracks in
+//!   its `BackoffState`, and that end up in `SyncResult.next_sync_allowed_at`.
+
+use crate::msg_types::{ServiceStatus, SyncResult};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Tells the scheduler what time it is. Tests use a fake clock, so that
+/// they don't have to wait for real time to pass.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// A clock that returns the system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// How often to sync. The default intervals match desktop Firefox.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncPolicy {
+    /// How often to sync if there are no other devices on the account.
+    pub single_device_interval: Duration,
+    /// How often to sync if there are other devices, and the user is idle.
+    pub idle_interval: Duration,
+    /// How often to sync if there are other devices, and the user is active.
+    pub active_interval: Duration,
+    /// How long to wait after a local change before syncing, in case more
+    /// changes follow.
+    pub local_change_delay: Duration,
+    /// The longest we'll put off syncing local changes if the user keeps
+    /// making them.
+    pub local_change_max_delay: Duration,
+    /// How long to wait before retrying after the first error. The delay
+    /// doubles with each error after that, up to the regular interval.
+    pub error_retry_delay: Duration,
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        Self {
+            single_device_interval: Duration::from_secs(24 * 60 * 60),
+            idle_interval: Duration::from_secs(60 * 60),
+            active_interval: Duration::from_secs(10 * 60),
+            local_change_delay: Duration::from_secs(5),
+            local_change_max_delay: Duration::from_secs(90),
+            error_retry_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Tracks what's happened since the last sync, to decide when the next one
+/// should happen. See the module docs for details.
+pub struct SyncScheduler {
+    policy: SyncPolicy,
+    clock: Box<dyn Clock>,
+    active: bool,
+    multi_device: bool,
+    /// When the last sync finished, successfully or not.
+    last_sync: Option<SystemTime>,
+    /// When the sync in progress started, if there is one.
+    sync_started: Option<SystemTime>,
+    /// The first and last local changes that haven't been synced.
+    pending_changes: Option<(SystemTime, SystemTime)>,
+    /// The number of syncs in a row that failed.
+    error_count: u32,
+    /// The server asked us not to sync before this time.
+    backoff_until: Option<SystemTime>,
+}
+
+impl SyncScheduler {
+    pub fn new(policy: SyncPolicy, clock: Box<dyn Clock>) -> Self {
+        Self {
+            policy,
+            clock,
+            active: false,
+            multi_device: false,
+            last_sync: None,
+            sync_started: None,
+            pending_changes: None,
+            error_count: 0,
+            backoff_until: None,
+        }
+    }
+
+    pub fn policy(&self) -> &SyncPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SyncPolicy) {
+        self.policy = policy;
+    }
+
+    /// Notes whether the user is using the application.
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Notes whether there are other devices on the account, as reported by
+    /// FxA or the clients collection.
+    pub fn set_multi_device(&mut self, multi_device: bool) {
+        self.multi_device = multi_device;
+    }
+
+    /// Notes that the user changed something that should be synced.
+    pub fn note_local_change(&mut self) {
+        let now = self.clock.now();
+        self.pending_changes = Some(match self.pending_changes {
+            Some((first, _)) => (first, now),
+            None => (now, now),
+        });
+    }
+
+    /// Notes that a sync started. Local changes made after this are synced
+    /// by the next sync.
+    pub fn sync_started(&mut self) {
+        self.sync_started = Some(self.clock.now());
+    }
+
+    /// Notes that a sync finished with `result`.
+    pub fn sync_finished(&mut self, result: &SyncResult) {
+        let now = self.clock.now();
+        let started = self.sync_started.take().unwrap_or(now);
+        self.last_sync = Some(now);
+        self.backoff_until = result
+            .next_sync_allowed_at
+            .and_then(millis_to_system_time)
+            .filter(|&until| until > now);
+
+        let status = ServiceStatus::from_i32(result.status).unwrap_or(ServiceStatus::OtherError);
+        match status {
+            ServiceStatus::Ok if result.results.values().all(String::is_empty) => {
+                self.error_count = 0;
+                // Changes made while we were syncing might not have made it
+                // into this sync.
+                self.pending_changes = match self.pending_changes {
+                    Some((_, last)) if last >= started => Some((started, last)),
+                    _ => None,
+                };
+            }
+            // We didn't try to sync, so nothing changed except the backoff.
+            ServiceStatus::BackedOff => {}
+            // The sync was cut short, but not because of a problem, so we'll
+            // try again at the next regular time.
+            ServiceStatus::Interrupted => {}
+            _ => {
+                self.error_count = self.error_count.saturating_add(1);
+            }
+        }
+    }
+
+    /// Notes that a sync failed before it started, for example, because
+    /// the parameters were invalid.
+    pub fn sync_failed(&mut self) {
+        self.sync_started = None;
+        self.last_sync = Some(self.clock.now());
+        self.error_count = self.error_count.saturating_add(1);
+    }
+
+    /// Returns how long to wait between regular syncs.
+    pub fn interval(&self) -> Duration {
+        if !self.multi_device {
+            self.policy.single_device_interval
+        } else if self.active {
+            self.policy.active_interval
+        } else {
+            self.policy.idle_interval
+        }
+    }
+
+    /// Returns when the next sync should happen. This may be in the past, if
+    /// a sync is overdue.
+    pub fn next_sync_at(&self) -> SystemTime {
+        let last_sync = match self.last_sync {
+            Some(last_sync) => last_sync,
+            // We've never synced, so do it now. Even this respects backoff,
+            // since the application might have restored it.
+            None => return self.respect_backoff(self.clock.now()),
+        };
+        let interval = self.interval();
+        let mut next = last_sync + interval;
+        if self.error_count > 0 {
+            next = next.min(last_sync + self.retry_delay(interval));
+        }
+        if let Some((first, last)) = self.pending_changes {
+            let deadline = (last + self.policy.local_change_delay)
+                .min(first + self.policy.local_change_max_delay);
+            next = next.min(deadline);
+        }
+        self.respect_backoff(next)
+    }
+
+    /// Returns how long to wait before the next sync, or zero if a sync is
+    /// due now.
+    pub fn time_until_next_sync(&self) -> Duration {
+        self.next_sync_at()
+            .duration_since(self.clock.now())
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if a sync is due, and no sync is in progress.
+    pub fn should_sync(&self) -> bool {
+        self.sync_started.is_none() && self.next_sync_at() <= self.clock.now()
+    }
+
+    fn retry_delay(&self, interval: Duration) -> Duration {
+        // `checked_shl` fails if we shift out all the bits, at which point
+        // we've long since hit the interval anyway.
+        let factor = 1u32
+            .checked_shl(self.error_count - 1)
+            .unwrap_or(u32::max_value());
+        self.policy
+            .error_retry_delay
+            .checked_mul(factor)
+            .map_or(interval, |delay| delay.min(interval))
+    }
+
+    fn respect_backoff(&self, next: SystemTime) -> SystemTime {
+        match self.backoff_until {
+            Some(until) => next.max(until),
+            None => next,
+        }
+    }
+}
+
+fn millis_to_system_time(millis: i64) -> Option<SystemTime> {
+    use std::convert::TryFrom;
+    let millis = u64::try_from(millis).ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct TestClock(Arc<Mutex<SystemTime>>);
+
+    impl TestClock {
+        fn new() -> Self {
+            TestClock(Arc::new(Mutex::new(
+                UNIX_EPOCH + Duration::from_secs(1_500_000_000),
+            )))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> SystemTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    fn result(status: ServiceStatus) -> SyncResult {
+        SyncResult {
+            status: status as i32,
+            results: Default::default(),
+            have_declined: false,
+            declined: vec![],
+            next_sync_allowed_at: None,
+            persisted_state: "".to_string(),
+            telemetry_json: None,
+            plans: Default::default(),
+        }
+    }
+
+    fn scheduler(clock: &TestClock) -> SyncScheduler {
+        SyncScheduler::new(SyncPolicy::default(), Box::new(clock.clone()))
+    }
+
+    fn sync(scheduler: &mut SyncScheduler, result: SyncResult) {
+        assert!(scheduler.should_sync());
+        scheduler.sync_started();
+        scheduler.sync_finished(&result);
+    }
+
+    fn sync_after(
+        clock: &TestClock,
+        scheduler: &mut SyncScheduler,
+        after: Duration,
+        result: SyncResult,
+    ) {
+        clock.advance(after);
+        sync(scheduler, result);
+    }
+
+    #[test]
+    fn test_intervals() {
+        let clock = TestClock::new();
+        let mut scheduler = scheduler(&clock);
+        // We sync right away the first time.
+        sync(&mut scheduler, result(ServiceStatus::Ok));
+
+        assert_eq!(scheduler.time_until_next_sync(), secs(24 * 60 * 60));
+        scheduler.set_multi_device(true);
+        assert_eq!(scheduler.time_until_next_sync(), secs(60 * 60));
+        scheduler.set_active(true);
+        assert_eq!(scheduler.time_until_next_sync(), secs(10 * 60));
+
+        clock.advance(secs(10 * 60 - 1));
+        assert!(!scheduler.should_sync());
+        clock.advance(secs(1));
+        assert!(scheduler.should_sync());
+    }
+
+    #[test]
+    fn test_local_changes() {
+        let clock = TestClock::new();
+        let mut scheduler = scheduler(&clock);
+        sync(&mut scheduler, result(ServiceStatus::Ok));
+
+        // Each change pushes the sync back a little...
+        scheduler.note_local_change();
+        assert_eq!(scheduler.time_until_next_sync(), secs(5));
+        clock.advance(secs(4));
+        scheduler.note_local_change();
+        assert_eq!(scheduler.time_until_next_sync(), secs(5));
+
+        // ...but not forever.
+        for _ in 0..30 {
+            clock.advance(secs(3));
+            scheduler.note_local_change();
+        }
+        assert!(scheduler.should_sync());
+
+        // Changes made during the sync are synced by the next one.
+        scheduler.sync_started();
+        clock.advance(secs(1));
+        scheduler.note_local_change();
+        scheduler.sync_finished(&result(ServiceStatus::Ok));
+        assert_eq!(scheduler.time_until_next_sync(), secs(5));
+        sync_after(&clock, &mut scheduler, secs(5), result(ServiceStatus::Ok));
+        assert_eq!(scheduler.time_until_next_sync(), secs(24 * 60 * 60));
+    }
+
+    #[test]
+    fn test_error_retry() {
+        let clock = TestClock::new();
+        let mut scheduler = scheduler(&clock);
+        scheduler.set_multi_device(true);
+        sync(&mut scheduler, result(ServiceStatus::NetworkError));
+        assert_eq!(scheduler.time_until_next_sync(), secs(60));
+        sync_after(
+            &clock,
+            &mut scheduler,
+            secs(60),
+            result(ServiceStatus::ServiceError),
+        );
+        assert_eq!(scheduler.time_until_next_sync(), secs(120));
+
+        // Engine failures count as errors, too.
+        let mut engine_failure = result(ServiceStatus::Ok);
+        engine_failure
+            .results
+            .insert("bookmarks".to_string(), "oops".to_string());
+        sync_after(&clock, &mut scheduler, secs(120), engine_failure);
+        assert_eq!(scheduler.time_until_next_sync(), secs(240));
+
+        // The delay is capped at the regular interval.
+        for _ in 0..40 {
+            scheduler.sync_failed();
+        }
+        assert_eq!(scheduler.time_until_next_sync(), secs(60 * 60));
+
+        // Interruptions aren't errors, and don't reset the count.
+        sync_after(
+            &clock,
+            &mut scheduler,
+            secs(60 * 60),
+            result(ServiceStatus::Interrupted),
+        );
+        assert_eq!(scheduler.time_until_next_sync(), secs(60 * 60));
+
+        sync_after(
+            &clock,
+            &mut scheduler,
+            secs(60 * 60),
+            result(ServiceStatus::Ok),
+        );
+        assert_eq!(scheduler.time_until_next_sync(), secs(60 * 60));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let clock = TestClock::new();
+        let mut scheduler = scheduler(&clock);
+        scheduler.set_multi_device(true);
+        scheduler.set_active(true);
+
+        let mut backed_off = result(ServiceStatus::ServiceError);
+        let until = clock.now() + secs(30 * 60);
+        backed_off.next_sync_allowed_at =
+            Some(until.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64);
+        sync(&mut scheduler, backed_off);
+        assert_eq!(scheduler.next_sync_at(), until);
+
+        // Local changes don't override the backoff.
+        scheduler.note_local_change();
+        assert_eq!(scheduler.next_sync_at(), until);
+
+        // Once it expires, we're back to normal.
+        sync_after(
+            &clock,
+            &mut scheduler,
+            secs(30 * 60),
+            result(ServiceStatus::Ok),
+        );
+        assert_eq!(scheduler.time_until_next_sync(), secs(10 * 60));
+    }
+}
@@ -56,3 +56,25 @@ data class RemoteTab(
         }
     }
 }
+
+/**
+ * The local tabs to close for a close-tabs command from another device.
+ */
+data class CloseTabsResult(
+    /** The tabs to close. */
+    val closed: List<RemoteTab>,
+    /**
+     * The URLs from the command that didn't match a local tab, once for each
+     * tab that we couldn't find.
+     */
+    val notFound: List<String>
+) {
+    companion object {
+        internal fun fromMessage(msg: MsgTypes.CloseTabsResult): CloseTabsResult {
+            return CloseTabsResult(
+                    closed = msg.closedList.map { RemoteTab.fromMessage(it) },
+                    notFound = msg.notFoundList
+            )
+        }
+    }
+}
@@ -15,3 +15,8 @@ class SyncAuthInvalidException(msg: String) : RemoteTabProviderException(msg)
  * This error is emitted if a request to a sync server failed.
  */
 class RequestFailedException(msg: String) : RemoteTabProviderException(msg)
+
+/**
+ * This error is emitted if a sync or other operation is interrupted.
+ */
+class InterruptedException(msg: String) : RemoteTabProviderException(msg)
@@ -6,20 +6,43 @@ package mozilla.appservices.remotetabs
 
 import com.sun.jna.Pointer
 import mozilla.appservices.remotetabs.rust.LibRemoteTabsFFI
+import mozilla.appservices.remotetabs.rust.RawTabsInterruptHandle
 import mozilla.appservices.remotetabs.rust.RustError
 import mozilla.appservices.sync15.SyncTelemetryPing
 import org.json.JSONArray
 import java.util.concurrent.atomic.AtomicLong
+import java.util.concurrent.atomic.AtomicReference
 
-class RemoteTabsProvider : AutoCloseable {
+/**
+ * Provides access to the local and remote tabs. Remote tabs are stored in
+ * a SQLite database at [path], so that they're available before the first sync
+ * after a restart.
+ */
+class RemoteTabsProvider(path: String) : AutoCloseable {
     private var handle: AtomicLong = AtomicLong(0)
+    private var interruptHandle: AtomicReference<RawTabsInterruptHandle?> = AtomicReference(null)
 
     init {
         handle.set(rustCall { error ->
-            LibRemoteTabsFFI.INSTANCE.remote_tabs_new(error)
+            LibRemoteTabsFFI.INSTANCE.remote_tabs_new(path, error)
+        })
+        interruptHandle.set(rustCall { error ->
+            LibRemoteTabsFFI.INSTANCE.remote_tabs_new_interrupt_handle(this.handle.get(), error)
         })
     }
 
+    /**
+     * Interrupt an in-progress sync or database operation, which will then
+     * throw an [InterruptedException]. This is safe to call from any thread.
+     */
+    fun interrupt() {
+        interruptHandle.get()?.let {
+            rustCall { error ->
+                LibRemoteTabsFFI.INSTANCE.remote_tabs_interrupt(it, error)
+            }
+        }
+    }
+
     /**
      * Update our local tabs state.
      */
@@ -36,7 +59,8 @@ class RemoteTabsProvider : AutoCloseable {
     }
 
     /**
-     * Get the remote tabs. Might be null if we haven't synced yet.
+     * Get the remote tabs, as of the last sync. Might be null if we haven't
+     * synced yet.
      */
     fun getAll(): List<ClientTabs>? {
         val rustBuf = rustCallWithLock { error ->
@@ -53,6 +77,32 @@ class RemoteTabsProvider : AutoCloseable {
         }
     }
 
+    /**
+     * Find the local tabs to close for a close-tabs command from another
+     * device, using the [urls] from the command. Each URL closes at most one
+     * tab, so a URL that's listed twice closes two tabs with that URL. The
+     * local tabs are the ones last passed to [setLocalTabs].
+     *
+     * After closing the tabs, report the result back to the device that
+     * sent the command.
+     */
+    fun findTabsToClose(urls: List<String>): CloseTabsResult {
+        val urlsJson = JSONArray(urls).toString()
+        val rustBuf = rustCallWithLock { error ->
+            LibRemoteTabsFFI.INSTANCE.remote_tabs_find_tabs_to_close(
+                this.handle.get(), urlsJson, error)
+        }
+
+        try {
+            // An empty result is encoded as an empty buffer.
+            return rustBuf.asCodedInputStream()?.let { stream ->
+                CloseTabsResult.fromMessage(MsgTypes.CloseTabsResult.parseFrom(stream))
+            } ?: CloseTabsResult(closed = listOf(), notFound = listOf())
+        } finally {
+            LibRemoteTabsFFI.INSTANCE.remote_tabs_destroy_bytebuffer(rustBuf)
+        }
+    }
+
     /**
      * Convenience Sync function.
      */
@@ -88,6 +138,9 @@ class RemoteTabsProvider : AutoCloseable {
                 LibRemoteTabsFFI.INSTANCE.remote_tabs_destroy(handle, error)
             }
         }
+        interruptHandle.getAndSet(null)?.let {
+            LibRemoteTabsFFI.INSTANCE.remote_tabs_interrupt_handle_destroy(it)
+        }
     }
 
     private inline fun <U> nullableRustCall(callback: (RustError.ByReference) -> U?): U? {
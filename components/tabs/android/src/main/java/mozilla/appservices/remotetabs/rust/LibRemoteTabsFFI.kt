@@ -7,6 +7,7 @@ package mozilla.appservices.remotetabs.rust
 
 import com.sun.jna.Library
 import com.sun.jna.Pointer
+import com.sun.jna.PointerType
 import mozilla.appservices.support.native.RustBuffer
 import mozilla.appservices.support.native.loadIndirect
 import org.mozilla.appservices.remotetabs.BuildConfig
@@ -19,11 +20,16 @@ internal interface LibRemoteTabsFFI : Library {
     }
 
     fun remote_tabs_new(
+        db_path: String,
         error: RustError.ByReference
     ): TabsApiHandle
 
     fun remote_tabs_destroy(handle: TabsApiHandle, error: RustError.ByReference)
 
+    fun remote_tabs_new_interrupt_handle(handle: TabsApiHandle, error: RustError.ByReference): RawTabsInterruptHandle?
+    fun remote_tabs_interrupt(handle: RawTabsInterruptHandle, error: RustError.ByReference)
+    fun remote_tabs_interrupt_handle_destroy(handle: RawTabsInterruptHandle)
+
     fun remote_tabs_update_local(
         handle: TabsApiHandle,
         local_state_json: String,
@@ -35,6 +41,12 @@ internal interface LibRemoteTabsFFI : Library {
         error: RustError.ByReference
     ): RustBuffer.ByValue
 
+    fun remote_tabs_find_tabs_to_close(
+        handle: TabsApiHandle,
+        urls_json: String,
+        error: RustError.ByReference
+    ): RustBuffer.ByValue
+
     // Returns a JSON string containing a sync ping.
     fun remote_tabs_sync(
         handle: TabsApiHandle,
@@ -51,3 +63,5 @@ internal interface LibRemoteTabsFFI : Library {
 }
 
 internal typealias TabsApiHandle = Long
+
+internal class RawTabsInterruptHandle : PointerType()
@@ -6,6 +6,7 @@ package mozilla.appservices.remotetabs.rust
 
 import com.sun.jna.Pointer
 import com.sun.jna.Structure
+import mozilla.appservices.remotetabs.InterruptedException
 import mozilla.appservices.remotetabs.RemoteTabProviderException
 import mozilla.appservices.remotetabs.RequestFailedException
 import mozilla.appservices.remotetabs.SyncAuthInvalidException
@@ -41,6 +42,7 @@ open class RustError : Structure() {
         when (code) {
             1 -> return SyncAuthInvalidException(message)
             2 -> return RequestFailedException(message)
+            3 -> return InterruptedException(message)
             else -> return RemoteTabProviderException(message)
         }
     }
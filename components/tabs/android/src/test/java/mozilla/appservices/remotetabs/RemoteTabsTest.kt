@@ -4,12 +4,18 @@ import mozilla.appservices.Megazord
 import org.junit.runner.RunWith
 import org.robolectric.RobolectricTestRunner
 import org.robolectric.annotation.Config
+import org.junit.Assert.assertNull
 import org.junit.Before
+import org.junit.Rule
 import org.junit.Test
+import org.junit.rules.TemporaryFolder
 
 @RunWith(RobolectricTestRunner::class)
 @Config(manifest = Config.NONE)
 class RemoteTabsTest {
+    @Rule
+    @JvmField
+    val dbFolder = TemporaryFolder()
 
     @Before
     fun init() {
@@ -17,7 +23,8 @@ class RemoteTabsTest {
     }
     @Test
     fun doTest() {
-        RemoteTabsProvider().use { tabs ->
+        RemoteTabsProvider(dbFolder.newFile().absolutePath).use { tabs ->
+            assertNull(tabs.getAll())
             tabs.setLocalTabs(listOf(
                 RemoteTab(
                     title = "cool things to look at in your remote tabs",
@@ -26,15 +26,24 @@ fn main() -> Result<()> {
                     "Path to store our cached fxa credentials (defaults to \"./credentials.json\"",
                 ),
         )
+        .arg(
+            clap::Arg::with_name("database_path")
+                .short("d")
+                .long("database")
+                .value_name("TABS_DATABASE")
+                .takes_value(true)
+                .help("Path to the tabs database (defaults to \"./tabs.db\")"),
+        )
         .get_matches();
     let cred_file = matches
         .value_of("credential_file")
         .unwrap_or("./credentials.json");
+    let db_path = matches.value_of("database_path").unwrap_or("./tabs.db");
 
     let mut cli_fxa = get_cli_fxa(get_default_fxa_config(), &cred_file)?;
     let device_id = cli_fxa.account.get_current_device_id()?;
 
-    let mut engine = TabsEngine::new();
+    let mut engine = TabsEngine::new(db_path)?;
 
     loop {
         match prompt_char("[U]pdate local state, [L]ist remote tabs, [S]ync or [Q]uit")
@@ -48,7 +57,7 @@ fn main() -> Result<()> {
             }
             'L' | 'l' => {
                 log::info!("Listing remote tabs.");
-                let tabs_and_clients = match engine.remote_tabs() {
+                let tabs_and_clients = match engine.remote_tabs()? {
                     Some(tc) => tc,
                     None => {
                         println!("No remote tabs! Did you try syncing first?");
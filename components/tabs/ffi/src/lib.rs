@@ -6,8 +6,8 @@
 #![warn(rust_2018_idioms)]
 
 use ffi_support::{
-    define_bytebuffer_destructor, define_handle_map_deleter, define_string_destructor, ByteBuffer,
-    ConcurrentHandleMap, ExternError, FfiStr,
+    define_box_destructor, define_bytebuffer_destructor, define_handle_map_deleter,
+    define_string_destructor, ByteBuffer, ConcurrentHandleMap, ExternError, FfiStr,
 };
 use std::{
     os::raw::c_char,
@@ -27,13 +27,33 @@ fn parse_url(url: &str) -> Result<url::Url> {
 }
 
 #[no_mangle]
-pub extern "C" fn remote_tabs_new(error: &mut ExternError) -> u64 {
+pub extern "C" fn remote_tabs_new(db_path: FfiStr<'_>, error: &mut ExternError) -> u64 {
     log::debug!("remote_tabs_new");
     ENGINES.insert_with_result(error, || -> Result<_> {
-        Ok(Arc::new(Mutex::new(TabsEngine::new())))
+        Ok(Arc::new(Mutex::new(TabsEngine::new(db_path.as_str())?)))
     })
 }
 
+#[no_mangle]
+pub extern "C" fn remote_tabs_new_interrupt_handle(
+    handle: u64,
+    error: &mut ExternError,
+) -> *mut sql_support::SqlInterruptHandle {
+    log::debug!("remote_tabs_new_interrupt_handle");
+    ENGINES.call_with_output(error, handle, |engine| {
+        engine.lock().unwrap().new_interrupt_handle()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn remote_tabs_interrupt(
+    handle: &sql_support::SqlInterruptHandle,
+    error: &mut ExternError,
+) {
+    log::debug!("remote_tabs_interrupt");
+    ffi_support::call_with_output(error, || handle.interrupt())
+}
+
 #[no_mangle]
 pub extern "C" fn remote_tabs_sync(
     handle: u64,
@@ -83,11 +103,32 @@ pub extern "C" fn remote_tabs_get_all(handle: u64, error: &mut ExternError) -> B
         Ok(engine
             .lock()
             .unwrap()
-            .remote_tabs()
+            .remote_tabs()?
             .map(|tabs| -> ClientsTabs { tabs.into() }))
     })
 }
 
+/// Finds the local tabs to close for a close-tabs command from another
+/// device. `urls` is a JSON array of the URLs from the command.
+#[no_mangle]
+pub extern "C" fn remote_tabs_find_tabs_to_close(
+    handle: u64,
+    urls: FfiStr<'_>,
+    error: &mut ExternError,
+) -> ByteBuffer {
+    log::debug!("remote_tabs_find_tabs_to_close");
+    use tabs::msg_types::CloseTabsResult;
+    ENGINES.call_with_result(error, handle, |engine| -> Result<_> {
+        let urls: Vec<String> = serde_json::from_str(urls.as_str())?;
+        let result: CloseTabsResult = engine.lock().unwrap().find_tabs_to_close(&urls).into();
+        Ok(result)
+    })
+}
+
 define_string_destructor!(remote_tabs_destroy_string);
 define_bytebuffer_destructor!(remote_tabs_destroy_bytebuffer);
 define_handle_map_deleter!(ENGINES, remote_tabs_destroy);
+define_box_destructor!(
+    sql_support::SqlInterruptHandle,
+    remote_tabs_interrupt_handle_destroy
+);
@@ -0,0 +1,119 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::*;
+use crate::schema;
+use rusqlite::{
+    named_params,
+    types::{FromSql, ToSql},
+    Connection,
+};
+use sql_support::ConnExt;
+use sql_support::{SqlInterruptHandle, SqlInterruptScope};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{atomic::AtomicUsize, Arc};
+
+/// A `TabsDb` wraps the SQLite connection used to persist remote tabs and
+/// sync metadata, so that they survive restarts of the application.
+pub struct TabsDb {
+    pub db: Connection,
+    interrupt_counter: Arc<AtomicUsize>,
+}
+
+impl TabsDb {
+    pub fn with_connection(db: Connection) -> Result<Self> {
+        // `temp_store = 2` is required on Android to force the DB to keep temp
+        // files in memory, since on Android there's no tmp partition. See
+        // https://github.com/mozilla/mentat/issues/505. Ideally we'd only
+        // do this on Android, or allow caller to configure it.
+        db.set_pragma("temp_store", 2)?;
+
+        let mut tabs = Self {
+            db,
+            interrupt_counter: Arc::new(AtomicUsize::new(0)),
+        };
+        let tx = tabs.db.transaction()?;
+        schema::init(&tx)?;
+        tx.commit()?;
+        Ok(tabs)
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::with_connection(Connection::open(path)?)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Ok(Self::with_connection(Connection::open_in_memory()?)?)
+    }
+
+    pub fn new_interrupt_handle(&self) -> SqlInterruptHandle {
+        SqlInterruptHandle::new(
+            self.db.get_interrupt_handle(),
+            self.interrupt_counter.clone(),
+        )
+    }
+
+    #[inline]
+    pub fn begin_interrupt_scope(&self) -> SqlInterruptScope {
+        SqlInterruptScope::new(self.interrupt_counter.clone())
+    }
+
+    pub(crate) fn put_meta(&self, key: &str, value: &dyn ToSql) -> Result<()> {
+        self.execute_named_cached(
+            "REPLACE INTO tabs_meta (key, value) VALUES (:key, :value)",
+            named_params! { ":key": key, ":value": value },
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn get_meta<T: FromSql>(&self, key: &str) -> Result<Option<T>> {
+        Ok(self.try_query_row(
+            "SELECT value FROM tabs_meta WHERE key = :key",
+            named_params! { ":key": key },
+            |row| Ok::<_, Error>(row.get(0)?),
+            true,
+        )?)
+    }
+
+    pub(crate) fn delete_meta(&self, key: &str) -> Result<()> {
+        self.execute_named_cached(
+            "DELETE FROM tabs_meta WHERE key = :key",
+            named_params! { ":key": key },
+        )?;
+        Ok(())
+    }
+}
+
+impl ConnExt for TabsDb {
+    #[inline]
+    fn conn(&self) -> &Connection {
+        &self.db
+    }
+}
+
+impl Deref for TabsDb {
+    type Target = Connection;
+    #[inline]
+    fn deref(&self) -> &Connection {
+        &self.db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta() {
+        let db = TabsDb::open_in_memory().unwrap();
+        assert_eq!(db.get_meta::<i64>("foo").unwrap(), None);
+        db.put_meta("foo", &1i64).unwrap();
+        assert_eq!(db.get_meta::<i64>("foo").unwrap(), Some(1));
+        db.put_meta("foo", &2i64).unwrap();
+        assert_eq!(db.get_meta::<i64>("foo").unwrap(), Some(2));
+        db.delete_meta("foo").unwrap();
+        assert_eq!(db.get_meta::<i64>("foo").unwrap(), None);
+    }
+}
@@ -10,9 +10,15 @@ pub enum ErrorKind {
     #[error("Error parsing JSON data: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    #[error("Error executing SQL: {0}")]
+    SqlError(#[from] rusqlite::Error),
+
     #[error("Error parsing URL: {0}")]
     UrlParseError(#[from] url::ParseError),
 
+    #[error("{0}")]
+    Interrupted(#[from] interrupt_support::Interrupted),
+
     #[error("Protobuf decode error: {0}")]
     ProtobufDecodeError(#[from] prost::DecodeError),
 }
@@ -21,7 +27,9 @@ error_support::define_error! {
     ErrorKind {
         (SyncAdapterError, sync15::Error),
         (JsonError, serde_json::Error),
+        (SqlError, rusqlite::Error),
         (UrlParseError, url::ParseError),
+        (Interrupted, interrupt_support::Interrupted),
         (ProtobufDecodeError, prost::DecodeError),
     }
 }
@@ -4,7 +4,7 @@
 
 // This module implement the traits that make the FFI code easier to manage.
 
-use crate::{msg_types, ClientRemoteTabs, Error, ErrorKind, RemoteTab};
+use crate::{msg_types, ClientRemoteTabs, CloseTabsResult, Error, ErrorKind, RemoteTab};
 use ffi_support::{implement_into_ffi_by_protobuf, ErrorCode, ExternError};
 use std::convert::TryInto;
 use sync15::ErrorKind as Sync15ErrorKind;
@@ -21,6 +21,9 @@ pub mod error_codes {
 
     /// A request to the sync server failed.
     pub const NETWORK: i32 = 2;
+
+    /// An operation has been interrupted.
+    pub const INTERRUPTED: i32 = 3;
 }
 
 fn get_code(err: &Error) -> ErrorCode {
@@ -36,6 +39,18 @@ fn get_code(err: &Error) -> ErrorCode {
             }
         }
 
+        ErrorKind::SqlError(rusqlite::Error::SqliteFailure(err, _))
+            if err.code == rusqlite::ErrorCode::OperationInterrupted =>
+        {
+            log::warn!("Operation interrupted (SQL)");
+            ErrorCode::new(error_codes::INTERRUPTED)
+        }
+
+        ErrorKind::Interrupted(_) => {
+            log::warn!("Operation interrupted (Outside SQL)");
+            ErrorCode::new(error_codes::INTERRUPTED)
+        }
+
         err => {
             log::error!("Unexpected error: {:?}", err);
             ErrorCode::new(error_codes::UNEXPECTED)
@@ -88,6 +103,15 @@ impl From<msg_types::RemoteTabs> for Vec<RemoteTab> {
     }
 }
 
+impl From<CloseTabsResult> for msg_types::CloseTabsResult {
+    fn from(result: CloseTabsResult) -> Self {
+        Self {
+            closed: result.closed.into_iter().map(Into::into).collect(),
+            not_found: result.not_found,
+        }
+    }
+}
+
 impl From<Error> for ExternError {
     fn from(e: Error) -> ExternError {
         ExternError::new_error(get_code(&e), e.to_string())
@@ -95,3 +119,4 @@ impl From<Error> for ExternError {
 }
 
 implement_into_ffi_by_protobuf!(msg_types::ClientsTabs);
+implement_into_ffi_by_protobuf!(msg_types::CloseTabsResult);
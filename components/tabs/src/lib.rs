@@ -7,7 +7,9 @@
 
 #[macro_use]
 pub mod error;
+mod db;
 mod ffi;
+mod schema;
 mod storage;
 mod sync;
 
@@ -15,7 +17,7 @@ pub mod msg_types {
     include!("mozilla.appservices.remotetabs.protobuf.rs");
 }
 
-pub use crate::storage::{ClientRemoteTabs, RemoteTab};
+pub use crate::storage::{ClientRemoteTabs, CloseTabsResult, RemoteTab};
 pub use crate::sync::engine::TabsEngine;
 pub use crate::sync::store::TabsStore;
 pub use error::{Error, ErrorKind, Result};
@@ -26,3 +26,10 @@ pub struct RemoteTabs {
     #[prost(message, repeated, tag="1")]
     pub remote_tabs: ::std::vec::Vec<RemoteTab>,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloseTabsResult {
+    #[prost(message, repeated, tag="1")]
+    pub closed: ::std::vec::Vec<RemoteTab>,
+    #[prost(string, repeated, tag="2")]
+    pub not_found: ::std::vec::Vec<std::string::String>,
+}
@@ -0,0 +1,115 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Tabs Schema v1
+//! ==============
+//!
+//! There are two tables:
+//!
+//! - `remote_tabs`: The tabs of other clients, as of the last sync. Each row
+//!   holds a single tabs record, keyed by the ID of the record (which is the
+//!   ID of the client in the `clients` collection), with the
+//!   `ClientRemoteTabs` for that client stored as JSON.
+//!
+//! - `tabs_meta`: A simple key-value table, used to store the last sync
+//!   timestamp and the sync IDs. It's based on the `moz_meta` table in places.
+//!
+//! Local tabs aren't stored here: the application always knows its own tabs,
+//! and hands us the current list via `TabsStorage::update_local_state`.
+
+use crate::error::*;
+use rusqlite::Connection;
+use sql_support::ConnExt;
+
+pub const VERSION: i64 = 1;
+
+const CREATE_REMOTE_TABS_TABLE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS remote_tabs (
+        guid            TEXT PRIMARY KEY,
+        record          TEXT NOT NULL,
+        -- Milliseconds (a sync15::ServerTimestamp), from the record.
+        server_modified INTEGER NOT NULL
+    ) WITHOUT ROWID
+";
+
+const CREATE_META_TABLE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS tabs_meta (
+        key TEXT PRIMARY KEY,
+        value NOT NULL
+    ) WITHOUT ROWID
+";
+
+pub(crate) static LAST_SYNC_META_KEY: &str = "last_sync_time";
+pub(crate) static GLOBAL_SYNCID_META_KEY: &str = "global_sync_id";
+pub(crate) static COLLECTION_SYNCID_META_KEY: &str = "tabs_sync_id";
+
+pub(crate) fn init(db: &Connection) -> Result<()> {
+    let user_version = db.query_one::<i64>("PRAGMA user_version")?;
+    if user_version == 0 {
+        return create(db);
+    }
+    if user_version != VERSION {
+        if user_version < VERSION {
+            upgrade(db, user_version)?;
+        } else {
+            log::warn!(
+                "Loaded future schema version {} (we only understand version {}). \
+                 Optimistically ",
+                user_version,
+                VERSION
+            )
+        }
+    }
+    Ok(())
+}
+
+fn upgrade(db: &Connection, from: i64) -> Result<()> {
+    log::debug!("Upgrading schema from {} to {}", from, VERSION);
+    if from == VERSION {
+        return Ok(());
+    }
+    // Nothing to upgrade yet! When bumping `VERSION`, add a block here for
+    // each version, like `if from < 2 { ... }`, so that databases at any
+    // earlier version end up with the current schema.
+    db.execute_all(&[set_version_sql().as_str()])?;
+    Ok(())
+}
+
+pub(crate) fn create(db: &Connection) -> Result<()> {
+    log::debug!("Creating schema");
+    db.execute_all(&[
+        CREATE_REMOTE_TABS_TABLE_SQL,
+        CREATE_META_TABLE_SQL,
+        set_version_sql().as_str(),
+    ])?;
+    Ok(())
+}
+
+fn set_version_sql() -> String {
+    format!("PRAGMA user_version = {version}", version = VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TabsDb;
+
+    #[test]
+    fn test_create_schema_twice() {
+        let db = TabsDb::open_in_memory().expect("should open");
+        create(&db).expect("should allow running twice");
+    }
+
+    #[test]
+    fn test_future_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tabs.db");
+        {
+            let db = TabsDb::open(&path).expect("should open");
+            db.execute_all(&["PRAGMA user_version = 100"]).unwrap();
+        }
+        let db = TabsDb::open(&path).expect("should open a db from the future");
+        assert_eq!(db.query_one::<i64>("PRAGMA user_version").unwrap(), 100);
+    }
+}
@@ -7,8 +7,16 @@ const URI_LENGTH_MAX: usize = 65536;
 // https://searchfox.org/mozilla-central/rev/ea63a0888d406fae720cf24f4727d87569a8cab5/services/sync/modules/engines/tabs.js#8
 const TAB_ENTRIES_LIMIT: usize = 5;
 
+use crate::db::TabsDb;
+use crate::error::*;
+use crate::schema;
+use rusqlite::{named_params, Row};
+use sql_support::{ConnExt, SqlInterruptHandle};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
 use sync15::clients::DeviceType;
+use sync15::ServerTimestamp;
 
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct RemoteTab {
@@ -26,25 +34,68 @@ pub struct ClientRemoteTabs {
     pub remote_tabs: Vec<RemoteTab>,
 }
 
+impl ClientRemoteTabs {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        let record: String = row.get("record")?;
+        Ok(serde_json::from_str(&record)?)
+    }
+}
+
+/// The outcome of matching a close-tabs request from another device against
+/// our local tabs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CloseTabsResult {
+    /// The local tabs whose current URL was requested, which the application
+    /// should close.
+    pub closed: Vec<RemoteTab>,
+    /// The requested URLs that didn't match a local tab. A URL that was
+    /// requested more times than we have tabs for is listed once for each
+    /// tab that we couldn't find.
+    pub not_found: Vec<String>,
+}
+
+/// An incoming change to the remote tabs of another client, keyed by the ID
+/// of its record in the `tabs` collection.
+pub(crate) enum RemoteTabsChange {
+    Update {
+        guid: String,
+        tabs: ClientRemoteTabs,
+        modified: ServerTimestamp,
+    },
+    Delete {
+        guid: String,
+    },
+}
+
+/// Local tabs are only kept in memory, since the application hands us the
+/// full list whenever it changes. Remote tabs are persisted in a `TabsDb`, so
+/// that they're available as soon as the application starts, without waiting
+/// for a sync.
 pub struct TabsStorage {
+    pub(crate) db: TabsDb,
     local_tabs: RefCell<Option<Vec<RemoteTab>>>,
-    remote_tabs: RefCell<Option<Vec<ClientRemoteTabs>>>,
 }
 
-impl Default for TabsStorage {
-    fn default() -> Self {
-        Self::new()
+impl TabsStorage {
+    pub fn new(db_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::with_db(TabsDb::open(db_path)?))
     }
-}
 
-impl TabsStorage {
-    pub fn new() -> Self {
+    pub fn new_in_memory() -> Result<Self> {
+        Ok(Self::with_db(TabsDb::open_in_memory()?))
+    }
+
+    fn with_db(db: TabsDb) -> Self {
         Self {
+            db,
             local_tabs: RefCell::default(),
-            remote_tabs: RefCell::default(),
         }
     }
 
+    pub fn new_interrupt_handle(&self) -> SqlInterruptHandle {
+        self.db.new_interrupt_handle()
+    }
+
     pub fn update_local_state(&mut self, local_state: Vec<RemoteTab>) {
         self.local_tabs.borrow_mut().replace(local_state);
     }
@@ -77,17 +128,118 @@ impl TabsStorage {
         None
     }
 
-    pub fn get_remote_tabs(&self) -> Option<Vec<ClientRemoteTabs>> {
-        self.remote_tabs.borrow().clone()
+    /// Matches the URLs from a close-tabs command against the current URL of
+    /// each local tab. Each URL closes one tab, so a URL that's listed twice
+    /// closes two tabs with that URL, if we have them. Tabs are picked in the
+    /// order of our local tabs, and each tab is only closed once.
+    pub fn find_tabs_to_close(&self, urls: &[String]) -> CloseTabsResult {
+        // How many tabs to close for each URL.
+        let mut remaining = HashMap::<&str, usize>::with_capacity(urls.len());
+        for url in urls {
+            *remaining.entry(url.as_str()).or_default() += 1;
+        }
+        let mut result = CloseTabsResult::default();
+        let local_tabs = self.local_tabs.borrow();
+        for tab in local_tabs.as_deref().unwrap_or_default() {
+            let count = tab
+                .url_history
+                .first()
+                .and_then(|url| remaining.get_mut(url.as_str()));
+            if let Some(count) = count {
+                if *count > 0 {
+                    *count -= 1;
+                    result.closed.push(tab.clone());
+                }
+            }
+        }
+        // Report the URLs that we didn't find enough tabs for, in the order
+        // they were requested.
+        for url in urls {
+            if let Some(count) = remaining.get_mut(url.as_str()) {
+                if *count > 0 {
+                    *count -= 1;
+                    result.not_found.push(url.clone());
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the remote tabs as of the last sync, or `None` if we've never
+    /// synced.
+    pub fn get_remote_tabs(&self) -> Result<Option<Vec<ClientRemoteTabs>>> {
+        if self
+            .db
+            .get_meta::<i64>(schema::LAST_SYNC_META_KEY)?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let tabs = self.db.query_rows_and_then_named(
+            "SELECT record FROM remote_tabs ORDER BY server_modified DESC",
+            &[],
+            ClientRemoteTabs::from_row,
+        )?;
+        Ok(Some(tabs))
+    }
+
+    pub(crate) fn apply_remote_tabs_changes(&self, changes: Vec<RemoteTabsChange>) -> Result<()> {
+        let tx = self.db.unchecked_transaction()?;
+        for change in changes {
+            match change {
+                RemoteTabsChange::Update {
+                    guid,
+                    tabs,
+                    modified,
+                } => {
+                    self.db.execute_named_cached(
+                        "REPLACE INTO remote_tabs (guid, record, server_modified)
+                         VALUES (:guid, :record, :server_modified)",
+                        named_params! {
+                            ":guid": guid,
+                            ":record": serde_json::to_string(&tabs)?,
+                            ":server_modified": modified.as_millis(),
+                        },
+                    )?;
+                }
+                RemoteTabsChange::Delete { guid } => {
+                    self.db.execute_named_cached(
+                        "DELETE FROM remote_tabs WHERE guid = :guid",
+                        named_params! { ":guid": guid },
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
     }
 
-    pub(crate) fn replace_remote_tabs(&self, new_remote_tabs: Vec<ClientRemoteTabs>) {
-        let mut remote_tabs = self.remote_tabs.borrow_mut();
-        remote_tabs.replace(new_remote_tabs);
+    /// Removes the tabs of clients that aren't in `client_ids`. Clients that
+    /// disappear from the clients collection don't delete their tabs
+    /// records, so we'd show their tabs forever otherwise.
+    pub(crate) fn remove_stale_clients(&self, client_ids: &[&str]) -> Result<()> {
+        let tx = self.db.unchecked_transaction()?;
+        let guids =
+            self.db
+                .query_rows_and_then_named("SELECT guid FROM remote_tabs", &[], |row| {
+                    row.get::<_, String>("guid")
+                })?;
+        for guid in guids {
+            if !client_ids.contains(&guid.as_str()) {
+                log::debug!("Removing tabs for stale client {}", guid);
+                self.db.execute_named_cached(
+                    "DELETE FROM remote_tabs WHERE guid = :guid",
+                    named_params! { ":guid": guid },
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
     }
 
-    pub fn wipe_remote_tabs(&self) {
-        self.remote_tabs.replace(None);
+    pub fn wipe_remote_tabs(&self) -> Result<()> {
+        self.db.execute_all(&["DELETE FROM remote_tabs"])?;
+        Ok(())
     }
 
     pub fn wipe_local_tabs(&self) {
@@ -121,7 +273,7 @@ mod tests {
 
     #[test]
     fn test_prepare_local_tabs_for_upload() {
-        let mut storage = TabsStorage::new();
+        let mut storage = TabsStorage::new_in_memory().unwrap();
         assert_eq!(storage.prepare_local_tabs_for_upload(), None);
         storage.update_local_state(vec![
             RemoteTab {
@@ -190,4 +342,108 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_find_tabs_to_close() {
+        let mut storage = TabsStorage::new_in_memory().unwrap();
+        let tab = |url: &str, last_used| RemoteTab {
+            title: "".to_owned(),
+            url_history: vec![url.to_owned(), "https://previous.com".to_owned()],
+            icon: None,
+            last_used,
+        };
+        let urls = vec![
+            "https://foo.bar".to_owned(),
+            "https://previous.com".to_owned(),
+            "https://foo.bar".to_owned(),
+            "https://example.com".to_owned(),
+            "https://example.com".to_owned(),
+        ];
+        assert_eq!(
+            storage.find_tabs_to_close(&urls),
+            CloseTabsResult {
+                closed: vec![],
+                not_found: urls.clone(),
+            }
+        );
+        storage.update_local_state(vec![
+            tab("https://foo.bar", 1),
+            tab("https://example.com", 2),
+            tab("https://foo.bar", 3),
+            tab("https://foo.bar", 4),
+        ]);
+        assert_eq!(
+            storage.find_tabs_to_close(&urls),
+            CloseTabsResult {
+                // We only close as many tabs as were requested for each URL.
+                closed: vec![
+                    tab("https://foo.bar", 1),
+                    tab("https://example.com", 2),
+                    tab("https://foo.bar", 3),
+                ],
+                // Only the current URL of a tab is matched, not its history.
+                not_found: vec![
+                    "https://previous.com".to_owned(),
+                    "https://example.com".to_owned(),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_remote_tabs_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tabs.db");
+        let tabs = ClientRemoteTabs {
+            client_id: "device-1".to_owned(),
+            client_name: "Device 1".to_owned(),
+            device_type: DeviceType::Desktop,
+            remote_tabs: vec![RemoteTab {
+                title: "Example".to_owned(),
+                url_history: vec!["https://example.com".to_owned()],
+                icon: None,
+                last_used: 1000,
+            }],
+        };
+        {
+            let storage = TabsStorage::new(&path).unwrap();
+            assert!(storage.get_remote_tabs().unwrap().is_none());
+            storage
+                .apply_remote_tabs_changes(vec![
+                    RemoteTabsChange::Update {
+                        guid: "client-1".to_owned(),
+                        tabs: tabs.clone(),
+                        modified: ServerTimestamp(1000),
+                    },
+                    RemoteTabsChange::Update {
+                        guid: "client-2".to_owned(),
+                        tabs: tabs.clone(),
+                        modified: ServerTimestamp(2000),
+                    },
+                ])
+                .unwrap();
+            storage
+                .db
+                .put_meta(schema::LAST_SYNC_META_KEY, &2000i64)
+                .unwrap();
+        }
+        let storage = TabsStorage::new(&path).unwrap();
+        let remote_tabs = storage
+            .get_remote_tabs()
+            .unwrap()
+            .expect("should have remote tabs after reopening");
+        assert_eq!(remote_tabs.len(), 2);
+        assert_eq!(remote_tabs[0].client_id, "device-1");
+        assert_eq!(remote_tabs[0].remote_tabs, tabs.remote_tabs);
+
+        storage
+            .apply_remote_tabs_changes(vec![RemoteTabsChange::Delete {
+                guid: "client-1".to_owned(),
+            }])
+            .unwrap();
+        assert_eq!(storage.get_remote_tabs().unwrap().unwrap().len(), 1);
+
+        storage.wipe_remote_tabs().unwrap();
+        assert_eq!(storage.get_remote_tabs().unwrap().map(|t| t.len()), Some(0));
+    }
 }
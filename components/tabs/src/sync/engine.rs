@@ -3,10 +3,11 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::error::*;
-use crate::storage::{ClientRemoteTabs, RemoteTab, TabsStorage};
+use crate::storage::{ClientRemoteTabs, CloseTabsResult, RemoteTab, TabsStorage};
 use crate::sync::store::TabsStore;
-use interrupt_support::NeverInterrupts;
+use sql_support::SqlInterruptHandle;
 use std::cell::{Cell, RefCell};
+use std::path::Path;
 use sync15::{sync_multiple, telemetry, KeyBundle, MemoryCachedState, Sync15StorageClientInit};
 
 pub struct TabsEngine {
@@ -14,28 +15,40 @@ pub struct TabsEngine {
     mem_cached_state: Cell<MemoryCachedState>,
 }
 
-impl Default for TabsEngine {
-    fn default() -> Self {
-        Self::new()
+impl TabsEngine {
+    pub fn new(db_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::with_storage(TabsStorage::new(db_path)?))
     }
-}
 
-impl TabsEngine {
-    pub fn new() -> Self {
+    pub fn new_in_memory() -> Result<Self> {
+        Ok(Self::with_storage(TabsStorage::new_in_memory()?))
+    }
+
+    fn with_storage(storage: TabsStorage) -> Self {
         Self {
-            storage: TabsStorage::new(),
+            storage,
             mem_cached_state: Cell::default(),
         }
     }
 
+    pub fn new_interrupt_handle(&self) -> SqlInterruptHandle {
+        self.storage.new_interrupt_handle()
+    }
+
     pub fn update_local_state(&mut self, local_state: Vec<RemoteTab>) {
         self.storage.update_local_state(local_state);
     }
 
-    pub fn remote_tabs(&self) -> Option<Vec<ClientRemoteTabs>> {
+    pub fn remote_tabs(&self) -> Result<Option<Vec<ClientRemoteTabs>>> {
         self.storage.get_remote_tabs()
     }
 
+    /// Finds the local tabs another device asked us to close, using the URLs
+    /// from an incoming close-tabs device command.
+    pub fn find_tabs_to_close(&self, urls: &[String]) -> CloseTabsResult {
+        self.storage.find_tabs_to_close(urls)
+    }
+
     /// A convenience wrapper around sync_multiple.
     pub fn sync(
         &self,
@@ -58,7 +71,7 @@ impl TabsEngine {
             &mut mem_cached_state,
             storage_init,
             root_sync_key,
-            &NeverInterrupts,
+            &self.storage.db.begin_interrupt_scope(),
             None,
         );
 
@@ -2,16 +2,17 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::storage::TabsStorage;
-use crate::storage::{ClientRemoteTabs, RemoteTab};
+use crate::schema;
+use crate::storage::{ClientRemoteTabs, RemoteTab, RemoteTabsChange, TabsStorage};
 use crate::sync::record::{TabsRecord, TabsRecordTab};
 use anyhow::Result;
-use std::cell::{Cell, RefCell};
+use sql_support::ConnExt;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use sync15::{
     clients::{self, DeviceType, RemoteClient},
-    telemetry, CollectionRequest, IncomingChangeset, OutgoingChangeset, Payload, ServerTimestamp,
-    Store, StoreSyncAssociation,
+    telemetry, CollSyncIds, CollectionRequest, DryRun, IncomingChangeset, OutgoingChangeset,
+    Payload, PlannedAction, RecordPlan, ServerTimestamp, Store, StoreSyncAssociation,
 };
 use sync_guid::Guid;
 
@@ -72,8 +73,6 @@ impl ClientRemoteTabs {
 pub struct TabsStore<'a> {
     storage: &'a TabsStorage,
     remote_clients: RefCell<HashMap<String, RemoteClient>>,
-    last_sync: Cell<Option<ServerTimestamp>>, // We use a cell because `sync_finished` doesn't take a mutable reference to &self.
-    sync_store_assoc: RefCell<StoreSyncAssociation>,
     pub(crate) local_id: RefCell<String>,
 }
 
@@ -82,42 +81,50 @@ impl<'a> TabsStore<'a> {
         Self {
             storage,
             remote_clients: RefCell::default(),
-            last_sync: Cell::default(),
-            sync_store_assoc: RefCell::new(StoreSyncAssociation::Disconnected),
             local_id: RefCell::default(), // Will get replaced in `prepare_for_sync`.
         }
     }
-}
 
-impl<'a> Store for TabsStore<'a> {
-    fn collection_name(&self) -> std::borrow::Cow<'static, str> {
-        "tabs".into()
+    fn get_last_sync(&self) -> Result<Option<ServerTimestamp>> {
+        let millis = self
+            .storage
+            .db
+            .get_meta::<i64>(schema::LAST_SYNC_META_KEY)?;
+        Ok(millis.map(ServerTimestamp))
     }
 
-    fn prepare_for_sync(&self, get_client_data: &dyn Fn() -> clients::ClientData) -> Result<()> {
-        let data = get_client_data();
-        self.remote_clients.replace(data.recent_clients);
-        self.local_id.replace(data.local_client_id);
+    fn set_last_sync(&self, last_sync: ServerTimestamp) -> Result<()> {
+        log::debug!("Updating last sync to {}", last_sync);
+        self.storage
+            .db
+            .put_meta(schema::LAST_SYNC_META_KEY, &last_sync.as_millis())?;
         Ok(())
     }
 
-    fn apply_incoming(
+    /// Converts incoming records into changes to our remote tabs.
+    fn remote_tabs_changes(
         &self,
-        inbound: Vec<IncomingChangeset>,
+        records: Vec<(Payload, ServerTimestamp)>,
         telem: &mut telemetry::Engine,
-    ) -> Result<OutgoingChangeset> {
-        assert_eq!(inbound.len(), 1, "only requested one item");
-        let inbound = inbound.into_iter().next().unwrap();
+    ) -> Result<Vec<RemoteTabsChange>> {
         let mut incoming_telemetry = telemetry::EngineIncoming::new();
         let local_id = self.local_id.borrow().clone();
-        let mut remote_tabs = Vec::with_capacity(inbound.changes.len());
+        let mut changes = Vec::with_capacity(records.len());
+        let scope = self.storage.db.begin_interrupt_scope();
 
-        for incoming in inbound.changes {
-            if incoming.0.id() == local_id {
+        for (payload, modified) in records {
+            scope.err_if_interrupted()?;
+            if payload.id() == local_id {
                 // That's our own record, ignore it.
                 continue;
             }
-            let record = match TabsRecord::from_payload(incoming.0) {
+            if payload.is_tombstone() {
+                changes.push(RemoteTabsChange::Delete {
+                    guid: payload.id().to_owned(),
+                });
+                continue;
+            }
+            let record = match TabsRecord::from_payload(payload) {
                 Ok(record) => record,
                 Err(e) => {
                     log::warn!("Error deserializing incoming record: {}", e);
@@ -126,7 +133,7 @@ impl<'a> Store for TabsStore<'a> {
                 }
             };
             let id = record.id.clone();
-            let tab = if let Some(remote_client) = self.remote_clients.borrow().get(&id) {
+            let tabs = if let Some(remote_client) = self.remote_clients.borrow().get(&id) {
                 ClientRemoteTabs::from_record_with_remote_client(
                     remote_client
                         .fxa_device_id
@@ -137,12 +144,22 @@ impl<'a> Store for TabsStore<'a> {
                     record,
                 )
             } else {
-                ClientRemoteTabs::from_record(id, record)
+                ClientRemoteTabs::from_record(id.clone(), record)
             };
-            remote_tabs.push(tab);
+            changes.push(RemoteTabsChange::Update {
+                guid: id,
+                tabs,
+                modified,
+            });
         }
-        self.storage.replace_remote_tabs(remote_tabs);
-        let mut outgoing = OutgoingChangeset::new("tabs", inbound.timestamp);
+        telem.incoming(incoming_telemetry);
+        Ok(changes)
+    }
+
+    /// Returns a changeset with our local tabs, if we have any.
+    fn outgoing_changeset(&self, timestamp: ServerTimestamp) -> Result<OutgoingChangeset> {
+        let local_id = self.local_id.borrow().clone();
+        let mut outgoing = OutgoingChangeset::new("tabs", timestamp);
         if let Some(local_tabs) = self.storage.prepare_local_tabs_for_upload() {
             let (client_name, device_type) = self
                 .remote_clients
@@ -165,9 +182,58 @@ impl<'a> Store for TabsStore<'a> {
             log::trace!("outgoing {:?}", payload);
             outgoing.changes.push(payload);
         }
-        telem.incoming(incoming_telemetry);
         Ok(outgoing)
     }
+}
+
+impl<'a> Store for TabsStore<'a> {
+    fn collection_name(&self) -> std::borrow::Cow<'static, str> {
+        "tabs".into()
+    }
+
+    fn prepare_for_sync(&self, get_client_data: &dyn Fn() -> clients::ClientData) -> Result<()> {
+        let data = get_client_data();
+        self.remote_clients.replace(data.recent_clients);
+        self.local_id.replace(data.local_client_id);
+        Ok(())
+    }
+
+    fn apply_incoming(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> Result<OutgoingChangeset> {
+        assert_eq!(inbound.len(), 1, "only requested one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        let changes = self.remote_tabs_changes(inbound.changes, telem)?;
+        self.storage.apply_remote_tabs_changes(changes)?;
+        self.outgoing_changeset(inbound.timestamp)
+    }
+
+    fn apply_incoming_dry_run(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> Result<DryRun> {
+        assert_eq!(inbound.len(), 1, "only requested one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        // We replace each client's tabs wholesale, so there's nothing to
+        // reconcile, and we don't need to touch storage to plan.
+        let incoming = self
+            .remote_tabs_changes(inbound.changes, telem)?
+            .into_iter()
+            .map(|change| match change {
+                RemoteTabsChange::Update { guid, .. } => {
+                    RecordPlan::new(guid, PlannedAction::Apply)
+                }
+                RemoteTabsChange::Delete { guid } => RecordPlan::new(guid, PlannedAction::Delete),
+            })
+            .collect();
+        Ok(DryRun {
+            incoming,
+            outgoing: self.outgoing_changeset(inbound.timestamp)?,
+        })
+    }
 
     fn sync_finished(
         &self,
@@ -178,7 +244,17 @@ impl<'a> Store for TabsStore<'a> {
             "sync completed after uploading {} records",
             records_synced.len()
         );
-        self.last_sync.set(Some(new_timestamp));
+        self.set_last_sync(new_timestamp)?;
+        // We only know which clients still exist if the clients engine ran
+        // before us, in which case the list includes our own client.
+        let remote_clients = self.remote_clients.borrow();
+        if !remote_clients.is_empty() {
+            let client_ids = remote_clients
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            self.storage.remove_stale_clients(&client_ids)?;
+        }
         Ok(())
     }
 
@@ -186,7 +262,7 @@ impl<'a> Store for TabsStore<'a> {
         &self,
         server_timestamp: ServerTimestamp,
     ) -> Result<Vec<CollectionRequest>> {
-        let since = self.last_sync.get().unwrap_or_default();
+        let since = self.get_last_sync()?.unwrap_or_default();
         Ok(if since == server_timestamp {
             vec![]
         } else {
@@ -195,14 +271,34 @@ impl<'a> Store for TabsStore<'a> {
     }
 
     fn get_sync_assoc(&self) -> Result<StoreSyncAssociation> {
-        Ok(self.sync_store_assoc.borrow().clone())
+        let db = &self.storage.db;
+        let global = db.get_meta(schema::GLOBAL_SYNCID_META_KEY)?;
+        let coll = db.get_meta(schema::COLLECTION_SYNCID_META_KEY)?;
+        Ok(if let (Some(global), Some(coll)) = (global, coll) {
+            StoreSyncAssociation::Connected(CollSyncIds { global, coll })
+        } else {
+            StoreSyncAssociation::Disconnected
+        })
     }
 
     fn reset(&self, assoc: &StoreSyncAssociation) -> Result<()> {
+        log::info!("Executing reset on tabs store!");
         self.remote_clients.borrow_mut().clear();
-        self.sync_store_assoc.replace(assoc.clone());
-        self.last_sync.set(None);
-        self.storage.wipe_remote_tabs();
+        let db = &self.storage.db;
+        let tx = db.unchecked_transaction()?;
+        self.storage.wipe_remote_tabs()?;
+        db.delete_meta(schema::LAST_SYNC_META_KEY)?;
+        match assoc {
+            StoreSyncAssociation::Disconnected => {
+                db.delete_meta(schema::GLOBAL_SYNCID_META_KEY)?;
+                db.delete_meta(schema::COLLECTION_SYNCID_META_KEY)?;
+            }
+            StoreSyncAssociation::Connected(ids) => {
+                db.put_meta(schema::GLOBAL_SYNCID_META_KEY, &ids.global)?;
+                db.put_meta(schema::COLLECTION_SYNCID_META_KEY, &ids.coll)?;
+            }
+        };
+        tx.commit()?;
         Ok(())
     }
 
@@ -212,3 +308,163 @@ impl<'a> Store for TabsStore<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tabs_payload(id: &str, title: &str) -> Payload {
+        Payload::from_json(json!({
+            "id": id,
+            "clientName": "Some client",
+            "tabs": [{
+                "title": title,
+                "urlHistory": ["https://example.com"],
+                "icon": null,
+                "lastUsed": 1,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_incoming() {
+        let storage = TabsStorage::new_in_memory().unwrap();
+        let store = TabsStore::new(&storage);
+        store.local_id.replace("local".to_owned());
+
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(1000));
+        incoming.changes = vec![
+            (tabs_payload("local", "Ours"), ServerTimestamp(1000)),
+            (tabs_payload("remote-1", "Theirs"), ServerTimestamp(1000)),
+            (tabs_payload("remote-2", "Gone soon"), ServerTimestamp(1000)),
+        ];
+        let mut telem = telemetry::Engine::new("tabs");
+        store.apply_incoming(vec![incoming], &mut telem).unwrap();
+        store.sync_finished(ServerTimestamp(1000), vec![]).unwrap();
+
+        let remote_tabs = storage.get_remote_tabs().unwrap().unwrap();
+        let mut titles = remote_tabs
+            .iter()
+            .map(|client| client.remote_tabs[0].title.as_str())
+            .collect::<Vec<_>>();
+        titles.sort();
+        assert_eq!(titles, vec!["Gone soon", "Theirs"]);
+
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(2000));
+        incoming.changes = vec![(Payload::new_tombstone("remote-2"), ServerTimestamp(2000))];
+        store.apply_incoming(vec![incoming], &mut telem).unwrap();
+
+        let remote_tabs = storage.get_remote_tabs().unwrap().unwrap();
+        assert_eq!(remote_tabs.len(), 1);
+        assert_eq!(remote_tabs[0].client_id, "remote-1");
+    }
+
+    #[test]
+    fn test_stale_clients_removed() {
+        let storage = TabsStorage::new_in_memory().unwrap();
+        let store = TabsStore::new(&storage);
+        let mut recent_clients = HashMap::new();
+        for id in &["local", "remote-1"] {
+            recent_clients.insert(
+                id.to_string(),
+                RemoteClient {
+                    fxa_device_id: None,
+                    device_name: id.to_string(),
+                    device_type: Some(DeviceType::Desktop),
+                },
+            );
+        }
+        store
+            .prepare_for_sync(&|| clients::ClientData {
+                local_client_id: "local".to_owned(),
+                recent_clients: recent_clients.clone(),
+            })
+            .unwrap();
+
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(1000));
+        incoming.changes = vec![
+            (tabs_payload("remote-1", "Theirs"), ServerTimestamp(1000)),
+            (
+                tabs_payload("remote-2", "Removed client"),
+                ServerTimestamp(1000),
+            ),
+        ];
+        let mut telem = telemetry::Engine::new("tabs");
+        store.apply_incoming(vec![incoming], &mut telem).unwrap();
+        store.sync_finished(ServerTimestamp(1000), vec![]).unwrap();
+
+        let remote_tabs = storage.get_remote_tabs().unwrap().unwrap();
+        assert_eq!(remote_tabs.len(), 1);
+        assert_eq!(remote_tabs[0].remote_tabs[0].title, "Theirs");
+    }
+
+    #[test]
+    fn test_apply_incoming_dry_run() {
+        let storage = TabsStorage::new_in_memory().unwrap();
+        let store = TabsStore::new(&storage);
+        store.local_id.replace("local".to_owned());
+
+        let mut incoming = IncomingChangeset::new(store.collection_name(), ServerTimestamp(1000));
+        incoming.changes = vec![
+            (tabs_payload("local", "Ours"), ServerTimestamp(1000)),
+            (tabs_payload("remote-1", "Theirs"), ServerTimestamp(1000)),
+            (Payload::new_tombstone("remote-2"), ServerTimestamp(1000)),
+        ];
+        let mut telem = telemetry::Engine::new("tabs");
+        let dry_run = store
+            .apply_incoming_dry_run(vec![incoming], &mut telem)
+            .unwrap();
+        assert_eq!(
+            dry_run.incoming,
+            vec![
+                RecordPlan::new("remote-1", PlannedAction::Apply),
+                RecordPlan::new("remote-2", PlannedAction::Delete),
+            ]
+        );
+        assert!(dry_run.outgoing.changes.is_empty());
+        assert!(storage.get_remote_tabs().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_meta_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tabs.db");
+        let ids = CollSyncIds {
+            global: Guid::random(),
+            coll: Guid::random(),
+        };
+        {
+            let storage = TabsStorage::new(&path).unwrap();
+            let store = TabsStore::new(&storage);
+            assert_eq!(
+                store.get_sync_assoc().unwrap(),
+                StoreSyncAssociation::Disconnected
+            );
+            store
+                .reset(&StoreSyncAssociation::Connected(ids.clone()))
+                .unwrap();
+            store.sync_finished(ServerTimestamp(1234), vec![]).unwrap();
+        }
+        let storage = TabsStorage::new(&path).unwrap();
+        let store = TabsStore::new(&storage);
+        assert_eq!(
+            store.get_sync_assoc().unwrap(),
+            StoreSyncAssociation::Connected(ids)
+        );
+        assert_eq!(
+            store
+                .get_collection_requests(ServerTimestamp(1234))
+                .unwrap(),
+            vec![]
+        );
+
+        store.wipe().unwrap();
+        assert_eq!(
+            store.get_sync_assoc().unwrap(),
+            StoreSyncAssociation::Disconnected
+        );
+        assert!(storage.get_remote_tabs().unwrap().is_none());
+    }
+}
@@ -4,6 +4,10 @@
 
 use ffi::FfiBackend;
 use once_cell::sync::OnceCell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
 
 mod ffi;
 
@@ -38,9 +42,121 @@ pub(crate) fn get_backend() -> &'static dyn Backend {
     *BACKEND.get_or_init(|| Box::leak(Box::new(FfiBackend)))
 }
 
+type SendResult = Result<crate::Response, crate::Error>;
+
+/// Interrupts requests sent from inside `with_interrupt_handle`. This is safe
+/// to call from any thread.
+#[derive(Clone, Default)]
+pub struct RequestInterruptHandle(Arc<Mutex<InterruptState>>);
+
+#[derive(Default)]
+struct InterruptState {
+    interrupted: bool,
+    next_id: usize,
+    /// The channels of the requests that are waiting for a response, so that
+    /// interrupting can wake them up.
+    waiting: HashMap<usize, mpsc::Sender<SendResult>>,
+}
+
+impl RequestInterruptHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes requests that are waiting for a response return
+    /// `Error::Interrupted` right away, along with any requests sent after.
+    pub fn interrupt(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.interrupted = true;
+        for (_, tx) in state.waiting.drain() {
+            // The request may have just finished; that's fine.
+            let _ = tx.send(Err(crate::Error::Interrupted));
+        }
+    }
+
+    pub fn was_interrupted(&self) -> bool {
+        self.0.lock().unwrap().interrupted
+    }
+}
+
+thread_local! {
+    static INTERRUPT_HANDLE: RefCell<Option<RequestInterruptHandle>> = RefCell::new(None);
+}
+
+/// Calls `f`, making any requests sent from this thread while it runs return
+/// `Error::Interrupted` as soon as `handle` is interrupted, instead of waiting
+/// for the response.
+///
+/// The backend can't cancel a request once it's sent, so each of these
+/// requests is sent from its own thread. An interrupted request keeps its
+/// thread running until the backend's connect and read timeouts (see
+/// `GLOBAL_SETTINGS`) expire, and its response is dropped.
+pub fn with_interrupt_handle<R>(handle: RequestInterruptHandle, f: impl FnOnce() -> R) -> R {
+    // Restores the previous handle, even if `f` panics.
+    struct Restore(Option<RequestInterruptHandle>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            INTERRUPT_HANDLE.with(|h| *h.borrow_mut() = prev);
+        }
+    }
+    let _restore = Restore(INTERRUPT_HANDLE.with(|h| h.replace(Some(handle))));
+    f()
+}
+
 pub fn send(request: crate::Request) -> Result<crate::Response, crate::Error> {
     validate_request(&request)?;
-    get_backend().send(request)
+    match INTERRUPT_HANDLE.with(|h| h.borrow().clone()) {
+        Some(handle) => send_interruptible(request, &handle, get_backend()),
+        None => get_backend().send(request),
+    }
+}
+
+fn send_interruptible(
+    request: crate::Request,
+    interrupt_handle: &RequestInterruptHandle,
+    backend: &'static dyn Backend,
+) -> Result<crate::Response, crate::Error> {
+    let (tx, rx) = mpsc::channel();
+    let id = {
+        let mut state = interrupt_handle.0.lock().unwrap();
+        if state.interrupted {
+            return Err(crate::Error::Interrupted);
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiting.insert(id, tx.clone());
+        id
+    };
+    // The thread is detached, so a request that hangs doesn't hold up any
+    // other requests, interruptible or not.
+    let spawned = std::thread::Builder::new()
+        .name("viaduct-request".into())
+        .spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| backend.send(request)))
+                .unwrap_or_else(|_| {
+                    Err(crate::Error::BackendError(
+                        "The backend panicked while sending the request".into(),
+                    ))
+                });
+            // The receiver is gone if we were interrupted; that's fine.
+            let _ = tx.send(result);
+        });
+    let result = match spawned {
+        // Either the request thread or `interrupt` sends the result, whichever
+        // is first. The handle keeps a sender until we remove it below, so
+        // this can't fail.
+        Ok(_) => rx.recv().expect("The request should have a sender"),
+        Err(e) => Err(crate::Error::BackendError(format!(
+            "Failed to start a thread for the request: {}",
+            e
+        ))),
+    };
+    interrupt_handle.0.lock().unwrap().waiting.remove(&id);
+    if let Err(crate::Error::Interrupted) = result {
+        log::info!("Interrupted while waiting for a response");
+    }
+    result
 }
 
 pub fn validate_request(request: &crate::Request) -> Result<(), crate::Error> {
@@ -55,7 +171,7 @@ pub fn validate_request(request: &crate::Request) -> Result<(), crate::Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::validate_request;
+    use super::*;
     #[test]
     fn test_validate_request() {
         let _https_request = crate::Request::new(
@@ -94,4 +210,83 @@ mod tests {
         );
         assert!(validate_request(&localhost_request).is_err());
     }
+
+    #[test]
+    fn test_send_interrupted() {
+        let handle = RequestInterruptHandle::new();
+        handle.interrupt();
+        let request = crate::Request::new(
+            crate::Method::Get,
+            url::Url::parse("https://www.example.com").unwrap(),
+        );
+        let result = with_interrupt_handle(handle, || request.send());
+        match result {
+            Err(crate::Error::Interrupted) => {}
+            _ => panic!("The request should be interrupted"),
+        }
+        // The handle only applies while `with_interrupt_handle` runs.
+        assert!(INTERRUPT_HANDLE.with(|h| h.borrow().is_none()));
+    }
+
+    struct HangingBackend;
+    impl Backend for HangingBackend {
+        fn send(&self, _request: crate::Request) -> SendResult {
+            std::thread::sleep(std::time::Duration::from_secs(60));
+            Err(crate::Error::BackendError("Timed out".into()))
+        }
+    }
+
+    struct OkBackend;
+    impl Backend for OkBackend {
+        fn send(&self, request: crate::Request) -> SendResult {
+            Ok(crate::Response {
+                request_method: request.method,
+                url: request.url,
+                status: 200,
+                headers: crate::Headers::new(),
+                body: vec![],
+            })
+        }
+    }
+
+    fn example_request() -> crate::Request {
+        crate::Request::new(
+            crate::Method::Get,
+            url::Url::parse("https://www.example.com").unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_interrupt_waiting_request() {
+        let handle = RequestInterruptHandle::new();
+        let interrupter = handle.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            interrupter.interrupt();
+        });
+        let result = send_interruptible(example_request(), &handle, &HangingBackend);
+        match result {
+            Err(crate::Error::Interrupted) => {}
+            _ => panic!("The request should be interrupted"),
+        }
+        assert!(handle.0.lock().unwrap().waiting.is_empty());
+    }
+
+    #[test]
+    fn test_hanging_request_does_not_block_others() {
+        let hanging = RequestInterruptHandle::new();
+        let waiter = hanging.clone();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(());
+            send_interruptible(example_request(), &waiter, &HangingBackend)
+        });
+        rx.recv().unwrap();
+
+        let handle = RequestInterruptHandle::new();
+        let response = send_interruptible(example_request(), &handle, &OkBackend).unwrap();
+        assert_eq!(response.status, 200);
+        assert!(!hanging.was_interrupted());
+        hanging.interrupt();
+    }
 }
@@ -26,6 +26,11 @@ pub enum Error {
 
     #[error("[no-sentry] Validation error: URL does not use TLS protocol.")]
     NonTlsUrl,
+
+    /// Returned when the handle passed to `with_interrupt_handle` is
+    /// interrupted while we're waiting for a response.
+    #[error("[no-sentry] The request was interrupted")]
+    Interrupted,
 }
 
 impl From<url::ParseError> for Error {
@@ -14,7 +14,9 @@ pub mod error;
 pub mod settings;
 pub use error::*;
 
-pub use backend::{note_backend, set_backend, Backend};
+pub use backend::{
+    note_backend, set_backend, with_interrupt_handle, Backend, RequestInterruptHandle,
+};
 pub use headers::{consts as header_names, Header, HeaderName, Headers, InvalidHeaderName};
 pub use settings::GLOBAL_SETTINGS;
 
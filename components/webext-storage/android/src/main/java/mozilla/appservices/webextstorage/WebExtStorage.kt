@@ -0,0 +1,154 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.webextstorage
+
+import com.sun.jna.Pointer
+import mozilla.appservices.webextstorage.rust.LibWebExtStorageFFI
+import mozilla.appservices.webextstorage.rust.RawWebExtStoreInterruptHandle
+import mozilla.appservices.webextstorage.rust.RustError
+import java.util.concurrent.atomic.AtomicLong
+import java.util.concurrent.atomic.AtomicReference
+
+/**
+ * Provides access to the `storage.sync` area for WebExtensions, stored in a
+ * SQLite database at [path]. Keys and values are passed as JSON strings, in
+ * the same formats that the `storage.sync` WebExtension API accepts.
+ */
+class WebExtStorage(path: String) : AutoCloseable {
+    private var handle: AtomicLong = AtomicLong(0)
+    private var interruptHandle: AtomicReference<RawWebExtStoreInterruptHandle?> = AtomicReference(null)
+
+    init {
+        handle.set(rustCall { error ->
+            LibWebExtStorageFFI.INSTANCE.webext_store_new(path, error)
+        })
+        interruptHandle.set(rustCall { error ->
+            LibWebExtStorageFFI.INSTANCE.webext_store_new_interrupt_handle(this.handle.get(), error)
+        })
+    }
+
+    /**
+     * Interrupt an in-progress sync or database operation, which will then
+     * throw an [InterruptedException]. This is safe to call from any thread.
+     */
+    fun interrupt() {
+        interruptHandle.get()?.let {
+            rustCall { error ->
+                LibWebExtStorageFFI.INSTANCE.webext_store_interrupt(it, error)
+            }
+        }
+    }
+
+    /**
+     * Returns the stored values for an extension, as a JSON object string.
+     *
+     * @param keys A JSON string with a key, an array of keys, an object with
+     * default values, or `null` for all keys.
+     */
+    fun get(extId: String, keys: String): String {
+        return rustCallWithLock { error ->
+            LibWebExtStorageFFI.INSTANCE.webext_store_get(this.handle.get(), extId, keys, error)
+        }.getAndConsumeRustString()
+    }
+
+    /**
+     * Stores the key-value pairs in [value], which is a JSON object string,
+     * and returns the changes as a JSON string.
+     */
+    fun set(extId: String, value: String): String {
+        return rustCallWithLock { error ->
+            LibWebExtStorageFFI.INSTANCE.webext_store_set(this.handle.get(), extId, value, error)
+        }.getAndConsumeRustString()
+    }
+
+    /**
+     * Removes the values for [keys], which is a JSON string with a key or an
+     * array of keys, and returns the changes as a JSON string.
+     */
+    fun remove(extId: String, keys: String): String {
+        return rustCallWithLock { error ->
+            LibWebExtStorageFFI.INSTANCE.webext_store_remove(this.handle.get(), extId, keys, error)
+        }.getAndConsumeRustString()
+    }
+
+    /**
+     * Removes all values for an extension, and returns the changes as a JSON
+     * string.
+     */
+    fun clear(extId: String): String {
+        return rustCallWithLock { error ->
+            LibWebExtStorageFFI.INSTANCE.webext_store_clear(this.handle.get(), extId, error)
+        }.getAndConsumeRustString()
+    }
+
+    /**
+     * Return the raw handle used to reference this store.
+     *
+     * Generally should only be used to pass the handle into `SyncManager.setWebExtStorage`
+     */
+    fun getHandle(): Long {
+        return this.handle.get()
+    }
+
+    @Synchronized
+    override fun close() {
+        val handle = this.handle.getAndSet(0L)
+        if (handle != 0L) {
+            rustCall { error ->
+                LibWebExtStorageFFI.INSTANCE.webext_store_destroy(handle, error)
+            }
+        }
+        interruptHandle.getAndSet(null)?.let {
+            LibWebExtStorageFFI.INSTANCE.webext_store_interrupt_handle_destroy(it)
+        }
+    }
+
+    private inline fun <U> nullableRustCall(callback: (RustError.ByReference) -> U?): U? {
+        val e = RustError.ByReference()
+        try {
+            val ret = callback(e)
+            if (e.isFailure()) {
+                throw e.intoException()
+            }
+            return ret
+        } finally {
+            // This only matters if `callback` throws (or does a non-local return, which
+            // we currently don't do)
+            e.ensureConsumed()
+        }
+    }
+
+    private inline fun <U> rustCall(callback: (RustError.ByReference) -> U?): U {
+        return nullableRustCall(callback)!!
+    }
+
+    private inline fun <U> rustCallWithLock(callback: (RustError.ByReference) -> U?): U {
+        return synchronized(this) {
+            rustCall { callback(it) }
+        }
+    }
+}
+
+/**
+ * Helper to read a null terminated String out of the Pointer and free it.
+ *
+ * Important: Do not use this pointer after this! For anything!
+ */
+internal fun Pointer.getAndConsumeRustString(): String {
+    try {
+        return this.getRustString()
+    } finally {
+        LibWebExtStorageFFI.INSTANCE.webext_store_destroy_string(this)
+    }
+}
+
+/**
+ * Helper to read a null terminated string out of the pointer.
+ *
+ * Important: doesn't free the pointer, use [getAndConsumeRustString] for that!
+ */
+internal fun Pointer.getRustString(): String {
+    return this.getString(0, "utf8")
+}
@@ -0,0 +1,24 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.webextstorage
+
+open class WebExtStorageException(msg: String) : Exception(msg)
+
+/**
+ * This error is emitted if an extension exceeds one of the `storage.sync`
+ * quotas.
+ */
+class QuotaExceededException(msg: String) : WebExtStorageException(msg)
+
+/**
+ * This error is emitted if an operation is interrupted.
+ */
+class InterruptedException(msg: String) : WebExtStorageException(msg)
+
+/**
+ * This error is emitted if the keys or value passed to the store aren't
+ * valid JSON.
+ */
+class InvalidJsonException(msg: String) : WebExtStorageException(msg)
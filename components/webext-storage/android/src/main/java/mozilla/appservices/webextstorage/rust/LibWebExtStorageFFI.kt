@@ -0,0 +1,63 @@
+@file:Suppress("MaxLineLength")
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.webextstorage.rust
+
+import com.sun.jna.Library
+import com.sun.jna.Pointer
+import com.sun.jna.PointerType
+import mozilla.appservices.support.native.loadIndirect
+import org.mozilla.appservices.webextstorage.BuildConfig
+
+@Suppress("FunctionNaming", "FunctionParameterNaming", "LongParameterList", "TooGenericExceptionThrown")
+internal interface LibWebExtStorageFFI : Library {
+    companion object {
+        internal var INSTANCE: LibWebExtStorageFFI =
+            loadIndirect(componentName = "webextstorage", componentVersion = BuildConfig.LIBRARY_VERSION)
+    }
+
+    fun webext_store_new(db_path: String, error: RustError.ByReference): WebExtStoreHandle
+
+    fun webext_store_destroy(handle: WebExtStoreHandle, error: RustError.ByReference)
+
+    fun webext_store_new_interrupt_handle(handle: WebExtStoreHandle, error: RustError.ByReference): RawWebExtStoreInterruptHandle?
+    fun webext_store_interrupt(handle: RawWebExtStoreInterruptHandle, error: RustError.ByReference)
+    fun webext_store_interrupt_handle_destroy(handle: RawWebExtStoreInterruptHandle)
+
+    // The functions below take and return JSON strings.
+
+    fun webext_store_get(
+        handle: WebExtStoreHandle,
+        ext_id: String,
+        keys: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun webext_store_set(
+        handle: WebExtStoreHandle,
+        ext_id: String,
+        value: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun webext_store_remove(
+        handle: WebExtStoreHandle,
+        ext_id: String,
+        keys: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun webext_store_clear(
+        handle: WebExtStoreHandle,
+        ext_id: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun webext_store_destroy_string(p: Pointer)
+}
+
+internal typealias WebExtStoreHandle = Long
+
+internal class RawWebExtStoreInterruptHandle : PointerType()
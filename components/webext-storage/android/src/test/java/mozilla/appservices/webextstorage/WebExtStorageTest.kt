@@ -0,0 +1,53 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.webextstorage
+
+import mozilla.appservices.Megazord
+import org.json.JSONObject
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Before
+import org.junit.Rule
+import org.junit.Test
+import org.junit.rules.TemporaryFolder
+import org.junit.runner.RunWith
+import org.robolectric.RobolectricTestRunner
+import org.robolectric.annotation.Config
+
+@RunWith(RobolectricTestRunner::class)
+@Config(manifest = Config.NONE)
+class WebExtStorageTest {
+    @Rule
+    @JvmField
+    val dbFolder = TemporaryFolder()
+
+    @Before
+    fun init() {
+        Megazord.init()
+    }
+
+    @Test
+    fun testGetSetRemove() {
+        WebExtStorage(dbFolder.newFile().absolutePath).use { store ->
+            store.set("ext-id", """{"key": "value", "other": 1}""")
+            assertEquals("value", JSONObject(store.get("ext-id", "\"key\"")).getString("key"))
+
+            store.remove("ext-id", "\"key\"")
+            val values = JSONObject(store.get("ext-id", "null"))
+            assertFalse(values.has("key"))
+            assertEquals(1, values.getInt("other"))
+
+            store.clear("ext-id")
+            assertEquals(0, JSONObject(store.get("ext-id", "null")).length())
+        }
+    }
+
+    @Test(expected = InvalidJsonException::class)
+    fun testInvalidJson() {
+        WebExtStorage(dbFolder.newFile().absolutePath).use { store ->
+            store.set("ext-id", "not json")
+        }
+    }
+}
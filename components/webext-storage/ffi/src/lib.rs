@@ -0,0 +1,119 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![allow(unknown_lints)]
+#![warn(rust_2018_idioms)]
+
+use ffi_support::{
+    define_box_destructor, define_handle_map_deleter, define_string_destructor,
+    ConcurrentHandleMap, ExternError, FfiStr,
+};
+use std::sync::{Arc, Mutex};
+use webext_storage::{error::Result, store::Store};
+
+lazy_static::lazy_static! {
+    // The Sync Manager holds a weak reference to the store, so we wrap it in
+    // an `Arc<Mutex<...>>`, like the other engines.
+    pub static ref STORES: ConcurrentHandleMap<Arc<Mutex<Store>>> = ConcurrentHandleMap::new();
+}
+
+#[no_mangle]
+pub extern "C" fn webext_store_new(db_path: FfiStr<'_>, error: &mut ExternError) -> u64 {
+    log::debug!("webext_store_new");
+    STORES.insert_with_result(error, || -> Result<_> {
+        Ok(Arc::new(Mutex::new(Store::new(db_path.as_str())?)))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn webext_store_new_interrupt_handle(
+    handle: u64,
+    error: &mut ExternError,
+) -> *mut sql_support::SqlInterruptHandle {
+    log::debug!("webext_store_new_interrupt_handle");
+    STORES.call_with_output(error, handle, |store| {
+        store.lock().unwrap().interrupt_handle()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn webext_store_interrupt(
+    handle: &sql_support::SqlInterruptHandle,
+    error: &mut ExternError,
+) {
+    log::debug!("webext_store_interrupt");
+    ffi_support::call_with_output(error, || handle.interrupt())
+}
+
+/// Returns the values for `keys`, which is a JSON string. See
+/// `webext_storage::store::Store::get` for the accepted formats.
+#[no_mangle]
+pub extern "C" fn webext_store_get(
+    handle: u64,
+    ext_id: FfiStr<'_>,
+    keys: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut std::os::raw::c_char {
+    log::debug!("webext_store_get");
+    STORES.call_with_result(error, handle, |store| -> Result<_> {
+        let keys = serde_json::from_str(keys.as_str())?;
+        let result = store.lock().unwrap().get(ext_id.as_str(), keys)?;
+        Ok(result.to_string())
+    })
+}
+
+/// Sets the key-value pairs in `val`, which is a JSON object string. Returns
+/// the changes as a JSON string.
+#[no_mangle]
+pub extern "C" fn webext_store_set(
+    handle: u64,
+    ext_id: FfiStr<'_>,
+    val: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut std::os::raw::c_char {
+    log::debug!("webext_store_set");
+    STORES.call_with_result(error, handle, |store| -> Result<_> {
+        let val = serde_json::from_str(val.as_str())?;
+        let changes = store.lock().unwrap().set(ext_id.as_str(), val)?;
+        Ok(serde_json::to_string(&changes)?)
+    })
+}
+
+/// Removes the values for `keys`, which is a JSON string. Returns the changes
+/// as a JSON string.
+#[no_mangle]
+pub extern "C" fn webext_store_remove(
+    handle: u64,
+    ext_id: FfiStr<'_>,
+    keys: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut std::os::raw::c_char {
+    log::debug!("webext_store_remove");
+    STORES.call_with_result(error, handle, |store| -> Result<_> {
+        let keys = serde_json::from_str(keys.as_str())?;
+        let changes = store.lock().unwrap().remove(ext_id.as_str(), keys)?;
+        Ok(serde_json::to_string(&changes)?)
+    })
+}
+
+/// Removes all values for the extension. Returns the changes as a JSON string.
+#[no_mangle]
+pub extern "C" fn webext_store_clear(
+    handle: u64,
+    ext_id: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut std::os::raw::c_char {
+    log::debug!("webext_store_clear");
+    STORES.call_with_result(error, handle, |store| -> Result<_> {
+        let changes = store.lock().unwrap().clear(ext_id.as_str())?;
+        Ok(serde_json::to_string(&changes)?)
+    })
+}
+
+define_string_destructor!(webext_store_destroy_string);
+define_handle_map_deleter!(STORES, webext_store_destroy);
+define_box_destructor!(
+    sql_support::SqlInterruptHandle,
+    webext_store_interrupt_handle_destroy
+);
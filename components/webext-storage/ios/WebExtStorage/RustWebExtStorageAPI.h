@@ -0,0 +1,62 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+#pragma once
+
+#include <stdint.h>
+
+typedef uint64_t WebExtStoreHandle;
+
+typedef enum WebExtStorageErrorCode {
+    WebExtStorage_Panic = -1,
+    WebExtStorage_NoError = 0,
+    WebExtStorage_UnexpectedError = -2,
+    WebExtStorage_QuotaExceeded = 1,
+    WebExtStorage_Interrupted = 2,
+    WebExtStorage_InvalidJson = 3,
+} WebExtStorageErrorCode;
+
+typedef struct WebExtStorageRustError {
+    WebExtStorageErrorCode code;
+    char *_Nullable message;
+} WebExtStorageRustError;
+
+typedef struct RawWebExtStoreInterruptHandle RawWebExtStoreInterruptHandle;
+
+WebExtStoreHandle webext_store_new(const char *_Nonnull db_path,
+                                   WebExtStorageRustError *_Nonnull out_err);
+
+RawWebExtStoreInterruptHandle *_Nullable
+webext_store_new_interrupt_handle(WebExtStoreHandle handle,
+                                  WebExtStorageRustError *_Nonnull out_err);
+
+void webext_store_interrupt(RawWebExtStoreInterruptHandle *_Nonnull interrupt_handle,
+                            WebExtStorageRustError *_Nonnull out_err);
+
+// The functions below take and return JSON strings.
+
+char *_Nullable webext_store_get(WebExtStoreHandle handle,
+                                 const char *_Nonnull ext_id,
+                                 const char *_Nonnull keys,
+                                 WebExtStorageRustError *_Nonnull out_err);
+
+char *_Nullable webext_store_set(WebExtStoreHandle handle,
+                                 const char *_Nonnull ext_id,
+                                 const char *_Nonnull val,
+                                 WebExtStorageRustError *_Nonnull out_err);
+
+char *_Nullable webext_store_remove(WebExtStoreHandle handle,
+                                    const char *_Nonnull ext_id,
+                                    const char *_Nonnull keys,
+                                    WebExtStorageRustError *_Nonnull out_err);
+
+char *_Nullable webext_store_clear(WebExtStoreHandle handle,
+                                   const char *_Nonnull ext_id,
+                                   WebExtStorageRustError *_Nonnull out_err);
+
+void webext_store_destroy_string(const char *_Nonnull s);
+
+void webext_store_destroy(WebExtStoreHandle handle,
+                          WebExtStorageRustError *_Nonnull out_err);
+
+void webext_store_interrupt_handle_destroy(RawWebExtStoreInterruptHandle *_Nonnull handle);
@@ -0,0 +1,111 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+import Foundation
+
+/**
+ * Provides access to the `storage.sync` area for WebExtensions. Keys and
+ * values are passed as JSON strings, in the same formats that the
+ * `storage.sync` WebExtension API accepts.
+ */
+open class WebExtStorage {
+    private let handle: WebExtStoreHandle
+    private let interruptHandle: OpaquePointer
+    private let queue = DispatchQueue(label: "com.mozilla.webext-storage")
+
+    /**
+     * Initialize a WebExtStorage
+     *
+     * - Parameter path: an absolute path to a file that will be used for the internal database.
+     *
+     * - Throws: `WebExtStorageError` if initializing the database failed.
+     */
+    public init(path: String) throws {
+        let handle = try WebExtStorageError.unwrap { error in
+            webext_store_new(path, error)
+        }
+        self.handle = handle
+        do {
+            interruptHandle = try WebExtStorageError.unwrap { error in
+                webext_store_new_interrupt_handle(handle, error)
+            }
+        } catch let e {
+            WebExtStorageError.unwrapOrLog { error in
+                webext_store_destroy(handle, error)
+            }
+            throw e
+        }
+    }
+
+    deinit {
+        webext_store_interrupt_handle_destroy(interruptHandle)
+        WebExtStorageError.unwrapOrLog { error in
+            webext_store_destroy(self.handle, error)
+        }
+    }
+
+    /**
+     * Interrupt an in-progress operation, which will then throw
+     * `WebExtStorageError.interrupted`. This is safe to call from any thread.
+     */
+    open func interrupt() {
+        WebExtStorageError.unwrapOrLog { error in
+            webext_store_interrupt(self.interruptHandle, error)
+        }
+    }
+
+    /**
+     * Returns the stored values for an extension, as a JSON object string.
+     *
+     * - Parameter keys: A JSON string with a key, an array of keys, an object
+     *                   with default values, or `null` for all keys.
+     */
+    open func get(extId: String, keys: String) throws -> String {
+        return try queue.sync {
+            let json = try WebExtStorageError.unwrap { error in
+                webext_store_get(self.handle, extId, keys, error)
+            }
+            return String(freeingWebExtStorageString: json)
+        }
+    }
+
+    /**
+     * Stores the key-value pairs in `value`, which is a JSON object string,
+     * and returns the changes as a JSON string.
+     */
+    open func set(extId: String, value: String) throws -> String {
+        return try queue.sync {
+            let json = try WebExtStorageError.unwrap { error in
+                webext_store_set(self.handle, extId, value, error)
+            }
+            return String(freeingWebExtStorageString: json)
+        }
+    }
+
+    /**
+     * Removes the values for `keys`, which is a JSON string with a key or an
+     * array of keys, and returns the changes as a JSON string.
+     */
+    open func remove(extId: String, keys: String) throws -> String {
+        return try queue.sync {
+            let json = try WebExtStorageError.unwrap { error in
+                webext_store_remove(self.handle, extId, keys, error)
+            }
+            return String(freeingWebExtStorageString: json)
+        }
+    }
+
+    /**
+     * Removes all values for an extension, and returns the changes as a JSON
+     * string.
+     */
+    open func clear(extId: String) throws -> String {
+        return try queue.sync {
+            let json = try WebExtStorageError.unwrap { error in
+                webext_store_clear(self.handle, extId, error)
+            }
+            return String(freeingWebExtStorageString: json)
+        }
+    }
+}
@@ -0,0 +1,103 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+import Foundation
+import os.log
+
+/// Indicates an error occurred while calling into the WebExtension storage layer
+public enum WebExtStorageError: LocalizedError {
+    /// This is a catch-all error code used for errors not yet exposed to consumers,
+    /// typically since it doesn't seem like there's a sane way for them to be handled.
+    case unexpected(message: String)
+
+    /// The rust code implementing the storage paniced. This always indicates a bug.
+    case panic(message: String)
+
+    /// The extension exceeded one of the `storage.sync` quotas.
+    case quotaExceeded(message: String)
+
+    /// The requested operation failed because it was interrupted.
+    case interrupted(message: String)
+
+    /// The keys or value passed to the store aren't valid JSON.
+    case invalidJson(message: String)
+
+    /// Our implementation of the localizedError protocol -- (This shows up in Sentry)
+    public var errorDescription: String? {
+        switch self {
+        case let .unexpected(message):
+            return "WebExtStorageError.unexpected: \(message)"
+        case let .panic(message):
+            return "WebExtStorageError.panic: \(message)"
+        case let .quotaExceeded(message):
+            return "WebExtStorageError.quotaExceeded: \(message)"
+        case let .interrupted(message):
+            return "WebExtStorageError.interrupted: \(message)"
+        case let .invalidJson(message):
+            return "WebExtStorageError.invalidJson: \(message)"
+        }
+    }
+
+    // The name is attempting to indicate that we free rustError.message if it
+    // existed, and that it's a very bad idea to touch it after you call this
+    // function
+    static func fromConsuming(_ rustError: WebExtStorageRustError) -> WebExtStorageError? {
+        let message = rustError.message
+
+        switch rustError.code {
+        case WebExtStorage_NoError:
+            return nil
+
+        case WebExtStorage_Panic:
+            return .panic(message: String(freeingWebExtStorageString: message!))
+
+        case WebExtStorage_QuotaExceeded:
+            return .quotaExceeded(message: String(freeingWebExtStorageString: message!))
+
+        case WebExtStorage_Interrupted:
+            return .interrupted(message: String(freeingWebExtStorageString: message!))
+
+        case WebExtStorage_InvalidJson:
+            return .invalidJson(message: String(freeingWebExtStorageString: message!))
+
+        default:
+            return .unexpected(message: String(freeingWebExtStorageString: message!))
+        }
+    }
+
+    @discardableResult
+    static func tryUnwrap<T>(_ callback: (UnsafeMutablePointer<WebExtStorageRustError>) throws -> T?) throws -> T? {
+        var err = WebExtStorageRustError(code: WebExtStorage_NoError, message: nil)
+        let returnedVal = try callback(&err)
+        if let storageErr = WebExtStorageError.fromConsuming(err) {
+            throw storageErr
+        }
+        guard let result = returnedVal else {
+            return nil
+        }
+        return result
+    }
+
+    @discardableResult
+    static func unwrap<T>(_ callback: (UnsafeMutablePointer<WebExtStorageRustError>) throws -> T?) throws -> T {
+        guard let result = try WebExtStorageError.tryUnwrap(callback) else {
+            throw ResultError.empty
+        }
+        return result
+    }
+
+    // Same as `tryUnwrap`, but instead of erroring, just logs. Useful for cases like destructors where we
+    // cannot throw.
+    @discardableResult
+    static func unwrapOrLog<T>(_ callback: (UnsafeMutablePointer<WebExtStorageRustError>) throws -> T?) -> T? {
+        do {
+            let result = try WebExtStorageError.tryUnwrap(callback)
+            return result
+        } catch let e {
+            // Can't log what the error is without jumping through hoops apparently, oh well...
+            os_log("Hit WebExtension storage error when throwing is impossible %{public}@", type: .error, "\(e)")
+            return nil
+        }
+    }
+}
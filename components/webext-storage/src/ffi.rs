@@ -0,0 +1,62 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// This module implement the traits that make the FFI code easier to manage.
+
+use crate::error::{Error, ErrorKind};
+use ffi_support::{ErrorCode, ExternError};
+
+pub mod error_codes {
+    /// An unexpected error occurred which likely cannot be meaningfully handled
+    /// by the application.
+    pub const UNEXPECTED: i32 = -2;
+
+    // Note: -1 and 0 (panic and success) codes are reserved by the ffi-support library
+
+    /// The extension has exceeded one of the `storage.sync` quotas.
+    pub const QUOTA_EXCEEDED: i32 = 1;
+
+    /// An operation has been interrupted.
+    pub const INTERRUPTED: i32 = 2;
+
+    /// The value passed to `set`, `get` or `remove` isn't valid JSON.
+    pub const INVALID_JSON: i32 = 3;
+}
+
+fn get_code(err: &Error) -> ErrorCode {
+    match err.kind() {
+        ErrorKind::QuotaError(reason) => {
+            log::warn!("Quota exceeded: {:?}", reason);
+            ErrorCode::new(error_codes::QUOTA_EXCEEDED)
+        }
+
+        ErrorKind::SqlError(rusqlite::Error::SqliteFailure(err, _))
+            if err.code == rusqlite::ErrorCode::OperationInterrupted =>
+        {
+            log::warn!("Operation interrupted (SQL)");
+            ErrorCode::new(error_codes::INTERRUPTED)
+        }
+
+        ErrorKind::InterruptedError(_) => {
+            log::warn!("Operation interrupted (Outside SQL)");
+            ErrorCode::new(error_codes::INTERRUPTED)
+        }
+
+        ErrorKind::JsonError(e) => {
+            log::warn!("Invalid JSON: {}", e);
+            ErrorCode::new(error_codes::INVALID_JSON)
+        }
+
+        err => {
+            log::error!("Unexpected error: {:?}", err);
+            ErrorCode::new(error_codes::UNEXPECTED)
+        }
+    }
+}
+
+impl From<Error> for ExternError {
+    fn from(e: Error) -> ExternError {
+        ExternError::new_error(get_code(&e), e.to_string())
+    }
+}
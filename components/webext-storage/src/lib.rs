@@ -8,6 +8,7 @@
 mod api;
 mod db;
 pub mod error;
+mod ffi;
 mod migration;
 mod schema;
 pub mod store;
@@ -16,7 +17,7 @@ mod sync;
 pub use migration::MigrationInfo;
 
 // We publish some constants from non-public modules.
-pub use sync::STORAGE_VERSION;
+pub use sync::{COLLECTION_NAME, STORAGE_VERSION};
 
 pub use api::SYNC_MAX_ITEMS;
 pub use api::SYNC_QUOTA_BYTES;
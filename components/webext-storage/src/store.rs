@@ -117,6 +117,12 @@ impl Store {
         sync::BridgedEngine::new(&self.db)
     }
 
+    /// Returns a `sync15_traits::Store` for this store, which can be synced
+    /// along with other collections by the Sync Manager.
+    pub fn sync_store(&self) -> sync::StorageSyncStore<'_> {
+        sync::StorageSyncStore::new(&self.db)
+    }
+
     /// Closes the store and its database connection. See the docs for
     /// `StorageDb::close` for more details on when this can fail.
     pub fn close(self) -> result::Result<(), (Store, Error)> {
@@ -12,8 +12,8 @@ use crate::schema;
 use crate::sync::incoming::{apply_actions, get_incoming, plan_incoming, stage_incoming};
 use crate::sync::outgoing::{get_outgoing, record_uploaded, stage_outgoing};
 
-const LAST_SYNC_META_KEY: &str = "last_sync_time";
-const SYNC_ID_META_KEY: &str = "sync_id";
+pub(super) const LAST_SYNC_META_KEY: &str = "last_sync_time";
+pub(super) const SYNC_ID_META_KEY: &str = "sync_id";
 
 /// A bridged engine implements all the methods needed to make the
 /// `storage.sync` store work with Desktop's Sync implementation.
@@ -28,15 +28,26 @@ impl<'a> BridgedEngine<'a> {
     pub fn new(db: &'a StorageDb) -> Self {
         BridgedEngine { db }
     }
+}
 
-    fn do_reset(&self, tx: &Transaction<'_>) -> Result<()> {
-        tx.execute_batch(
-            "DELETE FROM storage_sync_mirror;
-             UPDATE storage_sync_data SET sync_change_counter = 1;",
-        )?;
-        delete_meta(tx, LAST_SYNC_META_KEY)?;
-        Ok(())
-    }
+/// Forgets everything we know about the server, so that the next sync is a
+/// "first sync". Local data is kept, and marked as needing to be uploaded.
+pub(super) fn do_reset(tx: &Transaction<'_>) -> Result<()> {
+    tx.execute_batch(
+        "DELETE FROM storage_sync_mirror;
+         UPDATE storage_sync_data SET sync_change_counter = 1;",
+    )?;
+    delete_meta(tx, LAST_SYNC_META_KEY)?;
+    Ok(())
+}
+
+/// Deletes all local and synced data, along with all sync metadata.
+pub(super) fn do_wipe(tx: &Transaction<'_>) -> Result<()> {
+    // We assume the meta table is only used by sync.
+    tx.execute_batch(
+        "DELETE FROM storage_sync_data; DELETE FROM storage_sync_mirror; DELETE FROM meta;",
+    )?;
+    Ok(())
 }
 
 impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
@@ -58,7 +69,7 @@ impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
     fn reset_sync_id(&self) -> Result<String> {
         let tx = self.db.unchecked_transaction()?;
         let new_id = SyncGuid::random().to_string();
-        self.do_reset(&tx)?;
+        do_reset(&tx)?;
         put_meta(self.db, SYNC_ID_META_KEY, &new_id)?;
         tx.commit()?;
         Ok(new_id)
@@ -70,7 +81,7 @@ impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
             Some(current) if current == sync_id => current,
             _ => {
                 let tx = self.db.unchecked_transaction()?;
-                self.do_reset(&tx)?;
+                do_reset(&tx)?;
                 let result = sync_id.to_string();
                 put_meta(self.db, SYNC_ID_META_KEY, &result)?;
                 tx.commit()?;
@@ -135,7 +146,7 @@ impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
 
     fn reset(&self) -> Result<()> {
         let tx = self.db.unchecked_transaction()?;
-        self.do_reset(&tx)?;
+        do_reset(&tx)?;
         delete_meta(&tx, SYNC_ID_META_KEY)?;
         tx.commit()?;
         Ok(())
@@ -143,10 +154,7 @@ impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
 
     fn wipe(&self) -> Result<()> {
         let tx = self.db.unchecked_transaction()?;
-        // We assume the meta table is only used by sync.
-        tx.execute_batch(
-            "DELETE FROM storage_sync_data; DELETE FROM storage_sync_mirror; DELETE FROM meta;",
-        )?;
+        do_wipe(&tx)?;
         tx.commit()?;
         Ok(())
     }
@@ -101,7 +101,7 @@ pub fn stage_incoming(
 /// Details about an incoming item.
 #[derive(Debug, PartialEq)]
 pub struct IncomingItem {
-    guid: SyncGuid,
+    pub(super) guid: SyncGuid,
     ext_id: String,
 }
 
@@ -5,6 +5,7 @@
 mod bridge;
 mod incoming;
 mod outgoing;
+mod store;
 
 #[cfg(test)]
 mod sync_tests;
@@ -18,11 +19,15 @@ use sync_guid::Guid as SyncGuid;
 
 pub use bridge::BridgedEngine;
 use incoming::IncomingAction;
+pub use store::StorageSyncStore;
 
 type JsonMap = serde_json::Map<String, serde_json::Value>;
 
 pub const STORAGE_VERSION: usize = 1;
 
+/// The name of the collection on the Sync server.
+pub const COLLECTION_NAME: &str = "storage-sync-v2";
+
 /// For use with `#[serde(skip_serializing_if = )]`
 #[inline]
 pub fn is_default<T: PartialEq + Default>(v: &T) -> bool {
@@ -0,0 +1,361 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::Cow;
+
+use interrupt_support::Interruptee;
+use rusqlite::Transaction;
+use sync15_traits::{
+    telemetry, CollSyncIds, CollectionRequest, DryRun, Guid, IncomingChangeset, OutgoingChangeset,
+    PlannedAction, RecordPlan, ServerTimestamp, Store, StoreSyncAssociation,
+};
+
+use super::bridge::{do_reset, do_wipe, LAST_SYNC_META_KEY, SYNC_ID_META_KEY};
+use super::incoming::{
+    apply_actions, get_incoming, plan_incoming, stage_incoming, IncomingAction, IncomingItem,
+};
+use super::outgoing::{get_outgoing, record_uploaded, stage_outgoing};
+use super::COLLECTION_NAME;
+use crate::db::{delete_meta, get_meta, put_meta, StorageDb};
+use crate::schema;
+
+/// `BridgedEngine` only tracks the collection sync ID, since Desktop manages
+/// the global sync ID itself. `sync15_traits::Store` needs both, so we store
+/// the global one separately.
+const GLOBAL_SYNC_ID_META_KEY: &str = "global_sync_id";
+
+/// A `storage.sync` store that can be synced with `sync15::sync_multiple`,
+/// like the stores for the other collections. This is what the Sync Manager
+/// uses on mobile; Desktop uses the `BridgedEngine` instead. Both share the
+/// same tables and sync metadata.
+pub struct StorageSyncStore<'a> {
+    db: &'a StorageDb,
+}
+
+impl<'a> StorageSyncStore<'a> {
+    pub fn new(db: &'a StorageDb) -> Self {
+        StorageSyncStore { db }
+    }
+
+    /// Stages the incoming records and works out what to do with each one.
+    /// Shared by real and dry-run syncs.
+    fn stage_and_plan_incoming(
+        &self,
+        tx: &Transaction<'_>,
+        inbound: IncomingChangeset,
+        telem: &mut telemetry::Engine,
+        signal: &dyn Interruptee,
+    ) -> anyhow::Result<Vec<(IncomingItem, IncomingAction)>> {
+        let payloads = inbound
+            .changes
+            .into_iter()
+            .map(|(payload, _)| payload)
+            .collect();
+        stage_incoming(tx, payloads, signal)?;
+        let actions = get_incoming(tx)?
+            .into_iter()
+            .map(|(item, state)| (item, plan_incoming(state)))
+            .collect::<Vec<_>>();
+        let mut incoming_telemetry = telemetry::EngineIncoming::new();
+        for (_, action) in &actions {
+            match action {
+                IncomingAction::TakeRemote { .. } | IncomingAction::DeleteLocally { .. } => {
+                    incoming_telemetry.applied(1)
+                }
+                IncomingAction::Merge { .. } => incoming_telemetry.reconciled(1),
+                // Records that we already have aren't applied.
+                IncomingAction::Same => {}
+            }
+        }
+        telem.incoming(incoming_telemetry);
+        Ok(actions)
+    }
+}
+
+impl<'a> Store for StorageSyncStore<'a> {
+    fn collection_name(&self) -> Cow<'static, str> {
+        COLLECTION_NAME.into()
+    }
+
+    fn apply_incoming(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<OutgoingChangeset> {
+        assert_eq!(inbound.len(), 1, "only requested one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        let signal = self.db.begin_interrupt_scope();
+
+        schema::create_empty_sync_temp_tables(&self.db)?;
+        let timestamp = inbound.timestamp;
+        let tx = self.db.unchecked_transaction()?;
+        let actions = self.stage_and_plan_incoming(&tx, inbound, telem, &signal)?;
+        apply_actions(&tx, actions, &signal)?;
+        stage_outgoing(&tx)?;
+        tx.commit()?;
+
+        let mut outgoing = OutgoingChangeset::new(COLLECTION_NAME, timestamp);
+        outgoing.changes = get_outgoing(&self.db, &signal)?;
+        Ok(outgoing)
+    }
+
+    fn apply_incoming_dry_run(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<DryRun> {
+        assert_eq!(inbound.len(), 1, "only requested one item");
+        let inbound = inbound.into_iter().next().unwrap();
+        let signal = self.db.begin_interrupt_scope();
+
+        schema::create_empty_sync_temp_tables(&self.db)?;
+        let timestamp = inbound.timestamp;
+        let tx = self.db.unchecked_transaction()?;
+        let actions = self.stage_and_plan_incoming(&tx, inbound, telem, &signal)?;
+        let incoming = actions
+            .iter()
+            .filter_map(|(item, action)| {
+                let action = match action {
+                    IncomingAction::TakeRemote { .. } => PlannedAction::Apply,
+                    IncomingAction::DeleteLocally { .. } => PlannedAction::Delete,
+                    IncomingAction::Merge { .. } => PlannedAction::Conflict,
+                    IncomingAction::Same => return None,
+                };
+                Some(RecordPlan::new(item.guid.clone(), action))
+            })
+            .collect();
+        // Apply the actions so that we can see what we'd upload, then throw
+        // it all away, including what we staged in the temp tables.
+        apply_actions(&tx, actions, &signal)?;
+        stage_outgoing(&tx)?;
+        let mut outgoing = OutgoingChangeset::new(COLLECTION_NAME, timestamp);
+        outgoing.changes = get_outgoing(&tx, &signal)?;
+        tx.rollback()?;
+        Ok(DryRun { incoming, outgoing })
+    }
+
+    fn sync_finished(
+        &self,
+        new_timestamp: ServerTimestamp,
+        records_synced: Vec<Guid>,
+    ) -> anyhow::Result<()> {
+        let signal = self.db.begin_interrupt_scope();
+        let tx = self.db.unchecked_transaction()?;
+        record_uploaded(&tx, &records_synced, &signal)?;
+        put_meta(&tx, LAST_SYNC_META_KEY, &new_timestamp.as_millis())?;
+        tx.commit()?;
+        schema::create_empty_sync_temp_tables(&self.db)?;
+        Ok(())
+    }
+
+    fn get_collection_requests(
+        &self,
+        server_timestamp: ServerTimestamp,
+    ) -> anyhow::Result<Vec<CollectionRequest>> {
+        let since = ServerTimestamp(get_meta(self.db, LAST_SYNC_META_KEY)?.unwrap_or_default());
+        Ok(if since == server_timestamp {
+            vec![]
+        } else {
+            vec![CollectionRequest::new(COLLECTION_NAME)
+                .full()
+                .newer_than(since)]
+        })
+    }
+
+    fn get_sync_assoc(&self) -> anyhow::Result<StoreSyncAssociation> {
+        let global = get_meta(self.db, GLOBAL_SYNC_ID_META_KEY)?;
+        let coll = get_meta(self.db, SYNC_ID_META_KEY)?;
+        Ok(if let (Some(global), Some(coll)) = (global, coll) {
+            StoreSyncAssociation::Connected(CollSyncIds { global, coll })
+        } else {
+            StoreSyncAssociation::Disconnected
+        })
+    }
+
+    fn reset(&self, assoc: &StoreSyncAssociation) -> anyhow::Result<()> {
+        let tx = self.db.unchecked_transaction()?;
+        do_reset(&tx)?;
+        match assoc {
+            StoreSyncAssociation::Disconnected => {
+                delete_meta(&tx, GLOBAL_SYNC_ID_META_KEY)?;
+                delete_meta(&tx, SYNC_ID_META_KEY)?;
+            }
+            StoreSyncAssociation::Connected(ids) => {
+                put_meta(&tx, GLOBAL_SYNC_ID_META_KEY, &ids.global)?;
+                put_meta(&tx, SYNC_ID_META_KEY, &ids.coll)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn wipe(&self) -> anyhow::Result<()> {
+        let tx = self.db.unchecked_transaction()?;
+        do_wipe(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{get, set};
+    use crate::db::test::new_mem_db;
+    use crate::sync::{BridgedEngine, Record};
+    use serde_json::json;
+    use sync15_traits::Payload;
+
+    /// Returns the `applied` and `reconciled` counts recorded in `telem`.
+    fn incoming_counts(telem: telemetry::Engine) -> (u64, u64) {
+        let telem = serde_json::to_value(&telem).unwrap();
+        let count = |name: &str| telem["incoming"][name].as_u64().unwrap_or(0);
+        (count("applied"), count("reconciled"))
+    }
+
+    #[test]
+    fn test_sync_round_trip() -> anyhow::Result<()> {
+        let db = new_mem_db();
+        let store = StorageSyncStore::new(&db);
+        let tx = db.unchecked_transaction()?;
+        set(&tx, "ext-local", json!({"key-local": "value"}))?;
+        tx.commit()?;
+
+        let mut incoming = IncomingChangeset::new(COLLECTION_NAME, ServerTimestamp(1000));
+        incoming.changes.push((
+            Payload::from_record(Record {
+                guid: Guid::from("guid-remote"),
+                ext_id: "ext-remote".to_string(),
+                data: Some(json!({"key-remote": "value"}).to_string()),
+            })?,
+            ServerTimestamp(1000),
+        ));
+        let mut telem = telemetry::Engine::new(COLLECTION_NAME);
+        let outgoing = store.apply_incoming(vec![incoming.clone()], &mut telem)?;
+        assert_eq!(incoming_counts(telem), (1, 0));
+
+        // The incoming record was applied...
+        assert_eq!(
+            get(&db, "ext-remote", json!(null))?,
+            json!({"key-remote": "value"})
+        );
+        // ...and only our local change is uploaded.
+        assert_eq!(outgoing.changes.len(), 1);
+        let record = outgoing.changes[0].clone().into_record::<Record>()?;
+        assert_eq!(record.ext_id, "ext-local");
+
+        let uploaded = outgoing
+            .changes
+            .iter()
+            .map(|p| p.id.clone())
+            .collect::<Vec<_>>();
+        store.sync_finished(ServerTimestamp(2000), uploaded)?;
+
+        // Seeing the same record again doesn't apply anything.
+        let mut telem = telemetry::Engine::new(COLLECTION_NAME);
+        store.apply_incoming(vec![incoming], &mut telem)?;
+        assert_eq!(incoming_counts(telem), (0, 0));
+        assert_eq!(
+            store.get_collection_requests(ServerTimestamp(2000))?,
+            vec![]
+        );
+        assert_eq!(
+            store.get_collection_requests(ServerTimestamp(3000))?,
+            vec![CollectionRequest::new(COLLECTION_NAME)
+                .full()
+                .newer_than(ServerTimestamp(2000))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_incoming_dry_run() -> anyhow::Result<()> {
+        let db = new_mem_db();
+        let store = StorageSyncStore::new(&db);
+        let tx = db.unchecked_transaction()?;
+        set(&tx, "ext-local", json!({"key-local": "value"}))?;
+        set(&tx, "ext-both", json!({"key-local": "value"}))?;
+        tx.commit()?;
+
+        let mut incoming = IncomingChangeset::new(COLLECTION_NAME, ServerTimestamp(1000));
+        for (guid, ext_id) in &[("guid-remote", "ext-remote"), ("guid-both", "ext-both")] {
+            incoming.changes.push((
+                Payload::from_record(Record {
+                    guid: Guid::from(*guid),
+                    ext_id: ext_id.to_string(),
+                    data: Some(json!({"key-remote": "value"}).to_string()),
+                })?,
+                ServerTimestamp(1000),
+            ));
+        }
+        let mut telem = telemetry::Engine::new(COLLECTION_NAME);
+        let dry_run = store.apply_incoming_dry_run(vec![incoming], &mut telem)?;
+        assert_eq!(incoming_counts(telem), (1, 1));
+        let mut plans = dry_run.incoming;
+        plans.sort_by(|a, b| a.id.as_str().cmp(b.id.as_str()));
+        assert_eq!(
+            plans,
+            vec![
+                RecordPlan::new("guid-both", PlannedAction::Conflict),
+                RecordPlan::new("guid-remote", PlannedAction::Apply),
+            ]
+        );
+        // We'd upload our local record, and the merged one.
+        let mut uploads = dry_run
+            .outgoing
+            .changes
+            .into_iter()
+            .map(|p| Ok(p.into_record::<Record>()?.ext_id))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        uploads.sort();
+        assert_eq!(uploads, vec!["ext-both", "ext-local"]);
+
+        // But nothing changed locally...
+        assert_eq!(get(&db, "ext-remote", json!(null))?, json!({}));
+        assert_eq!(
+            get(&db, "ext-both", json!(null))?,
+            json!({"key-local": "value"})
+        );
+        // ...and a real sync still sees every incoming record.
+        let mut incoming = IncomingChangeset::new(COLLECTION_NAME, ServerTimestamp(1000));
+        incoming.changes.push((
+            Payload::from_record(Record {
+                guid: Guid::from("guid-remote"),
+                ext_id: "ext-remote".to_string(),
+                data: Some(json!({"key-remote": "value"}).to_string()),
+            })?,
+            ServerTimestamp(1000),
+        ));
+        let mut telem = telemetry::Engine::new(COLLECTION_NAME);
+        store.apply_incoming(vec![incoming], &mut telem)?;
+        assert_eq!(incoming_counts(telem), (1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_assoc() -> anyhow::Result<()> {
+        let db = new_mem_db();
+        let store = StorageSyncStore::new(&db);
+        assert_eq!(store.get_sync_assoc()?, StoreSyncAssociation::Disconnected);
+
+        let ids = CollSyncIds {
+            global: Guid::random(),
+            coll: Guid::random(),
+        };
+        store.reset(&StoreSyncAssociation::Connected(ids.clone()))?;
+        assert_eq!(
+            store.get_sync_assoc()?,
+            StoreSyncAssociation::Connected(ids.clone())
+        );
+        // The bridged engine should see the same collection sync ID.
+        assert_eq!(
+            sync15_traits::BridgedEngine::sync_id(&BridgedEngine::new(&db))?,
+            Some(ids.coll.to_string())
+        );
+
+        store.wipe()?;
+        assert_eq!(store.get_sync_assoc()?, StoreSyncAssociation::Disconnected);
+        Ok(())
+    }
+}
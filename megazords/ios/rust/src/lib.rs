@@ -10,3 +10,4 @@ pub use logins_ffi;
 pub use places_ffi;
 pub use rc_log_ffi;
 pub use viaduct_reqwest;
+pub use webext_storage_ffi;
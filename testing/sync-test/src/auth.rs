@@ -1,14 +1,17 @@
 /* Any copyright is dedicated to the Public Domain.
 http://creativecommons.org/publicdomain/zero/1.0/ */
 
-use crate::{restmail, Opts};
+use crate::{restmail, server, Opts};
 use anyhow::Result;
 use fxa_client::{self, auth, Config as FxaConfig, FirefoxAccount};
 use logins::PasswordEngine;
+use places::PlacesApi;
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use sync15::{KeyBundle, Sync15StorageClientInit};
+use sync_guid::Guid;
 use tabs::TabsEngine;
 use url::Url;
 use viaduct::Request;
@@ -213,12 +216,55 @@ impl Drop for TestAccount {
     }
 }
 
+/// A Sync account that only exists on the mock server. Unlike a
+/// `TestAccount`, this doesn't need a real FxA stack: the mock tokenserver
+/// accepts the account's uid as its access token.
+#[derive(Debug)]
+pub struct MockAccount {
+    pub uid: String,
+    pub root_sync_key: KeyBundle,
+}
+
+impl MockAccount {
+    pub fn new_random() -> Result<Arc<MockAccount>> {
+        let uid = Guid::random().to_string();
+        log::info!("Creating mock account {}", uid);
+        Ok(Arc::new(MockAccount {
+            uid,
+            root_sync_key: KeyBundle::new_random()?,
+        }))
+    }
+
+    fn client_init(&self) -> Result<Sync15StorageClientInit> {
+        Ok(Sync15StorageClientInit {
+            key_id: "1-mock".into(),
+            access_token: self.uid.clone(),
+            tokenserver_url: Url::parse(server::TOKENSERVER_URL)?,
+        })
+    }
+}
+
+/// How a test client signs in to Sync.
+pub enum ClientAuth {
+    /// A real Firefox Account, on the FxA stack passed to `--fxa-stack`.
+    Fxa {
+        fxa: fxa_client::FirefoxAccount,
+        test_acct: Arc<TestAccount>,
+    },
+    /// A mock account, on the mock server. We make up a device ID, since
+    /// there's no FxA device.
+    Mock {
+        account: Arc<MockAccount>,
+        device_id: String,
+    },
+}
+
 pub struct TestClient {
-    pub fxa: fxa_client::FirefoxAccount,
-    pub test_acct: Arc<TestAccount>,
+    pub auth: ClientAuth,
     // XXX do this more generically...
     pub logins_engine: PasswordEngine,
     pub tabs_engine: TabsEngine,
+    pub places_api: Arc<PlacesApi>,
 }
 
 impl TestClient {
@@ -238,15 +284,46 @@ impl TestClient {
 
         fxa.initialize_device("Testing Device", fxa_client::device::Type::Desktop, &[])?;
 
-        Ok(Self {
+        Self::with_auth(ClientAuth::Fxa {
             fxa,
             test_acct: acct,
+        })
+    }
+
+    pub fn new_mock(account: Arc<MockAccount>) -> Result<Self> {
+        Self::with_auth(ClientAuth::Mock {
+            account,
+            device_id: Guid::random().to_string(),
+        })
+    }
+
+    fn with_auth(auth: ClientAuth) -> Result<Self> {
+        Ok(Self {
+            auth,
             logins_engine: PasswordEngine::new_in_memory(None)?,
-            tabs_engine: TabsEngine::new(),
+            tabs_engine: TabsEngine::new_in_memory()?,
+            places_api: new_places_api()?,
+        })
+    }
+
+    pub fn device_id(&self) -> Result<String> {
+        Ok(match &self.auth {
+            ClientAuth::Fxa { fxa, .. } => fxa.get_current_device_id()?,
+            ClientAuth::Mock { device_id, .. } => device_id.clone(),
         })
     }
 
     pub fn data_for_sync(&mut self) -> Result<(Sync15StorageClientInit, KeyBundle, String)> {
+        let (fxa, test_acct) = match &mut self.auth {
+            ClientAuth::Fxa { fxa, test_acct } => (fxa, test_acct),
+            ClientAuth::Mock { account, device_id } => {
+                return Ok((
+                    account.client_init()?,
+                    account.root_sync_key.clone(),
+                    device_id.clone(),
+                ));
+            }
+        };
         // Allow overriding it via environment
         let tokenserver_url = option_env!("TOKENSERVER_URL")
             .map(|env_var| {
@@ -255,8 +332,8 @@ impl TestClient {
                 Ok(Url::parse(env_var)
                     .expect("Failed to parse TOKENSERVER_URL environment variable!"))
             })
-            .unwrap_or_else(|| self.test_acct.cfg.token_server_endpoint_url())?;
-        let token = self.fxa.get_access_token(SYNC_SCOPE, None)?;
+            .unwrap_or_else(|| test_acct.cfg.token_server_endpoint_url())?;
+        let token = fxa.get_access_token(SYNC_SCOPE, None)?;
 
         let key = token.key.as_ref().unwrap();
 
@@ -268,7 +345,7 @@ impl TestClient {
 
         let root_sync_key = KeyBundle::from_ksync_base64(&key.k)?;
 
-        let device_id = self.fxa.get_current_device_id()?;
+        let device_id = fxa.get_current_device_id()?;
 
         Ok((client_init, root_sync_key, device_id))
     }
@@ -283,11 +360,23 @@ impl TestClient {
     pub fn fully_reset_local_db(&mut self) -> Result<()> {
         // Not great...
         self.logins_engine = PasswordEngine::new_in_memory(None)?;
-        self.tabs_engine = TabsEngine::new();
+        self.tabs_engine = TabsEngine::new_in_memory()?;
+        self.places_api = new_places_api()?;
         Ok(())
     }
 }
 
+// Each client needs its own in-memory places database, so we give them
+// unique names.
+fn new_places_api() -> Result<Arc<PlacesApi>> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "sync-test-places-{}",
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    );
+    Ok(PlacesApi::new_memory(&name)?)
+}
+
 // Wipes the server using the first client that can manage it.
 // We do this at the end of each test to avoid creating N accounts for N tests,
 // and just creating 1 account per file containing tests.
@@ -308,7 +397,6 @@ pub fn cleanup_server(clients: Vec<&mut TestClient>) -> Result<()> {
 }
 
 pub struct TestUser {
-    pub account: Arc<TestAccount>,
     pub clients: Vec<TestClient>,
 }
 
@@ -323,10 +411,23 @@ impl TestUser {
             log::info!("Creating test client {}", c);
             clients.push(TestClient::new(account.clone())?);
         }
-        Ok(Self { account, clients })
+        Ok(Self { clients })
+    }
+
+    fn new_mock(client_count: usize) -> Result<Self> {
+        log::info!("Creating mock account with {} clients", client_count);
+
+        let account = MockAccount::new_random()?;
+        let clients = (0..client_count)
+            .map(|_| TestClient::new_mock(account.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { clients })
     }
 
     pub fn new(opts: &Opts, client_count: usize) -> Result<TestUser> {
+        if opts.mock_server {
+            return TestUser::new_mock(client_count);
+        }
         if opts.oauth_retries > 0 && opts.no_delete_account {
             anyhow::bail!(
                 "Illegal option combination: oauth-retries is nonzero \
@@ -0,0 +1,188 @@
+/* Any copyright is dedicated to the Public Domain.
+http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use crate::auth::TestClient;
+use crate::history::with_places_conn;
+use crate::testing::TestGroup;
+use anyhow::Result;
+use places::storage::bookmarks::{
+    delete_bookmark, insert_bookmark, public_node::fetch_public_tree, update_bookmark,
+    BookmarkPosition, BookmarkRootGuid, InsertableBookmark, InsertableFolder, PublicNode,
+    UpdatableBookmark, UpdateTreeLocation,
+};
+use sync_guid::Guid;
+use url::Url;
+// helpers...
+
+pub fn insert_folder(client: &TestClient, guid: &str, parent: &Guid, title: &str) {
+    with_places_conn(client, |conn| {
+        insert_bookmark(
+            conn,
+            &InsertableFolder {
+                parent_guid: parent.clone(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: Some(guid.into()),
+                title: Some(title.to_owned()),
+            }
+            .into(),
+        )
+    })
+    .expect("Should insert folder");
+}
+
+pub fn insert_url(client: &TestClient, guid: &str, parent: &Guid, url: &str, title: &str) {
+    with_places_conn(client, |conn| {
+        insert_bookmark(
+            conn,
+            &InsertableBookmark {
+                parent_guid: parent.clone(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: Some(guid.into()),
+                url: Url::parse(url).expect("Should be a valid URL"),
+                title: Some(title.to_owned()),
+            }
+            .into(),
+        )
+    })
+    .expect("Should insert bookmark");
+}
+
+pub fn fetch_tree(client: &TestClient, guid: &Guid) -> Option<PublicNode> {
+    with_places_conn(client, |conn| fetch_public_tree(conn, guid)).expect("Should fetch tree")
+}
+
+/// Returns the GUIDs of the children of the folder `guid`, in order.
+pub fn child_guids(client: &TestClient, guid: &Guid) -> Vec<Guid> {
+    fetch_tree(client, guid)
+        .expect("Folder should exist")
+        .child_nodes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|node| node.guid)
+        .collect()
+}
+
+pub fn sync_bookmarks(client: &mut TestClient) -> Result<()> {
+    let (init, key, _device_id) = client.data_for_sync()?;
+    client.places_api.sync_bookmarks(&init, &key)?;
+    Ok(())
+}
+
+// Actual tests.
+
+fn test_bookmarks_general(c0: &mut TestClient, c1: &mut TestClient) {
+    log::info!("Add some bookmarks to client0");
+
+    let menu = BookmarkRootGuid::Menu.as_guid();
+    insert_folder(c0, "folderAAAAAA", &menu, "Folder");
+    insert_url(
+        c0,
+        "bookmarkAAAA",
+        &"folderAAAAAA".into(),
+        "https://example.com/",
+        "Example",
+    );
+    insert_url(c0, "bookmarkBBBB", &menu, "https://example.org/", "Other");
+
+    log::info!("Syncing client0");
+    sync_bookmarks(c0).expect("c0 sync to work");
+
+    log::info!("Syncing client1");
+    sync_bookmarks(c1).expect("c1 sync to work");
+
+    log::info!("Check state");
+    assert_eq!(
+        fetch_tree(c1, &menu),
+        fetch_tree(c0, &menu),
+        "Menus should match after first sync"
+    );
+    assert_eq!(
+        child_guids(c1, &menu),
+        vec![Guid::from("folderAAAAAA"), Guid::from("bookmarkBBBB")]
+    );
+
+    log::info!("Update and move bookmarks on client1");
+    with_places_conn(c1, |conn| {
+        update_bookmark(
+            conn,
+            &"bookmarkAAAA".into(),
+            &UpdatableBookmark {
+                location: UpdateTreeLocation::None,
+                url: None,
+                title: Some("Renamed".to_owned()),
+            }
+            .into(),
+        )?;
+        update_bookmark(
+            conn,
+            &"bookmarkBBBB".into(),
+            &UpdatableBookmark {
+                location: UpdateTreeLocation::Parent(
+                    BookmarkRootGuid::Toolbar.as_guid(),
+                    BookmarkPosition::Append,
+                ),
+                url: None,
+                title: None,
+            }
+            .into(),
+        )
+    })
+    .expect("Should update bookmarks");
+
+    sync_bookmarks(c1).expect("c1 sync to work");
+    sync_bookmarks(c0).expect("c0 sync to work");
+
+    let renamed = fetch_tree(c0, &"bookmarkAAAA".into()).expect("Bookmark should exist");
+    assert_eq!(renamed.title.as_deref(), Some("Renamed"));
+    assert_eq!(
+        child_guids(c0, &menu),
+        vec![Guid::from("folderAAAAAA")],
+        "c0 should move the bookmark out of the menu"
+    );
+    assert_eq!(
+        child_guids(c0, &BookmarkRootGuid::Toolbar.as_guid()),
+        vec![Guid::from("bookmarkBBBB")],
+        "c0 should move the bookmark to the toolbar"
+    );
+}
+
+fn test_bookmarks_deletes(c0: &mut TestClient, c1: &mut TestClient) {
+    log::info!("Add bookmarks to client0");
+
+    let unfiled = BookmarkRootGuid::Unfiled.as_guid();
+    insert_url(c0, "bookmarkAAAA", &unfiled, "https://example.com/", "A");
+    insert_url(c0, "bookmarkBBBB", &unfiled, "https://example.org/", "B");
+
+    sync_bookmarks(c0).expect("c0 sync to work");
+    sync_bookmarks(c1).expect("c1 sync to work");
+
+    assert_eq!(child_guids(c1, &unfiled).len(), 2);
+
+    log::info!("Delete bookmarkAAAA on client1");
+    with_places_conn(c1, |conn| delete_bookmark(conn, &"bookmarkAAAA".into()))
+        .expect("Should delete bookmark");
+
+    sync_bookmarks(c1).expect("c1 sync to work");
+    sync_bookmarks(c0).expect("c0 sync to work");
+
+    log::info!("Check state");
+    assert!(
+        fetch_tree(c0, &"bookmarkAAAA".into()).is_none(),
+        "Deletion should sync to c0"
+    );
+    assert_eq!(child_guids(c0, &unfiled), vec![Guid::from("bookmarkBBBB")]);
+}
+
+pub fn get_test_group() -> TestGroup {
+    TestGroup::new(
+        "bookmarks",
+        vec![
+            ("test_bookmarks_general", test_bookmarks_general),
+            ("test_bookmarks_deletes", test_bookmarks_deletes),
+        ],
+    )
+}
@@ -0,0 +1,159 @@
+/* Any copyright is dedicated to the Public Domain.
+http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use crate::auth::TestClient;
+use crate::testing::TestGroup;
+use anyhow::Result;
+use places::storage::history::{delete_visits_for, get_visit_infos, url_to_guid};
+use places::{
+    ConnectionType, PlacesDb, Result as PlacesResult, Timestamp, VisitObservation, VisitTransition,
+    VisitTransitionSet,
+};
+use url::Url;
+
+/// Runs `f` with the client's places write connection.
+pub fn with_places_conn<T, F>(client: &TestClient, f: F) -> PlacesResult<T>
+where
+    F: FnOnce(&mut PlacesDb) -> PlacesResult<T>,
+{
+    let mut conn = client
+        .places_api
+        .open_connection(ConnectionType::ReadWrite)?;
+    let result = f(&mut conn);
+    client.places_api.close_connection(conn)?;
+    result
+}
+
+pub fn add_visit(client: &TestClient, url: &str, title: &str, at: Timestamp) {
+    let url = Url::parse(url).expect("Should be a valid URL");
+    with_places_conn(client, |conn| {
+        places::apply_observation(
+            conn,
+            VisitObservation::new(url)
+                .with_title(title.to_owned())
+                .with_visit_type(VisitTransition::Link)
+                .with_at(at),
+        )
+    })
+    .expect("Should add visit");
+}
+
+/// Returns the titles and times of all visits to `url`, oldest first.
+pub fn visits_for_url(client: &TestClient, url: &str) -> Vec<(Option<String>, i64)> {
+    let url = Url::parse(url).expect("Should be a valid URL");
+    let infos = with_places_conn(client, |conn| {
+        get_visit_infos(
+            conn,
+            Timestamp(0),
+            Timestamp::now(),
+            VisitTransitionSet::empty(),
+        )
+    })
+    .expect("Should fetch visits")
+    .infos;
+    infos
+        .into_iter()
+        .filter(|info| info.url == url.as_str())
+        .map(|info| (info.title, info.timestamp))
+        .collect()
+}
+
+pub fn sync_history(client: &mut TestClient) -> Result<()> {
+    let (init, key, _device_id) = client.data_for_sync()?;
+    client.places_api.sync_history(&init, &key)?;
+    Ok(())
+}
+
+// Visits in the last few minutes, so that they're well within the window
+// that we sync.
+fn minutes_ago(minutes: u64) -> Timestamp {
+    Timestamp(Timestamp::now().0 - minutes * 60 * 1000)
+}
+
+// Actual tests.
+
+fn test_history_general(c0: &mut TestClient, c1: &mut TestClient) {
+    log::info!("Add some visits to client0");
+
+    let first_visit = minutes_ago(10);
+    let second_visit = minutes_ago(5);
+    add_visit(c0, "https://example.com/", "Example", first_visit);
+    add_visit(c0, "https://example.org/page", "Page", second_visit);
+
+    log::info!("Syncing client0");
+    sync_history(c0).expect("c0 sync to work");
+
+    log::info!("Syncing client1");
+    sync_history(c1).expect("c1 sync to work");
+
+    log::info!("Check state");
+    assert_eq!(
+        visits_for_url(c1, "https://example.com/"),
+        vec![(Some("Example".to_owned()), first_visit.0 as i64)]
+    );
+    assert_eq!(
+        visits_for_url(c1, "https://example.org/page"),
+        vec![(Some("Page".to_owned()), second_visit.0 as i64)]
+    );
+
+    log::info!("Visit https://example.com/ again on client1");
+    let third_visit = minutes_ago(1);
+    add_visit(c1, "https://example.com/", "Example", third_visit);
+
+    sync_history(c1).expect("c1 sync to work");
+    sync_history(c0).expect("c0 sync to work");
+
+    assert_eq!(
+        visits_for_url(c0, "https://example.com/"),
+        vec![
+            (Some("Example".to_owned()), first_visit.0 as i64),
+            (Some("Example".to_owned()), third_visit.0 as i64),
+        ],
+        "c0 should have both visits"
+    );
+}
+
+fn test_history_deletes(c0: &mut TestClient, c1: &mut TestClient) {
+    log::info!("Add visits to client0");
+
+    add_visit(c0, "https://example.com/", "Example", minutes_ago(10));
+    add_visit(c0, "https://example.net/", "Kept", minutes_ago(5));
+
+    sync_history(c0).expect("c0 sync to work");
+    sync_history(c1).expect("c1 sync to work");
+
+    assert_eq!(visits_for_url(c1, "https://example.com/").len(), 1);
+
+    log::info!("Delete https://example.com/ on client1");
+    with_places_conn(c1, |conn| {
+        let guid = url_to_guid(conn, &Url::parse("https://example.com/").unwrap())?
+            .expect("Should have a GUID for the page");
+        delete_visits_for(conn, &guid)
+    })
+    .expect("Should delete visits");
+    assert!(visits_for_url(c1, "https://example.com/").is_empty());
+
+    sync_history(c1).expect("c1 sync to work");
+    sync_history(c0).expect("c0 sync to work");
+
+    log::info!("Check state");
+    assert!(
+        visits_for_url(c0, "https://example.com/").is_empty(),
+        "Deletion should sync to c0"
+    );
+    assert_eq!(
+        visits_for_url(c0, "https://example.net/").len(),
+        1,
+        "Other pages should be untouched"
+    );
+}
+
+pub fn get_test_group() -> TestGroup {
+    TestGroup::new(
+        "history",
+        vec![
+            ("test_history_general", test_history_general),
+            ("test_history_deletes", test_history_deletes),
+        ],
+    )
+}
@@ -8,8 +8,11 @@ use std::{collections::HashSet, process};
 use structopt::StructOpt;
 
 mod auth;
+mod bookmarks;
+mod history;
 mod logins;
 mod restmail;
+mod server;
 mod sync15;
 mod tabs;
 mod testing;
@@ -24,8 +27,12 @@ macro_rules! cleanup_clients {
     };
 }
 
-pub fn init_testing() {
-    viaduct_reqwest::use_reqwest_backend();
+pub fn init_testing(opts: &Opts) {
+    if opts.mock_server {
+        server::use_mock_backend();
+    } else {
+        viaduct_reqwest::use_reqwest_backend();
+    }
     // Enable backtraces.
     std::env::set_var("RUST_BACKTRACE", "1");
     // Turn on trace logging for everything except for a few crates (mostly from
@@ -121,21 +128,30 @@ pub struct Opts {
     /// Run the helper browser as non-headless, and enable extra logging
     pub helper_debug: bool,
 
+    #[structopt(name = "mock-server", long)]
+    /// Run against an in-process mock tokenserver and storage server, instead
+    /// of a real FxA stack. This doesn't need network access, and ignores the
+    /// FxA and OAuth options.
+    pub mock_server: bool,
+
     pub groups: Vec<String>,
 }
 
 pub fn main() {
     let opts = Opts::from_args();
     println!("### Running sync integration tests ###");
-    init_testing();
-    run_test_groups(
-        &opts,
-        vec![
-            crate::logins::get_test_group(),
-            crate::tabs::get_test_group(),
-            crate::sync15::get_test_group(),
-        ],
-    );
+    init_testing(&opts);
+    let mut groups = vec![
+        crate::logins::get_test_group(),
+        crate::tabs::get_test_group(),
+        crate::sync15::get_test_group(),
+        crate::history::get_test_group(),
+        crate::bookmarks::get_test_group(),
+    ];
+    if opts.mock_server {
+        groups.push(crate::server::get_test_group());
+    }
+    run_test_groups(&opts, groups);
 
     println!("\n### Sync integration tests passed!");
 }
@@ -0,0 +1,227 @@
+/* Any copyright is dedicated to the Public Domain.
+http://creativecommons.org/publicdomain/zero/1.0/ */
+
+//! An in-process stand-in for the tokenserver and the Sync 1.5 storage
+//! server, so that the tests can run without network access or a live FxA
+//! stack. It's installed as viaduct's backend, and answers requests for
+//! `TOKENSERVER_URL`, and for the storage endpoints that the tokenserver hands
+//! out. Everything is kept in memory, and shared by all clients.
+//!
+//! The mock implements enough of the real servers for our sync engines,
+//! including batch uploads, `X-If-Unmodified-Since` preconditions, and
+//! backoff headers. Tests can change its behavior with the methods on
+//! `MockServer`.
+
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sync15_traits::ServerTimestamp;
+use viaduct::{header_names, Backend, Headers, Request, Response};
+
+mod scenarios;
+mod storage;
+mod tokenserver;
+
+pub use scenarios::get_test_group;
+pub use storage::Configuration;
+
+pub const TOKENSERVER_URL: &str = "https://token.sync.mock/";
+const TOKENSERVER_HOST: &str = "token.sync.mock";
+const STORAGE_URL: &str = "https://storage.sync.mock/1.5/";
+const STORAGE_HOST: &str = "storage.sync.mock";
+
+lazy_static::lazy_static! {
+    static ref SERVER: MockServer = MockServer::default();
+}
+
+/// Sends all requests to the mock server. Like `use_reqwest_backend`, this
+/// must be called before making any requests.
+pub fn use_mock_backend() {
+    viaduct::set_backend(&*SERVER).expect("Backend already set");
+}
+
+/// Returns the mock server, so that tests can change how it behaves.
+pub fn mock_server() -> &'static MockServer {
+    &SERVER
+}
+
+#[derive(Default)]
+pub struct MockServer {
+    state: Mutex<ServerState>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    clock: Clock,
+    tokenserver: tokenserver::Tokenserver,
+    storage: storage::Storage,
+    backoff: Option<u32>,
+    retry_after: Option<u32>,
+}
+
+impl MockServer {
+    /// Sends an `X-Weave-Backoff` header with every storage response, asking
+    /// clients to wait `secs` before syncing again, until it's cleared.
+    pub fn set_backoff(&self, secs: Option<u32>) {
+        self.state.lock().unwrap().backoff = secs;
+    }
+
+    /// Makes both servers fail every request with a "503 Service Unavailable"
+    /// and a `Retry-After` header, until it's cleared.
+    pub fn set_retry_after(&self, secs: Option<u32>) {
+        self.state.lock().unwrap().retry_after = secs;
+    }
+
+    /// Changes the limits that the storage server advertises in
+    /// `info/configuration`, and enforces for uploads.
+    pub fn set_configuration(&self, config: Configuration) {
+        self.state.lock().unwrap().storage.config = config;
+    }
+
+    /// Changes the modified time of `collection`, as if another client wrote
+    /// to it, without changing its records.
+    pub fn touch_collection(&self, collection: &str) {
+        let mut state = self.state.lock().unwrap();
+        let now = state.clock.tick();
+        state.storage.touch_collection(collection, now);
+    }
+
+    fn handle(&self, request: &Request) -> MockResponse {
+        let mut state = self.state.lock().unwrap();
+        let now = state.clock.tick();
+        let mut response = if let Some(secs) = state.retry_after {
+            MockResponse::json(503, &serde_json::json!({ "status": "unavailable" }))
+                .header(header_names::RETRY_AFTER, secs.to_string())
+        } else {
+            match request.url.host_str() {
+                Some(TOKENSERVER_HOST) => state.tokenserver.handle(request, now),
+                Some(STORAGE_HOST) => {
+                    let mut response = state.handle_storage(request, now);
+                    if let Some(secs) = state.backoff {
+                        response = response.header(header_names::X_WEAVE_BACKOFF, secs.to_string());
+                    }
+                    response
+                }
+                _ => MockResponse::json(404, &serde_json::json!({ "status": "not-found" })),
+            }
+        };
+        response = response.header(header_names::X_WEAVE_TIMESTAMP, now.to_string());
+        log::trace!(
+            "Mock server: {} {} => {}",
+            request.method,
+            request.url,
+            response.status
+        );
+        response
+    }
+}
+
+impl ServerState {
+    /// Checks that a storage request is for the user that its Hawk token was
+    /// issued to, and hands it off to the storage server.
+    fn handle_storage(&mut self, request: &Request, now: ServerTimestamp) -> MockResponse {
+        let mut segments = request
+            .url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let uid = match segments.as_slice() {
+            ["1.5", uid, ..] => uid.parse::<u64>().ok(),
+            _ => None,
+        };
+        let uid = match uid {
+            Some(uid) => uid,
+            None => return MockResponse::json(404, &serde_json::json!({ "status": "not-found" })),
+        };
+        let token_uid = hawk_id(request).and_then(|id| self.tokenserver.uid_for_token(id));
+        if token_uid != Some(uid) {
+            return MockResponse::json(
+                401,
+                &serde_json::json!({ "status": "invalid-credentials" }),
+            );
+        }
+        segments.drain(..2);
+        self.storage.handle(uid, request, &segments, now)
+    }
+}
+
+impl Backend for MockServer {
+    fn send(&self, request: Request) -> Result<Response, viaduct::Error> {
+        viaduct::note_backend("sync-test mock server");
+        let response = self.handle(&request);
+        Ok(Response {
+            request_method: request.method,
+            url: request.url,
+            status: response.status,
+            headers: response.headers,
+            body: response.body,
+        })
+    }
+}
+
+/// Extracts the token id from a Hawk `Authorization` header, which looks like
+/// `Hawk id="...", ts="...", nonce="...", mac="..."`. We don't check the MAC.
+fn hawk_id(request: &Request) -> Option<&str> {
+    let header = request.headers.get(header_names::AUTHORIZATION)?;
+    if !header.starts_with("Hawk ") {
+        return None;
+    }
+    header["Hawk ".len()..].split(',').find_map(|param| {
+        let param = param.trim();
+        if param.len() > 5 && param.starts_with("id=\"") && param.ends_with('"') {
+            Some(&param[4..param.len() - 1])
+        } else {
+            None
+        }
+    })
+}
+
+/// A response from one of the mock servers, before we know which request it
+/// belongs to.
+pub struct MockResponse {
+    status: u16,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    fn json<T: serde::Serialize + ?Sized>(status: u16, body: &T) -> Self {
+        let mut headers = Headers::new();
+        headers
+            .insert(header_names::CONTENT_TYPE, "application/json")
+            .unwrap();
+        MockResponse {
+            status,
+            headers,
+            body: serde_json::to_vec(body).expect("Response bodies should serialize"),
+        }
+    }
+
+    fn header(mut self, name: viaduct::HeaderName, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value.into()).unwrap();
+        self
+    }
+
+    fn last_modified(self, modified: ServerTimestamp) -> Self {
+        self.header(header_names::X_LAST_MODIFIED, modified.to_string())
+    }
+}
+
+/// Hands out server timestamps. Like the real server, these have a resolution
+/// of 10 milliseconds, and we make sure they always increase, so that every
+/// write gets a new modified time.
+#[derive(Default)]
+struct Clock {
+    last: ServerTimestamp,
+}
+
+impl Clock {
+    fn tick(&mut self) -> ServerTimestamp {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Current time should be after the epoch")
+            .as_millis() as i64;
+        let now = ServerTimestamp((now / 10 * 10).max(self.last.0 + 10));
+        self.last = now;
+        now
+    }
+}
@@ -0,0 +1,259 @@
+/* Any copyright is dedicated to the Public Domain.
+http://creativecommons.org/publicdomain/zero/1.0/ */
+
+//! Tests for how our clients handle server behavior that's hard to trigger on
+//! a real server, like backoff and small upload limits. These only run with
+//! `--mock-server`.
+
+use super::{mock_server, Configuration};
+use crate::auth::TestClient;
+use crate::sync15::{TestRecord, TestStore};
+use crate::testing::TestGroup;
+use interrupt_support::NeverInterrupts;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, SystemTime};
+use sync15::{telemetry, MemoryCachedState, SyncResult};
+use sync15_traits::{
+    CollectionRequest, IncomingChangeset, OutgoingChangeset, RequestOrder, ServerTimestamp, Store,
+    StoreSyncAssociation,
+};
+use sync_guid::Guid;
+
+fn new_store(name: &'static str, records: Vec<TestRecord>) -> TestStore {
+    TestStore {
+        name,
+        test_records: RefCell::new(records),
+        store_sync_assoc: RefCell::new(StoreSyncAssociation::Disconnected),
+        was_reset_called: Cell::new(false),
+
+        global_id: Some(Guid::random()),
+        coll_id: Some(Guid::random()),
+    }
+}
+
+fn sync_store(client: &mut TestClient, store: &dyn Store) -> SyncResult {
+    let (init, key, _device_id) = client
+        .data_for_sync()
+        .expect("Should have data for syncing");
+    let result = sync15::sync_multiple(
+        &[store],
+        &mut None,
+        &mut MemoryCachedState::default(),
+        &init,
+        &key,
+        &NeverInterrupts,
+        None,
+    );
+    log::info!("Finished syncing: {:?}", result.result);
+    result
+}
+
+fn assert_sync_after(result: &SyncResult, secs: u64) {
+    let next_sync_after = result
+        .next_sync_after
+        .expect("Should ask us to wait before syncing again");
+    // Allow some slack for how long the sync took.
+    let earliest = SystemTime::now() + Duration::from_secs(secs - 5);
+    assert!(
+        next_sync_after >= earliest,
+        "Should wait at least {} seconds, but next sync is at {:?}",
+        secs,
+        next_sync_after
+    );
+}
+
+fn test_backoff(c0: &mut TestClient, _c1: &mut TestClient) {
+    log::info!("Syncing with X-Weave-Backoff");
+    mock_server().set_backoff(Some(600));
+    let store = new_store("c0", Vec::new());
+    let result = sync_store(c0, &store);
+    mock_server().set_backoff(None);
+
+    assert!(result.result.is_ok(), "Backoff shouldn't fail the sync");
+    assert_sync_after(&result, 600);
+
+    log::info!("Syncing with Retry-After");
+    mock_server().set_retry_after(Some(300));
+    let result = sync_store(c0, &store);
+    mock_server().set_retry_after(None);
+
+    assert!(result.result.is_err(), "503s should fail the sync");
+    assert_sync_after(&result, 300);
+
+    log::info!("Syncing after the server recovers");
+    let result = sync_store(c0, &store);
+    assert!(result.result.is_ok(), "Sync should work again");
+    assert!(result.next_sync_after.is_none());
+}
+
+fn test_batching(c0: &mut TestClient, c1: &mut TestClient) {
+    // Small enough that uploading all our records takes a few POSTs, but big
+    // enough to fit them in one batch.
+    mock_server().set_configuration(Configuration {
+        max_post_records: 10,
+        max_total_records: 50,
+        ..Configuration::default()
+    });
+
+    let records = (0..25)
+        .map(|i| TestRecord {
+            id: Guid::random(),
+            message: format!("Record {}", i),
+        })
+        .collect::<Vec<_>>();
+    log::info!("Uploading {} records from c0", records.len());
+    let first_store = new_store("c0", records.clone());
+    let result = sync_store(c0, &first_store);
+    assert!(result.result.is_ok(), "c0 sync to work");
+
+    let second_store = TestStore {
+        store_sync_assoc: first_store.store_sync_assoc,
+        ..new_store("c1", Vec::new())
+    };
+    let result = sync_store(c1, &second_store);
+    assert!(result.result.is_ok(), "c1 sync to work");
+
+    mock_server().set_configuration(Configuration::default());
+
+    let mut expected = records;
+    let mut downloaded = second_store.test_records.into_inner();
+    expected.sort_by(|a, b| a.id.cmp(&b.id));
+    downloaded.sort_by(|a, b| a.id.cmp(&b.id));
+    assert_eq!(expected, downloaded, "c1 should download every record");
+}
+
+/// A store that downloads its records a page at a time, and can change the
+/// collection on the server after the first page.
+struct PagedStore {
+    inner: TestStore,
+    page_size: usize,
+    touch_after_first_page: bool,
+    pages: Cell<usize>,
+}
+
+impl Store for PagedStore {
+    fn collection_name(&self) -> std::borrow::Cow<'static, str> {
+        self.inner.collection_name()
+    }
+
+    fn apply_incoming(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<OutgoingChangeset> {
+        self.inner.apply_incoming(inbound, telem)
+    }
+
+    fn stage_incoming(
+        &self,
+        _page: &mut IncomingChangeset,
+        _high_water_mark: ServerTimestamp,
+        _telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<bool> {
+        self.pages.set(self.pages.get() + 1);
+        if self.pages.get() == 1 && self.touch_after_first_page {
+            mock_server().touch_collection(&self.collection_name());
+        }
+        // Keep the page, so that `apply_incoming` gets all the records.
+        Ok(false)
+    }
+
+    fn sync_finished(
+        &self,
+        new_timestamp: ServerTimestamp,
+        records_synced: Vec<Guid>,
+    ) -> anyhow::Result<()> {
+        self.inner.sync_finished(new_timestamp, records_synced)
+    }
+
+    fn get_collection_requests(
+        &self,
+        _server_timestamp: ServerTimestamp,
+    ) -> anyhow::Result<Vec<CollectionRequest>> {
+        Ok(vec![CollectionRequest::new(self.collection_name())
+            .full()
+            .sort_by(RequestOrder::Oldest)
+            .paged(self.page_size)])
+    }
+
+    fn get_sync_assoc(&self) -> anyhow::Result<StoreSyncAssociation> {
+        self.inner.get_sync_assoc()
+    }
+
+    fn reset(&self, assoc: &StoreSyncAssociation) -> anyhow::Result<()> {
+        self.inner.reset(assoc)
+    }
+
+    fn wipe(&self) -> anyhow::Result<()> {
+        self.inner.wipe()
+    }
+}
+
+fn test_paging(c0: &mut TestClient, c1: &mut TestClient) {
+    let records = (0..25)
+        .map(|i| TestRecord {
+            id: Guid::random(),
+            message: format!("Record {}", i),
+        })
+        .collect::<Vec<_>>();
+    log::info!("Uploading {} records from c0", records.len());
+    let first_store = new_store("c0", records.clone());
+    let result = sync_store(c0, &first_store);
+    assert!(result.result.is_ok(), "c0 sync to work");
+
+    log::info!("Downloading the records on c1 in pages");
+    let paged_store = PagedStore {
+        inner: TestStore {
+            store_sync_assoc: RefCell::new(first_store.store_sync_assoc.borrow().clone()),
+            ..new_store("c1", Vec::new())
+        },
+        page_size: 10,
+        touch_after_first_page: false,
+        pages: Cell::new(0),
+    };
+    let result = sync_store(c1, &paged_store);
+    assert!(result.result.is_ok(), "c1 sync to work");
+    assert_eq!(paged_store.pages.get(), 3);
+
+    let mut expected = records;
+    let mut downloaded = paged_store.inner.test_records.into_inner();
+    expected.sort_by(|a, b| a.id.cmp(&b.id));
+    downloaded.sort_by(|a, b| a.id.cmp(&b.id));
+    assert_eq!(expected, downloaded, "c1 should download every record");
+
+    log::info!("Changing the collection while c1 downloads it");
+    let changed_store = PagedStore {
+        inner: TestStore {
+            store_sync_assoc: RefCell::new(first_store.store_sync_assoc.borrow().clone()),
+            ..new_store("c1", Vec::new())
+        },
+        page_size: 10,
+        touch_after_first_page: true,
+        pages: Cell::new(0),
+    };
+    let result = sync_store(c1, &changed_store);
+    let engine_result = result
+        .engine_results
+        .get("addresses")
+        .expect("Should sync the store");
+    assert!(
+        engine_result.is_err(),
+        "Downloading a changed collection should fail"
+    );
+    assert_eq!(changed_store.pages.get(), 1, "Should stop after the change");
+    assert!(
+        changed_store.inner.test_records.borrow().is_empty(),
+        "Shouldn't apply a partial download"
+    );
+}
+
+pub fn get_test_group() -> TestGroup {
+    TestGroup::new(
+        "server",
+        vec![
+            ("test_backoff", test_backoff),
+            ("test_batching", test_batching),
+            ("test_paging", test_paging),
+        ],
+    )
+}
@@ -0,0 +1,546 @@
+/* Any copyright is dedicated to the Public Domain.
+http://creativecommons.org/publicdomain/zero/1.0/ */
+
+//! A mock Sync 1.5 storage server, which keeps each user's records in memory.
+//! This implements the parts of the API that our clients use, as described in
+//! https://mozilla-services.readthedocs.io/en/latest/storage/apis-1.5.html.
+
+use super::MockResponse;
+use serde_derive::*;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use sync15_traits::ServerTimestamp;
+use viaduct::{header_names, Method, Request};
+
+// The error code the real server sends for requests over its limits.
+const SIZE_LIMIT_EXCEEDED: u32 = 17;
+
+/// The limits that the storage server advertises in `info/configuration`.
+/// The defaults match the real server.
+#[derive(Clone, Debug, Serialize)]
+pub struct Configuration {
+    pub max_request_bytes: usize,
+    pub max_post_records: usize,
+    pub max_post_bytes: usize,
+    pub max_total_records: usize,
+    pub max_total_bytes: usize,
+    pub max_record_payload_bytes: usize,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            max_request_bytes: 2_101_248,
+            max_post_records: 100,
+            max_post_bytes: 2_097_152,
+            max_total_records: 10_000,
+            max_total_bytes: 209_715_200,
+            max_record_payload_bytes: 2_097_152,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Bso {
+    id: String,
+    modified: ServerTimestamp,
+    payload: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sortindex: Option<i32>,
+    #[serde(skip)]
+    expires: Option<ServerTimestamp>,
+}
+
+impl Bso {
+    fn is_expired(&self, now: ServerTimestamp) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+}
+
+/// A record uploaded by a client. Uploads can leave out the payload to only
+/// change the sort index or TTL of an existing record.
+#[derive(Clone, Debug, Deserialize)]
+struct BsoUpload {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    payload: Option<String>,
+    #[serde(default)]
+    sortindex: Option<i32>,
+    #[serde(default)]
+    ttl: Option<u32>,
+}
+
+#[derive(Default)]
+struct Collection {
+    modified: ServerTimestamp,
+    records: BTreeMap<String, Bso>,
+}
+
+impl Collection {
+    /// Writes `uploads`, which have already been validated, with the same
+    /// modified time.
+    fn apply(&mut self, uploads: Vec<BsoUpload>, now: ServerTimestamp) {
+        for upload in uploads {
+            let id = upload.id.expect("Uploads should be validated");
+            let bso = self.records.entry(id.clone()).or_insert_with(|| Bso {
+                id,
+                modified: now,
+                payload: String::new(),
+                sortindex: None,
+                expires: None,
+            });
+            bso.modified = now;
+            if let Some(payload) = upload.payload {
+                bso.payload = payload;
+            }
+            if upload.sortindex.is_some() {
+                bso.sortindex = upload.sortindex;
+            }
+            if let Some(ttl) = upload.ttl {
+                bso.expires = Some(ServerTimestamp(now.0 + i64::from(ttl) * 1000));
+            }
+        }
+        self.modified = now;
+    }
+}
+
+/// An uncommitted batch upload.
+struct Batch {
+    collection: String,
+    uploads: Vec<BsoUpload>,
+}
+
+#[derive(Default)]
+struct User {
+    modified: ServerTimestamp,
+    collections: BTreeMap<String, Collection>,
+    batches: HashMap<String, Batch>,
+    next_batch_id: u64,
+}
+
+#[derive(Default)]
+pub struct Storage {
+    pub config: Configuration,
+    users: HashMap<u64, User>,
+}
+
+impl Storage {
+    /// Handles a request for `uid`'s storage. `path` has the segments of the
+    /// request path after `/1.5/{uid}`.
+    pub fn handle(
+        &mut self,
+        uid: u64,
+        request: &Request,
+        path: &[&str],
+        now: ServerTimestamp,
+    ) -> MockResponse {
+        let config = &self.config;
+        let user = self.users.entry(uid).or_default();
+        match path {
+            ["info", "configuration"] => match request.method {
+                Method::Get => MockResponse::json(200, config).last_modified(user.modified),
+                method => method_not_allowed(method),
+            },
+            ["info", "collections"] => match request.method {
+                Method::Get => {
+                    let modified = user
+                        .collections
+                        .iter()
+                        .map(|(name, collection)| (name, collection.modified))
+                        .collect::<BTreeMap<_, _>>();
+                    MockResponse::json(200, &modified).last_modified(user.modified)
+                }
+                method => method_not_allowed(method),
+            },
+            ["info", "collection_counts"] => match request.method {
+                Method::Get => {
+                    let counts = user
+                        .collections
+                        .iter()
+                        .map(|(name, collection)| {
+                            let count = collection
+                                .records
+                                .values()
+                                .filter(|bso| !bso.is_expired(now))
+                                .count();
+                            (name, count)
+                        })
+                        .collect::<BTreeMap<_, _>>();
+                    MockResponse::json(200, &counts).last_modified(user.modified)
+                }
+                method => method_not_allowed(method),
+            },
+            [] | ["storage"] => match request.method {
+                Method::Delete => {
+                    if let Some(response) = check_unmodified_since(request, user.modified) {
+                        return response;
+                    }
+                    *user = User {
+                        modified: now,
+                        ..User::default()
+                    };
+                    MockResponse::json(200, &json!({})).last_modified(now)
+                }
+                method => method_not_allowed(method),
+            },
+            ["storage", collection] => match request.method {
+                Method::Get => user.get_collection(request, collection, now),
+                Method::Post => user.post_collection(request, config, collection, now),
+                Method::Delete => user.delete_collection(request, collection, now),
+                method => method_not_allowed(method),
+            },
+            ["storage", collection, id] => match request.method {
+                Method::Get => user.get_record(collection, id, now),
+                Method::Put => user.put_record(request, config, collection, id, now),
+                Method::Delete => user.delete_record(request, collection, id, now),
+                method => method_not_allowed(method),
+            },
+            _ => not_found(),
+        }
+    }
+
+    /// Bumps the modified time of `collection` for every user that has it, as
+    /// if another client wrote to it.
+    pub fn touch_collection(&mut self, collection: &str, now: ServerTimestamp) {
+        for user in self.users.values_mut() {
+            if let Some(c) = user.collections.get_mut(collection) {
+                c.modified = now;
+                user.modified = now;
+            }
+        }
+    }
+}
+
+impl User {
+    fn collection_modified(&self, collection: &str) -> ServerTimestamp {
+        self.collections
+            .get(collection)
+            .map(|c| c.modified)
+            .unwrap_or_default()
+    }
+
+    fn get_collection(
+        &self,
+        request: &Request,
+        collection: &str,
+        now: ServerTimestamp,
+    ) -> MockResponse {
+        let modified = self.collection_modified(collection);
+        if let Some(response) = check_unmodified_since(request, modified) {
+            return response;
+        }
+        let query = request
+            .url
+            .query_pairs()
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        let timestamp = |name: &str| query.get(name).and_then(|value| value.parse().ok());
+        let newer: Option<ServerTimestamp> = timestamp("newer");
+        let older: Option<ServerTimestamp> = timestamp("older");
+        let ids = query
+            .get("ids")
+            .map(|ids| ids.split(',').collect::<Vec<_>>());
+
+        let mut records = self
+            .collections
+            .get(collection)
+            .map(|c| c.records.values().collect::<Vec<_>>())
+            .unwrap_or_default();
+        records.retain(|bso| {
+            !bso.is_expired(now)
+                && ids
+                    .as_ref()
+                    .map_or(true, |ids| ids.contains(&bso.id.as_str()))
+                && newer.map_or(true, |newer| bso.modified > newer)
+                && older.map_or(true, |older| bso.modified < older)
+        });
+        match query.get("sort").map(String::as_str) {
+            Some("newest") => records.sort_by(|a, b| b.modified.0.cmp(&a.modified.0)),
+            Some("oldest") => records.sort_by(|a, b| a.modified.0.cmp(&b.modified.0)),
+            Some("index") => records.sort_by(|a, b| b.sortindex.cmp(&a.sortindex)),
+            _ => {}
+        }
+
+        // Our offsets are just positions in the results, which is fine as long
+        // as the collection doesn't change between pages. Clients can check
+        // that using `X-If-Unmodified-Since`.
+        let total = records.len();
+        let offset = query
+            .get("offset")
+            .and_then(|offset| offset.parse::<usize>().ok())
+            .unwrap_or(0)
+            .min(total);
+        let limit = query
+            .get("limit")
+            .and_then(|limit| limit.parse::<usize>().ok())
+            .filter(|&limit| limit > 0)
+            .unwrap_or(total);
+        let page = records
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect::<Vec<_>>();
+        let next_offset = offset + page.len();
+
+        let mut response = if query.contains_key("full") {
+            MockResponse::json(200, &page)
+        } else {
+            let ids = page.iter().map(|bso| &bso.id).collect::<Vec<_>>();
+            MockResponse::json(200, &ids)
+        };
+        response = response
+            .header(header_names::X_WEAVE_RECORDS, page.len().to_string())
+            .last_modified(modified);
+        if next_offset < total {
+            response = response.header(header_names::X_WEAVE_NEXT_OFFSET, next_offset.to_string());
+        }
+        response
+    }
+
+    fn post_collection(
+        &mut self,
+        request: &Request,
+        config: &Configuration,
+        collection: &str,
+        now: ServerTimestamp,
+    ) -> MockResponse {
+        let modified = self.collection_modified(collection);
+        if let Some(response) = check_unmodified_since(request, modified) {
+            return response;
+        }
+        let uploads: Vec<BsoUpload> = match parse_body(request) {
+            Ok(uploads) => uploads,
+            Err(response) => return response,
+        };
+        if uploads.len() > config.max_post_records {
+            return MockResponse::json(400, &SIZE_LIMIT_EXCEEDED);
+        }
+        let mut success = Vec::with_capacity(uploads.len());
+        let mut failed = BTreeMap::new();
+        let mut valid = Vec::with_capacity(uploads.len());
+        for upload in uploads {
+            match validate(&upload, config) {
+                Ok(()) => {
+                    success.push(upload.id.clone().unwrap());
+                    valid.push(upload);
+                }
+                Err(reason) => {
+                    failed.insert(upload.id.unwrap_or_default(), reason);
+                }
+            }
+        }
+
+        let query = request
+            .url
+            .query_pairs()
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        let commit = query.get("commit").map_or(false, |commit| commit == "true");
+        let batch_id = match query.get("batch") {
+            None if commit => return bad_request("Can't commit without a batch"),
+            None => {
+                self.apply(collection, valid, now);
+                return MockResponse::json(
+                    200,
+                    &json!({ "modified": now, "success": success, "failed": failed }),
+                )
+                .last_modified(now);
+            }
+            Some(batch) if batch == "true" => {
+                self.next_batch_id += 1;
+                let batch_id = self.next_batch_id.to_string();
+                self.batches.insert(
+                    batch_id.clone(),
+                    Batch {
+                        collection: collection.to_string(),
+                        uploads: Vec::new(),
+                    },
+                );
+                batch_id
+            }
+            Some(batch_id) => batch_id.clone(),
+        };
+        let batch = match self.batches.get_mut(&batch_id) {
+            Some(batch) if batch.collection == collection => batch,
+            _ => return bad_request("Invalid batch"),
+        };
+        batch.uploads.extend(valid);
+        if batch.uploads.len() > config.max_total_records {
+            self.batches.remove(&batch_id);
+            return MockResponse::json(400, &SIZE_LIMIT_EXCEEDED);
+        }
+        if commit {
+            let batch = self.batches.remove(&batch_id).unwrap();
+            self.apply(collection, batch.uploads, now);
+            MockResponse::json(
+                200,
+                &json!({ "modified": now, "success": success, "failed": failed }),
+            )
+            .last_modified(now)
+        } else {
+            MockResponse::json(
+                202,
+                &json!({ "batch": batch_id, "success": success, "failed": failed }),
+            )
+            .last_modified(modified)
+        }
+    }
+
+    fn delete_collection(
+        &mut self,
+        request: &Request,
+        collection: &str,
+        now: ServerTimestamp,
+    ) -> MockResponse {
+        if let Some(response) =
+            check_unmodified_since(request, self.collection_modified(collection))
+        {
+            return response;
+        }
+        let ids = request
+            .url
+            .query_pairs()
+            .find(|(name, _)| name == "ids")
+            .map(|(_, ids)| ids.split(',').map(ToString::to_string).collect::<Vec<_>>());
+        match ids {
+            Some(ids) => {
+                if let Some(c) = self.collections.get_mut(collection) {
+                    for id in ids {
+                        c.records.remove(&id);
+                    }
+                    c.modified = now;
+                }
+            }
+            None => {
+                self.collections.remove(collection);
+            }
+        }
+        self.modified = now;
+        MockResponse::json(200, &json!({ "modified": now })).last_modified(now)
+    }
+
+    fn get_record(&self, collection: &str, id: &str, now: ServerTimestamp) -> MockResponse {
+        match self
+            .collections
+            .get(collection)
+            .and_then(|c| c.records.get(id))
+            .filter(|bso| !bso.is_expired(now))
+        {
+            Some(bso) => MockResponse::json(200, bso).last_modified(bso.modified),
+            None => not_found(),
+        }
+    }
+
+    fn put_record(
+        &mut self,
+        request: &Request,
+        config: &Configuration,
+        collection: &str,
+        id: &str,
+        now: ServerTimestamp,
+    ) -> MockResponse {
+        // Preconditions for a single record are checked against the record,
+        // not the collection.
+        let record_modified = self
+            .collections
+            .get(collection)
+            .and_then(|c| c.records.get(id))
+            .map(|bso| bso.modified)
+            .unwrap_or_default();
+        if let Some(response) = check_unmodified_since(request, record_modified) {
+            return response;
+        }
+        let mut upload: BsoUpload = match parse_body(request) {
+            Ok(upload) => upload,
+            Err(response) => return response,
+        };
+        if upload.id.as_deref().map_or(false, |body_id| body_id != id) {
+            return bad_request("Record id doesn't match URL");
+        }
+        upload.id = Some(id.to_string());
+        if validate(&upload, config).is_err() {
+            return bad_request("Invalid record");
+        }
+        self.apply(collection, vec![upload], now);
+        MockResponse::json(200, &now).last_modified(now)
+    }
+
+    fn delete_record(
+        &mut self,
+        request: &Request,
+        collection: &str,
+        id: &str,
+        now: ServerTimestamp,
+    ) -> MockResponse {
+        let c = match self.collections.get_mut(collection) {
+            Some(c) if c.records.contains_key(id) => c,
+            _ => return not_found(),
+        };
+        if let Some(response) = check_unmodified_since(request, c.records[id].modified) {
+            return response;
+        }
+        c.records.remove(id);
+        c.modified = now;
+        self.modified = now;
+        MockResponse::json(200, &json!({ "modified": now })).last_modified(now)
+    }
+
+    fn apply(&mut self, collection: &str, uploads: Vec<BsoUpload>, now: ServerTimestamp) {
+        if uploads.is_empty() {
+            return;
+        }
+        self.collections
+            .entry(collection.to_string())
+            .or_default()
+            .apply(uploads, now);
+        self.modified = now;
+    }
+}
+
+/// Returns why the server would reject `upload`, if it would.
+fn validate(upload: &BsoUpload, config: &Configuration) -> Result<(), &'static str> {
+    let id = upload.id.as_deref().unwrap_or_default();
+    if id.is_empty() || id.len() > 64 || !id.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err("invalid id");
+    }
+    let payload_len = upload.payload.as_ref().map_or(0, String::len);
+    if payload_len > config.max_record_payload_bytes {
+        return Err("retry bytes");
+    }
+    Ok(())
+}
+
+/// Returns a "412 Precondition Failed" response if the resource was modified
+/// after the request's `X-If-Unmodified-Since` header.
+fn check_unmodified_since(request: &Request, modified: ServerTimestamp) -> Option<MockResponse> {
+    let since = request.headers.get(header_names::X_IF_UNMODIFIED_SINCE)?;
+    match since.parse::<ServerTimestamp>() {
+        Ok(since) if modified > since => Some(
+            MockResponse::json(412, &json!({ "status": "precondition-failed" }))
+                .last_modified(modified),
+        ),
+        Ok(_) => None,
+        Err(_) => Some(bad_request("Invalid X-If-Unmodified-Since")),
+    }
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(request: &Request) -> Result<T, MockResponse> {
+    request
+        .body
+        .as_ref()
+        .and_then(|body| serde_json::from_slice(body).ok())
+        .ok_or_else(|| bad_request("Invalid JSON body"))
+}
+
+fn bad_request(reason: &str) -> MockResponse {
+    MockResponse::json(400, &json!({ "status": reason }))
+}
+
+fn not_found() -> MockResponse {
+    MockResponse::json(404, &json!({ "status": "not-found" }))
+}
+
+fn method_not_allowed(method: Method) -> MockResponse {
+    MockResponse::json(405, &json!({ "status": format!("{} not allowed", method) }))
+}
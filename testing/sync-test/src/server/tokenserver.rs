@@ -0,0 +1,66 @@
+/* Any copyright is dedicated to the Public Domain.
+http://creativecommons.org/publicdomain/zero/1.0/ */
+
+//! A mock tokenserver, which exchanges OAuth access tokens for Hawk tokens
+//! for the mock storage server.
+
+use super::{MockResponse, STORAGE_URL};
+use serde_json::json;
+use std::collections::HashMap;
+use sync15_traits::ServerTimestamp;
+use viaduct::{header_names, Method, Request};
+
+/// How long our tokens are valid for, in seconds. We don't expire them, but
+/// clients use this to decide when to fetch a new one.
+const TOKEN_DURATION: u64 = 3600;
+
+#[derive(Default)]
+pub struct Tokenserver {
+    /// Maps FxA uids to storage uids.
+    users: HashMap<String, u64>,
+    /// Maps the ids of the tokens we've issued to storage uids.
+    tokens: HashMap<String, u64>,
+}
+
+impl Tokenserver {
+    pub fn handle(&mut self, request: &Request, now: ServerTimestamp) -> MockResponse {
+        if request.method != Method::Get || request.url.path() != "/1.0/sync/1.5" {
+            return MockResponse::json(404, &json!({ "status": "not-found" }));
+        }
+        // The real tokenserver verifies the access token with FxA, and gets the
+        // user's FxA uid. We don't have an FxA server to ask, so mock accounts
+        // use their uid as their access token.
+        let fxa_uid = match request.headers.get(header_names::AUTHORIZATION) {
+            Some(value) if value.starts_with("Bearer ") => &value["Bearer ".len()..],
+            _ => return MockResponse::json(401, &json!({ "status": "invalid-credentials" })),
+        };
+        if request.headers.get(header_names::X_KEYID).is_none() {
+            return MockResponse::json(401, &json!({ "status": "invalid-key-id" }));
+        }
+
+        let next_uid = self.users.len() as u64 + 1;
+        let uid = *self.users.entry(fxa_uid.to_string()).or_insert(next_uid);
+        let id = format!("token-{}-{}", uid, self.tokens.len());
+        self.tokens.insert(id.clone(), uid);
+        MockResponse::json(
+            200,
+            &json!({
+                "id": id,
+                "key": format!("key-{}", id),
+                "api_endpoint": format!("{}{}", STORAGE_URL, uid),
+                "uid": uid,
+                "duration": TOKEN_DURATION,
+                "hashed_fxa_uid": fxa_uid,
+            }),
+        )
+        .header(
+            header_names::X_TIMESTAMP,
+            (now.as_millis() / 1000).to_string(),
+        )
+    }
+
+    /// Returns the storage uid for a token that we issued.
+    pub fn uid_for_token(&self, id: &str) -> Option<u64> {
+        self.tokens.get(id).copied()
+    }
+}
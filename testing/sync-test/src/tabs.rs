@@ -10,6 +10,7 @@ use tabs::{ClientRemoteTabs, DeviceType, RemoteTab, TabsEngine};
 pub fn verify_tabs(tabs_engine: &TabsEngine, expected: &ClientRemoteTabs) {
     let remote_tabs = tabs_engine
         .remote_tabs()
+        .expect("should be able to read remote tabs")
         .expect("should have synced already");
     let equivalent = remote_tabs
         .iter()
@@ -58,7 +59,7 @@ fn test_tabs(c0: &mut TestClient, c1: &mut TestClient) {
     verify_tabs(
         &c1.tabs_engine,
         &ClientRemoteTabs {
-            client_id: c0.fxa.get_current_device_id().unwrap(),
+            client_id: c0.device_id().unwrap(),
             client_name: String::new(),
             device_type: DeviceType::Mobile,
             remote_tabs: vec![t0],
@@ -87,7 +88,7 @@ fn test_tabs(c0: &mut TestClient, c1: &mut TestClient) {
     verify_tabs(
         &c0.tabs_engine,
         &ClientRemoteTabs {
-            client_id: c1.fxa.get_current_device_id().unwrap(),
+            client_id: c1.device_id().unwrap(),
             client_name: String::new(),
             device_type: DeviceType::Mobile,
             remote_tabs: vec![t1, t2],